@@ -0,0 +1,53 @@
+//! Dataset loading and encoding
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use std::hint::black_box;
+use tiny_agent_trainer::dataset::encoded::EncodedDataset;
+use tiny_agent_trainer::dataset::WGSLDataset;
+use tiny_agent_trainer::WGSLTokenizer;
+
+const TRAINING_DATA: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/config/wgsl_training_data.toml"
+);
+
+fn bench_dataset(c: &mut Criterion) {
+    let dataset = WGSLDataset::from_toml(TRAINING_DATA).expect("bundled training data");
+    let dir = tempfile::tempdir().unwrap();
+    let jsonl = dir.path().join("train.jsonl");
+    dataset.to_jsonl(&jsonl).unwrap();
+    let mut tokenizer = WGSLTokenizer::new(8192, false);
+    let texts: Vec<&str> = dataset
+        .examples
+        .iter()
+        .flat_map(|example| {
+            [
+                example.natural_language.as_str(),
+                example.wgsl_code.as_str(),
+            ]
+        })
+        .collect();
+    tokenizer.fit(&texts, 1);
+
+    let mut group = c.benchmark_group("dataset");
+    group.throughput(Throughput::Elements(dataset.len() as u64));
+    group.bench_function("load_toml", |b| {
+        b.iter(|| WGSLDataset::from_toml(black_box(TRAINING_DATA)).unwrap())
+    });
+    group.bench_function("load_jsonl", |b| {
+        b.iter(|| WGSLDataset::from_jsonl(black_box(&jsonl)).unwrap())
+    });
+    group.bench_function("encode", |b| {
+        b.iter(|| EncodedDataset::encode(black_box(&dataset), &tokenizer))
+    });
+    group.bench_function("load_encoded_cache", |b| {
+        EncodedDataset::load_or_encode(&dataset, &tokenizer, dir.path()).unwrap();
+        b.iter(|| {
+            EncodedDataset::load_or_encode(black_box(&dataset), &tokenizer, dir.path()).unwrap()
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_dataset);
+criterion_main!(benches);
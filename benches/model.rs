@@ -0,0 +1,82 @@
+//! Attention-dominated encoder passes and end-to-end generation latency
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use std::hint::black_box;
+use tiny_agent_trainer::inference::{GenerationOptions, WGSLGenerator};
+use tiny_agent_trainer::model::{CodeGenerationModel, ModelArchitecture};
+use tiny_agent_trainer::{AttentionConfig, WGSLTokenizer};
+
+const VOCAB_SIZE: usize = 512;
+
+fn model(vocab_size: usize, max_seq_len: usize, attention: AttentionConfig) -> CodeGenerationModel {
+    CodeGenerationModel::new(
+        ModelArchitecture::Transformer,
+        vocab_size,
+        64,
+        4,
+        2,
+        Some(128),
+        Some(max_seq_len),
+    )
+    .with_attention(attention)
+}
+
+/// Synthetic ids past the special tokens
+fn token_ids(len: usize) -> Vec<usize> {
+    (0..len).map(|i| 4 + i % (VOCAB_SIZE - 4)).collect()
+}
+
+fn bench_attention(c: &mut Criterion) {
+    let mut group = c.benchmark_group("encoder_forward");
+    group.sample_size(20);
+    for seq_len in [64, 256, 1024] {
+        let ids = token_ids(seq_len);
+        group.throughput(Throughput::Elements(seq_len as u64));
+        for (name, block_size) in [("full", 0), ("blocked", 64)] {
+            let model = model(
+                VOCAB_SIZE,
+                seq_len,
+                AttentionConfig {
+                    block_size,
+                    window: 0,
+                },
+            );
+            group.bench_with_input(BenchmarkId::new(name, seq_len), &ids, |b, ids| {
+                b.iter(|| model.encode(black_box(ids)))
+            });
+        }
+    }
+    group.finish();
+}
+
+fn bench_generation(c: &mut Criterion) {
+    let mut tokenizer = WGSLTokenizer::new(VOCAB_SIZE, false);
+    tokenizer.fit(
+        &[
+            "@compute @workgroup_size(64) fn main(@builtin(global_invocation_id) id: vec3<u32>) { }",
+            "fn mix_colors(a: vec4<f32>, b: vec4<f32>) -> vec4<f32> { return mix(a, b, 0.5); }",
+        ],
+        1,
+    );
+    let model = model(tokenizer.vocab_size(), 128, AttentionConfig::default());
+    let generator = WGSLGenerator::new(model, tokenizer);
+
+    let mut group = c.benchmark_group("generation");
+    group.sample_size(10);
+    for max_new_tokens in [16, 64] {
+        let options = GenerationOptions {
+            max_new_tokens: Some(max_new_tokens),
+            min_new_tokens: max_new_tokens,
+            ..GenerationOptions::default()
+        };
+        group.bench_with_input(
+            BenchmarkId::new("greedy", max_new_tokens),
+            &options,
+            |b, options| b.iter(|| generator.generate_with(black_box("mix two colors"), options)),
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_attention, bench_generation);
+criterion_main!(benches);
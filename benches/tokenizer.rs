@@ -0,0 +1,46 @@
+//! Tokenizer throughput: tokenizing, fitting and encoding WGSL source
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use std::hint::black_box;
+use tiny_agent_trainer::dataset::WGSLDataset;
+use tiny_agent_trainer::WGSLTokenizer;
+
+fn training_texts() -> Vec<String> {
+    let path = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/config/wgsl_training_data.toml"
+    );
+    let dataset = WGSLDataset::from_toml(path).expect("bundled training data");
+    dataset
+        .examples
+        .into_iter()
+        .flat_map(|example| [example.natural_language, example.wgsl_code])
+        .collect()
+}
+
+fn bench_tokenizer(c: &mut Criterion) {
+    let texts = training_texts();
+    let corpus = texts.join("\n");
+    let mut tokenizer = WGSLTokenizer::new(8192, false);
+    tokenizer.fit(&texts, 1);
+
+    let mut group = c.benchmark_group("tokenizer");
+    group.throughput(Throughput::Bytes(corpus.len() as u64));
+    group.bench_function("tokenize", |b| {
+        b.iter(|| tokenizer.tokenize(black_box(&corpus)))
+    });
+    group.bench_function("encode", |b| {
+        b.iter(|| tokenizer.encode_text(black_box(&corpus)))
+    });
+    group.bench_with_input(BenchmarkId::new("fit", texts.len()), &texts, |b, texts| {
+        b.iter(|| {
+            let mut tokenizer = WGSLTokenizer::new(8192, false);
+            tokenizer.fit(black_box(texts), 1);
+            tokenizer
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_tokenizer);
+criterion_main!(benches);
@@ -0,0 +1,23 @@
+//! Records the git commit the crate is built from, for checkpoint provenance
+
+use std::path::Path;
+use std::process::Command;
+
+fn main() {
+    let git = Path::new(".git");
+    if git.exists() {
+        // A new commit moves `HEAD` or the branch it points to
+        println!("cargo:rerun-if-changed=.git/HEAD");
+        println!("cargo:rerun-if-changed=.git/refs");
+        println!("cargo:rerun-if-changed=.git/packed-refs");
+    }
+    let commit = Command::new("git")
+        .args(["rev-parse", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string());
+    if let Some(commit) = commit {
+        println!("cargo:rustc-env=TINY_TRAINER_GIT_COMMIT={}", commit);
+    }
+}
@@ -0,0 +1,248 @@
+//! Throughput of the model's forward pass, training step and generation
+//! across model sizes and sequence lengths
+//!
+//! Models are randomly initialized and fed synthetic token ids, so results
+//! measure compute alone and compare backends and optimizations directly.
+
+use crate::model::{CodeGenerationModel, ModelArchitecture};
+use crate::tokenizer::SpecialToken;
+use crate::training::{Optimizer, OptimizerKind};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// Learning rate of benchmarked training steps
+const BENCH_LEARNING_RATE: f64 = 1e-4;
+
+/// Model sizes, sequence lengths and repetitions to benchmark
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BenchConfig {
+    pub d_models: Vec<usize>,
+    pub num_layers: Vec<usize>,
+    pub seq_lens: Vec<usize>,
+    pub nhead: usize,
+    pub vocab_size: usize,
+    /// Timed repetitions of each measurement, after one warm-up
+    pub iterations: usize,
+}
+
+impl Default for BenchConfig {
+    fn default() -> Self {
+        Self {
+            d_models: vec![64, 128],
+            num_layers: vec![1, 2],
+            seq_lens: vec![32, 128],
+            nhead: 4,
+            vocab_size: 512,
+            iterations: 3,
+        }
+    }
+}
+
+impl BenchConfig {
+    /// Every combination of model size and sequence length, smallest first
+    pub fn cases(&self) -> Vec<BenchCase> {
+        let mut cases = Vec::new();
+        for &d_model in &self.d_models {
+            for &num_layers in &self.num_layers {
+                for &seq_len in &self.seq_lens {
+                    cases.push(BenchCase {
+                        d_model,
+                        num_layers,
+                        seq_len,
+                    });
+                }
+            }
+        }
+        cases
+    }
+
+    fn validate(&self) -> crate::Result<()> {
+        let invalid = |message: String| Err(crate::Error::ConfigError(message));
+        if self.iterations == 0 {
+            return invalid("benchmark iterations must be positive".to_string());
+        }
+        if self.vocab_size <= SpecialToken::EndOfSequence.token_id() + 1 {
+            return invalid(format!(
+                "benchmark vocabulary of {} tokens leaves no room past the special tokens",
+                self.vocab_size
+            ));
+        }
+        if self.cases().is_empty() {
+            return invalid("no model sizes or sequence lengths to benchmark".to_string());
+        }
+        if let Some(&d_model) = self
+            .d_models
+            .iter()
+            .find(|&&d| d == 0 || self.nhead == 0 || d % self.nhead != 0)
+        {
+            return invalid(format!(
+                "d_model {} is not divisible by {} heads",
+                d_model, self.nhead
+            ));
+        }
+        if self.num_layers.contains(&0) || self.seq_lens.contains(&0) {
+            return invalid("layers and sequence lengths must be positive".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// One model size and sequence length
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BenchCase {
+    pub d_model: usize,
+    pub num_layers: usize,
+    pub seq_len: usize,
+}
+
+/// Measured throughput of one [`BenchCase`], in tokens per second
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BenchResult {
+    pub case: BenchCase,
+    pub parameters: usize,
+    /// Tokens of input run through encoder and decoder
+    pub forward: f64,
+    /// Tokens of input trained on, including the optimizer update
+    pub train_step: f64,
+    /// Tokens generated greedily, one decoder pass per token
+    pub generation: f64,
+}
+
+/// Benchmark every case of `config`
+pub fn run_benchmarks(config: &BenchConfig) -> crate::Result<Vec<BenchResult>> {
+    config.validate()?;
+    let max_seq_len = config.seq_lens.iter().copied().max().unwrap_or(0) + 2;
+    Ok(config
+        .cases()
+        .into_iter()
+        .map(|case| {
+            let model = CodeGenerationModel::new(
+                ModelArchitecture::Transformer,
+                config.vocab_size,
+                case.d_model,
+                config.nhead,
+                case.num_layers,
+                Some(case.d_model * 4),
+                Some(max_seq_len),
+            );
+            tracing::debug!("Benchmarking {:?}", case);
+            bench_case(model, case, config)
+        })
+        .collect())
+}
+
+fn bench_case(
+    mut model: CodeGenerationModel,
+    case: BenchCase,
+    config: &BenchConfig,
+) -> BenchResult {
+    let first_id = SpecialToken::EndOfSequence.token_id() + 1;
+    let ids: Vec<usize> = (0..case.seq_len)
+        .map(|i| first_id + (i * 7) % (config.vocab_size - first_id))
+        .collect();
+    let tokens = case.seq_len as f64;
+
+    let forward = throughput(tokens, config.iterations, || {
+        model.forward(&ids);
+    });
+
+    let mut optimizer = Optimizer::new(OptimizerKind::Adam);
+    let train_step = throughput(tokens, config.iterations, || {
+        let mut grads = model.zero_gradients();
+        model.accumulate_gradients(&ids, &ids, &mut grads);
+        optimizer.step(&mut model, &grads, BENCH_LEARNING_RATE);
+    });
+
+    let generation = throughput(tokens, config.iterations, || {
+        let encoded = model.encode(&ids);
+        let mut decoder_ids = vec![SpecialToken::StartOfSequence.token_id()];
+        while decoder_ids.len() <= case.seq_len {
+            let logits = model.decode(&encoded, &decoder_ids);
+            let last = logits.row(logits.nrows() - 1);
+            let next = (0..last.len())
+                .max_by(|&a, &b| last[a].total_cmp(&last[b]))
+                .unwrap_or(0);
+            decoder_ids.push(next);
+        }
+    });
+
+    BenchResult {
+        case,
+        parameters: model.num_parameters(),
+        forward,
+        train_step,
+        generation,
+    }
+}
+
+/// Tokens per second of `run`, which handles `tokens` tokens per call,
+/// averaged over `iterations` calls after a warm-up call
+fn throughput(tokens: f64, iterations: usize, mut run: impl FnMut()) -> f64 {
+    run();
+    let start = Instant::now();
+    for _ in 0..iterations {
+        run();
+    }
+    let elapsed = start.elapsed().max(Duration::from_nanos(1));
+    tokens * iterations as f64 / elapsed.as_secs_f64()
+}
+
+/// Benchmark results laid out as a table
+pub struct BenchTable<'a>(pub &'a [BenchResult]);
+
+impl fmt::Display for BenchTable<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:>7}  {:>6}  {:>7}  {:>10}  {:>12}  {:>12}  {:>12}",
+            "d_model", "layers", "seq_len", "params", "forward/s", "train/s", "generate/s"
+        )?;
+        writeln!(f, "{}", "-".repeat(78))?;
+        for result in self.0 {
+            writeln!(
+                f,
+                "{:>7}  {:>6}  {:>7}  {:>10}  {:>12.1}  {:>12.1}  {:>12.1}",
+                result.case.d_model,
+                result.case.num_layers,
+                result.case.seq_len,
+                result.parameters,
+                result.forward,
+                result.train_step,
+                result.generation
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_benchmarks() {
+        let config = BenchConfig {
+            d_models: vec![8, 16],
+            num_layers: vec![1],
+            seq_lens: vec![4],
+            nhead: 2,
+            vocab_size: 32,
+            iterations: 1,
+        };
+        let results = run_benchmarks(&config).unwrap();
+
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[1].case, config.cases()[1]);
+        assert!(results[0].parameters < results[1].parameters);
+        for result in &results {
+            assert!(result.forward > 0.0 && result.forward.is_finite());
+            assert!(result.train_step > 0.0 && result.generation > 0.0);
+        }
+        let table = BenchTable(&results).to_string();
+        assert_eq!(table.lines().count(), 4);
+
+        let odd_heads = BenchConfig { nhead: 3, ..config };
+        assert!(run_benchmarks(&odd_heads).is_err());
+    }
+}
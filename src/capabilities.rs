@@ -0,0 +1,246 @@
+//! GPU capability detection and checks against [`RequirementsConfig`]
+//!
+//! [`CapabilityReport::detect`] lists every adapter wgpu can open with the
+//! features and limits shaders depend on, so deployment scripts can verify
+//! a machine before training or running shaders on it.
+
+use crate::config::RequirementsConfig;
+use serde::{Deserialize, Serialize};
+
+/// Limits of an adapter that bound what shaders can do
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AdapterLimits {
+    pub max_buffer_size: u64,
+    pub max_storage_buffer_binding_size: u32,
+    pub max_compute_invocations_per_workgroup: u32,
+    pub max_compute_workgroup_size: [u32; 3],
+}
+
+/// One adapter and its capabilities
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AdapterReport {
+    pub name: String,
+    /// Backend name as wgpu spells it: "vulkan", "metal", "dx12", "gl", ...
+    pub backend: String,
+    pub device_type: String,
+    pub driver: String,
+    /// Whether shaders may use `f16`
+    pub shader_f16: bool,
+    pub limits: AdapterLimits,
+}
+
+impl AdapterReport {
+    #[cfg(not(target_arch = "wasm32"))]
+    fn from_adapter(adapter: &wgpu::Adapter) -> Self {
+        let info = adapter.get_info();
+        let limits = adapter.limits();
+        Self {
+            name: info.name,
+            backend: info.backend.to_str().to_string(),
+            device_type: format!("{:?}", info.device_type),
+            driver: info.driver,
+            shader_f16: adapter.features().contains(wgpu::Features::SHADER_F16),
+            limits: AdapterLimits {
+                max_buffer_size: limits.max_buffer_size,
+                max_storage_buffer_binding_size: limits.max_storage_buffer_binding_size,
+                max_compute_invocations_per_workgroup: limits.max_compute_invocations_per_workgroup,
+                max_compute_workgroup_size: [
+                    limits.max_compute_workgroup_size_x,
+                    limits.max_compute_workgroup_size_y,
+                    limits.max_compute_workgroup_size_z,
+                ],
+            },
+        }
+    }
+
+    /// Whether this is a hardware GPU rather than a software rasterizer
+    pub fn is_gpu(&self) -> bool {
+        matches!(
+            self.device_type.as_str(),
+            "DiscreteGpu" | "IntegratedGpu" | "VirtualGpu"
+        )
+    }
+
+    /// Requirements of `requirements` this adapter fails, as messages
+    pub fn unmet(&self, requirements: &RequirementsConfig) -> Vec<String> {
+        let mut unmet = Vec::new();
+        if requirements.gpu && !self.is_gpu() {
+            unmet.push(format!("not a GPU ({})", self.device_type));
+        }
+        if !requirements.backends.is_empty()
+            && !requirements
+                .backends
+                .iter()
+                .any(|backend| backend.eq_ignore_ascii_case(&self.backend))
+        {
+            unmet.push(format!(
+                "backend {} is not one of {}",
+                self.backend,
+                requirements.backends.join(", ")
+            ));
+        }
+        if requirements.shader_f16 && !self.shader_f16 {
+            unmet.push("no shader-f16 support".to_string());
+        }
+        if let Some(min) = requirements.min_storage_buffer_binding_size {
+            if u64::from(self.limits.max_storage_buffer_binding_size) < min {
+                unmet.push(format!(
+                    "storage buffer bindings up to {} bytes, {} required",
+                    self.limits.max_storage_buffer_binding_size, min
+                ));
+            }
+        }
+        if let Some(min) = requirements.min_buffer_size {
+            if self.limits.max_buffer_size < min {
+                unmet.push(format!(
+                    "buffers up to {} bytes, {} required",
+                    self.limits.max_buffer_size, min
+                ));
+            }
+        }
+        if let Some(min) = requirements.min_workgroup_invocations {
+            if self.limits.max_compute_invocations_per_workgroup < min {
+                unmet.push(format!(
+                    "{} invocations per workgroup, {} required",
+                    self.limits.max_compute_invocations_per_workgroup, min
+                ));
+            }
+        }
+        unmet
+    }
+}
+
+/// Every detected adapter, and whether one meets the requirements
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CapabilityReport {
+    pub version: String,
+    pub adapters: Vec<AdapterReport>,
+    /// Backends with at least one adapter
+    pub backends: Vec<String>,
+    /// First adapter meeting every requirement
+    pub selected: Option<String>,
+    /// Why each adapter was rejected, by adapter name; empty once one is
+    /// selected, or when nothing is required of a machine without adapters
+    pub unmet: Vec<(String, Vec<String>)>,
+}
+
+/// Adapters of every backend wgpu can open; none in WebAssembly builds,
+/// which have no native GPU access
+pub(crate) fn enumerate_adapters() -> Vec<AdapterReport> {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        wgpu::Instance::default()
+            .enumerate_adapters(wgpu::Backends::all())
+            .iter()
+            .map(AdapterReport::from_adapter)
+            .collect()
+    }
+    #[cfg(target_arch = "wasm32")]
+    {
+        Vec::new()
+    }
+}
+
+impl CapabilityReport {
+    /// Detect the adapters of every backend and check them against
+    /// `requirements`
+    pub fn detect(requirements: &RequirementsConfig) -> Self {
+        Self::from_adapters(enumerate_adapters(), requirements)
+    }
+
+    pub fn from_adapters(adapters: Vec<AdapterReport>, requirements: &RequirementsConfig) -> Self {
+        let mut backends: Vec<String> = adapters.iter().map(|a| a.backend.clone()).collect();
+        backends.sort();
+        backends.dedup();
+
+        let mut unmet = Vec::new();
+        let mut selected = None;
+        for adapter in &adapters {
+            let reasons = adapter.unmet(requirements);
+            if reasons.is_empty() {
+                selected = Some(adapter.name.clone());
+                unmet.clear();
+                break;
+            }
+            unmet.push((adapter.name.clone(), reasons));
+        }
+        if adapters.is_empty() && *requirements != RequirementsConfig::default() {
+            unmet.push(("(none)".to_string(), vec!["no adapter found".to_string()]));
+        }
+
+        Self {
+            version: crate::VERSION.to_string(),
+            adapters,
+            backends,
+            selected,
+            unmet,
+        }
+    }
+
+    /// Whether some adapter meets every requirement, or none are declared
+    pub fn is_satisfied(&self) -> bool {
+        self.unmet.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn adapter(name: &str, device_type: &str, backend: &str, shader_f16: bool) -> AdapterReport {
+        AdapterReport {
+            name: name.to_string(),
+            backend: backend.to_string(),
+            device_type: device_type.to_string(),
+            driver: String::new(),
+            shader_f16,
+            limits: AdapterLimits {
+                max_buffer_size: 1 << 28,
+                max_storage_buffer_binding_size: 1 << 27,
+                max_compute_invocations_per_workgroup: 256,
+                max_compute_workgroup_size: [256, 256, 64],
+            },
+        }
+    }
+
+    #[test]
+    fn test_requirements() {
+        let adapters = vec![
+            adapter("llvmpipe", "Cpu", "gl", false),
+            adapter("gpu", "DiscreteGpu", "vulkan", true),
+        ];
+
+        let report = CapabilityReport::from_adapters(adapters.clone(), &Default::default());
+        assert_eq!(report.selected.as_deref(), Some("llvmpipe"));
+        assert_eq!(report.backends, vec!["gl", "vulkan"]);
+
+        let requirements = RequirementsConfig {
+            gpu: true,
+            backends: vec!["Vulkan".to_string()],
+            shader_f16: true,
+            min_workgroup_invocations: Some(256),
+            ..Default::default()
+        };
+        let report = CapabilityReport::from_adapters(adapters.clone(), &requirements);
+        assert_eq!(report.selected.as_deref(), Some("gpu"));
+        assert!(report.unmet.is_empty());
+
+        let requirements = RequirementsConfig {
+            min_buffer_size: Some(1 << 30),
+            ..requirements
+        };
+        let report = CapabilityReport::from_adapters(adapters, &requirements);
+        assert!(!report.is_satisfied());
+        assert_eq!(report.unmet.len(), 2);
+        assert_eq!(report.unmet[0].1.len(), 4);
+
+        let none = CapabilityReport::from_adapters(Vec::new(), &Default::default());
+        assert!(none.is_satisfied());
+        let gpu = RequirementsConfig {
+            gpu: true,
+            ..Default::default()
+        };
+        let none = CapabilityReport::from_adapters(Vec::new(), &gpu);
+        assert!(!none.is_satisfied());
+    }
+}
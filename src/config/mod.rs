@@ -3,7 +3,140 @@
 //! This module provides TOML-based configuration following the chromatic_cognition_core pattern.
 
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fmt;
 use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// Key naming the file a config inherits from, relative to the config
+pub const EXTENDS_KEY: &str = "extends";
+
+/// Names accepted by [`Config::preset`], smallest first
+pub const PRESETS: [&str; 3] = ["tiny", "small", "base"];
+
+/// Values a preset overrides in the default configuration
+struct Preset {
+    d_model: usize,
+    nhead: usize,
+    num_layers: usize,
+    dim_feedforward: usize,
+    learning_rate: f64,
+    num_epochs: usize,
+    batch_size: usize,
+    patience: usize,
+}
+
+/// File format of a configuration
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ConfigFormat {
+    #[default]
+    Toml,
+    Json,
+    Yaml,
+}
+
+impl ConfigFormat {
+    /// Extensions of config files, in the order [`Config::find`] tries them
+    pub const EXTENSIONS: [&'static str; 4] = ["toml", "json", "yaml", "yml"];
+
+    /// Format named by `path`'s extension: `.json`, `.yaml`/`.yml`, else TOML
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("json") => ConfigFormat::Json,
+            Some("yaml" | "yml") => ConfigFormat::Yaml,
+            _ => ConfigFormat::Toml,
+        }
+    }
+
+    /// Parse a document into a TOML table; JSON and YAML nulls are dropped,
+    /// leaving the field at its default
+    pub fn parse(&self, content: &str) -> crate::Result<toml::Table> {
+        let value = match self {
+            ConfigFormat::Toml => return Ok(toml::from_str(content)?),
+            ConfigFormat::Json => serde_json::from_str(content)?,
+            ConfigFormat::Yaml => {
+                let mut value: serde_yaml_ng::Value = serde_yaml_ng::from_str(content)?;
+                value.apply_merge()?;
+                match value {
+                    // An empty document is an empty mapping
+                    serde_yaml_ng::Value::Null => serde_json::Value::Object(Default::default()),
+                    value => serde_json::to_value(value)?,
+                }
+            }
+        };
+        match json_to_toml(value, "")? {
+            Some(toml::Value::Table(table)) => Ok(table),
+            _ => Err(crate::Error::ConfigError(
+                "config must be a mapping of sections".to_string(),
+            )),
+        }
+    }
+
+    /// Render `value` in this format
+    pub fn render<T: Serialize>(&self, value: &T) -> crate::Result<String> {
+        Ok(match self {
+            ConfigFormat::Toml => toml::to_string_pretty(value)?,
+            ConfigFormat::Json => serde_json::to_string_pretty(value)? + "\n",
+            ConfigFormat::Yaml => serde_yaml_ng::to_string(value)?,
+        })
+    }
+}
+
+impl FromStr for ConfigFormat {
+    type Err = crate::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "toml" => Ok(ConfigFormat::Toml),
+            "json" => Ok(ConfigFormat::Json),
+            "yaml" | "yml" => Ok(ConfigFormat::Yaml),
+            other => Err(crate::Error::ConfigError(format!(
+                "Unknown config format '{}'. Must be one of: toml, json, yaml",
+                other
+            ))),
+        }
+    }
+}
+
+/// `value` as TOML, or `None` for null; `field` names it in errors
+fn json_to_toml(value: serde_json::Value, field: &str) -> crate::Result<Option<toml::Value>> {
+    use serde_json::Value;
+    Ok(Some(match value {
+        Value::Null => return Ok(None),
+        Value::Bool(b) => toml::Value::Boolean(b),
+        Value::Number(n) => match n.as_i64() {
+            Some(int) => toml::Value::Integer(int),
+            None => toml::Value::Float(n.as_f64().unwrap_or(f64::NAN)),
+        },
+        Value::String(s) => toml::Value::String(s),
+        Value::Array(items) => toml::Value::Array(
+            items
+                .into_iter()
+                .enumerate()
+                .map(|(i, item)| {
+                    let field = format!("{}[{}]", field, i);
+                    json_to_toml(item, &field)?.ok_or_else(|| {
+                        crate::Error::ConfigError(format!("{}: lists cannot hold null", field))
+                    })
+                })
+                .collect::<crate::Result<_>>()?,
+        ),
+        Value::Object(map) => {
+            let mut table = toml::Table::new();
+            for (key, value) in map {
+                let field = match field {
+                    "" => key.clone(),
+                    parent => format!("{}.{}", parent, key),
+                };
+                if let Some(value) = json_to_toml(value, &field)? {
+                    table.insert(key, value);
+                }
+            }
+            toml::Value::Table(table)
+        }
+    }))
+}
 
 /// Main configuration structure
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -18,6 +151,24 @@ pub struct Config {
     pub tokenizer: TokenizerConfig,
     /// Dataset configuration
     pub dataset: DatasetConfig,
+    /// WGSL validation settings
+    #[serde(default)]
+    pub validation: ValidationConfig,
+    /// WGSL lint rule levels
+    #[serde(default)]
+    pub lint: LintConfig,
+    /// Experiment tracking
+    #[serde(default)]
+    pub tracking: TrackingConfig,
+    /// Prompt formatting shared by training and inference
+    #[serde(default)]
+    pub prompt: PromptConfig,
+    /// Decoding defaults for generation and training previews
+    #[serde(default)]
+    pub generation: GenerationConfig,
+    /// Compute device
+    #[serde(default)]
+    pub device: DeviceConfig,
 }
 
 /// Task-level configuration
@@ -52,6 +203,163 @@ pub struct ModelConfig {
     /// Maximum sequence length
     #[serde(default = "default_max_seq_len")]
     pub max_seq_len: usize,
+    /// Feed-forward activation
+    #[serde(default)]
+    pub activation: Activation,
+    /// Where layer normalization sits in each transformer block
+    #[serde(default)]
+    pub norm_placement: NormPlacement,
+    /// How token positions are encoded
+    #[serde(default)]
+    pub positional_encoding: PositionalEncoding,
+    /// Storage of the token embeddings and position table
+    #[serde(default)]
+    pub embedding_dtype: Dtype,
+    /// Weight initialization seed; defaults to `training.seed`. Prefer
+    /// `init.seed`, which takes precedence
+    #[serde(default)]
+    pub seed: Option<u64>,
+    /// Weight initialization scheme and seed under `[model.init]`
+    #[serde(default)]
+    pub init: InitConfig,
+    /// Blocked and sliding-window attention under `[model.attention]`
+    #[serde(default)]
+    pub attention: AttentionConfig,
+    /// Initialize weights from a pretrained checkpoint under `[model.pretrained]`
+    #[serde(default)]
+    pub pretrained: Option<PretrainedConfig>,
+}
+
+/// Non-linearity of the transformer feed-forward blocks
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Activation {
+    #[default]
+    Relu,
+    /// Gaussian error linear unit (tanh approximation)
+    Gelu,
+    /// Sigmoid linear unit, `x * sigmoid(x)`
+    Silu,
+    /// SiLU-gated linear unit: `silu(x W1) * (x W_gate)`, adding a gate
+    /// projection to every feed-forward block
+    Swiglu,
+}
+
+/// Position of layer normalization relative to the residual connections
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NormPlacement {
+    /// Normalize after adding each sub-layer's output to the residual, as in
+    /// the original transformer
+    #[default]
+    Post,
+    /// Normalize each sub-layer's input and the output of each stack, which
+    /// trains more stably from scratch
+    Pre,
+}
+
+/// Distribution of the initial weights
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InitScheme {
+    /// Uniform in ±0.1 regardless of layer size, including biases
+    Uniform,
+    /// Xavier/Glorot uniform, scaled by fan-in and fan-out; zero biases
+    #[default]
+    Xavier,
+    /// Kaiming/He uniform, scaled by fan-in for ReLU-like activations; zero
+    /// biases
+    He,
+}
+
+/// `[model.init]` section
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InitConfig {
+    pub scheme: InitScheme,
+    /// Seed of the initial weights; defaults to `model.seed`, then
+    /// `training.seed`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+}
+
+/// Floating-point storage format
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Dtype {
+    #[default]
+    F32,
+    /// Half precision: half the memory, about three significant digits
+    F16,
+}
+
+impl Dtype {
+    /// Bytes per stored value
+    pub fn size(self) -> usize {
+        match self {
+            Self::F32 => 4,
+            Self::F16 => 2,
+        }
+    }
+}
+
+/// `[model.attention]` section
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AttentionConfig {
+    /// Compute attention over blocks of this many queries and keys with an
+    /// online softmax, so memory grows linearly with the sequence length
+    /// instead of quadratically; 0 computes full score matrices
+    pub block_size: usize,
+    /// Let each query attend only to keys fewer than this many positions
+    /// away in self-attention; 0 attends to the whole sequence
+    pub window: usize,
+}
+
+impl std::fmt::Display for AttentionConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.block_size {
+            0 => write!(f, "full")?,
+            size => write!(f, "blocks of {}", size)?,
+        }
+        if self.window > 0 {
+            write!(f, ", window {}", self.window)?;
+        }
+        Ok(())
+    }
+}
+
+/// How the transformer sees token positions
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PositionalEncoding {
+    /// Absolute sine/cosine vectors added to the token embeddings
+    #[default]
+    Sinusoidal,
+    /// Rotary position embedding: queries and keys of self-attention are
+    /// rotated by their position, so scores depend on relative offsets and
+    /// extend to lengths not seen in training
+    Rope,
+}
+
+/// External weights loaded over the random initialization before training
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PretrainedConfig {
+    /// `.safetensors` or `.npz` file
+    pub path: PathBuf,
+    /// `{"token": id}` vocabulary of the pretrained model (e.g. a Hugging Face
+    /// `vocab.json`); when set, vocabulary-indexed tensors are matched by
+    /// token text instead of by id
+    #[serde(default)]
+    pub vocab: Option<PathBuf>,
+    /// Parameter name to tensor name in `path`; unmapped parameters look for
+    /// a tensor of the same name
+    #[serde(default)]
+    pub map: BTreeMap<String, String>,
+    /// Parameters whose source tensor is stored transposed, such as PyTorch
+    /// `nn.Linear` weights (`[out, in]`)
+    #[serde(default)]
+    pub transpose: Vec<String>,
 }
 
 /// Training configuration
@@ -78,6 +386,152 @@ pub struct TrainingConfig {
     /// Save checkpoint every N epochs
     #[serde(default = "default_save_every")]
     pub save_every: usize,
+    /// Write training scalars under `paths.log_path` ("tensorboard" or "json")
+    #[serde(default)]
+    pub metrics: Option<MetricsFormat>,
+    /// Seed for weight initialization, data shuffling and evaluation sampling
+    #[serde(default = "default_seed")]
+    pub seed: u64,
+    /// Train on the simplest examples first, under `[training.curriculum]`
+    #[serde(default)]
+    pub curriculum: Option<CurriculumConfig>,
+    /// Log per-layer gradient norms and activation statistics every N steps,
+    /// and abort naming the layer once any of them turns NaN or infinite;
+    /// 0 disables both
+    #[serde(default)]
+    pub layer_stats_every: u64,
+    /// Validate and checkpoint an exponential moving average of the weights
+    /// with this decay per step (e.g. 0.999) instead of the raw weights
+    #[serde(default)]
+    pub ema_decay: Option<f64>,
+    /// Parameters left as they are, under `[training.freeze]`
+    #[serde(default)]
+    pub freeze: Option<FreezeConfig>,
+    /// Learn from a larger model's predictions, under `[training.distillation]`
+    #[serde(default)]
+    pub distillation: Option<DistillationConfig>,
+    /// Generations written to the run's `samples/` while training, under
+    /// `[training.preview]`
+    #[serde(default)]
+    pub preview: Option<PreviewConfig>,
+}
+
+/// Sample previews under `[training.preview]`, decoded with the
+/// `[generation]` settings
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PreviewConfig {
+    /// Prompts generated from
+    pub prompts: Vec<String>,
+    /// Preview every N epochs
+    #[serde(default = "default_preview_every")]
+    pub every: usize,
+}
+
+fn default_preview_every() -> usize {
+    1
+}
+
+/// Knowledge distillation under `[training.distillation]`
+///
+/// The student uses the teacher's tokenizer, and the loss of each target
+/// token mixes the hard cross-entropy with the KL divergence from the
+/// teacher's prediction, both softened by `temperature`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DistillationConfig {
+    /// Teacher checkpoint
+    pub teacher: PathBuf,
+    /// Weight of the soft-label loss; the hard loss gets `1 - alpha`
+    #[serde(default = "default_distillation_alpha")]
+    pub alpha: f64,
+    #[serde(default = "default_distillation_temperature")]
+    pub temperature: f64,
+}
+
+/// Components kept fixed while training, under `[training.freeze]`
+///
+/// Fine-tuning a base model on a handful of examples from a niche domain
+/// overfits less when only the upper layers adapt.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FreezeConfig {
+    /// The token embeddings
+    pub embeddings: bool,
+    /// Every encoder layer and the encoder norm
+    pub encoder: bool,
+    /// The first N layers of both the encoder and the decoder
+    pub layers: usize,
+}
+
+impl FreezeConfig {
+    /// Whether the parameter `name` stays fixed
+    pub fn freezes(&self, name: &str) -> bool {
+        let layer = crate::model::diagnostics::layer_of(name);
+        if layer == "token_embedding" {
+            return self.embeddings;
+        }
+        if self.encoder && (layer == "encoder_norm" || layer.starts_with("encoder.")) {
+            return true;
+        }
+        match layer.split_once('.') {
+            Some(("encoder" | "decoder", index)) => {
+                index.parse::<usize>().is_ok_and(|index| index < self.layers)
+            }
+            _ => false,
+        }
+    }
+}
+
+/// What makes an example harder in a curriculum
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CurriculumMetric {
+    /// Prompt plus code tokens
+    #[default]
+    Length,
+    /// The example's `difficulty`, then its length; examples without one
+    /// count as medium
+    Difficulty,
+    /// Position in `tags` of the hardest listed tag the example carries, then
+    /// its length; examples with none of the tags come last
+    Tags,
+}
+
+/// Curriculum schedule under `[training.curriculum]`
+///
+/// Each curriculum epoch trains on the easiest part of the data, growing
+/// linearly from `start_fraction` to everything by epoch `epochs`, in
+/// batches ordered from easiest to hardest. Later epochs shuffle as usual.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CurriculumConfig {
+    pub by: CurriculumMetric,
+    /// Epochs the curriculum lasts
+    pub epochs: usize,
+    /// Share of the examples, easiest first, the first epoch trains on
+    pub start_fraction: f64,
+    /// Tags from easiest to hardest, for `by = "tags"`
+    pub tags: Vec<String>,
+}
+
+impl Default for CurriculumConfig {
+    fn default() -> Self {
+        Self {
+            by: CurriculumMetric::Length,
+            epochs: 5,
+            start_fraction: 0.25,
+            tags: Vec::new(),
+        }
+    }
+}
+
+/// On-disk format of training scalars
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MetricsFormat {
+    /// `events.out.tfevents.*` records readable by TensorBoard
+    Tensorboard,
+    /// `scalars.jsonl`, one JSON object per scalar
+    Json,
 }
 
 /// Tokenizer configuration
@@ -95,6 +549,18 @@ pub struct TokenizerConfig {
     /// Minimum frequency for vocabulary
     #[serde(default = "default_min_freq")]
     pub min_freq: usize,
+    /// Task control tokens such as `<compute>` or `<sep>`, given stable ids
+    /// ahead of the fitted vocabulary
+    #[serde(default)]
+    pub control_tokens: Vec<String>,
+    /// Normalization of natural language prompts under
+    /// `[tokenizer.normalize]`, applied in training and saved for inference
+    #[serde(default)]
+    pub normalize: Option<crate::tokenizer::PromptNormalizer>,
+    /// Extra keywords, types and attributes, and disabled categories, under
+    /// `[tokenizer.patterns]`
+    #[serde(default)]
+    pub patterns: crate::tokenizer::PatternOverrides,
 }
 
 /// Dataset configuration
@@ -112,6 +578,382 @@ pub struct DatasetConfig {
     /// Validation split ratio
     #[serde(default = "default_val_ratio")]
     pub val_ratio: f32,
+    /// Token limit per example (defaults to the model's max_seq_len)
+    #[serde(default)]
+    pub max_tokens: Option<usize>,
+    /// What to do with examples longer than the token limit
+    #[serde(default)]
+    pub length_policy: LengthPolicy,
+}
+
+/// Handling of examples that exceed the token limit
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LengthPolicy {
+    /// Remove over-long examples from the dataset
+    Drop,
+    /// Cut over-long text down to the token limit
+    #[default]
+    Truncate,
+}
+
+/// WGSL validation settings
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ValidationConfig {
+    /// Capability and limit profile shaders are checked against
+    #[serde(default)]
+    pub profile: ValidationProfileKind,
+    /// Extra naga capabilities (e.g. "float64"), added to the profile's own
+    #[serde(default)]
+    pub capabilities: Vec<String>,
+    /// Limit overrides; defaults to the profile's limits
+    #[serde(default)]
+    pub limits: Option<ValidationLimits>,
+}
+
+/// Named validation profile
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ValidationProfileKind {
+    /// Features and limits guaranteed by every WebGPU implementation
+    WebgpuCore,
+    /// Every naga capability with typical desktop GPU limits
+    #[default]
+    NativeExtended,
+    /// Only the listed capabilities, WebGPU limits unless overridden
+    Custom,
+}
+
+/// Resource limits checked in addition to naga validation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ValidationLimits {
+    /// Maximum `@workgroup_size` per dimension
+    pub max_workgroup_size: [u32; 3],
+    /// Maximum product of the workgroup dimensions
+    pub max_invocations_per_workgroup: u32,
+    /// Maximum number of bind groups (`@group` indices must be below this)
+    pub max_bind_groups: u32,
+    /// Maximum storage buffers used by one entry point
+    pub max_storage_buffers_per_stage: u32,
+    /// Maximum uniform buffers used by one entry point
+    pub max_uniform_buffers_per_stage: u32,
+}
+
+impl ValidationLimits {
+    /// WebGPU default limits
+    pub fn webgpu() -> Self {
+        Self {
+            max_workgroup_size: [256, 256, 64],
+            max_invocations_per_workgroup: 256,
+            max_bind_groups: 4,
+            max_storage_buffers_per_stage: 8,
+            max_uniform_buffers_per_stage: 12,
+        }
+    }
+
+    /// Limits typical of desktop GPUs
+    pub fn native() -> Self {
+        Self {
+            max_workgroup_size: [1024, 1024, 64],
+            max_invocations_per_workgroup: 1024,
+            max_bind_groups: 8,
+            max_storage_buffers_per_stage: 64,
+            max_uniform_buffers_per_stage: 64,
+        }
+    }
+}
+
+impl Default for ValidationLimits {
+    fn default() -> Self {
+        Self::webgpu()
+    }
+}
+
+/// WGSL lint rule
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LintRule {
+    /// Resource binding no entry point accesses
+    UnusedBinding,
+    /// `let` or `var` that is never read
+    UnusedVariable,
+    /// `@workgroup_size` written with literals instead of a named constant
+    MagicWorkgroupSize,
+    /// `var<storage>` without an explicit access mode
+    MissingStorageAccess,
+    /// Implicit-derivative texture sample under non-uniform control flow
+    NonUniformTextureSample,
+}
+
+impl LintRule {
+    /// Every rule, in reporting order
+    pub const ALL: [LintRule; 5] = [
+        LintRule::UnusedBinding,
+        LintRule::UnusedVariable,
+        LintRule::MagicWorkgroupSize,
+        LintRule::MissingStorageAccess,
+        LintRule::NonUniformTextureSample,
+    ];
+
+    /// Rule name as used in config files
+    pub fn name(&self) -> &'static str {
+        match self {
+            LintRule::UnusedBinding => "unused-binding",
+            LintRule::UnusedVariable => "unused-variable",
+            LintRule::MagicWorkgroupSize => "magic-workgroup-size",
+            LintRule::MissingStorageAccess => "missing-storage-access",
+            LintRule::NonUniformTextureSample => "non-uniform-texture-sample",
+        }
+    }
+
+    /// Level used when the config does not override it
+    pub fn default_level(&self) -> LintLevel {
+        match self {
+            LintRule::MagicWorkgroupSize => LintLevel::Allow,
+            LintRule::NonUniformTextureSample => LintLevel::Deny,
+            _ => LintLevel::Warn,
+        }
+    }
+}
+
+impl fmt::Display for LintRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Severity of a lint rule
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LintLevel {
+    /// Rule is disabled
+    Allow,
+    /// Report without failing
+    Warn,
+    /// Report and fail the lint run
+    Deny,
+}
+
+/// Lint rule levels, e.g. `unused-variable = "deny"` under `[lint]`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LintConfig {
+    #[serde(flatten)]
+    pub levels: BTreeMap<LintRule, LintLevel>,
+}
+
+impl LintConfig {
+    /// Effective level of a rule
+    pub fn level(&self, rule: LintRule) -> LintLevel {
+        self.levels
+            .get(&rule)
+            .copied()
+            .unwrap_or_else(|| rule.default_level())
+    }
+}
+
+/// Experiment tracking service a run reports to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TrackingBackend {
+    /// No tracking
+    #[default]
+    None,
+    /// Weights & Biases (requires the `wandb` cargo feature and `WANDB_API_KEY`)
+    Wandb,
+}
+
+/// Experiment tracking settings under `[tracking]`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrackingConfig {
+    #[serde(default)]
+    pub backend: TrackingBackend,
+    /// Project the run is reported to (defaults to the task name)
+    #[serde(default)]
+    pub project: Option<String>,
+    /// User or team owning the project (defaults to the API key's user)
+    #[serde(default)]
+    pub entity: Option<String>,
+    /// Display name of the run
+    #[serde(default)]
+    pub run_name: Option<String>,
+    /// API host (defaults to `https://api.wandb.ai`)
+    #[serde(default)]
+    pub base_url: Option<String>,
+}
+
+/// Prompt formatting under `[prompt]`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PromptConfig {
+    /// Template every prompt is formatted with before tokenization, using
+    /// `{description}`, `{stage}` and `{bindings}`; saved in the checkpoint
+    #[serde(default)]
+    pub template: Option<String>,
+}
+
+/// Decoding defaults under `[generation]`, shared by the CLI and the
+/// trainer's sample previews
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GenerationConfig {
+    /// Softmax temperature; 0 always picks the most likely token
+    pub temperature: f32,
+    /// Sample only among the `top_k` most likely tokens (0 = no limit)
+    pub top_k: usize,
+    /// Sample only among the most likely tokens whose probabilities add up
+    /// to `top_p` (1 = no limit)
+    pub top_p: f32,
+    /// Beams searched; 1 decodes a single sequence
+    pub beam_width: usize,
+    /// Most tokens to generate (defaults to the model's limit)
+    pub max_new_tokens: Option<usize>,
+    /// Regenerations of invalid code, with the error in the prompt
+    pub retries: usize,
+    /// Shader stage to condition on, through its control token
+    pub target_stage: Option<crate::wgsl::Stage>,
+    /// Keep other stages' entry point attributes out of the output
+    pub constrain_stage: bool,
+    /// When no generation validates, return the valid shader of
+    /// `fallback_dataset` or the built-in templates whose prompt is closest
+    pub fallback: bool,
+    /// Examples to fall back to besides the templates (the CLI defaults to
+    /// the config's training data)
+    pub fallback_dataset: Option<PathBuf>,
+    /// End each line `generate` outputs with the model's confidence in it,
+    /// as a comment
+    pub confidence_comments: bool,
+    /// Remote API used for `openai:<model>` models
+    pub provider: ProviderConfig,
+}
+
+impl Default for GenerationConfig {
+    fn default() -> Self {
+        Self {
+            temperature: 0.0,
+            top_k: 0,
+            top_p: 1.0,
+            beam_width: 1,
+            max_new_tokens: None,
+            retries: 0,
+            target_stage: None,
+            constrain_stage: false,
+            fallback: false,
+            fallback_dataset: None,
+            confidence_comments: false,
+            provider: ProviderConfig::default(),
+        }
+    }
+}
+
+impl GenerationConfig {
+    /// Problems with the settings, each prefixed with its field
+    pub fn validation_errors(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        let mut check = |ok: bool, field: &str, problem: String| {
+            if !ok {
+                errors.push(format!("{}: {}", field, problem));
+            }
+        };
+        check(
+            self.temperature.is_finite() && self.temperature >= 0.0,
+            "generation.temperature",
+            format!("{} is not a non-negative number", self.temperature),
+        );
+        check(
+            self.top_p > 0.0 && self.top_p <= 1.0,
+            "generation.top_p",
+            format!("{} is outside (0, 1]", self.top_p),
+        );
+        check(
+            self.beam_width > 0,
+            "generation.beam_width",
+            "must be positive".to_string(),
+        );
+        check(
+            self.max_new_tokens != Some(0),
+            "generation.max_new_tokens",
+            "must be positive".to_string(),
+        );
+        check(
+            self.provider.timeout_secs > 0,
+            "generation.provider.timeout_secs",
+            "must be positive".to_string(),
+        );
+        errors
+    }
+
+    /// Decoding options for one sequence, seeded with `seed`
+    pub fn options(&self, seed: Option<u64>) -> crate::inference::GenerationOptions {
+        crate::inference::GenerationOptions {
+            temperature: self.temperature,
+            top_k: self.top_k,
+            top_p: self.top_p,
+            seed,
+            max_new_tokens: self.max_new_tokens,
+            target_stage: self.target_stage,
+            constrain_stage: self.constrain_stage,
+            ..Default::default()
+        }
+    }
+
+    /// Repair loop `retries` asks for: the first generation plus one per
+    /// retry, or `None` without retries
+    pub fn repair_options(&self) -> Option<crate::inference::RepairOptions> {
+        (self.retries > 0).then(|| crate::inference::RepairOptions {
+            max_attempts: self.retries + 1,
+            ..Default::default()
+        })
+    }
+}
+
+/// OpenAI-compatible chat completions API under `[generation.provider]`
+/// (requires the `openai` cargo feature)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProviderConfig {
+    /// API base URL, including the version path (defaults to
+    /// `https://api.openai.com/v1`)
+    pub endpoint: Option<String>,
+    /// API key; falls back to `OPENAI_API_KEY`
+    pub api_key: Option<String>,
+    /// System message sent before every prompt (defaults to asking for WGSL
+    /// code only)
+    pub system_prompt: Option<String>,
+    /// Seconds to wait for each response
+    pub timeout_secs: u64,
+}
+
+impl Default for ProviderConfig {
+    fn default() -> Self {
+        Self {
+            endpoint: None,
+            api_key: None,
+            system_prompt: None,
+            timeout_secs: 120,
+        }
+    }
+}
+
+/// CPU threads and memory budget under `[device]`, shared by training and
+/// inference
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DeviceConfig {
+    /// Worker threads (0 = one per core)
+    pub threads: usize,
+    /// Most memory models and training state may take, in MiB
+    pub memory_limit_mb: Option<u64>,
+}
+
+impl DeviceConfig {
+    /// Problems with the settings, each prefixed with its field
+    pub fn validation_errors(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        if self.memory_limit_mb == Some(0) {
+            errors.push("device.memory_limit_mb: must be positive".to_string());
+        }
+        errors
+    }
 }
 
 /// Engine configuration for production environment
@@ -125,6 +967,105 @@ pub struct EngineConfig {
     pub disable_debug_assertions: bool,
     /// Output paths configuration
     pub paths: PathsConfig,
+    /// Hardware `check` requires
+    #[serde(default)]
+    pub requirements: RequirementsConfig,
+    /// Log files under `paths.log_path`
+    #[serde(default)]
+    pub logging: LoggingConfig,
+    /// Hugging Face Hub access for `hub` commands
+    #[serde(default)]
+    pub hub: HubConfig,
+}
+
+/// Hugging Face Hub access under `[hub]` (requires the `hub` cargo feature)
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HubConfig {
+    /// Access token; falls back to `HF_TOKEN`. Needed to push and to pull
+    /// private or gated repositories
+    #[serde(default)]
+    pub token: Option<String>,
+    /// Hub host (defaults to `https://huggingface.co`)
+    #[serde(default)]
+    pub endpoint: Option<String>,
+}
+
+/// When log files are rotated
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogRotation {
+    /// One file per UTC day, suffixed with the date
+    #[default]
+    Daily,
+    /// Once the file reaches `max_size_mb`, shifting older files to `.1`, `.2`, ...
+    Size,
+    /// A single ever-growing file
+    Never,
+}
+
+/// Log line format
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// The human-readable console format, without colors
+    #[default]
+    Text,
+    /// One JSON object per line
+    Json,
+}
+
+/// Log file settings under `[logging]`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LoggingConfig {
+    /// Also write logs to files in `paths.log_path`
+    pub file: bool,
+    pub rotation: LogRotation,
+    /// Size at which `size` rotation starts a new file, in MiB
+    pub max_size_mb: u64,
+    /// Log files kept, including the current one
+    pub max_files: usize,
+    pub format: LogFormat,
+    /// Log each span as it closes, with its busy and idle time
+    pub span_events: bool,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self {
+            file: false,
+            rotation: LogRotation::Daily,
+            max_size_mb: 10,
+            max_files: 7,
+            format: LogFormat::Text,
+            span_events: false,
+        }
+    }
+}
+
+/// Hardware requirements under `[requirements]`, checked by `check`
+///
+/// All requirements must be met by a single adapter.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RequirementsConfig {
+    /// Require a hardware GPU rather than a software rasterizer
+    #[serde(default)]
+    pub gpu: bool,
+    /// Accepted backends, e.g. `["vulkan", "metal"]`; empty accepts any
+    #[serde(default)]
+    pub backends: Vec<String>,
+    /// Require `f16` support in shaders
+    #[serde(default)]
+    pub shader_f16: bool,
+    /// Smallest acceptable storage buffer binding, in bytes
+    #[serde(default)]
+    pub min_storage_buffer_binding_size: Option<u64>,
+    /// Smallest acceptable buffer, in bytes
+    #[serde(default)]
+    pub min_buffer_size: Option<u64>,
+    /// Smallest acceptable number of invocations per compute workgroup
+    #[serde(default)]
+    pub min_workgroup_invocations: Option<u32>,
 }
 
 /// Output path configuration
@@ -139,6 +1080,9 @@ pub struct PathsConfig {
     /// Checkpoint file output directory
     #[serde(default = "default_checkpoint_path")]
     pub checkpoint_path: PathBuf,
+    /// Encoded datasets reused across runs
+    #[serde(default = "default_cache_path")]
+    pub cache_path: PathBuf,
 }
 
 // Default value functions
@@ -170,6 +1114,18 @@ fn default_grad_clip() -> f64 {
     1.0
 }
 
+fn default_seed() -> u64 {
+    42
+}
+
+fn default_distillation_alpha() -> f64 {
+    0.5
+}
+
+fn default_distillation_temperature() -> f64 {
+    2.0
+}
+
 fn default_save_every() -> usize {
     10
 }
@@ -210,21 +1166,262 @@ fn default_checkpoint_path() -> PathBuf {
     PathBuf::from("checkpoints/")
 }
 
+fn default_cache_path() -> PathBuf {
+    PathBuf::from("cache/")
+}
+
 impl Config {
-    /// Load configuration from TOML file
+    /// Path of the config `name` in `dir`, with whichever supported
+    /// extension exists
+    pub fn find(dir: &Path, name: &str) -> crate::Result<PathBuf> {
+        ConfigFormat::EXTENSIONS
+            .iter()
+            .map(|ext| dir.join(format!("{}.{}", name, ext)))
+            .find(|path| path.is_file())
+            .ok_or_else(|| {
+                crate::Error::ConfigError(format!(
+                    "no config named '{}' in {}",
+                    name,
+                    dir.display()
+                ))
+            })
+    }
+
+    /// Load configuration from a TOML, JSON or YAML file (by extension),
+    /// rejecting it if [`validate`](Self::validate) fails
     pub fn from_file<P: AsRef<Path>>(path: P) -> crate::Result<Self> {
-        let content = std::fs::read_to_string(path)?;
-        let config: Config = toml::from_str(&content)?;
+        let config = Self::parse_file(path)?;
+        config.validate()?;
         Ok(config)
     }
 
-    /// Save configuration to TOML file
+    /// Load configuration from a TOML, JSON or YAML file without validating
+    /// the values
+    pub fn parse_file<P: AsRef<Path>>(path: P) -> crate::Result<Self> {
+        let table = Self::resolve_file(path)?;
+        Ok(toml::Value::Table(table).try_into()?)
+    }
+
+    /// A config file's values with the files it `extends` merged in
+    ///
+    /// Each file's values override those of its base; tables are merged key
+    /// by key, anything else is replaced whole. Bases may be in any format.
+    pub fn resolve_file<P: AsRef<Path>>(path: P) -> crate::Result<toml::Table> {
+        resolve_extends(path.as_ref(), &mut Vec::new())
+    }
+
+    /// Check values and their consistency, failing with every problem found
+    pub fn validate(&self) -> crate::Result<()> {
+        let errors = self.validation_errors();
+        if errors.is_empty() {
+            return Ok(());
+        }
+        Err(crate::Error::ConfigError(format!(
+            "invalid configuration:\n  - {}",
+            errors.join("\n  - ")
+        )))
+    }
+
+    /// Problems with the configuration, each prefixed with its field
+    pub fn validation_errors(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        let mut check = |ok: bool, field: &str, problem: String| {
+            if !ok {
+                errors.push(format!("{}: {}", field, problem));
+            }
+        };
+
+        check(
+            !self.task.name.trim().is_empty(),
+            "task.name",
+            "must not be empty".to_string(),
+        );
+
+        let model = &self.model;
+        check(
+            ["transformer", "lstm"].contains(&model.architecture.to_lowercase().as_str()),
+            "model.architecture",
+            format!(
+                "unknown architecture '{}', expected transformer or lstm",
+                model.architecture
+            ),
+        );
+        for (field, value) in [
+            ("model.d_model", model.d_model),
+            ("model.nhead", model.nhead),
+            ("model.num_layers", model.num_layers),
+            ("model.dim_feedforward", model.dim_feedforward),
+            ("model.max_seq_len", model.max_seq_len),
+            ("training.num_epochs", self.training.num_epochs),
+            ("training.batch_size", self.training.batch_size),
+            ("tokenizer.max_length", self.tokenizer.max_length),
+        ] {
+            check(value > 0, field, "must be positive".to_string());
+        }
+        check(
+            model.nhead == 0 || model.d_model.is_multiple_of(model.nhead),
+            "model.d_model",
+            format!(
+                "{} is not divisible by model.nhead = {}",
+                model.d_model, model.nhead
+            ),
+        );
+        check(
+            model.positional_encoding != PositionalEncoding::Rope
+                || model.nhead == 0
+                || (model.d_model / model.nhead).is_multiple_of(2),
+            "model.positional_encoding",
+            format!(
+                "rope needs an even head size, d_model / nhead = {}",
+                model.d_model / model.nhead.max(1)
+            ),
+        );
+        for token in &self.tokenizer.control_tokens {
+            if let Some(problem) = crate::tokenizer::control_token_problem(token) {
+                check(
+                    false,
+                    "tokenizer.control_tokens",
+                    format!("'{}': {}", token, problem),
+                );
+            }
+        }
+        check(
+            (0.0..1.0).contains(&model.dropout),
+            "model.dropout",
+            format!("{} is outside [0, 1)", model.dropout),
+        );
+
+        let training = &self.training;
+        if let Some(curriculum) = &training.curriculum {
+            check(
+                curriculum.epochs > 0,
+                "training.curriculum.epochs",
+                "must be positive".to_string(),
+            );
+            check(
+                curriculum.start_fraction > 0.0 && curriculum.start_fraction <= 1.0,
+                "training.curriculum.start_fraction",
+                format!("{} is outside (0, 1]", curriculum.start_fraction),
+            );
+            check(
+                curriculum.by != CurriculumMetric::Tags || !curriculum.tags.is_empty(),
+                "training.curriculum.tags",
+                "must list tags from easiest to hardest when by = \"tags\"".to_string(),
+            );
+        }
+        if let Some(distillation) = &training.distillation {
+            check(
+                (0.0..=1.0).contains(&distillation.alpha),
+                "training.distillation.alpha",
+                format!("{} is outside [0, 1]", distillation.alpha),
+            );
+            check(
+                distillation.temperature.is_finite() && distillation.temperature > 0.0,
+                "training.distillation.temperature",
+                format!("{} is not a positive number", distillation.temperature),
+            );
+        }
+        if let Some(preview) = &training.preview {
+            check(
+                !preview.prompts.is_empty(),
+                "training.preview.prompts",
+                "must list at least one prompt".to_string(),
+            );
+            check(
+                preview.every > 0,
+                "training.preview.every",
+                "must be positive".to_string(),
+            );
+        }
+        if let Some(decay) = training.ema_decay {
+            check(
+                decay > 0.0 && decay < 1.0,
+                "training.ema_decay",
+                format!("{} is outside (0, 1)", decay),
+            );
+        }
+        check(
+            training.learning_rate.is_finite() && training.learning_rate > 0.0,
+            "training.learning_rate",
+            format!("{} is not a positive number", training.learning_rate),
+        );
+        if let Err(error) = training.optimizer.parse::<crate::training::OptimizerKind>() {
+            check(false, "training.optimizer", error.to_string());
+        }
+        check(
+            training.gradient_clip_norm.is_finite() && training.gradient_clip_norm >= 0.0,
+            "training.gradient_clip_norm",
+            format!(
+                "{} is not a non-negative number",
+                training.gradient_clip_norm
+            ),
+        );
+
+        let dataset = &self.dataset;
+        check(
+            dataset.train_ratio > 0.0 && dataset.train_ratio <= 1.0,
+            "dataset.train_ratio",
+            format!("{} is outside (0, 1]", dataset.train_ratio),
+        );
+        check(
+            (0.0..1.0).contains(&dataset.val_ratio),
+            "dataset.val_ratio",
+            format!("{} is outside [0, 1)", dataset.val_ratio),
+        );
+        check(
+            dataset.train_ratio + dataset.val_ratio <= 1.0 + f32::EPSILON,
+            "dataset.val_ratio",
+            format!(
+                "train_ratio + val_ratio = {} exceeds 1",
+                dataset.train_ratio + dataset.val_ratio
+            ),
+        );
+        check(
+            dataset.max_tokens != Some(0),
+            "dataset.max_tokens",
+            "must be positive".to_string(),
+        );
+
+        if let Err(error) = self.prompt_template() {
+            check(false, "prompt.template", error.to_string());
+        }
+        if let Err(error) = crate::wgsl::ValidationProfile::from_config(&self.validation) {
+            check(false, "validation", error.to_string());
+        }
+        errors.extend(
+            self.tokenizer
+                .patterns
+                .validation_errors("tokenizer.patterns"),
+        );
+        errors.extend(self.generation.validation_errors());
+        errors.extend(self.device.validation_errors());
+        errors
+    }
+
+    /// Weight initialization seed: `model.init.seed`, else `model.seed`,
+    /// else `training.seed`
+    pub fn init_seed(&self) -> u64 {
+        self.model
+            .init
+            .seed
+            .or(self.model.seed)
+            .unwrap_or(self.training.seed)
+    }
+
+    /// Save configuration as TOML, JSON or YAML based on the file extension
     pub fn to_file<P: AsRef<Path>>(&self, path: P) -> crate::Result<()> {
-        let content = toml::to_string_pretty(self)?;
+        let path = path.as_ref();
+        let content = ConfigFormat::from_path(path).render(self)?;
         std::fs::write(path, content)?;
         Ok(())
     }
 
+    /// SHA-256 of the configuration's TOML form, as lowercase hex
+    pub fn content_hash(&self) -> crate::Result<String> {
+        let toml = toml::to_string(self)?;
+        Ok(format!("{:x}", Sha256::digest(toml.as_bytes())))
+    }
+
     /// Create a default configuration for WGSL generation
     pub fn default_wgsl_generation() -> Self {
         Config {
@@ -241,6 +1438,14 @@ impl Config {
                 dim_feedforward: 2048,
                 dropout: 0.1,
                 max_seq_len: 512,
+                activation: Activation::Relu,
+                norm_placement: NormPlacement::Post,
+                positional_encoding: PositionalEncoding::Sinusoidal,
+                embedding_dtype: Dtype::F32,
+                seed: None,
+                init: InitConfig::default(),
+                attention: AttentionConfig::default(),
+                pretrained: None,
             },
             training: TrainingConfig {
                 num_epochs: 100,
@@ -251,12 +1456,23 @@ impl Config {
                 early_stopping_patience: 15,
                 gradient_clip_norm: 1.0,
                 save_every: 10,
+                metrics: None,
+                seed: default_seed(),
+                curriculum: None,
+                layer_stats_every: 0,
+                ema_decay: None,
+                freeze: None,
+                distillation: None,
+                preview: None,
             },
             tokenizer: TokenizerConfig {
                 tokenizer_type: "wgsl".to_string(),
                 max_length: 512,
                 lowercase: false,
                 min_freq: 1,
+                control_tokens: Vec::new(),
+                normalize: None,
+                patterns: Default::default(),
             },
             dataset: DatasetConfig {
                 train_path: PathBuf::from("config/wgsl_training_data.toml"),
@@ -264,7 +1480,148 @@ impl Config {
                 test_path: None,
                 train_ratio: 0.8,
                 val_ratio: 0.1,
+                max_tokens: None,
+                length_policy: LengthPolicy::Truncate,
             },
+            validation: ValidationConfig::default(),
+            lint: LintConfig::default(),
+            tracking: TrackingConfig::default(),
+            prompt: PromptConfig::default(),
+            generation: GenerationConfig::default(),
+            device: DeviceConfig::default(),
+        }
+    }
+
+    /// Curated model size and training schedule for a dataset scale:
+    ///
+    /// - `tiny`: 2 × 64-d layers for a few hundred examples
+    /// - `small`: 3 × 128-d layers for a few thousand
+    /// - `base`: 4 × 256-d layers for tens of thousands
+    ///
+    /// Smaller models train faster and overfit small corpora less than the
+    /// 512-d [`default_wgsl_generation`](Self::default_wgsl_generation).
+    pub fn preset(name: &str) -> crate::Result<Self> {
+        let preset = match name.to_lowercase().as_str() {
+            "tiny" => Preset {
+                d_model: 64,
+                nhead: 4,
+                num_layers: 2,
+                dim_feedforward: 256,
+                learning_rate: 1e-3,
+                num_epochs: 50,
+                batch_size: 8,
+                patience: 10,
+            },
+            "small" => Preset {
+                d_model: 128,
+                nhead: 4,
+                num_layers: 3,
+                dim_feedforward: 512,
+                learning_rate: 5e-4,
+                num_epochs: 100,
+                batch_size: 16,
+                patience: 15,
+            },
+            "base" => Preset {
+                d_model: 256,
+                nhead: 8,
+                num_layers: 4,
+                dim_feedforward: 1024,
+                learning_rate: 3e-4,
+                num_epochs: 150,
+                batch_size: 32,
+                patience: 20,
+            },
+            other => {
+                return Err(crate::Error::ConfigError(format!(
+                    "Unknown preset '{}'. Must be one of: {}",
+                    other,
+                    PRESETS.join(", ")
+                )))
+            }
+        };
+        let mut config = Self::default_wgsl_generation();
+        config.model.d_model = preset.d_model;
+        config.model.nhead = preset.nhead;
+        config.model.num_layers = preset.num_layers;
+        config.model.dim_feedforward = preset.dim_feedforward;
+        config.training.learning_rate = preset.learning_rate;
+        config.training.num_epochs = preset.num_epochs;
+        config.training.batch_size = preset.batch_size;
+        config.training.early_stopping_patience = preset.patience;
+        Ok(config)
+    }
+
+    /// The `[prompt]` template, checked for a `{description}` placeholder
+    pub fn prompt_template(&self) -> crate::Result<Option<crate::inference::PromptTemplate>> {
+        self.prompt
+            .template
+            .clone()
+            .map(crate::inference::PromptTemplate::new)
+            .transpose()
+    }
+
+    /// Token limit applied to dataset examples
+    pub fn max_example_tokens(&self) -> usize {
+        self.dataset.max_tokens.unwrap_or(self.model.max_seq_len)
+    }
+}
+
+/// `path` resolved against its bases; `chain` holds the files extending
+/// it, to detect cycles
+fn resolve_extends(path: &Path, chain: &mut Vec<PathBuf>) -> crate::Result<toml::Table> {
+    let content = std::fs::read_to_string(path)?;
+    let canonical = path.canonicalize()?;
+    if chain.contains(&canonical) {
+        let cycle: Vec<String> = chain
+            .iter()
+            .chain([&canonical])
+            .map(|file| file.display().to_string())
+            .collect();
+        return Err(crate::Error::ConfigError(format!(
+            "config extends itself: {}",
+            cycle.join(" -> ")
+        )));
+    }
+    let mut table = ConfigFormat::from_path(path).parse(&content)?;
+    let base = match table.remove(EXTENDS_KEY) {
+        None => return Ok(table),
+        Some(toml::Value::String(base)) => base,
+        Some(other) => {
+            return Err(crate::Error::ConfigError(format!(
+                "{} in {} must be a path, found {}",
+                EXTENDS_KEY,
+                path.display(),
+                other.type_str()
+            )))
+        }
+    };
+    let base_path = path.parent().unwrap_or(Path::new("")).join(&base);
+    if !base_path.exists() {
+        return Err(crate::Error::ConfigError(format!(
+            "{} extends {}, which does not exist",
+            path.display(),
+            base_path.display()
+        )));
+    }
+
+    chain.push(canonical);
+    let mut merged = resolve_extends(&base_path, chain)?;
+    chain.pop();
+    merge_tables(&mut merged, table);
+    Ok(merged)
+}
+
+/// Override `base` with `overrides`, merging nested tables
+fn merge_tables(base: &mut toml::Table, overrides: toml::Table) {
+    for (key, value) in overrides {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base)), toml::Value::Table(value)) => {
+                merge_tables(base, value)
+            }
+            (_, value) => {
+                base.insert(key, value);
+            }
         }
     }
 }
@@ -273,20 +1630,29 @@ impl EngineConfig {
     /// Load engine configuration from TOML file
     pub fn from_file<P: AsRef<Path>>(path: P) -> crate::Result<Self> {
         let path_ref = path.as_ref();
-        if !path_ref.exists() {
-            return Err(crate::Error::ConfigError(format!(
+        Self::from_file_if_present(path_ref)?.ok_or_else(|| {
+            crate::Error::ConfigError(format!(
                 "Engine configuration file not found: {}",
                 path_ref.display()
-            )));
-        }
+            ))
+        })
+    }
 
-        let content = std::fs::read_to_string(path_ref)?;
+    /// Load engine configuration from a TOML file, or `None` when the file
+    /// does not exist; a file that exists but fails to read, parse or
+    /// validate is an error
+    pub fn from_file_if_present<P: AsRef<Path>>(path: P) -> crate::Result<Option<Self>> {
+        let content = match std::fs::read_to_string(path.as_ref()) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
         let config: EngineConfig = toml::from_str(&content)?;
 
         // Validate log level
         config.validate()?;
 
-        Ok(config)
+        Ok(Some(config))
     }
 
     /// Save engine configuration to TOML file
@@ -296,15 +1662,6 @@ impl EngineConfig {
         Ok(())
     }
 
-    /// Create a default engine configuration
-    pub fn default() -> Self {
-        EngineConfig {
-            log_level: "INFO".to_string(),
-            disable_debug_assertions: false,
-            paths: PathsConfig::default(),
-        }
-    }
-
     /// Validate configuration values
     pub fn validate(&self) -> crate::Result<()> {
         // Validate log level
@@ -332,6 +1689,16 @@ impl EngineConfig {
                 "checkpoint_path cannot be empty".to_string()
             ));
         }
+        if self.paths.cache_path.as_os_str().is_empty() {
+            return Err(crate::Error::ConfigError(
+                "cache_path cannot be empty".to_string(),
+            ));
+        }
+        if self.logging.max_files == 0 || self.logging.max_size_mb == 0 {
+            return Err(crate::Error::ConfigError(
+                "logging.max_files and logging.max_size_mb must be positive".to_string(),
+            ));
+        }
 
         Ok(())
     }
@@ -371,17 +1738,33 @@ impl EngineConfig {
         std::fs::create_dir_all(&self.paths.log_path)?;
         std::fs::create_dir_all(&self.paths.journal_path)?;
         std::fs::create_dir_all(&self.paths.checkpoint_path)?;
+        std::fs::create_dir_all(&self.paths.cache_path)?;
         Ok(())
     }
 }
 
-impl PathsConfig {
+impl Default for EngineConfig {
+    /// Create a default engine configuration
+    fn default() -> Self {
+        EngineConfig {
+            log_level: "INFO".to_string(),
+            disable_debug_assertions: false,
+            paths: PathsConfig::default(),
+            requirements: RequirementsConfig::default(),
+            logging: LoggingConfig::default(),
+            hub: HubConfig::default(),
+        }
+    }
+}
+
+impl Default for PathsConfig {
     /// Create a default paths configuration
-    pub fn default() -> Self {
+    fn default() -> Self {
         PathsConfig {
             log_path: PathBuf::from("logs/"),
             journal_path: PathBuf::from("journals/"),
             checkpoint_path: PathBuf::from("checkpoints/"),
+            cache_path: PathBuf::from("cache/"),
         }
     }
 }
@@ -405,4 +1788,410 @@ mod tests {
         let deserialized: Config = toml::from_str(&toml_str).unwrap();
         assert_eq!(config.task.name, deserialized.task.name);
     }
+
+    #[test]
+    fn test_content_hash() {
+        let config = Config::default_wgsl_generation();
+        let hash = config.content_hash().unwrap();
+        assert_eq!(hash, config.clone().content_hash().unwrap());
+
+        let mut changed = config;
+        changed.training.learning_rate *= 2.0;
+        assert_ne!(changed.content_hash().unwrap(), hash);
+    }
+
+    #[test]
+    fn test_length_policy_defaults() {
+        let dataset: DatasetConfig = toml::from_str(r#"train_path = "data.toml""#).unwrap();
+        assert_eq!(dataset.length_policy, LengthPolicy::Truncate);
+        assert_eq!(dataset.max_tokens, None);
+
+        let dataset: DatasetConfig =
+            toml::from_str("train_path = \"data.toml\"\nlength_policy = \"drop\"").unwrap();
+        assert_eq!(dataset.length_policy, LengthPolicy::Drop);
+    }
+
+    #[test]
+    fn test_model_architecture_options() {
+        let model = toml::to_string(&Config::default_wgsl_generation().model).unwrap();
+        let sizes: String = model
+            .lines()
+            .filter(|line| {
+                ![
+                    "activation",
+                    "norm_placement",
+                    "positional_encoding",
+                    "embedding_dtype",
+                ]
+                .iter()
+                .any(|option| line.starts_with(option))
+            })
+            .map(|line| format!("{}\n", line))
+            .collect();
+
+        let defaults: ModelConfig = toml::from_str(&sizes).unwrap();
+        assert_eq!(defaults.activation, Activation::Relu);
+        assert_eq!(defaults.norm_placement, NormPlacement::Post);
+        assert_eq!(defaults.positional_encoding, PositionalEncoding::Sinusoidal);
+        assert_eq!(defaults.embedding_dtype, Dtype::F32);
+
+        assert_eq!(defaults.init, InitConfig::default());
+        assert_eq!(defaults.init.scheme, InitScheme::Xavier);
+
+        let options: ModelConfig = toml::from_str(&format!(
+            "activation = \"swiglu\"\nnorm_placement = \"pre\"\npositional_encoding = \"rope\"\n\
+             embedding_dtype = \"f16\"\n{}",
+            sizes
+        ))
+        .unwrap();
+        assert_eq!(options.activation, Activation::Swiglu);
+        assert_eq!(options.norm_placement, NormPlacement::Pre);
+        assert_eq!(options.positional_encoding, PositionalEncoding::Rope);
+        assert_eq!(options.embedding_dtype, Dtype::F16);
+
+        assert_eq!(defaults.attention.to_string(), "full");
+        let blocked: ModelConfig = toml::from_str(
+            &sizes
+                .replace("block_size = 0", "block_size = 64")
+                .replace("window = 0", "window = 128"),
+        )
+        .unwrap();
+        assert_eq!(
+            blocked.attention,
+            AttentionConfig {
+                block_size: 64,
+                window: 128
+            }
+        );
+        assert_eq!(blocked.attention.to_string(), "blocks of 64, window 128");
+
+        let mut config = Config::default_wgsl_generation();
+        config.model.init = toml::from_str("scheme = \"he\"\nseed = 7").unwrap();
+        assert_eq!(config.model.init.scheme, InitScheme::He);
+        assert_eq!(config.init_seed(), 7);
+        let reloaded: Config = toml::from_str(&toml::to_string(&config).unwrap()).unwrap();
+        assert_eq!(reloaded.model.init, config.model.init);
+    }
+
+    #[test]
+    fn test_prompt_template() {
+        let mut config = Config::default_wgsl_generation();
+        assert!(config.prompt_template().unwrap().is_none());
+
+        config.prompt = toml::from_str("template = \"{stage}: {description}\"").unwrap();
+        let template = config.prompt_template().unwrap().unwrap();
+        assert_eq!(template.as_str(), "{stage}: {description}");
+
+        config.prompt.template = Some("{stage} only".to_string());
+        assert!(config.prompt_template().is_err());
+    }
+
+    #[test]
+    fn test_config_validate() {
+        assert!(Config::default_wgsl_generation().validate().is_ok());
+        assert!(Config::from_file("config/wgsl_generation.toml").is_ok());
+
+        let mut config = Config::default_wgsl_generation();
+        config.model.d_model = 100;
+        config.model.nhead = 8;
+        config.dataset.train_ratio = 0.8;
+        config.dataset.val_ratio = 0.3;
+        config.training.optimizer = "rmsprop".to_string();
+        let errors = config.validation_errors();
+        assert_eq!(errors.len(), 3, "{:?}", errors);
+        assert!(errors[0].starts_with("model.d_model: 100 is not divisible"));
+        assert!(errors[1].starts_with("training.optimizer: "));
+        assert!(errors[2].starts_with("dataset.val_ratio: train_ratio + val_ratio"));
+
+        let message = config.validate().unwrap_err().to_string();
+        assert!(message.contains("model.d_model") && message.contains("training.optimizer"));
+
+        config = Config::default_wgsl_generation();
+        config.model.nhead = 0;
+        config.model.positional_encoding = PositionalEncoding::Rope;
+        assert_eq!(
+            config.validation_errors(),
+            vec!["model.nhead: must be positive"]
+        );
+    }
+
+    #[test]
+    fn test_presets() {
+        let presets: Vec<Config> = PRESETS
+            .iter()
+            .map(|name| Config::preset(name).unwrap())
+            .collect();
+        for config in &presets {
+            assert!(config.validate().is_ok());
+        }
+        let sizes: Vec<_> = presets
+            .iter()
+            .map(|config| config.model.d_model * config.model.num_layers)
+            .collect();
+        assert!(sizes.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(Config::preset("Small").unwrap().model.d_model, 128);
+        assert!(Config::preset("huge").is_err());
+    }
+
+    #[test]
+    fn test_config_extends() {
+        let dir = tempfile::tempdir().unwrap();
+        Config::default_wgsl_generation()
+            .to_file(dir.path().join("base.toml"))
+            .unwrap();
+        std::fs::create_dir(dir.path().join("tasks")).unwrap();
+        let child = dir.path().join("tasks/child.toml");
+        std::fs::write(
+            &child,
+            "extends = \"../base.toml\"\n[model]\nd_model = 256\n[training]\nnum_epochs = 3",
+        )
+        .unwrap();
+        let grandchild = dir.path().join("grandchild.toml");
+        std::fs::write(
+            &grandchild,
+            "extends = \"tasks/child.toml\"\n[task]\nname = \"override\"",
+        )
+        .unwrap();
+
+        let config = Config::from_file(&grandchild).unwrap();
+        assert_eq!(config.task.name, "override");
+        assert_eq!(config.task.task_type, "code_generation");
+        assert_eq!(config.model.d_model, 256);
+        assert_eq!(config.model.nhead, 8);
+        assert_eq!(config.training.num_epochs, 3);
+        assert!(!Config::resolve_file(&grandchild)
+            .unwrap()
+            .contains_key(EXTENDS_KEY));
+
+        let a = dir.path().join("a.toml");
+        std::fs::write(&a, "extends = \"b.toml\"").unwrap();
+        std::fs::write(dir.path().join("b.toml"), "extends = \"a.toml\"").unwrap();
+        let error = Config::from_file(&a).unwrap_err().to_string();
+        assert!(error.contains("extends itself"), "{}", error);
+
+        std::fs::write(&a, "extends = \"missing.toml\"").unwrap();
+        assert!(Config::from_file(&a).is_err());
+    }
+
+    #[test]
+    fn test_config_formats() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = Config::preset("tiny").unwrap();
+        config.prompt.template = Some("{stage}: {description}".to_string());
+        let expected = toml::to_string(&config).unwrap();
+        for name in ["config.toml", "config.json", "config.yaml", "config.yml"] {
+            let path = dir.path().join(name);
+            config.to_file(&path).unwrap();
+            let loaded = Config::from_file(&path).unwrap();
+            assert_eq!(toml::to_string(&loaded).unwrap(), expected, "{}", name);
+        }
+
+        // Names resolve to any supported extension
+        let yaml_dir = tempfile::tempdir().unwrap();
+        config.to_file(yaml_dir.path().join("shaders.yml")).unwrap();
+        assert_eq!(
+            Config::find(yaml_dir.path(), "shaders").unwrap(),
+            yaml_dir.path().join("shaders.yml")
+        );
+        assert_eq!(
+            Config::find(dir.path(), "config").unwrap(),
+            dir.path().join("config.toml")
+        );
+        assert!(Config::find(dir.path(), "missing").is_err());
+
+        let child = dir.path().join("child.yaml");
+        std::fs::write(
+            &child,
+            "extends: config.json  # any format\nmodel:\n  d_model: 32\n  seed: null\n",
+        )
+        .unwrap();
+        let loaded = Config::from_file(&child).unwrap();
+        assert_eq!(loaded.model.d_model, 32);
+        assert_eq!(loaded.model.nhead, config.model.nhead);
+        assert_eq!(loaded.task.name, config.task.name);
+
+        // Anchors, aliases and merge keys resolve; several documents don't
+        let table = ConfigFormat::Yaml
+            .parse("base: &base\n  d_model: 32\n  nhead: 4\nmodel:\n  <<: *base\n  nhead: !!int 8\n")
+            .unwrap();
+        assert_eq!(table["model"]["d_model"].as_integer(), Some(32));
+        assert_eq!(table["model"]["nhead"].as_integer(), Some(8));
+        assert!(ConfigFormat::Yaml.parse("a: 1\n---\nb: 2\n").is_err());
+        assert!(ConfigFormat::Yaml.parse("").unwrap().is_empty());
+
+        assert_eq!("YML".parse::<ConfigFormat>().unwrap(), ConfigFormat::Yaml);
+        assert!("ini".parse::<ConfigFormat>().is_err());
+        assert!(ConfigFormat::Json.parse("[1, 2]").is_err());
+    }
+
+    #[test]
+    fn test_engine_config_if_present() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("engine.toml");
+        assert!(EngineConfig::from_file_if_present(&path).unwrap().is_none());
+        assert!(EngineConfig::from_file(&path).is_err());
+
+        EngineConfig::default().to_file(&path).unwrap();
+        assert!(EngineConfig::from_file_if_present(&path).unwrap().is_some());
+
+        // A malformed file is an error, not a silent fallback to defaults
+        std::fs::write(&path, "[requirements]\ngpu = true\n[requirements]\n").unwrap();
+        assert!(EngineConfig::from_file_if_present(&path).is_err());
+        std::fs::write(&path, "log_level = \"INFO\"\n").unwrap();
+        assert!(EngineConfig::from_file_if_present(&path).is_err());
+    }
+
+    #[test]
+    fn test_generation_config() {
+        let config: Config =
+            toml::from_str(&toml::to_string(&Config::default_wgsl_generation()).unwrap()).unwrap();
+        assert_eq!(config.generation, GenerationConfig::default());
+
+        let generation: GenerationConfig =
+            toml::from_str("temperature = 0.8\ntop_p = 0.9\nmax_new_tokens = 64").unwrap();
+        assert_eq!(generation.beam_width, 1);
+        let options = generation.options(Some(5));
+        assert_eq!(
+            (options.temperature, options.top_p, options.seed),
+            (0.8, 0.9, Some(5))
+        );
+        assert_eq!(options.max_new_tokens, Some(64));
+
+        let mut config = Config::default_wgsl_generation();
+        config.generation.top_p = 0.0;
+        config.generation.beam_width = 0;
+        assert_eq!(config.validation_errors().len(), 2);
+    }
+
+    #[test]
+    fn test_curriculum_config() {
+        let training: TrainingConfig = toml::from_str(
+            "num_epochs = 10\nbatch_size = 4\nlearning_rate = 0.001\n\
+             [curriculum]\nby = \"tags\"\ntags = [\"snippet\", \"loop\"]",
+        )
+        .unwrap();
+        let curriculum = training.curriculum.unwrap();
+        assert_eq!(curriculum.by, CurriculumMetric::Tags);
+        assert_eq!((curriculum.epochs, curriculum.start_fraction), (5, 0.25));
+
+        let mut config = Config::default_wgsl_generation();
+        config.training.curriculum = Some(CurriculumConfig {
+            by: CurriculumMetric::Tags,
+            start_fraction: 0.0,
+            ..CurriculumConfig::default()
+        });
+        let errors = config.validation_errors();
+        assert_eq!(errors.len(), 2, "{:?}", errors);
+        assert!(errors[0].starts_with("training.curriculum.start_fraction"));
+        assert!(errors[1].starts_with("training.curriculum.tags"));
+    }
+
+    #[test]
+    fn test_preview_config() {
+        let training: TrainingConfig = toml::from_str(
+            "num_epochs = 10\nbatch_size = 4\nlearning_rate = 0.001\n\
+             [preview]\nprompts = [\"mix colors\"]",
+        )
+        .unwrap();
+        let preview = training.preview.unwrap();
+        assert_eq!((preview.prompts.len(), preview.every), (1, 1));
+
+        let mut config = Config::default_wgsl_generation();
+        config.training.preview = Some(PreviewConfig {
+            prompts: Vec::new(),
+            every: 0,
+        });
+        let errors = config.validation_errors();
+        assert_eq!(errors.len(), 2, "{:?}", errors);
+        assert!(errors[0].starts_with("training.preview.prompts"));
+        assert!(errors[1].starts_with("training.preview.every"));
+    }
+
+    #[test]
+    fn test_freeze_config() {
+        let training: TrainingConfig = toml::from_str(
+            "num_epochs = 10\nbatch_size = 4\nlearning_rate = 0.001\n\
+             [freeze]\nembeddings = true\nlayers = 1",
+        )
+        .unwrap();
+        let freeze = training.freeze.unwrap();
+        assert!(freeze.freezes("token_embedding"));
+        assert!(freeze.freezes("encoder.0.self_attn.w_q"));
+        assert!(freeze.freezes("decoder.0.norm1.gamma"));
+        assert!(!freeze.freezes("decoder.1.feedforward.linear1.weight"));
+        assert!(!freeze.freezes("encoder_norm.gamma"));
+        assert!(!freeze.freezes("output.weight"));
+
+        let encoder = FreezeConfig {
+            encoder: true,
+            ..FreezeConfig::default()
+        };
+        assert!(encoder.freezes("encoder.3.norm2.beta"));
+        assert!(encoder.freezes("encoder_norm.gamma"));
+        assert!(!encoder.freezes("token_embedding"));
+        assert!(!encoder.freezes("decoder.0.cross_attn.w_k"));
+    }
+
+    #[test]
+    fn test_distillation_config() {
+        let training: TrainingConfig = toml::from_str(
+            "num_epochs = 10\nbatch_size = 4\nlearning_rate = 0.001\n\
+             [distillation]\nteacher = \"teacher.ckpt\"",
+        )
+        .unwrap();
+        let distillation = training.distillation.unwrap();
+        assert_eq!(distillation.teacher, PathBuf::from("teacher.ckpt"));
+        assert_eq!((distillation.alpha, distillation.temperature), (0.5, 2.0));
+
+        let mut config = Config::default_wgsl_generation();
+        config.training.distillation = Some(DistillationConfig {
+            alpha: 1.5,
+            temperature: 0.0,
+            ..distillation
+        });
+        let errors = config.validation_errors();
+        assert_eq!(errors.len(), 2, "{:?}", errors);
+        assert!(errors[0].starts_with("training.distillation.alpha"));
+        assert!(errors[1].starts_with("training.distillation.temperature"));
+    }
+
+    #[test]
+    fn test_validation_config() {
+        let validation: ValidationConfig = toml::from_str("").unwrap();
+        assert_eq!(validation.profile, ValidationProfileKind::NativeExtended);
+        assert!(validation.limits.is_none());
+
+        let validation: ValidationConfig = toml::from_str(
+            "profile = \"custom\"\ncapabilities = [\"float64\"]\n[limits]\nmax_bind_groups = 2",
+        )
+        .unwrap();
+        assert_eq!(validation.profile, ValidationProfileKind::Custom);
+        assert_eq!(validation.capabilities, vec!["float64"]);
+
+        let limits = validation.limits.unwrap();
+        assert_eq!(limits.max_bind_groups, 2);
+        assert_eq!(limits.max_invocations_per_workgroup, 256);
+    }
+
+    #[test]
+    fn test_lint_config() {
+        let lint: LintConfig = toml::from_str("").unwrap();
+        assert_eq!(lint.level(LintRule::UnusedBinding), LintLevel::Warn);
+        assert_eq!(lint.level(LintRule::MagicWorkgroupSize), LintLevel::Allow);
+
+        let lint: LintConfig =
+            toml::from_str("unused-variable = \"deny\"\nnon-uniform-texture-sample = \"allow\"")
+                .unwrap();
+        assert_eq!(lint.level(LintRule::UnusedVariable), LintLevel::Deny);
+        assert_eq!(
+            lint.level(LintRule::NonUniformTextureSample),
+            LintLevel::Allow
+        );
+
+        assert!(toml::from_str::<LintConfig>("unused-variable = \"error\"").is_err());
+
+        let config: Config =
+            toml::from_str(&toml::to_string(&Config::default_wgsl_generation()).unwrap()).unwrap();
+        assert!(config.lint.levels.is_empty());
+    }
 }
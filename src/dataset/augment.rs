@@ -0,0 +1,508 @@
+//! Data augmentation for WGSL training examples
+//!
+//! Produces extra examples from an existing corpus by systematically renaming
+//! local identifiers, shuffling module-level function order and perturbing
+//! floating point literals. Variants of examples that validate are themselves
+//! re-validated with naga and dropped if the transformation broke them.
+
+use super::{WGSLDataset, WGSLExample};
+use crate::wgsl::WGSLValidator;
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/// Replacement names used when renaming local identifiers
+const RENAME_POOL: &[&str] = &[
+    "value", "tmp", "res", "acc", "item", "elem", "cur", "val", "src", "dst", "lhs", "rhs",
+    "sample", "factor", "base", "offset",
+];
+
+/// Augmentation settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AugmentationConfig {
+    /// Number of variants to attempt per example
+    #[serde(default = "default_variants")]
+    pub variants_per_example: usize,
+    /// Rename `let`/`var` bindings and function parameters
+    #[serde(default = "default_true")]
+    pub rename_identifiers: bool,
+    /// Shuffle the order of module-level functions
+    #[serde(default = "default_true")]
+    pub shuffle_functions: bool,
+    /// Perturb floating point literals
+    #[serde(default = "default_true")]
+    pub perturb_literals: bool,
+    /// Maximum relative change applied to a literal (0.05 = ±5%)
+    #[serde(default = "default_literal_jitter")]
+    pub literal_jitter: f32,
+    /// Random seed
+    #[serde(default = "default_seed")]
+    pub seed: u64,
+}
+
+fn default_variants() -> usize {
+    2
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_literal_jitter() -> f32 {
+    0.05
+}
+
+fn default_seed() -> u64 {
+    42
+}
+
+impl Default for AugmentationConfig {
+    fn default() -> Self {
+        Self {
+            variants_per_example: default_variants(),
+            rename_identifiers: true,
+            shuffle_functions: true,
+            perturb_literals: true,
+            literal_jitter: default_literal_jitter(),
+            seed: default_seed(),
+        }
+    }
+}
+
+/// Generates augmented variants of WGSL examples
+pub struct Augmenter {
+    config: AugmentationConfig,
+    rng: StdRng,
+}
+
+impl Augmenter {
+    /// Create a new augmenter
+    pub fn new(config: AugmentationConfig) -> Self {
+        let rng = StdRng::seed_from_u64(config.seed);
+        Self { config, rng }
+    }
+
+    /// Produce distinct variants of a single example (the original is not included)
+    pub fn augment_example(&mut self, example: &WGSLExample) -> Vec<WGSLExample> {
+        let mut seen = HashSet::new();
+        seen.insert(example.wgsl_code.clone());
+
+        let mut variants = Vec::new();
+        for _ in 0..self.config.variants_per_example {
+            let mut code = example.wgsl_code.clone();
+            if self.config.rename_identifiers {
+                code = rename_identifiers(&code, &mut self.rng);
+            }
+            if self.config.shuffle_functions {
+                code = shuffle_functions(&code, &mut self.rng);
+            }
+            if self.config.perturb_literals {
+                code = perturb_literals(&code, self.config.literal_jitter, &mut self.rng);
+            }
+
+            if seen.insert(code.clone()) {
+                variants.push(WGSLExample {
+                    wgsl_code: code,
+                    ..example.clone()
+                });
+            }
+        }
+
+        variants
+    }
+
+    /// Augment a whole dataset, returning the originals followed by their variants.
+    ///
+    /// Variants of examples that pass validation must pass validation too; snippets
+    /// that are not complete modules cannot be checked and are kept as-is.
+    pub fn augment(
+        &mut self,
+        dataset: &WGSLDataset,
+        validator: &WGSLValidator,
+    ) -> crate::Result<WGSLDataset> {
+        let mut examples =
+            Vec::with_capacity(dataset.len() * (1 + self.config.variants_per_example));
+
+        for example in &dataset.examples {
+            examples.push(example.clone());
+
+            let original_valid = validator.validate(&example.wgsl_code)?.is_valid;
+            for variant in self.augment_example(example) {
+                if original_valid && !validator.validate(&variant.wgsl_code)?.is_valid {
+                    tracing::debug!("Dropping invalid variant of '{}'", example.natural_language);
+                    continue;
+                }
+                examples.push(variant);
+            }
+        }
+
+        Ok(WGSLDataset { examples })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LexKind {
+    Identifier,
+    Number,
+    Comment,
+    Whitespace,
+    Symbol,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Lexeme {
+    kind: LexKind,
+    start: usize,
+    end: usize,
+}
+
+/// Minimal lexer that keeps byte spans so code can be rewritten in place
+fn lex(code: &str) -> Vec<Lexeme> {
+    let bytes = code.as_bytes();
+    let mut lexemes = Vec::new();
+    let mut pos = 0;
+
+    while pos < bytes.len() {
+        let start = pos;
+        let ch = bytes[pos];
+        let kind = if ch.is_ascii_whitespace() {
+            while pos < bytes.len() && bytes[pos].is_ascii_whitespace() {
+                pos += 1;
+            }
+            LexKind::Whitespace
+        } else if code[pos..].starts_with("//") {
+            while pos < bytes.len() && bytes[pos] != b'\n' {
+                pos += 1;
+            }
+            LexKind::Comment
+        } else if code[pos..].starts_with("/*") {
+            pos = code[pos + 2..]
+                .find("*/")
+                .map(|offset| pos + 2 + offset + 2)
+                .unwrap_or(bytes.len());
+            LexKind::Comment
+        } else if ch.is_ascii_alphabetic() || ch == b'_' {
+            while pos < bytes.len() && (bytes[pos].is_ascii_alphanumeric() || bytes[pos] == b'_') {
+                pos += 1;
+            }
+            LexKind::Identifier
+        } else if ch.is_ascii_digit()
+            || (ch == b'.' && bytes.get(pos + 1).is_some_and(u8::is_ascii_digit))
+        {
+            let is_hex = code[pos..].starts_with("0x") || code[pos..].starts_with("0X");
+            pos += 1;
+            while pos < bytes.len() {
+                let c = bytes[pos];
+                let after_exponent = !is_hex && matches!(bytes[pos - 1], b'e' | b'E');
+                if c.is_ascii_alphanumeric()
+                    || c == b'.'
+                    || (after_exponent && matches!(c, b'+' | b'-'))
+                {
+                    pos += 1;
+                } else {
+                    break;
+                }
+            }
+            LexKind::Number
+        } else {
+            // Advance one full character to stay on UTF-8 boundaries
+            pos += code[pos..].chars().next().map(char::len_utf8).unwrap_or(1);
+            LexKind::Symbol
+        };
+
+        lexemes.push(Lexeme {
+            kind,
+            start,
+            end: pos,
+        });
+    }
+
+    lexemes
+}
+
+/// Indices of lexemes that carry meaning (no whitespace or comments)
+fn significant(lexemes: &[Lexeme]) -> Vec<usize> {
+    lexemes
+        .iter()
+        .enumerate()
+        .filter(|(_, lexeme)| !matches!(lexeme.kind, LexKind::Whitespace | LexKind::Comment))
+        .map(|(index, _)| index)
+        .collect()
+}
+
+fn text<'a>(code: &'a str, lexeme: &Lexeme) -> &'a str {
+    &code[lexeme.start..lexeme.end]
+}
+
+/// Collect names introduced by `let`/`var` bindings and function parameters
+fn local_names(code: &str, lexemes: &[Lexeme]) -> Vec<String> {
+    let tokens = significant(lexemes);
+    let token_text = |i: usize| text(code, &lexemes[tokens[i]]);
+    let is_ident = |i: usize| lexemes[tokens[i]].kind == LexKind::Identifier;
+
+    let mut names = Vec::new();
+    let mut push = |name: &str| {
+        if !names.iter().any(|n: &String| n == name) {
+            names.push(name.to_string());
+        }
+    };
+
+    for i in 0..tokens.len() {
+        match token_text(i) {
+            // `var<storage, ...>` declares a module-level binding; leave those alone
+            "let" | "var" if i + 1 < tokens.len() && is_ident(i + 1) => push(token_text(i + 1)),
+            "fn" if i + 2 < tokens.len() && token_text(i + 2) == "(" => {
+                let mut depth = 0;
+                for j in i + 2..tokens.len() {
+                    match token_text(j) {
+                        "(" => depth += 1,
+                        ")" => {
+                            depth -= 1;
+                            if depth == 0 {
+                                break;
+                            }
+                        }
+                        ":" if depth == 1 && is_ident(j - 1) => push(token_text(j - 1)),
+                        _ => {}
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    names
+}
+
+/// Consistently rename local bindings and parameters to fresh names
+fn rename_identifiers(code: &str, rng: &mut StdRng) -> String {
+    let lexemes = lex(code);
+    let locals = local_names(code, &lexemes);
+    if locals.is_empty() {
+        return code.to_string();
+    }
+
+    let used: HashSet<&str> = lexemes
+        .iter()
+        .filter(|lexeme| lexeme.kind == LexKind::Identifier)
+        .map(|lexeme| text(code, lexeme))
+        .collect();
+
+    let mut pool: Vec<&str> = RENAME_POOL
+        .iter()
+        .copied()
+        .filter(|name| !used.contains(name))
+        .collect();
+    pool.shuffle(rng);
+
+    let mut mapping = HashMap::new();
+    for (index, name) in locals.iter().enumerate() {
+        let replacement = pool
+            .pop()
+            .map(str::to_string)
+            .unwrap_or_else(|| format!("{}_{}", name, index));
+        mapping.insert(name.as_str(), replacement);
+    }
+
+    let tokens = significant(&lexemes);
+    let mut output = String::with_capacity(code.len());
+    let mut last = 0;
+    let mut struct_depth: Option<usize> = None;
+    let mut depth = 0usize;
+
+    for (position, &index) in tokens.iter().enumerate() {
+        let lexeme = &lexemes[index];
+        let token = text(code, lexeme);
+        match token {
+            "{" => depth += 1,
+            "}" => {
+                depth = depth.saturating_sub(1);
+                if struct_depth == Some(depth) {
+                    struct_depth = None;
+                }
+            }
+            "struct" => struct_depth = Some(depth),
+            _ => {}
+        }
+
+        if lexeme.kind != LexKind::Identifier || struct_depth.is_some_and(|d| depth > d) {
+            continue;
+        }
+        let after_dot = position > 0 && text(code, &lexemes[tokens[position - 1]]) == ".";
+        if let (false, Some(replacement)) = (after_dot, mapping.get(token)) {
+            output.push_str(&code[last..lexeme.start]);
+            output.push_str(replacement);
+            last = lexeme.end;
+        }
+    }
+
+    output.push_str(&code[last..]);
+    output
+}
+
+/// Shuffle module-level function declarations, keeping other items in place
+fn shuffle_functions(code: &str, rng: &mut StdRng) -> String {
+    let lexemes = lex(code);
+
+    // Split the module into top-level items ending at `;` or a closing `}`
+    let mut items: Vec<(usize, usize, bool)> = Vec::new();
+    let mut item_start = 0;
+    let mut depth = 0usize;
+    let mut is_function = false;
+    for lexeme in &lexemes {
+        if lexeme.kind != LexKind::Symbol && lexeme.kind != LexKind::Identifier {
+            continue;
+        }
+        match text(code, lexeme) {
+            "fn" if depth == 0 => is_function = true,
+            "{" | "(" | "[" => depth += 1,
+            ")" | "]" => depth = depth.saturating_sub(1),
+            "}" => {
+                depth = depth.saturating_sub(1);
+                if depth == 0 {
+                    items.push((item_start, lexeme.end, is_function));
+                    item_start = lexeme.end;
+                    is_function = false;
+                }
+            }
+            ";" if depth == 0 => {
+                items.push((item_start, lexeme.end, is_function));
+                item_start = lexeme.end;
+                is_function = false;
+            }
+            _ => {}
+        }
+    }
+
+    let function_slots: Vec<usize> = items
+        .iter()
+        .enumerate()
+        .filter(|(_, item)| item.2)
+        .map(|(index, _)| index)
+        .collect();
+    if function_slots.len() < 2 {
+        return code.to_string();
+    }
+
+    let mut shuffled = function_slots.clone();
+    shuffled.shuffle(rng);
+
+    let mut order: Vec<usize> = (0..items.len()).collect();
+    for (slot, source) in function_slots.iter().zip(shuffled) {
+        order[*slot] = source;
+    }
+
+    let mut output = String::with_capacity(code.len());
+    for index in order {
+        let (start, end, _) = items[index];
+        output.push_str(&code[start..end]);
+    }
+    output.push_str(&code[item_start..]);
+    output
+}
+
+/// Multiply non-zero float literals by a random factor in `1 ± jitter`
+fn perturb_literals(code: &str, jitter: f32, rng: &mut StdRng) -> String {
+    if jitter <= 0.0 {
+        return code.to_string();
+    }
+
+    let mut output = String::with_capacity(code.len());
+    let mut last = 0;
+
+    for lexeme in lex(code).iter().filter(|l| l.kind == LexKind::Number) {
+        let literal = text(code, lexeme);
+        let (number, suffix) = match literal.strip_suffix('f') {
+            Some(number) => (number, "f"),
+            None => (literal, ""),
+        };
+        if !number.contains('.') || number.starts_with("0x") {
+            continue;
+        }
+        let value: f32 = match number.parse() {
+            Ok(value) if value != 0.0 => value,
+            _ => continue,
+        };
+
+        let decimals = number.split('.').nth(1).map(str::len).unwrap_or(0).max(2);
+        let perturbed = value * (1.0 + rng.gen_range(-jitter..=jitter));
+
+        output.push_str(&code[last..lexeme.start]);
+        output.push_str(&format!("{:.*}{}", decimals, perturbed, suffix));
+        last = lexeme.end;
+    }
+
+    output.push_str(&code[last..]);
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHADER: &str = r#"
+@group(0) @binding(0) var<storage, read_write> data: array<f32>;
+
+fn scale(input: f32) -> f32 {
+    let factor_a = 0.5;
+    return input * factor_a;
+}
+
+fn offset(input: f32) -> f32 {
+    return input + 1.25;
+}
+
+@compute @workgroup_size(64)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let index = id.x;
+    data[index] = offset(scale(data[index]));
+}
+"#;
+
+    #[test]
+    fn test_rename_identifiers_keeps_module_valid() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let renamed = rename_identifiers(SHADER, &mut rng);
+
+        assert_ne!(renamed, SHADER);
+        assert!(!renamed.contains("factor_a"));
+        assert!(WGSLValidator::new().validate(&renamed).unwrap().is_valid);
+    }
+
+    #[test]
+    fn test_shuffle_functions_preserves_items() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let shuffled = shuffle_functions(SHADER, &mut rng);
+
+        assert_eq!(shuffled.len(), SHADER.len());
+        assert!(shuffled.contains("fn scale"));
+        assert!(shuffled.contains("fn offset"));
+        assert!(WGSLValidator::new().validate(&shuffled).unwrap().is_valid);
+    }
+
+    #[test]
+    fn test_perturb_literals() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let perturbed = perturb_literals("vec4<f32>(1.0, 0.0, 0.5, 1.0)", 0.1, &mut rng);
+
+        assert_ne!(perturbed, "vec4<f32>(1.0, 0.0, 0.5, 1.0)");
+        assert!(perturbed.contains("0.0"));
+        assert!(perturbed.starts_with("vec4<f32>("));
+    }
+
+    #[test]
+    fn test_augment_dataset() {
+        let dataset = WGSLDataset {
+            examples: vec![WGSLExample::new("Scale and offset a buffer", SHADER)],
+        };
+
+        let mut augmenter = Augmenter::new(AugmentationConfig::default());
+        let validator = WGSLValidator::new();
+        let augmented = augmenter.augment(&dataset, &validator).unwrap();
+
+        assert!(augmented.len() > dataset.len());
+        for example in &augmented.examples {
+            assert!(validator.validate(&example.wgsl_code).unwrap().is_valid);
+        }
+    }
+}
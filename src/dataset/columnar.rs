@@ -0,0 +1,260 @@
+//! Parquet datasets (`arrow` feature)
+//!
+//! One row per example, with `natural_language` and `wgsl_code` string
+//! columns and the optional metadata as `tags` (a list of strings),
+//! `category`, `difficulty` and `source`. Reading also accepts the large and
+//! view string types that other writers produce, and ignores columns it
+//! doesn't know, so corpora exported from data pipelines load as they are.
+
+use super::{dataset_error, WGSLDataset, WGSLExample};
+use arrow_array::builder::{ListBuilder, StringBuilder};
+use arrow_array::cast::AsArray;
+use arrow_array::{Array, ArrayRef, RecordBatch, StringArray};
+use arrow_schema::{DataType, Field, Schema};
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::file::properties::WriterProperties;
+use std::fs::File;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Examples per record batch, and so per row group at most, when writing
+const BATCH_ROWS: usize = 8192;
+
+impl WGSLDataset {
+    /// Load a dataset from a Parquet file
+    pub fn from_parquet<P: AsRef<Path>>(path: P) -> crate::Result<Self> {
+        let path = path.as_ref();
+        let reader = ParquetRecordBatchReaderBuilder::try_new(File::open(path)?)
+            .and_then(|builder| builder.build())
+            .map_err(failed(path))?;
+
+        let mut examples = Vec::new();
+        for batch in reader {
+            let batch = batch.map_err(failed(path))?;
+            let rows = batch.num_rows();
+            let column = |name: &str, required: bool| match batch.column_by_name(name) {
+                Some(array) => strings(array).ok_or_else(|| {
+                    dataset_error(path, None, format!("column `{}` is not a string column", name))
+                }),
+                None if required => Err(dataset_error(
+                    path,
+                    None,
+                    format!("missing column `{}`", name),
+                )),
+                None => Ok(vec![None; rows]),
+            };
+            let natural_language = column("natural_language", true)?;
+            let wgsl_code = column("wgsl_code", true)?;
+            let category = column("category", false)?;
+            let difficulty = column("difficulty", false)?;
+            let source = column("source", false)?;
+            let tags = match batch.column_by_name("tags") {
+                Some(array) => string_lists(array).ok_or_else(|| {
+                    dataset_error(path, None, "column `tags` is not a list of strings")
+                })?,
+                None => vec![Vec::new(); rows],
+            };
+
+            for (row, tags) in tags.into_iter().enumerate() {
+                let line = Some(examples.len() + 1);
+                let (Some(natural_language), Some(wgsl_code)) =
+                    (natural_language[row].clone(), wgsl_code[row].clone())
+                else {
+                    return Err(dataset_error(
+                        path,
+                        line,
+                        "natural_language and wgsl_code must not be null",
+                    ));
+                };
+                examples.push(WGSLExample {
+                    natural_language,
+                    wgsl_code,
+                    tags,
+                    category: category[row].clone(),
+                    difficulty: difficulty[row]
+                        .as_deref()
+                        .map(str::parse)
+                        .transpose()
+                        .map_err(|e| dataset_error(path, line, e))?,
+                    source: source[row].clone(),
+                });
+            }
+        }
+        Ok(WGSLDataset { examples })
+    }
+
+    /// Save the dataset as a Snappy-compressed Parquet file
+    pub fn to_parquet<P: AsRef<Path>>(&self, path: P) -> crate::Result<()> {
+        let path = path.as_ref();
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("natural_language", DataType::Utf8, false),
+            Field::new("wgsl_code", DataType::Utf8, false),
+            Field::new(
+                "tags",
+                DataType::List(Arc::new(Field::new("item", DataType::Utf8, true))),
+                false,
+            ),
+            Field::new("category", DataType::Utf8, true),
+            Field::new("difficulty", DataType::Utf8, true),
+            Field::new("source", DataType::Utf8, true),
+        ]));
+        let properties = WriterProperties::builder()
+            .set_compression(Compression::SNAPPY)
+            .build();
+        let mut writer = ArrowWriter::try_new(File::create(path)?, schema.clone(), Some(properties))
+            .map_err(failed(path))?;
+
+        for chunk in self.examples.chunks(BATCH_ROWS) {
+            let mut tags = ListBuilder::new(StringBuilder::new());
+            for example in chunk {
+                tags.append_value(example.tags.iter().map(Some));
+            }
+            let columns: Vec<ArrayRef> = vec![
+                Arc::new(StringArray::from_iter_values(
+                    chunk.iter().map(|example| &example.natural_language),
+                )),
+                Arc::new(StringArray::from_iter_values(
+                    chunk.iter().map(|example| &example.wgsl_code),
+                )),
+                Arc::new(tags.finish()),
+                Arc::new(StringArray::from_iter(
+                    chunk.iter().map(|example| example.category.as_deref()),
+                )),
+                Arc::new(StringArray::from_iter(
+                    chunk
+                        .iter()
+                        .map(|example| example.difficulty.map(|d| d.as_str())),
+                )),
+                Arc::new(StringArray::from_iter(
+                    chunk.iter().map(|example| example.source.as_deref()),
+                )),
+            ];
+            let batch = RecordBatch::try_new(schema.clone(), columns).map_err(failed(path))?;
+            writer.write(&batch).map_err(failed(path))?;
+        }
+        writer.close().map_err(failed(path))?;
+        Ok(())
+    }
+}
+
+/// Values of a string column of any width, or `None` for other types
+fn strings(array: &dyn Array) -> Option<Vec<Option<String>>> {
+    let owned = |value: Option<&str>| value.map(str::to_string);
+    match array.data_type() {
+        DataType::Utf8 => Some(array.as_string::<i32>().iter().map(owned).collect()),
+        DataType::LargeUtf8 => Some(array.as_string::<i64>().iter().map(owned).collect()),
+        DataType::Utf8View => Some(array.as_string_view().iter().map(owned).collect()),
+        _ => None,
+    }
+}
+
+/// Values of a list-of-strings column, with null lists and items dropped
+fn string_lists(array: &dyn Array) -> Option<Vec<Vec<String>>> {
+    let lists: Vec<Option<ArrayRef>> = match array.data_type() {
+        DataType::List(_) => array.as_list::<i32>().iter().collect(),
+        DataType::LargeList(_) => array.as_list::<i64>().iter().collect(),
+        _ => return None,
+    };
+    lists
+        .into_iter()
+        .map(|list| match list {
+            Some(values) => Some(strings(&values)?.into_iter().flatten().collect()),
+            None => Some(Vec::new()),
+        })
+        .collect()
+}
+
+/// Wrap Arrow and Parquet errors as dataset errors of `path`
+fn failed<E: std::fmt::Display>(path: &Path) -> impl Fn(E) -> crate::Error + '_ {
+    move |error| dataset_error(path, None, error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dataset::Difficulty;
+    use arrow_array::LargeStringArray;
+
+    #[test]
+    fn test_parquet_round_trip() {
+        let dataset = WGSLDataset {
+            examples: vec![
+                WGSLExample {
+                    tags: vec!["loop".to_string(), "snippet".to_string()],
+                    category: Some("compute".to_string()),
+                    difficulty: Some(Difficulty::Hard),
+                    source: Some("https://example.com".to_string()),
+                    ..WGSLExample::new("Double every value", "fn main() {}")
+                },
+                WGSLExample::new("Red fragment", "@fragment fn main() {}"),
+            ],
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("shaders.parquet");
+        dataset.to_file(&path).unwrap();
+
+        let loaded = WGSLDataset::from_file(&path).unwrap();
+        assert_eq!(
+            serde_json::to_value(&loaded.examples).unwrap(),
+            serde_json::to_value(&dataset.examples).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_parquet_from_other_writers() {
+        let dir = tempfile::tempdir().unwrap();
+        let write = |name: &str, batch: RecordBatch| {
+            let path = dir.path().join(name);
+            let mut writer =
+                ArrowWriter::try_new(File::create(&path).unwrap(), batch.schema(), None).unwrap();
+            writer.write(&batch).unwrap();
+            writer.close().unwrap();
+            path
+        };
+
+        // Large strings and an unknown column, without any metadata columns
+        let path = write(
+            "large.parquet",
+            RecordBatch::try_from_iter([
+                (
+                    "natural_language",
+                    Arc::new(LargeStringArray::from(vec!["Blur"])) as ArrayRef,
+                ),
+                ("wgsl_code", Arc::new(LargeStringArray::from(vec!["fn f() {}"]))),
+                ("score", Arc::new(StringArray::from(vec!["0.9"]))),
+            ])
+            .unwrap(),
+        );
+        let loaded = WGSLDataset::from_parquet(&path).unwrap();
+        assert_eq!(loaded.examples[0].natural_language, "Blur");
+        assert!(loaded.examples[0].tags.is_empty());
+
+        let path = write(
+            "missing.parquet",
+            RecordBatch::try_from_iter([(
+                "prompt",
+                Arc::new(StringArray::from(vec!["Blur"])) as ArrayRef,
+            )])
+            .unwrap(),
+        );
+        let error = WGSLDataset::from_parquet(&path).unwrap_err().to_string();
+        assert!(error.contains("missing column `natural_language`"), "{}", error);
+
+        let path = write(
+            "difficulty.parquet",
+            RecordBatch::try_from_iter([
+                (
+                    "natural_language",
+                    Arc::new(StringArray::from(vec!["Blur"])) as ArrayRef,
+                ),
+                ("wgsl_code", Arc::new(StringArray::from(vec!["fn f() {}"]))),
+                ("difficulty", Arc::new(StringArray::from(vec!["extreme"]))),
+            ])
+            .unwrap(),
+        );
+        let error = WGSLDataset::from_parquet(&path).unwrap_err().to_string();
+        assert!(error.contains("extreme"), "{}", error);
+    }
+}
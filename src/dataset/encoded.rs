@@ -0,0 +1,215 @@
+//! Datasets tokenized and encoded once, ahead of training
+//!
+//! [`EncodedDataset::encode`] encodes every example in parallel, and
+//! [`EncodedDataset::encode_source`] does the same for any [`ExampleSource`],
+//! such as a [`LazyDataset`](super::lazy::LazyDataset) that never holds more than
+//! the examples in flight; [`EncodedDataset::load_or_encode`] additionally
+//! caches the result under a directory, keyed by the dataset's and
+//! tokenizer's content hashes, so later runs on the same data skip
+//! tokenization entirely.
+
+use super::{ExampleSource, WGSLDataset, WGSLExample};
+use crate::tokenizer::WGSLTokenizer;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Version of the cache file layout; bumped on incompatible changes
+const CACHE_VERSION: u32 = 1;
+
+/// Prompt and code token ids of every example, in dataset order
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EncodedDataset {
+    pub pairs: Vec<(Vec<usize>, Vec<usize>)>,
+}
+
+/// On-disk layout; ids are stored as `u32` to halve the file
+#[derive(Serialize, Deserialize)]
+struct CacheFile {
+    version: u32,
+    pairs: Vec<(Vec<u32>, Vec<u32>)>,
+}
+
+impl EncodedDataset {
+    /// Encode every example of `dataset` across rayon's threads
+    pub fn encode(dataset: &WGSLDataset, tokenizer: &WGSLTokenizer) -> Self {
+        Self {
+            pairs: dataset
+                .examples
+                .par_iter()
+                .map(|example| encode_example(example, tokenizer))
+                .collect(),
+        }
+    }
+
+    /// Encode every example of `source` across rayon's threads, reading each
+    /// one only while it is encoded
+    pub fn encode_source(
+        source: &(dyn ExampleSource + Sync),
+        tokenizer: &WGSLTokenizer,
+    ) -> crate::Result<Self> {
+        let pairs = (0..source.len())
+            .into_par_iter()
+            .map(|index| Ok(encode_example(&source.get(index)?, tokenizer)))
+            .collect::<crate::Result<_>>()?;
+        Ok(Self { pairs })
+    }
+
+    /// Read `dataset` encoded with `tokenizer` from `cache_dir`, or encode and
+    /// write it there; returns whether the cache was hit
+    ///
+    /// An unreadable cache file is replaced rather than failing.
+    pub fn load_or_encode(
+        dataset: &(dyn ExampleSource + Sync),
+        tokenizer: &WGSLTokenizer,
+        cache_dir: &Path,
+    ) -> crate::Result<(Self, bool)> {
+        let path = Self::cache_path(cache_dir, dataset, tokenizer)?;
+        if path.exists() {
+            match Self::read(&path) {
+                Ok(encoded) if encoded.len() == dataset.len() => return Ok((encoded, true)),
+                Ok(_) => tracing::warn!("Ignoring {}: wrong example count", path.display()),
+                Err(e) => tracing::warn!("Ignoring {}: {}", path.display(), e),
+            }
+        }
+        let encoded = Self::encode_source(dataset, tokenizer)?;
+        encoded.write(&path)?;
+        Ok((encoded, false))
+    }
+
+    /// Cache file of `dataset` encoded with `tokenizer` under `cache_dir`
+    pub fn cache_path(
+        cache_dir: &Path,
+        dataset: &dyn ExampleSource,
+        tokenizer: &WGSLTokenizer,
+    ) -> crate::Result<PathBuf> {
+        Ok(cache_dir.join(format!(
+            "encoded-{}-{}.bin",
+            &dataset.content_hash()?[..16],
+            &tokenizer.content_hash()[..16]
+        )))
+    }
+
+    fn read(path: &Path) -> crate::Result<Self> {
+        let file = std::io::BufReader::new(std::fs::File::open(path)?);
+        let cache: CacheFile = bincode::deserialize_from(file)?;
+        if cache.version != CACHE_VERSION {
+            return Err(crate::Error::Other(format!(
+                "cache version {}, expected {}",
+                cache.version, CACHE_VERSION
+            )));
+        }
+        let widen = |ids: Vec<u32>| ids.into_iter().map(|id| id as usize).collect();
+        Ok(Self {
+            pairs: cache
+                .pairs
+                .into_iter()
+                .map(|(input, target)| (widen(input), widen(target)))
+                .collect(),
+        })
+    }
+
+    /// Write to `path` through a temporary file, so concurrent runs never
+    /// read a partial cache
+    fn write(&self, path: &Path) -> crate::Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let narrow = |ids: &[usize]| ids.iter().map(|&id| id as u32).collect();
+        let cache = CacheFile {
+            version: CACHE_VERSION,
+            pairs: self
+                .pairs
+                .iter()
+                .map(|(input, target)| (narrow(input), narrow(target)))
+                .collect(),
+        };
+        let temp = path.with_extension(format!("tmp{}", std::process::id()));
+        bincode::serialize_into(
+            std::io::BufWriter::new(std::fs::File::create(&temp)?),
+            &cache,
+        )?;
+        std::fs::rename(&temp, path)?;
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.pairs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pairs.is_empty()
+    }
+
+    /// Prompt and code tokens over all examples
+    pub fn token_count(&self) -> usize {
+        self.pairs
+            .iter()
+            .map(|(input, target)| input.len() + target.len())
+            .sum()
+    }
+}
+
+/// Prompts are normalized with the tokenizer's normalizer first, as they
+/// will be at inference
+fn encode_example(example: &WGSLExample, tokenizer: &WGSLTokenizer) -> (Vec<usize>, Vec<usize>) {
+    (
+        tokenizer.encode_text(&tokenizer.normalize_prompt(&example.natural_language)),
+        tokenizer.encode_text(&example.wgsl_code),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_or_encode() {
+        let mut dataset = WGSLDataset::new();
+        dataset
+            .examples
+            .push(WGSLExample::new("empty main", "fn main() { }"));
+        dataset
+            .examples
+            .push(WGSLExample::new("one", "fn f() -> f32 { return 1.0; }"));
+        let mut tokenizer = WGSLTokenizer::new(64, false);
+        tokenizer.fit(&["fn main() { }", "empty"], 1);
+        let dir = tempfile::tempdir().unwrap();
+
+        let (encoded, hit) =
+            EncodedDataset::load_or_encode(&dataset, &tokenizer, dir.path()).unwrap();
+        assert!(!hit);
+        assert_eq!(encoded.pairs[0].0, tokenizer.encode_text("empty main"));
+        assert_eq!(
+            encoded.pairs[1].1,
+            tokenizer.encode_text("fn f() -> f32 { return 1.0; }")
+        );
+        assert_eq!(encoded.token_count(), 2 + 6 + 1 + 11);
+
+        let (cached, hit) =
+            EncodedDataset::load_or_encode(&dataset, &tokenizer, dir.path()).unwrap();
+        assert!(hit);
+        assert_eq!(cached, encoded);
+
+        // A changed vocabulary gets its own cache file
+        tokenizer.fit(&["one"], 1);
+        let (_, hit) = EncodedDataset::load_or_encode(&dataset, &tokenizer, dir.path()).unwrap();
+        assert!(!hit);
+
+        // A corrupt cache is replaced
+        let path = EncodedDataset::cache_path(dir.path(), &dataset, &tokenizer).unwrap();
+        std::fs::write(&path, b"garbage").unwrap();
+        let (reencoded, hit) =
+            EncodedDataset::load_or_encode(&dataset, &tokenizer, dir.path()).unwrap();
+        assert!(!hit);
+        assert_eq!(reencoded, EncodedDataset::encode(&dataset, &tokenizer));
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 2);
+
+        // Prompts are normalized, and a normalizer gets its own cache file
+        tokenizer.normalizer = Some(crate::tokenizer::PromptNormalizer::default());
+        let (normalized, hit) =
+            EncodedDataset::load_or_encode(&dataset, &tokenizer, dir.path()).unwrap();
+        assert!(!hit);
+        assert_eq!(normalized.pairs[1].0, tokenizer.encode_text("1"));
+    }
+}
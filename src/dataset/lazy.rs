@@ -0,0 +1,202 @@
+//! Lazily loaded datasets for corpora larger than memory
+//!
+//! A [`LazyDataset`] scans a JSONL file once, recording the byte offset of every
+//! example, and reads individual examples from disk on demand.
+
+use super::{dataset_error, ExampleSource, WGSLDataset, WGSLExample};
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Location of a single example inside the JSONL file
+#[derive(Debug, Clone, Copy)]
+struct LineSpan {
+    offset: u64,
+    len: u32,
+    line: usize,
+}
+
+/// JSONL-backed dataset that keeps only an offset index in memory
+#[derive(Debug)]
+pub struct LazyDataset {
+    path: PathBuf,
+    index: Vec<LineSpan>,
+    file: Mutex<File>,
+}
+
+impl LazyDataset {
+    /// Index a JSONL file with one [`WGSLExample`] object per line
+    pub fn open<P: AsRef<Path>>(path: P) -> crate::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let mut reader = BufReader::new(File::open(&path)?);
+
+        let mut index = Vec::new();
+        let mut offset = 0u64;
+        let mut line = Vec::new();
+        let mut line_number = 0;
+
+        loop {
+            line.clear();
+            let read = reader.read_until(b'\n', &mut line)?;
+            if read == 0 {
+                break;
+            }
+            line_number += 1;
+
+            let content_len = line.trim_ascii_end().len();
+            if !line.trim_ascii().is_empty() {
+                let len = u32::try_from(content_len).map_err(|_| {
+                    dataset_error(&path, Some(line_number), "example exceeds 4 GiB")
+                })?;
+                index.push(LineSpan {
+                    offset,
+                    len,
+                    line: line_number,
+                });
+            }
+            offset += read as u64;
+        }
+
+        tracing::debug!("Indexed {} examples in {}", index.len(), path.display());
+
+        Ok(Self {
+            file: Mutex::new(File::open(&path)?),
+            path,
+            index,
+        })
+    }
+
+    /// Path of the underlying JSONL file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Number of indexed examples
+    pub fn len(&self) -> usize {
+        self.index.len()
+    }
+
+    /// Check if the file contains no examples
+    pub fn is_empty(&self) -> bool {
+        self.index.is_empty()
+    }
+
+    /// Read a single example from disk
+    pub fn get(&self, index: usize) -> crate::Result<WGSLExample> {
+        let span = self.index.get(index).ok_or_else(|| {
+            crate::Error::Other(format!(
+                "Example index {} out of range for {} examples",
+                index,
+                self.index.len()
+            ))
+        })?;
+
+        let mut buffer = vec![0u8; span.len as usize];
+        {
+            let mut file = self
+                .file
+                .lock()
+                .map_err(|_| crate::Error::Other("Dataset file lock poisoned".to_string()))?;
+            file.seek(SeekFrom::Start(span.offset))?;
+            file.read_exact(&mut buffer)?;
+        }
+
+        serde_json::from_slice(&buffer).map_err(|e| dataset_error(&self.path, Some(span.line), e))
+    }
+
+    /// Load a contiguous range of examples into memory
+    pub fn load_range(&self, start: usize, end: usize) -> crate::Result<WGSLDataset> {
+        let end = end.min(self.len());
+        let examples = (start.min(end)..end)
+            .map(|index| self.get(index))
+            .collect::<crate::Result<Vec<_>>>()?;
+        Ok(WGSLDataset { examples })
+    }
+
+    /// Stream every example in file order
+    pub fn iter(&self) -> impl Iterator<Item = crate::Result<WGSLExample>> + '_ {
+        (0..self.len()).map(move |index| self.get(index))
+    }
+}
+
+impl ExampleSource for LazyDataset {
+    fn len(&self) -> usize {
+        LazyDataset::len(self)
+    }
+
+    fn get(&self, index: usize) -> crate::Result<WGSLExample> {
+        LazyDataset::get(self, index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_corpus(dir: &Path) -> PathBuf {
+        let dataset = WGSLDataset {
+            examples: vec![
+                WGSLExample::new("Red", "vec4<f32>(1.0, 0.0, 0.0, 1.0)"),
+                WGSLExample::new("Green", "vec4<f32>(0.0, 1.0, 0.0, 1.0)"),
+                WGSLExample::new("Blue", "vec4<f32>(0.0, 0.0, 1.0, 1.0)"),
+            ],
+        };
+        let path = dir.join("corpus.jsonl");
+        dataset.to_jsonl(&path).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_lazy_random_access() {
+        let dir = tempfile::tempdir().unwrap();
+        let dataset = LazyDataset::open(write_corpus(dir.path())).unwrap();
+
+        assert_eq!(dataset.len(), 3);
+        assert_eq!(dataset.get(2).unwrap().natural_language, "Blue");
+        assert_eq!(dataset.get(0).unwrap().natural_language, "Red");
+        assert!(dataset.get(3).is_err());
+    }
+
+    #[test]
+    fn test_lazy_range_and_iter() {
+        let dir = tempfile::tempdir().unwrap();
+        let dataset = LazyDataset::open(write_corpus(dir.path())).unwrap();
+
+        let range = dataset.load_range(1, 10).unwrap();
+        assert_eq!(range.len(), 2);
+        assert_eq!(range.examples[0].natural_language, "Green");
+
+        let names: Vec<String> = dataset
+            .iter()
+            .map(|example| example.unwrap().natural_language)
+            .collect();
+        assert_eq!(names, vec!["Red", "Green", "Blue"]);
+    }
+
+    #[test]
+    fn test_lazy_skips_blank_lines_and_reports_line() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("broken.jsonl");
+        std::fs::write(
+            &path,
+            "{\"natural_language\": \"a\", \"wgsl_code\": \"b\"}\n\n{not json}\n",
+        )
+        .unwrap();
+
+        let dataset = LazyDataset::open(&path).unwrap();
+        assert_eq!(dataset.len(), 2);
+        assert!(dataset.get(0).is_ok());
+
+        let error = dataset.get(1).unwrap_err();
+        assert!(
+            error.to_string().contains(":3:"),
+            "unexpected error: {}",
+            error
+        );
+        assert!(matches!(
+            error,
+            crate::Error::DatasetError { line: Some(3), .. }
+        ));
+    }
+}
@@ -1,13 +1,144 @@
 //! Dataset management for WGSL code generation training
 
+pub mod augment;
+#[cfg(feature = "arrow")]
+mod columnar;
+pub mod encoded;
+pub mod lazy;
+pub mod stats;
+
+use crate::config::LengthPolicy;
+use crate::tokenizer::WGSLTokenizer;
+use crate::wgsl::WGSLValidator;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 use std::path::Path;
 
 /// A single training example: natural language → WGSL code
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct WGSLExample {
     pub natural_language: String,
     pub wgsl_code: String,
+    /// Free-form tags (e.g. "snippet", "loop")
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Shader family (e.g. "chromatic", "compute", "fragment")
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub category: Option<String>,
+    /// Relative difficulty of the example
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub difficulty: Option<Difficulty>,
+    /// Where the example came from (file, URL, generator, ...)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+}
+
+/// Difficulty levels, ordered from simplest to hardest
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Difficulty {
+    Easy,
+    Medium,
+    Hard,
+}
+
+impl Difficulty {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Difficulty::Easy => "easy",
+            Difficulty::Medium => "medium",
+            Difficulty::Hard => "hard",
+        }
+    }
+}
+
+impl std::str::FromStr for Difficulty {
+    type Err = crate::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "easy" => Ok(Difficulty::Easy),
+            "medium" => Ok(Difficulty::Medium),
+            "hard" => Ok(Difficulty::Hard),
+            other => Err(crate::Error::ConfigError(format!(
+                "Unknown difficulty '{}'. Must be one of: easy, medium, hard",
+                other
+            ))),
+        }
+    }
+}
+
+/// Random access to training examples, whether held in memory or read from disk
+pub trait ExampleSource {
+    /// Number of examples
+    fn len(&self) -> usize;
+
+    /// Check if there are no examples
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Fetch the example at `index`
+    fn get(&self, index: usize) -> crate::Result<WGSLExample>;
+
+    /// SHA-256 of every prompt and its code, in order, as lowercase hex;
+    /// reads each example once
+    fn content_hash(&self) -> crate::Result<String> {
+        let mut hasher = Sha256::new();
+        for index in 0..self.len() {
+            let example = self.get(index)?;
+            hash_example(&mut hasher, &example);
+        }
+        Ok(format!("{:x}", hasher.finalize()))
+    }
+}
+
+fn hash_example(hasher: &mut Sha256, example: &WGSLExample) {
+    hasher.update(example.natural_language.as_bytes());
+    hasher.update([0]);
+    hasher.update(example.wgsl_code.as_bytes());
+    hasher.update([0]);
+}
+
+/// Category name used for examples without one
+pub const UNCATEGORIZED: &str = "uncategorized";
+
+#[cfg(not(feature = "arrow"))]
+const UNSUPPORTED_FORMAT: &str = "unsupported format (expected .toml, .json or .jsonl)";
+#[cfg(feature = "arrow")]
+const UNSUPPORTED_FORMAT: &str = "unsupported format (expected .toml, .json, .jsonl or .parquet)";
+
+impl WGSLExample {
+    /// Create an example without metadata
+    pub fn new(natural_language: impl Into<String>, wgsl_code: impl Into<String>) -> Self {
+        Self {
+            natural_language: natural_language.into(),
+            wgsl_code: wgsl_code.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Category name, falling back to [`UNCATEGORIZED`]
+    pub fn category_or_default(&self) -> &str {
+        self.category.as_deref().unwrap_or(UNCATEGORIZED)
+    }
+
+    /// Check if the example carries the given tag
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t == tag)
+    }
+
+    /// Identity used for deduplication: trimmed prompt plus whitespace-normalized code
+    pub fn dedup_key(&self) -> (String, String) {
+        (
+            self.natural_language.trim().to_string(),
+            self.wgsl_code
+                .split_whitespace()
+                .collect::<Vec<_>>()
+                .join(" "),
+        )
+    }
 }
 
 /// Dataset for WGSL code generation
@@ -26,11 +157,81 @@ impl WGSLDataset {
 
     /// Load dataset from JSON file
     pub fn from_json<P: AsRef<Path>>(path: P) -> crate::Result<Self> {
+        let path = path.as_ref();
         let content = std::fs::read_to_string(path)?;
-        let examples: Vec<WGSLExample> = serde_json::from_str(&content)?;
+        let examples: Vec<WGSLExample> =
+            serde_json::from_str(&content).map_err(|e| dataset_error(path, Some(e.line()), e))?;
         Ok(WGSLDataset { examples })
     }
 
+    /// Load dataset from a `.toml`, `.json` or `.jsonl` file based on its extension,
+    /// or `.parquet` with the `arrow` feature
+    pub fn from_file<P: AsRef<Path>>(path: P) -> crate::Result<Self> {
+        let path = path.as_ref();
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => Self::from_toml(path),
+            Some("json") => Self::from_json(path),
+            Some("jsonl") => Self::from_jsonl(path),
+            #[cfg(feature = "arrow")]
+            Some("parquet") => Self::from_parquet(path),
+            _ => Err(dataset_error(path, None, UNSUPPORTED_FORMAT)),
+        }
+    }
+
+    /// Save dataset as `.toml`, `.json` or `.jsonl` based on the file extension,
+    /// or `.parquet` with the `arrow` feature
+    pub fn to_file<P: AsRef<Path>>(&self, path: P) -> crate::Result<()> {
+        let path = path.as_ref();
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => {
+                #[derive(Serialize)]
+                struct DatasetFile<'a> {
+                    examples: &'a [WGSLExample],
+                }
+
+                let content = toml::to_string_pretty(&DatasetFile {
+                    examples: &self.examples,
+                })?;
+                std::fs::write(path, content)?;
+                Ok(())
+            }
+            Some("json") => {
+                std::fs::write(path, serde_json::to_string_pretty(&self.examples)?)?;
+                Ok(())
+            }
+            Some("jsonl") => self.to_jsonl(path),
+            #[cfg(feature = "arrow")]
+            Some("parquet") => self.to_parquet(path),
+            _ => Err(dataset_error(path, None, UNSUPPORTED_FORMAT)),
+        }
+    }
+
+    /// Load dataset from JSON Lines file (one example object per line)
+    pub fn from_jsonl<P: AsRef<Path>>(path: P) -> crate::Result<Self> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)?;
+        let examples = content
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(index, line)| {
+                serde_json::from_str(line).map_err(|e| dataset_error(path, Some(index + 1), e))
+            })
+            .collect::<crate::Result<Vec<WGSLExample>>>()?;
+        Ok(WGSLDataset { examples })
+    }
+
+    /// Save dataset as JSON Lines
+    pub fn to_jsonl<P: AsRef<Path>>(&self, path: P) -> crate::Result<()> {
+        let mut content = String::new();
+        for example in &self.examples {
+            content.push_str(&serde_json::to_string(example)?);
+            content.push('\n');
+        }
+        std::fs::write(path, content)?;
+        Ok(())
+    }
+
     /// Load dataset from TOML file
     pub fn from_toml<P: AsRef<Path>>(path: P) -> crate::Result<Self> {
         use serde::Deserialize;
@@ -40,9 +241,17 @@ impl WGSLDataset {
             examples: Vec<WGSLExample>,
         }
 
+        let path = path.as_ref();
         let content = std::fs::read_to_string(path)?;
-        let data: DatasetFile = toml::from_str(&content)?;
-        Ok(WGSLDataset { examples: data.examples })
+        let data: DatasetFile = toml::from_str(&content).map_err(|e| {
+            let line = e
+                .span()
+                .map(|span| content[..span.start].matches('\n').count() + 1);
+            dataset_error(path, line, e.message())
+        })?;
+        Ok(WGSLDataset {
+            examples: data.examples,
+        })
     }
 
     /// Get number of examples
@@ -71,6 +280,251 @@ impl WGSLDataset {
             WGSLDataset { examples: test_examples },
         )
     }
+
+    /// Partition into `k` contiguous folds, returning `(train, held_out)` for
+    /// each fold; fold sizes differ by at most one example
+    pub fn k_fold(&self, k: usize) -> crate::Result<Vec<(Self, Self)>> {
+        let total = self.examples.len();
+        if k < 2 || k > total {
+            return Err(crate::Error::Other(format!(
+                "Cannot split {} examples into {} folds",
+                total, k
+            )));
+        }
+
+        let mut folds = Vec::with_capacity(k);
+        let mut start = 0;
+        for fold in 0..k {
+            let end = start + total / k + usize::from(fold < total % k);
+            let held_out = self.examples[start..end].to_vec();
+            let train = [&self.examples[..start], &self.examples[end..]].concat();
+            folds.push((
+                WGSLDataset { examples: train },
+                WGSLDataset { examples: held_out },
+            ));
+            start = end;
+        }
+        Ok(folds)
+    }
+
+    /// Validate every example's WGSL code with naga
+    pub fn validate(&self, validator: &WGSLValidator) -> crate::Result<DatasetValidationReport> {
+        let mut invalid = Vec::new();
+
+        for (index, example) in self.examples.iter().enumerate() {
+            let result = validator.validate(&example.wgsl_code)?;
+            if !result.is_valid {
+                invalid.push(InvalidExample {
+                    index,
+                    natural_language: example.natural_language.clone(),
+                    errors: result.errors,
+                });
+            }
+        }
+
+        Ok(DatasetValidationReport {
+            total: self.examples.len(),
+            invalid,
+        })
+    }
+
+    /// Return a copy of the dataset containing only examples that pass validation
+    pub fn filter_valid(&self, validator: &WGSLValidator) -> crate::Result<Self> {
+        let mut examples = Vec::new();
+        for example in &self.examples {
+            if validator.validate(&example.wgsl_code)?.is_valid {
+                examples.push(example.clone());
+            }
+        }
+
+        Ok(WGSLDataset { examples })
+    }
+
+    /// Apply a length policy to examples whose prompt or code exceeds `max_tokens`
+    pub fn filter_by_length(
+        &self,
+        tokenizer: &WGSLTokenizer,
+        max_tokens: usize,
+        policy: LengthPolicy,
+    ) -> (Self, LengthReport) {
+        let mut report = LengthReport {
+            policy,
+            max_tokens,
+            total: self.examples.len(),
+            dropped: Vec::new(),
+            truncated: Vec::new(),
+            longest: 0,
+        };
+        let mut examples = Vec::with_capacity(self.examples.len());
+
+        for (index, example) in self.examples.iter().enumerate() {
+            let prompt = tokenizer.normalize_prompt(&example.natural_language);
+            let nl_len = tokenizer.tokenize(&prompt).len();
+            let code_len = tokenizer.tokenize(&example.wgsl_code).len();
+            report.longest = report.longest.max(nl_len).max(code_len);
+
+            if nl_len <= max_tokens && code_len <= max_tokens {
+                examples.push(example.clone());
+                continue;
+            }
+
+            match policy {
+                LengthPolicy::Drop => report.dropped.push(index),
+                LengthPolicy::Truncate => {
+                    report.truncated.push(index);
+                    examples.push(WGSLExample {
+                        natural_language: tokenizer.truncate_text(&prompt, max_tokens),
+                        wgsl_code: tokenizer.truncate_text(&example.wgsl_code, max_tokens),
+                        ..example.clone()
+                    });
+                }
+            }
+        }
+
+        if !report.dropped.is_empty() || !report.truncated.is_empty() {
+            tracing::warn!(
+                "{} of {} examples exceed {} tokens ({} dropped, {} truncated)",
+                report.dropped.len() + report.truncated.len(),
+                report.total,
+                max_tokens,
+                report.dropped.len(),
+                report.truncated.len()
+            );
+        }
+
+        (WGSLDataset { examples }, report)
+    }
+
+    /// Keep only examples matching a predicate
+    pub fn filter<F>(&self, mut predicate: F) -> Self
+    where
+        F: FnMut(&WGSLExample) -> bool,
+    {
+        WGSLDataset {
+            examples: self
+                .examples
+                .iter()
+                .filter(|example| predicate(example))
+                .cloned()
+                .collect(),
+        }
+    }
+
+    /// Keep only examples in the given category
+    pub fn filter_by_category(&self, category: &str) -> Self {
+        self.filter(|example| example.category_or_default() == category)
+    }
+
+    /// Keep only examples carrying the given tag
+    pub fn filter_by_tag(&self, tag: &str) -> Self {
+        self.filter(|example| example.has_tag(tag))
+    }
+
+    /// Keep only examples at or below the given difficulty
+    pub fn filter_by_max_difficulty(&self, max: Difficulty) -> Self {
+        self.filter(|example| example.difficulty.is_some_and(|d| d <= max))
+    }
+
+    /// Group examples by category (uncategorized examples use [`UNCATEGORIZED`])
+    pub fn group_by_category(&self) -> BTreeMap<String, WGSLDataset> {
+        let mut groups: BTreeMap<String, WGSLDataset> = BTreeMap::new();
+        for example in &self.examples {
+            groups
+                .entry(example.category_or_default().to_string())
+                .or_default()
+                .examples
+                .push(example.clone());
+        }
+        groups
+    }
+
+    /// Sorted list of distinct categories
+    pub fn categories(&self) -> Vec<String> {
+        self.group_by_category().into_keys().collect()
+    }
+
+    /// Remove duplicate examples, keeping the first occurrence
+    pub fn dedup(&self) -> Self {
+        let mut seen = HashSet::new();
+        self.filter(|example| seen.insert(example.dedup_key()))
+    }
+
+    /// Combine two datasets, dropping duplicates (the first occurrence wins)
+    pub fn merge(&self, other: &WGSLDataset) -> Self {
+        let mut seen = HashSet::new();
+        let examples = self
+            .examples
+            .iter()
+            .chain(&other.examples)
+            .filter(|example| seen.insert(example.dedup_key()))
+            .cloned()
+            .collect();
+        WGSLDataset { examples }
+    }
+
+    /// SHA-256 of every prompt and its code, in order, as lowercase hex;
+    /// identifies the exact data a model was trained on
+    pub fn content_hash(&self) -> String {
+        let mut hasher = Sha256::new();
+        for example in &self.examples {
+            hash_example(&mut hasher, example);
+        }
+        format!("{:x}", hasher.finalize())
+    }
+
+    /// Compare two datasets by prompt and code
+    pub fn diff(&self, other: &WGSLDataset) -> DatasetDiff {
+        let ours: HashSet<_> = self.examples.iter().map(|e| e.dedup_key()).collect();
+        let theirs: HashSet<_> = other.examples.iter().map(|e| e.dedup_key()).collect();
+
+        let mut only_in_self: Vec<WGSLExample> = self
+            .examples
+            .iter()
+            .filter(|e| !theirs.contains(&e.dedup_key()))
+            .cloned()
+            .collect();
+        let mut only_in_other: Vec<WGSLExample> = other
+            .examples
+            .iter()
+            .filter(|e| !ours.contains(&e.dedup_key()))
+            .cloned()
+            .collect();
+        let common = ours.intersection(&theirs).count();
+
+        // Same prompt on both sides but different code counts as a change;
+        // repeated prompts pair up in order, the rest stay unpaired
+        let mut theirs_by_prompt: HashMap<String, VecDeque<usize>> = HashMap::new();
+        for (index, example) in only_in_other.iter().enumerate() {
+            theirs_by_prompt
+                .entry(example.dedup_key().0)
+                .or_default()
+                .push_back(index);
+        }
+        let mut paired = vec![false; only_in_other.len()];
+        let mut changed = Vec::new();
+        only_in_self.retain(|example| {
+            let theirs = theirs_by_prompt
+                .get_mut(&example.dedup_key().0)
+                .and_then(VecDeque::pop_front);
+            match theirs {
+                Some(index) => {
+                    paired[index] = true;
+                    changed.push((example.clone(), only_in_other[index].clone()));
+                    false
+                }
+                None => true,
+            }
+        });
+        let mut paired = paired.into_iter();
+        only_in_other.retain(|_| !paired.next().unwrap_or(false));
+
+        DatasetDiff {
+            only_in_self,
+            only_in_other,
+            changed,
+            common,
+        }
+    }
 }
 
 impl Default for WGSLDataset {
@@ -78,3 +532,390 @@ impl Default for WGSLDataset {
         Self::new()
     }
 }
+
+impl ExampleSource for WGSLDataset {
+    fn len(&self) -> usize {
+        self.examples.len()
+    }
+
+    fn get(&self, index: usize) -> crate::Result<WGSLExample> {
+        self.examples.get(index).cloned().ok_or_else(|| {
+            crate::Error::Other(format!(
+                "Example index {} out of range for {} examples",
+                index,
+                self.examples.len()
+            ))
+        })
+    }
+
+    fn content_hash(&self) -> crate::Result<String> {
+        Ok(WGSLDataset::content_hash(self))
+    }
+}
+
+/// An example whose WGSL code failed validation
+#[derive(Debug, Clone)]
+pub struct InvalidExample {
+    /// Position of the example in the dataset
+    pub index: usize,
+    pub natural_language: String,
+    pub errors: Vec<String>,
+}
+
+/// Summary of a dataset validation pass
+#[derive(Debug, Clone)]
+pub struct DatasetValidationReport {
+    pub total: usize,
+    pub invalid: Vec<InvalidExample>,
+}
+
+impl DatasetValidationReport {
+    /// Number of examples that passed validation
+    pub fn valid_count(&self) -> usize {
+        self.total - self.invalid.len()
+    }
+
+    /// Check if every example passed validation
+    pub fn is_clean(&self) -> bool {
+        self.invalid.is_empty()
+    }
+
+    /// Print validation summary
+    pub fn print(&self) {
+        println!(
+            "📋 Dataset validation: {}/{} examples valid",
+            self.valid_count(),
+            self.total
+        );
+
+        for example in &self.invalid {
+            println!("  ❌ #{} {}", example.index, example.natural_language);
+            for error in &example.errors {
+                println!("     - {}", error);
+            }
+        }
+    }
+}
+
+/// Differences between two datasets
+#[derive(Debug, Clone)]
+pub struct DatasetDiff {
+    /// Examples whose prompt appears only in the first dataset
+    pub only_in_self: Vec<WGSLExample>,
+    /// Examples whose prompt appears only in the second dataset
+    pub only_in_other: Vec<WGSLExample>,
+    /// Same prompt with different code: (first, second)
+    pub changed: Vec<(WGSLExample, WGSLExample)>,
+    /// Number of identical examples
+    pub common: usize,
+}
+
+impl DatasetDiff {
+    /// Check if both datasets contain the same examples
+    pub fn is_empty(&self) -> bool {
+        self.only_in_self.is_empty() && self.only_in_other.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// Summary of a length filtering pass
+#[derive(Debug, Clone)]
+pub struct LengthReport {
+    pub policy: LengthPolicy,
+    pub max_tokens: usize,
+    pub total: usize,
+    /// Indices of examples removed from the dataset
+    pub dropped: Vec<usize>,
+    /// Indices of examples that were cut down to the limit
+    pub truncated: Vec<usize>,
+    /// Longest prompt or code length seen, in tokens
+    pub longest: usize,
+}
+
+impl LengthReport {
+    /// Print length filtering summary
+    pub fn print(&self) {
+        println!(
+            "📏 Length filtering ({:?}, max {} tokens): {} examples, longest {} tokens",
+            self.policy, self.max_tokens, self.total, self.longest
+        );
+        if !self.dropped.is_empty() {
+            println!(
+                "  Dropped: {} examples {:?}",
+                self.dropped.len(),
+                self.dropped
+            );
+        }
+        if !self.truncated.is_empty() {
+            println!(
+                "  Truncated: {} examples {:?}",
+                self.truncated.len(),
+                self.truncated
+            );
+        }
+    }
+}
+
+/// [`Error::DatasetError`](crate::Error::DatasetError) at `line` of `path`
+pub(crate) fn dataset_error(
+    path: &Path,
+    line: Option<usize>,
+    message: impl std::fmt::Display,
+) -> crate::Error {
+    crate::Error::DatasetError {
+        path: path.to_path_buf(),
+        line,
+        message: message.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_dataset() -> WGSLDataset {
+        WGSLDataset {
+            examples: vec![
+                WGSLExample {
+                    category: Some("fragment".to_string()),
+                    difficulty: Some(Difficulty::Medium),
+                    ..WGSLExample::new(
+                        "Red fragment shader",
+                        r#"
+@fragment
+fn main() -> @location(0) vec4<f32> {
+    return vec4<f32>(1.0, 0.0, 0.0, 1.0);
+}
+"#,
+                    )
+                },
+                WGSLExample {
+                    tags: vec!["broken".to_string()],
+                    difficulty: Some(Difficulty::Easy),
+                    ..WGSLExample::new("Broken shader", "fn main( {")
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn test_validate_reports_invalid_examples() {
+        let dataset = sample_dataset();
+        let report = dataset.validate(&WGSLValidator::new()).unwrap();
+
+        assert_eq!(report.total, 2);
+        assert_eq!(report.valid_count(), 1);
+        assert_eq!(report.invalid[0].index, 1);
+        assert!(!report.invalid[0].errors.is_empty());
+        assert!(!report.is_clean());
+    }
+
+    #[test]
+    fn test_filter_valid() {
+        let dataset = sample_dataset();
+        let filtered = dataset.filter_valid(&WGSLValidator::new()).unwrap();
+
+        assert_eq!(filtered.len(), 1);
+        assert_eq!(filtered.examples[0].natural_language, "Red fragment shader");
+    }
+
+    #[test]
+    fn test_filter_by_length_drop() {
+        let dataset = sample_dataset();
+        let tokenizer = WGSLTokenizer::new(512, false);
+        let (filtered, report) = dataset.filter_by_length(&tokenizer, 8, LengthPolicy::Drop);
+
+        assert_eq!(filtered.len(), 1);
+        assert_eq!(report.dropped, vec![0]);
+        assert!(report.truncated.is_empty());
+        assert!(report.longest > 8);
+    }
+
+    #[test]
+    fn test_filter_by_length_truncate() {
+        let dataset = sample_dataset();
+        let tokenizer = WGSLTokenizer::new(512, false);
+        let (filtered, report) = dataset.filter_by_length(&tokenizer, 8, LengthPolicy::Truncate);
+
+        assert_eq!(filtered.len(), 2);
+        assert_eq!(report.truncated, vec![0]);
+        assert_eq!(tokenizer.tokenize(&filtered.examples[0].wgsl_code).len(), 8);
+        assert_eq!(filtered.examples[0].category.as_deref(), Some("fragment"));
+    }
+
+    #[test]
+    fn test_metadata_filters() {
+        let dataset = sample_dataset();
+
+        assert_eq!(dataset.filter_by_category("fragment").len(), 1);
+        assert_eq!(dataset.filter_by_category(UNCATEGORIZED).len(), 1);
+        assert_eq!(dataset.filter_by_tag("broken").len(), 1);
+        assert_eq!(dataset.filter_by_max_difficulty(Difficulty::Easy).len(), 1);
+        assert_eq!(dataset.categories(), vec!["fragment", UNCATEGORIZED]);
+    }
+
+    #[test]
+    fn test_content_hash() {
+        let dataset = WGSLDataset {
+            examples: vec![
+                WGSLExample::new("a", "fn a() {}"),
+                WGSLExample::new("b", "fn b() {}"),
+            ],
+        };
+        let hash = dataset.content_hash();
+        assert_eq!(hash.len(), 64);
+        assert_eq!(hash, dataset.clone().content_hash());
+
+        let mut reordered = dataset.clone();
+        reordered.examples.reverse();
+        assert_ne!(reordered.content_hash(), hash);
+        // The separator keeps prompt/code boundaries significant
+        let shifted = WGSLDataset {
+            examples: vec![WGSLExample::new("ab", "c")],
+        };
+        let other = WGSLDataset {
+            examples: vec![WGSLExample::new("a", "bc")],
+        };
+        assert_ne!(shifted.content_hash(), other.content_hash());
+    }
+
+    #[test]
+    fn test_k_fold() {
+        let dataset = WGSLDataset {
+            examples: (0..7)
+                .map(|i| WGSLExample::new(format!("prompt {}", i), "fn main() { }"))
+                .collect(),
+        };
+        let folds = dataset.k_fold(3).unwrap();
+
+        let sizes: Vec<usize> = folds.iter().map(|(_, held_out)| held_out.len()).collect();
+        assert_eq!(sizes, vec![3, 2, 2]);
+        for (train, held_out) in &folds {
+            assert_eq!(train.len() + held_out.len(), 7);
+            assert!(held_out.examples.iter().all(|e| train
+                .examples
+                .iter()
+                .all(|t| t.natural_language != e.natural_language)));
+        }
+        assert_eq!(folds[1].1.examples[0].natural_language, "prompt 3");
+        assert!(dataset.k_fold(1).is_err());
+        assert!(dataset.k_fold(8).is_err());
+    }
+
+    #[test]
+    fn test_merge_deduplicates() {
+        let a = WGSLDataset {
+            examples: vec![
+                WGSLExample::new("Red", "vec4<f32>(1.0, 0.0, 0.0, 1.0)"),
+                WGSLExample::new("Red", "vec4<f32>(1.0,  0.0, 0.0, 1.0)"),
+            ],
+        };
+        let b = WGSLDataset {
+            examples: vec![
+                WGSLExample::new(" Red", "vec4<f32>(1.0, 0.0, 0.0, 1.0)\n"),
+                WGSLExample::new("Blue", "vec4<f32>(0.0, 0.0, 1.0, 1.0)"),
+            ],
+        };
+
+        assert_eq!(a.dedup().len(), 1);
+        let merged = a.merge(&b);
+        assert_eq!(merged.len(), 2);
+        assert_eq!(merged.examples[1].natural_language, "Blue");
+    }
+
+    #[test]
+    fn test_diff() {
+        let a = WGSLDataset {
+            examples: vec![
+                WGSLExample::new("Red", "vec4<f32>(1.0, 0.0, 0.0, 1.0)"),
+                WGSLExample::new("Green", "vec4<f32>(0.0, 1.0, 0.0, 1.0)"),
+                WGSLExample::new("Clamp", "clamp(x, 0.0, 1.0)"),
+            ],
+        };
+        let b = WGSLDataset {
+            examples: vec![
+                WGSLExample::new("Red", "vec4<f32>(1.0, 0.0, 0.0, 1.0)"),
+                WGSLExample::new("Green", "vec4<f32>(0.0, 0.9, 0.0, 1.0)"),
+                WGSLExample::new("Blue", "vec4<f32>(0.0, 0.0, 1.0, 1.0)"),
+            ],
+        };
+
+        let diff = a.diff(&b);
+        assert_eq!(diff.common, 1);
+        assert_eq!(diff.changed.len(), 1);
+        assert_eq!(diff.changed[0].0.natural_language, "Green");
+        assert_eq!(diff.only_in_self[0].natural_language, "Clamp");
+        assert_eq!(diff.only_in_other[0].natural_language, "Blue");
+        assert!(a.diff(&a).is_empty());
+
+        // Duplicate prompts pair once; the extra example stays unpaired
+        let ours = WGSLDataset {
+            examples: vec![WGSLExample::new("A", "c0")],
+        };
+        let theirs = WGSLDataset {
+            examples: vec![WGSLExample::new("A", "c1"), WGSLExample::new("A", "c2")],
+        };
+        let diff = ours.diff(&theirs);
+        assert_eq!(diff.changed.len(), 1);
+        assert_eq!(diff.changed[0].1.wgsl_code, "c1");
+        assert!(diff.only_in_self.is_empty());
+        assert_eq!(diff.only_in_other.len(), 1);
+        assert_eq!(diff.only_in_other[0].wgsl_code, "c2");
+    }
+
+    #[test]
+    fn test_file_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let dataset = sample_dataset();
+
+        for name in ["data.toml", "data.json", "data.jsonl"] {
+            let path = dir.path().join(name);
+            dataset.to_file(&path).unwrap();
+            let loaded = WGSLDataset::from_file(&path).unwrap();
+            assert_eq!(loaded.len(), dataset.len());
+            assert_eq!(loaded.examples[0].category.as_deref(), Some("fragment"));
+        }
+
+        assert!(dataset.to_file(dir.path().join("data.csv")).is_err());
+
+        let path = dir.path().join("broken.jsonl");
+        std::fs::write(&path, "\n{\"natural_language\": \"a\"}\n").unwrap();
+        match WGSLDataset::from_file(&path) {
+            Err(crate::Error::DatasetError {
+                path: error_path,
+                line,
+                message,
+            }) => {
+                assert_eq!((error_path, line), (path, Some(2)));
+                assert!(message.contains("wgsl_code"), "{}", message);
+            }
+            other => panic!("expected a dataset error, got {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn test_metadata_is_optional() {
+        let data = r#"
+            [[examples]]
+            natural_language = "Red"
+            wgsl_code = "vec4<f32>(1.0, 0.0, 0.0, 1.0)"
+
+            [[examples]]
+            natural_language = "Blue"
+            wgsl_code = "vec4<f32>(0.0, 0.0, 1.0, 1.0)"
+            category = "color"
+            difficulty = "easy"
+            tags = ["snippet"]
+        "#;
+
+        #[derive(Deserialize)]
+        struct DatasetFile {
+            examples: Vec<WGSLExample>,
+        }
+        let file: DatasetFile = toml::from_str(data).unwrap();
+
+        assert!(file.examples[0].category.is_none());
+        assert_eq!(file.examples[1].category_or_default(), "color");
+        assert_eq!(file.examples[1].difficulty, Some(Difficulty::Easy));
+        assert!(file.examples[1].has_tag("snippet"));
+    }
+}
@@ -0,0 +1,201 @@
+//! Summary statistics of a dataset: sizes, token lengths, categories,
+//! duplicates and validity
+
+use super::WGSLDataset;
+use crate::tokenizer::WGSLTokenizer;
+use crate::wgsl::WGSLValidator;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Examples whose length falls in `[start, end)` tokens
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LengthBucket {
+    pub start: usize,
+    pub end: usize,
+    pub count: usize,
+}
+
+/// Distribution of token lengths
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LengthStats {
+    pub min: usize,
+    pub max: usize,
+    pub mean: f64,
+    /// Power-of-two buckets from 0 up to the longest length; empty buckets
+    /// are kept so the histogram has no gaps
+    pub histogram: Vec<LengthBucket>,
+}
+
+impl LengthStats {
+    pub fn from_lengths(lengths: &[usize]) -> Self {
+        let max = lengths.iter().copied().max().unwrap_or(0);
+        let mut histogram = Vec::new();
+        let (mut start, mut end) = (0, 8);
+        while start <= max && !lengths.is_empty() {
+            let count = lengths.iter().filter(|&&l| l >= start && l < end).count();
+            histogram.push(LengthBucket { start, end, count });
+            (start, end) = (end, end * 2);
+        }
+        Self {
+            min: lengths.iter().copied().min().unwrap_or(0),
+            max,
+            mean: if lengths.is_empty() {
+                0.0
+            } else {
+                lengths.iter().sum::<usize>() as f64 / lengths.len() as f64
+            },
+            histogram,
+        }
+    }
+
+    fn print(&self, title: &str) {
+        println!(
+            "📏 {}: min {}, mean {:.1}, max {} tokens",
+            title, self.min, self.mean, self.max
+        );
+        let most = self.histogram.iter().map(|b| b.count).max().unwrap_or(0);
+        for bucket in &self.histogram {
+            let bar = if most == 0 {
+                0
+            } else {
+                (bucket.count * 30).div_ceil(most)
+            };
+            println!(
+                "   {:>5}-{:<5} {:>5}  {}",
+                bucket.start,
+                bucket.end - 1,
+                bucket.count,
+                "█".repeat(bar)
+            );
+        }
+    }
+}
+
+/// Overview of a dataset
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DatasetStats {
+    pub examples: usize,
+    /// Examples repeating an earlier one (same prompt, same code up to
+    /// whitespace)
+    pub duplicates: usize,
+    /// Example count per category
+    pub categories: BTreeMap<String, usize>,
+    pub prompt_tokens: LengthStats,
+    pub code_tokens: LengthStats,
+    /// Examples whose code passes validation
+    pub valid: usize,
+}
+
+impl DatasetStats {
+    /// Fraction of examples with valid code; 0 for an empty dataset
+    pub fn validity_rate(&self) -> f64 {
+        if self.examples == 0 {
+            0.0
+        } else {
+            self.valid as f64 / self.examples as f64
+        }
+    }
+
+    pub fn print(&self) {
+        println!("📊 Dataset statistics");
+        println!("   Examples: {}", self.examples);
+        println!("   Duplicates: {}", self.duplicates);
+        println!(
+            "   Valid: {}/{} ({:.1}%)",
+            self.valid,
+            self.examples,
+            self.validity_rate() * 100.0
+        );
+        println!("\n🏷️  Categories:");
+        let mut categories: Vec<_> = self.categories.iter().collect();
+        categories.sort_by_key(|(_, &count)| std::cmp::Reverse(count));
+        for (category, count) in categories {
+            println!(
+                "   {}: {} ({:.1}%)",
+                category,
+                count,
+                *count as f64 / self.examples.max(1) as f64 * 100.0
+            );
+        }
+        println!();
+        self.prompt_tokens.print("Prompt length");
+        println!();
+        self.code_tokens.print("Code length");
+    }
+}
+
+impl WGSLDataset {
+    /// Counts, token length distributions (as split by `tokenizer`),
+    /// categories, duplicates and validity under `validator`
+    pub fn stats(
+        &self,
+        tokenizer: &WGSLTokenizer,
+        validator: &WGSLValidator,
+    ) -> crate::Result<DatasetStats> {
+        let lengths = |text: fn(&super::WGSLExample) -> &str| -> Vec<usize> {
+            self.examples
+                .iter()
+                .map(|example| tokenizer.tokenize(text(example)).len())
+                .collect()
+        };
+        let mut categories = BTreeMap::new();
+        for example in &self.examples {
+            *categories
+                .entry(example.category_or_default().to_string())
+                .or_default() += 1;
+        }
+        Ok(DatasetStats {
+            examples: self.len(),
+            duplicates: self.len() - self.dedup().len(),
+            categories,
+            prompt_tokens: LengthStats::from_lengths(&lengths(|e| &e.natural_language)),
+            code_tokens: LengthStats::from_lengths(&lengths(|e| &e.wgsl_code)),
+            valid: self.validate(validator)?.valid_count(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dataset::WGSLExample;
+    use crate::wgsl::ChromaticTemplate;
+
+    #[test]
+    fn test_length_stats() {
+        let stats = LengthStats::from_lengths(&[3, 9, 12, 20]);
+        assert_eq!((stats.min, stats.max, stats.mean), (3, 20, 11.0));
+        let counts: Vec<_> = stats
+            .histogram
+            .iter()
+            .map(|b| (b.start, b.end, b.count))
+            .collect();
+        assert_eq!(counts, vec![(0, 8, 1), (8, 16, 2), (16, 32, 1)]);
+        assert!(LengthStats::from_lengths(&[]).histogram.is_empty());
+    }
+
+    #[test]
+    fn test_dataset_stats() {
+        let mut compute = WGSLExample::new("mix colors", ChromaticTemplate::mix());
+        compute.category = Some("chromatic".to_string());
+        let dataset = WGSLDataset {
+            examples: vec![
+                compute.clone(),
+                compute,
+                WGSLExample::new("broken", "fn main( {"),
+            ],
+        };
+        let stats = dataset
+            .stats(&WGSLTokenizer::new(512, false), &WGSLValidator::new())
+            .unwrap();
+
+        assert_eq!(stats.examples, 3);
+        assert_eq!(stats.duplicates, 1);
+        assert_eq!(stats.valid, 2);
+        assert!((stats.validity_rate() - 2.0 / 3.0).abs() < 1e-9);
+        assert_eq!(stats.categories["chromatic"], 2);
+        assert_eq!(stats.categories["uncategorized"], 1);
+        assert_eq!(stats.prompt_tokens.max, 2);
+        assert!(stats.code_tokens.max > 10);
+    }
+}
@@ -0,0 +1,120 @@
+//! Compute device selected by the `[device]` config section
+//!
+//! A [`Device`] owns the thread pool model math runs on and the memory budget
+//! models and training state must fit in.
+
+use crate::config::DeviceConfig;
+use std::fmt;
+use std::sync::Arc;
+
+/// Bytes in a MiB, the unit of `device.memory_limit_mb`
+const MIB: u64 = 1 << 20;
+
+/// Thread pool and memory budget work runs with
+#[derive(Debug, Clone)]
+pub struct Device {
+    /// Dedicated pool; `None` runs on rayon's global pool
+    pool: Option<Arc<rayon::ThreadPool>>,
+    memory_limit: Option<u64>,
+}
+
+impl Default for Device {
+    fn default() -> Self {
+        Self::cpu()
+    }
+}
+
+impl Device {
+    /// Rayon's global pool, without a memory limit
+    pub fn cpu() -> Self {
+        Self {
+            pool: None,
+            memory_limit: None,
+        }
+    }
+
+    /// Open the device `config` describes
+    pub fn from_config(config: &DeviceConfig) -> crate::Result<Self> {
+        let errors = config.validation_errors();
+        if !errors.is_empty() {
+            return Err(crate::Error::ConfigError(errors.join("; ")));
+        }
+        let pool = match config.threads {
+            0 => None,
+            threads => Some(Arc::new(
+                rayon::ThreadPoolBuilder::new()
+                    .num_threads(threads)
+                    .build()
+                    .map_err(|e| crate::Error::Other(format!("Failed to start threads: {}", e)))?,
+            )),
+        };
+        Ok(Self {
+            pool,
+            memory_limit: config.memory_limit_mb.map(|mb| mb * MIB),
+        })
+    }
+
+    /// Threads parallel work is spread over
+    pub fn threads(&self) -> usize {
+        match &self.pool {
+            Some(pool) => pool.current_num_threads(),
+            None => rayon::current_num_threads(),
+        }
+    }
+
+    /// Run `op` with its rayon parallelism on this device's threads
+    pub fn install<R: Send>(&self, op: impl FnOnce() -> R + Send) -> R {
+        match &self.pool {
+            Some(pool) => pool.install(op),
+            None => op(),
+        }
+    }
+
+    /// Fail when `bytes` of `what` exceed the memory limit
+    pub fn check_memory(&self, what: &str, bytes: u64) -> crate::Result<()> {
+        match self.memory_limit {
+            Some(limit) if bytes > limit => Err(crate::Error::ConfigError(format!(
+                "{} needs {:.1} MiB, over the device memory limit of {} MiB",
+                what,
+                bytes as f64 / MIB as f64,
+                limit / MIB
+            ))),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl fmt::Display for Device {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "cpu, {} threads", self.threads())?;
+        if let Some(limit) = self.memory_limit {
+            write!(f, ", {} MiB limit", limit / MIB)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cpu_device() {
+        let device = Device::from_config(&DeviceConfig {
+            threads: 2,
+            memory_limit_mb: Some(1),
+        })
+        .unwrap();
+        assert_eq!(device.threads(), 2);
+        assert_eq!(device.install(rayon::current_num_threads), 2);
+        assert!(device.check_memory("model", MIB).is_ok());
+        assert!(device.check_memory("model", MIB + 1).is_err());
+        assert!(device.to_string().starts_with("cpu, 2 threads"));
+
+        let invalid = DeviceConfig {
+            memory_limit_mb: Some(0),
+            ..Default::default()
+        };
+        assert!(Device::from_config(&invalid).is_err());
+    }
+}
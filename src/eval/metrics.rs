@@ -0,0 +1,163 @@
+//! BLEU and CodeBLEU scores for generated WGSL
+//!
+//! Both scores compare token sequences produced by [`WGSLTokenizer`]. CodeBLEU
+//! follows Ren et al. (2020) without the data-flow component: it averages plain
+//! BLEU, a keyword-weighted n-gram match and the structural similarity of the
+//! two naga modules.
+
+use crate::tokenizer::WGSLTokenizer;
+use crate::wgsl::compare;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Longest n-gram considered by BLEU
+pub const MAX_NGRAM: usize = 4;
+
+/// Weight of WGSL keywords, attributes and types in the weighted n-gram match
+const KEYWORD_WEIGHT: f64 = 5.0;
+
+const KEYWORDS: &str = "fn var let const override struct alias if else for while loop break \
+    continue continuing return switch case default discard storage uniform workgroup private \
+    function read read_write write bool i32 u32 f32 f16 vec2 vec3 vec4 mat2x2 mat3x3 mat4x4 \
+    array atomic ptr sampler texture_2d";
+
+/// Sentence-level BLEU-4 with add-one smoothing for n > 1
+///
+/// Returns a score in `[0, 1]`; identical non-empty sequences score 1.0.
+pub fn bleu<S: AsRef<str>>(reference: &[S], candidate: &[S]) -> f64 {
+    let reference: Vec<&str> = reference.iter().map(AsRef::as_ref).collect();
+    let candidate: Vec<&str> = candidate.iter().map(AsRef::as_ref).collect();
+    score(&reference, &candidate, |_| 1.0)
+}
+
+/// CodeBLEU components and their average
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct CodeBleu {
+    pub bleu: f64,
+    /// BLEU with WGSL keywords weighted more heavily in unigram matches
+    pub weighted_ngram: f64,
+    /// Structural similarity of the naga IR (0 when either side does not parse)
+    pub structure: f64,
+    /// Mean of the three components
+    pub score: f64,
+}
+
+/// CodeBLEU of `candidate` against `reference` WGSL source
+pub fn code_bleu(reference: &str, candidate: &str) -> CodeBleu {
+    let tokenizer = WGSLTokenizer::new(usize::MAX, false);
+    let reference_tokens = tokenizer.tokenize(reference);
+    let candidate_tokens = tokenizer.tokenize(candidate);
+    let reference_tokens: Vec<&str> = reference_tokens.iter().map(String::as_str).collect();
+    let candidate_tokens: Vec<&str> = candidate_tokens.iter().map(String::as_str).collect();
+
+    let bleu = score(&reference_tokens, &candidate_tokens, |_| 1.0);
+    let weighted_ngram = score(&reference_tokens, &candidate_tokens, keyword_weight);
+    let structure = compare(reference, candidate).unwrap_or(0.0);
+
+    CodeBleu {
+        bleu,
+        weighted_ngram,
+        structure,
+        score: (bleu + weighted_ngram + structure) / 3.0,
+    }
+}
+
+fn keyword_weight(token: &str) -> f64 {
+    // Parameterized types such as `vec3<f32>` are a single token
+    let base = token.split('<').next().unwrap_or(token);
+    if token.starts_with('@') || KEYWORDS.split_whitespace().any(|k| k == base) {
+        KEYWORD_WEIGHT
+    } else {
+        1.0
+    }
+}
+
+/// BLEU with per-token weights applied to unigram precision
+fn score(reference: &[&str], candidate: &[&str], weight: fn(&str) -> f64) -> f64 {
+    if candidate.is_empty() || reference.is_empty() {
+        return if candidate.is_empty() && reference.is_empty() {
+            1.0
+        } else {
+            0.0
+        };
+    }
+
+    let mut log_precision = 0.0;
+    for n in 1..=MAX_NGRAM {
+        let candidate_counts = ngrams(candidate, n);
+        let reference_counts = ngrams(reference, n);
+        let gram_weight = |gram: &[&str]| if n == 1 { weight(gram[0]) } else { 1.0 };
+
+        let (mut matched, mut total) = (0.0, 0.0);
+        for (gram, &count) in &candidate_counts {
+            let clipped = count.min(reference_counts.get(gram).copied().unwrap_or(0));
+            matched += gram_weight(gram) * clipped as f64;
+            total += gram_weight(gram) * count as f64;
+        }
+        let precision = match n {
+            1 if matched == 0.0 => return 0.0,
+            1 => matched / total,
+            _ => (matched + 1.0) / (total + 1.0),
+        };
+        log_precision += precision.ln() / MAX_NGRAM as f64;
+    }
+
+    let brevity_penalty = if candidate.len() >= reference.len() {
+        1.0
+    } else {
+        (1.0 - reference.len() as f64 / candidate.len() as f64).exp()
+    };
+    brevity_penalty * log_precision.exp()
+}
+
+fn ngrams<'a>(tokens: &[&'a str], n: usize) -> HashMap<Vec<&'a str>, usize> {
+    let mut counts = HashMap::new();
+    for window in tokens.windows(n) {
+        *counts.entry(window.to_vec()).or_default() += 1;
+    }
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wgsl::ChromaticTemplate;
+
+    fn words(text: &str) -> Vec<&str> {
+        text.split_whitespace().collect()
+    }
+
+    #[test]
+    fn test_bleu() {
+        let reference = words("the cat sat on the mat today");
+        assert!((bleu(&reference, &reference) - 1.0).abs() < 1e-12);
+        assert_eq!(bleu(&reference, &words("dogs run fast")), 0.0);
+        assert_eq!(bleu::<&str>(&[], &[]), 1.0);
+
+        let close = bleu(&reference, &words("the cat sat on a mat today"));
+        let far = bleu(&reference, &words("a cat is on the mat"));
+        assert!(close > far && far > 0.0, "{} {}", close, far);
+
+        // Short candidates are penalized even when every n-gram matches
+        let short = bleu(&reference, &words("the cat sat"));
+        assert!(short < close);
+    }
+
+    #[test]
+    fn test_code_bleu() {
+        let mix = ChromaticTemplate::mix();
+        let perfect = code_bleu(&mix, &mix);
+        assert!((perfect.score - 1.0).abs() < 1e-12);
+
+        // Renaming keeps keywords and structure but changes plain tokens
+        let renamed = mix.replace("tensor_a", "lhs").replace("tensor_b", "rhs");
+        let renamed = code_bleu(&mix, &renamed);
+        assert_eq!(renamed.structure, 1.0);
+        assert!(renamed.weighted_ngram > renamed.bleu);
+        assert!(renamed.score < 1.0);
+
+        let broken = code_bleu(&mix, "fn main( {");
+        assert_eq!(broken.structure, 0.0);
+        assert!(broken.score < renamed.score);
+    }
+}
@@ -0,0 +1,640 @@
+//! Evaluation of generated WGSL against held-out reference examples
+//!
+//! Every example's prompt is run through a generator and the output is scored
+//! against the reference code:
+//!
+//! - **validity**: the generated code passes naga validation
+//! - **exact match**: generated and reference tokens are identical, so
+//!   whitespace and formatting differences do not count
+//! - **token accuracy**: position-wise token matches divided by the longer of
+//!   the two sequences
+//! - **edit distance**: token-level Levenshtein distance
+//! - **BLEU / CodeBLEU**: n-gram overlap, see [`metrics`]
+//!
+//! [`pass_at_k`] additionally scores several sampled generations per prompt,
+//! and [`regression`] checks a model's outputs against golden generations.
+//! Every metric is also broken down by example category.
+
+pub mod metrics;
+pub mod pass_at_k;
+pub mod regression;
+
+pub use metrics::{bleu, code_bleu, CodeBleu};
+pub use pass_at_k::{PassAtK, PassAtKReport};
+pub use regression::{GoldenSuite, RegressionReport};
+
+use crate::dataset::{WGSLDataset, UNCATEGORIZED};
+use crate::inference::{GenerationOptions, GeneratorBackend};
+use crate::model::CodeGenerationModel;
+use crate::progress;
+use crate::tokenizer::WGSLTokenizer;
+use crate::training::{perplexity, Perplexity};
+use crate::wgsl::WGSLValidator;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Scores for a single example
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExampleEvaluation {
+    pub prompt: String,
+    /// Category of the dataset example
+    #[serde(default = "uncategorized")]
+    pub category: String,
+    pub reference: String,
+    pub generated: String,
+    pub is_valid: bool,
+    pub exact_match: bool,
+    pub token_accuracy: f64,
+    pub edit_distance: usize,
+    pub bleu: f64,
+    pub code_bleu: f64,
+}
+
+/// Aggregate metrics over an evaluation set
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct EvalMetrics {
+    pub count: usize,
+    /// Fraction of generations that pass naga validation
+    pub validity_rate: f64,
+    /// Fraction of generations token-identical to the reference
+    pub exact_match_rate: f64,
+    /// Mean position-wise token accuracy
+    pub token_accuracy: f64,
+    /// Mean token-level edit distance to the reference
+    pub avg_edit_distance: f64,
+    /// Mean sentence-level BLEU-4
+    #[serde(default)]
+    pub bleu: f64,
+    /// Mean CodeBLEU score
+    #[serde(default)]
+    pub code_bleu: f64,
+}
+
+impl EvalMetrics {
+    /// Average the per-example scores
+    pub fn from_examples(examples: &[ExampleEvaluation]) -> Self {
+        if examples.is_empty() {
+            return Self::default();
+        }
+        let n = examples.len() as f64;
+        let rate = |hit: fn(&ExampleEvaluation) -> bool| {
+            examples.iter().filter(|e| hit(e)).count() as f64 / n
+        };
+        let mean =
+            |value: fn(&ExampleEvaluation) -> f64| examples.iter().map(value).sum::<f64>() / n;
+
+        Self {
+            count: examples.len(),
+            validity_rate: rate(|e| e.is_valid),
+            exact_match_rate: rate(|e| e.exact_match),
+            token_accuracy: mean(|e| e.token_accuracy),
+            avg_edit_distance: mean(|e| e.edit_distance as f64),
+            bleu: mean(|e| e.bleu),
+            code_bleu: mean(|e| e.code_bleu),
+        }
+    }
+}
+
+fn uncategorized() -> String {
+    UNCATEGORIZED.to_string()
+}
+
+/// Metrics restricted to the examples of one category
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CategoryBreakdown {
+    pub metrics: EvalMetrics,
+    /// Mean pass@k keyed by k, when sampling was evaluated
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub pass_at_k: BTreeMap<usize, f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub perplexity: Option<Perplexity>,
+}
+
+/// Evaluation results, per example and aggregated
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EvalReport {
+    pub metrics: EvalMetrics,
+    pub examples: Vec<ExampleEvaluation>,
+    /// Present when sampled generations were scored as well
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pass_at_k: Option<PassAtKReport>,
+    /// Present when reference likelihood was computed as well
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub perplexity: Option<Perplexity>,
+    /// Every metric broken down by example category
+    #[serde(default)]
+    pub categories: BTreeMap<String, CategoryBreakdown>,
+}
+
+impl EvalReport {
+    /// Build a report from scored examples, aggregating overall and per category
+    pub fn from_examples(examples: Vec<ExampleEvaluation>) -> Self {
+        let mut grouped: BTreeMap<String, Vec<ExampleEvaluation>> = BTreeMap::new();
+        for example in &examples {
+            grouped
+                .entry(example.category.clone())
+                .or_default()
+                .push(example.clone());
+        }
+        let categories = grouped
+            .into_iter()
+            .map(|(category, examples)| {
+                let breakdown = CategoryBreakdown {
+                    metrics: EvalMetrics::from_examples(&examples),
+                    ..Default::default()
+                };
+                (category, breakdown)
+            })
+            .collect();
+
+        Self {
+            metrics: EvalMetrics::from_examples(&examples),
+            examples,
+            pass_at_k: None,
+            perplexity: None,
+            categories,
+        }
+    }
+
+    /// Attach pass@k results, overall and per category
+    pub fn with_pass_at_k(mut self, report: PassAtKReport) -> Self {
+        for (category, scores) in report.by_category() {
+            self.categories.entry(category).or_default().pass_at_k = scores;
+        }
+        self.pass_at_k = Some(report);
+        self
+    }
+
+    /// Compute the perplexity of `dataset`'s reference code, overall and per
+    /// category
+    pub fn with_perplexity(
+        mut self,
+        model: &CodeGenerationModel,
+        tokenizer: &WGSLTokenizer,
+        dataset: &WGSLDataset,
+    ) -> crate::Result<Self> {
+        self.perplexity = Some(perplexity(model, tokenizer, dataset)?);
+        for (category, examples) in dataset.group_by_category() {
+            self.categories.entry(category).or_default().perplexity =
+                Some(perplexity(model, tokenizer, &examples)?);
+        }
+        Ok(self)
+    }
+
+    /// Print the aggregate metrics
+    pub fn print(&self) {
+        let m = &self.metrics;
+        println!("📊 Evaluation ({} examples)", m.count);
+        println!("   Validity rate:     {:.1}%", m.validity_rate * 100.0);
+        println!("   Exact match rate:  {:.1}%", m.exact_match_rate * 100.0);
+        println!("   Token accuracy:    {:.1}%", m.token_accuracy * 100.0);
+        println!("   Avg edit distance: {:.2}", m.avg_edit_distance);
+        println!("   BLEU:              {:.3}", m.bleu);
+        println!("   CodeBLEU:          {:.3}", m.code_bleu);
+        if let Some(p) = &self.perplexity {
+            println!(
+                "   Perplexity:        {:.3} (NLL {:.4} over {} tokens)",
+                p.perplexity, p.nll, p.tokens
+            );
+        }
+        if let Some(pass_at_k) = &self.pass_at_k {
+            println!();
+            pass_at_k.print();
+        }
+
+        if self.categories.len() > 1 {
+            println!("\n📂 By category");
+            let width = self
+                .categories
+                .keys()
+                .map(String::len)
+                .chain(std::iter::once("Category".len()))
+                .max()
+                .unwrap_or(0);
+            let (header, _) = self.category_columns();
+            println!(
+                "   {:<width$}  {}",
+                "Category",
+                header.join("  "),
+                width = width
+            );
+            for (category, breakdown) in &self.categories {
+                let cells = self.category_cells(breakdown);
+                let cells: Vec<String> = cells
+                    .iter()
+                    .zip(&header)
+                    .map(|(cell, title)| format!("{:>w$}", cell, w = title.len()))
+                    .collect();
+                println!(
+                    "   {:<width$}  {}",
+                    category,
+                    cells.join("  "),
+                    width = width
+                );
+            }
+        }
+    }
+
+    /// Column titles of the category table and the pass@k values shown
+    fn category_columns(&self) -> (Vec<String>, Vec<usize>) {
+        let mut header: Vec<String> = ["N", "Valid", "Exact", "Tok acc", "Edit", "CodeBLEU"]
+            .iter()
+            .map(|title| format!("{:>8}", title))
+            .collect();
+        if self.perplexity.is_some() {
+            header.push(format!("{:>8}", "PPL"));
+        }
+        let ks: Vec<usize> = self
+            .pass_at_k
+            .iter()
+            .flat_map(|report| report.pass_at_k.keys().copied())
+            .collect();
+        header.extend(ks.iter().map(|k| format!("{:>8}", format!("pass@{}", k))));
+        (header, ks)
+    }
+
+    fn category_cells(&self, breakdown: &CategoryBreakdown) -> Vec<String> {
+        let m = &breakdown.metrics;
+        let mut cells = vec![
+            m.count.to_string(),
+            format!("{:.1}%", m.validity_rate * 100.0),
+            format!("{:.1}%", m.exact_match_rate * 100.0),
+            format!("{:.1}%", m.token_accuracy * 100.0),
+            format!("{:.2}", m.avg_edit_distance),
+            format!("{:.3}", m.code_bleu),
+        ];
+        if self.perplexity.is_some() {
+            cells.push(
+                breakdown
+                    .perplexity
+                    .map(|p| format!("{:.3}", p.perplexity))
+                    .unwrap_or_else(|| "-".to_string()),
+            );
+        }
+        let (_, ks) = self.category_columns();
+        cells.extend(ks.iter().map(|k| {
+            breakdown
+                .pass_at_k
+                .get(k)
+                .map(|score| format!("{:.1}%", score * 100.0))
+                .unwrap_or_else(|| "-".to_string())
+        }));
+        cells
+    }
+
+    /// Render the report as a Markdown document
+    pub fn to_markdown(&self) -> String {
+        let m = &self.metrics;
+        let mut out = String::from("# Evaluation Report\n\n");
+        out.push_str("| Metric | Value |\n|--------|-------|\n");
+        out.push_str(&format!("| Examples | {} |\n", m.count));
+        out.push_str(&format!(
+            "| Validity rate | {:.1}% |\n",
+            m.validity_rate * 100.0
+        ));
+        out.push_str(&format!(
+            "| Exact match rate | {:.1}% |\n",
+            m.exact_match_rate * 100.0
+        ));
+        out.push_str(&format!(
+            "| Token accuracy | {:.1}% |\n",
+            m.token_accuracy * 100.0
+        ));
+        out.push_str(&format!(
+            "| Avg edit distance | {:.2} |\n",
+            m.avg_edit_distance
+        ));
+        out.push_str(&format!("| BLEU | {:.3} |\n", m.bleu));
+        out.push_str(&format!("| CodeBLEU | {:.3} |\n", m.code_bleu));
+        if let Some(p) = &self.perplexity {
+            out.push_str(&format!("| Perplexity | {:.3} |\n", p.perplexity));
+            out.push_str(&format!("| NLL per token | {:.4} |\n", p.nll));
+        }
+        if let Some(pass_at_k) = &self.pass_at_k {
+            for (k, score) in &pass_at_k.pass_at_k {
+                out.push_str(&format!(
+                    "| pass@{} ({} samples) | {:.1}% |\n",
+                    k,
+                    pass_at_k.samples,
+                    score * 100.0
+                ));
+            }
+        }
+
+        let (header, _) = self.category_columns();
+        let header: Vec<&str> = header.iter().map(|title| title.trim()).collect();
+        out.push_str("\n## By Category\n\n");
+        out.push_str(&format!("| Category | {} |\n", header.join(" | ")));
+        out.push_str(&format!("|----------|{}\n", "------|".repeat(header.len())));
+        for (category, breakdown) in &self.categories {
+            out.push_str(&format!(
+                "| {} | {} |\n",
+                category,
+                self.category_cells(breakdown).join(" | ")
+            ));
+        }
+
+        out.push_str("\n## Examples\n\n");
+        out.push_str(
+            "| Prompt | Category | Valid | Exact | Token acc. | Edit dist. | CodeBLEU |\n",
+        );
+        out.push_str(
+            "|--------|----------|-------|-------|------------|------------|----------|\n",
+        );
+        for e in &self.examples {
+            out.push_str(&format!(
+                "| {} | {} | {} | {} | {:.1}% | {} | {:.3} |\n",
+                e.prompt.replace('|', "\\|").replace('\n', " "),
+                e.category,
+                if e.is_valid { "✅" } else { "❌" },
+                if e.exact_match { "✅" } else { "❌" },
+                e.token_accuracy * 100.0,
+                e.edit_distance,
+                e.code_bleu
+            ));
+        }
+        out
+    }
+
+    /// Write the report as Markdown for `.md` paths and JSON otherwise
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> crate::Result<()> {
+        let path = path.as_ref();
+        let contents = if path.extension().is_some_and(|ext| ext == "md") {
+            self.to_markdown()
+        } else {
+            serde_json::to_string_pretty(self)?
+        };
+        std::fs::write(path, contents)?;
+        Ok(())
+    }
+}
+
+/// Scores generated code against reference examples
+pub struct Evaluator {
+    validator: WGSLValidator,
+    tokenizer: WGSLTokenizer,
+    progress: bool,
+}
+
+impl Evaluator {
+    /// Create an evaluator validating with `validator`
+    pub fn new(validator: WGSLValidator) -> Self {
+        Self {
+            validator,
+            tokenizer: WGSLTokenizer::new(usize::MAX, false),
+            progress: false,
+        }
+    }
+
+    /// Show a progress bar while generating
+    pub fn with_progress(mut self, enabled: bool) -> Self {
+        self.progress = enabled;
+        self
+    }
+
+    /// Score one generation against its reference
+    pub fn score(
+        &self,
+        prompt: &str,
+        reference: &str,
+        generated: &str,
+    ) -> crate::Result<ExampleEvaluation> {
+        let is_valid = self.validator.validate(generated)?.is_valid;
+        let expected = self.tokenizer.tokenize(reference);
+        let actual = self.tokenizer.tokenize(generated);
+        let code = code_bleu(reference, generated);
+
+        Ok(ExampleEvaluation {
+            prompt: prompt.to_string(),
+            category: uncategorized(),
+            reference: reference.to_string(),
+            generated: generated.to_string(),
+            is_valid,
+            exact_match: expected == actual,
+            token_accuracy: token_accuracy(&expected, &actual),
+            edit_distance: edit_distance(&expected, &actual),
+            bleu: code.bleu,
+            code_bleu: code.score,
+        })
+    }
+
+    /// Evaluate any generation function over a dataset
+    pub fn evaluate_with<F>(
+        &self,
+        dataset: &WGSLDataset,
+        mut generate: F,
+    ) -> crate::Result<EvalReport>
+    where
+        F: FnMut(&str) -> crate::Result<String>,
+    {
+        let bar = progress::bar(dataset.len() as u64, "Generating", self.progress);
+        let examples = dataset
+            .examples
+            .iter()
+            .map(|example| {
+                let generated = generate(&example.natural_language)?;
+                let mut evaluation =
+                    self.score(&example.natural_language, &example.wgsl_code, &generated)?;
+                evaluation.category = example.category_or_default().to_string();
+                bar.inc(1);
+                Ok(evaluation)
+            })
+            .collect::<crate::Result<Vec<_>>>()?;
+        bar.finish_and_clear();
+
+        Ok(EvalReport::from_examples(examples))
+    }
+
+    /// Evaluate a model, local or remote, over a dataset
+    pub fn evaluate(
+        &self,
+        generator: &dyn GeneratorBackend,
+        dataset: &WGSLDataset,
+    ) -> crate::Result<EvalReport> {
+        let options = GenerationOptions::default();
+        self.evaluate_with(dataset, |prompt| generator.generate_with(prompt, &options))
+    }
+}
+
+impl Default for Evaluator {
+    fn default() -> Self {
+        Self::new(WGSLValidator::new())
+    }
+}
+
+/// Position-wise matches over the longer sequence; two empty sequences match fully
+pub fn token_accuracy<T: PartialEq>(expected: &[T], actual: &[T]) -> f64 {
+    let longest = expected.len().max(actual.len());
+    if longest == 0 {
+        return 1.0;
+    }
+    let matches = expected.iter().zip(actual).filter(|(a, b)| a == b).count();
+    matches as f64 / longest as f64
+}
+
+/// Levenshtein distance between two sequences
+pub fn edit_distance<T: PartialEq>(a: &[T], b: &[T]) -> usize {
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+
+    for (i, x) in a.iter().enumerate() {
+        current[0] = i + 1;
+        for (j, y) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(x != y);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dataset::WGSLExample;
+    use crate::wgsl::ChromaticTemplate;
+
+    #[test]
+    fn test_sequence_metrics() {
+        assert_eq!(edit_distance(&["a", "b", "c"], &["a", "b", "c"]), 0);
+        assert_eq!(edit_distance(&["a", "b", "c"], &["a", "c"]), 1);
+        assert_eq!(edit_distance::<&str>(&[], &["x", "y"]), 2);
+        assert_eq!(
+            edit_distance(
+                &"kitten".chars().collect::<Vec<_>>(),
+                &"sitting".chars().collect::<Vec<_>>()
+            ),
+            3
+        );
+
+        assert_eq!(token_accuracy::<u8>(&[], &[]), 1.0);
+        assert_eq!(token_accuracy(&[1, 2, 3, 4], &[1, 2, 0, 4]), 0.75);
+        assert_eq!(token_accuracy(&[1, 2], &[1, 2, 3, 4]), 0.5);
+    }
+
+    #[test]
+    fn test_evaluate_with() {
+        let mix = ChromaticTemplate::mix();
+        let mut dataset = WGSLDataset::new();
+        dataset
+            .examples
+            .push(WGSLExample::new("mix colors", mix.clone()));
+        dataset.examples.push(WGSLExample::new(
+            "red fragment",
+            "@fragment fn main() -> @location(0) vec4<f32> { return vec4<f32>(1.0, 0.0, 0.0, 1.0); }",
+        ));
+
+        // Reformatted mix is an exact match; the second prompt gets a broken prefix
+        let report = Evaluator::default()
+            .evaluate_with(&dataset, |prompt| {
+                Ok(if prompt.starts_with("mix") {
+                    mix.replace("\n\n", "\n")
+                } else {
+                    "@fragment fn main( {".to_string()
+                })
+            })
+            .unwrap();
+
+        let m = &report.metrics;
+        assert_eq!(m.count, 2);
+        assert_eq!(m.validity_rate, 0.5);
+        assert_eq!(m.exact_match_rate, 0.5);
+        assert!(m.token_accuracy > 0.5 && m.token_accuracy < 1.0);
+        assert!(report.examples[1].edit_distance > 0);
+        assert!((report.examples[0].code_bleu - 1.0).abs() < 1e-12);
+        assert!(m.bleu > 0.5 && m.bleu < 1.0);
+
+        let dir = tempfile::tempdir().unwrap();
+        report.write(dir.path().join("report.json")).unwrap();
+        report.write(dir.path().join("report.md")).unwrap();
+        let json = std::fs::read_to_string(dir.path().join("report.json")).unwrap();
+        let parsed: EvalReport = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.metrics, report.metrics);
+        let markdown = std::fs::read_to_string(dir.path().join("report.md")).unwrap();
+        assert!(markdown.contains("| Validity rate | 50.0% |"));
+        assert!(markdown.contains("| mix colors | uncategorized | ✅ | ✅ |"));
+    }
+
+    #[test]
+    fn test_category_breakdown() {
+        use crate::model::ModelArchitecture;
+
+        let mut dataset = WGSLDataset::new();
+        for (prompt, code, category) in [
+            ("mix", ChromaticTemplate::mix(), "chromatic"),
+            ("filter", ChromaticTemplate::filter(), "chromatic"),
+            ("broken", "fn main() { }".to_string(), "compute"),
+        ] {
+            let mut example = WGSLExample::new(prompt, code);
+            example.category = Some(category.to_string());
+            dataset.examples.push(example);
+        }
+
+        // Chromatic prompts echo their reference, compute ones fail to parse
+        let references: BTreeMap<String, String> = dataset
+            .examples
+            .iter()
+            .map(|e| (e.natural_language.clone(), e.wgsl_code.clone()))
+            .collect();
+        let generate = |prompt: &str| match prompt {
+            "broken" => "fn main( {".to_string(),
+            _ => references[prompt].clone(),
+        };
+        let report = Evaluator::default()
+            .evaluate_with(&dataset, |prompt| Ok(generate(prompt)))
+            .unwrap();
+        let pass_at_k = PassAtK::new(2)
+            .with_ks(vec![1])
+            .evaluate_with(
+                &dataset,
+                |prompt, n| Ok(vec![generate(prompt); n]),
+                |_, _| Ok(true),
+            )
+            .unwrap();
+
+        let mut tokenizer = WGSLTokenizer::new(256, false);
+        tokenizer.fit(
+            &[references["mix"].as_str(), references["filter"].as_str()],
+            1,
+        );
+        let model = CodeGenerationModel::new(
+            ModelArchitecture::LSTM,
+            tokenizer.vocab_size(),
+            16,
+            2,
+            1,
+            None,
+            None,
+        );
+        let report = report
+            .with_pass_at_k(pass_at_k)
+            .with_perplexity(&model, &tokenizer, &dataset)
+            .unwrap();
+
+        assert_eq!(
+            report.categories.keys().collect::<Vec<_>>(),
+            vec!["chromatic", "compute"]
+        );
+        let chromatic = &report.categories["chromatic"];
+        assert_eq!(chromatic.metrics.count, 2);
+        assert_eq!(chromatic.metrics.validity_rate, 1.0);
+        assert_eq!(chromatic.pass_at_k[&1], 1.0);
+        let compute = &report.categories["compute"];
+        assert_eq!(compute.metrics.exact_match_rate, 0.0);
+        assert_eq!(compute.pass_at_k[&1], 0.0);
+        assert!(compute.perplexity.is_some());
+        assert!((report.metrics.validity_rate - 2.0 / 3.0).abs() < 1e-12);
+
+        let markdown = report.to_markdown();
+        assert!(markdown.contains(
+            "| Category | N | Valid | Exact | Tok acc | Edit | CodeBLEU | PPL | pass@1 |"
+        ));
+        assert!(markdown.contains("| compute | 1 | 0.0% | 0.0% |"));
+
+        let json = serde_json::to_string(&report).unwrap();
+        let parsed: EvalReport = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.categories, report.categories);
+    }
+}
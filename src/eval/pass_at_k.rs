@@ -0,0 +1,308 @@
+//! pass@k over sampled generations
+//!
+//! Each prompt gets `n` sampled generations; a sample passes when it validates
+//! and, optionally, when it computes the same output as the reference shader.
+//! pass@k is the unbiased estimator of Chen et al. (2021): the probability that
+//! at least one of `k` samples drawn without replacement from the `n` passes.
+
+use crate::dataset::{WGSLDataset, WGSLExample};
+use crate::inference::{GenerationOptions, GeneratorBackend};
+use crate::progress;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::wgsl::{ShaderBuffer, ShaderRunner};
+use crate::wgsl::WGSLValidator;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// k values reported by default
+pub const DEFAULT_KS: &[usize] = &[1, 5, 10];
+
+/// Unbiased pass@k for `correct` passing samples out of `n`
+pub fn pass_at_k(n: usize, correct: usize, k: usize) -> f64 {
+    if n.saturating_sub(correct) < k {
+        return 1.0;
+    }
+    let failing_only: f64 = (n - correct + 1..=n)
+        .map(|i| 1.0 - k as f64 / i as f64)
+        .product();
+    1.0 - failing_only
+}
+
+/// Passing sample count for one prompt
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PromptPasses {
+    pub prompt: String,
+    #[serde(default = "super::uncategorized")]
+    pub category: String,
+    pub samples: usize,
+    pub passed: usize,
+}
+
+/// pass@k results over a dataset
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PassAtKReport {
+    /// Samples drawn per prompt
+    pub samples: usize,
+    /// Mean pass@k keyed by k
+    pub pass_at_k: BTreeMap<usize, f64>,
+    pub prompts: Vec<PromptPasses>,
+}
+
+impl PassAtKReport {
+    /// Mean pass@k per category, for the same k values as the overall scores
+    pub fn by_category(&self) -> BTreeMap<String, BTreeMap<usize, f64>> {
+        let mut grouped: BTreeMap<String, Vec<&PromptPasses>> = BTreeMap::new();
+        for prompt in &self.prompts {
+            grouped
+                .entry(prompt.category.clone())
+                .or_default()
+                .push(prompt);
+        }
+        grouped
+            .into_iter()
+            .map(|(category, prompts)| {
+                let scores = self
+                    .pass_at_k
+                    .keys()
+                    .map(|&k| (k, mean_pass_at_k(&prompts, k)))
+                    .collect();
+                (category, scores)
+            })
+            .collect()
+    }
+
+    /// Print pass@k for every k
+    pub fn print(&self) {
+        println!("🎯 pass@k ({} samples per prompt)", self.samples);
+        for (k, score) in &self.pass_at_k {
+            println!("   pass@{:<3} {:.1}%", k, score * 100.0);
+        }
+    }
+}
+
+/// Samples `n` generations per prompt and scores pass@k
+pub struct PassAtK {
+    samples: usize,
+    ks: Vec<usize>,
+    validator: WGSLValidator,
+    progress: bool,
+}
+
+impl PassAtK {
+    /// Draw `samples` generations per prompt, reporting k in [`DEFAULT_KS`]
+    /// that do not exceed `samples`
+    pub fn new(samples: usize) -> Self {
+        Self {
+            samples,
+            ks: DEFAULT_KS
+                .iter()
+                .copied()
+                .filter(|&k| k <= samples)
+                .collect(),
+            validator: WGSLValidator::new(),
+            progress: false,
+        }
+    }
+
+    /// Report these k values instead of the defaults
+    pub fn with_ks(mut self, ks: Vec<usize>) -> Self {
+        self.ks = ks;
+        self
+    }
+
+    /// Use `validator` to decide whether a sample compiles
+    pub fn with_validator(mut self, validator: WGSLValidator) -> Self {
+        self.validator = validator;
+        self
+    }
+
+    /// Show a progress bar while sampling
+    pub fn with_progress(mut self, enabled: bool) -> Self {
+        self.progress = enabled;
+        self
+    }
+
+    /// pass@k where a sample passes when it validates
+    pub fn evaluate(
+        &self,
+        generator: &dyn GeneratorBackend,
+        dataset: &WGSLDataset,
+        options: &GenerationOptions,
+    ) -> crate::Result<PassAtKReport> {
+        self.evaluate_with(
+            dataset,
+            |prompt, n| generator.sample_n(prompt, n, options),
+            |_, _| Ok(true),
+        )
+    }
+
+    /// pass@k with a custom sampler and an extra correctness check run on
+    /// samples that validate
+    pub fn evaluate_with<S, C>(
+        &self,
+        dataset: &WGSLDataset,
+        mut sample: S,
+        mut is_correct: C,
+    ) -> crate::Result<PassAtKReport>
+    where
+        S: FnMut(&str, usize) -> crate::Result<Vec<String>>,
+        C: FnMut(&WGSLExample, &str) -> crate::Result<bool>,
+    {
+        if let Some(&k) = self.ks.iter().find(|&&k| k == 0 || k > self.samples) {
+            return Err(crate::Error::ConfigError(format!(
+                "pass@{} needs 1 <= k <= samples ({})",
+                k, self.samples
+            )));
+        }
+
+        let bar = progress::bar(dataset.len() as u64, "Sampling", self.progress);
+        let mut prompts = Vec::new();
+        for example in &dataset.examples {
+            let mut passed = 0;
+            for code in sample(&example.natural_language, self.samples)? {
+                if self.validator.validate(&code)?.is_valid && is_correct(example, &code)? {
+                    passed += 1;
+                }
+            }
+            prompts.push(PromptPasses {
+                prompt: example.natural_language.clone(),
+                category: example.category_or_default().to_string(),
+                samples: self.samples,
+                passed,
+            });
+            bar.inc(1);
+        }
+        bar.finish_and_clear();
+
+        let all: Vec<&PromptPasses> = prompts.iter().collect();
+        let pass_at_k = self
+            .ks
+            .iter()
+            .map(|&k| (k, mean_pass_at_k(&all, k)))
+            .collect();
+
+        Ok(PassAtKReport {
+            samples: self.samples,
+            pass_at_k,
+            prompts,
+        })
+    }
+}
+
+fn mean_pass_at_k(prompts: &[&PromptPasses], k: usize) -> f64 {
+    let total: f64 = prompts
+        .iter()
+        .map(|p| pass_at_k(p.samples, p.passed, k))
+        .sum();
+    total / prompts.len().max(1) as f64
+}
+
+/// Correctness check that runs the sample and the reference shader on the same
+/// buffers and compares every read-write buffer as `f32` within `tolerance`
+#[cfg(not(target_arch = "wasm32"))]
+pub fn matches_reference<'a>(
+    runner: &'a ShaderRunner,
+    buffers: &'a [ShaderBuffer],
+    workgroups: [u32; 3],
+    tolerance: f32,
+) -> impl FnMut(&WGSLExample, &str) -> crate::Result<bool> + 'a {
+    move |example, code| {
+        let expected = runner.run(&example.wgsl_code, None, buffers, workgroups)?;
+        let Ok(actual) = runner.run(code, None, buffers, workgroups) else {
+            return Ok(false);
+        };
+        Ok(expected.buffers.iter().all(|(&(group, binding), _)| {
+            match (expected.f32(group, binding), actual.f32(group, binding)) {
+                (Some(a), Some(b)) => {
+                    a.len() == b.len() && a.iter().zip(&b).all(|(x, y)| (x - y).abs() <= tolerance)
+                }
+                _ => false,
+            }
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wgsl::ChromaticTemplate;
+
+    #[test]
+    fn test_pass_at_k_estimator() {
+        assert_eq!(pass_at_k(10, 0, 1), 0.0);
+        assert_eq!(pass_at_k(10, 10, 5), 1.0);
+        assert!((pass_at_k(10, 3, 1) - 0.3).abs() < 1e-12);
+        // 1 - C(8,2)/C(10,2) = 1 - 28/45
+        assert!((pass_at_k(10, 2, 2) - 17.0 / 45.0).abs() < 1e-12);
+        // Fewer failures than k means some draw always passes
+        assert_eq!(pass_at_k(10, 8, 5), 1.0);
+    }
+
+    #[test]
+    fn test_evaluate_with() {
+        let mut dataset = WGSLDataset::new();
+        dataset
+            .examples
+            .push(WGSLExample::new("mix", ChromaticTemplate::mix()));
+        dataset
+            .examples
+            .push(WGSLExample::new("filter", ChromaticTemplate::filter()));
+
+        // "mix" gets one valid sample in five, "filter" never compiles
+        let report = PassAtK::new(5)
+            .evaluate_with(
+                &dataset,
+                |prompt, n| {
+                    Ok((0..n)
+                        .map(|i| match (prompt, i) {
+                            ("mix", 0) => ChromaticTemplate::mix(),
+                            _ => "fn main( {".to_string(),
+                        })
+                        .collect())
+                },
+                |_, _| Ok(true),
+            )
+            .unwrap();
+
+        assert_eq!(report.prompts[0].passed, 1);
+        assert_eq!(report.prompts[1].passed, 0);
+        assert_eq!(
+            report.pass_at_k.keys().copied().collect::<Vec<_>>(),
+            vec![1, 5]
+        );
+        assert!((report.pass_at_k[&1] - 0.1).abs() < 1e-12);
+        assert!((report.pass_at_k[&5] - 0.5).abs() < 1e-12);
+
+        assert!(PassAtK::new(5)
+            .with_ks(vec![10])
+            .evaluate_with(&dataset, |_, _| Ok(vec![]), |_, _| Ok(true))
+            .is_err());
+    }
+
+    #[test]
+    fn test_matches_reference() {
+        let Ok(runner) = ShaderRunner::new() else {
+            eprintln!("skipping: no GPU adapter");
+            return;
+        };
+        let reference = r#"@group(0) @binding(0) var<storage, read> input: array<f32>;
+@group(0) @binding(1) var<storage, read_write> output: array<f32>;
+
+@compute @workgroup_size(4)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    output[id.x] = input[id.x] * 2.0;
+}
+"#;
+        let example = WGSLExample::new("double", reference);
+        let buffers = [
+            ShaderBuffer::input_f32(0, 0, &[1.0, 2.0, 3.0, 4.0]),
+            ShaderBuffer::output_f32(0, 1, 4),
+        ];
+        let mut check = matches_reference(&runner, &buffers, [1, 1, 1], 1e-6);
+
+        let same = reference.replace("input[id.x] * 2.0", "input[id.x] + input[id.x]");
+        assert!(check(&example, &same).unwrap());
+        let wrong = reference.replace("2.0", "3.0");
+        assert!(!check(&example, &wrong).unwrap());
+    }
+}
@@ -0,0 +1,267 @@
+//! Regression checks of model outputs against golden generations
+//!
+//! A [`GoldenSuite`] is a fixed list of prompts with the shader a known-good
+//! checkpoint generated for each, kept under version control together with
+//! the decoding options that produced them. [`GoldenSuite::check_with`]
+//! generates again for every prompt and flags the outputs whose token
+//! similarity to the golden one falls below a threshold; at the default of 1
+//! any change of tokens is drift, while layout changes never are.
+
+use super::edit_distance;
+use crate::inference::{GenerationOptions, GeneratorBackend};
+use crate::tokenizer::WGSLTokenizer;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Similarity required by default: token-identical outputs
+pub const DEFAULT_THRESHOLD: f64 = 1.0;
+
+const HEADER: &str = "# Golden generations for `tiny-agent-trainer regress`\n\
+                      # Re-record them with `tiny-agent-trainer regress --update`\n\n";
+
+/// Prompts with their golden generations
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GoldenSuite {
+    /// Token similarity a generation needs to its golden output, in `[0, 1]`
+    pub threshold: f64,
+    /// Decoding options every prompt is generated with; greedy by default,
+    /// so outputs only change with the model
+    pub options: GenerationOptions,
+    pub cases: Vec<GoldenCase>,
+}
+
+impl Default for GoldenSuite {
+    fn default() -> Self {
+        Self {
+            threshold: DEFAULT_THRESHOLD,
+            options: GenerationOptions::default(),
+            cases: Vec::new(),
+        }
+    }
+}
+
+/// One prompt of the suite
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GoldenCase {
+    pub prompt: String,
+    /// Recorded generation; `None` until the suite is recorded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub golden: Option<String>,
+}
+
+/// Outcome of one prompt of a regression check
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CaseResult {
+    pub prompt: String,
+    pub golden: String,
+    pub generated: String,
+    /// One minus the token edit distance over the longer token sequence
+    pub similarity: f64,
+    pub passed: bool,
+}
+
+/// Outcome of a regression check over a suite
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RegressionReport {
+    pub threshold: f64,
+    pub cases: Vec<CaseResult>,
+}
+
+impl RegressionReport {
+    /// Cases whose generation drifted from the golden output
+    pub fn drifted(&self) -> impl Iterator<Item = &CaseResult> {
+        self.cases.iter().filter(|case| !case.passed)
+    }
+
+    /// Whether every generation is close enough to its golden output
+    pub fn passed(&self) -> bool {
+        self.drifted().next().is_none()
+    }
+
+    /// Print one line per case and a summary
+    pub fn print(&self) {
+        for case in &self.cases {
+            let mark = if case.passed { "✅" } else { "❌" };
+            println!("{} {:.3}  {}", mark, case.similarity, case.prompt);
+        }
+        let drifted = self.drifted().count();
+        println!(
+            "\n📊 {}/{} prompt(s) match their golden output (threshold {})",
+            self.cases.len() - drifted,
+            self.cases.len(),
+            self.threshold
+        );
+    }
+}
+
+impl GoldenSuite {
+    /// An unrecorded suite of `prompts`
+    pub fn new<I: IntoIterator<Item = String>>(prompts: I) -> Self {
+        let mut suite = Self::default();
+        suite.add_prompts(prompts);
+        suite
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> crate::Result<Self> {
+        Ok(toml::from_str(&std::fs::read_to_string(path)?)?)
+    }
+
+    /// Write the suite as TOML
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> crate::Result<()> {
+        let content = toml::to_string_pretty(self)?;
+        std::fs::write(path, format!("{}{}", HEADER, content))?;
+        Ok(())
+    }
+
+    /// Append the `prompts` not in the suite yet; returns how many were added
+    pub fn add_prompts<I: IntoIterator<Item = String>>(&mut self, prompts: I) -> usize {
+        let before = self.cases.len();
+        for prompt in prompts {
+            if !self.cases.iter().any(|case| case.prompt == prompt) {
+                self.cases.push(GoldenCase {
+                    prompt,
+                    golden: None,
+                });
+            }
+        }
+        self.cases.len() - before
+    }
+
+    /// Record `generator`'s output for every prompt as its golden output
+    pub fn record(&mut self, generator: &dyn GeneratorBackend) -> crate::Result<usize> {
+        let options = self.options.clone();
+        self.record_with(|prompt| generator.generate_with(prompt, &options))
+    }
+
+    /// Record any generation function's output for every prompt; returns
+    /// how many golden outputs changed
+    pub fn record_with<F>(&mut self, mut generate: F) -> crate::Result<usize>
+    where
+        F: FnMut(&str) -> crate::Result<String>,
+    {
+        let mut changed = 0;
+        for case in &mut self.cases {
+            let generated = generate(&case.prompt)?;
+            if case.golden.as_ref() != Some(&generated) {
+                case.golden = Some(generated);
+                changed += 1;
+            }
+        }
+        Ok(changed)
+    }
+
+    /// Compare `generator`'s outputs against the golden ones
+    pub fn check(
+        &self,
+        generator: &dyn GeneratorBackend,
+        threshold: f64,
+    ) -> crate::Result<RegressionReport> {
+        self.check_with(threshold, |prompt| {
+            generator.generate_with(prompt, &self.options)
+        })
+    }
+
+    /// Compare any generation function's outputs against the golden ones;
+    /// fails when a prompt has not been recorded
+    pub fn check_with<F>(&self, threshold: f64, mut generate: F) -> crate::Result<RegressionReport>
+    where
+        F: FnMut(&str) -> crate::Result<String>,
+    {
+        if !(0.0..=1.0).contains(&threshold) {
+            return Err(crate::Error::ConfigError(format!(
+                "regression threshold {} is outside [0, 1]",
+                threshold
+            )));
+        }
+        let unrecorded = self.cases.iter().filter(|case| case.golden.is_none());
+        if let Some(case) = unrecorded.clone().next() {
+            return Err(crate::Error::ConfigError(format!(
+                "{} prompt(s) have no golden output, such as {:?}; record them first",
+                unrecorded.count(),
+                case.prompt
+            )));
+        }
+
+        let tokenizer = WGSLTokenizer::new(usize::MAX, false);
+        let cases = self
+            .cases
+            .iter()
+            .map(|case| {
+                let golden = case.golden.clone().unwrap_or_default();
+                let generated = generate(&case.prompt)?;
+                let similarity = similarity(
+                    &tokenizer.tokenize(&golden),
+                    &tokenizer.tokenize(&generated),
+                );
+                Ok(CaseResult {
+                    prompt: case.prompt.clone(),
+                    golden,
+                    generated,
+                    similarity,
+                    passed: similarity >= threshold,
+                })
+            })
+            .collect::<crate::Result<Vec<_>>>()?;
+        Ok(RegressionReport { threshold, cases })
+    }
+}
+
+fn similarity(a: &[String], b: &[String]) -> f64 {
+    let longest = a.len().max(b.len());
+    if longest == 0 {
+        return 1.0;
+    }
+    1.0 - edit_distance(a, b) as f64 / longest as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_golden_suite() {
+        let mut suite = GoldenSuite::new(["red".to_string(), "blue".to_string()]);
+        assert_eq!(suite.add_prompts(["red".to_string()]), 0);
+        assert!(suite.check_with(1.0, |_| Ok(String::new())).is_err());
+
+        let outputs = |prompt: &str| -> crate::Result<String> {
+            Ok(match prompt {
+                "red" => "vec4<f32>(1.0, 0.0, 0.0, 1.0)",
+                _ => "vec4<f32>(0.0, 0.0, 1.0, 1.0)",
+            }
+            .to_string())
+        };
+        assert_eq!(suite.record_with(outputs).unwrap(), 2);
+        assert_eq!(suite.record_with(outputs).unwrap(), 0);
+
+        let path = std::env::temp_dir().join("tiny_trainer_golden_suite.toml");
+        suite.save(&path).unwrap();
+        let loaded = GoldenSuite::from_file(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+        assert_eq!(loaded, suite);
+
+        // Layout is not drift
+        let report = suite
+            .check_with(DEFAULT_THRESHOLD, |prompt| {
+                Ok(outputs(prompt)?.replace(", ", ","))
+            })
+            .unwrap();
+        assert!(report.passed());
+
+        // One token of ten changed
+        let drift = |prompt: &str| -> crate::Result<String> {
+            Ok(outputs(prompt)?.replace("(1.0,", "(0.5,"))
+        };
+        let report = suite.check_with(DEFAULT_THRESHOLD, drift).unwrap();
+        let drifted: Vec<&str> = report.drifted().map(|case| case.prompt.as_str()).collect();
+        assert_eq!(drifted, ["red"]);
+        assert!((report.cases[0].similarity - 0.9).abs() < 1e-9);
+        assert!(suite.check_with(0.85, drift).unwrap().passed());
+        assert!(suite.check_with(1.5, drift).is_err());
+
+        let shipped = GoldenSuite::from_file("config/wgsl_golden.toml").unwrap();
+        assert_eq!(shipped.threshold, DEFAULT_THRESHOLD);
+        assert!(!shipped.cases.is_empty());
+    }
+}
@@ -0,0 +1,604 @@
+//! Hugging Face Hub downloads and uploads (`hub` feature)
+//!
+//! Talks to the Hub's HTTP API directly: files are fetched through their
+//! `resolve` URLs, and uploads are a single commit whose large files are
+//! first stored through the repository's Git LFS batch endpoint, as the Hub
+//! requires for anything it doesn't accept inline.
+
+use crate::config::HubConfig;
+use crate::eval::EvalReport;
+use crate::model::Checkpoint;
+use base64::Engine as _;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::fmt::Write as _;
+use std::io::Write as _;
+use std::path::{Path, PathBuf};
+
+const DEFAULT_ENDPOINT: &str = "https://huggingface.co";
+
+/// Bytes of each file the Hub inspects to choose between inline and LFS
+/// storage
+const SAMPLE_LEN: usize = 512;
+
+/// Kind of Hub repository
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RepoType {
+    #[default]
+    Model,
+    Dataset,
+}
+
+impl RepoType {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RepoType::Model => "model",
+            RepoType::Dataset => "dataset",
+        }
+    }
+
+    /// Repository path as it appears in file URLs, where datasets are
+    /// prefixed with `datasets/`
+    fn url_path(self, repo: &str) -> String {
+        match self {
+            RepoType::Model => repo.to_string(),
+            RepoType::Dataset => format!("datasets/{}", repo),
+        }
+    }
+}
+
+/// A file to commit, by its path in the repository
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UploadFile {
+    pub path: String,
+    pub content: Vec<u8>,
+}
+
+/// Client for one Hub endpoint
+pub struct HubClient {
+    agent: ureq::Agent,
+    endpoint: String,
+    token: Option<String>,
+}
+
+impl HubClient {
+    /// Client for `config.endpoint`, authenticated with `config.token` or
+    /// `HF_TOKEN` when either is set
+    pub fn new(config: &HubConfig) -> Self {
+        Self {
+            agent: ureq::AgentBuilder::new().build(),
+            endpoint: config
+                .endpoint
+                .clone()
+                .unwrap_or_else(|| DEFAULT_ENDPOINT.to_string())
+                .trim_end_matches('/')
+                .to_string(),
+            token: config
+                .token
+                .clone()
+                .or_else(|| std::env::var("HF_TOKEN").ok())
+                .filter(|token| !token.is_empty()),
+        }
+    }
+
+    /// Download `filename` of `repo` at `revision` (a branch, tag or commit)
+    /// to the same relative path under `dir`
+    pub fn download(
+        &self,
+        repo_type: RepoType,
+        repo: &str,
+        revision: &str,
+        filename: &str,
+        dir: &Path,
+    ) -> crate::Result<PathBuf> {
+        let url = format!(
+            "{}/{}/resolve/{}/{}",
+            self.endpoint,
+            repo_type.url_path(repo),
+            revision,
+            filename
+        );
+        let mut request = self.agent.get(&url);
+        if let Some(token) = &self.token {
+            request = request.set("Authorization", &format!("Bearer {}", token));
+        }
+        let response = request.call().map_err(request_error)?;
+
+        let path = dir.join(filename);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        // Through a temporary file, so an interrupted download leaves nothing
+        let temp = path.with_extension(format!("part{}", std::process::id()));
+        let mut file = std::fs::File::create(&temp)?;
+        std::io::copy(&mut response.into_reader(), &mut file)?;
+        file.flush()?;
+        std::fs::rename(&temp, &path)?;
+        Ok(path)
+    }
+
+    /// Create `repo` (`name` or `organization/name`), doing nothing if it
+    /// already exists
+    pub fn create_repo(&self, repo_type: RepoType, repo: &str, private: bool) -> crate::Result<()> {
+        let (organization, name) = match repo.split_once('/') {
+            Some((organization, name)) => (Some(organization), name),
+            None => (None, repo),
+        };
+        let request = self.authorized(
+            self.agent
+                .post(&format!("{}/api/repos/create", self.endpoint)),
+        )?;
+        match request.send_json(json!({
+            "name": name,
+            "organization": organization,
+            "type": repo_type.as_str(),
+            "private": private,
+        })) {
+            Ok(_) | Err(ureq::Error::Status(409, _)) => Ok(()),
+            Err(e) => Err(request_error(e)),
+        }
+    }
+
+    /// Commit `files` to `revision` of `repo` with message `summary`,
+    /// returning the commit's URL
+    pub fn upload(
+        &self,
+        repo_type: RepoType,
+        repo: &str,
+        revision: &str,
+        files: &[UploadFile],
+        summary: &str,
+    ) -> crate::Result<String> {
+        let api = format!("{}/api/{}s/{}", self.endpoint, repo_type.as_str(), repo);
+        let lfs = self.lfs_files(&api, revision, files)?;
+
+        let mut body =
+            json!({ "key": "header", "value": { "summary": summary, "description": "" } })
+                .to_string();
+        for file in files {
+            let operation = if lfs.contains(&file.path) {
+                let oid = self.upload_lfs(repo_type, repo, &file.content)?;
+                json!({ "key": "lfsFile", "value": {
+                    "path": file.path,
+                    "algo": "sha256",
+                    "oid": oid,
+                    "size": file.content.len(),
+                } })
+            } else {
+                json!({ "key": "file", "value": {
+                    "path": file.path,
+                    "content": base64::engine::general_purpose::STANDARD.encode(&file.content),
+                    "encoding": "base64",
+                } })
+            };
+            body.push('\n');
+            body.push_str(&operation.to_string());
+        }
+
+        let response: Value = self
+            .authorized(self.agent.post(&format!("{}/commit/{}", api, revision)))?
+            .set("Content-Type", "application/x-ndjson")
+            .send_string(&body)
+            .map_err(request_error)?
+            .into_json()?;
+        Ok(response["commitUrl"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string())
+    }
+
+    /// Push the checkpoint at `path` to model repository `repo`, creating it
+    /// if needed, with its tokenizer as `tokenizer.json` and a model card;
+    /// `eval` adds its metrics to the card and the report as `eval.json`
+    pub fn push_checkpoint(
+        &self,
+        repo: &str,
+        path: &Path,
+        eval: Option<&EvalReport>,
+        private: bool,
+    ) -> crate::Result<String> {
+        let bytes = std::fs::read(path)?;
+        let checkpoint = Checkpoint::from_bytes(&bytes)?;
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "model.ckpt".to_string());
+
+        let mut files = vec![
+            UploadFile {
+                path: "README.md".to_string(),
+                content: model_card(&checkpoint, &name, eval).into_bytes(),
+            },
+            UploadFile {
+                path: "tokenizer.json".to_string(),
+                content: checkpoint.tokenizer.to_json()?.into_bytes(),
+            },
+        ];
+        if let Some(eval) = eval {
+            files.push(UploadFile {
+                path: "eval.json".to_string(),
+                content: serde_json::to_vec_pretty(eval)?,
+            });
+        }
+        files.push(UploadFile {
+            path: name.clone(),
+            content: bytes,
+        });
+
+        self.create_repo(RepoType::Model, repo, private)?;
+        self.upload(
+            RepoType::Model,
+            repo,
+            "main",
+            &files,
+            &format!("Upload {}", name),
+        )
+    }
+
+    /// Paths of `files` the Hub wants stored in LFS
+    fn lfs_files(
+        &self,
+        api: &str,
+        revision: &str,
+        files: &[UploadFile],
+    ) -> crate::Result<Vec<String>> {
+        let files: Vec<Value> = files
+            .iter()
+            .map(|file| {
+                let sample = &file.content[..file.content.len().min(SAMPLE_LEN)];
+                json!({
+                    "path": file.path,
+                    "size": file.content.len(),
+                    "sample": base64::engine::general_purpose::STANDARD.encode(sample),
+                })
+            })
+            .collect();
+        let response: Value = self
+            .authorized(self.agent.post(&format!("{}/preupload/{}", api, revision)))?
+            .send_json(json!({ "files": files }))
+            .map_err(request_error)?
+            .into_json()?;
+        Ok(response["files"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter(|file| file["uploadMode"] == "lfs")
+            .filter_map(|file| file["path"].as_str().map(str::to_string))
+            .collect())
+    }
+
+    /// Store `content` in the repository's LFS, returning its object id
+    fn upload_lfs(&self, repo_type: RepoType, repo: &str, content: &[u8]) -> crate::Result<String> {
+        let oid = format!("{:x}", Sha256::digest(content));
+        let object = json!({ "oid": oid, "size": content.len() });
+        let url = format!(
+            "{}/{}.git/info/lfs/objects/batch",
+            self.endpoint,
+            repo_type.url_path(repo)
+        );
+        let response: Value = self
+            .authorized(self.agent.post(&url))?
+            .set("Accept", "application/vnd.git-lfs+json")
+            .set("Content-Type", "application/vnd.git-lfs+json")
+            .send_string(
+                &json!({
+                    "operation": "upload",
+                    "transfers": ["basic"],
+                    "hash_algo": "sha256",
+                    "objects": [object],
+                })
+                .to_string(),
+            )
+            .map_err(request_error)?
+            .into_json()?;
+
+        let answer = &response["objects"][0];
+        if let Some(error) = answer.get("error") {
+            return Err(crate::Error::Other(format!("Hub LFS error: {}", error)));
+        }
+        // Without actions the Hub already has the object
+        let actions = &answer["actions"];
+        if let Some(upload) = actions.get("upload") {
+            let href = upload["href"]
+                .as_str()
+                .ok_or_else(|| crate::Error::Other("Hub returned no LFS upload URL".to_string()))?;
+            with_headers(self.agent.put(href), &upload["header"])
+                .send_bytes(content)
+                .map_err(request_error)?;
+        }
+        if let Some(verify) = actions.get("verify") {
+            let href = verify["href"]
+                .as_str()
+                .ok_or_else(|| crate::Error::Other("Hub returned no LFS verify URL".to_string()))?;
+            self.authorized(with_headers(self.agent.post(href), &verify["header"]))?
+                .send_json(object)
+                .map_err(request_error)?;
+        }
+        Ok(oid)
+    }
+
+    fn authorized(&self, request: ureq::Request) -> crate::Result<ureq::Request> {
+        let token = self.token.as_ref().ok_or_else(|| {
+            crate::Error::ConfigError(
+                "Writing to the Hugging Face Hub requires a token: set [hub] token or HF_TOKEN"
+                    .to_string(),
+            )
+        })?;
+        Ok(request.set("Authorization", &format!("Bearer {}", token)))
+    }
+}
+
+/// README of a pushed checkpoint: Hub metadata, the model's shape and
+/// provenance, usage, and the evaluation results when there are some
+pub fn model_card(checkpoint: &Checkpoint, file_name: &str, eval: Option<&EvalReport>) -> String {
+    let model = &checkpoint.model;
+    let metadata = &checkpoint.metadata;
+    let mut card = String::from(
+        "---\nlibrary_name: tiny-agent-trainer\ntags:\n- wgsl\n- shader\n- code-generation\n---\n\n",
+    );
+    let _ = writeln!(card, "# {}\n", file_name);
+    card.push_str("WGSL shader generator trained with tiny-agent-trainer.\n\n");
+    card.push_str("| | |\n|---|---|\n");
+    let _ = writeln!(card, "| Architecture | {:?} |", model.architecture);
+    let _ = writeln!(card, "| Parameters | {} |", model.num_parameters());
+    let _ = writeln!(
+        card,
+        "| Layers × d_model × heads | {} × {} × {} |",
+        model.num_layers, model.d_model, model.nhead
+    );
+    let _ = writeln!(
+        card,
+        "| Vocabulary | {} |",
+        checkpoint.tokenizer.vocab_size()
+    );
+    let _ = writeln!(card, "| Max sequence length | {} |", model.max_seq_len);
+    if let Some(epochs) = metadata.epochs {
+        let _ = writeln!(card, "| Epochs | {} |", epochs);
+    }
+    if let Some(loss) = metadata.final_loss {
+        let _ = writeln!(card, "| Final loss | {:.4} |", loss);
+    }
+    if let Some(version) = &metadata.crate_version {
+        let _ = writeln!(card, "| Trainer version | {} |", version);
+    }
+    if let Some(created_at) = &metadata.created_at {
+        let _ = writeln!(card, "| Created | {} |", created_at);
+    }
+    if let Some(commit) = &metadata.git_commit {
+        let _ = writeln!(card, "| Git commit | `{}` |", commit);
+    }
+
+    let _ = write!(
+        card,
+        "\n## Usage\n\n```bash\ntiny-agent-trainer generate -m {} -p \"compute shader that doubles every value\"\n```\n",
+        file_name
+    );
+    if let Some(eval) = eval {
+        card.push('\n');
+        card.push_str(
+            &eval
+                .to_markdown()
+                .replacen("# Evaluation Report", "## Evaluation", 1)
+                .replace("\n## ", "\n### "),
+        );
+    }
+    card
+}
+
+/// `request` with the headers of an LFS action
+fn with_headers(mut request: ureq::Request, headers: &Value) -> ureq::Request {
+    for (key, value) in headers.as_object().into_iter().flatten() {
+        if let Some(value) = value.as_str() {
+            request = request.set(key, value);
+        }
+    }
+    request
+}
+
+fn request_error(error: ureq::Error) -> crate::Error {
+    crate::Error::Other(format!("Hub request failed: {}", error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::tiny_generator;
+    use std::io::{BufRead, BufReader, Read};
+    use std::net::TcpListener;
+    use std::sync::{Arc, Mutex};
+
+    /// (method, path, authorization, body) of every request received
+    type Requests = Arc<Mutex<Vec<(String, String, String, Vec<u8>)>>>;
+
+    /// Minimal HTTP server answering Hub calls and recording requests
+    fn fake_hub() -> (String, Requests) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let recorded = Arc::clone(&requests);
+        let storage_url = format!("{}/lfs-storage", base);
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                let (mut length, mut authorization) = (0, String::new());
+                loop {
+                    let mut header = String::new();
+                    reader.read_line(&mut header).unwrap();
+                    if header.trim().is_empty() {
+                        break;
+                    }
+                    let lower = header.to_lowercase();
+                    if let Some(value) = lower.strip_prefix("content-length:") {
+                        length = value.trim().parse().unwrap();
+                    }
+                    if lower.starts_with("authorization:") {
+                        authorization = header["authorization:".len()..].trim().to_string();
+                    }
+                }
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).unwrap();
+                let mut parts = request_line.split_whitespace();
+                let method = parts.next().unwrap().to_string();
+                let path = parts.next().unwrap().to_string();
+
+                let (status, response) = if path.contains("/resolve/") {
+                    (200, "natural_language,wgsl_code".to_string())
+                } else if path == "/api/repos/create" {
+                    (409, json!({ "error": "exists" }).to_string())
+                } else if path.contains("/preupload/") {
+                    let request: Value = serde_json::from_slice(&body).unwrap();
+                    let files: Vec<Value> = request["files"]
+                        .as_array()
+                        .unwrap()
+                        .iter()
+                        .map(|file| {
+                            let lfs = file["path"].as_str().unwrap().ends_with(".ckpt");
+                            json!({
+                                "path": file["path"],
+                                "uploadMode": if lfs { "lfs" } else { "regular" },
+                            })
+                        })
+                        .collect();
+                    (200, json!({ "files": files }).to_string())
+                } else if path.ends_with("/info/lfs/objects/batch") {
+                    let request: Value = serde_json::from_slice(&body).unwrap();
+                    let object = &request["objects"][0];
+                    (
+                        200,
+                        json!({ "objects": [{
+                            "oid": object["oid"],
+                            "size": object["size"],
+                            "actions": { "upload": {
+                                "href": storage_url,
+                                "header": { "X-Storage": "1" },
+                            } },
+                        }] })
+                        .to_string(),
+                    )
+                } else if path.contains("/commit/") {
+                    (
+                        200,
+                        json!({ "commitUrl": "https://hub/commit/1" }).to_string(),
+                    )
+                } else {
+                    (200, String::new())
+                };
+                recorded
+                    .lock()
+                    .unwrap()
+                    .push((method, path, authorization, body));
+                write!(
+                    stream,
+                    "HTTP/1.1 {} OK\r\nContent-Type: application/json\r\n\
+                     Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    response.len(),
+                    response
+                )
+                .unwrap();
+            }
+        });
+        (base, requests)
+    }
+
+    #[test]
+    fn test_download_and_push() {
+        let (endpoint, requests) = fake_hub();
+        let client = HubClient::new(&HubConfig {
+            token: Some("hf_test".to_string()),
+            endpoint: Some(endpoint),
+        });
+        let dir = tempfile::tempdir().unwrap();
+
+        let path = client
+            .download(
+                RepoType::Dataset,
+                "me/shaders",
+                "main",
+                "data/train.csv",
+                dir.path(),
+            )
+            .unwrap();
+        assert_eq!(path, dir.path().join("data/train.csv"));
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "natural_language,wgsl_code"
+        );
+
+        let (tokenizer, model) = tiny_generator(&["fn main ( ) { }"]);
+        let checkpoint_path = dir.path().join("wgsl.ckpt");
+        Checkpoint::new(model, tokenizer)
+            .save(&checkpoint_path)
+            .unwrap();
+        let eval = EvalReport::default();
+        let url = client
+            .push_checkpoint("me/wgsl", &checkpoint_path, Some(&eval), false)
+            .unwrap();
+        assert_eq!(url, "https://hub/commit/1");
+
+        let requests = requests.lock().unwrap();
+        let paths: Vec<&str> = requests
+            .iter()
+            .map(|(_, path, _, _)| path.as_str())
+            .collect();
+        assert_eq!(
+            paths,
+            [
+                "/datasets/me/shaders/resolve/main/data/train.csv",
+                "/api/repos/create",
+                "/api/models/me/wgsl/preupload/main",
+                "/me/wgsl.git/info/lfs/objects/batch",
+                "/lfs-storage",
+                "/api/models/me/wgsl/commit/main",
+            ]
+        );
+        assert_eq!(requests[1].2, "Bearer hf_test");
+        assert_eq!(requests[4].3, std::fs::read(&checkpoint_path).unwrap());
+
+        let operations: Vec<Value> = String::from_utf8_lossy(&requests[5].3)
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(operations[0]["value"]["summary"], "Upload wgsl.ckpt");
+        let keys: Vec<(&str, &str)> = operations[1..]
+            .iter()
+            .map(|op| {
+                (
+                    op["key"].as_str().unwrap(),
+                    op["value"]["path"].as_str().unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            keys,
+            [
+                ("file", "README.md"),
+                ("file", "tokenizer.json"),
+                ("file", "eval.json"),
+                ("lfsFile", "wgsl.ckpt"),
+            ]
+        );
+        let readme = base64::engine::general_purpose::STANDARD
+            .decode(operations[1]["value"]["content"].as_str().unwrap())
+            .unwrap();
+        let readme = String::from_utf8(readme).unwrap();
+        assert!(readme.starts_with("---\nlibrary_name: tiny-agent-trainer"));
+        assert!(readme.contains("## Evaluation"));
+    }
+
+    #[test]
+    fn test_push_requires_token() {
+        std::env::remove_var("HF_TOKEN");
+        let client = HubClient::new(&HubConfig {
+            token: None,
+            endpoint: Some("http://127.0.0.1:9".to_string()),
+        });
+        let error = client
+            .upload(RepoType::Model, "me/wgsl", "main", &[], "empty")
+            .unwrap_err();
+        assert!(error.to_string().contains("HF_TOKEN"));
+    }
+}
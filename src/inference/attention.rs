@@ -0,0 +1,95 @@
+//! Attention maps of a prompt and the code generated for it
+
+use super::{PromptFields, WGSLGenerator};
+use crate::model::AttentionMap;
+use crate::tokenizer::SpecialToken;
+use serde::Serialize;
+
+/// Attention weights of every layer with the tokens they relate
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AttentionReport {
+    pub prompt: String,
+    pub code: String,
+    /// Prompt tokens: queries and keys of encoder self-attention, keys of
+    /// decoder cross-attention
+    pub input_tokens: Vec<String>,
+    /// Start token followed by the code tokens: queries of decoder
+    /// attention and keys of decoder self-attention
+    pub decoder_tokens: Vec<String>,
+    /// Token predicted at each decoder query: the code tokens followed by
+    /// end-of-sequence
+    pub output_tokens: Vec<String>,
+    pub maps: Vec<AttentionMap>,
+}
+
+impl WGSLGenerator {
+    /// Attention maps of the model reading `prompt` and writing `code`,
+    /// such as a result of [`generate_with`](Self::generate_with) or a
+    /// reference shader; both are cut to the model's length limits
+    pub fn attention(&self, prompt: &str, code: &str) -> AttentionReport {
+        let max_len = self.model.max_seq_len;
+        let mut input_ids = self
+            .tokenizer
+            .encode_text(&self.format_prompt(&PromptFields::new(prompt)));
+        input_ids.truncate(max_len);
+        let mut code_ids = self.tokenizer.encode_text(code);
+        code_ids.truncate(max_len.saturating_sub(1));
+
+        let tokens = |ids: &[usize]| -> Vec<String> {
+            ids.iter()
+                .map(|id| match self.tokenizer.reverse_vocab.get(id) {
+                    Some(token) => token.clone(),
+                    None => SpecialToken::Unknown.as_str().to_string(),
+                })
+                .collect()
+        };
+        let code_tokens = tokens(&code_ids);
+        let mut decoder_tokens = vec![SpecialToken::StartOfSequence.as_str().to_string()];
+        decoder_tokens.extend(code_tokens.iter().cloned());
+        let mut output_tokens = code_tokens;
+        output_tokens.push(SpecialToken::EndOfSequence.as_str().to_string());
+
+        AttentionReport {
+            prompt: prompt.to_string(),
+            code: code.to_string(),
+            input_tokens: tokens(&input_ids),
+            decoder_tokens,
+            output_tokens,
+            maps: self.model.attention_maps(&input_ids, &code_ids),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::tiny_generator;
+
+    #[test]
+    fn test_attention_report() {
+        let (tokenizer, model) = tiny_generator(&["double every value", "fn main() { }"]);
+        let generator = WGSLGenerator::new(model, tokenizer);
+
+        let report = generator.attention("double every value", "fn main() { }");
+        assert_eq!(report.input_tokens, ["double", "every", "value"]);
+        assert_eq!(
+            report.decoder_tokens[0],
+            SpecialToken::StartOfSequence.as_str()
+        );
+        assert_eq!(
+            &report.decoder_tokens[1..],
+            ["fn", "main", "(", ")", "{", "}"]
+        );
+        assert_eq!(
+            report.output_tokens.last().unwrap(),
+            SpecialToken::EndOfSequence.as_str()
+        );
+        assert_eq!(report.maps.len(), 3);
+
+        // Cross-attention relates each decoder query to the prompt tokens
+        let cross = &report.maps[2];
+        assert_eq!(cross.name, "decoder.0.cross_attn");
+        assert_eq!(cross.heads[0].dim(), (7, 3));
+        assert_eq!(report.decoder_tokens.len(), report.output_tokens.len());
+    }
+}
@@ -0,0 +1,280 @@
+//! Interchangeable generators
+//!
+//! The CLI, evaluation and repair loops only need prompts turned into code.
+//! [`GeneratorBackend`] is that contract: the local [`WGSLGenerator`]
+//! implements it on top of the transformer, and other backends, such as a
+//! remote model behind an HTTP API, only have to provide
+//! [`sample_n`](GeneratorBackend::sample_n).
+
+use super::repair::{repair_loop, repair_loop_from};
+use super::{GenerationOptions, GenerationResult, RepairOptions, RepairResult, WGSLGenerator};
+use crate::config::GenerationConfig;
+use crate::{WGSLTokenizer, WGSLValidator};
+use web_time::Instant;
+
+/// Model name prefix selecting the OpenAI-compatible backend (`openai`
+/// feature) in the CLI, as in `openai:gpt-4o-mini`
+pub const REMOTE_PREFIX: &str = "openai:";
+
+/// Something that turns natural language prompts into WGSL code
+pub trait GeneratorBackend: Send + Sync {
+    /// Draw `n` generations for `prompt`
+    fn sample_n(
+        &self,
+        prompt: &str,
+        n: usize,
+        options: &GenerationOptions,
+    ) -> crate::Result<Vec<String>>;
+
+    /// Tokenizer the backend's token scores refer to, if it runs locally
+    fn tokenizer(&self) -> Option<&WGSLTokenizer> {
+        None
+    }
+
+    /// Generate one shader for `prompt`
+    fn generate_with(&self, prompt: &str, options: &GenerationOptions) -> crate::Result<String> {
+        self.sample_n(prompt, 1, options)?
+            .pop()
+            .ok_or_else(|| crate::Error::Other("backend returned no generation".to_string()))
+    }
+
+    /// Generate one shader per prompt, batching them where the backend can;
+    /// with a seed, prompt `i` is sampled with `seed + i`
+    fn generate_batch(
+        &self,
+        prompts: &[String],
+        options: &GenerationOptions,
+    ) -> crate::Result<Vec<String>> {
+        prompts
+            .iter()
+            .enumerate()
+            .map(|(index, prompt)| {
+                let options = GenerationOptions {
+                    seed: options.seed.map(|seed| seed.wrapping_add(index as u64)),
+                    ..options.clone()
+                };
+                self.generate_with(prompt, &options)
+            })
+            .collect()
+    }
+
+    /// Generate and validate one shader; backends without token scores
+    /// leave `tokens`, `log_probs` and `score` empty
+    fn generate_result(
+        &self,
+        prompt: &str,
+        options: &GenerationOptions,
+        validator: &WGSLValidator,
+    ) -> crate::Result<GenerationResult> {
+        let start = Instant::now();
+        let code = self.generate_with(prompt, options)?;
+        let validation = validator.validate(&code)?;
+        Ok(GenerationResult {
+            elapsed: start.elapsed(),
+            ..GenerationResult::unscored(code, validation)
+        })
+    }
+
+    /// Generate, and while the code fails `validator`, regenerate from a
+    /// repair prompt holding the error, up to `repair.max_attempts` times
+    fn generate_with_repair(
+        &self,
+        prompt: &str,
+        options: &GenerationOptions,
+        repair: &RepairOptions,
+        validator: &WGSLValidator,
+    ) -> crate::Result<RepairResult> {
+        repair_loop(prompt, repair, |current| {
+            self.generate_result(current, options, validator)
+        })
+    }
+
+    /// Continue a repair loop after `failed`, a generation for `prompt`
+    /// made elsewhere, such as in a batch, starting at the repair prompt
+    /// holding its error
+    fn repair_from(
+        &self,
+        prompt: &str,
+        failed: GenerationResult,
+        options: &GenerationOptions,
+        repair: &RepairOptions,
+        validator: &WGSLValidator,
+    ) -> crate::Result<RepairResult> {
+        repair_loop_from(prompt, failed, repair, |current| {
+            self.generate_result(current, options, validator)
+        })
+    }
+
+    /// Generate as `config` says; backends without beam search ignore
+    /// `beam_width`
+    fn generate_with_config(
+        &self,
+        prompt: &str,
+        config: &GenerationConfig,
+        seed: Option<u64>,
+        validator: &WGSLValidator,
+    ) -> crate::Result<GenerationResult> {
+        let options = config.options(seed);
+        if let Some(repair) = config.repair_options() {
+            return Ok(self
+                .generate_with_repair(prompt, &options, &repair, validator)?
+                .result);
+        }
+        self.generate_result(prompt, &options, validator)
+    }
+}
+
+impl GeneratorBackend for WGSLGenerator {
+    fn sample_n(
+        &self,
+        prompt: &str,
+        n: usize,
+        options: &GenerationOptions,
+    ) -> crate::Result<Vec<String>> {
+        WGSLGenerator::sample_n(self, prompt, n, options)
+    }
+
+    fn tokenizer(&self) -> Option<&WGSLTokenizer> {
+        Some(WGSLGenerator::tokenizer(self))
+    }
+
+    fn generate_with(&self, prompt: &str, options: &GenerationOptions) -> crate::Result<String> {
+        WGSLGenerator::generate_with(self, prompt, options)
+    }
+
+    fn generate_batch(
+        &self,
+        prompts: &[String],
+        options: &GenerationOptions,
+    ) -> crate::Result<Vec<String>> {
+        WGSLGenerator::generate_batch(self, prompts, options)
+    }
+
+    fn generate_result(
+        &self,
+        prompt: &str,
+        options: &GenerationOptions,
+        validator: &WGSLValidator,
+    ) -> crate::Result<GenerationResult> {
+        WGSLGenerator::generate_result(self, prompt, options, validator)
+    }
+
+    fn generate_with_repair(
+        &self,
+        prompt: &str,
+        options: &GenerationOptions,
+        repair: &RepairOptions,
+        validator: &WGSLValidator,
+    ) -> crate::Result<RepairResult> {
+        WGSLGenerator::generate_with_repair(self, prompt, options, repair, validator)
+    }
+
+    fn repair_from(
+        &self,
+        prompt: &str,
+        failed: GenerationResult,
+        options: &GenerationOptions,
+        repair: &RepairOptions,
+        validator: &WGSLValidator,
+    ) -> crate::Result<RepairResult> {
+        WGSLGenerator::repair_from(self, prompt, failed, options, repair, validator)
+    }
+
+    fn generate_with_config(
+        &self,
+        prompt: &str,
+        config: &GenerationConfig,
+        seed: Option<u64>,
+        validator: &WGSLValidator,
+    ) -> crate::Result<GenerationResult> {
+        WGSLGenerator::generate_with_config(self, prompt, config, seed, validator)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    /// Backend answering from a script, recording the prompts it was given
+    struct Scripted {
+        outputs: Mutex<Vec<&'static str>>,
+        prompts: Mutex<Vec<String>>,
+    }
+
+    impl GeneratorBackend for Scripted {
+        fn sample_n(
+            &self,
+            prompt: &str,
+            n: usize,
+            _: &GenerationOptions,
+        ) -> crate::Result<Vec<String>> {
+            self.prompts.lock().unwrap().push(prompt.to_string());
+            let mut outputs = self.outputs.lock().unwrap();
+            Ok((0..n).map(|_| outputs.remove(0).to_string()).collect())
+        }
+    }
+
+    #[test]
+    fn test_default_repair_loop() {
+        let backend = Scripted {
+            outputs: Mutex::new(vec![
+                "fn main( {",
+                "@compute @workgroup_size(1) fn main() {}",
+            ]),
+            prompts: Mutex::new(Vec::new()),
+        };
+        let config = GenerationConfig {
+            retries: 2,
+            ..GenerationConfig::default()
+        };
+        let result = backend
+            .generate_with_config("noop kernel", &config, None, &WGSLValidator::new())
+            .unwrap();
+
+        assert!(result.is_valid());
+        assert_eq!(result.retries, 1);
+        assert!(result.tokens.is_empty());
+        let prompts = backend.prompts.lock().unwrap();
+        assert_eq!(prompts[0], "noop kernel");
+        assert!(prompts[1].starts_with("noop kernel fix error: "));
+    }
+
+    #[test]
+    fn test_repair_from_failed_generation() {
+        let backend = Scripted {
+            outputs: Mutex::new(vec!["@compute @workgroup_size(1) fn main() {}"]),
+            prompts: Mutex::new(Vec::new()),
+        };
+        let validator = WGSLValidator::new();
+        let failed = GenerationResult::unscored(
+            "fn main( {".to_string(),
+            validator.validate("fn main( {").unwrap(),
+        );
+        // One retry is one regeneration after the failed attempt
+        let config = GenerationConfig {
+            retries: 1,
+            ..GenerationConfig::default()
+        };
+        let repair = config.repair_options().unwrap();
+        let result = backend
+            .repair_from(
+                "noop kernel",
+                failed,
+                &GenerationOptions::default(),
+                &repair,
+                &validator,
+            )
+            .unwrap();
+
+        assert!(result.is_valid());
+        assert_eq!(result.attempts.len(), 2);
+        assert_eq!(result.attempts[0].code, "fn main( {");
+        assert_eq!(result.result.retries, 1);
+        // The failed attempt is not regenerated from the plain prompt
+        let prompts = backend.prompts.lock().unwrap();
+        assert_eq!(prompts.len(), 1);
+        assert!(prompts[0].starts_with("noop kernel fix error: "));
+        assert!(GenerationConfig::default().repair_options().is_none());
+    }
+}
@@ -0,0 +1,116 @@
+//! Beam search returning every finished hypothesis
+//!
+//! Rather than only the most likely generation, [`WGSLGenerator::beam_search`]
+//! returns the n best, so rerankers (the validator, AST similarity, custom
+//! heuristics) can choose among alternatives.
+
+use super::{GenerationOptions, GenerationResult, PromptFields, TokenStream, WGSLGenerator};
+use crate::wgsl::format_wgsl_or_original;
+use crate::WGSLValidator;
+use rand::{rngs::StdRng, SeedableRng};
+use web_time::Instant;
+
+impl WGSLGenerator {
+    /// Keep the `beam_width` most likely partial generations at every step
+    /// and return up to `beam_width` finished ones, best score first
+    ///
+    /// Length limits, stop sequences, penalties, biases and banned tokens of
+    /// `options` apply; temperature, top-k, top-p and the seed don't.
+    pub fn beam_search(
+        &self,
+        prompt: &str,
+        beam_width: usize,
+        options: &GenerationOptions,
+        validator: &WGSLValidator,
+    ) -> crate::Result<Vec<GenerationResult>> {
+        let start = Instant::now();
+        let width = beam_width.max(1);
+        let text = self.format_prompt(&PromptFields::new(prompt));
+        let mut beams = vec![TokenStream::new(
+            self,
+            &text,
+            options,
+            StdRng::seed_from_u64(0),
+        )];
+        let mut finished: Vec<TokenStream> = Vec::new();
+        while !beams.is_empty() && finished.len() < width {
+            let mut expansions: Vec<(f32, usize, usize, f32)> = Vec::new();
+            for (index, beam) in beams.iter_mut().enumerate() {
+                let score = beam.score();
+                for (id, log_prob) in beam.candidates(width) {
+                    expansions.push((score + log_prob, index, id, log_prob));
+                }
+            }
+            expansions.sort_by(|a, b| b.0.total_cmp(&a.0));
+            expansions.truncate(width);
+
+            let mut next = Vec::with_capacity(width);
+            for (_, index, id, log_prob) in expansions {
+                let mut beam = beams[index].clone();
+                if beam.push(id, log_prob) && !beam.is_done() {
+                    next.push(beam);
+                } else {
+                    finished.push(beam);
+                }
+            }
+            beams = next;
+        }
+        // Hypotheses cut off by the loop still count, ranked with the rest
+        finished.extend(beams);
+        finished.sort_by(|a, b| b.score().total_cmp(&a.score()));
+        finished.truncate(width);
+
+        finished
+            .into_iter()
+            .map(|beam| {
+                let code =
+                    format_wgsl_or_original(&self.tokenizer.decode_to_text(beam.generated_ids()));
+                Ok(GenerationResult {
+                    validation: validator.validate(&code)?,
+                    code,
+                    tokens: beam.tokens().to_vec(),
+                    log_probs: beam.log_probs().to_vec(),
+                    score: beam.score(),
+                    elapsed: start.elapsed(),
+                    retries: 0,
+                    fallback: None,
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::tiny_generator;
+
+    #[test]
+    fn test_beam_search() {
+        let (tokenizer, model) = tiny_generator(&["fn main() { let x = 1.0; }"]);
+        let generator = WGSLGenerator::new(model, tokenizer);
+        let options = GenerationOptions {
+            max_new_tokens: Some(6),
+            ..GenerationOptions::default()
+        };
+        let validator = WGSLValidator::new();
+
+        let beams = generator
+            .beam_search("main", 4, &options, &validator)
+            .unwrap();
+        assert!(!beams.is_empty() && beams.len() <= 4);
+        assert!(beams.windows(2).all(|pair| pair[0].score >= pair[1].score));
+        assert!(beams.iter().all(|beam| beam.tokens.len() <= 6));
+
+        // Width 1 keeps only the most likely token: greedy decoding
+        let greedy = generator
+            .beam_search("main", 1, &options, &validator)
+            .unwrap();
+        assert_eq!(greedy.len(), 1);
+        assert_eq!(
+            greedy[0].code,
+            generator.generate_with("main", &options).unwrap()
+        );
+        assert!(beams[0].score >= greedy[0].score - 1e-5);
+    }
+}
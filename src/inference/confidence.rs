@@ -0,0 +1,155 @@
+//! Per-line confidence of generated code
+//!
+//! Token log probabilities say where the model was unsure, but reviewers
+//! read lines. Each line of the formatted code is tokenized again to find the
+//! generated tokens it holds, which works because formatting only moves
+//! whitespace, and the line is scored by its tokens' geometric mean
+//! probability.
+
+use super::GenerationResult;
+use crate::WGSLTokenizer;
+use serde::{Deserialize, Serialize};
+
+/// How sure the model was of one line of generated code
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LineConfidence {
+    /// Line number, from 1
+    pub line: usize,
+    /// Number of generated tokens on the line
+    pub tokens: usize,
+    /// Geometric mean probability of the tokens, in `[0, 1]`
+    pub confidence: f32,
+    /// Least likely token of the line
+    pub weakest_token: String,
+    /// Probability of `weakest_token`
+    pub weakest_probability: f32,
+}
+
+impl GenerationResult {
+    /// Confidence of every line holding generated tokens; empty when the
+    /// result has no token scores, when `code` is a fallback, or when the
+    /// lines don't tokenize back into the generated tokens
+    pub fn line_confidence(&self, tokenizer: &WGSLTokenizer) -> Vec<LineConfidence> {
+        if self.fallback.is_some() || self.log_probs.len() != self.tokens.len() {
+            return Vec::new();
+        }
+        let mut lines = Vec::new();
+        let mut next = 0;
+        for (index, line) in self.code.lines().enumerate() {
+            let count = tokenizer.tokenize(line).len();
+            if count == 0 {
+                continue;
+            }
+            let end = next + count;
+            if end > self.log_probs.len() {
+                break;
+            }
+            let log_probs = &self.log_probs[next..end];
+            let (weakest, &lowest) = log_probs
+                .iter()
+                .enumerate()
+                .min_by(|a, b| a.1.total_cmp(b.1))
+                .expect("line has tokens");
+            lines.push(LineConfidence {
+                line: index + 1,
+                tokens: count,
+                confidence: (log_probs.iter().sum::<f32>() / count as f32).exp(),
+                weakest_token: self.tokens[next + weakest].clone(),
+                weakest_probability: lowest.exp(),
+            });
+            next = end;
+        }
+        if next != self.tokens.len() {
+            tracing::debug!(
+                "{} generated tokens but {} on the formatted lines; not scoring lines",
+                self.tokens.len(),
+                next
+            );
+            return Vec::new();
+        }
+        lines
+    }
+
+    /// `code` with each line's confidence as a trailing comment, aligned
+    /// after the longest annotated line; unchanged when
+    /// [`line_confidence`](Self::line_confidence) is empty
+    pub fn annotate_confidence(&self, tokenizer: &WGSLTokenizer) -> String {
+        let scores = self.line_confidence(tokenizer);
+        let lines: Vec<&str> = self.code.lines().collect();
+        let width = scores
+            .iter()
+            .map(|score| lines[score.line - 1].trim_end().chars().count())
+            .max()
+            .unwrap_or(0);
+        let mut scores = scores.iter().peekable();
+        let mut annotated = String::with_capacity(self.code.len() * 2);
+        for (index, line) in lines.iter().enumerate() {
+            match scores.next_if(|score| score.line == index + 1) {
+                Some(score) => annotated.push_str(&format!(
+                    "{:<width$}  // confidence {:.2} (lowest {:.2}: `{}`)",
+                    line.trim_end(),
+                    score.confidence,
+                    score.weakest_probability,
+                    score.weakest_token,
+                    width = width
+                )),
+                None => annotated.push_str(line),
+            }
+            annotated.push('\n');
+        }
+        if !self.code.ends_with('\n') {
+            annotated.pop();
+        }
+        annotated
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wgsl::ValidationResult;
+    use web_time::Duration;
+
+    #[test]
+    fn test_annotate_confidence() {
+        let tokenizer = WGSLTokenizer::new(64, false);
+        let tokens = ["fn", "main", "(", ")", "{", "}"];
+        let probabilities = [0.9f32, 0.5, 1.0, 1.0, 0.8, 0.2];
+        let result = GenerationResult {
+            code: "fn main() {\n\n}\n".to_string(),
+            tokens: tokens.iter().map(|t| t.to_string()).collect(),
+            log_probs: probabilities.iter().map(|p| p.ln()).collect(),
+            score: probabilities.iter().map(|p| p.ln()).sum(),
+            validation: ValidationResult::invalid("no entry point".to_string()),
+            elapsed: Duration::ZERO,
+            retries: 0,
+            fallback: None,
+        };
+
+        let lines = result.line_confidence(&tokenizer);
+        assert_eq!(lines.len(), 2);
+        assert_eq!((lines[0].line, lines[0].tokens), (1, 5));
+        let expected = (0.9f32 * 0.5 * 0.8).powf(1.0 / 5.0);
+        assert!((lines[0].confidence - expected).abs() < 1e-5);
+        assert_eq!(lines[0].weakest_token, "main");
+        assert_eq!((lines[1].line, lines[1].weakest_token.as_str()), (3, "}"));
+        assert!((lines[1].confidence - 0.2).abs() < 1e-5);
+
+        assert_eq!(
+            result.annotate_confidence(&tokenizer),
+            "fn main() {  // confidence 0.82 (lowest 0.50: `main`)\n\
+             \n\
+             }            // confidence 0.20 (lowest 0.20: `}`)\n"
+        );
+
+        // Scores of other tokens are not attributed to lines
+        let shifted = GenerationResult {
+            code: "fn main() { }".to_string(),
+            tokens: result.tokens[..4].to_vec(),
+            log_probs: result.log_probs[..4].to_vec(),
+            ..result
+        };
+        assert!(shifted.line_confidence(&tokenizer).is_empty());
+        assert_eq!(shifted.annotate_confidence(&tokenizer), shifted.code);
+    }
+}
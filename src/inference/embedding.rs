@@ -0,0 +1,113 @@
+//! Prompt embeddings
+//!
+//! The encoder already maps a prompt to one state per token. Averaging them
+//! gives a fixed-size vector per prompt, which is enough to retrieve similar
+//! training examples, spot near-duplicates or cluster a corpus without a
+//! separate embedding model.
+
+use super::{PromptFields, WGSLGenerator};
+use crate::dataset::WGSLDataset;
+use rayon::prelude::*;
+
+impl WGSLGenerator {
+    /// Mean-pooled encoder states of `prompt`, formatted and tokenized like a
+    /// generation prompt; of length `d_model`
+    pub fn embed(&self, prompt: &str) -> Vec<f32> {
+        let text = self.format_prompt(&PromptFields::new(prompt));
+        self.model
+            .encode(&self.tokenizer.encode_text(&text))
+            .mean_pool()
+    }
+
+    /// [`embed`](Self::embed) every prompt, in parallel
+    pub fn embed_batch<S: AsRef<str> + Sync>(&self, prompts: &[S]) -> Vec<Vec<f32>> {
+        prompts
+            .par_iter()
+            .map(|prompt| self.embed(prompt.as_ref()))
+            .collect()
+    }
+
+    /// Cosine similarity of the embeddings of two prompts
+    pub fn similarity(&self, a: &str, b: &str) -> f32 {
+        cosine_similarity(&self.embed(a), &self.embed(b))
+    }
+
+    /// The `k` examples of `dataset` whose prompts are most similar to
+    /// `prompt`, as example index and similarity, most similar first
+    pub fn nearest_examples(
+        &self,
+        prompt: &str,
+        dataset: &WGSLDataset,
+        k: usize,
+    ) -> Vec<(usize, f32)> {
+        let query = self.embed(prompt);
+        let mut ranked: Vec<(usize, f32)> = dataset
+            .examples
+            .par_iter()
+            .map(|example| cosine_similarity(&query, &self.embed(&example.natural_language)))
+            .enumerate()
+            .collect();
+        // Stable, so earlier examples stay first among equals
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+        ranked.truncate(k);
+        ranked
+    }
+}
+
+/// Cosine of the angle between `a` and `b`, in `[-1, 1]`; 0 when either is
+/// all zeros
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm = |v: &[f32]| v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norms = norm(a) * norm(b);
+    if norms == 0.0 {
+        return 0.0;
+    }
+    (dot / norms).clamp(-1.0, 1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dataset::WGSLExample;
+    use crate::test_support::tiny_generator;
+
+    #[test]
+    fn test_embed_and_rank_examples() {
+        assert_eq!(cosine_similarity(&[1.0, 0.0], &[2.0, 0.0]), 1.0);
+        assert_eq!(cosine_similarity(&[1.0, 0.0], &[0.0, 3.0]), 0.0);
+        assert_eq!(cosine_similarity(&[1.0, 2.0], &[-1.0, -2.0]), -1.0);
+        assert_eq!(cosine_similarity(&[0.0, 0.0], &[1.0, 1.0]), 0.0);
+
+        let (tokenizer, model) = tiny_generator(&["double every value", "red fragment color"]);
+        let generator = WGSLGenerator::new(model, tokenizer);
+
+        let embedding = generator.embed("double every value");
+        assert_eq!(embedding.len(), 16);
+        assert!(embedding.iter().all(|x| x.is_finite()));
+        assert_eq!(
+            generator.embed_batch(&["double every value", "red fragment color"])[0],
+            embedding
+        );
+        assert!((generator.similarity("red color", "red color") - 1.0).abs() < 1e-5);
+
+        let dataset = WGSLDataset {
+            examples: vec![
+                WGSLExample::new("red fragment color", "@fragment fn main() {}"),
+                WGSLExample::new(
+                    "double every value",
+                    "@compute @workgroup_size(1) fn main() {}",
+                ),
+                WGSLExample::new(
+                    "double every value",
+                    "@compute @workgroup_size(2) fn main() {}",
+                ),
+            ],
+        };
+        let nearest = generator.nearest_examples("double every value", &dataset, 2);
+        assert_eq!(nearest.len(), 2);
+        assert_eq!(nearest[0].0, 1);
+        assert_eq!(nearest[1].0, 2);
+        assert!((nearest[0].1 - 1.0).abs() < 1e-5);
+    }
+}
@@ -0,0 +1,277 @@
+//! Nearest-example fallback for generations that never validate
+//!
+//! A tiny model sometimes fails validation on every retry. Integrators that
+//! must always ship a compiling shader can keep a [`FallbackIndex`] of known
+//! good code, dataset examples and built-in templates, and swap an invalid
+//! result for the one whose prompt shares the most words with the request.
+//! The swap is recorded in [`GenerationResult::fallback`].
+
+use super::GenerationResult;
+use crate::dataset::WGSLDataset;
+use crate::wgsl::{TemplateParams, TemplateRegistry};
+use crate::WGSLValidator;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fmt;
+
+/// Where a fallback shader comes from
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FallbackSource {
+    /// A built-in template, by name, rendered with default parameters
+    Template(String),
+    /// An example of the indexed dataset, by position
+    Example(usize),
+}
+
+impl fmt::Display for FallbackSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FallbackSource::Template(name) => write!(f, "template `{}`", name),
+            FallbackSource::Example(index) => write!(f, "dataset example {}", index + 1),
+        }
+    }
+}
+
+/// A stored shader returned in place of generated code that failed
+/// validation
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Fallback {
+    pub source: FallbackSource,
+    /// Prompt the shader is stored under
+    pub prompt: String,
+    /// Share of words the stored prompt has in common with the request, in
+    /// `[0, 1]`
+    pub similarity: f64,
+    /// The generated code that was rejected
+    pub generated: String,
+}
+
+#[derive(Debug, Clone)]
+struct Entry {
+    source: FallbackSource,
+    prompt: String,
+    words: BTreeSet<String>,
+    code: String,
+}
+
+/// Known good shaders, looked up by prompt similarity
+#[derive(Debug, Clone, Default)]
+pub struct FallbackIndex {
+    entries: Vec<Entry>,
+}
+
+impl FallbackIndex {
+    /// Index of the examples of `dataset`; invalid ones are skipped when
+    /// looked up
+    pub fn from_dataset(dataset: &WGSLDataset) -> Self {
+        Self::default().with_dataset(dataset)
+    }
+
+    /// Also index the examples of `dataset`
+    pub fn with_dataset(mut self, dataset: &WGSLDataset) -> Self {
+        for (index, example) in dataset.examples.iter().enumerate() {
+            self.push(
+                FallbackSource::Example(index),
+                &example.natural_language,
+                &example.natural_language,
+                example.wgsl_code.clone(),
+            );
+        }
+        self
+    }
+
+    /// Also index the templates of `registry`, described by their name,
+    /// description and keywords
+    pub fn with_templates(mut self, registry: &TemplateRegistry) -> Self {
+        for template in registry.templates() {
+            let Ok(code) = template.render(&TemplateParams::default()) else {
+                continue;
+            };
+            let text = format!(
+                "{} {} {}",
+                template.name,
+                template.description,
+                template.keywords.join(" ")
+            );
+            self.push(
+                FallbackSource::Template(template.name.clone()),
+                &template.description,
+                &text,
+                code,
+            );
+        }
+        self
+    }
+
+    fn push(&mut self, source: FallbackSource, prompt: &str, text: &str, code: String) {
+        self.entries.push(Entry {
+            source,
+            prompt: prompt.to_string(),
+            words: words(text),
+            code,
+        });
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The valid shader whose prompt is most similar to `prompt`: its
+    /// source, code and similarity; earlier entries win ties
+    pub fn nearest(
+        &self,
+        prompt: &str,
+        validator: &WGSLValidator,
+    ) -> crate::Result<Option<(&FallbackSource, &str, f64)>> {
+        Ok(self
+            .nearest_entry(prompt, validator)?
+            .map(|(entry, similarity)| (&entry.source, entry.code.as_str(), similarity)))
+    }
+
+    fn nearest_entry(
+        &self,
+        prompt: &str,
+        validator: &WGSLValidator,
+    ) -> crate::Result<Option<(&Entry, f64)>> {
+        let query = words(prompt);
+        let mut ranked: Vec<(&Entry, f64)> = self
+            .entries
+            .iter()
+            .map(|entry| (entry, jaccard(&query, &entry.words)))
+            .collect();
+        // Stable, so earlier entries stay first among equals
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+        for (entry, similarity) in ranked {
+            if validator.validate(&entry.code)?.is_valid {
+                return Ok(Some((entry, similarity)));
+            }
+        }
+        Ok(None)
+    }
+
+    /// `result` if it is valid, else the nearest valid shader marked as a
+    /// fallback; token scores still describe the rejected generation
+    pub fn apply(
+        &self,
+        prompt: &str,
+        mut result: GenerationResult,
+        validator: &WGSLValidator,
+    ) -> crate::Result<GenerationResult> {
+        if result.is_valid() {
+            return Ok(result);
+        }
+        let Some((entry, similarity)) = self.nearest_entry(prompt, validator)? else {
+            return Ok(result);
+        };
+        tracing::debug!("Falling back to {} for: {}", entry.source, prompt);
+        result.validation = validator.validate(&entry.code)?;
+        let generated = std::mem::replace(&mut result.code, entry.code.clone());
+        result.fallback = Some(Fallback {
+            source: entry.source.clone(),
+            prompt: entry.prompt.clone(),
+            similarity,
+            generated,
+        });
+        Ok(result)
+    }
+}
+
+/// Lowercase alphanumeric words of `text`
+fn words(text: &str) -> BTreeSet<String> {
+    text.split(|c: char| !c.is_alphanumeric() && c != '_')
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// Shared words over all words of either set; 0 when both are empty
+fn jaccard(a: &BTreeSet<String>, b: &BTreeSet<String>) -> f64 {
+    let union = a.union(b).count();
+    if union == 0 {
+        return 0.0;
+    }
+    a.intersection(b).count() as f64 / union as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dataset::WGSLExample;
+    use crate::wgsl::ValidationResult;
+    use web_time::Duration;
+
+    #[test]
+    fn test_fallback_to_nearest_valid_example() {
+        let dataset = WGSLDataset {
+            examples: vec![
+                WGSLExample::new("double every value", "fn broken( {"),
+                WGSLExample::new(
+                    "double the values of a buffer",
+                    "@compute @workgroup_size(1) fn main() {}",
+                ),
+                WGSLExample::new("red fragment", "@fragment fn main() {}"),
+            ],
+        };
+        let index =
+            FallbackIndex::from_dataset(&dataset).with_templates(&TemplateRegistry::builtin());
+        assert!(index.len() > 3);
+        let validator = WGSLValidator::new();
+
+        // The closest example doesn't compile, so the next one is used
+        let invalid = GenerationResult {
+            code: "fn (".to_string(),
+            tokens: vec!["fn".to_string(), "(".to_string()],
+            log_probs: vec![-0.5, -1.0],
+            score: -1.5,
+            validation: ValidationResult::invalid("Parse error".to_string()),
+            elapsed: Duration::ZERO,
+            retries: 2,
+            fallback: None,
+        };
+        let result = index
+            .apply(
+                "double every value of a buffer",
+                invalid.clone(),
+                &validator,
+            )
+            .unwrap();
+        assert!(result.is_valid());
+        assert_eq!(result.code, dataset.examples[1].wgsl_code);
+        let fallback = result.fallback.unwrap();
+        assert_eq!(fallback.source, FallbackSource::Example(1));
+        assert_eq!(fallback.prompt, "double the values of a buffer");
+        assert_eq!(fallback.similarity, 4.0 / 8.0);
+        assert_eq!(fallback.generated, "fn (");
+        assert_eq!(result.retries, 2);
+
+        let (source, _, _) = index
+            .nearest("mix two colors", &validator)
+            .unwrap()
+            .unwrap();
+        assert_eq!(source, &FallbackSource::Template("mix".to_string()));
+
+        // Valid generations are left alone
+        let valid = GenerationResult {
+            validation: ValidationResult {
+                is_valid: true,
+                errors: Vec::new(),
+                warnings: Vec::new(),
+            },
+            fallback: None,
+            ..invalid.clone()
+        };
+        assert_eq!(
+            index.apply("red", valid.clone(), &validator).unwrap(),
+            valid
+        );
+        let empty = FallbackIndex::default()
+            .apply("red", invalid, &validator)
+            .unwrap();
+        assert!(empty.fallback.is_none());
+    }
+}
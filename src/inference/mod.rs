@@ -1,64 +1,504 @@
 //! Inference engine for generating WGSL code from natural language
 
-use crate::model::CodeGenerationModel;
-use crate::tokenizer::WGSLTokenizer;
+mod attention;
+mod backend;
+mod beam;
+mod confidence;
+mod embedding;
+mod fallback;
+mod prompt;
+#[cfg(feature = "openai")]
+mod remote;
+mod repair;
+mod result;
+mod stream;
+
+pub use attention::AttentionReport;
+pub use backend::{GeneratorBackend, REMOTE_PREFIX};
+pub use confidence::LineConfidence;
+pub use embedding::cosine_similarity;
+pub use fallback::{Fallback, FallbackIndex, FallbackSource};
+pub use prompt::{
+    shader_bindings, shader_stage, stage_token, with_stage_tokens, PromptFields, PromptTemplate,
+    PROMPT_PLACEHOLDERS,
+};
+#[cfg(feature = "openai")]
+pub use remote::OpenAiBackend;
+pub use repair::{RepairAttempt, RepairOptions, RepairResult, DEFAULT_REPAIR_TEMPLATE};
+pub use result::GenerationResult;
+pub use stream::{StreamToken, TokenStream};
+
+use crate::logging::timed;
+use crate::model::{Checkpoint, CodeGenerationModel};
+use crate::tokenizer::{SpecialToken, WGSLTokenizer};
+use crate::wgsl::{format_wgsl_or_original, Stage};
+use ndarray::{Array1, ArrayView1};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use tracing::field::Empty;
+
+/// Decoding settings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GenerationOptions {
+    /// Softmax temperature; 0 always picks the most likely token
+    pub temperature: f32,
+    /// Sample only among the `top_k` most likely tokens (0 = no limit)
+    pub top_k: usize,
+    /// Sample only among the most likely tokens whose probabilities add up
+    /// to `top_p` (1 = no limit)
+    pub top_p: f32,
+    /// Sampling seed; `None` seeds from system entropy
+    pub seed: Option<u64>,
+    /// Stop as soon as the output contains one of these strings, which are
+    /// cut from the output along with any token they overlap
+    pub stop: Vec<String>,
+    /// Most tokens to generate; the tokenizer and model length limits
+    /// always apply
+    pub max_new_tokens: Option<usize>,
+    /// Fewest tokens to generate before end-of-sequence may be picked
+    pub min_new_tokens: usize,
+    /// Divides positive (multiplies negative) logits of tokens already
+    /// generated; 1 disables it
+    pub repetition_penalty: f32,
+    /// Subtracted once from the logit of every token already generated
+    pub presence_penalty: f32,
+    /// Subtracted from a token's logit for every time it was generated
+    pub frequency_penalty: f32,
+    /// Added to the logits of these tokens, by token text
+    pub logit_bias: BTreeMap<String, f32>,
+    /// Tokens that are never generated, by token text
+    pub banned_tokens: Vec<String>,
+    /// Shader stage to generate; prepends the stage's control token, such
+    /// as `<compute>`, to the prompt when the tokenizer registers it
+    pub target_stage: Option<Stage>,
+    /// With a target stage, never generate the entry point attributes of
+    /// other stages, nor `@workgroup_size` outside compute shaders
+    pub constrain_stage: bool,
+}
+
+impl Default for GenerationOptions {
+    fn default() -> Self {
+        Self {
+            temperature: 0.0,
+            top_k: 0,
+            top_p: 1.0,
+            seed: None,
+            stop: Vec::new(),
+            max_new_tokens: None,
+            min_new_tokens: 0,
+            repetition_penalty: 1.0,
+            presence_penalty: 0.0,
+            frequency_penalty: 0.0,
+            logit_bias: BTreeMap::new(),
+            banned_tokens: Vec::new(),
+            target_stage: None,
+            constrain_stage: false,
+        }
+    }
+}
+
+impl GenerationOptions {
+    /// Sampling at `temperature` from the full distribution
+    pub fn sampling(temperature: f32) -> Self {
+        Self {
+            temperature,
+            ..Self::default()
+        }
+    }
+
+    fn has_penalties(&self) -> bool {
+        self.repetition_penalty != 1.0
+            || self.presence_penalty != 0.0
+            || self.frequency_penalty != 0.0
+    }
+
+    /// Attribute tokens other stages than the target use, when constrained
+    fn off_stage_tokens(&self) -> Vec<&'static str> {
+        match self.target_stage {
+            Some(target) if self.constrain_stage => {
+                let mut tokens: Vec<&'static str> = [
+                    (Stage::Vertex, "@vertex"),
+                    (Stage::Fragment, "@fragment"),
+                    (Stage::Compute, "@compute"),
+                ]
+                .into_iter()
+                .filter(|&(stage, _)| stage != target)
+                .map(|(_, token)| token)
+                .collect();
+                if target != Stage::Compute {
+                    tokens.push("@workgroup_size");
+                }
+                tokens
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    fn rng(&self) -> StdRng {
+        match self.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        }
+    }
+}
 
 /// WGSL code generator
 pub struct WGSLGenerator {
     model: CodeGenerationModel,
     tokenizer: WGSLTokenizer,
+    prompt_template: Option<PromptTemplate>,
 }
 
 impl WGSLGenerator {
     /// Create a new generator from a trained model and tokenizer
+    ///
+    /// Warns when their vocabulary sizes differ, which makes the output
+    /// garbage; [`WGSLGenerator::from_checkpoint`] refuses such pairs.
     pub fn new(model: CodeGenerationModel, tokenizer: WGSLTokenizer) -> Self {
-        Self { model, tokenizer }
+        if tokenizer.vocab_size() != model.vocab_size {
+            tracing::warn!(
+                "Tokenizer has {} tokens but the model was built for {}",
+                tokenizer.vocab_size(),
+                model.vocab_size
+            );
+        }
+        Self {
+            model,
+            tokenizer,
+            prompt_template: None,
+        }
     }
 
-    /// Load generator from checkpoint (placeholder)
-    pub fn from_checkpoint(_path: &str) -> crate::Result<Self> {
-        // TODO: Implement checkpoint loading
-        Err(crate::Error::Other(
-            "Checkpoint loading not yet implemented".to_string(),
-        ))
+    /// Load generator from a checkpoint written by [`Checkpoint::save`],
+    /// refusing one whose tokenizer doesn't match the model
+    pub fn from_checkpoint<P: AsRef<Path>>(path: P) -> crate::Result<Self> {
+        let path = path.as_ref();
+        let checkpoint = Checkpoint::load(path)?;
+        checkpoint
+            .check_tokenizer()
+            .map_err(|e| crate::model::checkpoint::with_path(e, path))?;
+        for warning in checkpoint.compatibility_warnings() {
+            tracing::warn!("{}: {}", path.display(), warning);
+        }
+        Self::from_loaded_checkpoint(checkpoint)
+    }
+
+    /// Load generator from checkpoint bytes, such as a file fetched by a
+    /// browser
+    pub fn from_checkpoint_bytes(bytes: &[u8]) -> crate::Result<Self> {
+        let checkpoint = Checkpoint::from_bytes(bytes)?;
+        checkpoint.check_tokenizer()?;
+        for warning in checkpoint.compatibility_warnings() {
+            tracing::warn!("{}", warning);
+        }
+        Self::from_loaded_checkpoint(checkpoint)
+    }
+
+    fn from_loaded_checkpoint(checkpoint: Checkpoint) -> crate::Result<Self> {
+        let prompt_template = checkpoint
+            .metadata
+            .prompt_template
+            .map(PromptTemplate::new)
+            .transpose()?;
+        Ok(Self::new(checkpoint.model, checkpoint.tokenizer).with_prompt_template(prompt_template))
+    }
+
+    /// Format every prompt with `template` before tokenizing it, as the
+    /// model's training data was
+    pub fn with_prompt_template(mut self, template: Option<PromptTemplate>) -> Self {
+        self.prompt_template = template;
+        self
+    }
+
+    /// Template prompts are formatted with, if any
+    pub fn prompt_template(&self) -> Option<&PromptTemplate> {
+        self.prompt_template.as_ref()
+    }
+
+    /// Text the model sees for `fields`, normalized like the training
+    /// prompts were
+    pub fn format_prompt(&self, fields: &PromptFields) -> String {
+        let text = match &self.prompt_template {
+            Some(template) => template.render(fields),
+            None => fields.description.clone(),
+        };
+        self.tokenizer.normalize_prompt(&text)
+    }
+
+    /// The underlying model
+    pub fn model(&self) -> &CodeGenerationModel {
+        &self.model
+    }
+
+    /// The tokenizer prompts and code are encoded with
+    pub fn tokenizer(&self) -> &WGSLTokenizer {
+        &self.tokenizer
     }
 
     /// Generate WGSL code from natural language description
     pub fn generate(&self, prompt: &str) -> crate::Result<String> {
-        tracing::debug!("Generating WGSL for prompt: {}", prompt);
+        self.generate_with(prompt, &GenerationOptions::default())
+    }
 
-        // Tokenize input
-        let tokens = self.tokenizer.tokenize(prompt);
-        let input_ids = self.tokenizer.encode(&tokens);
+    /// Generate WGSL code using the given decoding settings
+    pub fn generate_with(
+        &self,
+        prompt: &str,
+        options: &GenerationOptions,
+    ) -> crate::Result<String> {
+        Ok(self.sample_n(prompt, 1, options)?.remove(0))
+    }
+
+    /// Draw `n` generations for one prompt from a single seeded sampler
+    pub fn sample_n(
+        &self,
+        prompt: &str,
+        n: usize,
+        options: &GenerationOptions,
+    ) -> crate::Result<Vec<String>> {
+        tracing::debug!("Generating {} sample(s) for prompt: {}", n, prompt);
+        self.sample_text(&self.format_prompt(&PromptFields::new(prompt)), n, options)
+    }
 
-        // Run model inference
-        let _logits = self.model.forward(&input_ids);
+    /// Generate from a description plus shader stage and bindings, which
+    /// only reach the model through a prompt template
+    pub fn generate_from_fields(
+        &self,
+        fields: &PromptFields,
+        options: &GenerationOptions,
+    ) -> crate::Result<String> {
+        Ok(self
+            .sample_text(&self.format_prompt(fields), 1, options)?
+            .remove(0))
+    }
+
+    /// Draw `n` generations from already formatted prompt `text`
+    fn sample_text(
+        &self,
+        text: &str,
+        n: usize,
+        options: &GenerationOptions,
+    ) -> crate::Result<Vec<String>> {
+        let span =
+            tracing::debug_span!("generate", samples = n, tokens = Empty, elapsed_ms = Empty);
+        let mut rng = options.rng();
+        let mut tokens = 0;
+        let outputs = timed(&span, || {
+            (0..n)
+                .map(|_| {
+                    let output_ids = self.decode_ids(text, options, &mut rng);
+                    tokens += output_ids.len();
+                    format_wgsl_or_original(&self.tokenizer.decode_to_text(&output_ids))
+                })
+                .collect()
+        });
+        span.record("tokens", tokens);
+        Ok(outputs)
+    }
 
-        // TODO: Implement beam search / greedy decoding
+    /// Generate code for every prompt, decoding them together
+    ///
+    /// The prompts go through one encoder pass, then every decoding step runs
+    /// one decoder pass over the prompts still generating, padded to a common
+    /// length and masked, so results match
+    /// [`generate_with`](Self::generate_with). With a seed, prompt `i` is
+    /// sampled with `seed + i`.
+    pub fn generate_batch<S: AsRef<str>>(
+        &self,
+        prompts: &[S],
+        options: &GenerationOptions,
+    ) -> crate::Result<Vec<String>> {
+        let span = tracing::debug_span!(
+            "generate",
+            samples = prompts.len(),
+            tokens = Empty,
+            elapsed_ms = Empty
+        );
+        let prompts: Vec<(String, GenerationOptions)> = prompts
+            .iter()
+            .enumerate()
+            .map(|(index, prompt)| {
+                let options = GenerationOptions {
+                    seed: options.seed.map(|seed| seed.wrapping_add(index as u64)),
+                    ..options.clone()
+                };
+                (
+                    self.format_prompt(&PromptFields::new(prompt.as_ref())),
+                    options,
+                )
+            })
+            .collect();
+        let streams = timed(&span, || {
+            let mut streams = TokenStream::new_batch(self, &prompts);
+            while TokenStream::step_batch(&mut streams) {}
+            streams
+        });
+        span.record(
+            "tokens",
+            streams
+                .iter()
+                .map(|stream| stream.generated_ids().len())
+                .sum::<usize>(),
+        );
+        Ok(streams
+            .iter()
+            .map(|stream| {
+                format_wgsl_or_original(&self.tokenizer.decode_to_text(stream.generated_ids()))
+            })
+            .collect())
+    }
 
-        // Placeholder response
-        Ok(format!(
-            "// Generated WGSL for: {}\nfn placeholder() {{\n    // TODO: Implement\n}}",
-            prompt
-        ))
+    /// Decode lazily, yielding each token as soon as it is picked
+    pub fn stream(&self, prompt: &str, options: &GenerationOptions) -> TokenStream<'_> {
+        let text = self.format_prompt(&PromptFields::new(prompt));
+        TokenStream::new(self, &text, options, options.rng())
+    }
+
+    /// Generate like [`generate_with`](Self::generate_with), calling
+    /// `on_token` with every token as it is decoded
+    ///
+    /// The streamed deltas concatenate to the raw decoded text; the returned
+    /// code is that text formatted.
+    pub fn generate_streaming(
+        &self,
+        prompt: &str,
+        options: &GenerationOptions,
+        mut on_token: impl FnMut(&StreamToken),
+    ) -> crate::Result<String> {
+        let span =
+            tracing::debug_span!("generate", samples = 1, tokens = Empty, elapsed_ms = Empty);
+        let mut stream = self.stream(prompt, options);
+        timed(&span, || {
+            for token in stream.by_ref() {
+                on_token(&token);
+            }
+        });
+        span.record("tokens", stream.generated_ids().len());
+        let text = self.tokenizer.decode_to_text(stream.generated_ids());
+        Ok(format_wgsl_or_original(&text))
+    }
+
+    /// Pick next tokens for formatted prompt `text` until end-of-sequence or
+    /// the length limit, returning the generated ids without special tokens
+    fn decode_ids(&self, text: &str, options: &GenerationOptions, rng: &mut StdRng) -> Vec<usize> {
+        let mut stream = TokenStream::new(self, text, options, rng.clone());
+        while stream.step() {}
+        let ids = stream.generated_ids().to_vec();
+        *rng = stream.rng;
+        ids
+    }
+
+    /// Mask of model outputs the tokenizer can turn back into text, plus
+    /// end-of-sequence; control tokens only ever condition the prompt
+    fn decodable_tokens(&self) -> Vec<bool> {
+        let never = [
+            SpecialToken::Padding.token_id(),
+            SpecialToken::Unknown.token_id(),
+            SpecialToken::StartOfSequence.token_id(),
+        ];
+        (0..self.model.vocab_size)
+            .map(|id| {
+                self.tokenizer.reverse_vocab.contains_key(&id)
+                    && !never.contains(&id)
+                    && !self.tokenizer.is_control_id(id)
+            })
+            .collect()
     }
 
     /// Generate with configuration options
     pub fn generate_with_options(
         &self,
         prompt: &str,
-        _temperature: f32,
-        _top_k: usize,
+        temperature: f32,
+        top_k: usize,
     ) -> crate::Result<String> {
-        // For now, just call basic generate
-        self.generate(prompt)
+        let options = GenerationOptions {
+            temperature,
+            top_k,
+            ..GenerationOptions::default()
+        };
+        self.generate_with(prompt, &options)
     }
 }
 
+/// Lower the logits of tokens in `generated` by the repetition, presence and
+/// frequency penalties of `options`
+fn apply_penalties(logits: &mut Array1<f32>, generated: &[usize], options: &GenerationOptions) {
+    let mut counts: HashMap<usize, usize> = HashMap::new();
+    for &id in generated {
+        *counts.entry(id).or_default() += 1;
+    }
+    for (id, count) in counts {
+        let Some(logit) = logits.get_mut(id) else {
+            continue;
+        };
+        if *logit > 0.0 {
+            *logit /= options.repetition_penalty;
+        } else {
+            *logit *= options.repetition_penalty;
+        }
+        *logit -= options.presence_penalty + options.frequency_penalty * count as f32;
+    }
+}
+
+/// Choose the next token among `allowed` ids: the arg-max at temperature 0,
+/// otherwise a draw from the (top-k and top-p truncated) tempered softmax
+fn next_token(
+    logits: ArrayView1<f32>,
+    allowed: &[bool],
+    options: &GenerationOptions,
+    rng: &mut StdRng,
+) -> Option<usize> {
+    let mut candidates: Vec<(usize, f32)> = logits
+        .iter()
+        .enumerate()
+        .filter(|(id, _)| allowed[*id])
+        .map(|(id, &logit)| (id, logit))
+        .collect();
+    candidates.sort_by(|a, b| b.1.total_cmp(&a.1));
+    if options.temperature <= 0.0 {
+        return candidates.first().map(|(id, _)| *id);
+    }
+    if options.top_k > 0 {
+        candidates.truncate(options.top_k);
+    }
+
+    let max = candidates.first()?.1;
+    let mut weights: Vec<f32> = candidates
+        .iter()
+        .map(|(_, logit)| ((logit - max) / options.temperature).exp())
+        .collect();
+    if options.top_p < 1.0 {
+        let total: f32 = weights.iter().sum();
+        let mut cumulative = 0.0;
+        let nucleus = weights
+            .iter()
+            .position(|weight| {
+                cumulative += weight / total;
+                cumulative >= options.top_p
+            })
+            .map_or(weights.len(), |last| last + 1);
+        weights.truncate(nucleus);
+        candidates.truncate(nucleus);
+    }
+    let mut target = rng.gen::<f32>() * weights.iter().sum::<f32>();
+    for ((id, _), weight) in candidates.iter().zip(&weights) {
+        if target < *weight {
+            return Some(*id);
+        }
+        target -= weight;
+    }
+    candidates.last().map(|(id, _)| *id)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::model::{CodeGenerationModel, ModelArchitecture};
+    use crate::model::{CheckpointMetadata, CodeGenerationModel, ModelArchitecture};
+    use crate::test_support::{tiny_generator, tiny_model};
     use crate::tokenizer::WGSLTokenizer;
 
     #[test]
@@ -71,4 +511,299 @@ mod tests {
         let result = generator.generate("create a red color");
         assert!(result.is_ok());
     }
+
+    #[test]
+    fn test_generate_from_checkpoint() {
+        let (tokenizer, model) = tiny_generator(&["fn main() { }"]);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("model.ckpt");
+        Checkpoint::new(model, tokenizer.clone())
+            .save(&path)
+            .unwrap();
+
+        let generator = WGSLGenerator::from_checkpoint(&path).unwrap();
+        let ids = generator.decode_ids(
+            "main function",
+            &GenerationOptions::default(),
+            &mut StdRng::seed_from_u64(0),
+        );
+        assert!(ids.len() <= 16);
+        assert!(ids
+            .iter()
+            .all(|id| *id > SpecialToken::EndOfSequence.token_id()));
+
+        // Only tokens from the vocabulary are ever produced
+        let code = generator.generate("main function").unwrap();
+        for token in tokenizer.tokenize(&code) {
+            assert!(tokenizer.vocab.contains_key(&token), "{}", token);
+        }
+
+        assert!(WGSLGenerator::from_checkpoint(dir.path().join("missing.ckpt")).is_err());
+    }
+
+    #[test]
+    fn test_penalties() {
+        let mut logits = ndarray::arr1(&[2.0, -1.0, 3.0, 1.0]);
+        let options = GenerationOptions {
+            repetition_penalty: 2.0,
+            presence_penalty: 0.5,
+            frequency_penalty: 0.25,
+            ..GenerationOptions::default()
+        };
+        apply_penalties(&mut logits, &[0, 1, 0, 7], &options);
+        assert_eq!(
+            logits.to_vec(),
+            vec![2.0 / 2.0 - 1.0, -2.0 - 0.75, 3.0, 1.0]
+        );
+
+        // Looping on a token eventually makes another one the greedy pick
+        let mut logits = ndarray::arr1(&[2.0, 1.5]);
+        apply_penalties(
+            &mut logits,
+            &[0, 0, 0],
+            &GenerationOptions {
+                frequency_penalty: 0.2,
+                ..GenerationOptions::default()
+            },
+        );
+        let mut rng = StdRng::seed_from_u64(0);
+        let greedy = GenerationOptions::default();
+        assert_eq!(
+            next_token(logits.view(), &[true, true], &greedy, &mut rng),
+            Some(1)
+        );
+    }
+
+    #[test]
+    fn test_logit_bias_and_banned_tokens() {
+        let (tokenizer, model) = tiny_generator(&["@compute fn main() { }"]);
+        let generator = WGSLGenerator::new(model, tokenizer);
+        let greedy = GenerationOptions {
+            max_new_tokens: Some(1),
+            min_new_tokens: 1,
+            ..GenerationOptions::default()
+        };
+        let first = generator.stream("main", &greedy).next().unwrap();
+
+        let banned = GenerationOptions {
+            banned_tokens: vec![first.token.clone(), "not-a-token".to_string()],
+            ..greedy.clone()
+        };
+        let tokens: Vec<StreamToken> = generator.stream("main", &banned).collect();
+        assert_ne!(tokens[0].id, first.id);
+
+        let forced = GenerationOptions {
+            logit_bias: [("@compute".to_string(), 100.0)].into_iter().collect(),
+            max_new_tokens: Some(3),
+            min_new_tokens: 3,
+            ..GenerationOptions::default()
+        };
+        assert!(generator
+            .stream("main", &forced)
+            .all(|token| token.token == "@compute"));
+    }
+
+    #[test]
+    fn test_target_stage() {
+        let mut tokenizer = WGSLTokenizer::new(16, false);
+        tokenizer.add_control_token("<compute>").unwrap();
+        tokenizer.add_control_token("<fragment>").unwrap();
+        tokenizer.fit(&["@compute @workgroup_size(1) fn main() { } @fragment"], 1);
+        let model = tiny_model(&tokenizer);
+        let generator = WGSLGenerator::new(model, tokenizer);
+        let biased = |tokens: &[&str], constrain_stage| GenerationOptions {
+            logit_bias: tokens.iter().map(|t| (t.to_string(), 100.0)).collect(),
+            max_new_tokens: Some(4),
+            min_new_tokens: 4,
+            target_stage: Some(Stage::Fragment),
+            constrain_stage,
+            ..GenerationOptions::default()
+        };
+
+        let tokens: Vec<String> = generator
+            .stream("blur", &biased(&["@compute"], false))
+            .map(|token| token.token)
+            .collect();
+        assert_eq!(tokens, ["@compute"; 4]);
+
+        // Constrained, only the fragment stage's attributes can appear, and
+        // control tokens are never generated
+        let options = biased(&["@compute", "@workgroup_size", "<fragment>"], true);
+        for token in generator.stream("blur", &options) {
+            assert!(
+                !["@compute", "@workgroup_size", "<fragment>"].contains(&token.token.as_str()),
+                "{}",
+                token.token
+            );
+        }
+    }
+
+    #[test]
+    fn test_next_token_sampling() {
+        let logits = ndarray::arr1(&[5.0, 1.0, 4.0, 0.0, 3.0]);
+        let allowed = [false, true, true, true, true];
+        let mut rng = StdRng::seed_from_u64(7);
+
+        // Greedy skips the masked arg-max
+        let greedy = GenerationOptions::default();
+        assert_eq!(
+            next_token(logits.view(), &allowed, &greedy, &mut rng),
+            Some(2)
+        );
+
+        let top_2 = GenerationOptions {
+            temperature: 1.0,
+            top_k: 2,
+            ..GenerationOptions::default()
+        };
+        let mut drawn = std::collections::BTreeSet::new();
+        for _ in 0..200 {
+            drawn.insert(next_token(logits.view(), &allowed, &top_2, &mut rng).unwrap());
+        }
+        assert_eq!(drawn.into_iter().collect::<Vec<_>>(), vec![2, 4]);
+
+        let nucleus = GenerationOptions {
+            temperature: 1.0,
+            top_p: 0.01,
+            ..GenerationOptions::default()
+        };
+        for _ in 0..20 {
+            assert_eq!(
+                next_token(logits.view(), &allowed, &nucleus, &mut rng),
+                Some(2)
+            );
+        }
+    }
+
+    #[test]
+    fn test_streaming_matches_generate() {
+        let (tokenizer, model) = tiny_generator(&["fn main() { let x = 1.0; }"]);
+        let generator = WGSLGenerator::new(model, tokenizer);
+        let options = GenerationOptions {
+            seed: Some(11),
+            ..GenerationOptions::sampling(1.0)
+        };
+
+        let mut streamed = String::new();
+        let mut count = 0;
+        let code = generator
+            .generate_streaming("main", &options, |token| {
+                streamed.push_str(&token.delta);
+                count += 1;
+            })
+            .unwrap();
+        assert!(count > 0);
+        assert_eq!(code, generator.generate_with("main", &options).unwrap());
+
+        let tokens: Vec<StreamToken> = generator.stream("main", &options).collect();
+        assert_eq!(tokens.len(), count);
+        let ids: Vec<usize> = tokens.iter().map(|token| token.id).collect();
+        assert_eq!(streamed, generator.tokenizer().decode_to_text(&ids));
+    }
+
+    #[test]
+    fn test_length_limits_and_stop_sequences() {
+        let (tokenizer, model) =
+            tiny_generator(&["fn main() { let x = vec3<f32>(1.0, 0.5, 0.0); }"]);
+        let generator = WGSLGenerator::new(model, tokenizer);
+        let exactly = |n| GenerationOptions {
+            seed: Some(5),
+            min_new_tokens: n,
+            max_new_tokens: Some(n),
+            ..GenerationOptions::sampling(1.0)
+        };
+
+        let full: Vec<StreamToken> = generator.stream("main", &exactly(8)).collect();
+        assert_eq!(full.len(), 8);
+        assert_eq!(generator.stream("main", &exactly(3)).count(), 3);
+
+        let stop = format!("{} {}", full[3].token, full[4].token);
+        let text: Vec<&str> = full.iter().map(|token| token.token.as_str()).collect();
+        let start = text.join(" ").find(&stop).unwrap();
+        let mut end = 0;
+        let expected = text
+            .iter()
+            .take_while(|token| {
+                end += token.len() + 1;
+                end <= start + 1
+            })
+            .count();
+
+        let options = GenerationOptions {
+            stop: vec![stop.clone()],
+            ..exactly(8)
+        };
+        let stopped: Vec<StreamToken> = generator.stream("main", &options).collect();
+        assert_eq!(stopped, full[..expected].to_vec());
+        let code = generator.generate_with("main", &options).unwrap();
+        assert!(!code.contains(&stop));
+    }
+
+    #[test]
+    fn test_prompt_template_from_checkpoint() {
+        let (tokenizer, model) = tiny_generator(&["compute: fn main() { }"]);
+        let metadata = CheckpointMetadata {
+            prompt_template: Some("{stage}: {description}".to_string()),
+            ..CheckpointMetadata::default()
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("model.ckpt");
+        Checkpoint::new(model, tokenizer)
+            .with_metadata(metadata)
+            .save(&path)
+            .unwrap();
+
+        let generator = WGSLGenerator::from_checkpoint(&path).unwrap();
+        let fields = PromptFields::new("main").with_stage("compute");
+        assert_eq!(generator.format_prompt(&fields), "compute: main");
+        let options = GenerationOptions::default();
+        let plain = generator
+            .with_prompt_template(None)
+            .generate_with("compute: main", &options)
+            .unwrap();
+        let generator = WGSLGenerator::from_checkpoint(&path).unwrap();
+        assert_eq!(
+            generator.generate_from_fields(&fields, &options).unwrap(),
+            plain
+        );
+    }
+
+    #[test]
+    fn test_generate_batch() {
+        let (tokenizer, model) = tiny_generator(&["fn main() { let x = 1.0; }"]);
+        let generator = WGSLGenerator::new(model, tokenizer);
+        let options = GenerationOptions {
+            seed: Some(4),
+            ..GenerationOptions::sampling(1.0)
+        };
+
+        let prompts = ["main", "let x", "fn"];
+        let batch = generator.generate_batch(&prompts, &options).unwrap();
+        assert_eq!(batch.len(), 3);
+        for (index, prompt) in prompts.iter().enumerate() {
+            let single = GenerationOptions {
+                seed: Some(4 + index as u64),
+                ..options.clone()
+            };
+            assert_eq!(
+                batch[index],
+                generator.generate_with(prompt, &single).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn test_seeded_sampling_is_reproducible() {
+        let (tokenizer, model) = tiny_generator(&["fn main() { let x = 1.0; }"]);
+        let generator = WGSLGenerator::new(model, tokenizer);
+
+        let options = GenerationOptions {
+            seed: Some(3),
+            ..GenerationOptions::sampling(1.5)
+        };
+        let first = generator.sample_n("main", 4, &options).unwrap();
+        assert_eq!(first.len(), 4);
+        assert_eq!(first, generator.sample_n("main", 4, &options).unwrap());
+    }
 }
@@ -0,0 +1,220 @@
+//! Prompt formatting shared by training and inference
+//!
+//! A [`PromptTemplate`] turns a description, plus optionally the shader stage
+//! and resource bindings, into the text that is tokenized. Training formats
+//! every example with it and the template is saved in the checkpoint, so
+//! [`WGSLGenerator`](super::WGSLGenerator) formats prompts the same way.
+
+use crate::dataset::{WGSLDataset, WGSLExample};
+use crate::tokenizer::WGSLTokenizer;
+use serde::{Deserialize, Serialize};
+
+/// Placeholders a template may use
+pub const PROMPT_PLACEHOLDERS: [&str; 3] = ["{description}", "{stage}", "{bindings}"];
+
+/// Values substituted into a [`PromptTemplate`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PromptFields {
+    /// Natural language description of the shader
+    pub description: String,
+    /// Shader stage: "compute", "vertex" or "fragment"
+    pub stage: String,
+    /// Resource bindings, e.g. `var<storage, read_write> data: array<f32>`
+    pub bindings: String,
+}
+
+impl PromptFields {
+    /// Fields of a prompt with only a description
+    pub fn new(description: impl Into<String>) -> Self {
+        Self {
+            description: description.into(),
+            ..Self::default()
+        }
+    }
+
+    pub fn with_stage(mut self, stage: impl Into<String>) -> Self {
+        self.stage = stage.into();
+        self
+    }
+
+    pub fn with_bindings(mut self, bindings: impl Into<String>) -> Self {
+        self.bindings = bindings.into();
+        self
+    }
+
+    /// Fields of a training example: its description, plus the stage and
+    /// bindings declared by its code
+    pub fn from_example(example: &WGSLExample) -> Self {
+        Self::new(example.natural_language.clone())
+            .with_stage(shader_stage(&example.wgsl_code).unwrap_or_default())
+            .with_bindings(shader_bindings(&example.wgsl_code))
+    }
+}
+
+/// Text with `{description}`, `{stage}` and `{bindings}` placeholders
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct PromptTemplate(String);
+
+impl PromptTemplate {
+    /// Parse a template, rejecting one without `{description}`
+    pub fn new(template: impl Into<String>) -> crate::Result<Self> {
+        let template = template.into();
+        if !template.contains("{description}") {
+            return Err(crate::Error::ConfigError(format!(
+                "prompt template '{}' has no {{description}} placeholder",
+                template
+            )));
+        }
+        Ok(Self(template))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// The template with its placeholders replaced by `fields`
+    pub fn render(&self, fields: &PromptFields) -> String {
+        self.0
+            .replace("{stage}", &fields.stage)
+            .replace("{bindings}", &fields.bindings)
+            .replace("{description}", &fields.description)
+    }
+
+    /// `dataset` with every description replaced by its rendered prompt
+    pub fn format_dataset(&self, dataset: &WGSLDataset) -> WGSLDataset {
+        let examples = dataset
+            .examples
+            .iter()
+            .map(|example| WGSLExample {
+                natural_language: self.render(&PromptFields::from_example(example)),
+                ..example.clone()
+            })
+            .collect();
+        WGSLDataset { examples }
+    }
+}
+
+impl Default for PromptTemplate {
+    fn default() -> Self {
+        Self("{description}".to_string())
+    }
+}
+
+/// Stage of the first entry point declared in `code`
+pub fn shader_stage(code: &str) -> Option<&'static str> {
+    ["compute", "vertex", "fragment"]
+        .into_iter()
+        .filter_map(|stage| {
+            code.find(&format!("@{}", stage))
+                .map(|position| (position, stage))
+        })
+        .min()
+        .map(|(_, stage)| stage)
+}
+
+/// Control token conditioning a prompt on `stage`, e.g. `<compute>`
+pub fn stage_token(stage: &str) -> String {
+    format!("<{}>", stage)
+}
+
+/// `dataset` with every description prefixed by its shader stage's control
+/// token, for the stages `tokenizer` registers a token for
+///
+/// Training on these prompts is what lets
+/// [`GenerationOptions::target_stage`](super::GenerationOptions::target_stage)
+/// steer generation.
+pub fn with_stage_tokens(dataset: &WGSLDataset, tokenizer: &WGSLTokenizer) -> WGSLDataset {
+    let examples = dataset
+        .examples
+        .iter()
+        .map(|example| {
+            let token = shader_stage(&example.wgsl_code)
+                .map(stage_token)
+                .filter(|token| tokenizer.control_tokens().contains(token));
+            match token {
+                Some(token) => WGSLExample {
+                    natural_language: format!("{} {}", token, example.natural_language),
+                    ..example.clone()
+                },
+                None => example.clone(),
+            }
+        })
+        .collect();
+    WGSLDataset { examples }
+}
+
+/// Declarations of the `@binding` variables of `code`, joined by `, `
+pub fn shader_bindings(code: &str) -> String {
+    code.split(';')
+        .filter_map(|statement| {
+            let (_, declaration) = statement.split_once("@binding(")?;
+            let (_, declaration) = declaration.split_once(')')?;
+            Some(declaration.split_whitespace().collect::<Vec<_>>().join(" "))
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHADER: &str = "@group(0) @binding(0) var<storage, read_write> data: array<f32>;\n\
+        @group(0) @binding(1)\n  var<uniform> scale: f32;\n\
+        @compute @workgroup_size(64) fn main() { }";
+
+    #[test]
+    fn test_render_template() {
+        let template = PromptTemplate::new("[{stage}] {description} | {bindings}").unwrap();
+        let fields = PromptFields::new("double values")
+            .with_stage("compute")
+            .with_bindings("var<uniform> scale: f32");
+        assert_eq!(
+            template.render(&fields),
+            "[compute] double values | var<uniform> scale: f32"
+        );
+        assert_eq!(PromptTemplate::default().render(&fields), "double values");
+        assert!(PromptTemplate::new("{stage} only").is_err());
+    }
+
+    #[test]
+    fn test_fields_from_example() {
+        let example = WGSLExample::new("double values", SHADER);
+        let fields = PromptFields::from_example(&example);
+
+        assert_eq!(fields.stage, "compute");
+        assert_eq!(
+            fields.bindings,
+            "var<storage, read_write> data: array<f32>, var<uniform> scale: f32"
+        );
+        assert_eq!(shader_stage("fn helper() { }"), None);
+        assert_eq!(
+            shader_stage("@vertex fn vs() { } @fragment fn fs() { }"),
+            Some("vertex")
+        );
+    }
+
+    #[test]
+    fn test_with_stage_tokens() {
+        let dataset = WGSLDataset {
+            examples: vec![
+                WGSLExample::new("double values", SHADER),
+                WGSLExample::new(
+                    "red",
+                    "@fragment fn main() -> @location(0) vec4<f32> { return vec4<f32>(1.0); }",
+                ),
+                WGSLExample::new("helper", "fn helper() { }"),
+            ],
+        };
+
+        let mut tokenizer = WGSLTokenizer::new(64, false);
+        tokenizer.add_control_token("<compute>").unwrap();
+        let prompts: Vec<String> = with_stage_tokens(&dataset, &tokenizer)
+            .examples
+            .into_iter()
+            .map(|example| example.natural_language)
+            .collect();
+        assert_eq!(prompts, ["<compute> double values", "red", "helper"]);
+    }
+}
@@ -0,0 +1,258 @@
+//! OpenAI-compatible HTTP backend (`openai` feature)
+//!
+//! Sends each prompt to a chat completions endpoint, which OpenAI and most
+//! self-hosted servers (vLLM, llama.cpp, Ollama) expose, and keeps the code
+//! of the reply. A larger remote model can then be validated, repaired and
+//! evaluated like a checkpoint, or write the training data of a local one.
+
+use super::{GenerationOptions, GeneratorBackend, REMOTE_PREFIX};
+use crate::config::ProviderConfig;
+use crate::wgsl::format_wgsl_or_original;
+use serde_json::{json, Value};
+use std::time::Duration;
+
+const DEFAULT_ENDPOINT: &str = "https://api.openai.com/v1";
+
+const DEFAULT_SYSTEM_PROMPT: &str = "You write WGSL shaders for WebGPU. \
+    Reply with only the WGSL code, without explanations or Markdown.";
+
+/// Most stop sequences the API accepts
+const MAX_STOP: usize = 4;
+
+/// Client for one model of an OpenAI-compatible API
+pub struct OpenAiBackend {
+    agent: ureq::Agent,
+    endpoint: String,
+    api_key: Option<String>,
+    model: String,
+    system_prompt: String,
+}
+
+impl OpenAiBackend {
+    /// Backend for `model` at `config.endpoint`, authenticated with
+    /// `config.api_key` or `OPENAI_API_KEY` when either is set
+    pub fn new(model: &str, config: &ProviderConfig) -> Self {
+        Self {
+            agent: ureq::AgentBuilder::new()
+                .timeout(Duration::from_secs(config.timeout_secs))
+                .build(),
+            endpoint: config
+                .endpoint
+                .clone()
+                .unwrap_or_else(|| DEFAULT_ENDPOINT.to_string())
+                .trim_end_matches('/')
+                .to_string(),
+            api_key: config
+                .api_key
+                .clone()
+                .or_else(|| std::env::var("OPENAI_API_KEY").ok())
+                .filter(|key| !key.is_empty()),
+            model: model.to_string(),
+            system_prompt: config
+                .system_prompt
+                .clone()
+                .unwrap_or_else(|| DEFAULT_SYSTEM_PROMPT.to_string()),
+        }
+    }
+
+    /// Backend for a CLI model name such as `openai:gpt-4o-mini`, or `None`
+    /// when `name` has no [`REMOTE_PREFIX`]
+    pub fn from_model_name(name: &str, config: &ProviderConfig) -> Option<Self> {
+        name.strip_prefix(REMOTE_PREFIX)
+            .map(|model| Self::new(model, config))
+    }
+
+    /// Name of the remote model
+    pub fn model(&self) -> &str {
+        &self.model
+    }
+
+    /// Chat completion request for `prompt`
+    fn request_body(&self, prompt: &str, n: usize, options: &GenerationOptions) -> Value {
+        let prompt = match options.target_stage {
+            Some(stage) => format!("{} (a {} shader)", prompt, stage.as_str()),
+            None => prompt.to_string(),
+        };
+        let mut body = json!({
+            "model": self.model,
+            "messages": [
+                { "role": "system", "content": self.system_prompt },
+                { "role": "user", "content": prompt },
+            ],
+            "n": n,
+            "temperature": options.temperature,
+        });
+        if options.top_p < 1.0 {
+            body["top_p"] = json!(options.top_p);
+        }
+        if let Some(max_tokens) = options.max_new_tokens {
+            body["max_tokens"] = json!(max_tokens);
+        }
+        if let Some(seed) = options.seed {
+            body["seed"] = json!(seed);
+        }
+        if !options.stop.is_empty() {
+            body["stop"] = json!(&options.stop[..options.stop.len().min(MAX_STOP)]);
+        }
+        if options.presence_penalty != 0.0 {
+            body["presence_penalty"] = json!(options.presence_penalty);
+        }
+        if options.frequency_penalty != 0.0 {
+            body["frequency_penalty"] = json!(options.frequency_penalty);
+        }
+        body
+    }
+}
+
+impl GeneratorBackend for OpenAiBackend {
+    fn sample_n(
+        &self,
+        prompt: &str,
+        n: usize,
+        options: &GenerationOptions,
+    ) -> crate::Result<Vec<String>> {
+        tracing::debug!("Requesting {} completion(s) from {}", n, self.model);
+        let mut request = self
+            .agent
+            .post(&format!("{}/chat/completions", self.endpoint));
+        if let Some(key) = &self.api_key {
+            request = request.set("Authorization", &format!("Bearer {}", key));
+        }
+        let response: Value = request
+            .send_json(self.request_body(prompt, n, options))
+            .map_err(request_error)?
+            .into_json()?;
+
+        let choices = response["choices"].as_array().ok_or_else(|| {
+            crate::Error::Other(format!("Provider response has no choices: {}", response))
+        })?;
+        let codes: Vec<String> = choices
+            .iter()
+            .filter_map(|choice| choice["message"]["content"].as_str())
+            .map(|content| format_wgsl_or_original(extract_code(content)))
+            .collect();
+        if codes.len() < n {
+            return Err(crate::Error::Other(format!(
+                "{} returned {} of {} completions",
+                self.model,
+                codes.len(),
+                n
+            )));
+        }
+        Ok(codes)
+    }
+}
+
+/// Body of the first fenced code block of `reply`, or the whole reply when
+/// it has none
+fn extract_code(reply: &str) -> &str {
+    let Some(start) = reply.find("```") else {
+        return reply.trim();
+    };
+    let block = &reply[start + 3..];
+    // Skip the info string, such as `wgsl`
+    let block = block.split_once('\n').map_or("", |(_, rest)| rest);
+    block.split("```").next().unwrap_or_default().trim()
+}
+
+fn request_error(error: ureq::Error) -> crate::Error {
+    match error {
+        ureq::Error::Status(status, response) => crate::Error::Other(format!(
+            "Provider request failed with status {}: {}",
+            status,
+            response.into_string().unwrap_or_default().trim()
+        )),
+        error => crate::Error::Other(format!("Provider request failed: {}", error)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wgsl::Stage;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::sync::{Arc, Mutex};
+
+    /// Server answering one chat completion with `reply`, recording the
+    /// authorization header and body of the request
+    fn fake_provider(reply: &'static str) -> (String, Arc<Mutex<(String, Value)>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("http://{}/v1", listener.local_addr().unwrap());
+        let recorded = Arc::new(Mutex::new((String::new(), Value::Null)));
+        let request = Arc::clone(&recorded);
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let (mut length, mut authorization) = (0, String::new());
+            loop {
+                let mut header = String::new();
+                reader.read_line(&mut header).unwrap();
+                if header.trim().is_empty() {
+                    break;
+                }
+                let lower = header.to_lowercase();
+                if let Some(value) = lower.strip_prefix("content-length:") {
+                    length = value.trim().parse().unwrap();
+                }
+                if lower.starts_with("authorization:") {
+                    authorization = header["authorization:".len()..].trim().to_string();
+                }
+            }
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).unwrap();
+            *request.lock().unwrap() = (authorization, serde_json::from_slice(&body).unwrap());
+
+            let response = json!({ "choices": [{ "message": { "content": reply } }] }).to_string();
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\
+                 Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                response.len(),
+                response
+            )
+            .unwrap();
+        });
+        (endpoint, recorded)
+    }
+
+    #[test]
+    fn test_chat_completion() {
+        let (endpoint, request) = fake_provider(
+            "Here you go:\n```wgsl\n@compute @workgroup_size(1)\nfn main() {}\n```\nEnjoy!",
+        );
+        let config = ProviderConfig {
+            endpoint: Some(endpoint),
+            api_key: Some("sk-test".to_string()),
+            ..ProviderConfig::default()
+        };
+        let backend = OpenAiBackend::from_model_name("openai:wgsl-large", &config).unwrap();
+        assert!(OpenAiBackend::from_model_name("checkpoints/best.ckpt", &config).is_none());
+
+        let options = GenerationOptions {
+            seed: Some(3),
+            max_new_tokens: Some(256),
+            target_stage: Some(Stage::Compute),
+            ..GenerationOptions::sampling(0.7)
+        };
+        let code = backend.generate_with("noop kernel", &options).unwrap();
+        assert_eq!(
+            code,
+            format_wgsl_or_original("@compute @workgroup_size(1)\nfn main() {}")
+        );
+
+        let (authorization, body) = request.lock().unwrap().clone();
+        assert_eq!(authorization, "Bearer sk-test");
+        assert_eq!(body["model"], "wgsl-large");
+        assert_eq!(
+            body["messages"][1]["content"],
+            "noop kernel (a compute shader)"
+        );
+        assert_eq!((&body["n"], &body["seed"]), (&json!(1), &json!(3)));
+        assert_eq!(body["max_tokens"], 256);
+        assert!(body.get("top_p").is_none());
+
+        assert_eq!(extract_code("  fn f() {}\n"), "fn f() {}");
+        assert_eq!(extract_code("```\nfn f() {}\n```"), "fn f() {}");
+    }
+}
@@ -0,0 +1,239 @@
+//! Generate → validate → retry with the validator's feedback
+//!
+//! When a generation fails validation, the first error is spliced into a
+//! repair prompt and the model tries again, so a model trained on such
+//! prompts can fix its own mistakes.
+
+use super::{GenerationOptions, GenerationResult, PromptFields, WGSLGenerator};
+use crate::WGSLValidator;
+use serde::{Deserialize, Serialize};
+use web_time::Instant;
+
+/// Default repair prompt
+pub const DEFAULT_REPAIR_TEMPLATE: &str = "{prompt} fix error: {error}";
+
+/// Longest error message spliced into a repair prompt, in characters
+const MAX_ERROR_CHARS: usize = 200;
+
+/// Settings of [`WGSLGenerator::generate_with_repair`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RepairOptions {
+    /// Most generations, including the first
+    pub max_attempts: usize,
+    /// Prompt of each retry; `{prompt}`, `{code}` and `{error}` are replaced
+    /// with the original prompt, the rejected code and its first error
+    pub template: String,
+}
+
+impl Default for RepairOptions {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            template: DEFAULT_REPAIR_TEMPLATE.to_string(),
+        }
+    }
+}
+
+impl RepairOptions {
+    /// Retry prompt after `code` was rejected with `error`
+    pub fn repair_prompt(&self, prompt: &str, code: &str, error: &str) -> String {
+        self.template
+            .replace("{prompt}", prompt)
+            .replace("{code}", code)
+            .replace("{error}", error)
+    }
+}
+
+/// One generation of a repair loop
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RepairAttempt {
+    /// Prompt the code was generated from
+    pub prompt: String,
+    pub code: String,
+    /// Validation errors; empty if the code is valid
+    pub errors: Vec<String>,
+}
+
+/// Every attempt of a repair loop
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RepairResult {
+    pub attempts: Vec<RepairAttempt>,
+    /// The first valid attempt, or the last one when none was valid, timed
+    /// over the whole loop
+    pub result: GenerationResult,
+}
+
+impl RepairResult {
+    /// The first valid shader, if any attempt produced one
+    pub fn valid_code(&self) -> Option<&str> {
+        self.is_valid().then_some(self.result.code.as_str())
+    }
+
+    /// The first valid shader, or the last attempt when none was valid
+    pub fn code(&self) -> &str {
+        &self.result.code
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.result.is_valid()
+    }
+}
+
+/// First line of `error`, shortened to [`MAX_ERROR_CHARS`]
+fn error_summary(error: &str) -> String {
+    error
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .chars()
+        .take(MAX_ERROR_CHARS)
+        .collect()
+}
+
+impl WGSLGenerator {
+    /// Generate, and while the code fails `validator`, regenerate from a
+    /// repair prompt holding the error, up to `repair.max_attempts` times
+    ///
+    /// All attempts draw from one sampler seeded by `options.seed`.
+    pub fn generate_with_repair(
+        &self,
+        prompt: &str,
+        options: &GenerationOptions,
+        repair: &RepairOptions,
+        validator: &WGSLValidator,
+    ) -> crate::Result<RepairResult> {
+        let mut rng = options.rng();
+        repair_loop(prompt, repair, |current| {
+            let text = self.format_prompt(&PromptFields::new(current));
+            self.scored_generation(&text, options, &mut rng, validator)
+        })
+    }
+
+    /// [`generate_with_repair`](Self::generate_with_repair) after `failed`,
+    /// a generation for `prompt` made elsewhere, such as in a batch;
+    /// regeneration starts at the repair prompt holding its error
+    pub fn repair_from(
+        &self,
+        prompt: &str,
+        failed: GenerationResult,
+        options: &GenerationOptions,
+        repair: &RepairOptions,
+        validator: &WGSLValidator,
+    ) -> crate::Result<RepairResult> {
+        let mut rng = options.rng();
+        repair_loop_from(prompt, failed, repair, |current| {
+            let text = self.format_prompt(&PromptFields::new(current));
+            self.scored_generation(&text, options, &mut rng, validator)
+        })
+    }
+}
+
+/// Call `attempt` with `prompt`, then with repair prompts holding the first
+/// error of its result, until one is valid or `repair.max_attempts` is
+/// reached
+pub(super) fn repair_loop(
+    prompt: &str,
+    repair: &RepairOptions,
+    attempt: impl FnMut(&str) -> crate::Result<GenerationResult>,
+) -> crate::Result<RepairResult> {
+    run_repair_loop(prompt, None, repair, attempt)
+}
+
+/// [`repair_loop`] whose first attempt at `prompt`, `first`, was already
+/// generated elsewhere
+pub(super) fn repair_loop_from(
+    prompt: &str,
+    first: GenerationResult,
+    repair: &RepairOptions,
+    attempt: impl FnMut(&str) -> crate::Result<GenerationResult>,
+) -> crate::Result<RepairResult> {
+    run_repair_loop(prompt, Some(first), repair, attempt)
+}
+
+fn run_repair_loop(
+    prompt: &str,
+    mut first: Option<GenerationResult>,
+    repair: &RepairOptions,
+    mut attempt: impl FnMut(&str) -> crate::Result<GenerationResult>,
+) -> crate::Result<RepairResult> {
+    let start = Instant::now();
+    let earlier = first.as_ref().map(|result| result.elapsed).unwrap_or_default();
+    let mut attempts: Vec<RepairAttempt> = Vec::new();
+    let mut current = prompt.to_string();
+    loop {
+        let mut result = match first.take() {
+            Some(result) => result,
+            None => attempt(&current)?,
+        };
+        result.retries = attempts.len();
+        let errors = result.validation.errors.clone();
+        tracing::debug!(
+            "Repair attempt {}: {} error(s)",
+            attempts.len() + 1,
+            errors.len()
+        );
+        attempts.push(RepairAttempt {
+            prompt: std::mem::take(&mut current),
+            code: result.code.clone(),
+            errors,
+        });
+        match result.validation.errors.first() {
+            Some(error) if attempts.len() < repair.max_attempts => {
+                current = repair.repair_prompt(prompt, &result.code, &error_summary(error));
+            }
+            _ => {
+                result.elapsed = earlier + start.elapsed();
+                return Ok(RepairResult { attempts, result });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::tiny_generator;
+
+    #[test]
+    fn test_repair_prompt() {
+        let repair = RepairOptions {
+            template: "{prompt} | {code} | {error}".to_string(),
+            ..RepairOptions::default()
+        };
+        assert_eq!(
+            repair.repair_prompt("red", "fn", "Parse error"),
+            "red | fn | Parse error"
+        );
+        assert_eq!(error_summary("first\nsecond"), "first");
+    }
+
+    #[test]
+    fn test_repair_loop_records_attempts() {
+        let (tokenizer, model) = tiny_generator(&["fn main() { let x = 1.0; }"]);
+        let generator = WGSLGenerator::new(model, tokenizer);
+        let repair = RepairOptions {
+            max_attempts: 3,
+            ..RepairOptions::default()
+        };
+        let result = generator
+            .generate_with_repair(
+                "main",
+                &GenerationOptions::default(),
+                &repair,
+                &WGSLValidator::new(),
+            )
+            .unwrap();
+
+        assert!(!result.attempts.is_empty() && result.attempts.len() <= 3);
+        assert_eq!(result.attempts[0].prompt, "main");
+        let (last, earlier) = result.attempts.split_last().unwrap();
+        for (attempt, next) in earlier.iter().zip(&result.attempts[1..]) {
+            assert!(!attempt.errors.is_empty());
+            assert!(next.prompt.starts_with("main fix error: "));
+        }
+        assert_eq!(result.is_valid(), last.errors.is_empty());
+        assert_eq!(result.code(), last.code);
+        assert_eq!(result.result.retries, result.attempts.len() - 1);
+    }
+}
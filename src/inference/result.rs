@@ -0,0 +1,197 @@
+//! Generations with their scores and diagnostics
+
+use super::{Fallback, GenerationOptions, PromptFields, TokenStream, WGSLGenerator};
+use crate::config::GenerationConfig;
+use crate::wgsl::{format_wgsl_or_original, ValidationResult};
+use crate::WGSLValidator;
+use rand::rngs::StdRng;
+use serde::{Deserialize, Serialize};
+use web_time::{Duration, Instant};
+
+/// Generated code with what callers need to judge it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GenerationResult {
+    pub code: String,
+    /// Text of each generated token
+    pub tokens: Vec<String>,
+    /// Log probability of each token, as in [`StreamToken::log_prob`](super::StreamToken::log_prob)
+    pub log_probs: Vec<f32>,
+    /// Log probability of the whole generation: the sum of `log_probs`,
+    /// plus that of the end-of-sequence ending it, if one did
+    pub score: f32,
+    pub validation: ValidationResult,
+    /// Time spent generating and validating, over all attempts
+    pub elapsed: Duration,
+    /// Generations discarded before this one
+    pub retries: usize,
+    /// Set when `code` is a stored shader returned because every generation
+    /// failed validation
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fallback: Option<Fallback>,
+}
+
+impl GenerationResult {
+    /// `code` validated as `validation`, without token scores or timing,
+    /// as backends that only return text produce it
+    pub fn unscored(code: String, validation: ValidationResult) -> Self {
+        Self {
+            code,
+            tokens: Vec::new(),
+            log_probs: Vec::new(),
+            score: 0.0,
+            validation,
+            elapsed: Duration::ZERO,
+            retries: 0,
+            fallback: None,
+        }
+    }
+
+    /// Mean log probability per token, comparable across lengths; 0 for an
+    /// empty generation
+    pub fn mean_log_prob(&self) -> f32 {
+        if self.log_probs.is_empty() {
+            0.0
+        } else {
+            self.score / self.log_probs.len() as f32
+        }
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.validation.is_valid
+    }
+}
+
+impl WGSLGenerator {
+    /// Generate like [`generate_with`](Self::generate_with), returning the
+    /// token scores, validation and timing along with the code
+    pub fn generate_result(
+        &self,
+        prompt: &str,
+        options: &GenerationOptions,
+        validator: &WGSLValidator,
+    ) -> crate::Result<GenerationResult> {
+        let text = self.format_prompt(&PromptFields::new(prompt));
+        self.scored_generation(&text, options, &mut options.rng(), validator)
+    }
+
+    /// Generate as `config` says: the best valid beam when `beam_width` is
+    /// above 1, else one sequence regenerated up to `retries` times while
+    /// it fails `validator`
+    pub fn generate_with_config(
+        &self,
+        prompt: &str,
+        config: &GenerationConfig,
+        seed: Option<u64>,
+        validator: &WGSLValidator,
+    ) -> crate::Result<GenerationResult> {
+        let options = config.options(seed);
+        if config.beam_width > 1 {
+            let start = Instant::now();
+            let beams = self.beam_search(prompt, config.beam_width, &options, validator)?;
+            let best = beams
+                .iter()
+                .position(GenerationResult::is_valid)
+                .unwrap_or(0);
+            let mut result = beams
+                .into_iter()
+                .nth(best)
+                .ok_or_else(|| crate::Error::Other("beam search found no sequence".to_string()))?;
+            result.elapsed = start.elapsed();
+            return Ok(result);
+        }
+        if let Some(repair) = config.repair_options() {
+            return Ok(self
+                .generate_with_repair(prompt, &options, &repair, validator)?
+                .result);
+        }
+        self.generate_result(prompt, &options, validator)
+    }
+
+    /// Decode formatted prompt `text` once and validate the code
+    pub(super) fn scored_generation(
+        &self,
+        text: &str,
+        options: &GenerationOptions,
+        rng: &mut StdRng,
+        validator: &WGSLValidator,
+    ) -> crate::Result<GenerationResult> {
+        let start = Instant::now();
+        let mut stream = TokenStream::new(self, text, options, rng.clone());
+        while stream.step() {}
+        let code = format_wgsl_or_original(&self.tokenizer.decode_to_text(stream.generated_ids()));
+        let validation = validator.validate(&code)?;
+        let result = GenerationResult {
+            code,
+            tokens: stream.tokens().to_vec(),
+            log_probs: stream.log_probs().to_vec(),
+            score: stream.score(),
+            validation,
+            elapsed: start.elapsed(),
+            retries: 0,
+            fallback: None,
+        };
+        *rng = stream.rng;
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::tiny_generator;
+
+    #[test]
+    fn test_generation_result() {
+        let (tokenizer, model) = tiny_generator(&["fn main() { let x = 1.0; }"]);
+        let generator = WGSLGenerator::new(model, tokenizer);
+        let options = GenerationOptions {
+            seed: Some(3),
+            ..GenerationOptions::sampling(1.0)
+        };
+        let validator = WGSLValidator::new();
+        let result = generator
+            .generate_result("main", &options, &validator)
+            .unwrap();
+
+        assert_eq!(
+            result.code,
+            generator.generate_with("main", &options).unwrap()
+        );
+        assert_eq!(result.tokens.len(), result.log_probs.len());
+        assert!(result.log_probs.iter().all(|&p| p <= 0.0 && p.is_finite()));
+        assert!(result.score <= result.log_probs.iter().sum::<f32>() + 1e-6);
+        assert!(result.mean_log_prob() <= 0.0);
+        assert_eq!(result.validation, validator.validate(&result.code).unwrap());
+        assert_eq!(result.retries, 0);
+
+        let configured = generator
+            .generate_with_config(
+                "main",
+                &GenerationConfig {
+                    temperature: 1.0,
+                    ..GenerationConfig::default()
+                },
+                Some(3),
+                &validator,
+            )
+            .unwrap();
+        assert_eq!(configured.code, result.code);
+        let beam = GenerationConfig {
+            beam_width: 2,
+            ..GenerationConfig::default()
+        };
+        let beams = generator
+            .beam_search("main", 2, &GenerationOptions::default(), &validator)
+            .unwrap();
+        let configured = generator
+            .generate_with_config("main", &beam, None, &validator)
+            .unwrap();
+        assert!(beams.iter().any(|result| result.code == configured.code));
+
+        let streamed: Vec<f32> = generator
+            .stream("main", &options)
+            .map(|token| token.log_prob)
+            .collect();
+        assert_eq!(streamed, result.log_probs);
+    }
+}
@@ -0,0 +1,428 @@
+//! Token-by-token generation
+//!
+//! [`TokenStream`] runs the decoding loop lazily, one model step per
+//! [`Iterator::next`], so callers can show code as it is produced. Tokens that
+//! could be the start of a stop sequence are held back until it is clear they
+//! are not, so nothing that is later cut off is ever yielded.
+
+use super::{apply_penalties, next_token, stage_token, GenerationOptions, WGSLGenerator};
+use crate::model::EncodedInput;
+use crate::tokenizer::SpecialToken;
+use ndarray::Array1;
+use rand::rngs::StdRng;
+
+/// One token produced by a [`TokenStream`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct StreamToken {
+    /// Vocabulary id
+    pub id: usize,
+    /// Token text
+    pub token: String,
+    /// Text to append to the output so far: the token, preceded by a space
+    /// after the first one
+    pub delta: String,
+    /// Natural log of the token's probability after penalties and biases,
+    /// before temperature and top-k
+    pub log_prob: f32,
+}
+
+/// Iterator over the tokens of one generation, ending at end-of-sequence, a
+/// stop sequence or the length limits
+#[derive(Clone)]
+pub struct TokenStream<'a> {
+    generator: &'a WGSLGenerator,
+    options: GenerationOptions,
+    encoded: EncodedInput,
+    allowed: Vec<bool>,
+    /// `options.logit_bias` by token id
+    bias: Vec<(usize, f32)>,
+    /// Most tokens to generate
+    max_new: usize,
+    decoder_ids: Vec<usize>,
+    /// Text of each generated token
+    tokens: Vec<String>,
+    /// Log probability of each generated token
+    log_probs: Vec<f32>,
+    /// Log probability of end-of-sequence, once it was picked
+    end_log_prob: f32,
+    /// Number of tokens yielded so far
+    emitted: usize,
+    pub(super) rng: StdRng,
+    finished: bool,
+}
+
+impl<'a> TokenStream<'a> {
+    pub(super) fn new(
+        generator: &'a WGSLGenerator,
+        text: &str,
+        options: &GenerationOptions,
+        rng: StdRng,
+    ) -> Self {
+        let input_ids = Self::input_ids(generator, text, options);
+        let encoded = generator.model.encode(&input_ids);
+        Self::with_encoded(generator, encoded, options, rng)
+    }
+
+    /// One stream per formatted prompt text and its options, with the
+    /// prompts encoded in one batched encoder pass
+    pub(super) fn new_batch(
+        generator: &'a WGSLGenerator,
+        prompts: &[(String, GenerationOptions)],
+    ) -> Vec<Self> {
+        let inputs: Vec<Vec<usize>> = prompts
+            .iter()
+            .map(|(text, options)| Self::input_ids(generator, text, options))
+            .collect();
+        generator
+            .model
+            .encode_batch(&inputs)
+            .into_iter()
+            .zip(prompts)
+            .map(|(encoded, (_, options))| {
+                Self::with_encoded(generator, encoded, options, options.rng())
+            })
+            .collect()
+    }
+
+    /// Encoder input for `text`, behind the target stage's control token
+    fn input_ids(generator: &WGSLGenerator, text: &str, options: &GenerationOptions) -> Vec<usize> {
+        match options.target_stage {
+            Some(stage) => {
+                let token = stage_token(stage.as_str());
+                if generator.tokenizer.control_tokens().contains(&token) {
+                    generator
+                        .tokenizer
+                        .encode_text(&format!("{} {}", token, text))
+                } else {
+                    tracing::warn!(
+                        "Not conditioning on the {} stage: the tokenizer has no {} control token",
+                        stage.as_str(),
+                        token
+                    );
+                    generator.tokenizer.encode_text(text)
+                }
+            }
+            None => generator.tokenizer.encode_text(text),
+        }
+    }
+
+    fn with_encoded(
+        generator: &'a WGSLGenerator,
+        encoded: EncodedInput,
+        options: &GenerationOptions,
+        rng: StdRng,
+    ) -> Self {
+        let max_len = generator
+            .tokenizer
+            .max_length
+            .min(generator.model.max_seq_len.saturating_sub(1));
+        let token_id = |token: &str| {
+            let id = generator.tokenizer.vocab.get(token).copied();
+            if id.is_none() {
+                tracing::warn!(
+                    "Ignoring '{}' in generation options: not in the vocabulary",
+                    token
+                );
+            }
+            id
+        };
+        let mut allowed = generator.decodable_tokens();
+        // Attributes of other stages are simply absent from small vocabularies
+        let off_stage = options
+            .off_stage_tokens()
+            .into_iter()
+            .filter_map(|token| generator.tokenizer.vocab.get(token).copied());
+        for id in options
+            .banned_tokens
+            .iter()
+            .filter_map(|token| token_id(token))
+            .chain(off_stage)
+        {
+            if let Some(allowed) = allowed.get_mut(id) {
+                *allowed = false;
+            }
+        }
+        let bias = options
+            .logit_bias
+            .iter()
+            .filter_map(|(token, &bias)| Some((token_id(token)?, bias)))
+            .filter(|&(id, _)| id < allowed.len())
+            .collect();
+
+        Self {
+            generator,
+            options: options.clone(),
+            encoded,
+            allowed,
+            bias,
+            max_new: options
+                .max_new_tokens
+                .map_or(max_len, |max| max.min(max_len)),
+            decoder_ids: vec![SpecialToken::StartOfSequence.token_id()],
+            tokens: Vec::new(),
+            log_probs: Vec::new(),
+            end_log_prob: 0.0,
+            emitted: 0,
+            rng,
+            finished: false,
+        }
+    }
+
+    /// Ids generated so far, without special tokens
+    pub fn generated_ids(&self) -> &[usize] {
+        &self.decoder_ids[1..]
+    }
+
+    /// Text of the tokens generated so far
+    pub fn tokens(&self) -> &[String] {
+        &self.tokens
+    }
+
+    /// Log probability of each token generated so far
+    pub fn log_probs(&self) -> &[f32] {
+        &self.log_probs
+    }
+
+    /// Log probability of the generation: its tokens plus the end-of-sequence
+    /// that ended it, if one did
+    pub fn score(&self) -> f32 {
+        self.log_probs.iter().sum::<f32>() + self.end_log_prob
+    }
+
+    /// Whether no more tokens will be decoded
+    pub(super) fn is_done(&self) -> bool {
+        self.finished || self.tokens.len() >= self.max_new
+    }
+
+    /// Decode one more token; `false` once decoding has stopped
+    pub(super) fn step(&mut self) -> bool {
+        if self.is_done() {
+            self.finished = true;
+            return false;
+        }
+        let last = self.next_logits();
+        self.pick(last)
+    }
+
+    /// Decode one more token in every stream that hasn't stopped, with one
+    /// batched decoder pass; `false` once all of them have stopped
+    pub(super) fn step_batch(streams: &mut [Self]) -> bool {
+        let mut active: Vec<&mut Self> = streams
+            .iter_mut()
+            .filter_map(|stream| {
+                if stream.is_done() {
+                    stream.finished = true;
+                    None
+                } else {
+                    Some(stream)
+                }
+            })
+            .collect();
+        let Some(first) = active.first() else {
+            return false;
+        };
+        let encoded: Vec<&EncodedInput> = active.iter().map(|stream| &stream.encoded).collect();
+        let decoder_ids: Vec<&[usize]> = active
+            .iter()
+            .map(|stream| stream.decoder_ids.as_slice())
+            .collect();
+        let logits = first.generator.model.decode_batch(&encoded, &decoder_ids);
+        for (stream, logits) in active.iter_mut().zip(logits) {
+            let last = stream.adjust_logits(logits.row(logits.nrows() - 1).to_owned());
+            stream.pick(last);
+        }
+        true
+    }
+
+    /// Pick the next token from adjusted logits `last`; `false` if none was
+    /// picked or it ended the generation
+    fn pick(&mut self, last: Array1<f32>) -> bool {
+        let next = self.with_eos_masked(|stream| {
+            let id = next_token(
+                last.view(),
+                &stream.allowed,
+                &stream.options,
+                &mut stream.rng,
+            )?;
+            Some((id, log_softmax_at(&last, &stream.allowed, id)))
+        });
+        match next {
+            Some((id, log_prob)) => self.push(id, log_prob),
+            None => {
+                self.finished = true;
+                false
+            }
+        }
+    }
+
+    /// The `k` most likely next tokens with their log probabilities, best
+    /// first
+    pub(super) fn candidates(&mut self, k: usize) -> Vec<(usize, f32)> {
+        let last = self.next_logits();
+        let mut candidates: Vec<(usize, f32)> = self.with_eos_masked(|stream| {
+            let normalizer = log_sum_exp(&last, &stream.allowed);
+            (0..last.len())
+                .filter(|&id| stream.allowed[id])
+                .map(|id| (id, last[id] - normalizer))
+                .collect()
+        });
+        candidates.sort_by(|a, b| b.1.total_cmp(&a.1));
+        candidates.truncate(k);
+        candidates
+    }
+
+    /// Append token `id`, picked with `log_prob`; `false` if it ended the
+    /// generation
+    pub(super) fn push(&mut self, id: usize, log_prob: f32) -> bool {
+        if id == SpecialToken::EndOfSequence.token_id() {
+            self.end_log_prob = log_prob;
+            self.finished = true;
+            return false;
+        }
+        self.decoder_ids.push(id);
+        self.log_probs.push(log_prob);
+        self.tokens
+            .push(self.generator.tokenizer.decode_to_text(&[id]));
+        if let Some(start) = self.stop_match() {
+            self.truncate_before(start);
+            self.finished = true;
+        }
+        true
+    }
+
+    /// Model logits for the next token after penalties and biases
+    fn next_logits(&self) -> Array1<f32> {
+        let logits = self
+            .generator
+            .model
+            .decode(&self.encoded, &self.decoder_ids);
+        self.adjust_logits(logits.row(logits.nrows() - 1).to_owned())
+    }
+
+    /// Apply penalties and biases to the model's logits for the next token
+    fn adjust_logits(&self, mut last: Array1<f32>) -> Array1<f32> {
+        if self.options.has_penalties() {
+            apply_penalties(&mut last, self.generated_ids(), &self.options);
+        }
+        for &(id, bias) in &self.bias {
+            last[id] += bias;
+        }
+        last
+    }
+
+    /// Run `f` with end-of-sequence masked until the minimum length is reached
+    fn with_eos_masked<R>(&mut self, f: impl FnOnce(&mut Self) -> R) -> R {
+        let eos = SpecialToken::EndOfSequence.token_id();
+        let eos_allowed = self.allowed[eos];
+        self.allowed[eos] &= self.tokens.len() >= self.options.min_new_tokens;
+        let result = f(self);
+        self.allowed[eos] = eos_allowed;
+        result
+    }
+
+    /// Byte offset in the generated text of the earliest complete stop sequence
+    fn stop_match(&self) -> Option<usize> {
+        let text = self.tokens.join(" ");
+        self.stop_sequences()
+            .filter_map(|stop| text.find(stop))
+            .min()
+    }
+
+    /// Byte offset where the generated text starts to spell a stop sequence it
+    /// doesn't complete yet
+    fn partial_stop_start(&self) -> Option<usize> {
+        let text = self.tokens.join(" ");
+        (0..text.len())
+            .filter(|&start| text.is_char_boundary(start))
+            .find(|&start| {
+                let tail = &text[start..];
+                self.stop_sequences()
+                    .any(|stop| stop.len() > tail.len() && stop.starts_with(tail))
+            })
+    }
+
+    fn stop_sequences(&self) -> impl Iterator<Item = &str> {
+        self.options
+            .stop
+            .iter()
+            .map(String::as_str)
+            .filter(|stop| !stop.is_empty())
+    }
+
+    /// Drop every token that isn't entirely before byte `start` of the text
+    fn truncate_before(&mut self, start: usize) {
+        let kept = self.tokens_before(start);
+        self.tokens.truncate(kept);
+        self.log_probs.truncate(kept);
+        self.decoder_ids.truncate(kept + 1);
+    }
+
+    /// Number of leading tokens that end at or before byte `offset` of the text
+    fn tokens_before(&self, offset: usize) -> usize {
+        let mut end = 0;
+        self.tokens
+            .iter()
+            .take_while(|token| {
+                end += token.len();
+                let before = end <= offset;
+                end += 1;
+                before
+            })
+            .count()
+    }
+
+    /// Number of generated tokens that can no longer be cut off by a stop
+    /// sequence
+    fn settled(&self) -> usize {
+        if self.finished {
+            return self.tokens.len();
+        }
+        self.partial_stop_start()
+            .map_or(self.tokens.len(), |start| self.tokens_before(start))
+    }
+}
+
+impl Iterator for TokenStream<'_> {
+    type Item = StreamToken;
+
+    fn next(&mut self) -> Option<StreamToken> {
+        while self.emitted >= self.settled() {
+            if !self.step() && self.emitted >= self.settled() {
+                return None;
+            }
+        }
+        let index = self.emitted;
+        self.emitted += 1;
+        let token = self.tokens[index].clone();
+        let delta = if index > 0 {
+            format!(" {}", token)
+        } else {
+            token.clone()
+        };
+        Some(StreamToken {
+            id: self.decoder_ids[index + 1],
+            token,
+            delta,
+            log_prob: self.log_probs[index],
+        })
+    }
+}
+
+/// Log-softmax of `logits` over the `allowed` ids, at `id`
+fn log_softmax_at(logits: &Array1<f32>, allowed: &[bool], id: usize) -> f32 {
+    logits[id] - log_sum_exp(logits, allowed)
+}
+
+/// Log of the softmax denominator of `logits` over the `allowed` ids
+fn log_sum_exp(logits: &Array1<f32>, allowed: &[bool]) -> f32 {
+    let allowed_logits = || {
+        logits
+            .iter()
+            .zip(allowed)
+            .filter(|(_, &allowed)| allowed)
+            .map(|(&logit, _)| logit)
+    };
+    let max = allowed_logits().fold(f32::NEG_INFINITY, f32::max);
+    let sum: f32 = allowed_logits().map(|logit| (logit - max).exp()).sum();
+    max + sum.ln()
+}
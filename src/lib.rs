@@ -10,6 +10,14 @@
 //! - **GPU Acceleration**: Native wgpu support for training and inference
 //! - **WGSL Validation**: Integrated naga validation for generated shaders
 //! - **Chromatic Operations**: Pre-built templates for color-based tensor operations
+//! - **WebAssembly**: Browser bindings for tokenization, validation and
+//!   inference behind the `wasm` feature
+//! - **Python**: PyO3 bindings for dataset curation and model evaluation
+//!   behind the `python` feature
+//! - **Hugging Face Hub**: Dataset downloads and checkpoint uploads behind
+//!   the `hub` feature
+//! - **Remote Generators**: OpenAI-compatible APIs as generation backends
+//!   behind the `openai` feature
 //!
 //! # Example
 //!
@@ -32,20 +40,36 @@
 //! # Ok::<(), Box<dyn std::error::Error>>(())
 //! ```
 
+pub mod bench;
+pub mod capabilities;
 pub mod config;
 pub mod dataset;
+pub mod device;
+pub mod eval;
+#[cfg(feature = "hub")]
+pub mod hub;
 pub mod inference;
+pub mod logging;
 pub mod model;
+mod progress;
+#[cfg(feature = "python")]
+pub mod python;
+#[cfg(test)]
+mod test_support;
 pub mod tokenizer;
 pub mod training;
+#[cfg(feature = "wasm")]
+pub mod wasm;
 pub mod wgsl;
 
 // Re-export commonly used types
-pub use config::{Config, DatasetConfig, EngineConfig, ModelConfig, PathsConfig, TokenizerConfig, TrainingConfig};
-pub use inference::WGSLGenerator;
+pub use config::{Activation, AttentionConfig, Config, ConfigFormat, CurriculumConfig, CurriculumMetric, DatasetConfig, DeviceConfig, DistillationConfig, Dtype, EngineConfig, FreezeConfig, GenerationConfig, HubConfig, InitConfig, InitScheme, LengthPolicy, LintConfig, LogFormat, LogRotation, LoggingConfig, MetricsFormat, ModelConfig, NormPlacement, PathsConfig, PositionalEncoding, PretrainedConfig, PreviewConfig, PromptConfig, ProviderConfig, RequirementsConfig, TokenizerConfig, TrackingConfig, TrainingConfig, ValidationConfig};
+pub use inference::{
+    GenerationOptions, GenerationResult, PromptTemplate, StreamToken, TokenStream, WGSLGenerator,
+};
 pub use tokenizer::WGSLTokenizer;
-pub use training::Trainer;
-pub use wgsl::{ChromaticTemplate, WGSLValidator};
+pub use training::{Trainer, TrainerCallback};
+pub use wgsl::{ChromaticTemplate, WGSLTranspiler, WGSLValidator};
 
 /// Custom error types for the library
 #[derive(Debug, thiserror::Error)]
@@ -65,6 +89,56 @@ pub enum Error {
     #[error("JSON error: {0}")]
     JsonError(#[from] serde_json::Error),
 
+    #[error("YAML error: {0}")]
+    YamlError(#[from] serde_yaml_ng::Error),
+
+    #[error("Binary serialization error: {0}")]
+    BincodeError(#[from] bincode::Error),
+
+    #[error("safetensors error: {0}")]
+    SafeTensorsError(#[from] safetensors::SafeTensorError),
+
+    /// Text has tokens outside the vocabulary
+    #[error(
+        "Tokenizer error: {} token(s) out of vocabulary ({:.1}% coverage): {}",
+        .unknown.len(),
+        .coverage * 100.0,
+        .unknown.join(" ")
+    )]
+    TokenizerError {
+        /// Distinct unknown tokens, in order of appearance
+        unknown: Vec<String>,
+        /// Fraction of the text's tokens in the vocabulary
+        coverage: f64,
+    },
+
+    /// WGSL failed to parse or validate
+    #[error("WGSL validation failed: {}", .0.errors.join("; "))]
+    ValidationError(wgsl::ValidationResult),
+
+    /// A checkpoint or weights file cannot be read
+    #[error(
+        "Checkpoint error: {}{kind}",
+        .path.as_ref().map(|p| format!("{}: ", p.display())).unwrap_or_default()
+    )]
+    CheckpointError {
+        path: Option<std::path::PathBuf>,
+        kind: model::CheckpointErrorKind,
+    },
+
+    /// A dataset file cannot be read or written
+    #[error(
+        "Dataset error: {}{}: {message}",
+        .path.display(),
+        .line.map(|line| format!(":{}", line)).unwrap_or_default()
+    )]
+    DatasetError {
+        path: std::path::PathBuf,
+        /// 1-based line of the offending example, when known
+        line: Option<usize>,
+        message: String,
+    },
+
     #[error("{0}")]
     Other(String),
 }
@@ -86,18 +160,69 @@ pub fn init_logging() {
         .init();
 }
 
-/// Initialize logging from engine configuration
-pub fn init_logging_from_config(engine_config: &EngineConfig) {
+/// Initialize logging to stderr, leaving stdout to machine-readable output
+pub fn init_stderr_logging() {
     use tracing_subscriber::{fmt, EnvFilter};
 
-    let level = engine_config.log_level.to_lowercase();
     fmt()
         .with_env_filter(
-            EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(&level)),
+            EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")),
         )
+        .with_writer(std::io::stderr)
         .init();
 }
 
+/// Initialize logging from engine configuration: the console at
+/// `log_level` (stderr when `stderr` is set, else stdout), plus rotated files
+/// under `paths.log_path` when `[logging]` enables them
+pub fn init_logging_from_config(engine_config: &EngineConfig, stderr: bool) -> Result<()> {
+    use config::LogFormat;
+    use tracing_subscriber::fmt::{format::FmtSpan, writer::BoxMakeWriter};
+    use tracing_subscriber::{fmt, prelude::*, EnvFilter};
+
+    let level = engine_config.log_level.to_lowercase();
+    let logging = &engine_config.logging;
+    let span_events = if logging.span_events {
+        FmtSpan::CLOSE
+    } else {
+        FmtSpan::NONE
+    };
+    let console = fmt::layer()
+        .with_span_events(span_events.clone())
+        .with_writer(if stderr {
+            BoxMakeWriter::new(std::io::stderr)
+        } else {
+            BoxMakeWriter::new(std::io::stdout)
+        });
+    let file = if logging.file {
+        let writer = logging::RollingFileWriter::new(
+            &engine_config.paths.log_path,
+            "tiny-agent-trainer",
+            logging,
+        )?;
+        let layer = fmt::layer()
+            .with_ansi(false)
+            .with_span_events(span_events)
+            .with_writer(std::sync::Mutex::new(writer));
+        Some(match logging.format {
+            LogFormat::Text => layer.fmt_fields(logging::PlainFields).boxed(),
+            LogFormat::Json => layer
+                .event_format(logging::JsonFormat)
+                .fmt_fields(logging::JsonFormat)
+                .boxed(),
+        })
+    } else {
+        None
+    };
+
+    tracing_subscriber::registry()
+        .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(&level)))
+        .with(console)
+        .with(file)
+        .try_init()
+        .map_err(|e| Error::Other(format!("Failed to initialize logging: {}", e)))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
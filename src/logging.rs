@@ -0,0 +1,457 @@
+//! Log files for [`init_logging_from_config`](crate::init_logging_from_config)
+//!
+//! [`RollingFileWriter`] appends to a file under `paths.log_path`, starting a
+//! new one each day or once it grows too large and deleting the oldest, and
+//! [`JsonFormat`] writes each event as one JSON object per line.
+//!
+//! Training and generation run inside spans (`tokenize`, `epoch`, `batch`,
+//! `load_batch`, `forward_backward`, `optimizer_step`, `generate`, ...)
+//! whose `elapsed_ms` field [`timed`] records, and `logging.span_events`
+//! additionally logs each span's busy and idle time as it closes.
+//!
+//! Span fields are recorded once per field formatter type and shared by all
+//! layers using it, so file layers use their own formatters,
+//! [`JsonFormat`] or [`PlainFields`], to keep the console's colors out.
+
+use crate::config::{LogRotation, LoggingConfig};
+use serde_json::{Map, Value};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use tracing::field::{Field, Visit};
+use tracing::span::Record;
+use tracing::{Event, Span, Subscriber};
+use tracing_subscriber::field::RecordFields;
+use tracing_subscriber::fmt::format::{DefaultFields, FormatEvent, FormatFields, Writer};
+use tracing_subscriber::fmt::{FmtContext, FormattedFields};
+use tracing_subscriber::registry::LookupSpan;
+use web_time::Instant;
+
+/// Bytes in a MiB, the unit of `logging.max_size_mb`
+const MIB: u64 = 1 << 20;
+
+/// Appends to `<dir>/<prefix>.log`, rotating it as `[logging]` describes
+///
+/// Daily files are named `<prefix>.log.YYYY-MM-DD`; size-rotated files are
+/// shifted to `<prefix>.log.1`, `<prefix>.log.2`, ... with `.1` the newest.
+pub struct RollingFileWriter {
+    dir: PathBuf,
+    name: String,
+    rotation: LogRotation,
+    max_size: u64,
+    max_files: usize,
+    file: File,
+    /// Date of the open file under daily rotation
+    day: String,
+    size: u64,
+}
+
+impl RollingFileWriter {
+    /// Open the current log file named `prefix` in `dir`, creating `dir`
+    pub fn new(dir: impl Into<PathBuf>, prefix: &str, config: &LoggingConfig) -> io::Result<Self> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir)?;
+        let name = format!("{}.log", prefix);
+        let day = today();
+        let path = file_path(&dir, &name, config.rotation, &day);
+        let file = open(&path)?;
+        let size = file.metadata()?.len();
+        let writer = Self {
+            dir,
+            name,
+            rotation: config.rotation,
+            max_size: config.max_size_mb.max(1) * MIB,
+            max_files: config.max_files.max(1),
+            file,
+            day,
+            size,
+        };
+        writer.prune()?;
+        Ok(writer)
+    }
+
+    /// Path of the file being written
+    pub fn current_path(&self) -> PathBuf {
+        file_path(&self.dir, &self.name, self.rotation, &self.day)
+    }
+
+    /// Write `buf` as of UTC date `day`, rotating first when due
+    fn write_on(&mut self, buf: &[u8], day: &str) -> io::Result<usize> {
+        match self.rotation {
+            LogRotation::Daily if day != self.day => {
+                self.day = day.to_string();
+                self.file = open(&self.current_path())?;
+                self.size = 0;
+                self.prune()?;
+            }
+            LogRotation::Size if self.size > 0 && self.size + buf.len() as u64 > self.max_size => {
+                self.file.flush()?;
+                for index in (1..self.max_files).rev() {
+                    let from = match index {
+                        1 => self.current_path(),
+                        _ => self.dir.join(format!("{}.{}", self.name, index - 1)),
+                    };
+                    if from.exists() {
+                        std::fs::rename(&from, self.dir.join(format!("{}.{}", self.name, index)))?;
+                    }
+                }
+                self.file = File::create(self.current_path())?;
+                self.size = 0;
+            }
+            _ => {}
+        }
+        let written = self.file.write(buf)?;
+        self.size += written as u64;
+        Ok(written)
+    }
+
+    /// Delete the oldest files beyond `max_files`
+    fn prune(&self) -> io::Result<()> {
+        if self.rotation != LogRotation::Daily {
+            return Ok(());
+        }
+        let prefix = format!("{}.", self.name);
+        let mut dated: Vec<PathBuf> = std::fs::read_dir(&self.dir)?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| {
+                path.file_name()
+                    .and_then(|name| name.to_str())
+                    .and_then(|name| name.strip_prefix(&prefix))
+                    .is_some_and(|date| date.len() == 10)
+            })
+            .collect();
+        // ISO dates sort chronologically
+        dated.sort();
+        let excess = dated.len().saturating_sub(self.max_files);
+        for path in &dated[..excess] {
+            std::fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+}
+
+impl Write for RollingFileWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.write_on(buf, &today())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+fn file_path(dir: &Path, name: &str, rotation: LogRotation, day: &str) -> PathBuf {
+    match rotation {
+        LogRotation::Daily => dir.join(format!("{}.{}", name, day)),
+        LogRotation::Size | LogRotation::Never => dir.join(name),
+    }
+}
+
+fn open(path: &Path) -> io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+/// Current UTC date as `YYYY-MM-DD`
+fn today() -> String {
+    humantime::format_rfc3339_seconds(SystemTime::now()).to_string()[..10].to_string()
+}
+
+/// Formats span fields as the default formatter does, recorded apart from
+/// the console's
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PlainFields;
+
+impl<'w> FormatFields<'w> for PlainFields {
+    fn format_fields<R: RecordFields>(&self, writer: Writer<'w>, fields: R) -> std::fmt::Result {
+        DefaultFields::new().format_fields(writer, fields)
+    }
+}
+
+/// Formats events as JSON lines with `timestamp`, `level`, `target`,
+/// `message`, the event's other `fields` and its enclosing `spans`
+///
+/// Use it as both the event and the field formatter, so span fields are
+/// recorded as JSON too.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct JsonFormat;
+
+impl<'w> FormatFields<'w> for JsonFormat {
+    fn format_fields<R: RecordFields>(
+        &self,
+        mut writer: Writer<'w>,
+        fields: R,
+    ) -> std::fmt::Result {
+        let mut visitor = JsonFields::default();
+        fields.record(&mut visitor);
+        write!(writer, "{}", Value::Object(visitor.0))
+    }
+
+    fn add_fields(
+        &self,
+        current: &'w mut FormattedFields<Self>,
+        fields: &Record<'_>,
+    ) -> std::fmt::Result {
+        let mut visitor = JsonFields(serde_json::from_str(&current.fields).unwrap_or_default());
+        fields.record(&mut visitor);
+        current.fields = Value::Object(visitor.0).to_string();
+        Ok(())
+    }
+}
+
+impl<S, N> FormatEvent<S, N> for JsonFormat
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(
+        &self,
+        ctx: &FmtContext<'_, S, N>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> std::fmt::Result {
+        let mut fields = JsonFields::default();
+        event.record(&mut fields);
+        let mut fields = fields.0;
+
+        let metadata = event.metadata();
+        let mut line = Map::new();
+        line.insert(
+            "timestamp".to_string(),
+            Value::from(humantime::format_rfc3339_micros(SystemTime::now()).to_string()),
+        );
+        line.insert("level".to_string(), Value::from(metadata.level().as_str()));
+        line.insert("target".to_string(), Value::from(metadata.target()));
+        if let Some(message) = fields.remove("message") {
+            line.insert("message".to_string(), message);
+        }
+        if !fields.is_empty() {
+            line.insert("fields".to_string(), Value::Object(fields));
+        }
+        if let Some(scope) = ctx.event_scope() {
+            let spans: Vec<Value> = scope
+                .from_root()
+                .map(|span| {
+                    let mut entry = Map::new();
+                    entry.insert("name".to_string(), Value::from(span.name()));
+                    let extensions = span.extensions();
+                    if let Some(recorded) = extensions.get::<FormattedFields<N>>() {
+                        // JSON when `N` is `JsonFormat`
+                        let fields = serde_json::from_str(recorded.as_str())
+                            .unwrap_or_else(|_| Value::from(recorded.as_str()));
+                        if fields != "" && fields != Value::Object(Map::new()) {
+                            entry.insert("fields".to_string(), fields);
+                        }
+                    }
+                    Value::Object(entry)
+                })
+                .collect();
+            if !spans.is_empty() {
+                line.insert("spans".to_string(), Value::Array(spans));
+            }
+        }
+        writeln!(writer, "{}", Value::Object(line))
+    }
+}
+
+/// Collects an event's fields as JSON values
+#[derive(Default)]
+struct JsonFields(Map<String, Value>);
+
+impl Visit for JsonFields {
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        self.0.insert(
+            field.name().to_string(),
+            Value::from(format!("{:?}", value)),
+        );
+    }
+}
+
+/// Run `f` inside `span`, recording its duration as the span's `elapsed_ms`
+///
+/// The span must declare `elapsed_ms = tracing::field::Empty`.
+pub fn timed<R>(span: &Span, f: impl FnOnce() -> R) -> R {
+    let start = Instant::now();
+    let result = span.in_scope(f);
+    span.record("elapsed_ms", start.elapsed().as_secs_f64() * 1000.0);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use tracing_subscriber::fmt::MakeWriter;
+    use tracing_subscriber::prelude::*;
+
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Buffer {
+        fn lines(&self) -> Vec<Value> {
+            String::from_utf8(self.0.lock().unwrap().clone())
+                .unwrap()
+                .lines()
+                .map(|line| serde_json::from_str(line).unwrap())
+                .collect()
+        }
+    }
+
+    impl Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl<'a> MakeWriter<'a> for Buffer {
+        type Writer = Buffer;
+
+        fn make_writer(&'a self) -> Self::Writer {
+            self.clone()
+        }
+    }
+
+    fn config(rotation: LogRotation, max_files: usize) -> LoggingConfig {
+        LoggingConfig {
+            file: true,
+            rotation,
+            max_size_mb: 1,
+            max_files,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_size_rotation() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut writer =
+            RollingFileWriter::new(dir.path(), "app", &config(LogRotation::Size, 3)).unwrap();
+        let line = vec![b'x'; (MIB / 2) as usize];
+        for _ in 0..7 {
+            writer.write_all(&line).unwrap();
+        }
+        writer.flush().unwrap();
+
+        let size = |name: &str| std::fs::metadata(dir.path().join(name)).unwrap().len();
+        assert_eq!(size("app.log"), MIB / 2);
+        assert_eq!(size("app.log.1"), MIB);
+        assert_eq!(size("app.log.2"), MIB);
+        assert!(!dir.path().join("app.log.3").exists());
+    }
+
+    #[test]
+    fn test_daily_rotation() {
+        let dir = tempfile::tempdir().unwrap();
+        for day in ["2026-01-01", "2026-01-02"] {
+            std::fs::write(dir.path().join(format!("app.log.{}", day)), "old\n").unwrap();
+        }
+        let mut writer =
+            RollingFileWriter::new(dir.path(), "app", &config(LogRotation::Daily, 2)).unwrap();
+        assert!(!dir.path().join("app.log.2026-01-01").exists());
+        assert!(writer
+            .current_path()
+            .ends_with(format!("app.log.{}", today())));
+
+        writer.write_on(b"later\n", "2999-12-31").unwrap();
+        writer.flush().unwrap();
+        let later = dir.path().join("app.log.2999-12-31");
+        assert_eq!(std::fs::read_to_string(&later).unwrap(), "later\n");
+        assert_eq!(writer.current_path(), later);
+        assert!(!dir.path().join("app.log.2026-01-02").exists());
+    }
+
+    #[test]
+    fn test_json_format() {
+        let buffer = Buffer::default();
+        let text = Buffer::default();
+        let subscriber = tracing_subscriber::registry()
+            .with(tracing_subscriber::fmt::layer().with_writer(io::sink))
+            .with(
+                tracing_subscriber::fmt::layer()
+                    .event_format(JsonFormat)
+                    .fmt_fields(JsonFormat)
+                    .with_writer(buffer.clone()),
+            )
+            .with(
+                tracing_subscriber::fmt::layer()
+                    .with_ansi(false)
+                    .fmt_fields(PlainFields)
+                    .with_writer(text.clone()),
+            );
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("epoch", epoch = 3, loss = tracing::field::Empty);
+            let _guard = span.enter();
+            span.record("loss", 1.5);
+            tracing::info!(loss = 0.5, step = 7u64, "batch done");
+        });
+
+        let line = &buffer.lines()[0];
+        assert_eq!(line["level"], "INFO");
+        assert_eq!(line["message"], "batch done");
+        assert_eq!(line["fields"]["loss"], 0.5);
+        assert_eq!(line["fields"]["step"], 7);
+        assert_eq!(line["spans"][0]["name"], "epoch");
+        assert_eq!(line["spans"][0]["fields"]["epoch"], 3);
+        assert_eq!(line["spans"][0]["fields"]["loss"], 1.5);
+        assert!(line["timestamp"].as_str().unwrap().ends_with('Z'));
+
+        let text = String::from_utf8(text.0.lock().unwrap().clone()).unwrap();
+        assert!(text.contains("epoch{epoch=3 loss=1.5}"), "{}", text);
+        assert!(!text.contains('\u{1b}'));
+    }
+
+    #[test]
+    fn test_timed() {
+        use tracing_subscriber::fmt::format::FmtSpan;
+
+        let buffer = Buffer::default();
+        let subscriber = tracing_subscriber::registry().with(
+            tracing_subscriber::fmt::layer()
+                .with_span_events(FmtSpan::CLOSE)
+                .event_format(JsonFormat)
+                .fmt_fields(JsonFormat)
+                .with_writer(buffer.clone()),
+        );
+        let result = tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!(
+                "optimizer_step",
+                step = 1,
+                elapsed_ms = tracing::field::Empty
+            );
+            timed(&span, || 6 * 7)
+        });
+
+        assert_eq!(result, 42);
+        let lines = buffer.lines();
+        let close = &lines[0];
+        assert_eq!(close["message"], "close");
+        assert!(close["fields"]["time.busy"].is_string());
+        let fields = &close["spans"][0]["fields"];
+        assert_eq!(fields["step"], 1);
+        assert!(fields["elapsed_ms"].as_f64().unwrap() >= 0.0);
+    }
+}
@@ -4,7 +4,42 @@
 
 use clap::{Parser, Subcommand};
 use std::path::PathBuf;
-use tiny_agent_trainer::{init_logging, Config, WGSLValidator};
+#[cfg(feature = "wandb")]
+use std::sync::{Arc, Mutex};
+use tiny_agent_trainer::bench::{run_benchmarks, BenchConfig, BenchTable};
+use tiny_agent_trainer::capabilities::CapabilityReport;
+use tiny_agent_trainer::config::{LintLevel, TrackingBackend};
+use tiny_agent_trainer::dataset::{WGSLDataset, WGSLExample};
+use tiny_agent_trainer::device::Device;
+#[cfg(feature = "hub")]
+use tiny_agent_trainer::eval::EvalReport;
+use tiny_agent_trainer::eval::{Evaluator, GoldenSuite, PassAtK};
+#[cfg(feature = "hub")]
+use tiny_agent_trainer::hub::{HubClient, RepoType};
+#[cfg(feature = "openai")]
+use tiny_agent_trainer::inference::OpenAiBackend;
+use tiny_agent_trainer::inference::{
+    with_stage_tokens, FallbackIndex, GeneratorBackend, REMOTE_PREFIX,
+};
+use tiny_agent_trainer::model::summary::format_bytes;
+use tiny_agent_trainer::model::{
+    Checkpoint, CheckpointMetadata, CodeGenerationModel, QuantizedCheckpoint,
+};
+use tiny_agent_trainer::tokenizer::round_trip_corpus;
+#[cfg(feature = "wandb")]
+use tiny_agent_trainer::training::wandb::WandbRun;
+use tiny_agent_trainer::training::{
+    create_sink, CancellationToken, CrossValidator, RunManager, SamplePreview,
+};
+use tiny_agent_trainer::wgsl::{
+    diff_wgsl, format_wgsl, format_wgsl_or_original, validate_directory, DirectoryWatcher,
+    ShaderTarget, Stage, TemplateParams, TemplateRegistry, ValidationProfile,
+};
+use tiny_agent_trainer::{
+    init_logging, init_logging_from_config, init_stderr_logging, Config, ConfigFormat,
+    EngineConfig, GenerationConfig, GenerationOptions, GenerationResult, LintConfig,
+    ProviderConfig, Trainer, WGSLGenerator, WGSLTokenizer, WGSLTranspiler, WGSLValidator,
+};
 
 #[derive(Parser)]
 #[command(name = "tiny-agent-trainer")]
@@ -17,12 +52,43 @@ struct Cli {
     /// Enable verbose logging
     #[arg(short, long, global = true)]
     verbose: bool,
+
+    /// Disable progress bars
+    #[arg(short, long, global = true)]
+    quiet: bool,
+
+    /// Print machine-readable JSON instead of text (check, validate, eval,
+    /// train, generate, dataset stats); logs go to stderr
+    #[arg(long, global = true)]
+    json: bool,
+}
+
+/// How long-running commands report: progress bars, and JSON or text
+#[derive(Debug, Clone, Copy)]
+struct Reporting {
+    progress: bool,
+    json: bool,
+}
+
+/// `println!` unless JSON output was requested
+macro_rules! status {
+    ($json:expr, $($arg:tt)*) => {
+        if !$json {
+            println!($($arg)*);
+        }
+    };
 }
 
 #[derive(Subcommand)]
 enum Commands {
-    /// Check system capabilities
-    Check,
+    /// Check system capabilities; exits with code 2 when the engine
+    /// config's `[requirements]` are unmet
+    Check {
+        /// Engine config declaring the requirements (defaults to
+        /// config/engine.toml when present)
+        #[arg(short, long)]
+        engine: Option<PathBuf>,
+    },
 
     /// List available configurations
     List {
@@ -47,11 +113,40 @@ enum Commands {
         /// Override number of epochs
         #[arg(short, long)]
         epochs: Option<usize>,
+
+        /// Final checkpoint path (defaults to <checkpoint_path>/<task>.ckpt)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Instead of training one model, train K models with K-fold
+        /// cross-validation and report the spread of their eval metrics
+        #[arg(long, value_name = "K", conflicts_with = "output")]
+        cross_validate: Option<usize>,
+    },
+
+    /// Continue training a checkpoint on the config's dataset, keeping its
+    /// tokenizer and freezing the parts listed under `[training.freeze]`
+    FineTune {
+        /// Base model checkpoint
+        #[arg(short, long)]
+        model: PathBuf,
+
+        /// Configuration file with the fine-tuning data and training settings
+        #[arg(short, long)]
+        config: PathBuf,
+
+        /// Override number of epochs
+        #[arg(short, long)]
+        epochs: Option<usize>,
+
+        /// Final checkpoint path (defaults to <checkpoint_path>/<task>.ckpt)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
     },
 
     /// Generate WGSL code from natural language
     Generate {
-        /// Model checkpoint path
+        /// Model checkpoint path, or `openai:<model>` for a remote model
         #[arg(short, long)]
         model: PathBuf,
 
@@ -62,202 +157,2924 @@ enum Commands {
         /// Output file (optional, prints to stdout if not specified)
         #[arg(short, long)]
         output: Option<PathBuf>,
+
+        /// Also write the attention maps of every layer and head for the
+        /// prompt and generated code to this JSON file (checkpoints only)
+        #[arg(long)]
+        attention: Option<PathBuf>,
+
+        /// End each generated line with the model's confidence in it, as a
+        /// comment
+        #[arg(long)]
+        confidence: bool,
+
+        #[command(flatten)]
+        generation: GenerationArgs,
+    },
+
+    /// Generate one shader per prompt of a file with a trained model,
+    /// decoding the prompts in batches
+    Batch {
+        /// Model checkpoint path, or `openai:<model>` for a remote model
+        #[arg(short, long)]
+        model: PathBuf,
+
+        /// Text file with one prompt per line (blank and # lines are skipped)
+        #[arg(short, long)]
+        prompts: PathBuf,
+
+        /// Directory for the generated .wgsl files
+        #[arg(short, long)]
+        output_dir: PathBuf,
+
+        /// Prompts decoded together in one batch
+        #[arg(long, default_value_t = 16)]
+        batch_size: usize,
+
+        #[command(flatten)]
+        generation: GenerationArgs,
+    },
+
+    /// Evaluate a trained model on a held-out dataset
+    Eval {
+        /// Model checkpoint path, or `openai:<model>` for a remote model
+        /// configured under `[generation.provider]` of --config
+        #[arg(short, long)]
+        model: PathBuf,
+
+        /// Evaluation dataset (defaults to the test split of --config)
+        #[arg(short, long)]
+        dataset: Option<PathBuf>,
+
+        /// Configuration file providing the test split and validation profile
+        #[arg(short, long)]
+        config: Option<PathBuf>,
+
+        /// Report file (.md for Markdown, JSON otherwise)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Also report the perplexity of the reference code
+        #[arg(long)]
+        perplexity: bool,
+
+        /// Also sample this many generations per prompt and report pass@1/5/10
+        #[arg(long)]
+        samples: Option<usize>,
+
+        /// Sampling temperature for --samples
+        #[arg(long, default_value_t = 0.8)]
+        temperature: f32,
+
+        /// Sample only among the k most likely tokens (0 = no limit)
+        #[arg(long, default_value_t = 0)]
+        top_k: usize,
+
+        /// Sampling seed
+        #[arg(long)]
+        seed: Option<u64>,
+    },
+
+    /// Check a model's outputs for a prompt suite against golden
+    /// generations kept under version control, failing on drift
+    Regress {
+        /// Model checkpoint path, or `openai:<model>` for a remote model
+        /// configured under `[generation.provider]` of --config
+        #[arg(short, long)]
+        model: PathBuf,
+
+        /// Golden suite of prompts and their recorded generations
+        #[arg(short, long, default_value = "config/wgsl_golden.toml")]
+        golden: PathBuf,
+
+        /// Token similarity each output needs to its golden output, in
+        /// [0, 1]; 1 requires the same tokens (defaults to the suite's)
+        #[arg(short, long)]
+        threshold: Option<f64>,
+
+        /// Record the model's outputs as the new golden generations instead
+        #[arg(long)]
+        update: bool,
+
+        /// Prompts to add to the suite with --update, one per line
+        #[arg(short, long, requires = "update")]
+        prompts: Option<PathBuf>,
+
+        /// Configuration file providing the device and remote provider
+        #[arg(short, long)]
+        config: Option<PathBuf>,
     },
 
     /// Validate WGSL code
     Validate {
-        /// WGSL file to validate
+        /// WGSL file, or directory to validate recursively
+        file: PathBuf,
+
+        /// Validation profile (webgpu-core, native-extended, custom)
+        #[arg(short, long)]
+        profile: Option<String>,
+
+        /// Configuration file whose [validation] section selects the profile
+        #[arg(short, long)]
+        config: Option<PathBuf>,
+
+        /// Only validate directory entries matching this glob (e.g. "compute/**/*.wgsl")
+        #[arg(short, long)]
+        glob: Option<String>,
+
+        /// Write a JSON report of a directory run to this file
+        #[arg(short, long)]
+        report: Option<PathBuf>,
+
+        /// Keep watching the directory and re-validate files as they change
+        #[arg(short, long)]
+        watch: bool,
+    },
+
+    /// Lint WGSL files for style issues and suspicious constructs
+    Lint {
+        /// WGSL files to lint
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+
+        /// Configuration file whose [lint] section sets rule levels
+        #[arg(short, long)]
+        config: Option<PathBuf>,
+    },
+
+    /// Format WGSL files in place
+    Fmt {
+        /// WGSL files to format
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+
+        /// Report unformatted files without writing them
+        #[arg(long)]
+        check: bool,
+    },
+
+    /// Show a colored line and token diff between two shaders: two files,
+    /// a reference file and a model's shader for --prompt, or the shaders of
+    /// two models for --prompt
+    Diff {
+        /// Shader files; the first one is the old side
+        files: Vec<PathBuf>,
+
+        /// Model generating a side for --prompt (repeatable), a checkpoint or
+        /// `openai:<model>`
+        #[arg(short, long, requires = "prompt")]
+        model: Vec<PathBuf>,
+
+        /// Prompt the models generate for
+        #[arg(short, long, requires = "model")]
+        prompt: Option<String>,
+
+        /// Never color the output; it is also plain when not printed to a
+        /// terminal or NO_COLOR is set
+        #[arg(long)]
+        no_color: bool,
+
+        #[command(flatten)]
+        generation: GenerationArgs,
+    },
+
+    /// Convert WGSL to SPIR-V, GLSL, HLSL or MSL
+    Convert {
+        /// WGSL file to convert
         file: PathBuf,
+
+        /// Target language (spirv, glsl, hlsl, msl)
+        #[arg(short, long)]
+        target: String,
+        /// Entry point to emit (GLSL defaults to the first one, other targets to all)
+        /// Entry point to emit (defaults to the first one)
+        #[arg(short, long)]
+        entry_point: Option<String>,
+
+        /// Output file (defaults to the input path with the target's extension)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
     },
 
     /// Create a default configuration file
     Init {
-        /// Output path for configuration
+        /// Output path for configuration (.toml, .json or .yaml)
         #[arg(short, long, default_value = "config/wgsl_generation.toml")]
         output: PathBuf,
+
+        /// Model size and training schedule: tiny, small or base (defaults
+        /// to a 512-d, 6-layer model)
+        #[arg(short, long)]
+        preset: Option<String>,
     },
-}
 
-fn main() -> anyhow::Result<()> {
-    let cli = Cli::parse();
+    /// Dataset utilities
+    Dataset {
+        #[command(subcommand)]
+        command: DatasetCommands,
+    },
 
-    // Initialize logging
-    if cli.verbose {
-        std::env::set_var("RUST_LOG", "debug");
-    }
-    init_logging();
+    /// Export, import or quantize model weights
+    Weights {
+        #[command(subcommand)]
+        command: WeightsCommands,
+    },
 
-    match cli.command {
-        Commands::Check => check_system(),
-        Commands::List { config_dir } => list_configs(&config_dir),
-        Commands::Show { config } => show_config(&config),
-        Commands::Train { config, epochs } => train_model(&config, epochs),
-        Commands::Generate {
-            model,
-            prompt,
-            output,
-        } => generate_wgsl(&model, &prompt, output.as_deref()),
-        Commands::Validate { file } => validate_wgsl(&file),
-        Commands::Init { output } => init_config(&output),
-    }
-}
+    /// Download from and push checkpoints to the Hugging Face Hub
+    #[cfg(feature = "hub")]
+    Hub {
+        #[command(subcommand)]
+        command: HubCommands,
+    },
 
-fn check_system() -> anyhow::Result<()> {
-    println!("🔍 System Check");
-    println!("{}", "=".repeat(40));
+    /// Show a checkpoint's architecture and parameter breakdown
+    Inspect {
+        /// Model checkpoint path
+        #[arg(short, long)]
+        model: PathBuf,
+    },
 
-    // Check WGPU availability
-    println!("🖥️  GPU Support:");
-    let adapters: Vec<_> = wgpu::Instance::default().enumerate_adapters(wgpu::Backends::all());
-    match adapters.first() {
-        Some(adapter) => {
-            let info = adapter.get_info();
-            println!("  ✅ GPU found: {}", info.name);
-            println!("     Backend: {:?}", info.backend);
-            println!("     Type: {:?}", info.device_type);
-        }
-        None => {
-            println!("  ⚠️  No GPU detected");
-        }
-    }
+    /// Inspect and check configuration files
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommands,
+    },
 
-    println!("\n📦 Dependencies:");
-    println!("  ✅ wgpu: {}", env!("CARGO_PKG_VERSION"));
-    println!("  ✅ naga: validation available");
+    /// Browse and render the built-in shader templates
+    Template {
+        #[command(subcommand)]
+        command: TemplateCommands,
+    },
 
-    println!("\n✅ System check complete!");
-    Ok(())
-}
+    /// Show how a file is tokenized: tokens, ids, out-of-vocabulary tokens
+    /// and length against the model's limits
+    Tokenize {
+        /// File to tokenize (WGSL or a prompt)
+        #[arg(short, long)]
+        file: PathBuf,
 
-fn list_configs(config_dir: &PathBuf) -> anyhow::Result<()> {
-    println!("📋 Available Configurations:");
-    println!("{}", "=".repeat(40));
+        /// Tokenizer JSON file
+        #[arg(short, long, conflicts_with = "model")]
+        tokenizer: Option<PathBuf>,
 
-    if !config_dir.exists() {
-        println!("⚠️  Config directory not found: {}", config_dir.display());
-        println!("💡 Run 'tiny-agent-trainer init' to create a default config");
-        return Ok(());
-    }
+        /// Checkpoint whose tokenizer and sequence limit to use
+        #[arg(short, long)]
+        model: Option<PathBuf>,
 
-    let mut found = false;
-    for entry in std::fs::read_dir(config_dir)? {
-        let entry = entry?;
-        let path = entry.path();
-        if path.extension().and_then(|s| s.to_str()) == Some("toml") {
-            println!("  📄 {}", path.file_stem().unwrap().to_string_lossy());
-            found = true;
-        }
-    }
+        /// Fail if any token is out of vocabulary
+        #[arg(long)]
+        strict: bool,
+    },
 
-    if !found {
-        println!("  No configuration files found");
-    }
+    /// Export mean-pooled encoder embeddings of prompts, or rank the
+    /// examples of a dataset by similarity to a query
+    Embed {
+        /// Model checkpoint path
+        #[arg(short, long)]
+        model: PathBuf,
 
-    Ok(())
-}
+        /// Prompt to embed (repeatable)
+        #[arg(short, long)]
+        prompt: Vec<String>,
 
-fn show_config(config_name: &str) -> anyhow::Result<()> {
-    let config_path = PathBuf::from(format!("config/{}.toml", config_name));
-    let config = Config::from_file(&config_path)?;
+        /// Dataset whose example prompts to embed or rank
+        #[arg(short, long)]
+        dataset: Option<PathBuf>,
 
-    println!("🔧 Configuration: {}", config.task.name);
-    println!("{}", "=".repeat(50));
-    println!("\n📊 Model:");
-    println!("  Architecture: {}", config.model.architecture);
-    println!("  d_model: {}", config.model.d_model);
-    println!("  Attention heads: {}", config.model.nhead);
-    println!("  Layers: {}", config.model.num_layers);
+        /// JSONL file of `{"prompt", "embedding"}` lines (prints to stdout if
+        /// not specified)
+        #[arg(short, long, conflicts_with = "query")]
+        output: Option<PathBuf>,
 
-    println!("\n🎯 Training:");
-    println!("  Epochs: {}", config.training.num_epochs);
-    println!("  Batch size: {}", config.training.batch_size);
-    println!("  Learning rate: {}", config.training.learning_rate);
-    println!("  Optimizer: {}", config.training.optimizer);
+        /// Instead of exporting, list the examples of --dataset most similar
+        /// to this prompt
+        #[arg(long, requires = "dataset")]
+        query: Option<String>,
 
-    println!("\n📚 Dataset:");
-    println!("  Train path: {}", config.dataset.train_path.display());
+        /// Number of examples listed for --query
+        #[arg(short, long, default_value_t = 5)]
+        k: usize,
+    },
 
-    Ok(())
-}
+    /// Load a model once and generate shaders from prompts interactively
+    Repl {
+        /// Model checkpoint path
+        #[arg(short, long)]
+        model: PathBuf,
+    },
 
-fn train_model(_config_path: &PathBuf, _epochs: Option<usize>) -> anyhow::Result<()> {
-    println!("🚀 Training model...");
-    println!("⚠️  Training not yet implemented - placeholder only");
+    /// Measure forward, training step and generation throughput across
+    /// model sizes and sequence lengths
+    Bench {
+        /// Model widths to benchmark
+        #[arg(long, value_delimiter = ',', default_values_t = [64, 128])]
+        d_model: Vec<usize>,
 
-    // TODO: Implement actual training
-    // let config = Config::from_file(config_path)?;
-    // let mut trainer = Trainer::new(config.training);
-    // trainer.train(&mut model)?;
+        /// Layer counts to benchmark
+        #[arg(long, value_delimiter = ',', default_values_t = [1, 2])]
+        layers: Vec<usize>,
 
-    Ok(())
+        /// Sequence lengths to benchmark
+        #[arg(long, value_delimiter = ',', default_values_t = [32, 128])]
+        seq_len: Vec<usize>,
+
+        /// Attention heads of every model
+        #[arg(long, default_value_t = 4)]
+        nhead: usize,
+
+        /// Timed repetitions of each measurement
+        #[arg(short, long, default_value_t = 3)]
+        iterations: usize,
+    },
 }
 
-fn generate_wgsl(
-    _model_path: &PathBuf,
-    prompt: &str,
-    output: Option<&std::path::Path>,
-) -> anyhow::Result<()> {
-    println!("🎨 Generating WGSL code...");
-    println!("Prompt: {}", prompt);
-
-    // TODO: Load actual model
-    // let generator = WGSLGenerator::from_checkpoint(model_path)?;
-
-    // For now, use templates as examples
-    let wgsl_code = if prompt.contains("mix") {
-        tiny_agent_trainer::ChromaticTemplate::mix()
-    } else if prompt.contains("filter") {
-        tiny_agent_trainer::ChromaticTemplate::filter()
-    } else if prompt.contains("complement") {
-        tiny_agent_trainer::ChromaticTemplate::complement()
-    } else if prompt.contains("saturate") {
-        tiny_agent_trainer::ChromaticTemplate::saturate()
-    } else {
-        format!("// Generated WGSL for: {}\n// TODO: Train model to generate actual code\n", prompt)
-    };
+/// Decoding settings: the `[generation]` section of `--config`, overridden
+/// by the individual flags
+#[derive(clap::Args)]
+struct GenerationArgs {
+    /// Config whose `[generation]` section provides the defaults
+    #[arg(short, long)]
+    config: Option<PathBuf>,
 
-    if let Some(output_path) = output {
-        std::fs::write(output_path, &wgsl_code)?;
-        println!("✅ Saved to: {}", output_path.display());
-    } else {
-        println!("\n{}", wgsl_code);
-    }
+    /// Sampling temperature (0 = greedy)
+    #[arg(long)]
+    temperature: Option<f32>,
 
-    Ok(())
-}
+    /// Sample only among the k most likely tokens (0 = no limit)
+    #[arg(long)]
+    top_k: Option<usize>,
 
-fn validate_wgsl(file: &PathBuf) -> anyhow::Result<()> {
-    println!("🔍 Validating WGSL: {}", file.display());
+    /// Sample only among the most likely tokens covering this probability
+    #[arg(long)]
+    top_p: Option<f32>,
 
-    let validator = WGSLValidator::new();
-    let result = validator.validate_file(file)?;
+    /// Beams searched (1 = a single sequence)
+    #[arg(long)]
+    beam_width: Option<usize>,
 
-    result.print();
+    /// Most tokens to generate
+    #[arg(long)]
+    max_new_tokens: Option<usize>,
 
-    if !result.is_valid {
-        std::process::exit(1);
-    }
+    /// Regenerate invalid shaders up to this many times, feeding the
+    /// validation error back into the prompt
+    #[arg(long, alias = "repair")]
+    retries: Option<usize>,
 
-    Ok(())
-}
+    /// Sampling seed; in batches prompt i uses seed + i
+    #[arg(long)]
+    seed: Option<u64>,
 
-fn init_config(output: &PathBuf) -> anyhow::Result<()> {
-    println!("📝 Creating default configuration...");
+    /// Shader stage to generate: compute, vertex or fragment
+    #[arg(long)]
+    stage: Option<Stage>,
 
-    let config = Config::default_wgsl_generation();
+    /// With --stage, never emit other stages' entry point attributes
+    #[arg(long)]
+    constrain_stage: bool,
 
-    // Ensure parent directory exists
-    if let Some(parent) = output.parent() {
-        std::fs::create_dir_all(parent)?;
+    /// When no generation validates, return the closest valid training
+    /// example or built-in template instead
+    #[arg(long)]
+    fallback: bool,
+}
+
+impl GenerationArgs {
+    /// The config's `[generation]` section with the flags applied, and the
+    /// device of its `[device]` section
+    fn resolve(&self) -> anyhow::Result<(GenerationConfig, Device)> {
+        let (mut generation, device) = match &self.config {
+            Some(path) => {
+                let config = Config::from_file(path)?;
+                let mut generation = config.generation;
+                if generation.fallback_dataset.is_none() {
+                    generation.fallback_dataset = Some(config.dataset.train_path);
+                }
+                (generation, Device::from_config(&config.device)?)
+            }
+            None => (GenerationConfig::default(), Device::cpu()),
+        };
+        if let Some(temperature) = self.temperature {
+            generation.temperature = temperature;
+        }
+        if let Some(top_k) = self.top_k {
+            generation.top_k = top_k;
+        }
+        if let Some(top_p) = self.top_p {
+            generation.top_p = top_p;
+        }
+        if let Some(beam_width) = self.beam_width {
+            generation.beam_width = beam_width;
+        }
+        if let Some(max_new_tokens) = self.max_new_tokens {
+            generation.max_new_tokens = Some(max_new_tokens);
+        }
+        if let Some(retries) = self.retries {
+            generation.retries = retries;
+        }
+        if let Some(stage) = self.stage {
+            generation.target_stage = Some(stage);
+        }
+        generation.constrain_stage |= self.constrain_stage;
+        generation.fallback |= self.fallback;
+        let errors = generation.validation_errors();
+        if !errors.is_empty() {
+            anyhow::bail!("invalid generation settings: {}", errors.join("; "));
+        }
+        Ok((generation, device))
     }
+}
 
-    config.to_file(output)?;
+#[derive(Subcommand)]
+enum DatasetCommands {
+    /// Merge datasets, dropping duplicate examples
+    Merge {
+        /// Dataset files to merge (.toml, .json, .jsonl, or .parquet with the `arrow` feature)
+        #[arg(required = true, num_args = 2..)]
+        inputs: Vec<PathBuf>,
 
-    println!("✅ Configuration created: {}", output.display());
-    println!("\n💡 Next steps:");
-    println!("  1. Edit the configuration file as needed");
-    println!("  2. Prepare training data at: config/wgsl_training_data.toml");
-    println!("  3. Run: tiny-agent-trainer train --config {}", output.display());
+        /// Output dataset file
+        #[arg(short, long)]
+        output: PathBuf,
+    },
+
+    /// Compare two datasets
+    Diff {
+        /// First dataset file
+        a: PathBuf,
+
+        /// Second dataset file
+        b: PathBuf,
+    },
+
+    /// Example counts, token lengths, categories, duplicates and validity
+    Stats {
+        /// Dataset file (.toml, .json, .jsonl, or .parquet with the `arrow` feature)
+        dataset: PathBuf,
+    },
+
+    /// Check that every valid shader still parses after tokenizing and
+    /// joining its tokens back together
+    RoundTrip {
+        /// Dataset file (.toml, .json, .jsonl, or .parquet with the `arrow` feature)
+        dataset: PathBuf,
+
+        /// Tokenizer JSON file
+        #[arg(short, long, conflicts_with = "model")]
+        tokenizer: Option<PathBuf>,
+
+        /// Checkpoint whose tokenizer to use
+        #[arg(short, long)]
+        model: Option<PathBuf>,
+    },
+
+    /// Regenerate a dataset's shaders with a teacher model, keeping the
+    /// valid ones as training data for a smaller model
+    Distill {
+        /// Dataset whose prompts to generate for (.toml, .json, .jsonl, or
+        /// .parquet with the `arrow` feature)
+        dataset: PathBuf,
+
+        /// Teacher checkpoint path, or `openai:<model>` for a remote model
+        #[arg(short, long)]
+        model: PathBuf,
+
+        /// Output dataset file
+        #[arg(short, long)]
+        output: PathBuf,
+
+        /// Also keep shaders that fail validation
+        #[arg(long)]
+        keep_invalid: bool,
+
+        #[command(flatten)]
+        generation: GenerationArgs,
+    },
+}
+
+#[derive(Subcommand)]
+enum ConfigCommands {
+    /// Check a config's values and their consistency, listing every problem
+    Validate {
+        /// Configuration file
+        config: PathBuf,
+    },
+
+    /// Print a config as loaded: its `extends` chain merged and defaults
+    /// filled in
+    Resolve {
+        /// Configuration file
+        config: PathBuf,
+
+        /// Output format: toml, json or yaml (defaults to the file's)
+        #[arg(short, long)]
+        format: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum TemplateCommands {
+    /// List the templates with their parameters and trigger keywords
+    List,
+
+    /// Render a template by name
+    Render {
+        /// Template name, as shown by `template list`
+        name: String,
+
+        /// Workgroup size: x, x,y or x,y,z
+        #[arg(long)]
+        workgroup_size: Option<String>,
+
+        /// Element scalar type (f32, i32, u32)
+        #[arg(long)]
+        scalar_type: Option<String>,
+
+        /// Components per element, 1 to 4
+        #[arg(long)]
+        channels: Option<u32>,
+
+        /// Output file (prints to stdout if not specified)
+        #[arg(short, long)]
+        out: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+enum WeightsCommands {
+    /// Write a checkpoint's weights to a safetensors file
+    Export {
+        /// Model checkpoint path
+        #[arg(short, long)]
+        model: PathBuf,
+
+        /// Output .safetensors file
+        #[arg(short, long)]
+        output: PathBuf,
+    },
+
+    /// Replace a checkpoint's weights with those of a safetensors file
+    Import {
+        /// Model checkpoint path
+        #[arg(short, long)]
+        model: PathBuf,
+
+        /// Safetensors file with a tensor for every parameter
+        #[arg(short, long)]
+        weights: PathBuf,
+
+        /// Output checkpoint (defaults to overwriting --model)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Write an int8 copy of a checkpoint; load it like any other checkpoint
+    Quantize {
+        /// Model checkpoint path
+        #[arg(short, long)]
+        model: PathBuf,
+
+        /// Output quantized checkpoint
+        #[arg(short, long)]
+        output: PathBuf,
+    },
+}
+
+#[cfg(feature = "hub")]
+#[derive(Subcommand)]
+enum HubCommands {
+    /// Download a file of a model or dataset repository
+    Download {
+        /// Repository, as `user/name`
+        repo: String,
+
+        /// Path of the file in the repository
+        file: String,
+
+        /// Download from a dataset repository rather than a model one
+        #[arg(long)]
+        dataset: bool,
+
+        /// Branch, tag or commit
+        #[arg(long, default_value = "main")]
+        revision: String,
+
+        /// Directory to download into
+        #[arg(short, long, default_value = ".")]
+        output: PathBuf,
+    },
+
+    /// Push a checkpoint with its tokenizer and a model card
+    Push {
+        /// Model checkpoint path
+        #[arg(short, long)]
+        model: PathBuf,
+
+        /// Repository, as `user/name`; created when missing
+        #[arg(short, long)]
+        repo: String,
+
+        /// JSON report written by `eval --output`, added to the model card
+        #[arg(short, long)]
+        eval: Option<PathBuf>,
+
+        /// Create the repository as private
+        #[arg(long)]
+        private: bool,
+    },
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+
+    // Initialize logging
+    if cli.verbose {
+        std::env::set_var("RUST_LOG", "debug");
+    }
+    match EngineConfig::from_file_if_present("config/engine.toml")? {
+        Some(engine) => init_logging_from_config(&engine, cli.json)?,
+        None if cli.json => init_stderr_logging(),
+        None => init_logging(),
+    }
+
+    let json = cli.json;
+    let reporting = Reporting {
+        progress: !cli.quiet && !json,
+        json,
+    };
+    let result = match cli.command {
+        Commands::Check { engine } => check_system(engine.as_ref(), json),
+        Commands::List { config_dir } => list_configs(&config_dir),
+        Commands::Show { config } => show_config(&config),
+        Commands::Train {
+            config,
+            epochs,
+            cross_validate: Some(folds),
+            ..
+        } => cross_validate(&config, epochs, folds, reporting),
+        Commands::Train {
+            config,
+            epochs,
+            output,
+            cross_validate: None,
+        } => train_model(&config, None, epochs, output.as_ref(), reporting),
+        Commands::FineTune {
+            model,
+            config,
+            epochs,
+            output,
+        } => train_model(&config, Some(&model), epochs, output.as_ref(), reporting),
+        Commands::Generate {
+            model,
+            prompt,
+            output,
+            attention,
+            confidence,
+            generation,
+        } => generation.resolve().and_then(|(mut config, device)| {
+            config.confidence_comments |= confidence;
+            if attention.is_some() && !model.is_file() {
+                anyhow::bail!("--attention needs a local checkpoint");
+            }
+            let code = generate_wgsl(
+                &model,
+                &prompt,
+                output.as_deref(),
+                &config,
+                generation.seed,
+                &device,
+                json,
+            )?;
+            match attention {
+                Some(path) => export_attention(&model, &prompt, &code, &path, json),
+                None => Ok(()),
+            }
+        }),
+        Commands::Batch {
+            model,
+            prompts,
+            output_dir,
+            batch_size,
+            generation,
+        } => generate_batch(&model, &prompts, &output_dir, batch_size, &generation, json),
+        Commands::Eval {
+            model,
+            dataset,
+            config,
+            output,
+            perplexity,
+            samples,
+            temperature,
+            top_k,
+            seed,
+        } => {
+            let sampling = samples.map(|samples| {
+                (
+                    samples,
+                    GenerationOptions {
+                        temperature,
+                        top_k,
+                        seed,
+                        ..GenerationOptions::default()
+                    },
+                )
+            });
+            evaluate_model(
+                &model,
+                dataset.as_ref(),
+                config.as_ref(),
+                output.as_ref(),
+                perplexity,
+                sampling,
+                reporting,
+            )
+        }
+        Commands::Regress {
+            model,
+            golden,
+            threshold,
+            update,
+            prompts,
+            config,
+        } => {
+            if update {
+                record_golden(&model, &golden, prompts.as_ref(), config.as_ref(), json)
+            } else {
+                regress_model(&model, &golden, threshold, config.as_ref(), json)
+            }
+        }
+        Commands::Validate {
+            file,
+            profile,
+            config,
+            glob,
+            report,
+            watch,
+        } => validate_wgsl(
+            &file,
+            profile.as_deref(),
+            config.as_ref(),
+            glob.as_deref(),
+            report.as_ref(),
+            watch,
+            json,
+        ),
+        Commands::Lint { files, config } => lint_files(&files, config.as_ref()),
+        Commands::Fmt { files, check } => format_files(&files, check),
+        Commands::Diff {
+            files,
+            model,
+            prompt,
+            no_color,
+            generation,
+        } => diff_sides(&files, &model, prompt.as_deref(), &generation)
+            .and_then(|sides| show_diff(sides, !no_color, json)),
+        Commands::Convert {
+            file,
+            target,
+            entry_point,
+            output,
+        } => convert_wgsl(&file, &target, entry_point.as_deref(), output.as_ref()),
+        Commands::Init { output, preset } => init_config(&output, preset.as_deref()),
+        Commands::Dataset { command } => match command {
+            DatasetCommands::Merge { inputs, output } => merge_datasets(&inputs, &output),
+            DatasetCommands::Diff { a, b } => diff_datasets(&a, &b),
+            DatasetCommands::Stats { dataset } => dataset_stats(&dataset, json),
+            DatasetCommands::RoundTrip {
+                dataset,
+                tokenizer,
+                model,
+            } => round_trip_dataset(&dataset, tokenizer.as_ref(), model.as_ref()),
+            DatasetCommands::Distill {
+                dataset,
+                model,
+                output,
+                keep_invalid,
+                generation,
+            } => generation.resolve().and_then(|(config, device)| {
+                distill_dataset(
+                    &dataset,
+                    &model,
+                    &output,
+                    &config,
+                    generation.seed,
+                    &device,
+                    keep_invalid,
+                )
+            }),
+        },
+        Commands::Weights { command } => match command {
+            WeightsCommands::Export { model, output } => export_weights(&model, &output),
+            WeightsCommands::Import {
+                model,
+                weights,
+                output,
+            } => import_weights(&model, &weights, output.as_ref()),
+            WeightsCommands::Quantize { model, output } => quantize_weights(&model, &output),
+        },
+        #[cfg(feature = "hub")]
+        Commands::Hub { command } => match command {
+            HubCommands::Download {
+                repo,
+                file,
+                dataset,
+                revision,
+                output,
+            } => hub_download(&repo, &file, dataset, &revision, &output),
+            HubCommands::Push {
+                model,
+                repo,
+                eval,
+                private,
+            } => hub_push(&model, &repo, eval.as_ref(), private),
+        },
+        Commands::Inspect { model } => inspect_model(&model),
+        Commands::Config { command } => match command {
+            ConfigCommands::Validate { config } => validate_config(&config, json),
+            ConfigCommands::Resolve { config, format } => {
+                resolve_config(&config, format.as_deref(), json)
+            }
+        },
+        Commands::Template { command } => match command {
+            TemplateCommands::List => list_templates(),
+            TemplateCommands::Render {
+                name,
+                workgroup_size,
+                scalar_type,
+                channels,
+                out,
+            } => render_template(
+                &name,
+                workgroup_size.as_deref(),
+                scalar_type.as_deref(),
+                channels,
+                out.as_ref(),
+            ),
+        },
+        Commands::Tokenize {
+            file,
+            tokenizer,
+            model,
+            strict,
+        } => tokenize_file(&file, tokenizer.as_ref(), model.as_ref(), strict),
+        Commands::Embed {
+            model,
+            prompt,
+            dataset,
+            output,
+            query,
+            k,
+        } => embed_prompts(
+            &model,
+            &prompt,
+            dataset.as_ref(),
+            output.as_ref(),
+            query.as_deref(),
+            k,
+        ),
+        Commands::Repl { model } => run_repl(&model),
+        Commands::Bench {
+            d_model,
+            layers,
+            seq_len,
+            nhead,
+            iterations,
+        } => run_bench(&BenchConfig {
+            d_models: d_model,
+            num_layers: layers,
+            seq_lens: seq_len,
+            nhead,
+            iterations,
+            ..BenchConfig::default()
+        }),
+    };
+
+    if let (true, Err(error)) = (json, &result) {
+        print_json(&serde_json::json!({ "error": format!("{:#}", error) }))?;
+        std::process::exit(1);
+    }
+    result
+}
+
+/// Print `value` as pretty JSON on stdout
+fn print_json(value: &impl serde::Serialize) -> anyhow::Result<()> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
+
+/// Exit code of `check` when no adapter meets the requirements
+const EXIT_REQUIREMENTS_UNMET: i32 = 2;
+
+fn check_system(engine: Option<&PathBuf>, json: bool) -> anyhow::Result<()> {
+    let engine = match engine {
+        Some(path) => EngineConfig::from_file(path)?,
+        None => EngineConfig::from_file_if_present("config/engine.toml")?.unwrap_or_default(),
+    };
+    let report = CapabilityReport::detect(&engine.requirements);
+    if json {
+        print_json(&report)?;
+    } else {
+        print_capabilities(&report);
+    }
+    if !report.is_satisfied() {
+        std::process::exit(EXIT_REQUIREMENTS_UNMET);
+    }
+    Ok(())
+}
+
+fn print_capabilities(report: &CapabilityReport) {
+    println!("🔍 System Check");
+    println!("{}", "=".repeat(40));
+
+    println!("🖥️  GPU Support:");
+    if report.adapters.is_empty() {
+        println!("  ⚠️  No GPU detected");
+    }
+    for adapter in &report.adapters {
+        println!(
+            "  {} {}",
+            if adapter.is_gpu() { "✅" } else { "⚠️ " },
+            adapter.name
+        );
+        println!("     Backend: {}", adapter.backend);
+        println!("     Type: {}", adapter.device_type);
+        println!(
+            "     shader-f16: {}",
+            if adapter.shader_f16 { "yes" } else { "no" }
+        );
+        println!(
+            "     Max buffer: {}, storage binding: {}",
+            format_bytes(adapter.limits.max_buffer_size as usize),
+            format_bytes(adapter.limits.max_storage_buffer_binding_size as usize)
+        );
+        println!(
+            "     Workgroup: {} invocations, size {:?}",
+            adapter.limits.max_compute_invocations_per_workgroup,
+            adapter.limits.max_compute_workgroup_size
+        );
+    }
+    if !report.backends.is_empty() {
+        println!("  Backends: {}", report.backends.join(", "));
+    }
+
+    println!("\n📦 Dependencies:");
+    println!("  ✅ wgpu: {}", report.version);
+    println!("  ✅ naga: validation available");
+
+    if report.is_satisfied() {
+        if let Some(adapter) = &report.selected {
+            println!("\n🎯 Requirements met by {}", adapter);
+        }
+        println!("\n✅ System check complete!");
+    } else {
+        println!("\n❌ Requirements not met:");
+        for (adapter, reasons) in &report.unmet {
+            println!("  {}: {}", adapter, reasons.join("; "));
+        }
+    }
+}
+
+fn list_configs(config_dir: &PathBuf) -> anyhow::Result<()> {
+    println!("📋 Available Configurations:");
+    println!("{}", "=".repeat(40));
+
+    if !config_dir.exists() {
+        println!("⚠️  Config directory not found: {}", config_dir.display());
+        println!("💡 Run 'tiny-agent-trainer init' to create a default config");
+        return Ok(());
+    }
+
+    let mut found = false;
+    for entry in std::fs::read_dir(config_dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        let extension = path.extension().and_then(|s| s.to_str());
+        if extension.is_some_and(|ext| ConfigFormat::EXTENSIONS.contains(&ext)) {
+            println!("  📄 {}", path.file_stem().unwrap().to_string_lossy());
+            found = true;
+        }
+    }
+
+    if !found {
+        println!("  No configuration files found");
+    }
+
+    Ok(())
+}
+
+fn show_config(config_name: &str) -> anyhow::Result<()> {
+    let config = Config::from_file(Config::find(std::path::Path::new("config"), config_name)?)?;
+
+    println!("🔧 Configuration: {}", config.task.name);
+    println!("{}", "=".repeat(50));
+    println!("\n📊 Model:");
+    println!("  Architecture: {}", config.model.architecture);
+    println!("  d_model: {}", config.model.d_model);
+    println!("  Attention heads: {}", config.model.nhead);
+    println!("  Layers: {}", config.model.num_layers);
+
+    println!("\n🎯 Training:");
+    println!("  Epochs: {}", config.training.num_epochs);
+    println!("  Batch size: {}", config.training.batch_size);
+    println!("  Learning rate: {}", config.training.learning_rate);
+    println!("  Optimizer: {}", config.training.optimizer);
+
+    println!("\n📚 Dataset:");
+    println!("  Train path: {}", config.dataset.train_path.display());
+
+    Ok(())
+}
+
+fn validate_config(path: &PathBuf, json: bool) -> anyhow::Result<()> {
+    let errors = Config::parse_file(path)?.validation_errors();
+    if json {
+        print_json(&serde_json::json!({
+            "file": path,
+            "valid": errors.is_empty(),
+            "errors": errors,
+        }))?;
+    } else if errors.is_empty() {
+        println!("✅ {} is valid", path.display());
+    } else {
+        println!("❌ {} has {} problem(s):", path.display(), errors.len());
+        for error in &errors {
+            println!("  - {}", error);
+        }
+    }
+    if !errors.is_empty() {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+fn resolve_config(path: &PathBuf, format: Option<&str>, json: bool) -> anyhow::Result<()> {
+    let config = Config::parse_file(path)?;
+    if json {
+        return print_json(&config);
+    }
+    let format = match format {
+        Some(format) => format.parse()?,
+        None => ConfigFormat::from_path(path),
+    };
+    print!("{}", format.render(&config)?);
+    Ok(())
+}
+
+/// Train a model from scratch, or from the `base` checkpoint with its
+/// tokenizer when fine-tuning
+fn train_model(
+    config_path: &PathBuf,
+    base: Option<&PathBuf>,
+    epochs: Option<usize>,
+    output: Option<&PathBuf>,
+    reporting: Reporting,
+) -> anyhow::Result<()> {
+    let Reporting { progress, json } = reporting;
+    let base = match base {
+        Some(path) => {
+            status!(json, "🎯 Fine-tuning {}...", path.display());
+            Some(Checkpoint::load(path)?)
+        }
+        None => {
+            status!(json, "🚀 Training model...");
+            None
+        }
+    };
+
+    let config = Config::from_file(config_path)?;
+    let engine = EngineConfig::from_file_if_present("config/engine.toml")?.unwrap_or_default();
+
+    let full = WGSLDataset::from_file(&config.dataset.train_path)?;
+    let (train, val, test) = match &config.dataset.val_path {
+        Some(path) => (full, WGSLDataset::from_file(path)?, WGSLDataset::new()),
+        None => full.split(config.dataset.train_ratio, config.dataset.val_ratio),
+    };
+    let test = match &config.dataset.test_path {
+        Some(path) => WGSLDataset::from_file(path)?,
+        None => test,
+    };
+    let prompt_template = config.prompt_template()?;
+    let (train, val, test) = match &prompt_template {
+        Some(template) => {
+            status!(json, "   Prompt template: {}", template.as_str());
+            (
+                template.format_dataset(&train),
+                template.format_dataset(&val),
+                template.format_dataset(&test),
+            )
+        }
+        None => (train, val, test),
+    };
+    // Fine-tuned and distilled models keep the base model's or teacher's tokenizer
+    let inherited_tokenizer = match (&base, &config.training.distillation) {
+        (Some(base), _) => Some(base.tokenizer.clone()),
+        (None, Some(distillation)) => {
+            status!(json, "   Teacher: {}", distillation.teacher.display());
+            Some(Checkpoint::load(&distillation.teacher)?.tokenizer)
+        }
+        (None, None) => None,
+    };
+    status!(json, "   Train examples: {}", train.len());
+    status!(json, "   Val examples: {}", val.len());
+    status!(json, "   Test examples: {}", test.len());
+
+    let inherited = inherited_tokenizer.is_some();
+    let mut tokenizer = match inherited_tokenizer {
+        Some(tokenizer) => tokenizer,
+        None => WGSLTokenizer::from_config(&config.tokenizer)?,
+    };
+    // Prefix prompts with their stage's control token, if the tokenizer has one
+    let (train, val) = (
+        with_stage_tokens(&train, &tokenizer),
+        with_stage_tokens(&val, &tokenizer),
+    );
+    // The trainer normalizes prompts while encoding; fit on the same text
+    let prompts: Vec<String> = train
+        .examples
+        .iter()
+        .map(|e| tokenizer.normalize_prompt(&e.natural_language))
+        .collect();
+    let texts: Vec<&str> = train
+        .examples
+        .iter()
+        .zip(&prompts)
+        .flat_map(|(e, prompt)| [prompt.as_str(), e.wgsl_code.as_str()])
+        .collect();
+    if inherited {
+        // The base model's or teacher's embeddings fix the vocabulary
+        let (known, total) = texts.iter().fold((0.0, 0), |(known, total), text| {
+            let tokenized = tokenizer.tokenize_detailed(text);
+            let tokens = tokenized.ids.len();
+            (known + tokenized.coverage() * tokens as f64, total + tokens)
+        });
+        status!(
+            json,
+            "   Vocabulary: {} tokens, covering {:.1}% of the training tokens",
+            tokenizer.vocab_size(),
+            100.0 * known / total.max(1) as f64
+        );
+    } else {
+        tokenizer.fit(&texts, config.tokenizer.min_freq);
+        status!(json, "   Vocabulary: {} tokens", tokenizer.vocab_size());
+    }
+
+    // Apply the length policy here rather than letting the model silently
+    // cut over-long sequences
+    let max_tokens = config.max_example_tokens();
+    let policy = config.dataset.length_policy;
+    let (train, train_lengths) = train.filter_by_length(&tokenizer, max_tokens, policy);
+    let (val, val_lengths) = val.filter_by_length(&tokenizer, max_tokens, policy);
+    if !json {
+        for (split, report) in [("Train", &train_lengths), ("Val", &val_lengths)] {
+            println!("   {} split:", split);
+            report.print();
+        }
+    }
+
+    let device = Device::from_config(&config.device)?;
+    status!(json, "   Device: {}", device);
+    let mut model = match &base {
+        Some(base) => base.model.clone(),
+        None => CodeGenerationModel::from_model_config(tokenizer.vocab_size(), &config.model)
+            .with_seed(config.init_seed()),
+    };
+    status!(json, "   Parameters: {}", model.num_parameters());
+    device.check_memory("model", model.parameter_bytes())?;
+    let pretrained = config.model.pretrained.as_ref().filter(|_| base.is_none());
+    if let Some(pretrained) = pretrained {
+        let report = model.import_pretrained(pretrained, &tokenizer)?;
+        status!(
+            json,
+            "   Pretrained: {} of {} tensors from {} ({} cropped)",
+            report.loaded.len(),
+            report.loaded.len() + report.skipped.len(),
+            pretrained.path.display(),
+            report.cropped.len()
+        );
+        if let Some(matched) = report.matched_tokens {
+            status!(
+                json,
+                "   Pretrained vocabulary covers {} of {} tokens",
+                matched,
+                tokenizer.vocab_size()
+            );
+        }
+    }
+
+    let mut training = config.training.clone();
+    if let Some(epochs) = epochs {
+        training.num_epochs = epochs;
+    }
+    let run_name = config
+        .tracking
+        .run_name
+        .as_deref()
+        .unwrap_or(&config.task.name);
+    let run = RunManager::from_paths(&engine.paths).create(run_name, &config)?;
+    status!(json, "   Run: {}", run.dir.display());
+    let checkpoint_dir = run.checkpoints_dir();
+    let mut trainer = Trainer::new(training.clone())
+        .with_checkpoint_dir(&checkpoint_dir)
+        .with_progress(progress)
+        .with_device(device)
+        .with_cache_dir(&engine.paths.cache_path)
+        .with_cancellation(CancellationToken::on_ctrl_c()?);
+    if let Some(format) = training.metrics {
+        trainer = trainer.with_metrics(create_sink(format, &run.logs_dir())?);
+        status!(json, "   Metrics: {}", run.logs_dir().display());
+    }
+    if let Some(preview) = &training.preview {
+        trainer = trainer.with_callback(Box::new(
+            SamplePreview::new(preview.prompts.clone(), preview.every)
+                .with_generation(config.generation.clone(), Some(training.seed))
+                .with_output_dir(run.samples_dir()),
+        ));
+        status!(json, "   Samples: {}", run.samples_dir().display());
+    }
+    #[cfg(feature = "wandb")]
+    let wandb_run = match config.tracking.backend {
+        TrackingBackend::Wandb => {
+            let run = WandbRun::start(&config.tracking, &config.task.name, &config)?;
+            status!(json, "   W&B run: {}", run.url());
+            let run = Arc::new(Mutex::new(run));
+            trainer = trainer.with_metrics(Box::new(Arc::clone(&run)));
+            Some(run)
+        }
+        TrackingBackend::None => None,
+    };
+    #[cfg(not(feature = "wandb"))]
+    if config.tracking.backend == TrackingBackend::Wandb {
+        status!(
+            json,
+            "⚠️  W&B tracking needs a build with `--features wandb`; skipping"
+        );
+    }
+
+    let val = (!val.is_empty()).then_some(&val);
+    let results = trainer.train(&mut model, &tokenizer, &train, val)?;
+    run.write_results(&results)?;
+
+    let output = output.cloned().unwrap_or_else(|| run.model_path());
+    if let Some(parent) = output.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut metadata = CheckpointMetadata {
+        epochs: Some(results.epochs_completed),
+        epoch_step: results.epoch_step,
+        final_loss: Some(results.final_loss as f64),
+        dataset_hash: Some(train.content_hash()),
+        config_hash: Some(config.content_hash()?),
+        prompt_template: prompt_template.map(|template| template.as_str().to_string()),
+        ..CheckpointMetadata::provenance()
+    };
+    metadata
+        .metrics
+        .insert("best_loss".to_string(), results.best_loss as f64);
+    if let Some(last) = results.history.last() {
+        metadata
+            .metrics
+            .insert("train_loss".to_string(), last.train_loss);
+        if let Some(val_loss) = last.val_loss {
+            metadata.metrics.insert("val_loss".to_string(), val_loss);
+        }
+    }
+    let checkpoint = Checkpoint::new(model, tokenizer).with_metadata(metadata);
+    checkpoint.save(&output)?;
+
+    #[cfg(feature = "wandb")]
+    if let Some(run) = wandb_run {
+        let mut run = run.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        run.set_summary("best_loss", results.best_loss as f64);
+        upload_run_artifacts(&mut run, &checkpoint, &output, &checkpoint_dir, &test, json)?;
+        run.finish()?;
+    }
+
+    if json {
+        let mut summary = serde_json::to_value(&results)?;
+        summary["checkpoint"] = serde_json::json!(output);
+        summary["run"] = serde_json::json!(run.dir);
+        summary["metrics"] = serde_json::json!(checkpoint_dir.join("metrics.csv"));
+        return print_json(&summary);
+    }
+    println!(
+        "\n✅ Trained {} epoch(s){} in {:.1}s",
+        results.epochs_completed,
+        if results.interrupted {
+            " (interrupted)"
+        } else if results.stopped_early {
+            " (early stop)"
+        } else {
+            ""
+        },
+        results.training_time_secs
+    );
+    println!("   Final loss: {:.4}", results.final_loss);
+    println!("   Best loss: {:.4}", results.best_loss);
+    println!("💾 Saved to: {}", output.display());
+    if results.interrupted {
+        println!(
+            "⏹️  Interrupted checkpoint: {}",
+            checkpoint_dir.join("interrupted.ckpt").display()
+        );
+    }
+    println!(
+        "📈 Epoch metrics: {}",
+        checkpoint_dir.join("metrics.csv").display()
+    );
+
+    Ok(())
+}
+
+fn cross_validate(
+    config_path: &PathBuf,
+    epochs: Option<usize>,
+    folds: usize,
+    reporting: Reporting,
+) -> anyhow::Result<()> {
+    let Reporting { progress, json } = reporting;
+    status!(json, "🔁 Cross-validating with {} folds...", folds);
+
+    let mut config = Config::from_file(config_path)?;
+    let engine = EngineConfig::from_file_if_present("config/engine.toml")?.unwrap_or_default();
+    if let Some(epochs) = epochs {
+        config.training.num_epochs = epochs;
+    }
+    let dataset = WGSLDataset::from_file(&config.dataset.train_path)?;
+    status!(json, "   Examples: {}", dataset.len());
+
+    let report_dir = engine.paths.checkpoint_path.join(&config.task.name);
+    let report = CrossValidator::new(config, folds)
+        .with_progress(progress)
+        .with_cache_dir(&engine.paths.cache_path)
+        .run(&dataset)?;
+
+    if json {
+        print_json(&report)?;
+    } else {
+        println!();
+        report.print();
+    }
+    std::fs::create_dir_all(&report_dir)?;
+    let report_path = report_dir.join("cv_report.json");
+    report.write(&report_path)?;
+    status!(json, "\n💾 Report saved to: {}", report_path.display());
+
+    Ok(())
+}
+
+/// Upload the checkpoint, tokenizer, epoch metrics and an evaluation report on
+/// the test split to a W&B run
+#[cfg(feature = "wandb")]
+fn upload_run_artifacts(
+    run: &mut WandbRun,
+    checkpoint: &Checkpoint,
+    checkpoint_path: &std::path::Path,
+    checkpoint_dir: &std::path::Path,
+    test: &WGSLDataset,
+    json: bool,
+) -> anyhow::Result<()> {
+    let tokenizer_path = checkpoint_dir.join("tokenizer.json");
+    checkpoint.tokenizer.save(&tokenizer_path)?;
+    run.upload_file(checkpoint_path, "model.ckpt")?;
+    run.upload_file(&tokenizer_path, "tokenizer.json")?;
+    run.upload_file(&checkpoint_dir.join("metrics.csv"), "metrics.csv")?;
+
+    if !test.is_empty() {
+        let generator = WGSLGenerator::new(checkpoint.model.clone(), checkpoint.tokenizer.clone());
+        let report = Evaluator::default()
+            .evaluate(&generator, test)?
+            .with_perplexity(generator.model(), generator.tokenizer(), test)?;
+        let report_path = checkpoint_dir.join("eval_report.json");
+        report.write(&report_path)?;
+        run.upload_file(&report_path, "eval_report.json")?;
+        run.set_summary("eval/exact_match", report.metrics.exact_match_rate);
+        run.set_summary("eval/validity", report.metrics.validity_rate);
+    }
+    status!(json, "☁️  Uploaded artifacts to {}", run.url());
+    Ok(())
+}
+
+fn generate_wgsl(
+    model_path: &std::path::Path,
+    prompt: &str,
+    output: Option<&std::path::Path>,
+    generation: &GenerationConfig,
+    seed: Option<u64>,
+    device: &Device,
+    json: bool,
+) -> anyhow::Result<String> {
+    status!(json, "🎨 Generating WGSL code...");
+    status!(json, "Prompt: {}", prompt);
+
+    let registry = TemplateRegistry::builtin();
+    let mut template = None;
+    let mut fallback = None;
+    let mut confidence = None;
+    let wgsl_code = if model_path.is_file() || is_remote(model_path) {
+        let generator = load_backend(model_path, &generation.provider, device)?;
+        let validator = WGSLValidator::new();
+        let mut result = generator.generate_with_config(prompt, generation, seed, &validator)?;
+        status!(
+            json,
+            "Model: {} ({})",
+            model_path.display(),
+            if result.is_valid() {
+                "valid"
+            } else {
+                "invalid"
+            }
+        );
+        if let Some(index) = fallback_index(generation)? {
+            result = index.apply(prompt, result, &validator)?;
+        }
+        if let Some(used) = &result.fallback {
+            status!(
+                json,
+                "⚠️  No generation validated; using {} (\"{}\", similarity {:.2})",
+                used.source,
+                used.prompt,
+                used.similarity
+            );
+        }
+        if generation.confidence_comments {
+            let lines = generator
+                .tokenizer()
+                .map(|tokenizer| (result.line_confidence(tokenizer), tokenizer));
+            match lines {
+                Some((lines, tokenizer)) if !lines.is_empty() => {
+                    confidence = Some((lines, result.annotate_confidence(tokenizer)));
+                }
+                _ => status!(json, "⚠️  No token scores to annotate the code with"),
+            }
+        }
+        fallback = result.fallback;
+        result.code
+    } else {
+        // Without a checkpoint, fall back to the built-in templates
+        template = registry.find(prompt);
+        match template {
+            Some(template) => {
+                status!(json, "Template: {}", template.name);
+                format_wgsl_or_original(&template.render(&TemplateParams::default())?)
+            }
+            None => format!(
+                "// Generated WGSL for: {}\n// TODO: Train model to generate actual code\n",
+                prompt
+            ),
+        }
+    };
+
+    let (lines, shown) = match &confidence {
+        Some((lines, annotated)) => (Some(lines), annotated),
+        None => (None, &wgsl_code),
+    };
+    if let Some(output_path) = output {
+        std::fs::write(output_path, shown)?;
+        status!(json, "✅ Saved to: {}", output_path.display());
+    } else {
+        status!(json, "\n{}", shown);
+    }
+    if json {
+        print_json(&serde_json::json!({
+            "prompt": prompt,
+            "template": template.map(|template| template.name.as_str()),
+            "fallback": fallback,
+            "code": wgsl_code,
+            "confidence": lines,
+            "output": output,
+        }))?;
+    }
+
+    Ok(wgsl_code)
+}
+
+/// Write the attention maps of `model` reading `prompt` and writing `code`
+fn export_attention(
+    model_path: &std::path::Path,
+    prompt: &str,
+    code: &str,
+    output: &std::path::Path,
+    json: bool,
+) -> anyhow::Result<()> {
+    let generator = WGSLGenerator::from_checkpoint(model_path)?;
+    let report = generator.attention(prompt, code);
+    std::fs::write(output, serde_json::to_string(&report)?)?;
+    status!(
+        json,
+        "🔍 {} attention maps ({} heads, {} prompt × {} decoder tokens) saved to: {}",
+        report.maps.len(),
+        generator.model().nhead,
+        report.input_tokens.len(),
+        report.decoder_tokens.len(),
+        output.display()
+    );
+    Ok(())
+}
+
+fn generate_batch(
+    model_path: &std::path::Path,
+    prompts_path: &PathBuf,
+    output_dir: &PathBuf,
+    batch_size: usize,
+    args: &GenerationArgs,
+    json: bool,
+) -> anyhow::Result<()> {
+    if batch_size == 0 {
+        anyhow::bail!("--batch-size must be at least 1");
+    }
+    let (generation, device) = args.resolve()?;
+    let prompts: Vec<String> = std::fs::read_to_string(prompts_path)?
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(String::from)
+        .collect();
+    if prompts.is_empty() {
+        anyhow::bail!("{} has no prompts", prompts_path.display());
+    }
+
+    status!(
+        json,
+        "🎨 Generating {} shaders with {}",
+        prompts.len(),
+        model_path.display()
+    );
+    let generator = load_backend(model_path, &generation.provider, &device)?;
+    let fallbacks = fallback_index(&generation)?;
+    let validator = WGSLValidator::new();
+    let options = generation.options(args.seed);
+    let mut outputs = if generation.beam_width > 1 {
+        // Beam search is deterministic, so there are no per-prompt seeds
+        prompts
+            .iter()
+            .map(|prompt| {
+                Ok(generator
+                    .generate_with_config(prompt, &generation, None, &validator)?
+                    .code)
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?
+    } else {
+        let mut outputs = Vec::with_capacity(prompts.len());
+        for (index, batch) in prompts.chunks(batch_size).enumerate() {
+            // Keep prompt `i` on seed `seed + i` across batches
+            let options = GenerationOptions {
+                seed: options
+                    .seed
+                    .map(|seed| seed.wrapping_add((index * batch_size) as u64)),
+                ..options.clone()
+            };
+            outputs.extend(device.install(|| generator.generate_batch(batch, &options))?);
+        }
+        outputs
+    };
+
+    std::fs::create_dir_all(output_dir)?;
+    let mut valid = 0;
+    let mut files = Vec::with_capacity(prompts.len());
+    for (index, prompt) in prompts.iter().enumerate() {
+        let validation = validator.validate(&outputs[index])?;
+        let mut ok = validation.is_valid;
+        if let (false, Some(repair)) = (ok, generation.repair_options()) {
+            // Pick up after the batched attempt, with its error in the prompt
+            let options = GenerationOptions {
+                seed: options.seed.map(|seed| seed.wrapping_add(index as u64)),
+                ..options.clone()
+            };
+            let failed = GenerationResult::unscored(outputs[index].clone(), validation);
+            let result = generator.repair_from(prompt, failed, &options, &repair, &validator)?;
+            if let Some(code) = result.valid_code() {
+                status!(
+                    json,
+                    "  🔧 {} repaired after {} attempt(s)",
+                    prompt,
+                    result.attempts.len()
+                );
+                outputs[index] = code.to_string();
+                ok = true;
+            }
+        }
+        if let (false, Some(fallbacks)) = (ok, &fallbacks) {
+            if let Some((source, code, _)) = fallbacks.nearest(prompt, &validator)? {
+                status!(json, "  ↩️  {} falls back to {}", prompt, source);
+                outputs[index] = code.to_string();
+                ok = true;
+            }
+        }
+        let path = output_dir.join(format!("{:03}_{}.wgsl", index + 1, slug(prompt)));
+        std::fs::write(&path, &outputs[index])?;
+        valid += ok as usize;
+        status!(
+            json,
+            "  {} {} ← {}",
+            if ok { "✅" } else { "❌" },
+            path.display(),
+            prompt
+        );
+        files.push(serde_json::json!({ "prompt": prompt, "path": path, "valid": ok }));
+    }
+    if json {
+        return print_json(&serde_json::json!({
+            "outputs": files,
+            "valid": valid,
+            "total": outputs.len(),
+            "output_dir": output_dir,
+        }));
+    }
+    println!(
+        "\n📊 {}/{} valid, written to {}",
+        valid,
+        outputs.len(),
+        output_dir.display()
+    );
+    Ok(())
+}
+
+/// Lowercase alphanumeric words of `text` joined by `_`, for file names
+fn slug(text: &str) -> String {
+    let words: Vec<String> = text
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect();
+    let mut slug = words.join("_");
+    slug.truncate(40);
+    slug.trim_end_matches('_').to_string()
+}
+
+/// Shaders `generation.fallback` returns: the valid examples of
+/// `generation.fallback_dataset` first, then the built-in templates
+fn fallback_index(generation: &GenerationConfig) -> anyhow::Result<Option<FallbackIndex>> {
+    if !generation.fallback {
+        return Ok(None);
+    }
+    let mut index = FallbackIndex::default();
+    if let Some(path) = &generation.fallback_dataset {
+        index = index.with_dataset(&WGSLDataset::from_file(path)?);
+    }
+    Ok(Some(index.with_templates(&TemplateRegistry::builtin())))
+}
+
+/// Whether `model` names a remote model rather than a checkpoint
+fn is_remote(model: &std::path::Path) -> bool {
+    model
+        .to_str()
+        .is_some_and(|name| name.starts_with(REMOTE_PREFIX))
+}
+
+/// Generator for a `--model` argument: a remote model for `openai:<model>`,
+/// else the checkpoint at that path
+fn load_backend(
+    model: &std::path::Path,
+    provider: &ProviderConfig,
+    device: &Device,
+) -> anyhow::Result<Box<dyn GeneratorBackend>> {
+    #[cfg(feature = "openai")]
+    if let Some(backend) = OpenAiBackend::from_model_name(&model.to_string_lossy(), provider) {
+        return Ok(Box::new(backend));
+    }
+    #[cfg(not(feature = "openai"))]
+    if is_remote(model) {
+        let _ = provider;
+        anyhow::bail!(
+            "{} is a remote model; rebuild with `--features openai`",
+            model.display()
+        );
+    }
+    let generator = WGSLGenerator::from_checkpoint(model)?;
+    device.check_memory("model", generator.model().parameter_bytes())?;
+    Ok(Box::new(generator))
+}
+
+fn evaluate_model(
+    model_path: &PathBuf,
+    dataset: Option<&PathBuf>,
+    config: Option<&PathBuf>,
+    output: Option<&PathBuf>,
+    with_perplexity: bool,
+    sampling: Option<(usize, GenerationOptions)>,
+    reporting: Reporting,
+) -> anyhow::Result<()> {
+    let Reporting { progress, json } = reporting;
+    status!(json, "🧪 Evaluating model: {}", model_path.display());
+
+    let config = config.map(Config::from_file).transpose()?;
+    let dataset = match (dataset, &config) {
+        (Some(path), _) => WGSLDataset::from_file(path)?,
+        (None, Some(config)) => match &config.dataset.test_path {
+            Some(path) => WGSLDataset::from_file(path)?,
+            None => {
+                let full = WGSLDataset::from_file(&config.dataset.train_path)?;
+                let (_, _, test) = full.split(config.dataset.train_ratio, config.dataset.val_ratio);
+                test
+            }
+        },
+        (None, None) => anyhow::bail!("either --dataset or --config is required"),
+    };
+    if dataset.is_empty() {
+        anyhow::bail!("evaluation dataset is empty");
+    }
+    status!(json, "   Examples: {}", dataset.len());
+
+    let profile = match &config {
+        Some(config) => ValidationProfile::from_config(&config.validation)?,
+        None => ValidationProfile::default(),
+    };
+    let validator = WGSLValidator::new().with_profile(profile);
+    let evaluator = Evaluator::new(validator.clone()).with_progress(progress);
+    let (generator, dataset, mut report): (Box<dyn GeneratorBackend>, _, _) =
+        if is_remote(model_path) {
+            if with_perplexity {
+                anyhow::bail!(
+                    "--perplexity needs a checkpoint; remote models have no token probabilities"
+                );
+            }
+            let provider = config
+                .as_ref()
+                .map(|config| config.generation.provider.clone())
+                .unwrap_or_default();
+            let generator = load_backend(model_path, &provider, &Device::cpu())?;
+            let report = evaluator.evaluate(generator.as_ref(), &dataset)?;
+            (generator, dataset, report)
+        } else {
+            let generator = WGSLGenerator::from_checkpoint(model_path)?;
+            // Format prompts from each example's own stage and bindings, as in training
+            let (generator, dataset) = match generator.prompt_template().cloned() {
+                Some(template) => (
+                    generator.with_prompt_template(None),
+                    template.format_dataset(&dataset),
+                ),
+                None => (generator, dataset),
+            };
+            let dataset = with_stage_tokens(&dataset, generator.tokenizer());
+            let mut report = evaluator.evaluate(&generator, &dataset)?;
+            if with_perplexity {
+                report =
+                    report.with_perplexity(generator.model(), generator.tokenizer(), &dataset)?;
+            }
+            (Box::new(generator), dataset, report)
+        };
+    if let Some((samples, mut options)) = sampling {
+        if options.seed.is_none() {
+            options.seed = config.as_ref().map(|config| config.training.seed);
+        }
+        status!(json, "   Sampling {} generation(s) per prompt", samples);
+        let pass_at_k = PassAtK::new(samples)
+            .with_validator(validator)
+            .with_progress(progress);
+        report =
+            report.with_pass_at_k(pass_at_k.evaluate(generator.as_ref(), &dataset, &options)?);
+    }
+
+    if json {
+        print_json(&report)?;
+    } else {
+        println!();
+        report.print();
+    }
+
+    if let Some(output_path) = output {
+        report.write(output_path)?;
+        status!(json, "\n📄 Report written to {}", output_path.display());
+    }
+
+    Ok(())
+}
+
+/// Backend for `model` on the device and provider of `config`
+fn regression_backend(
+    model: &std::path::Path,
+    config: Option<&PathBuf>,
+) -> anyhow::Result<Box<dyn GeneratorBackend>> {
+    let (provider, device) = match config {
+        Some(path) => {
+            let config = Config::from_file(path)?;
+            (
+                config.generation.provider,
+                Device::from_config(&config.device)?,
+            )
+        }
+        None => (ProviderConfig::default(), Device::cpu()),
+    };
+    load_backend(model, &provider, &device)
+}
+
+fn record_golden(
+    model: &std::path::Path,
+    golden: &std::path::Path,
+    prompts: Option<&PathBuf>,
+    config: Option<&PathBuf>,
+    json: bool,
+) -> anyhow::Result<()> {
+    let mut suite = if golden.exists() {
+        GoldenSuite::from_file(golden)?
+    } else {
+        GoldenSuite::default()
+    };
+    if let Some(path) = prompts {
+        let lines = std::fs::read_to_string(path)?;
+        let added = suite.add_prompts(
+            lines
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty())
+                .map(str::to_string),
+        );
+        status!(json, "➕ Added {} prompt(s) from {}", added, path.display());
+    }
+    if suite.cases.is_empty() {
+        anyhow::bail!(
+            "{} has no prompts; add some with --prompts",
+            golden.display()
+        );
+    }
+
+    status!(
+        json,
+        "📼 Recording {} golden generation(s) with {}",
+        suite.cases.len(),
+        model.display()
+    );
+    let backend = regression_backend(model, config)?;
+    let changed = suite.record(backend.as_ref())?;
+    suite.save(golden)?;
+    if json {
+        return print_json(&serde_json::json!({
+            "golden": golden,
+            "prompts": suite.cases.len(),
+            "changed": changed,
+        }));
+    }
+    println!(
+        "✅ {} golden generation(s) changed, saved to {}",
+        changed,
+        golden.display()
+    );
+    Ok(())
+}
+
+fn regress_model(
+    model: &std::path::Path,
+    golden: &std::path::Path,
+    threshold: Option<f64>,
+    config: Option<&PathBuf>,
+    json: bool,
+) -> anyhow::Result<()> {
+    use std::io::IsTerminal;
+
+    let suite = GoldenSuite::from_file(golden)?;
+    let threshold = threshold.unwrap_or(suite.threshold);
+    status!(
+        json,
+        "🔁 Checking {} against {} golden generation(s) in {}",
+        model.display(),
+        suite.cases.len(),
+        golden.display()
+    );
+    let backend = regression_backend(model, config)?;
+    let report = suite.check(backend.as_ref(), threshold)?;
+
+    if json {
+        print_json(&report)?;
+    } else {
+        let color = std::io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none();
+        for case in report.drifted() {
+            let diff = diff_wgsl(
+                &format_wgsl_or_original(&case.golden),
+                &format_wgsl_or_original(&case.generated),
+            );
+            println!("\n--- golden: {}\n+++ {}", case.prompt, model.display());
+            print!("{}", diff.render(color));
+        }
+        println!();
+        report.print();
+    }
+
+    if !report.passed() {
+        status!(
+            json,
+            "\n❌ {} prompt(s) drifted from their golden output",
+            report.drifted().count()
+        );
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+fn validate_wgsl(
+    file: &PathBuf,
+    profile: Option<&str>,
+    config: Option<&PathBuf>,
+    glob: Option<&str>,
+    report: Option<&PathBuf>,
+    watch: bool,
+    json: bool,
+) -> anyhow::Result<()> {
+    status!(json, "🔍 Validating WGSL: {}", file.display());
+
+    if !file.is_dir() && (glob.is_some() || report.is_some() || watch) {
+        anyhow::bail!("--glob, --report and --watch can only be used when validating a directory");
+    }
+    if watch && report.is_some() {
+        anyhow::bail!("--report can't be combined with --watch");
+    }
+
+    let profile = match (profile, config) {
+        (Some(name), _) => name.parse::<ValidationProfile>()?,
+        (None, Some(config_path)) => {
+            ValidationProfile::from_config(&Config::from_file(config_path)?.validation)?
+        }
+        (None, None) => ValidationProfile::default(),
+    };
+    status!(json, "   Profile: {}", profile.name());
+
+    let validator = WGSLValidator::new().with_profile(profile);
+
+    if watch {
+        return watch_directory(file, glob, &validator, json);
+    }
+    if file.is_dir() {
+        let batch = validate_directory(file, glob, &validator)?;
+        status!(json, "   Files: {}\n", batch.files.len());
+        if json {
+            print_json(&batch)?;
+        } else {
+            batch.print();
+        }
+
+        if let Some(report_path) = report {
+            batch.to_json_file(report_path)?;
+            status!(json, "\n📄 Report written to {}", report_path.display());
+        }
+
+        if batch.failed() > 0 {
+            status!(
+                json,
+                "\n❌ {} of {} file(s) failed",
+                batch.failed(),
+                batch.files.len()
+            );
+            std::process::exit(1);
+        }
+        status!(json, "\n✅ All {} file(s) are valid", batch.files.len());
+        return Ok(());
+    }
+
+    let result = validator.validate_file(file)?;
+
+    if json {
+        let mut report = serde_json::to_value(&result)?;
+        report["file"] = serde_json::json!(file);
+        print_json(&report)?;
+    } else {
+        result.print();
+    }
+
+    if !result.is_valid {
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
+
+/// How often `validate --watch` checks for changes
+const WATCH_INTERVAL: std::time::Duration = std::time::Duration::from_millis(500);
+
+fn watch_directory(
+    dir: &PathBuf,
+    glob: Option<&str>,
+    validator: &WGSLValidator,
+    json: bool,
+) -> anyhow::Result<()> {
+    let mut watcher = DirectoryWatcher::new(dir, glob)?;
+    status!(json, "👀 Watching for changes (Ctrl+C to stop)\n");
+    loop {
+        let changes = watcher.poll()?;
+        for relative in &changes.changed {
+            let path = dir.join(relative);
+            let Ok(code) = std::fs::read_to_string(&path) else {
+                // Removed or unreadable mid-save; the next poll catches up
+                continue;
+            };
+            let result = validator.validate(&code)?;
+            if json {
+                // One object per line, so consumers can read events as they come
+                let mut event = serde_json::to_value(&result)?;
+                event["event"] = serde_json::json!("changed");
+                event["file"] = serde_json::json!(relative);
+                println!("{}", event);
+                continue;
+            }
+            if result.is_valid {
+                println!("✅ {}", relative.display());
+            } else {
+                println!("❌ {}", relative.display());
+                for error in &result.errors {
+                    println!("  - {}", error);
+                }
+            }
+            for warning in &result.warnings {
+                println!("  ⚠️  {}", warning);
+            }
+        }
+        for relative in &changes.removed {
+            if json {
+                println!(
+                    "{}",
+                    serde_json::json!({ "event": "removed", "file": relative })
+                );
+            } else {
+                println!("🗑️  {} removed", relative.display());
+            }
+        }
+        std::thread::sleep(WATCH_INTERVAL);
+    }
+}
+
+fn lint_files(files: &[PathBuf], config: Option<&PathBuf>) -> anyhow::Result<()> {
+    let lint = match config {
+        Some(config_path) => Config::from_file(config_path)?.lint,
+        None => LintConfig::default(),
+    };
+    let validator = WGSLValidator::new().with_lint_config(lint);
+    let (mut warnings, mut errors) = (0, 0);
+
+    for file in files {
+        let code = std::fs::read_to_string(file)?;
+        let diagnostics = match validator.lint(&code) {
+            Ok(diagnostics) => diagnostics,
+            Err(e) => {
+                println!("❌ {}: {}", file.display(), e);
+                errors += 1;
+                continue;
+            }
+        };
+
+        for diagnostic in &diagnostics {
+            let icon = match diagnostic.level {
+                LintLevel::Deny => {
+                    errors += 1;
+                    "❌"
+                }
+                _ => {
+                    warnings += 1;
+                    "⚠️ "
+                }
+            };
+            println!(
+                "{} {}:{}: [{}] {}",
+                icon,
+                file.display(),
+                diagnostic.line,
+                diagnostic.rule,
+                diagnostic.message
+            );
+        }
+    }
+
+    if errors > 0 {
+        println!("❌ {} error(s), {} warning(s)", errors, warnings);
+        std::process::exit(1);
+    }
+
+    if warnings > 0 {
+        println!("⚠️  {} warning(s) in {} file(s)", warnings, files.len());
+    } else {
+        println!("✅ {} file(s) lint clean", files.len());
+    }
+
+    Ok(())
+}
+
+fn format_files(files: &[PathBuf], check: bool) -> anyhow::Result<()> {
+    let mut unformatted = 0;
+
+    for file in files {
+        let code = std::fs::read_to_string(file)?;
+        let formatted = match format_wgsl(&code) {
+            Ok(formatted) => formatted,
+            Err(e) => {
+                println!("❌ {}: {}", file.display(), e);
+                unformatted += 1;
+                continue;
+            }
+        };
+
+        if formatted == code {
+            continue;
+        }
+
+        if check {
+            println!("⚠️  {} is not formatted", file.display());
+            unformatted += 1;
+        } else {
+            std::fs::write(file, formatted)?;
+            println!("✨ Formatted {}", file.display());
+        }
+    }
+
+    if unformatted > 0 {
+        std::process::exit(1);
+    }
+
+    println!("✅ {} file(s) checked", files.len());
+
+    Ok(())
+}
+
+/// The two labelled shaders `diff` compares: the `files`, then the shader
+/// of each model for `prompt`
+fn diff_sides(
+    files: &[PathBuf],
+    models: &[PathBuf],
+    prompt: Option<&str>,
+    generation: &GenerationArgs,
+) -> anyhow::Result<[(String, String); 2]> {
+    let mut sides = Vec::new();
+    for file in files {
+        sides.push((file.display().to_string(), std::fs::read_to_string(file)?));
+    }
+    if let Some(prompt) = prompt {
+        let (config, device) = generation.resolve()?;
+        let validator = WGSLValidator::new();
+        for model in models {
+            let backend = load_backend(model, &config.provider, &device)?;
+            let result =
+                backend.generate_with_config(prompt, &config, generation.seed, &validator)?;
+            sides.push((model.display().to_string(), result.code));
+        }
+    }
+    sides.try_into().map_err(|sides: Vec<_>| {
+        anyhow::anyhow!(
+            "diff compares two shaders but got {}: pass two files, a file and a --model, \
+             or two --model",
+            sides.len()
+        )
+    })
+}
+
+fn show_diff(sides: [(String, String); 2], color: bool, json: bool) -> anyhow::Result<()> {
+    use std::io::IsTerminal;
+
+    let [(old_label, old), (new_label, new)] = sides;
+    // Layout differences are noise when triaging
+    let diff = diff_wgsl(
+        &format_wgsl_or_original(&old),
+        &format_wgsl_or_original(&new),
+    );
+    if json {
+        return print_json(&serde_json::json!({
+            "old": old_label,
+            "new": new_label,
+            "identical": diff.is_identical(),
+            "deleted": diff.deleted(),
+            "inserted": diff.inserted(),
+            "lines": diff.lines,
+        }));
+    }
+
+    let color = color && std::io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none();
+    println!("--- {}", old_label);
+    println!("+++ {}", new_label);
+    print!("{}", diff.render(color));
+    if diff.is_identical() {
+        println!("\n✅ Identical");
+    } else {
+        println!(
+            "\n📊 {} line(s) removed, {} added",
+            diff.deleted(),
+            diff.inserted()
+        );
+    }
+    Ok(())
+}
+
+fn convert_wgsl(
+    file: &PathBuf,
+    target: &str,
+    entry_point: Option<&str>,
+    output: Option<&PathBuf>,
+) -> anyhow::Result<()> {
+    let target: ShaderTarget = target.parse()?;
+    println!("🔄 Converting {} to {}", file.display(), target);
+
+    let code = std::fs::read_to_string(file)?;
+    let shader = WGSLTranspiler::new().transpile(&code, target, entry_point)?;
+
+    let output_path = output
+        .cloned()
+        .unwrap_or_else(|| file.with_extension(target.extension()));
+    shader.write_to(&output_path)?;
+
+    println!("✅ Saved to: {}", output_path.display());
+
+    Ok(())
+}
+
+fn init_config(output: &PathBuf, preset: Option<&str>) -> anyhow::Result<()> {
+    let config = match preset {
+        Some(name) => {
+            println!("📝 Creating '{}' configuration...", name);
+            Config::preset(name)?
+        }
+        None => {
+            println!("📝 Creating default configuration...");
+            Config::default_wgsl_generation()
+        }
+    };
+    println!(
+        "   Model: {} layers × {}-d, {} heads",
+        config.model.num_layers, config.model.d_model, config.model.nhead
+    );
+
+    // Ensure parent directory exists
+    if let Some(parent) = output.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    config.to_file(output)?;
+
+    println!("✅ Configuration created: {}", output.display());
+    println!("\n💡 Next steps:");
+    println!("  1. Edit the configuration file as needed");
+    println!("  2. Prepare training data at: config/wgsl_training_data.toml");
+    println!("  3. Run: tiny-agent-trainer train --config {}", output.display());
+
+    Ok(())
+}
+
+fn merge_datasets(inputs: &[PathBuf], output: &PathBuf) -> anyhow::Result<()> {
+    println!("🔀 Merging {} datasets...", inputs.len());
+
+    let mut merged = WGSLDataset::new();
+    let mut total = 0;
+    for input in inputs {
+        let dataset = WGSLDataset::from_file(input)?;
+        println!("  📄 {}: {} examples", input.display(), dataset.len());
+        total += dataset.len();
+        merged = merged.merge(&dataset);
+    }
+
+    merged.to_file(output)?;
+
+    println!(
+        "✅ Merged {} examples ({} duplicates removed)",
+        merged.len(),
+        total - merged.len()
+    );
+    println!("   Saved to: {}", output.display());
+
+    Ok(())
+}
+
+fn export_weights(model_path: &PathBuf, output: &PathBuf) -> anyhow::Result<()> {
+    let checkpoint = Checkpoint::load(model_path)?;
+    checkpoint.model.save_safetensors(output)?;
+
+    println!(
+        "✅ Exported {} tensors ({} parameters)",
+        checkpoint.model.parameter_shapes().len(),
+        checkpoint.model.num_parameters()
+    );
+    println!("   Saved to: {}", output.display());
+    Ok(())
+}
+
+fn import_weights(
+    model_path: &PathBuf,
+    weights: &PathBuf,
+    output: Option<&PathBuf>,
+) -> anyhow::Result<()> {
+    let mut checkpoint = Checkpoint::load(model_path)?;
+    checkpoint.model.load_safetensors(weights)?;
+
+    let output = output.unwrap_or(model_path);
+    checkpoint.save(output)?;
+    println!("✅ Imported weights from {}", weights.display());
+    println!("   Saved to: {}", output.display());
+    Ok(())
+}
+
+fn quantize_weights(model_path: &PathBuf, output: &PathBuf) -> anyhow::Result<()> {
+    let checkpoint = Checkpoint::load(model_path)?;
+    let quantized = checkpoint.model.quantize();
+    let max_error = quantized.max_error(&checkpoint.model);
+    QuantizedCheckpoint::new(quantized, checkpoint.tokenizer)
+        .with_metadata(checkpoint.metadata)
+        .save(output)?;
+
+    let original_size = std::fs::metadata(model_path)?.len();
+    let quantized_size = std::fs::metadata(output)?.len();
+    println!("✅ Quantized weights to int8");
+    println!(
+        "   Size: {:.1} KB -> {:.1} KB ({:.1}x smaller)",
+        original_size as f64 / 1024.0,
+        quantized_size as f64 / 1024.0,
+        original_size as f64 / quantized_size.max(1) as f64
+    );
+    println!("   Max weight error: {:.6}", max_error);
+    println!("   Saved to: {}", output.display());
+    Ok(())
+}
+
+fn list_templates() -> anyhow::Result<()> {
+    let registry = TemplateRegistry::builtin();
+    println!("📐 {} built-in templates\n", registry.templates().len());
+    for template in registry.templates() {
+        let slots: Vec<String> = template.slots.iter().map(ToString::to_string).collect();
+        println!("  {:<14} {}", template.name, template.description);
+        if !slots.is_empty() {
+            println!("  {:<14} params: {}", "", slots.join(", "));
+        }
+        println!("  {:<14} keywords: {}", "", template.keywords.join(", "));
+    }
+    Ok(())
+}
+
+fn render_template(
+    name: &str,
+    workgroup_size: Option<&str>,
+    scalar_type: Option<&str>,
+    channels: Option<u32>,
+    out: Option<&PathBuf>,
+) -> anyhow::Result<()> {
+    let mut params = TemplateParams::default();
+    if let Some(size) = workgroup_size {
+        params.workgroup_size = TemplateParams::parse_workgroup_size(size)?;
+    }
+    if let Some(scalar_type) = scalar_type {
+        params.scalar_type = scalar_type.parse()?;
+    }
+    if let Some(channels) = channels {
+        params.channels = channels;
+    }
+    let code = format_wgsl_or_original(&TemplateRegistry::builtin().render(name, &params)?);
+
+    match out {
+        Some(path) => {
+            std::fs::write(path, &code)?;
+            println!("✅ Rendered '{}' to: {}", name, path.display());
+        }
+        None => print!("{}", code),
+    }
+    Ok(())
+}
+
+fn tokenize_file(
+    file: &PathBuf,
+    tokenizer_path: Option<&PathBuf>,
+    model_path: Option<&PathBuf>,
+    strict: bool,
+) -> anyhow::Result<()> {
+    let text = std::fs::read_to_string(file)?;
+    let (tokenizer, max_seq_len) = match (tokenizer_path, model_path) {
+        (Some(path), _) => (WGSLTokenizer::load(path)?, None),
+        (None, Some(path)) => {
+            let checkpoint = Checkpoint::load(path)?;
+            let max_seq_len = checkpoint.model.max_seq_len;
+            (checkpoint.tokenizer, Some(max_seq_len))
+        }
+        (None, None) => {
+            println!("⚠️  No --tokenizer or --model: every token is out of vocabulary\n");
+            (WGSLTokenizer::new(512, false), None)
+        }
+    };
+
+    println!(
+        "🔤 Tokenizing {} ({} token vocabulary)\n",
+        file.display(),
+        tokenizer.vocab_size()
+    );
+    let tokenized = tokenizer.tokenize_detailed(&text);
+    println!("{:>5}  {:>6}  {:<11}  Token", "#", "Id", "Category");
+    for (index, ((token, id), category)) in tokenized
+        .tokens
+        .iter()
+        .zip(&tokenized.ids)
+        .zip(&tokenized.categories)
+        .enumerate()
+    {
+        let marker = if tokenizer.vocab.contains_key(token) {
+            ""
+        } else {
+            "  ❓"
+        };
+        println!(
+            "{:>5}  {:>6}  {:<11}  {}{}",
+            index,
+            id,
+            category.as_str(),
+            token,
+            marker
+        );
+    }
+
+    let length = tokenized.tokens.len();
+    println!("\n📏 Length: {} tokens", length);
+    let mut limits = vec![("tokenizer max_length", tokenizer.max_length)];
+    // The decoder also needs room for the start-of-sequence token
+    if let Some(max_seq_len) = max_seq_len {
+        limits.push(("model max_seq_len - 1", max_seq_len.saturating_sub(1)));
+    }
+    for (name, limit) in limits {
+        if length <= limit {
+            println!("   ✅ fits {} ({})", name, limit);
+        } else {
+            println!(
+                "   ⚠️  exceeds {} ({}): {} tokens would be cut",
+                name,
+                limit,
+                length - limit
+            );
+        }
+    }
+    println!(
+        "📊 Vocabulary coverage: {:.1}%",
+        tokenized.coverage() * 100.0
+    );
+    if !tokenized.unknown.is_empty() {
+        println!(
+            "❓ {} distinct out-of-vocabulary token(s): {}",
+            tokenized.unknown.len(),
+            tokenized.unknown.join(" ")
+        );
+    }
+    if strict {
+        tokenized.require_known()?;
+    }
+    Ok(())
+}
+
+fn embed_prompts(
+    model_path: &PathBuf,
+    prompts: &[String],
+    dataset_path: Option<&PathBuf>,
+    output: Option<&PathBuf>,
+    query: Option<&str>,
+    k: usize,
+) -> anyhow::Result<()> {
+    let generator = WGSLGenerator::from_checkpoint(model_path)?;
+    let dataset = dataset_path.map(WGSLDataset::from_file).transpose()?;
+
+    if let (Some(query), Some(dataset)) = (query, &dataset) {
+        println!(
+            "🔎 Examples of {} most similar to: {}\n",
+            dataset_path.unwrap().display(),
+            query
+        );
+        for (index, similarity) in generator.nearest_examples(query, dataset, k) {
+            println!(
+                "  {:.3}  #{:<5} {}",
+                similarity,
+                index + 1,
+                dataset.examples[index].natural_language
+            );
+        }
+        return Ok(());
+    }
+
+    let mut texts: Vec<&str> = prompts.iter().map(String::as_str).collect();
+    if let Some(dataset) = &dataset {
+        texts.extend(
+            dataset
+                .examples
+                .iter()
+                .map(|example| example.natural_language.as_str()),
+        );
+    }
+    if texts.is_empty() {
+        anyhow::bail!("Nothing to embed: pass --prompt or --dataset");
+    }
+    let mut lines = String::new();
+    for (prompt, embedding) in texts.iter().zip(generator.embed_batch(&texts)) {
+        let line = serde_json::json!({ "prompt": prompt, "embedding": embedding });
+        lines.push_str(&format!("{}\n", line));
+    }
+    match output {
+        Some(path) => {
+            std::fs::write(path, lines)?;
+            println!(
+                "✅ {} embeddings of size {} written to {}",
+                texts.len(),
+                generator.model().d_model,
+                path.display()
+            );
+        }
+        None => print!("{}", lines),
+    }
+    Ok(())
+}
+
+const REPL_HELP: &str = "\
+Type a prompt to generate a shader, or a command:
+  /temp <t>       sampling temperature (0 = greedy)
+  /topk <k>       sample among the k most likely tokens (0 = no limit)
+  /seed <n|none>  sampling seed
+  /options        show the generation options
+  /validate       validate the last shader again
+  /save <file>    write the last shader to a file
+  /help           show this help
+  /quit           leave";
+
+fn run_repl(model_path: &PathBuf) -> anyhow::Result<()> {
+    use std::io::{BufRead, Write};
+
+    println!("📦 Loading model: {}", model_path.display());
+    let generator = WGSLGenerator::from_checkpoint(model_path)?;
+    let validator = WGSLValidator::new();
+    let mut options = GenerationOptions::default();
+    let mut last: Option<String> = None;
+    println!("💬 {}\n", REPL_HELP);
+
+    let mut lines = std::io::stdin().lock().lines();
+    loop {
+        print!("wgsl> ");
+        std::io::stdout().flush()?;
+        let Some(line) = lines.next().transpose()? else {
+            break;
+        };
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let Some(command) = line.strip_prefix('/') else {
+            let result = match generator.generate_result(line, &options, &validator) {
+                Ok(result) => result,
+                Err(e) => {
+                    println!("❌ {}", e);
+                    continue;
+                }
+            };
+            println!("\n{}\n", result.code);
+            result.validation.print();
+            println!(
+                "⏱️  {} tokens in {:.0?}, mean log probability {:.3}",
+                result.tokens.len(),
+                result.elapsed,
+                result.mean_log_prob()
+            );
+            last = Some(result.code);
+            continue;
+        };
+        let (name, arg) = command
+            .split_once(char::is_whitespace)
+            .map_or((command, ""), |(name, arg)| (name, arg.trim()));
+        match name {
+            "quit" | "exit" | "q" => break,
+            "help" => println!("{}", REPL_HELP),
+            "temp" | "temperature" => match arg.parse() {
+                Ok(temperature) => options.temperature = temperature,
+                Err(_) => println!("❌ /temp needs a number"),
+            },
+            "topk" | "top_k" => match arg.parse() {
+                Ok(top_k) => options.top_k = top_k,
+                Err(_) => println!("❌ /topk needs a whole number"),
+            },
+            "seed" => match arg {
+                "none" => options.seed = None,
+                _ => match arg.parse() {
+                    Ok(seed) => options.seed = Some(seed),
+                    Err(_) => println!("❌ /seed needs a whole number or 'none'"),
+                },
+            },
+            "options" => println!(
+                "⚙️  temperature {}, top_k {}, seed {}",
+                options.temperature,
+                options.top_k,
+                options
+                    .seed
+                    .map_or("none".to_string(), |seed| seed.to_string())
+            ),
+            "validate" => match &last {
+                Some(code) => match validator.validate(code) {
+                    Ok(result) => result.print(),
+                    Err(e) => println!("❌ {}", e),
+                },
+                None => println!("❌ Nothing generated yet"),
+            },
+            "save" => match (&last, arg) {
+                (None, _) => println!("❌ Nothing generated yet"),
+                (Some(_), "") => println!("❌ /save needs a file name"),
+                (Some(code), path) => match std::fs::write(path, code) {
+                    Ok(()) => println!("✅ Saved to: {}", path),
+                    Err(e) => println!("❌ Could not save to {}: {}", path, e),
+                },
+            },
+            _ => println!("❌ Unknown command /{} (try /help)", name),
+        }
+    }
+    println!("👋 Bye");
+    Ok(())
+}
+
+fn inspect_model(model_path: &PathBuf) -> anyhow::Result<()> {
+    let checkpoint = Checkpoint::load(model_path)?;
+    let model = &checkpoint.model;
+
+    println!("🔍 Checkpoint: {}", model_path.display());
+    println!("{}", "=".repeat(50));
+    println!("\n📊 Model:");
+    println!("  Architecture: {:?}", model.architecture);
+    println!("  Vocabulary: {}", model.vocab_size);
+    println!("  d_model: {}", model.d_model);
+    println!("  Attention heads: {}", model.nhead);
+    println!("  Layers: {}", model.num_layers);
+    println!("  Feed-forward: {}", model.dim_feedforward);
+    println!("  Activation: {:?}", model.activation);
+    println!("  Norm placement: {:?}", model.norm_placement);
+    println!("  Positional encoding: {:?}", model.positional_encoding);
+    println!("  Attention: {}", model.attention);
+    println!("  Embedding storage: {:?}", model.embedding_dtype);
+    println!("  Initialization: {:?}", model.init);
+    println!("  Max sequence length: {}", model.max_seq_len);
+    println!("  Parameters: {}", model.num_parameters());
+
+    println!("\n🔤 Tokenizer:");
+    println!("  Vocabulary: {}", checkpoint.tokenizer.vocab_size());
+    println!("  Max length: {}", checkpoint.tokenizer.max_length);
+    println!("  Lowercase: {}", checkpoint.tokenizer.lowercase);
+    println!("  Vocabulary hash: {}", checkpoint.tokenizer.vocab_hash());
+    if let Err(e) = checkpoint.check_tokenizer() {
+        println!("  ❌ {}", e);
+    }
+    for warning in checkpoint.compatibility_warnings() {
+        println!("  ⚠️  {}", warning);
+    }
+
+    println!("\n🎯 Training:");
+    let metadata = &checkpoint.metadata;
+    // The tokenizer fields are filled in for every checkpoint, trained or not
+    let untrained = CheckpointMetadata {
+        tokenizer_version: metadata.tokenizer_version,
+        tokenizer_hash: metadata.tokenizer_hash.clone(),
+        ..CheckpointMetadata::default()
+    };
+    if metadata == &untrained {
+        println!("  No training metadata recorded");
+    } else {
+        let unknown = || "unknown".to_string();
+        println!(
+            "  Epochs: {}{}",
+            metadata
+                .epochs
+                .map(|e| e.to_string())
+                .unwrap_or_else(unknown),
+            metadata
+                .epoch_step
+                .map(|step| format!(" and {} step(s) of the next", step))
+                .unwrap_or_default()
+        );
+        println!(
+            "  Final loss: {}",
+            metadata
+                .final_loss
+                .map(|loss| format!("{:.4}", loss))
+                .unwrap_or_else(unknown)
+        );
+        println!(
+            "  Dataset hash: {}",
+            metadata.dataset_hash.clone().unwrap_or_else(unknown)
+        );
+        println!(
+            "  Config hash: {}",
+            metadata.config_hash.clone().unwrap_or_else(unknown)
+        );
+        for (name, value) in &metadata.metrics {
+            println!("  {}: {:.4}", name, value);
+        }
+
+        println!("\n🏷️  Provenance:");
+        println!(
+            "  Created: {}",
+            metadata.created_at.clone().unwrap_or_else(unknown)
+        );
+        println!(
+            "  tiny-agent-trainer: {}",
+            metadata.crate_version.clone().unwrap_or_else(unknown)
+        );
+        println!(
+            "  Git commit: {}",
+            metadata.git_commit.clone().unwrap_or_else(unknown)
+        );
+    }
+
+    let stats = model.weight_stats();
+    println!("\n📈 Weights:");
+    println!("  Min: {:.6}", stats.min);
+    println!("  Max: {:.6}", stats.max);
+    println!("  Mean: {:.6}", stats.mean);
+    println!("  NaN: {}", stats.nan_count);
+    println!("  Inf: {}", stats.inf_count);
+    if !stats.is_finite() {
+        println!("  ❌ Non-finite values in: {}", stats.non_finite.join(", "));
+    }
+
+    println!("\n🧱 Parameters:");
+    println!("{}", model.summary());
+    Ok(())
+}
+
+fn dataset_stats(path: &PathBuf, json: bool) -> anyhow::Result<()> {
+    let dataset = WGSLDataset::from_file(path)?;
+    let stats = dataset.stats(
+        &WGSLTokenizer::new(usize::MAX, false),
+        &WGSLValidator::new(),
+    )?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&stats)?);
+    } else {
+        println!("📂 {}\n", path.display());
+        stats.print();
+    }
+    Ok(())
+}
+
+fn round_trip_dataset(
+    path: &PathBuf,
+    tokenizer_path: Option<&PathBuf>,
+    model_path: Option<&PathBuf>,
+) -> anyhow::Result<()> {
+    let dataset = WGSLDataset::from_file(path)?;
+    let tokenizer = match (tokenizer_path, model_path) {
+        (Some(path), _) => WGSLTokenizer::load(path)?,
+        (None, Some(path)) => Checkpoint::load(path)?.tokenizer,
+        (None, None) => WGSLTokenizer::new(512, false),
+    };
+    let codes: Vec<&str> = dataset
+        .examples
+        .iter()
+        .map(|example| example.wgsl_code.as_str())
+        .collect();
+
+    println!("🔁 Round-tripping {} shaders from {}", codes.len(), path.display());
+    let failures = round_trip_corpus(&tokenizer, &codes);
+    for (index, result) in &failures {
+        println!(
+            "\n❌ #{}: {}",
+            index + 1,
+            dataset.examples[*index].natural_language
+        );
+        if let Some(error) = &result.error {
+            println!("{}", error.trim_end());
+        }
+    }
+
+    if failures.is_empty() {
+        println!("✅ Every valid shader survives tokenization");
+        Ok(())
+    } else {
+        anyhow::bail!("{} shader(s) no longer parse after tokenization", failures.len())
+    }
+}
+
+fn distill_dataset(
+    dataset_path: &PathBuf,
+    model: &std::path::Path,
+    output: &PathBuf,
+    generation: &GenerationConfig,
+    seed: Option<u64>,
+    device: &Device,
+    keep_invalid: bool,
+) -> anyhow::Result<()> {
+    let dataset = WGSLDataset::from_file(dataset_path)?;
+    if dataset.is_empty() {
+        anyhow::bail!("{} has no examples", dataset_path.display());
+    }
+    println!(
+        "🧑‍🏫 Generating {} shaders with {}",
+        dataset.len(),
+        model.display()
+    );
+    let teacher = load_backend(model, &generation.provider, device)?;
+    let validator = WGSLValidator::new();
+    let source = model.display().to_string();
+
+    let mut distilled = WGSLDataset::new();
+    for (index, example) in dataset.examples.iter().enumerate() {
+        let seed = seed.map(|seed| seed.wrapping_add(index as u64));
+        let result = teacher.generate_with_config(
+            &example.natural_language,
+            generation,
+            seed,
+            &validator,
+        )?;
+        println!(
+            "  {} {}",
+            if result.is_valid() { "✅" } else { "❌" },
+            example.natural_language
+        );
+        if result.is_valid() || keep_invalid {
+            distilled.examples.push(WGSLExample {
+                wgsl_code: result.code,
+                source: Some(source.clone()),
+                ..example.clone()
+            });
+        }
+    }
+
+    distilled.to_file(output)?;
+    println!(
+        "\n📊 {}/{} teacher shaders written to {}",
+        distilled.len(),
+        dataset.len(),
+        output.display()
+    );
+    Ok(())
+}
+
+#[cfg(feature = "hub")]
+fn hub_download(
+    repo: &str,
+    file: &str,
+    dataset: bool,
+    revision: &str,
+    output: &std::path::Path,
+) -> anyhow::Result<()> {
+    let engine = EngineConfig::from_file_if_present("config/engine.toml")?.unwrap_or_default();
+    let repo_type = if dataset {
+        RepoType::Dataset
+    } else {
+        RepoType::Model
+    };
+    println!("⬇️  Downloading {} from {} ({})", file, repo, revision);
+    let path = HubClient::new(&engine.hub).download(repo_type, repo, revision, file, output)?;
+    println!("✅ Saved to {}", path.display());
+    if dataset {
+        match WGSLDataset::from_file(&path) {
+            Ok(dataset) => println!("   Examples: {}", dataset.len()),
+            Err(e) => println!("⚠️  Not a dataset this tool reads: {}", e),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(feature = "hub")]
+fn hub_push(
+    model: &std::path::Path,
+    repo: &str,
+    eval: Option<&PathBuf>,
+    private: bool,
+) -> anyhow::Result<()> {
+    let engine = EngineConfig::from_file_if_present("config/engine.toml")?.unwrap_or_default();
+    let eval: Option<EvalReport> = eval
+        .map(|path| -> anyhow::Result<_> {
+            Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?)
+        })
+        .transpose()?;
+    println!("⬆️  Pushing {} to {}", model.display(), repo);
+    let url = HubClient::new(&engine.hub).push_checkpoint(repo, model, eval.as_ref(), private)?;
+    println!("✅ Committed: {}", url);
+    Ok(())
+}
+
+fn diff_datasets(a: &PathBuf, b: &PathBuf) -> anyhow::Result<()> {
+    let dataset_a = WGSLDataset::from_file(a)?;
+    let dataset_b = WGSLDataset::from_file(b)?;
+    let diff = dataset_a.diff(&dataset_b);
+
+    println!("🔍 Dataset diff");
+    println!("{}", "=".repeat(40));
+    println!("  A: {} ({} examples)", a.display(), dataset_a.len());
+    println!("  B: {} ({} examples)", b.display(), dataset_b.len());
+    println!("  Identical: {}", diff.common);
+
+    if diff.is_empty() {
+        println!("\n✅ Datasets contain the same examples");
+        return Ok(());
+    }
+
+    if !diff.only_in_self.is_empty() {
+        println!("\n➖ Only in A ({}):", diff.only_in_self.len());
+        for example in &diff.only_in_self {
+            println!("  - {}", example.natural_language);
+        }
+    }
+
+    if !diff.only_in_other.is_empty() {
+        println!("\n➕ Only in B ({}):", diff.only_in_other.len());
+        for example in &diff.only_in_other {
+            println!("  + {}", example.natural_language);
+        }
+    }
+
+    if !diff.changed.is_empty() {
+        println!("\n✏️  Changed code ({}):", diff.changed.len());
+        for (example, _) in &diff.changed {
+            println!("  ~ {}", example.natural_language);
+        }
+    }
+
+    Ok(())
+}
 
+fn run_bench(config: &BenchConfig) -> anyhow::Result<()> {
+    let cases = config.cases().len();
+    println!(
+        "⏱️  Benchmarking {} configuration(s) on CPU, {} iteration(s) each",
+        cases, config.iterations
+    );
+    let results = run_benchmarks(config)?;
+    println!("\n📊 Throughput (tokens/sec)\n");
+    print!("{}", BenchTable(&results));
     Ok(())
 }
@@ -1,16 +1,33 @@
 //! Multi-head attention implementation used by the WGSL transformer.
+//!
+//! Scores are computed either as one query × key matrix per head, or, with
+//! `[model.attention] block_size` set, block by block with an online softmax:
+//! only `block_size`² scores exist at a time and the backward pass recomputes
+//! them from each query's log-sum-exp, so memory grows linearly with the
+//! sequence length.
 
-use ndarray::{s, Array1, Array2};
-use rand::{distributions::Uniform, rngs::StdRng, Rng};
+use ndarray::{s, Array1, Array2, ArrayView2, ArrayViewMut2, Axis};
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
 
-use super::softmax_vec;
+use super::linalg::{add_matmul, matmul};
+use super::{init::Initializer, parameters, softmax_vec};
+use crate::config::AttentionConfig;
+
+/// Base of the rotary embedding frequencies, as for the sinusoidal encoding
+const ROPE_BASE: f32 = 10000.0;
 
 /// Multi-head scaled dot-product attention.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MultiHeadAttention {
     d_model: usize,
     nhead: usize,
     head_dim: usize,
+    /// Rotate queries and keys by position (RoPE)
+    rotary: bool,
+    /// Blocking and sliding window; not parameters
+    #[serde(default)]
+    config: AttentionConfig,
     w_q: Array2<f32>,
     w_k: Array2<f32>,
     w_v: Array2<f32>,
@@ -21,25 +38,163 @@ pub struct MultiHeadAttention {
     b_o: Array1<f32>,
 }
 
+parameters!(MultiHeadAttention {
+    w_q,
+    w_k,
+    w_v,
+    w_o,
+    b_q,
+    b_k,
+    b_v,
+    b_o
+});
+
+/// Keys hidden from queries, described per key rather than as a query × key
+/// matrix
+///
+/// A batched mask describes several sequences of equal, padded length
+/// stacked row by row; each query only sees the keys of its own sequence.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AttentionMask {
+    /// `true` at keys that are padding
+    padding: Vec<bool>,
+    /// Hide keys after the query's position
+    causal: bool,
+    /// Number of stacked sequences; 0 and 1 both mean a single one
+    sequences: usize,
+}
+
+impl AttentionMask {
+    /// Hide the keys where `is_padding` holds
+    pub fn padding(is_padding: impl IntoIterator<Item = bool>) -> Self {
+        Self {
+            padding: is_padding.into_iter().collect(),
+            causal: false,
+            sequences: 1,
+        }
+    }
+
+    /// Additionally hide keys after each query, for decoder self-attention
+    pub fn causal(mut self) -> Self {
+        self.causal = true;
+        self
+    }
+
+    /// Treat queries and keys as `sequences` stacked sequences of equal
+    /// length, with `padding` covering the keys of all of them
+    pub fn batched(mut self, sequences: usize) -> Self {
+        self.sequences = sequences;
+        self
+    }
+
+    /// Number of stacked sequences
+    fn sequences(&self) -> usize {
+        self.sequences.max(1)
+    }
+
+    /// Mask of sequence `index` of a batch whose sequences have `keys` keys
+    fn sequence(&self, index: usize, keys: usize) -> Self {
+        let start = (index * keys).min(self.padding.len());
+        let end = (start + keys).min(self.padding.len());
+        Self {
+            padding: self.padding[start..end].to_vec(),
+            causal: self.causal,
+            sequences: 1,
+        }
+    }
+
+    /// Keys any of `queries` may see out of `keys`, with attention limited
+    /// to `window` positions (0: unlimited)
+    fn key_range(&self, queries: Range<usize>, keys: usize, window: usize) -> Range<usize> {
+        let start = match window {
+            0 => 0,
+            window => queries.start.saturating_sub(window - 1),
+        };
+        let end = match (self.causal, window) {
+            (true, _) => queries.end,
+            (false, 0) => keys,
+            (false, window) => queries.end - 1 + window,
+        };
+        start..end.min(keys)
+    }
+
+    /// Set the scores of hidden keys to -inf in a block of queries starting
+    /// at `query_start` and keys starting at `key_start`
+    fn apply(
+        &self,
+        mut scores: ArrayViewMut2<f32>,
+        query_start: usize,
+        key_start: usize,
+        window: usize,
+    ) {
+        for ((i, j), score) in scores.indexed_iter_mut() {
+            let (query, key) = (query_start + i, key_start + j);
+            let hidden = self.padding.get(key).copied().unwrap_or(false)
+                || (self.causal && key > query)
+                || (window > 0 && query.abs_diff(key) >= window);
+            if hidden {
+                *score = f32::NEG_INFINITY;
+            }
+        }
+    }
+}
+
+/// Inputs, projections and what the backward pass needs of the attention
+/// probabilities.
+///
+/// With rotary embeddings `q` and `k` are kept after rotation.
+#[derive(Debug, Clone)]
+pub(super) struct AttentionCache {
+    query: Array2<f32>,
+    key: Array2<f32>,
+    value: Array2<f32>,
+    q: Array2<f32>,
+    k: Array2<f32>,
+    v: Array2<f32>,
+    probabilities: Probabilities,
+    mask: AttentionMask,
+    context: Array2<f32>,
+}
+
+/// Attention probabilities of each head
+#[derive(Debug, Clone)]
+enum Probabilities {
+    /// Full query × key weights
+    Full(Vec<Array2<f32>>),
+    /// Log-sum-exp of each query's scores, to recompute weights block by block
+    Blocked(Vec<Array1<f32>>),
+}
+
 impl MultiHeadAttention {
-    /// Create a new attention module with Xavier-like random initialisation.
-    pub fn new(d_model: usize, nhead: usize, rng: &mut StdRng, dist: Uniform<f32>) -> Self {
-        assert!(d_model % nhead == 0, "d_model must be divisible by nhead");
+    /// Create a new attention module with weights drawn from `init`.
+    pub(super) fn new(
+        d_model: usize,
+        nhead: usize,
+        rotary: bool,
+        config: AttentionConfig,
+        init: &mut Initializer,
+    ) -> Self {
+        assert!(
+            d_model.is_multiple_of(nhead),
+            "d_model must be divisible by nhead"
+        );
 
-        let w_q = Array2::from_shape_fn((d_model, d_model), |_| rng.sample(dist));
-        let w_k = Array2::from_shape_fn((d_model, d_model), |_| rng.sample(dist));
-        let w_v = Array2::from_shape_fn((d_model, d_model), |_| rng.sample(dist));
-        let w_o = Array2::from_shape_fn((d_model, d_model), |_| rng.sample(dist));
+        let w_q = init.matrix(d_model, d_model);
+        let w_k = init.matrix(d_model, d_model);
+        let w_v = init.matrix(d_model, d_model);
+        let w_o = init.matrix(d_model, d_model);
 
-        let b_q = Array1::from_shape_fn(d_model, |_| rng.sample(dist));
-        let b_k = Array1::from_shape_fn(d_model, |_| rng.sample(dist));
-        let b_v = Array1::from_shape_fn(d_model, |_| rng.sample(dist));
-        let b_o = Array1::from_shape_fn(d_model, |_| rng.sample(dist));
+        let b_q = init.bias(d_model);
+        let b_k = init.bias(d_model);
+        let b_v = init.bias(d_model);
+        let b_o = init.bias(d_model);
 
         Self {
             d_model,
             nhead,
             head_dim: d_model / nhead,
+            rotary,
+            config,
             w_q,
             w_k,
             w_v,
@@ -52,75 +207,394 @@ impl MultiHeadAttention {
     }
 
     /// Forward pass of the attention module.
+    ///
+    /// With a [batched](AttentionMask::batched) mask the projections run
+    /// once over all stacked sequences and the scores sequence by sequence.
     pub fn forward(
         &self,
         query: &Array2<f32>,
         key: &Array2<f32>,
         value: &Array2<f32>,
-        mask: Option<&Array2<f32>>,
+        mask: &AttentionMask,
     ) -> Array2<f32> {
-        let q = query.dot(&self.w_q) + &self.b_q;
-        let k = key.dot(&self.w_k) + &self.b_k;
-        let v = value.dot(&self.w_v) + &self.b_v;
+        let sequences = mask.sequences();
+        if sequences == 1 {
+            return self.forward_cached(query, key, value, mask).0;
+        }
 
-        let query_len = q.nrows();
-        let key_len = k.nrows();
+        let q = matmul(query, &self.w_q) + &self.b_q;
+        let k = matmul(key, &self.w_k) + &self.b_k;
+        let v = matmul(value, &self.w_v) + &self.b_v;
+        let (queries, keys) = (q.nrows() / sequences, k.nrows() / sequences);
+        let mut context = Array2::<f32>::zeros((q.nrows(), self.d_model));
+        for sequence in 0..sequences {
+            let query_rows = s![sequence * queries..(sequence + 1) * queries, ..];
+            let key_rows = s![sequence * keys..(sequence + 1) * keys, ..];
+            let (mut q, mut k) = (q.slice(query_rows).to_owned(), k.slice(key_rows).to_owned());
+            if self.rotary {
+                q = self.rotate(&q, 1.0);
+                k = self.rotate(&k, 1.0);
+            }
+            let (output, _) = self.attend(
+                &q,
+                &k,
+                &v.slice(key_rows).to_owned(),
+                &mask.sequence(sequence, keys),
+            );
+            context.slice_mut(query_rows).assign(&output);
+        }
+        matmul(&context, &self.w_o) + &self.b_o
+    }
 
-        let mut weights = Array2::<f32>::zeros((query_len, key_len));
-        let mut context = Array2::<f32>::zeros((query_len, self.d_model));
+    /// Forward pass that also returns the activations needed by [`backward`](Self::backward).
+    ///
+    /// Only for a single sequence: training never batches masks.
+    pub(super) fn forward_cached(
+        &self,
+        query: &Array2<f32>,
+        key: &Array2<f32>,
+        value: &Array2<f32>,
+        mask: &AttentionMask,
+    ) -> (Array2<f32>, AttentionCache) {
+        debug_assert_eq!(mask.sequences(), 1, "cached attention over a batched mask");
+        let mut q = matmul(query, &self.w_q) + &self.b_q;
+        let mut k = matmul(key, &self.w_k) + &self.b_k;
+        let v = matmul(value, &self.w_v) + &self.b_v;
+        if self.rotary {
+            q = self.rotate(&q, 1.0);
+            k = self.rotate(&k, 1.0);
+        }
+
+        let (context, probabilities) = self.attend(&q, &k, &v, mask);
+        let output = matmul(&context, &self.w_o) + &self.b_o;
+        let cache = AttentionCache {
+            query: query.clone(),
+            key: key.clone(),
+            value: value.clone(),
+            q,
+            k,
+            v,
+            probabilities,
+            mask: mask.clone(),
+            context,
+        };
+        (output, cache)
+    }
+
+    /// Context of every head for projected (and rotated) queries, keys and
+    /// values of one sequence, with what the backward pass needs of the
+    /// attention probabilities
+    fn attend(
+        &self,
+        q: &Array2<f32>,
+        k: &Array2<f32>,
+        v: &Array2<f32>,
+        mask: &AttentionMask,
+    ) -> (Array2<f32>, Probabilities) {
+        let mut context = Array2::<f32>::zeros((q.nrows(), self.d_model));
+        let mut weights = Vec::with_capacity(self.nhead);
+        let mut log_sum_exps = Vec::with_capacity(self.nhead);
 
         for head in 0..self.nhead {
             let start = head * self.head_dim;
             let end = start + self.head_dim;
 
-            let q_head = q.slice(s![.., start..end]).to_owned();
-            let k_head = k.slice(s![.., start..end]).to_owned();
-            let v_head = v.slice(s![.., start..end]).to_owned();
+            let q_head = q.slice(s![.., start..end]);
+            let k_head = k.slice(s![.., start..end]);
+            let v_head = v.slice(s![.., start..end]);
+            let mut context_head = context.slice_mut(s![.., start..end]);
 
-            for i in 0..query_len {
-                for j in 0..key_len {
-                    let mut score = 0.0f32;
-                    for d in 0..self.head_dim {
-                        score += q_head[[i, d]] * k_head[[j, d]];
-                    }
-                    score /= (self.head_dim as f32).sqrt();
-                    weights[[i, j]] = score;
-                }
+            if self.config.block_size == 0 {
+                let scores = self.probabilities(q_head, k_head, mask);
+                context_head.assign(&matmul(&scores, &v_head));
+                weights.push(scores);
+            } else {
+                let (output, log_sum_exp) = self.blocked_forward(q_head, k_head, v_head, mask);
+                context_head.assign(&output);
+                log_sum_exps.push(log_sum_exp);
             }
+        }
+
+        let probabilities = if self.config.block_size == 0 {
+            Probabilities::Full(weights)
+        } else {
+            Probabilities::Blocked(log_sum_exps)
+        };
+        (context, probabilities)
+    }
+
+    /// Attention weights of each head, queries × keys, of the forward pass
+    /// that filled `cache`; recomputed in full when attention is blocked
+    pub(super) fn weights(&self, cache: &AttentionCache) -> Vec<Array2<f32>> {
+        match &cache.probabilities {
+            Probabilities::Full(weights) => weights.clone(),
+            Probabilities::Blocked(_) => (0..self.nhead)
+                .map(|head| {
+                    let columns = head * self.head_dim..(head + 1) * self.head_dim;
+                    self.probabilities(
+                        cache.q.slice(s![.., columns.clone()]),
+                        cache.k.slice(s![.., columns]),
+                        &cache.mask,
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    /// Full query × key attention weights of one head
+    fn probabilities(
+        &self,
+        q_head: ArrayView2<f32>,
+        k_head: ArrayView2<f32>,
+        mask: &AttentionMask,
+    ) -> Array2<f32> {
+        let scale = (self.head_dim as f32).sqrt();
+        let mut scores = matmul(&q_head, &k_head.t()) / scale;
+        mask.apply(scores.view_mut(), 0, 0, self.config.window);
+        for mut row in scores.rows_mut() {
+            let softmax = softmax_vec(row.to_vec());
+            row.assign(&Array1::from(softmax));
+        }
+        scores
+    }
 
-            if let Some(mask) = mask {
-                for i in 0..query_len {
-                    for j in 0..key_len {
-                        weights[[i, j]] += mask[[i, j]];
+    /// Attention output of one head computed over blocks of queries and keys
+    /// with a running maximum and sum per query, and each query's log-sum-exp
+    ///
+    /// Queries that can see no key output zeros.
+    fn blocked_forward(
+        &self,
+        q_head: ArrayView2<f32>,
+        k_head: ArrayView2<f32>,
+        v_head: ArrayView2<f32>,
+        mask: &AttentionMask,
+    ) -> (Array2<f32>, Array1<f32>) {
+        let scale = (self.head_dim as f32).sqrt();
+        let block = self.config.block_size;
+        let (queries, keys) = (q_head.nrows(), k_head.nrows());
+        let mut output = Array2::<f32>::zeros((queries, self.head_dim));
+        let mut log_sum_exp = Array1::<f32>::from_elem(queries, f32::NEG_INFINITY);
+
+        for query_start in (0..queries).step_by(block) {
+            let query_end = (query_start + block).min(queries);
+            let q_block = q_head.slice(s![query_start..query_end, ..]);
+            let rows = query_end - query_start;
+            let mut max = vec![f32::NEG_INFINITY; rows];
+            let mut sum = vec![0.0f32; rows];
+            let mut accumulator = Array2::<f32>::zeros((rows, self.head_dim));
+
+            let key_range = mask.key_range(query_start..query_end, keys, self.config.window);
+            for key_start in key_range.clone().step_by(block) {
+                let key_end = (key_start + block).min(key_range.end);
+                let mut scores =
+                    matmul(&q_block, &k_head.slice(s![key_start..key_end, ..]).t()) / scale;
+                mask.apply(
+                    scores.view_mut(),
+                    query_start,
+                    key_start,
+                    self.config.window,
+                );
+
+                for (i, mut row) in scores.rows_mut().into_iter().enumerate() {
+                    let block_max = row.fold(f32::NEG_INFINITY, |a, &b| a.max(b));
+                    let new_max = max[i].max(block_max);
+                    if new_max == f32::NEG_INFINITY {
+                        row.fill(0.0);
+                        continue;
                     }
+                    // Rescale what was accumulated against the old maximum
+                    let correction = (max[i] - new_max).exp();
+                    row.mapv_inplace(|score| (score - new_max).exp());
+                    sum[i] = sum[i] * correction + row.sum();
+                    accumulator.row_mut(i).mapv_inplace(|x| x * correction);
+                    max[i] = new_max;
                 }
+                add_matmul(
+                    &mut accumulator,
+                    &scores,
+                    &v_head.slice(s![key_start..key_end, ..]),
+                );
             }
 
-            for i in 0..query_len {
-                let mut logits = Vec::with_capacity(key_len);
-                for j in 0..key_len {
-                    logits.push(weights[[i, j]]);
-                }
-                let softmax = softmax_vec(logits);
-                for (j, value) in softmax.into_iter().enumerate() {
-                    weights[[i, j]] = value;
+            for i in 0..rows {
+                if sum[i] > 0.0 {
+                    output
+                        .row_mut(query_start + i)
+                        .assign(&(&accumulator.row(i) / sum[i]));
+                    log_sum_exp[query_start + i] = max[i] + sum[i].ln();
                 }
             }
+        }
+        (output, log_sum_exp)
+    }
 
-            for i in 0..query_len {
-                for j in 0..key_len {
-                    let weight = weights[[i, j]];
-                    if weight == 0.0 {
-                        continue;
-                    }
-                    for d in 0..self.head_dim {
-                        context[[i, start + d]] += weight * v_head[[j, d]];
+    /// Gradients of one head's queries, keys and values from its full
+    /// attention weights
+    fn full_backward(
+        &self,
+        cache: &AttentionCache,
+        head: usize,
+        weights: &Array2<f32>,
+        d_context: &Array2<f32>,
+    ) -> (Array2<f32>, Array2<f32>, Array2<f32>) {
+        let scale = (self.head_dim as f32).sqrt();
+        let columns = s![.., head * self.head_dim..(head + 1) * self.head_dim];
+        let d_context_head = d_context.slice(columns);
+
+        let d_v = matmul(&weights.t(), &d_context_head);
+
+        // Softmax backward: dS = P * (dP - rowsum(dP * P))
+        let d_weights = matmul(&d_context_head, &cache.v.slice(columns).t());
+        let row_dot = (&d_weights * weights)
+            .sum_axis(Axis(1))
+            .insert_axis(Axis(1));
+        let d_scores = (&d_weights - &row_dot) * weights / scale;
+
+        (
+            matmul(&d_scores, &cache.k.slice(columns)),
+            matmul(&d_scores.t(), &cache.q.slice(columns)),
+            d_v,
+        )
+    }
+
+    /// Gradients of one head's queries, keys and values from blocked
+    /// attention, recomputing the weights of each block from `log_sum_exp`
+    fn blocked_backward(
+        &self,
+        cache: &AttentionCache,
+        head: usize,
+        log_sum_exp: &Array1<f32>,
+        d_context: &Array2<f32>,
+    ) -> (Array2<f32>, Array2<f32>, Array2<f32>) {
+        let scale = (self.head_dim as f32).sqrt();
+        let block = self.config.block_size;
+        let columns = s![.., head * self.head_dim..(head + 1) * self.head_dim];
+        let (q_head, k_head, v_head) = (
+            cache.q.slice(columns),
+            cache.k.slice(columns),
+            cache.v.slice(columns),
+        );
+        let d_context_head = d_context.slice(columns);
+        let (queries, keys) = (q_head.nrows(), k_head.nrows());
+        let mut d_q = Array2::<f32>::zeros(q_head.raw_dim());
+        let mut d_k = Array2::<f32>::zeros(k_head.raw_dim());
+        let mut d_v = Array2::<f32>::zeros(v_head.raw_dim());
+        // rowsum(dP * P) equals rowsum(dO * O)
+        let row_dot = (&d_context_head * &cache.context.slice(columns)).sum_axis(Axis(1));
+
+        for query_start in (0..queries).step_by(block) {
+            let query_end = (query_start + block).min(queries);
+            let query_rows = s![query_start..query_end, ..];
+            let key_range = cache
+                .mask
+                .key_range(query_start..query_end, keys, self.config.window);
+            for key_start in key_range.clone().step_by(block) {
+                let key_end = (key_start + block).min(key_range.end);
+                let key_rows = s![key_start..key_end, ..];
+
+                let mut weights =
+                    matmul(&q_head.slice(query_rows), &k_head.slice(key_rows).t()) / scale;
+                cache.mask.apply(
+                    weights.view_mut(),
+                    query_start,
+                    key_start,
+                    self.config.window,
+                );
+                for (i, mut row) in weights.rows_mut().into_iter().enumerate() {
+                    let log_sum_exp = log_sum_exp[query_start + i];
+                    if log_sum_exp == f32::NEG_INFINITY {
+                        row.fill(0.0);
+                    } else {
+                        row.mapv_inplace(|score| (score - log_sum_exp).exp());
                     }
                 }
+
+                let d_output = d_context_head.slice(query_rows);
+                let mut d_v_block = d_v.slice_mut(key_rows);
+                add_matmul(&mut d_v_block, &weights.t(), &d_output);
+
+                let d_weights = matmul(&d_output, &v_head.slice(key_rows).t());
+                let row_dot = row_dot
+                    .slice(s![query_start..query_end])
+                    .insert_axis(Axis(1));
+                let d_scores = (&d_weights - &row_dot) * &weights / scale;
+
+                let mut d_q_block = d_q.slice_mut(query_rows);
+                add_matmul(&mut d_q_block, &d_scores, &k_head.slice(key_rows));
+                let mut d_k_block = d_k.slice_mut(key_rows);
+                add_matmul(&mut d_k_block, &d_scores.t(), &q_head.slice(query_rows));
             }
         }
+        (d_q, d_k, d_v)
+    }
+
+    /// Accumulate parameter gradients into `grads` and return the gradients of
+    /// the query, key and value inputs.
+    pub(super) fn backward(
+        &self,
+        cache: &AttentionCache,
+        d_output: &Array2<f32>,
+        grads: &mut MultiHeadAttention,
+    ) -> (Array2<f32>, Array2<f32>, Array2<f32>) {
+        add_matmul(&mut grads.w_o, &cache.context.t(), d_output);
+        grads.b_o += &d_output.sum_axis(Axis(0));
+        let d_context = matmul(d_output, &self.w_o.t());
+
+        let mut d_q = Array2::<f32>::zeros(cache.q.raw_dim());
+        let mut d_k = Array2::<f32>::zeros(cache.k.raw_dim());
+        let mut d_v = Array2::<f32>::zeros(cache.v.raw_dim());
+        for head in 0..self.nhead {
+            let (d_q_head, d_k_head, d_v_head) = match &cache.probabilities {
+                Probabilities::Full(weights) => {
+                    self.full_backward(cache, head, &weights[head], &d_context)
+                }
+                Probabilities::Blocked(log_sum_exps) => {
+                    self.blocked_backward(cache, head, &log_sum_exps[head], &d_context)
+                }
+            };
+            let columns = s![.., head * self.head_dim..(head + 1) * self.head_dim];
+            d_q.slice_mut(columns).assign(&d_q_head);
+            d_k.slice_mut(columns).assign(&d_k_head);
+            d_v.slice_mut(columns).assign(&d_v_head);
+        }
+
+        if self.rotary {
+            // Rotations are orthogonal: the transpose rotates back
+            d_q = self.rotate(&d_q, -1.0);
+            d_k = self.rotate(&d_k, -1.0);
+        }
+
+        add_matmul(&mut grads.w_q, &cache.query.t(), &d_q);
+        grads.b_q += &d_q.sum_axis(Axis(0));
+        add_matmul(&mut grads.w_k, &cache.key.t(), &d_k);
+        grads.b_k += &d_k.sum_axis(Axis(0));
+        add_matmul(&mut grads.w_v, &cache.value.t(), &d_v);
+        grads.b_v += &d_v.sum_axis(Axis(0));
 
-        context.dot(&self.w_o) + &self.b_o
+        (
+            matmul(&d_q, &self.w_q.t()),
+            matmul(&d_k, &self.w_k.t()),
+            matmul(&d_v, &self.w_v.t()),
+        )
+    }
+
+    /// Rotate each pair of dimensions within every head by an angle
+    /// proportional to the row's position; `direction` -1 undoes the rotation
+    fn rotate(&self, x: &Array2<f32>, direction: f32) -> Array2<f32> {
+        let mut rotated = x.clone();
+        for (position, mut row) in rotated.rows_mut().into_iter().enumerate() {
+            for pair in 0..self.head_dim / 2 {
+                let frequency = ROPE_BASE.powf(-2.0 * pair as f32 / self.head_dim as f32);
+                let (sin, cos) = (direction * position as f32 * frequency).sin_cos();
+                for head in 0..self.nhead {
+                    let i = head * self.head_dim + 2 * pair;
+                    let (a, b) = (row[i], row[i + 1]);
+                    row[i] = a * cos - b * sin;
+                    row[i + 1] = a * sin + b * cos;
+                }
+            }
+        }
+        rotated
     }
 
     /// Number of trainable parameters contained in this module.
@@ -135,3 +609,93 @@ impl MultiHeadAttention {
             + self.b_o.len()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::Parameters;
+
+    #[test]
+    fn test_rotary_scores_depend_on_offset() {
+        let mut init = Initializer::new(Default::default(), 3);
+        let attention = MultiHeadAttention::new(8, 2, true, Default::default(), &mut init);
+        let row = Array1::from_shape_fn(8, |i| (i as f32 * 0.7).sin());
+        let x = Array2::from_shape_fn((6, 8), |(_, i)| row[i]);
+        let rotated = attention.rotate(&x, 1.0);
+
+        let score = |i: usize, j: usize| rotated.row(i).dot(&rotated.row(j));
+        assert!((score(3, 1) - score(5, 3)).abs() < 1e-5);
+        assert!((score(0, 0) - score(4, 4)).abs() < 1e-5);
+        assert!((score(2, 0) - score(2, 1)).abs() > 1e-4);
+
+        let restored = attention.rotate(&rotated, -1.0);
+        assert!(restored.iter().zip(&x).all(|(a, b)| (a - b).abs() < 1e-5));
+    }
+
+    fn assert_close(a: &Array2<f32>, b: &Array2<f32>) {
+        assert_eq!(a.shape(), b.shape());
+        for (x, y) in a.iter().zip(b) {
+            assert!((x - y).abs() < 1e-4, "{} != {}", x, y);
+        }
+    }
+
+    #[test]
+    fn test_blocked_attention_matches_full() {
+        let x = Array2::from_shape_fn((7, 8), |(i, j)| ((i * 8 + j) as f32 * 0.37).sin());
+        let d_output = Array2::from_shape_fn((7, 8), |(i, j)| ((i + 2 * j) as f32 * 0.21).cos());
+        let masks = [
+            AttentionMask::padding([false, false, true, false, false, false, true]),
+            AttentionMask::padding([false; 7]).causal(),
+        ];
+
+        for mask in &masks {
+            for window in [0, 3] {
+                let attention = |block_size| {
+                    let config = AttentionConfig { block_size, window };
+                    let mut init = Initializer::new(Default::default(), 3);
+                    MultiHeadAttention::new(8, 2, true, config, &mut init)
+                };
+                let (full, blocked) = (attention(0), attention(3));
+
+                let (full_output, full_cache) = full.forward_cached(&x, &x, &x, mask);
+                let (blocked_output, blocked_cache) = blocked.forward_cached(&x, &x, &x, mask);
+                assert_close(&full_output, &blocked_output);
+
+                let mut full_grads = full.clone();
+                let mut blocked_grads = blocked.clone();
+                for grads in [&mut full_grads, &mut blocked_grads] {
+                    grads.visit_mut("", &mut |_, values| values.fill(0.0));
+                }
+                let full_inputs = full.backward(&full_cache, &d_output, &mut full_grads);
+                let blocked_inputs =
+                    blocked.backward(&blocked_cache, &d_output, &mut blocked_grads);
+                assert_close(&full_inputs.0, &blocked_inputs.0);
+                assert_close(&full_inputs.1, &blocked_inputs.1);
+                assert_close(&full_inputs.2, &blocked_inputs.2);
+                assert_close(&full_grads.w_q, &blocked_grads.w_q);
+                assert_close(&full_grads.w_v, &blocked_grads.w_v);
+            }
+        }
+    }
+
+    #[test]
+    fn test_window_hides_distant_keys() {
+        let mask = AttentionMask::padding([false; 6]).causal();
+        let mut scores = Array2::<f32>::zeros((6, 6));
+        mask.apply(scores.view_mut(), 0, 0, 2);
+        let visible: Vec<Vec<usize>> = scores
+            .rows()
+            .into_iter()
+            .map(|row| (0..6).filter(|&j| row[j] == 0.0).collect())
+            .collect();
+        assert_eq!(visible[0], vec![0]);
+        assert_eq!(visible[4], vec![3, 4]);
+
+        assert_eq!(mask.key_range(2..4, 6, 2), 1..4);
+        assert_eq!(mask.key_range(2..4, 6, 0), 0..4);
+        assert_eq!(
+            AttentionMask::padding([false; 6]).key_range(2..4, 6, 2),
+            1..5
+        );
+    }
+}
@@ -0,0 +1,169 @@
+//! Attention weights for interpretability
+//!
+//! [`CodeGenerationModel::attention_maps`] runs one teacher-forced forward
+//! pass and keeps the weights of every attention head, so it can be read off
+//! which prompt and code tokens the model looked at while predicting each
+//! token of a shader.
+
+use super::{CodeGenerationModel, Transformer};
+use ndarray::Array2;
+use serde::{Serialize, Serializer};
+
+/// Weights of every head of one attention sub-layer
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AttentionMap {
+    /// `encoder.N.self_attn`, `decoder.N.self_attn` or `decoder.N.cross_attn`
+    pub name: String,
+    /// Weights of each head, queries × keys; every row sums to 1
+    #[serde(serialize_with = "serialize_heads")]
+    pub heads: Vec<Array2<f32>>,
+}
+
+impl AttentionMap {
+    /// Weights averaged over the heads
+    pub fn mean(&self) -> Array2<f32> {
+        let mut heads = self.heads.iter();
+        let Some(first) = heads.next() else {
+            return Array2::zeros((0, 0));
+        };
+        heads.fold(first.clone(), |sum, head| sum + head) / self.heads.len() as f32
+    }
+}
+
+/// Heads as nested lists of rows, which plain JSON readers understand
+fn serialize_heads<S: Serializer>(heads: &[Array2<f32>], serializer: S) -> Result<S::Ok, S::Error> {
+    let rows: Vec<Vec<Vec<f32>>> = heads
+        .iter()
+        .map(|head| head.rows().into_iter().map(|row| row.to_vec()).collect())
+        .collect();
+    rows.serialize(serializer)
+}
+
+impl CodeGenerationModel {
+    /// Attention maps of every layer for one teacher-forced example, in
+    /// forward order; empty for architectures without attention
+    ///
+    /// Decoder queries are the start token followed by `target_ids`, so row
+    /// `i` is the position predicting `target_ids[i]` and the last row the
+    /// one predicting end-of-sequence.
+    pub fn attention_maps(&self, input_ids: &[usize], target_ids: &[usize]) -> Vec<AttentionMap> {
+        let (decoder_ids, _) = self.teacher_forcing(target_ids);
+        match &self.transformer {
+            Some(transformer) => transformer.attention_maps(input_ids, &decoder_ids),
+            None => Vec::new(),
+        }
+    }
+}
+
+impl Transformer {
+    fn attention_maps(
+        &self,
+        encoder_input: &[usize],
+        decoder_input: &[usize],
+    ) -> Vec<AttentionMap> {
+        let mut maps = Vec::new();
+        let mut record = |name: String, heads: Vec<Array2<f32>>| {
+            maps.push(AttentionMap { name, heads });
+        };
+
+        let encoder_ids = self.sanitize_ids(encoder_input);
+        let mut encoder_states = self.embed(&encoder_ids);
+        let encoder_mask = self.padding_mask(&encoder_ids);
+        for (index, layer) in self.encoder_layers.iter().enumerate() {
+            let (states, cache) = layer.forward_cached(&encoder_states, &encoder_mask);
+            record(
+                format!("encoder.{}.self_attn", index),
+                layer.attention_weights(&cache),
+            );
+            encoder_states = states;
+        }
+        if let Some(norm) = &self.encoder_norm {
+            encoder_states = norm.forward(&encoder_states);
+        }
+
+        let decoder_ids = self.sanitize_ids(decoder_input);
+        let mut decoder_states = self.embed(&decoder_ids);
+        let decoder_mask = self.padding_mask(&decoder_ids).causal();
+        for (index, layer) in self.decoder_layers.iter().enumerate() {
+            let (states, cache) = layer.forward_cached(
+                &decoder_states,
+                &encoder_states,
+                &decoder_mask,
+                &encoder_mask,
+            );
+            let (self_attn, cross_attn) = layer.attention_weights(&cache);
+            record(format!("decoder.{}.self_attn", index), self_attn);
+            record(format!("decoder.{}.cross_attn", index), cross_attn);
+            decoder_states = states;
+        }
+        maps
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::AttentionConfig;
+    use crate::model::ModelArchitecture;
+
+    #[test]
+    fn test_attention_maps() {
+        let model = CodeGenerationModel::new(
+            ModelArchitecture::Transformer,
+            16,
+            8,
+            2,
+            2,
+            Some(16),
+            Some(16),
+        );
+        let maps = model.attention_maps(&[4, 5, 6], &[7, 8]);
+        let names: Vec<&str> = maps.iter().map(|map| map.name.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "encoder.0.self_attn",
+                "encoder.1.self_attn",
+                "decoder.0.self_attn",
+                "decoder.0.cross_attn",
+                "decoder.1.self_attn",
+                "decoder.1.cross_attn"
+            ]
+        );
+        for map in &maps {
+            assert_eq!(map.heads.len(), 2);
+            for row in map.heads.iter().flat_map(|head| head.rows()) {
+                assert!((row.sum() - 1.0).abs() < 1e-5, "{}", map.name);
+            }
+        }
+        // Decoder queries are <sos> plus the targets; keys are the prompt
+        // for cross-attention
+        assert_eq!(maps[0].heads[0].dim(), (3, 3));
+        assert_eq!(maps[3].heads[0].dim(), (3, 3));
+        let causal = &maps[2].heads[1];
+        assert_eq!(causal.dim(), (3, 3));
+        assert_eq!(
+            (causal[[0, 1]], causal[[0, 2]], causal[[1, 2]]),
+            (0.0, 0.0, 0.0)
+        );
+        assert_eq!(maps[2].mean().dim(), (3, 3));
+
+        let json = serde_json::to_value(&maps[0]).unwrap();
+        assert_eq!(json["heads"].as_array().unwrap().len(), 2);
+        assert_eq!(json["heads"][0][0].as_array().unwrap().len(), 3);
+
+        // Blocked attention is recomputed into the same weights; the seeded
+        // initialization gives both models the same parameters
+        let blocked = model
+            .with_attention(AttentionConfig {
+                block_size: 2,
+                ..AttentionConfig::default()
+            })
+            .attention_maps(&[4, 5, 6], &[7, 8]);
+        for (full, blocked) in maps.iter().zip(&blocked) {
+            for (a, b) in full.heads.iter().zip(&blocked.heads) {
+                assert!((a - b).iter().all(|d| d.abs() < 1e-6), "{}", full.name);
+            }
+        }
+    }
+}
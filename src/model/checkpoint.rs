@@ -0,0 +1,445 @@
+//! Saving and loading trained models together with their tokenizer
+
+use super::quantize::{QuantizedCheckpoint, QUANTIZED_MAGIC};
+use super::CodeGenerationModel;
+use crate::tokenizer::{WGSLTokenizer, TOKENIZER_SCHEMA_VERSION};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::{BufRead, Read};
+use std::path::Path;
+use std::time::SystemTime;
+
+/// Version of the checkpoint format; bumped on incompatible format changes
+pub const CHECKPOINT_VERSION: u32 = 1;
+
+/// Why a checkpoint or weights file cannot be read, carried by
+/// [`Error::CheckpointError`](crate::Error::CheckpointError)
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum CheckpointErrorKind {
+    /// Written in a format version this build does not read
+    #[error("format version {found}, expected {supported}")]
+    UnsupportedVersion { found: u32, supported: u32 },
+    #[error("not a quantized checkpoint")]
+    NotQuantized,
+    /// Weights metadata lacks a model dimension
+    #[error("no '{0}' in its metadata; was it written by tiny-agent-trainer?")]
+    MissingMetadata(String),
+    /// No tensor for a model parameter
+    #[error("no tensor '{0}'")]
+    MissingTensor(String),
+    #[error("tensor '{name}' has shape {found:?}, expected {expected:?}")]
+    ShapeMismatch {
+        name: String,
+        found: Vec<usize>,
+        expected: Vec<usize>,
+    },
+    #[error("unsupported tensor dtype {0}; expected F16, BF16, F32 or F64")]
+    UnsupportedDtype(String),
+    /// Tokenizer isn't the one the model was trained with
+    #[error("tokenizer does not match the model: {0}")]
+    TokenizerMismatch(String),
+    /// Stored model is inconsistent
+    #[error("{0}")]
+    Invalid(String),
+}
+
+impl CheckpointErrorKind {
+    /// Error for the file at `path`
+    pub fn at(self, path: &Path) -> crate::Error {
+        crate::Error::CheckpointError {
+            path: Some(path.to_path_buf()),
+            kind: self,
+        }
+    }
+}
+
+impl From<CheckpointErrorKind> for crate::Error {
+    fn from(kind: CheckpointErrorKind) -> Self {
+        crate::Error::CheckpointError { path: None, kind }
+    }
+}
+
+/// Attach `path` to a checkpoint error read without one
+pub(crate) fn with_path(error: crate::Error, path: &Path) -> crate::Error {
+    match error {
+        crate::Error::CheckpointError { path: None, kind } => kind.at(path),
+        error => error,
+    }
+}
+
+/// How a checkpoint was produced
+///
+/// Stored as JSON inside the checkpoint, so fields can be added without
+/// breaking existing files.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CheckpointMetadata {
+    /// Version of tiny-agent-trainer that wrote the checkpoint
+    pub crate_version: Option<String>,
+    /// When the checkpoint was written, in RFC 3339 format
+    pub created_at: Option<String>,
+    /// Git commit the training binary was built from
+    pub git_commit: Option<String>,
+    /// [`content_hash`](crate::config::Config::content_hash) of the training
+    /// configuration
+    pub config_hash: Option<String>,
+    /// Epochs trained to the end
+    pub epochs: Option<usize>,
+    /// Steps trained of the epoch after `epochs`, when training was
+    /// interrupted partway through it
+    pub epoch_step: Option<u64>,
+    /// Loss of the last epoch, on the validation set when there was one
+    pub final_loss: Option<f64>,
+    /// [`content_hash`](crate::dataset::WGSLDataset::content_hash) of the
+    /// training data
+    pub dataset_hash: Option<String>,
+    /// Snapshot of metrics at save time, such as `best_loss`
+    pub metrics: BTreeMap<String, f64>,
+    /// [`PromptTemplate`](crate::inference::PromptTemplate) training prompts
+    /// were formatted with
+    pub prompt_template: Option<String>,
+    /// [`TOKENIZER_SCHEMA_VERSION`](crate::tokenizer::TOKENIZER_SCHEMA_VERSION)
+    /// of the build that paired the tokenizer with the model
+    pub tokenizer_version: Option<u32>,
+    /// [`vocab_hash`](WGSLTokenizer::vocab_hash) of the tokenizer the model
+    /// was trained with
+    pub tokenizer_hash: Option<String>,
+}
+
+impl CheckpointMetadata {
+    /// Metadata identifying this build and its git commit, captured at
+    /// compile time, and the current time
+    pub fn provenance() -> Self {
+        Self {
+            crate_version: Some(env!("CARGO_PKG_VERSION").to_string()),
+            created_at: Some(humantime::format_rfc3339_seconds(SystemTime::now()).to_string()),
+            git_commit: option_env!("TINY_TRAINER_GIT_COMMIT").map(str::to_string),
+            ..Self::default()
+        }
+    }
+}
+
+/// Numeric components of a `major.minor.patch` version
+fn parse_version(version: &str) -> Vec<u64> {
+    version
+        .split(['.', '-', '+'])
+        .map_while(|part| part.parse().ok())
+        .collect()
+}
+
+/// A model and the tokenizer it was trained with
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub version: u32,
+    pub model: CodeGenerationModel,
+    #[serde(with = "tokenizer_json")]
+    pub tokenizer: WGSLTokenizer,
+    #[serde(with = "json_string")]
+    pub metadata: CheckpointMetadata,
+}
+
+impl Checkpoint {
+    /// Bundle a model with its tokenizer, recording the tokenizer's hash
+    pub fn new(model: CodeGenerationModel, tokenizer: WGSLTokenizer) -> Self {
+        Self {
+            version: CHECKPOINT_VERSION,
+            model,
+            tokenizer,
+            metadata: CheckpointMetadata::default(),
+        }
+        .with_metadata(CheckpointMetadata::default())
+    }
+
+    /// Replace the metadata, keeping the tokenizer hash when `metadata`
+    /// doesn't carry one
+    pub fn with_metadata(mut self, mut metadata: CheckpointMetadata) -> Self {
+        if metadata.tokenizer_hash.is_none() {
+            metadata.tokenizer_version = Some(TOKENIZER_SCHEMA_VERSION);
+            metadata.tokenizer_hash = Some(self.tokenizer.vocab_hash());
+        }
+        self.metadata = metadata;
+        self
+    }
+
+    /// Fail when the tokenizer can't be the one the model was trained with:
+    /// its vocabulary size differs from the model's, or its hash from the
+    /// recorded one
+    ///
+    /// Checkpoints from before tokenizer hashes were recorded are only
+    /// checked by size.
+    pub fn check_tokenizer(&self) -> crate::Result<()> {
+        let vocab_size = self.tokenizer.vocab_size();
+        if vocab_size != self.model.vocab_size {
+            return Err(CheckpointErrorKind::TokenizerMismatch(format!(
+                "tokenizer has {} tokens but the model was built for {}",
+                vocab_size, self.model.vocab_size
+            ))
+            .into());
+        }
+        if let Some(expected) = &self.metadata.tokenizer_hash {
+            if *expected != self.tokenizer.vocab_hash() {
+                return Err(CheckpointErrorKind::TokenizerMismatch(
+                    "vocabulary hash differs from the one recorded at training".to_string(),
+                )
+                .into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Problems that don't prevent using the checkpoint but may explain
+    /// surprising results
+    pub fn compatibility_warnings(&self) -> Vec<String> {
+        let mut warnings = Vec::new();
+        if let Some(version) = self.metadata.tokenizer_version {
+            if version > TOKENIZER_SCHEMA_VERSION {
+                warnings.push(format!(
+                    "Tokenizer schema version {} is newer than this build supports ({})",
+                    version, TOKENIZER_SCHEMA_VERSION
+                ));
+            }
+        }
+        let current = env!("CARGO_PKG_VERSION");
+        if let Some(version) = &self.metadata.crate_version {
+            if parse_version(version) > parse_version(current) {
+                warnings.push(format!(
+                    "Written by tiny-agent-trainer {}, which is newer than this build ({})",
+                    version, current
+                ));
+            }
+        }
+        warnings
+    }
+
+    /// Write the checkpoint in bincode format
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> crate::Result<()> {
+        let file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        bincode::serialize_into(file, self)?;
+        Ok(())
+    }
+
+    /// Read a checkpoint written by [`Checkpoint::save`], or a
+    /// [`QuantizedCheckpoint`], whose weights are dequantized
+    pub fn load<P: AsRef<Path>>(path: P) -> crate::Result<Self> {
+        let path = path.as_ref();
+        let file = std::io::BufReader::new(std::fs::File::open(path)?);
+        Self::read(file).map_err(|e| with_path(e, path))
+    }
+
+    /// Read a checkpoint from memory, as [`Checkpoint::load`] reads a file
+    pub fn from_bytes(bytes: &[u8]) -> crate::Result<Self> {
+        Self::read(bytes)
+    }
+
+    fn read<R: BufRead>(mut reader: R) -> crate::Result<Self> {
+        if reader.fill_buf()?.starts_with(&QUANTIZED_MAGIC) {
+            let quantized = QuantizedCheckpoint::read(reader)?;
+            // Kept as stored, so a missing tokenizer hash stays missing
+            return Ok(Self {
+                version: CHECKPOINT_VERSION,
+                model: quantized.model.dequantize(),
+                tokenizer: quantized.tokenizer,
+                metadata: quantized.metadata,
+            });
+        }
+        read_versioned(reader, CHECKPOINT_VERSION)
+    }
+}
+
+/// Deserialize a bincode value whose leading `u32` is its format version,
+/// refusing any version but `supported` before reading further
+pub(crate) fn read_versioned<T: DeserializeOwned, R: Read>(
+    mut reader: R,
+    supported: u32,
+) -> crate::Result<T> {
+    let mut version = [0u8; 4];
+    reader.read_exact(&mut version)?;
+    let found = u32::from_le_bytes(version);
+    if found != supported {
+        return Err(CheckpointErrorKind::UnsupportedVersion { found, supported }.into());
+    }
+    Ok(bincode::deserialize_from(std::io::Cursor::new(version).chain(reader))?)
+}
+
+/// Serializes the tokenizer as the JSON [`WGSLTokenizer::save`] writes, so
+/// new tokenizer fields don't change the checkpoint layout
+pub(crate) mod tokenizer_json {
+    use crate::tokenizer::WGSLTokenizer;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        tokenizer: &WGSLTokenizer,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let json = tokenizer.to_json().map_err(serde::ser::Error::custom)?;
+        serializer.serialize_str(&json)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<WGSLTokenizer, D::Error> {
+        let json = String::deserialize(deserializer)?;
+        WGSLTokenizer::from_json(&json).map_err(serde::de::Error::custom)
+    }
+}
+
+/// Serializes metadata as a JSON string
+pub(crate) mod json_string {
+    use serde::{de::DeserializeOwned, Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<T: Serialize, S: Serializer>(
+        value: &T,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let json = serde_json::to_string(value).map_err(serde::ser::Error::custom)?;
+        serializer.serialize_str(&json)
+    }
+
+    pub fn deserialize<'de, T: DeserializeOwned, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<T, D::Error> {
+        let json = String::deserialize(deserializer)?;
+        serde_json::from_str(&json).map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::ModelArchitecture;
+    use crate::test_support::tiny_generator;
+
+    #[test]
+    fn test_checkpoint_roundtrip() {
+        let (tokenizer, model) = tiny_generator(&["fn main() { return; }"]);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("model.ckpt");
+        let metadata = CheckpointMetadata {
+            epochs: Some(3),
+            final_loss: Some(0.5),
+            dataset_hash: Some("abc".to_string()),
+            metrics: [("best_loss".to_string(), 0.4)].into_iter().collect(),
+            ..CheckpointMetadata::provenance()
+        };
+        Checkpoint::new(model.clone(), tokenizer.clone())
+            .with_metadata(metadata.clone())
+            .save(&path)
+            .unwrap();
+
+        let loaded = Checkpoint::load(&path).unwrap();
+        let metadata = CheckpointMetadata {
+            tokenizer_version: Some(TOKENIZER_SCHEMA_VERSION),
+            tokenizer_hash: Some(tokenizer.vocab_hash()),
+            ..metadata
+        };
+        assert_eq!(loaded.metadata, metadata);
+        assert!(loaded.check_tokenizer().is_ok());
+        assert_eq!(
+            loaded.metadata.crate_version.as_deref(),
+            Some(env!("CARGO_PKG_VERSION"))
+        );
+        assert!(loaded.compatibility_warnings().is_empty());
+        assert_eq!(loaded.tokenizer.vocab, tokenizer.vocab);
+        assert_eq!(
+            loaded.tokenizer.tokenize("fn main"),
+            tokenizer.tokenize("fn main")
+        );
+
+        let ids = tokenizer.encode_text("fn main");
+        let expected = model.decode(&model.encode(&ids), &[2, 4]);
+        let actual = loaded.model.decode(&loaded.model.encode(&ids), &[2, 4]);
+        assert_eq!(actual, expected);
+
+        let from_bytes = Checkpoint::from_bytes(&std::fs::read(&path).unwrap()).unwrap();
+        assert_eq!(from_bytes.metadata, metadata);
+        assert_eq!(from_bytes.tokenizer.vocab, tokenizer.vocab);
+
+        std::fs::write(&path, b"not a checkpoint").unwrap();
+        assert!(Checkpoint::load(&path).is_err());
+
+        std::fs::write(&path, bincode::serialize(&99u32).unwrap()).unwrap();
+        match Checkpoint::load(&path) {
+            Err(crate::Error::CheckpointError {
+                path: Some(error_path),
+                kind: CheckpointErrorKind::UnsupportedVersion { found, supported },
+            }) => {
+                assert_eq!(error_path, path);
+                assert_eq!((found, supported), (99, CHECKPOINT_VERSION));
+            }
+            other => panic!("expected a version error, got {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn test_compatibility_warnings() {
+        let model = CodeGenerationModel::new(
+            ModelArchitecture::Transformer,
+            100,
+            8,
+            2,
+            1,
+            Some(16),
+            Some(16),
+        );
+        let checkpoint = Checkpoint::new(model, WGSLTokenizer::new(16, false)).with_metadata(
+            CheckpointMetadata {
+                crate_version: Some("999.0.0".to_string()),
+                ..CheckpointMetadata::default()
+            },
+        );
+        let warnings = checkpoint.compatibility_warnings();
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("999.0.0"), "{}", warnings[0]);
+
+        assert!(parse_version("0.10.0") > parse_version("0.9.3"));
+        assert_eq!(parse_version("1.2.3-beta.1"), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_check_tokenizer() {
+        let mut tokenizer = WGSLTokenizer::new(16, false);
+        tokenizer.fit(&["fn main"], 1);
+        let model = |vocab_size| {
+            CodeGenerationModel::new(
+                ModelArchitecture::Transformer,
+                vocab_size,
+                8,
+                2,
+                1,
+                Some(16),
+                Some(16),
+            )
+        };
+        let mismatch = |checkpoint: &Checkpoint| {
+            matches!(
+                checkpoint.check_tokenizer(),
+                Err(crate::Error::CheckpointError {
+                    kind: CheckpointErrorKind::TokenizerMismatch(_),
+                    ..
+                })
+            )
+        };
+
+        let checkpoint = Checkpoint::new(model(tokenizer.vocab_size()), tokenizer.clone());
+        assert!(checkpoint.check_tokenizer().is_ok());
+        assert!(mismatch(&Checkpoint::new(model(100), tokenizer.clone())));
+
+        // Same size, different tokens
+        let mut other = WGSLTokenizer::new(16, false);
+        other.fit(&["let x"], 1);
+        assert_eq!(other.vocab_size(), tokenizer.vocab_size());
+        let swapped = Checkpoint {
+            tokenizer: other,
+            ..checkpoint.clone()
+        };
+        assert!(mismatch(&swapped));
+
+        // Without a recorded hash only sizes are compared
+        let legacy = Checkpoint {
+            metadata: CheckpointMetadata::default(),
+            ..swapped
+        };
+        assert!(legacy.check_tokenizer().is_ok());
+    }
+}
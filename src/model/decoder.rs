@@ -1,13 +1,20 @@
 //! Transformer decoder layers with self and cross attention.
 
 use ndarray::Array2;
-use rand::{distributions::Uniform, rngs::StdRng};
+use serde::{Deserialize, Serialize};
 
-use super::{attention::MultiHeadAttention, FeedForward, LayerNorm};
+use super::{
+    attention::{AttentionCache, AttentionMask, MultiHeadAttention},
+    init::Initializer,
+    parameters, FeedForward, FeedForwardCache, LayerNorm, LayerNormCache, NormPlacement,
+    PositionalEncoding, TransformerSpec,
+};
+use crate::config::AttentionConfig;
 
 /// Decoder block with masked self-attention, encoder cross attention, and FFN.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DecoderLayer {
+    norm_placement: NormPlacement,
     self_attn: MultiHeadAttention,
     norm1: LayerNorm,
     cross_attn: MultiHeadAttention,
@@ -16,20 +23,49 @@ pub struct DecoderLayer {
     norm3: LayerNorm,
 }
 
+parameters!(DecoderLayer {
+    self_attn,
+    norm1,
+    cross_attn,
+    norm2,
+    feedforward,
+    norm3,
+});
+
+/// Activations kept by [`DecoderLayer::forward_cached`].
+#[derive(Debug, Clone)]
+pub(super) struct DecoderCache {
+    self_attn: AttentionCache,
+    norm1: LayerNormCache,
+    cross_attn: AttentionCache,
+    norm2: LayerNormCache,
+    feedforward: FeedForwardCache,
+    norm3: LayerNormCache,
+}
+
 impl DecoderLayer {
-    pub fn new(
-        d_model: usize,
-        nhead: usize,
-        dim_feedforward: usize,
-        rng: &mut StdRng,
-        dist: Uniform<f32>,
-    ) -> Self {
+    /// Rotary embeddings and the attention window only apply to
+    /// self-attention; queries and encoder states come from different
+    /// sequences
+    pub(super) fn new(spec: &TransformerSpec, init: &mut Initializer) -> Self {
+        let (d_model, nhead) = (spec.d_model, spec.nhead);
+        let rotary = spec.positional_encoding == PositionalEncoding::Rope;
         Self {
-            self_attn: MultiHeadAttention::new(d_model, nhead, rng, dist),
+            norm_placement: spec.norm_placement,
+            self_attn: MultiHeadAttention::new(d_model, nhead, rotary, spec.attention, init),
             norm1: LayerNorm::new(d_model),
-            cross_attn: MultiHeadAttention::new(d_model, nhead, rng, dist),
+            cross_attn: MultiHeadAttention::new(
+                d_model,
+                nhead,
+                false,
+                AttentionConfig {
+                    window: 0,
+                    ..spec.attention
+                },
+                init,
+            ),
             norm2: LayerNorm::new(d_model),
-            feedforward: FeedForward::new(d_model, dim_feedforward, rng, dist),
+            feedforward: FeedForward::new(d_model, spec.dim_feedforward, spec.activation, init),
             norm3: LayerNorm::new(d_model),
         }
     }
@@ -38,9 +74,12 @@ impl DecoderLayer {
         &self,
         x: &Array2<f32>,
         encoder_states: &Array2<f32>,
-        self_mask: Option<&Array2<f32>>,
-        cross_mask: Option<&Array2<f32>>,
+        self_mask: &AttentionMask,
+        cross_mask: &AttentionMask,
     ) -> Array2<f32> {
+        if self.norm_placement == NormPlacement::Pre {
+            return self.forward_pre_norm(x, encoder_states, self_mask, cross_mask);
+        }
         let self_attn = self.self_attn.forward(x, x, x, self_mask);
         let residual1 = x + &self_attn;
         let normed1 = self.norm1.forward(&residual1);
@@ -56,6 +95,182 @@ impl DecoderLayer {
         self.norm3.forward(&residual3)
     }
 
+    /// Normalize the input of each sub-layer, leaving the residual stream as is
+    fn forward_pre_norm(
+        &self,
+        x: &Array2<f32>,
+        encoder_states: &Array2<f32>,
+        self_mask: &AttentionMask,
+        cross_mask: &AttentionMask,
+    ) -> Array2<f32> {
+        let normed1 = self.norm1.forward(x);
+        let self_attn = self
+            .self_attn
+            .forward(&normed1, &normed1, &normed1, self_mask);
+        let residual1 = x + &self_attn;
+
+        let normed2 = self.norm2.forward(&residual1);
+        let cross_attn =
+            self.cross_attn
+                .forward(&normed2, encoder_states, encoder_states, cross_mask);
+        let residual2 = residual1 + &cross_attn;
+
+        let ff_output = self.feedforward.forward(&self.norm3.forward(&residual2));
+        residual2 + &ff_output
+    }
+
+    /// Self- and cross-attention weights of each head recorded in `cache`
+    pub(super) fn attention_weights(
+        &self,
+        cache: &DecoderCache,
+    ) -> (Vec<Array2<f32>>, Vec<Array2<f32>>) {
+        (
+            self.self_attn.weights(&cache.self_attn),
+            self.cross_attn.weights(&cache.cross_attn),
+        )
+    }
+
+    pub(super) fn forward_cached(
+        &self,
+        x: &Array2<f32>,
+        encoder_states: &Array2<f32>,
+        self_mask: &AttentionMask,
+        cross_mask: &AttentionMask,
+    ) -> (Array2<f32>, DecoderCache) {
+        if self.norm_placement == NormPlacement::Pre {
+            return self.forward_cached_pre_norm(x, encoder_states, self_mask, cross_mask);
+        }
+        let (self_attn_output, self_attn) = self.self_attn.forward_cached(x, x, x, self_mask);
+        let residual1 = x + &self_attn_output;
+        let (normed1, norm1) = self.norm1.forward_cached(&residual1);
+
+        let (cross_attn_output, cross_attn) =
+            self.cross_attn
+                .forward_cached(&normed1, encoder_states, encoder_states, cross_mask);
+        let residual2 = normed1 + &cross_attn_output;
+        let (normed2, norm2) = self.norm2.forward_cached(&residual2);
+
+        let (ff_output, feedforward) = self.feedforward.forward_cached(&normed2);
+        let residual3 = normed2 + &ff_output;
+        let (output, norm3) = self.norm3.forward_cached(&residual3);
+
+        let cache = DecoderCache {
+            self_attn,
+            norm1,
+            cross_attn,
+            norm2,
+            feedforward,
+            norm3,
+        };
+        (output, cache)
+    }
+
+    /// Pre-norm counterpart of [`forward_cached`](Self::forward_cached)
+    fn forward_cached_pre_norm(
+        &self,
+        x: &Array2<f32>,
+        encoder_states: &Array2<f32>,
+        self_mask: &AttentionMask,
+        cross_mask: &AttentionMask,
+    ) -> (Array2<f32>, DecoderCache) {
+        let (normed1, norm1) = self.norm1.forward_cached(x);
+        let (self_attn_output, self_attn) = self
+            .self_attn
+            .forward_cached(&normed1, &normed1, &normed1, self_mask);
+        let residual1 = x + &self_attn_output;
+
+        let (normed2, norm2) = self.norm2.forward_cached(&residual1);
+        let (cross_attn_output, cross_attn) =
+            self.cross_attn
+                .forward_cached(&normed2, encoder_states, encoder_states, cross_mask);
+        let residual2 = residual1 + &cross_attn_output;
+
+        let (normed3, norm3) = self.norm3.forward_cached(&residual2);
+        let (ff_output, feedforward) = self.feedforward.forward_cached(&normed3);
+
+        let cache = DecoderCache {
+            self_attn,
+            norm1,
+            cross_attn,
+            norm2,
+            feedforward,
+            norm3,
+        };
+        (residual2 + &ff_output, cache)
+    }
+
+    /// Accumulate parameter gradients into `grads` and return the gradients of
+    /// the decoder input and of the encoder states.
+    pub(super) fn backward(
+        &self,
+        cache: &DecoderCache,
+        d_output: &Array2<f32>,
+        grads: &mut DecoderLayer,
+    ) -> (Array2<f32>, Array2<f32>) {
+        if self.norm_placement == NormPlacement::Pre {
+            return self.backward_pre_norm(cache, d_output, grads);
+        }
+        let d_residual3 = self
+            .norm3
+            .backward(&cache.norm3, d_output, &mut grads.norm3);
+        let d_normed2 =
+            self.feedforward
+                .backward(&cache.feedforward, &d_residual3, &mut grads.feedforward)
+                + &d_residual3;
+
+        let d_residual2 = self
+            .norm2
+            .backward(&cache.norm2, &d_normed2, &mut grads.norm2);
+        let (d_cross_q, d_cross_k, d_cross_v) =
+            self.cross_attn
+                .backward(&cache.cross_attn, &d_residual2, &mut grads.cross_attn);
+        let d_normed1 = d_residual2 + &d_cross_q;
+        let d_encoder = d_cross_k + &d_cross_v;
+
+        let d_residual1 = self
+            .norm1
+            .backward(&cache.norm1, &d_normed1, &mut grads.norm1);
+        let (d_q, d_k, d_v) =
+            self.self_attn
+                .backward(&cache.self_attn, &d_residual1, &mut grads.self_attn);
+        (d_residual1 + &d_q + &d_k + &d_v, d_encoder)
+    }
+
+    /// Pre-norm counterpart of [`backward`](Self::backward)
+    fn backward_pre_norm(
+        &self,
+        cache: &DecoderCache,
+        d_output: &Array2<f32>,
+        grads: &mut DecoderLayer,
+    ) -> (Array2<f32>, Array2<f32>) {
+        let d_normed3 =
+            self.feedforward
+                .backward(&cache.feedforward, d_output, &mut grads.feedforward);
+        let d_residual2 = self
+            .norm3
+            .backward(&cache.norm3, &d_normed3, &mut grads.norm3)
+            + d_output;
+
+        let (d_cross_q, d_cross_k, d_cross_v) =
+            self.cross_attn
+                .backward(&cache.cross_attn, &d_residual2, &mut grads.cross_attn);
+        let d_encoder = d_cross_k + &d_cross_v;
+        let d_residual1 = self
+            .norm2
+            .backward(&cache.norm2, &d_cross_q, &mut grads.norm2)
+            + &d_residual2;
+
+        let (d_q, d_k, d_v) =
+            self.self_attn
+                .backward(&cache.self_attn, &d_residual1, &mut grads.self_attn);
+        let d_normed1 = d_q + &d_k + &d_v;
+        let d_input = self
+            .norm1
+            .backward(&cache.norm1, &d_normed1, &mut grads.norm1)
+            + &d_residual1;
+        (d_input, d_encoder)
+    }
+
     pub fn num_parameters(&self) -> usize {
         self.self_attn.num_parameters()
             + self.cross_attn.num_parameters()
@@ -0,0 +1,233 @@
+//! Per-layer gradient and activation statistics
+//!
+//! NaN and infinite values spread through every layer within a step or two,
+//! after which the weights only tell that training diverged, not where.
+//! [`Gradients::layer_stats`] and [`CodeGenerationModel::activation_stats`]
+//! break a step down by layer so the first one to go non-finite can be named.
+
+use super::linalg::matmul;
+use super::{CodeGenerationModel, Gradients, Transformer};
+use ndarray::Array2;
+
+/// Value statistics of one layer's gradients or activations
+#[derive(Debug, Clone, PartialEq)]
+pub struct LayerStats {
+    /// Layer name, e.g. `encoder.0` or `output`
+    pub name: String,
+    /// Number of values, including non-finite ones
+    pub count: usize,
+    /// L2 norm of the finite values
+    pub norm: f64,
+    /// Mean of the finite values
+    pub mean: f64,
+    /// Standard deviation of the finite values
+    pub std: f64,
+    /// Largest finite absolute value
+    pub max_abs: f32,
+    pub nan_count: usize,
+    pub inf_count: usize,
+}
+
+impl LayerStats {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            count: 0,
+            norm: 0.0,
+            mean: 0.0,
+            std: 0.0,
+            max_abs: 0.0,
+            nan_count: 0,
+            inf_count: 0,
+        }
+    }
+
+    /// Statistics of `values`
+    pub fn of<'a>(name: &str, values: impl IntoIterator<Item = &'a f32>) -> Self {
+        let mut stats = Self::new(name);
+        stats.add(values);
+        stats
+    }
+
+    /// Fold `values` into the statistics
+    fn add<'a>(&mut self, values: impl IntoIterator<Item = &'a f32>) {
+        let finite = self.count - self.nan_count - self.inf_count;
+        let (mut sum, mut squares) = (self.mean * finite as f64, self.norm * self.norm);
+        for &value in values {
+            self.count += 1;
+            if value.is_nan() {
+                self.nan_count += 1;
+            } else if value.is_infinite() {
+                self.inf_count += 1;
+            } else {
+                sum += value as f64;
+                squares += (value as f64) * (value as f64);
+                self.max_abs = self.max_abs.max(value.abs());
+            }
+        }
+        let finite = (self.count - self.nan_count - self.inf_count).max(1) as f64;
+        self.norm = squares.sqrt();
+        self.mean = sum / finite;
+        self.std = (squares / finite - self.mean * self.mean).max(0.0).sqrt();
+    }
+
+    pub fn is_finite(&self) -> bool {
+        self.nan_count == 0 && self.inf_count == 0
+    }
+}
+
+/// Layer a parameter belongs to: the stack and index for stacked layers
+/// (`encoder.0` for `encoder.0.self_attn.w_q`), else the first name part
+pub fn layer_of(parameter: &str) -> &str {
+    let mut parts = parameter.splitn(3, '.');
+    let first = parts.next().unwrap_or_default();
+    match parts.next() {
+        Some(index) if index.parse::<usize>().is_ok() => {
+            &parameter[..first.len() + 1 + index.len()]
+        }
+        _ => first,
+    }
+}
+
+impl Gradients {
+    /// Statistics of the gradients grouped by [`layer_of`], in parameter order
+    pub fn layer_stats(&self) -> Vec<LayerStats> {
+        let mut layers: Vec<LayerStats> = Vec::new();
+        self.visit(&mut |name, values| {
+            let layer = layer_of(name);
+            match layers.last_mut() {
+                Some(stats) if stats.name == layer => stats.add(values),
+                _ => layers.push(LayerStats::of(layer, values)),
+            }
+        });
+        layers
+    }
+}
+
+impl CodeGenerationModel {
+    /// Statistics of every layer's output for one teacher-forced example, in
+    /// forward order: `encoder_embedding`, `encoder.N`, `encoder_norm`,
+    /// `decoder_embedding`, `decoder.N`, `decoder_norm` and the `output`
+    /// logits
+    pub fn activation_stats(&self, input_ids: &[usize], target_ids: &[usize]) -> Vec<LayerStats> {
+        let (decoder_ids, _) = self.teacher_forcing(target_ids);
+        match &self.transformer {
+            Some(transformer) => transformer.activation_stats(input_ids, &decoder_ids),
+            None => Vec::new(),
+        }
+    }
+}
+
+impl Transformer {
+    /// Plain forward pass recording each layer's output
+    fn activation_stats(
+        &self,
+        encoder_input: &[usize],
+        decoder_input: &[usize],
+    ) -> Vec<LayerStats> {
+        let mut stats = Vec::new();
+        let mut record = |name: &str, states: &Array2<f32>| {
+            stats.push(LayerStats::of(name, states.iter()));
+        };
+
+        let encoder_ids = self.sanitize_ids(encoder_input);
+        let mut encoder_states = self.embed(&encoder_ids);
+        record("encoder_embedding", &encoder_states);
+        let encoder_mask = self.padding_mask(&encoder_ids);
+        for (index, layer) in self.encoder_layers.iter().enumerate() {
+            encoder_states = layer.forward(&encoder_states, &encoder_mask);
+            record(&format!("encoder.{}", index), &encoder_states);
+        }
+        if let Some(norm) = &self.encoder_norm {
+            encoder_states = norm.forward(&encoder_states);
+            record("encoder_norm", &encoder_states);
+        }
+
+        let decoder_ids = self.sanitize_ids(decoder_input);
+        let mut decoder_states = self.embed(&decoder_ids);
+        record("decoder_embedding", &decoder_states);
+        let decoder_mask = self.padding_mask(&decoder_ids).causal();
+        let cross_mask = self.padding_mask(&encoder_ids);
+        for (index, layer) in self.decoder_layers.iter().enumerate() {
+            decoder_states =
+                layer.forward(&decoder_states, &encoder_states, &decoder_mask, &cross_mask);
+            record(&format!("decoder.{}", index), &decoder_states);
+        }
+        if let Some(norm) = &self.decoder_norm {
+            decoder_states = norm.forward(&decoder_states);
+            record("decoder_norm", &decoder_states);
+        }
+
+        let logits = matmul(&decoder_states, &self.final_linear_weight) + &self.final_linear_bias;
+        record("output", &logits);
+        stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::ModelArchitecture;
+
+    #[test]
+    fn test_layer_stats() {
+        assert_eq!(layer_of("encoder.0.self_attn.w_q"), "encoder.0");
+        assert_eq!(layer_of("decoder.12.norm1.gamma"), "decoder.12");
+        assert_eq!(layer_of("encoder_norm.gamma"), "encoder_norm");
+        assert_eq!(layer_of("output.weight"), "output");
+        assert_eq!(layer_of("token_embedding"), "token_embedding");
+
+        let stats = LayerStats::of("x", &[3.0, -4.0, f32::NAN, f32::INFINITY]);
+        assert_eq!((stats.count, stats.nan_count, stats.inf_count), (4, 1, 1));
+        assert_eq!(
+            (stats.norm, stats.mean, stats.std, stats.max_abs),
+            (5.0, -0.5, 3.5, 4.0)
+        );
+        assert!(!stats.is_finite());
+
+        let mut model = CodeGenerationModel::new(
+            ModelArchitecture::Transformer,
+            16,
+            8,
+            2,
+            2,
+            Some(16),
+            Some(16),
+        );
+        let mut grads = model.zero_gradients();
+        model.accumulate_gradients(&[4, 5, 6], &[7, 8], &mut grads);
+        let layers = grads.layer_stats();
+        let names: Vec<&str> = layers.iter().map(|l| l.name.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "token_embedding",
+                "encoder.0",
+                "encoder.1",
+                "decoder.0",
+                "decoder.1",
+                "output"
+            ]
+        );
+        let total: f64 = layers.iter().map(|l| l.norm * l.norm).sum();
+        assert!((total.sqrt() - grads.global_norm()).abs() < 1e-6);
+
+        let activations = model.activation_stats(&[4, 5, 6], &[7, 8]);
+        assert_eq!(activations.first().unwrap().name, "encoder_embedding");
+        assert_eq!(activations.last().unwrap().name, "output");
+        assert_eq!(activations.last().unwrap().count, 3 * 16);
+        assert!(activations.iter().all(LayerStats::is_finite));
+
+        model.visit_parameters_mut(&mut |name, values| {
+            if name == "decoder.1.self_attn.w_v" {
+                values[0] = f32::NAN;
+            }
+        });
+        let first = model
+            .activation_stats(&[4, 5, 6], &[7, 8])
+            .into_iter()
+            .find(|l| !l.is_finite())
+            .unwrap();
+        assert_eq!(first.name, "decoder.1");
+    }
+}
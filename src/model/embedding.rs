@@ -0,0 +1,130 @@
+//! Embedding and positional tables stored at a chosen precision
+//!
+//! With `model.embedding_dtype = "f16"` the token embeddings and the
+//! sinusoidal position table are kept as half floats and widened to `f32`
+//! row by row on access, halving the memory of the largest tensors at larger
+//! vocabularies. Gradients of a half table are still accumulated in `f32`.
+
+use half::f16;
+use ndarray::{Array2, ArrayView1, CowArray, Ix1};
+use serde::{Deserialize, Serialize};
+
+use super::Parameters;
+use crate::config::Dtype;
+
+/// Matrix of row vectors in `f32` or `f16`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(super) enum Table {
+    F32(Array2<f32>),
+    F16(Array2<f16>),
+}
+
+impl Table {
+    /// `values` stored as `dtype`
+    pub fn new(values: Array2<f32>, dtype: Dtype) -> Self {
+        match dtype {
+            Dtype::F32 => Self::F32(values),
+            Dtype::F16 => Self::F16(values.mapv(f16::from_f32)),
+        }
+    }
+
+    /// The same values stored as `dtype`
+    pub fn to_dtype(&self, dtype: Dtype) -> Self {
+        match (self, dtype) {
+            (Self::F16(values), Dtype::F32) => Self::F32(values.mapv(f16::to_f32)),
+            (Self::F32(values), Dtype::F16) => Self::new(values.clone(), dtype),
+            _ => self.clone(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            Self::F32(values) => values.len(),
+            Self::F16(values) => values.len(),
+        }
+    }
+
+    /// Row `index`, widened to `f32` if needed
+    pub fn row(&self, index: usize) -> CowArray<'_, f32, Ix1> {
+        match self {
+            Self::F32(values) => values.row(index).into(),
+            Self::F16(values) => values.row(index).mapv(f16::to_f32).into(),
+        }
+    }
+
+    /// Add `delta` to row `index`
+    pub fn add_to_row(&mut self, index: usize, delta: ArrayView1<f32>) {
+        match self {
+            Self::F32(values) => {
+                let mut row = values.row_mut(index);
+                row += &delta;
+            }
+            Self::F16(values) => {
+                for (value, delta) in values.row_mut(index).iter_mut().zip(delta) {
+                    *value = f16::from_f32(value.to_f32() + delta);
+                }
+            }
+        }
+    }
+}
+
+/// Half tables are visited through a widened copy, written back after
+/// `visit_mut`
+impl Parameters for Table {
+    fn visit(&self, prefix: &str, f: &mut dyn FnMut(&str, &[f32])) {
+        match self {
+            Self::F32(values) => Parameters::visit(values, prefix, f),
+            Self::F16(values) => {
+                let widened: Vec<f32> = values.iter().map(|value| value.to_f32()).collect();
+                f(prefix, &widened);
+            }
+        }
+    }
+
+    fn visit_mut(&mut self, prefix: &str, f: &mut dyn FnMut(&str, &mut [f32])) {
+        match self {
+            Self::F32(values) => values.visit_mut(prefix, f),
+            Self::F16(values) => {
+                let mut widened: Vec<f32> = values.iter().map(|value| value.to_f32()).collect();
+                f(prefix, &mut widened);
+                for (value, widened) in values.iter_mut().zip(widened) {
+                    *value = f16::from_f32(widened);
+                }
+            }
+        }
+    }
+
+    fn visit_shapes(&self, prefix: &str, f: &mut dyn FnMut(&str, &[usize])) {
+        match self {
+            Self::F32(values) => values.visit_shapes(prefix, f),
+            Self::F16(values) => f(prefix, values.shape()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::Array1;
+
+    #[test]
+    fn test_half_table() {
+        let values = Array2::from_shape_fn((3, 4), |(i, j)| (i * 4 + j) as f32 * 0.1);
+        let mut table = Table::new(values, Dtype::F16);
+        assert!(matches!(table, Table::F16(_)));
+        assert!((table.row(2)[3] - 1.1).abs() < 1e-3);
+
+        table.add_to_row(1, Array1::from_elem(4, 1.0).view());
+        assert!((table.row(1)[0] - 1.4).abs() < 1e-3);
+
+        table.visit_mut("", &mut |_, values| values[0] = 2.5);
+        let mut visited = Vec::new();
+        table.visit("", &mut |_, values| visited = values.to_vec());
+        assert_eq!(visited[0], 2.5);
+        assert_eq!(visited.len(), 12);
+
+        let widened = table.to_dtype(Dtype::F32);
+        assert!(matches!(widened, Table::F32(_)));
+        assert_eq!(widened.row(0)[0], 2.5);
+    }
+}
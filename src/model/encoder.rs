@@ -1,36 +1,58 @@
 //! Transformer encoder layers for the WGSL model.
 
 use ndarray::Array2;
-use rand::{distributions::Uniform, rngs::StdRng};
+use serde::{Deserialize, Serialize};
 
-use super::{attention::MultiHeadAttention, FeedForward, LayerNorm};
+use super::{
+    attention::{AttentionCache, AttentionMask, MultiHeadAttention},
+    init::Initializer,
+    parameters, FeedForward, FeedForwardCache, LayerNorm, LayerNormCache, NormPlacement,
+    PositionalEncoding, TransformerSpec,
+};
 
 /// Single encoder block consisting of self-attention and a feed-forward network.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EncoderLayer {
+    norm_placement: NormPlacement,
     self_attn: MultiHeadAttention,
     norm1: LayerNorm,
     feedforward: FeedForward,
     norm2: LayerNorm,
 }
 
+parameters!(EncoderLayer {
+    self_attn,
+    norm1,
+    feedforward,
+    norm2
+});
+
+/// Activations kept by [`EncoderLayer::forward_cached`].
+#[derive(Debug, Clone)]
+pub(super) struct EncoderCache {
+    self_attn: AttentionCache,
+    norm1: LayerNormCache,
+    feedforward: FeedForwardCache,
+    norm2: LayerNormCache,
+}
+
 impl EncoderLayer {
-    pub fn new(
-        d_model: usize,
-        nhead: usize,
-        dim_feedforward: usize,
-        rng: &mut StdRng,
-        dist: Uniform<f32>,
-    ) -> Self {
+    pub(super) fn new(spec: &TransformerSpec, init: &mut Initializer) -> Self {
+        let d_model = spec.d_model;
+        let rotary = spec.positional_encoding == PositionalEncoding::Rope;
         Self {
-            self_attn: MultiHeadAttention::new(d_model, nhead, rng, dist),
+            norm_placement: spec.norm_placement,
+            self_attn: MultiHeadAttention::new(d_model, spec.nhead, rotary, spec.attention, init),
             norm1: LayerNorm::new(d_model),
-            feedforward: FeedForward::new(d_model, dim_feedforward, rng, dist),
+            feedforward: FeedForward::new(d_model, spec.dim_feedforward, spec.activation, init),
             norm2: LayerNorm::new(d_model),
         }
     }
 
-    pub fn forward(&self, x: &Array2<f32>, mask: Option<&Array2<f32>>) -> Array2<f32> {
+    pub fn forward(&self, x: &Array2<f32>, mask: &AttentionMask) -> Array2<f32> {
+        if self.norm_placement == NormPlacement::Pre {
+            return self.forward_pre_norm(x, mask);
+        }
         let attn_output = self.self_attn.forward(x, x, x, mask);
         let residual1 = x + &attn_output;
         let normed1 = self.norm1.forward(&residual1);
@@ -39,6 +61,114 @@ impl EncoderLayer {
         self.norm2.forward(&residual2)
     }
 
+    /// Normalize the input of each sub-layer, leaving the residual stream as is
+    fn forward_pre_norm(&self, x: &Array2<f32>, mask: &AttentionMask) -> Array2<f32> {
+        let normed1 = self.norm1.forward(x);
+        let attn_output = self.self_attn.forward(&normed1, &normed1, &normed1, mask);
+        let residual1 = x + &attn_output;
+        let ff_output = self.feedforward.forward(&self.norm2.forward(&residual1));
+        residual1 + &ff_output
+    }
+
+    /// Self-attention weights of each head recorded in `cache`
+    pub(super) fn attention_weights(&self, cache: &EncoderCache) -> Vec<Array2<f32>> {
+        self.self_attn.weights(&cache.self_attn)
+    }
+
+    pub(super) fn forward_cached(
+        &self,
+        x: &Array2<f32>,
+        mask: &AttentionMask,
+    ) -> (Array2<f32>, EncoderCache) {
+        if self.norm_placement == NormPlacement::Pre {
+            return self.forward_cached_pre_norm(x, mask);
+        }
+        let (attn_output, self_attn) = self.self_attn.forward_cached(x, x, x, mask);
+        let residual1 = x + &attn_output;
+        let (normed1, norm1) = self.norm1.forward_cached(&residual1);
+        let (ff_output, feedforward) = self.feedforward.forward_cached(&normed1);
+        let residual2 = normed1 + &ff_output;
+        let (output, norm2) = self.norm2.forward_cached(&residual2);
+        let cache = EncoderCache {
+            self_attn,
+            norm1,
+            feedforward,
+            norm2,
+        };
+        (output, cache)
+    }
+
+    /// Pre-norm counterpart of [`forward_cached`](Self::forward_cached)
+    fn forward_cached_pre_norm(
+        &self,
+        x: &Array2<f32>,
+        mask: &AttentionMask,
+    ) -> (Array2<f32>, EncoderCache) {
+        let (normed1, norm1) = self.norm1.forward_cached(x);
+        let (attn_output, self_attn) = self
+            .self_attn
+            .forward_cached(&normed1, &normed1, &normed1, mask);
+        let residual1 = x + &attn_output;
+        let (normed2, norm2) = self.norm2.forward_cached(&residual1);
+        let (ff_output, feedforward) = self.feedforward.forward_cached(&normed2);
+        let cache = EncoderCache {
+            self_attn,
+            norm1,
+            feedforward,
+            norm2,
+        };
+        (residual1 + &ff_output, cache)
+    }
+
+    /// Accumulate parameter gradients into `grads` and return the input gradient.
+    pub(super) fn backward(
+        &self,
+        cache: &EncoderCache,
+        d_output: &Array2<f32>,
+        grads: &mut EncoderLayer,
+    ) -> Array2<f32> {
+        if self.norm_placement == NormPlacement::Pre {
+            return self.backward_pre_norm(cache, d_output, grads);
+        }
+        let d_residual2 = self
+            .norm2
+            .backward(&cache.norm2, d_output, &mut grads.norm2);
+        let d_normed1 =
+            self.feedforward
+                .backward(&cache.feedforward, &d_residual2, &mut grads.feedforward)
+                + &d_residual2;
+        let d_residual1 = self
+            .norm1
+            .backward(&cache.norm1, &d_normed1, &mut grads.norm1);
+        let (d_q, d_k, d_v) =
+            self.self_attn
+                .backward(&cache.self_attn, &d_residual1, &mut grads.self_attn);
+        d_residual1 + &d_q + &d_k + &d_v
+    }
+
+    /// Pre-norm counterpart of [`backward`](Self::backward)
+    fn backward_pre_norm(
+        &self,
+        cache: &EncoderCache,
+        d_output: &Array2<f32>,
+        grads: &mut EncoderLayer,
+    ) -> Array2<f32> {
+        let d_normed2 =
+            self.feedforward
+                .backward(&cache.feedforward, d_output, &mut grads.feedforward);
+        let d_residual1 = self
+            .norm2
+            .backward(&cache.norm2, &d_normed2, &mut grads.norm2)
+            + d_output;
+        let (d_q, d_k, d_v) =
+            self.self_attn
+                .backward(&cache.self_attn, &d_residual1, &mut grads.self_attn);
+        let d_normed1 = d_q + &d_k + &d_v;
+        self.norm1
+            .backward(&cache.norm1, &d_normed1, &mut grads.norm1)
+            + &d_residual1
+    }
+
     pub fn num_parameters(&self) -> usize {
         self.self_attn.num_parameters()
             + self.feedforward.num_parameters()
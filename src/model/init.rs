@@ -0,0 +1,89 @@
+//! Seeded weight initialization
+//!
+//! Every initial tensor of a model is drawn in a fixed order from one
+//! generator, so a scheme and seed fully determine the starting weights.
+
+use crate::config::InitScheme;
+use ndarray::{Array1, Array2};
+use rand::{distributions::Uniform, rngs::StdRng, Rng, SeedableRng};
+
+/// Half-width of the [`InitScheme::Uniform`] range
+const UNIFORM_LIMIT: f32 = 0.1;
+
+/// Draws initial weights for one model
+pub(crate) struct Initializer {
+    scheme: InitScheme,
+    rng: StdRng,
+}
+
+impl Initializer {
+    pub(super) fn new(scheme: InitScheme, seed: u64) -> Self {
+        Self {
+            scheme,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    /// Weight matrix applied as `x.dot(w)`: rows are the fan-in, columns the
+    /// fan-out
+    pub(super) fn matrix(&mut self, fan_in: usize, fan_out: usize) -> Array2<f32> {
+        let limit = match self.scheme {
+            InitScheme::Uniform => UNIFORM_LIMIT,
+            InitScheme::Xavier => (6.0 / (fan_in + fan_out) as f32).sqrt(),
+            InitScheme::He => (6.0 / fan_in as f32).sqrt(),
+        };
+        self.sample((fan_in, fan_out), limit)
+    }
+
+    /// Embedding table of `rows` vectors of width `dim`, with variance
+    /// `1 / dim` under the scaled schemes
+    pub(super) fn embedding(&mut self, rows: usize, dim: usize) -> Array2<f32> {
+        let limit = match self.scheme {
+            InitScheme::Uniform => UNIFORM_LIMIT,
+            InitScheme::Xavier | InitScheme::He => (3.0 / dim as f32).sqrt(),
+        };
+        self.sample((rows, dim), limit)
+    }
+
+    /// Bias vector; zeros under the scaled schemes
+    pub(super) fn bias(&mut self, len: usize) -> Array1<f32> {
+        match self.scheme {
+            InitScheme::Uniform => {
+                let dist = Uniform::new(-UNIFORM_LIMIT, UNIFORM_LIMIT);
+                Array1::from_shape_fn(len, |_| self.rng.sample(dist))
+            }
+            InitScheme::Xavier | InitScheme::He => Array1::zeros(len),
+        }
+    }
+
+    fn sample(&mut self, shape: (usize, usize), limit: f32) -> Array2<f32> {
+        let dist = Uniform::new(-limit, limit);
+        Array2::from_shape_fn(shape, |_| self.rng.sample(dist))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scaled_limits() {
+        let mut init = Initializer::new(InitScheme::Xavier, 1);
+        let wide = init.matrix(256, 256);
+        let limit = (6.0f32 / 512.0).sqrt();
+        assert!(wide.iter().all(|v| v.abs() <= limit));
+        assert!(wide.iter().any(|v| v.abs() > limit * 0.9));
+        assert!(init.bias(4).iter().all(|&v| v == 0.0));
+
+        let mut init = Initializer::new(InitScheme::He, 1);
+        let limit = (6.0f32 / 16.0).sqrt();
+        assert!(init.matrix(16, 64).iter().all(|v| v.abs() <= limit));
+
+        let mut init = Initializer::new(InitScheme::Uniform, 1);
+        assert!(init
+            .matrix(256, 256)
+            .iter()
+            .all(|v| v.abs() <= UNIFORM_LIMIT));
+        assert!(init.bias(4).iter().all(|&v| v != 0.0));
+    }
+}
@@ -0,0 +1,243 @@
+//! Matrix products of the transformer
+//!
+//! Every linear layer, attention projection and score product and the output
+//! head go through [`matmul`] and [`add_matmul`]. By default they are
+//! ndarray's single-threaded products; the `blas` feature runs them on the
+//! `gemm` crate's BLAS-style kernels instead, which use the widest SIMD the
+//! CPU offers and split large products across rayon's threads, without
+//! needing a system BLAS library. The `candle` feature runs large products on
+//! candle's CPU kernels instead; small products stay on the host path, where
+//! copying into tensors would cost more than it saves.
+//!
+//! Neither feature is a separate model backend: the transformer, its weights
+//! and the training loop stay on ndarray, and every product handed to candle
+//! copies its operands in and the result out. A product candle fails to
+//! compute falls back to the host path.
+
+use ndarray::{Array2, ArrayBase, Data, DataMut, Ix2};
+
+/// `a · b`
+pub(crate) fn matmul<A, B>(a: &ArrayBase<A, Ix2>, b: &ArrayBase<B, Ix2>) -> Array2<f32>
+where
+    A: Data<Elem = f32>,
+    B: Data<Elem = f32>,
+{
+    let mut product = Array2::zeros((a.nrows(), b.ncols()));
+    gemm_into(&mut product, a, b, false);
+    product
+}
+
+/// `dst += a · b`, without allocating the product
+pub(crate) fn add_matmul<D, A, B>(
+    dst: &mut ArrayBase<D, Ix2>,
+    a: &ArrayBase<A, Ix2>,
+    b: &ArrayBase<B, Ix2>,
+) where
+    D: DataMut<Elem = f32>,
+    A: Data<Elem = f32>,
+    B: Data<Elem = f32>,
+{
+    gemm_into(dst, a, b, true);
+}
+
+#[cfg(not(feature = "candle"))]
+fn gemm_into<D, A, B>(
+    dst: &mut ArrayBase<D, Ix2>,
+    a: &ArrayBase<A, Ix2>,
+    b: &ArrayBase<B, Ix2>,
+    accumulate: bool,
+) where
+    D: DataMut<Elem = f32>,
+    A: Data<Elem = f32>,
+    B: Data<Elem = f32>,
+{
+    host_gemm_into(dst, a, b, accumulate);
+}
+
+/// Products with fewer multiply-adds than this skip the copies into candle
+#[cfg(feature = "candle")]
+const CANDLE_THRESHOLD: usize = 1 << 18;
+
+#[cfg(feature = "candle")]
+fn gemm_into<D, A, B>(
+    dst: &mut ArrayBase<D, Ix2>,
+    a: &ArrayBase<A, Ix2>,
+    b: &ArrayBase<B, Ix2>,
+    accumulate: bool,
+) where
+    D: DataMut<Elem = f32>,
+    A: Data<Elem = f32>,
+    B: Data<Elem = f32>,
+{
+    let ((m, k), n) = (a.dim(), b.ncols());
+    if m * n * k < CANDLE_THRESHOLD {
+        return host_gemm_into(dst, a, b, accumulate);
+    }
+    let product = match candle_backend::matmul(a, b) {
+        Ok(product) => product,
+        Err(e) => {
+            candle_backend::warn_fallback(&e);
+            return host_gemm_into(dst, a, b, accumulate);
+        }
+    };
+    if accumulate {
+        *dst += &product;
+    } else {
+        dst.assign(&product);
+    }
+}
+
+#[cfg(feature = "candle")]
+pub(crate) mod candle_backend {
+    use candle_core::{Device, Tensor};
+    use ndarray::{Array2, ArrayBase, Data, Ix2};
+    use std::sync::Once;
+
+    /// Report the first product candle failed, which then ran on the host
+    pub(crate) fn warn_fallback(error: &candle_core::Error) {
+        static WARNED: Once = Once::new();
+        WARNED.call_once(|| {
+            tracing::warn!(
+                "candle matrix product failed, computing on the host instead: {}",
+                error
+            )
+        });
+    }
+
+    fn to_tensor<S: Data<Elem = f32>>(array: &ArrayBase<S, Ix2>) -> candle_core::Result<Tensor> {
+        // Iteration is in logical row-major order whatever the strides
+        let data: Vec<f32> = array.iter().copied().collect();
+        Tensor::from_vec(data, array.dim(), &Device::Cpu)
+    }
+
+    /// `a · b` on candle's CPU kernels
+    pub(crate) fn matmul<A, B>(
+        a: &ArrayBase<A, Ix2>,
+        b: &ArrayBase<B, Ix2>,
+    ) -> candle_core::Result<Array2<f32>>
+    where
+        A: Data<Elem = f32>,
+        B: Data<Elem = f32>,
+    {
+        let product = to_tensor(a)?.matmul(&to_tensor(b)?)?;
+        let data = product.flatten_all()?.to_vec1::<f32>()?;
+        Ok(Array2::from_shape_vec((a.nrows(), b.ncols()), data)
+            .expect("candle product has the operands' outer dimensions"))
+    }
+}
+
+#[cfg(not(feature = "blas"))]
+fn host_gemm_into<D, A, B>(
+    dst: &mut ArrayBase<D, Ix2>,
+    a: &ArrayBase<A, Ix2>,
+    b: &ArrayBase<B, Ix2>,
+    accumulate: bool,
+) where
+    D: DataMut<Elem = f32>,
+    A: Data<Elem = f32>,
+    B: Data<Elem = f32>,
+{
+    let beta = if accumulate { 1.0 } else { 0.0 };
+    ndarray::linalg::general_mat_mul(1.0, a, b, beta, dst);
+}
+
+/// Products with fewer multiply-adds than this stay on the calling thread
+#[cfg(feature = "blas")]
+const PARALLEL_THRESHOLD: usize = 1 << 18;
+
+#[cfg(feature = "blas")]
+fn host_gemm_into<D, A, B>(
+    dst: &mut ArrayBase<D, Ix2>,
+    a: &ArrayBase<A, Ix2>,
+    b: &ArrayBase<B, Ix2>,
+    accumulate: bool,
+) where
+    D: DataMut<Elem = f32>,
+    A: Data<Elem = f32>,
+    B: Data<Elem = f32>,
+{
+    let ((m, k), n) = (a.dim(), b.ncols());
+    assert_eq!(b.nrows(), k, "inner dimensions differ");
+    assert_eq!(dst.dim(), (m, n), "destination shape differs");
+    let parallelism = if m * n * k >= PARALLEL_THRESHOLD {
+        gemm::Parallelism::Rayon(0)
+    } else {
+        gemm::Parallelism::None
+    };
+    let (dst_strides, a_strides, b_strides) = (dst.strides(), a.strides(), b.strides());
+    let (dst_rs, dst_cs) = (dst_strides[0], dst_strides[1]);
+    let (a_rs, a_cs) = (a_strides[0], a_strides[1]);
+    let (b_rs, b_cs) = (b_strides[0], b_strides[1]);
+    // SAFETY: the shapes are checked above and the strides are those of the
+    // arrays the pointers come from; `dst` is borrowed mutably, so it
+    // aliases neither input
+    unsafe {
+        gemm::gemm(
+            m,
+            n,
+            k,
+            dst.as_mut_ptr(),
+            dst_cs,
+            dst_rs,
+            accumulate,
+            a.as_ptr(),
+            a_cs,
+            a_rs,
+            b.as_ptr(),
+            b_cs,
+            b_rs,
+            1.0,
+            1.0,
+            false,
+            false,
+            false,
+            parallelism,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_products_match_dot() {
+        let a = Array2::from_shape_fn((5, 7), |(i, j)| (i * 7 + j) as f32 * 0.1 - 1.0);
+        let b = Array2::from_shape_fn((5, 3), |(i, j)| (i + 2 * j) as f32 * 0.3);
+        let expected = a.t().dot(&b);
+        let close = |x: &Array2<f32>, y: &Array2<f32>| {
+            x.shape() == y.shape() && x.iter().zip(y).all(|(x, y)| (x - y).abs() < 1e-4)
+        };
+
+        assert!(close(&matmul(&a.t(), &b), &expected));
+
+        let mut sum = Array2::from_elem((7, 3), 1.0);
+        add_matmul(&mut sum, &a.t(), &b);
+        assert!(close(&sum, &(expected + 1.0)));
+
+        // Into a strided view, with an empty inner dimension
+        let mut wide = Array2::from_elem((7, 6), 2.0);
+        let mut columns = wide.slice_mut(ndarray::s![.., ..;2]);
+        add_matmul(&mut columns, &Array2::zeros((7, 0)), &Array2::zeros((0, 3)));
+        assert!(wide.iter().all(|&x| x == 2.0));
+    }
+
+    #[cfg(feature = "candle")]
+    #[test]
+    fn test_candle_products_match_host() {
+        let a = Array2::from_shape_fn((96, 80), |(i, j)| ((i * 31 + j * 17) % 13) as f32 * 0.05);
+        let b = Array2::from_shape_fn((96, 72), |(i, j)| ((i * 7 + j * 3) % 11) as f32 * 0.1 - 0.5);
+        let mut expected = Array2::zeros((80, 72));
+        host_gemm_into(&mut expected, &a.t(), &b, false);
+
+        let product = candle_backend::matmul(&a.t(), &b).unwrap();
+        assert!(product.iter().zip(&expected).all(|(x, y)| (x - y).abs() < 1e-3));
+
+        // Above the threshold, through the public entry points
+        let a = Array2::from_elem((128, 64), 0.5);
+        let b = Array2::from_elem((64, 64), 0.25);
+        let mut sum = Array2::from_elem((128, 64), 1.0);
+        add_matmul(&mut sum, &a, &b);
+        assert!(sum.iter().all(|&x| (x - 9.0).abs() < 1e-4));
+    }
+}
@@ -3,20 +3,154 @@
 //! Implements an encoder-decoder transformer tailored for WGSL token sequences.
 
 pub mod attention;
+pub mod attention_maps;
+pub mod checkpoint;
 pub mod decoder;
+pub mod diagnostics;
+mod embedding;
 pub mod encoder;
+mod init;
+mod linalg;
+pub mod pretrained;
+pub mod quantize;
+mod storage;
+pub mod summary;
+pub mod weights;
 
-use crate::config::ModelConfig;
+use crate::config::{
+    Activation, AttentionConfig, Dtype, InitScheme, ModelConfig, NormPlacement, PositionalEncoding,
+};
 use crate::tokenizer::SpecialToken;
-use ndarray::{s, Array1, Array2};
-use rand::{distributions::Uniform, rngs::StdRng, Rng, SeedableRng};
+use ndarray::{s, Array, Array1, Array2, Axis, Dimension};
 use serde::{Deserialize, Serialize};
 
-use decoder::DecoderLayer;
-use encoder::EncoderLayer;
+use attention::AttentionMask;
+pub use attention_maps::AttentionMap;
+pub use checkpoint::{Checkpoint, CheckpointErrorKind, CheckpointMetadata};
+use decoder::{DecoderCache, DecoderLayer};
+use embedding::Table;
+use encoder::{EncoderCache, EncoderLayer};
+use init::Initializer;
+use linalg::{add_matmul, matmul};
+pub use quantize::QuantizedCheckpoint;
+use storage::{ModelOptions, SerializedModel};
+
+/// Named access to every trainable tensor of a module, in a fixed order
+pub(crate) trait Parameters {
+    fn visit(&self, prefix: &str, f: &mut dyn FnMut(&str, &[f32]));
+    fn visit_mut(&mut self, prefix: &str, f: &mut dyn FnMut(&str, &mut [f32]));
+    fn visit_shapes(&self, prefix: &str, f: &mut dyn FnMut(&str, &[usize]));
+}
+
+impl<D: Dimension> Parameters for Array<f32, D> {
+    fn visit(&self, prefix: &str, f: &mut dyn FnMut(&str, &[f32])) {
+        f(prefix, self.as_slice().expect("parameters are contiguous"));
+    }
+
+    fn visit_mut(&mut self, prefix: &str, f: &mut dyn FnMut(&str, &mut [f32])) {
+        f(
+            prefix,
+            self.as_slice_mut().expect("parameters are contiguous"),
+        );
+    }
+
+    fn visit_shapes(&self, prefix: &str, f: &mut dyn FnMut(&str, &[usize])) {
+        f(prefix, self.shape());
+    }
+}
+
+impl<T: Parameters> Parameters for Vec<T> {
+    fn visit(&self, prefix: &str, f: &mut dyn FnMut(&str, &[f32])) {
+        for (i, item) in self.iter().enumerate() {
+            item.visit(&param_name(prefix, &i.to_string()), f);
+        }
+    }
+
+    fn visit_mut(&mut self, prefix: &str, f: &mut dyn FnMut(&str, &mut [f32])) {
+        for (i, item) in self.iter_mut().enumerate() {
+            item.visit_mut(&param_name(prefix, &i.to_string()), f);
+        }
+    }
+
+    fn visit_shapes(&self, prefix: &str, f: &mut dyn FnMut(&str, &[usize])) {
+        for (i, item) in self.iter().enumerate() {
+            item.visit_shapes(&param_name(prefix, &i.to_string()), f);
+        }
+    }
+}
+
+impl<T: Parameters> Parameters for Option<T> {
+    fn visit(&self, prefix: &str, f: &mut dyn FnMut(&str, &[f32])) {
+        if let Some(item) = self {
+            item.visit(prefix, f);
+        }
+    }
+
+    fn visit_mut(&mut self, prefix: &str, f: &mut dyn FnMut(&str, &mut [f32])) {
+        if let Some(item) = self {
+            item.visit_mut(prefix, f);
+        }
+    }
+
+    fn visit_shapes(&self, prefix: &str, f: &mut dyn FnMut(&str, &[usize])) {
+        if let Some(item) = self {
+            item.visit_shapes(prefix, f);
+        }
+    }
+}
+
+pub(crate) fn param_name(prefix: &str, name: &str) -> String {
+    if prefix.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}.{}", prefix, name)
+    }
+}
+
+/// Implements [`Parameters`] by visiting the listed fields, named after the
+/// field unless renamed with `as "name"`
+macro_rules! parameters {
+    ($ty:ty { $($field:ident $(as $name:literal)?),* $(,)? }) => {
+        impl $crate::model::Parameters for $ty {
+            fn visit(&self, prefix: &str, f: &mut dyn FnMut(&str, &[f32])) {
+                $(
+                    $crate::model::Parameters::visit(
+                        &self.$field,
+                        &$crate::model::param_name(prefix, $crate::model::parameters!(@name $field $($name)?)),
+                        f,
+                    );
+                )*
+            }
+
+            fn visit_mut(&mut self, prefix: &str, f: &mut dyn FnMut(&str, &mut [f32])) {
+                $(
+                    $crate::model::Parameters::visit_mut(
+                        &mut self.$field,
+                        &$crate::model::param_name(prefix, $crate::model::parameters!(@name $field $($name)?)),
+                        f,
+                    );
+                )*
+            }
+
+            fn visit_shapes(&self, prefix: &str, f: &mut dyn FnMut(&str, &[usize])) {
+                $(
+                    $crate::model::Parameters::visit_shapes(
+                        &self.$field,
+                        &$crate::model::param_name(prefix, $crate::model::parameters!(@name $field $($name)?)),
+                        f,
+                    );
+                )*
+            }
+        }
+    };
+    (@name $field:ident) => { stringify!($field) };
+    (@name $field:ident $name:literal) => { $name };
+}
+pub(crate) use parameters;
 
 const DEFAULT_MAX_SEQ_LEN: usize = 512;
 const DEFAULT_DIM_FEEDFORWARD: usize = 2048;
+const DEFAULT_SEED: u64 = 42;
 
 /// Model architecture types
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -26,7 +160,10 @@ pub enum ModelArchitecture {
 }
 
 /// Neural network model for code generation
-#[derive(Debug, Clone)]
+///
+/// Serialized as its hyperparameters and named parameters, see [`storage`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(into = "SerializedModel", try_from = "SerializedModel")]
 pub struct CodeGenerationModel {
     pub architecture: ModelArchitecture,
     pub vocab_size: usize,
@@ -35,9 +172,37 @@ pub struct CodeGenerationModel {
     pub num_layers: usize,
     pub max_seq_len: usize,
     pub dim_feedforward: usize,
+    pub activation: Activation,
+    pub norm_placement: NormPlacement,
+    pub positional_encoding: PositionalEncoding,
+    /// Blocked and sliding-window attention
+    pub attention: AttentionConfig,
+    /// Storage of the token embeddings and position table
+    pub embedding_dtype: Dtype,
+    /// Scheme the current weights were initialized with
+    pub init: InitScheme,
+    /// Seed the current weights were initialized from
+    seed: u64,
     transformer: Option<Transformer>,
 }
 
+/// Sizes and architecture choices a [`Transformer`] is built from
+#[derive(Debug, Clone, Copy)]
+struct TransformerSpec {
+    vocab_size: usize,
+    d_model: usize,
+    nhead: usize,
+    num_layers: usize,
+    max_seq_len: usize,
+    dim_feedforward: usize,
+    activation: Activation,
+    norm_placement: NormPlacement,
+    positional_encoding: PositionalEncoding,
+    attention: AttentionConfig,
+    embedding_dtype: Dtype,
+    init: InitScheme,
+}
+
 impl CodeGenerationModel {
     /// Create a new model with the given configuration
     pub fn new(
@@ -49,19 +214,7 @@ impl CodeGenerationModel {
         dim_feedforward: Option<usize>,
         max_seq_len: Option<usize>,
     ) -> Self {
-        let transformer = match architecture {
-            ModelArchitecture::Transformer => Some(Transformer::new(
-                vocab_size,
-                d_model,
-                nhead,
-                num_layers,
-                max_seq_len.unwrap_or(DEFAULT_MAX_SEQ_LEN),
-                dim_feedforward.unwrap_or(DEFAULT_DIM_FEEDFORWARD),
-            )),
-            ModelArchitecture::LSTM => None,
-        };
-
-        Self {
+        let mut model = Self {
             architecture,
             vocab_size,
             d_model,
@@ -69,8 +222,17 @@ impl CodeGenerationModel {
             num_layers,
             max_seq_len: max_seq_len.unwrap_or(DEFAULT_MAX_SEQ_LEN),
             dim_feedforward: dim_feedforward.unwrap_or(DEFAULT_DIM_FEEDFORWARD),
-            transformer,
-        }
+            activation: Activation::default(),
+            norm_placement: NormPlacement::default(),
+            positional_encoding: PositionalEncoding::default(),
+            attention: AttentionConfig::default(),
+            embedding_dtype: Dtype::default(),
+            init: InitScheme::default(),
+            seed: DEFAULT_SEED,
+            transformer: None,
+        };
+        model.initialize();
+        model
     }
 
     /// Create a model from a [`ModelConfig`], applying production defaults when
@@ -97,6 +259,116 @@ impl CodeGenerationModel {
             Some(config.dim_feedforward),
             Some(config.max_seq_len),
         )
+        .with_options(&ModelOptions {
+            activation: config.activation,
+            norm_placement: config.norm_placement,
+            positional_encoding: config.positional_encoding,
+            attention: config.attention,
+            embedding_dtype: config.embedding_dtype,
+            init: config.init.scheme,
+        })
+    }
+
+    /// Re-initialize the weights from `seed`, so models built with the same
+    /// seed start out identical
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self.initialize();
+        self
+    }
+
+    /// Switch the feed-forward activation, re-initializing the weights
+    pub fn with_activation(mut self, activation: Activation) -> Self {
+        self.activation = activation;
+        self.initialize();
+        self
+    }
+
+    /// Switch between post- and pre-norm blocks, re-initializing the weights
+    pub fn with_norm_placement(mut self, norm_placement: NormPlacement) -> Self {
+        self.norm_placement = norm_placement;
+        self.initialize();
+        self
+    }
+
+    /// Switch how positions are encoded, re-initializing the weights
+    pub fn with_positional_encoding(mut self, positional_encoding: PositionalEncoding) -> Self {
+        self.positional_encoding = positional_encoding;
+        self.initialize();
+        self
+    }
+
+    /// Switch blocked or sliding-window attention, re-initializing the weights
+    pub fn with_attention(mut self, attention: AttentionConfig) -> Self {
+        self.attention = attention;
+        self.initialize();
+        self
+    }
+
+    /// Store the token embeddings and position table as `dtype`, converting
+    /// the current values
+    pub fn with_embedding_dtype(mut self, dtype: Dtype) -> Self {
+        self.embedding_dtype = dtype;
+        if let Some(transformer) = &mut self.transformer {
+            transformer.token_embedding = transformer.token_embedding.to_dtype(dtype);
+            if let Some(encoding) = &mut transformer.positional_encoding {
+                *encoding = encoding.to_dtype(dtype);
+            }
+        }
+        self
+    }
+
+    /// Switch the weight initialization scheme, re-initializing the weights
+    pub fn with_init(mut self, init: InitScheme) -> Self {
+        self.init = init;
+        self.initialize();
+        self
+    }
+
+    fn options(&self) -> ModelOptions {
+        ModelOptions {
+            activation: self.activation,
+            norm_placement: self.norm_placement,
+            positional_encoding: self.positional_encoding,
+            attention: self.attention,
+            embedding_dtype: self.embedding_dtype,
+            init: self.init,
+        }
+    }
+
+    fn with_options(mut self, options: &ModelOptions) -> Self {
+        self.activation = options.activation;
+        self.norm_placement = options.norm_placement;
+        self.positional_encoding = options.positional_encoding;
+        self.attention = options.attention;
+        self.embedding_dtype = options.embedding_dtype;
+        self.init = options.init;
+        self.initialize();
+        self
+    }
+
+    /// Build fresh weights from the hyperparameters and seed
+    fn initialize(&mut self) {
+        self.transformer = match self.architecture {
+            ModelArchitecture::Transformer => Some(Transformer::new(
+                &TransformerSpec {
+                    vocab_size: self.vocab_size,
+                    d_model: self.d_model,
+                    nhead: self.nhead,
+                    num_layers: self.num_layers,
+                    max_seq_len: self.max_seq_len,
+                    dim_feedforward: self.dim_feedforward,
+                    activation: self.activation,
+                    norm_placement: self.norm_placement,
+                    positional_encoding: self.positional_encoding,
+                    attention: self.attention,
+                    embedding_dtype: self.embedding_dtype,
+                    init: self.init,
+                },
+                self.seed,
+            )),
+            ModelArchitecture::LSTM => None,
+        };
     }
 
     /// Forward pass through the underlying model.
@@ -123,6 +395,198 @@ impl CodeGenerationModel {
         }
     }
 
+    /// Run the encoder over the input tokens once, for reuse across decoding steps
+    pub fn encode(&self, input_ids: &[usize]) -> EncodedInput {
+        match &self.transformer {
+            Some(transformer) => transformer.encode(input_ids),
+            None => EncodedInput {
+                ids: input_ids.to_vec(),
+                states: Array2::zeros((input_ids.len(), self.d_model)),
+            },
+        }
+    }
+
+    /// Logits for every decoder position, shape `(decoder_ids.len(), vocab_size)`
+    ///
+    /// Row `i` predicts the token following `decoder_ids[..=i]`. Decoder input
+    /// longer than `max_seq_len` is truncated.
+    pub fn decode(&self, encoded: &EncodedInput, decoder_ids: &[usize]) -> Array2<f32> {
+        match &self.transformer {
+            Some(transformer) => transformer.decode(encoded, decoder_ids),
+            None => Array2::zeros((decoder_ids.len(), self.vocab_size)),
+        }
+    }
+
+    /// [`encode`](Self::encode) every input in one encoder pass over the
+    /// inputs padded to a common length
+    ///
+    /// Padding is masked and cut off again, so each result matches encoding
+    /// its input alone.
+    pub fn encode_batch(&self, inputs: &[Vec<usize>]) -> Vec<EncodedInput> {
+        match &self.transformer {
+            Some(transformer) => transformer.encode_batch(inputs),
+            None => inputs.iter().map(|ids| self.encode(ids)).collect(),
+        }
+    }
+
+    /// [`decode`](Self::decode) every pair of encoded input and decoder
+    /// input in one decoder pass, padding both to a common length
+    ///
+    /// Padding is masked and its rows are cut from the logits, so each
+    /// result matches decoding the pair alone.
+    pub fn decode_batch(
+        &self,
+        encoded: &[&EncodedInput],
+        decoder_ids: &[&[usize]],
+    ) -> Vec<Array2<f32>> {
+        assert_eq!(
+            encoded.len(),
+            decoder_ids.len(),
+            "one decoder input per encoded input"
+        );
+        match &self.transformer {
+            Some(transformer) => transformer.decode_batch(encoded, decoder_ids),
+            None => decoder_ids
+                .iter()
+                .map(|ids| Array2::zeros((ids.len(), self.vocab_size)))
+                .collect(),
+        }
+    }
+
+    /// Summed negative log likelihood of `target_ids` given `input_ids` under
+    /// teacher forcing, and the number of tokens scored
+    ///
+    /// The decoder reads `<sos>` followed by the targets and is scored on
+    /// predicting each target and then `<eos>`. Targets are truncated so the
+    /// decoder input fits in `max_seq_len`.
+    pub fn sequence_nll(&self, input_ids: &[usize], target_ids: &[usize]) -> (f64, usize) {
+        let (decoder_ids, labels) = self.teacher_forcing(target_ids);
+        let logits = self.decode(&self.encode(input_ids), &decoder_ids);
+        let nll = logits
+            .rows()
+            .into_iter()
+            .zip(&labels)
+            .map(|(row, &label)| {
+                let max = row.fold(f32::NEG_INFINITY, |a, &b| a.max(b)) as f64;
+                let log_sum = row
+                    .iter()
+                    .map(|&x| (x as f64 - max).exp())
+                    .sum::<f64>()
+                    .ln()
+                    + max;
+                log_sum - row[label.min(self.vocab_size - 1)] as f64
+            })
+            .sum();
+        (nll, labels.len())
+    }
+
+    /// Like [`sequence_nll`](Self::sequence_nll), additionally adding the
+    /// gradient of the summed NLL with respect to every parameter to `grads`
+    pub fn accumulate_gradients(
+        &self,
+        input_ids: &[usize],
+        target_ids: &[usize],
+        grads: &mut Gradients,
+    ) -> (f64, usize) {
+        let (decoder_ids, labels) = self.teacher_forcing(target_ids);
+        match (&self.transformer, &mut grads.transformer) {
+            (Some(transformer), Some(grads)) => {
+                let nll = transformer.backward(input_ids, &decoder_ids, &labels, None, grads);
+                (nll, labels.len())
+            }
+            _ => self.sequence_nll(input_ids, target_ids),
+        }
+    }
+
+    /// Like [`accumulate_gradients`](Self::accumulate_gradients), with the
+    /// loss mixing the hard cross-entropy, weighted `1 - alpha`, with the KL
+    /// divergence from `teacher`'s predictions softened by `temperature`,
+    /// weighted `alpha * temperature²`
+    ///
+    /// `teacher` must share this model's vocabulary. Returns the hard NLL and
+    /// the number of tokens scored, so losses stay comparable to plain
+    /// training.
+    pub fn accumulate_distillation_gradients(
+        &self,
+        teacher: &CodeGenerationModel,
+        input_ids: &[usize],
+        target_ids: &[usize],
+        alpha: f32,
+        temperature: f32,
+        grads: &mut Gradients,
+    ) -> (f64, usize) {
+        let (decoder_ids, labels) = self.teacher_forcing(target_ids);
+        match (&self.transformer, &mut grads.transformer) {
+            (Some(transformer), Some(grads)) => {
+                let soft = SoftTargets {
+                    probabilities: tempered_softmax(
+                        teacher.decode(&teacher.encode(input_ids), &decoder_ids),
+                        temperature,
+                    ),
+                    weight: alpha,
+                    temperature,
+                };
+                let nll =
+                    transformer.backward(input_ids, &decoder_ids, &labels, Some(&soft), grads);
+                (nll, labels.len())
+            }
+            _ => self.sequence_nll(input_ids, target_ids),
+        }
+    }
+
+    /// Zero-valued gradients shaped like this model's parameters
+    pub fn zero_gradients(&self) -> Gradients {
+        let mut transformer = self.transformer.clone();
+        if let Some(transformer) = &mut transformer {
+            // Accumulate in full precision whatever the embeddings are stored as
+            transformer.token_embedding = transformer.token_embedding.to_dtype(Dtype::F32);
+            transformer.positional_encoding = None;
+            transformer.visit_mut("", &mut |_, values| values.fill(0.0));
+        }
+        Gradients { transformer }
+    }
+
+    /// Call `f` with the name and values of every trainable tensor
+    ///
+    /// Tensors are visited in a fixed order with dotted names such as
+    /// `encoder.0.self_attn.w_q`.
+    pub fn visit_parameters(&self, f: &mut dyn FnMut(&str, &[f32])) {
+        if let Some(transformer) = &self.transformer {
+            transformer.visit("", f);
+        }
+    }
+
+    /// Mutable counterpart of [`visit_parameters`](Self::visit_parameters)
+    pub fn visit_parameters_mut(&mut self, f: &mut dyn FnMut(&str, &mut [f32])) {
+        if let Some(transformer) = &mut self.transformer {
+            transformer.visit_mut("", f);
+        }
+    }
+
+    /// Name and shape of every trainable tensor, in
+    /// [`visit_parameters`](Self::visit_parameters) order
+    pub fn parameter_shapes(&self) -> Vec<(String, Vec<usize>)> {
+        let mut shapes = Vec::new();
+        if let Some(transformer) = &self.transformer {
+            transformer.visit_shapes("", &mut |name, shape| {
+                shapes.push((name.to_string(), shape.to_vec()))
+            });
+        }
+        shapes
+    }
+
+    /// Decoder input (`<sos>` + targets) and labels (targets + `<eos>`),
+    /// truncated so the decoder input fits in `max_seq_len`
+    fn teacher_forcing(&self, target_ids: &[usize]) -> (Vec<usize>, Vec<usize>) {
+        let kept = target_ids.len().min(self.max_seq_len.saturating_sub(1));
+        let mut decoder_ids = Vec::with_capacity(kept + 1);
+        decoder_ids.push(SpecialToken::StartOfSequence.token_id());
+        decoder_ids.extend_from_slice(&target_ids[..kept]);
+        let mut labels = target_ids[..kept].to_vec();
+        labels.push(SpecialToken::EndOfSequence.token_id());
+        (decoder_ids, labels)
+    }
+
     /// Get number of parameters
     pub fn num_parameters(&self) -> usize {
         match self.architecture {
@@ -134,108 +598,468 @@ impl CodeGenerationModel {
             ModelArchitecture::LSTM => 0,
         }
     }
+
+    /// Bytes the parameters take in memory
+    pub fn parameter_bytes(&self) -> u64 {
+        (self.num_parameters() * std::mem::size_of::<f32>()) as u64
+    }
 }
 
+/// Gradients of a loss with respect to every parameter of a
+/// [`CodeGenerationModel`], with the same names and shapes
 #[derive(Debug, Clone)]
+pub struct Gradients {
+    transformer: Option<Transformer>,
+}
+
+impl Gradients {
+    /// Call `f` with the name and gradient of every trainable tensor, in the
+    /// order of [`CodeGenerationModel::visit_parameters`]
+    pub fn visit(&self, f: &mut dyn FnMut(&str, &[f32])) {
+        if let Some(transformer) = &self.transformer {
+            transformer.visit("", f);
+        }
+    }
+
+    /// Mutable counterpart of [`visit`](Self::visit)
+    pub fn visit_mut(&mut self, f: &mut dyn FnMut(&str, &mut [f32])) {
+        if let Some(transformer) = &mut self.transformer {
+            transformer.visit_mut("", f);
+        }
+    }
+
+    /// Add `other`, gradients of the same model, to these
+    pub fn accumulate(&mut self, other: &Gradients) {
+        let mut values = Vec::new();
+        other.visit(&mut |_, v| values.extend_from_slice(v));
+        let mut offset = 0;
+        self.visit_mut(&mut |_, v| {
+            for (sum, value) in v.iter_mut().zip(&values[offset..]) {
+                *sum += value;
+            }
+            offset += v.len();
+        });
+    }
+
+    /// Multiply every gradient by `factor`
+    pub fn scale(&mut self, factor: f32) {
+        self.visit_mut(&mut |_, values| values.iter_mut().for_each(|v| *v *= factor));
+    }
+
+    /// L2 norm over all gradients
+    pub fn global_norm(&self) -> f64 {
+        let mut sum = 0.0f64;
+        self.visit(&mut |_, values| {
+            sum += values.iter().map(|&v| (v as f64) * (v as f64)).sum::<f64>();
+        });
+        sum.sqrt()
+    }
+}
+
+/// A teacher's predictions mixed into the loss when distilling
+struct SoftTargets {
+    /// Teacher distribution at `temperature` for each decoder position
+    probabilities: Array2<f32>,
+    /// Share of the loss given to matching the teacher
+    weight: f32,
+    temperature: f32,
+}
+
+/// Row-wise softmax of `logits / temperature`
+fn tempered_softmax(mut logits: Array2<f32>, temperature: f32) -> Array2<f32> {
+    for mut row in logits.rows_mut() {
+        let softmax = softmax_vec(row.iter().map(|&x| x / temperature).collect());
+        row.assign(&Array1::from(softmax));
+    }
+    logits
+}
+
+/// Sequences right-padded to the longest one and concatenated, with the
+/// padded length
+fn pad_batch(sequences: &[Vec<usize>]) -> (Vec<usize>, usize) {
+    let len = sequences.iter().map(Vec::len).max().unwrap_or(0);
+    let mut padded = vec![SpecialToken::Padding.token_id(); sequences.len() * len];
+    for (index, ids) in sequences.iter().enumerate() {
+        padded[index * len..index * len + ids.len()].copy_from_slice(ids);
+    }
+    (padded, len)
+}
+
+/// Encoder output for one input sequence
+#[derive(Debug, Clone)]
+pub struct EncodedInput {
+    ids: Vec<usize>,
+    states: Array2<f32>,
+}
+
+impl EncodedInput {
+    /// Average of the encoder states over the non-padding positions, of
+    /// length `d_model`; zeros when every position is padding
+    pub fn mean_pool(&self) -> Vec<f32> {
+        let mut sum = Array1::<f32>::zeros(self.states.ncols());
+        let mut count = 0;
+        for (row, &id) in self.states.rows().into_iter().zip(&self.ids) {
+            if id != SpecialToken::Padding.token_id() {
+                sum += &row;
+                count += 1;
+            }
+        }
+        if count > 0 {
+            sum /= count as f32;
+        }
+        sum.to_vec()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 struct Transformer {
     vocab_size: usize,
     d_model: usize,
-    nhead: usize,
-    num_layers: usize,
     max_seq_len: usize,
-    dim_feedforward: usize,
-    token_embedding: Array2<f32>,
-    positional_encoding: Array2<f32>,
+    token_embedding: Table,
+    /// Absolute position vectors; `None` with rotary embeddings
+    positional_encoding: Option<Table>,
     encoder_layers: Vec<EncoderLayer>,
+    /// Normalization of the encoder output, for pre-norm blocks
+    encoder_norm: Option<LayerNorm>,
     decoder_layers: Vec<DecoderLayer>,
+    /// Normalization of the decoder output, for pre-norm blocks
+    decoder_norm: Option<LayerNorm>,
     final_linear_weight: Array2<f32>,
     final_linear_bias: Array1<f32>,
 }
 
+impl Parameters for Transformer {
+    fn visit(&self, prefix: &str, f: &mut dyn FnMut(&str, &[f32])) {
+        let name = |field| param_name(prefix, field);
+        self.token_embedding.visit(&name("token_embedding"), f);
+        self.encoder_layers.visit(&name("encoder"), f);
+        self.encoder_norm.visit(&name("encoder_norm"), f);
+        self.decoder_layers.visit(&name("decoder"), f);
+        self.decoder_norm.visit(&name("decoder_norm"), f);
+        Parameters::visit(&self.final_linear_weight, &name("output.weight"), f);
+        Parameters::visit(&self.final_linear_bias, &name("output.bias"), f);
+    }
+
+    fn visit_mut(&mut self, prefix: &str, f: &mut dyn FnMut(&str, &mut [f32])) {
+        let name = |field| param_name(prefix, field);
+        self.token_embedding.visit_mut(&name("token_embedding"), f);
+        self.encoder_layers.visit_mut(&name("encoder"), f);
+        self.encoder_norm.visit_mut(&name("encoder_norm"), f);
+        self.decoder_layers.visit_mut(&name("decoder"), f);
+        self.decoder_norm.visit_mut(&name("decoder_norm"), f);
+        self.final_linear_weight
+            .visit_mut(&name("output.weight"), f);
+        self.final_linear_bias.visit_mut(&name("output.bias"), f);
+    }
+
+    fn visit_shapes(&self, prefix: &str, f: &mut dyn FnMut(&str, &[usize])) {
+        let name = |field| param_name(prefix, field);
+        self.token_embedding
+            .visit_shapes(&name("token_embedding"), f);
+        self.encoder_layers.visit_shapes(&name("encoder"), f);
+        self.encoder_norm.visit_shapes(&name("encoder_norm"), f);
+        self.decoder_layers.visit_shapes(&name("decoder"), f);
+        self.decoder_norm.visit_shapes(&name("decoder_norm"), f);
+        self.final_linear_weight
+            .visit_shapes(&name("output.weight"), f);
+        self.final_linear_bias.visit_shapes(&name("output.bias"), f);
+    }
+}
+
 impl Transformer {
-    fn new(
-        vocab_size: usize,
-        d_model: usize,
-        nhead: usize,
-        num_layers: usize,
-        max_seq_len: usize,
-        dim_feedforward: usize,
-    ) -> Self {
-        assert!(d_model % nhead == 0, "d_model must be divisible by nhead");
+    fn new(spec: &TransformerSpec, seed: u64) -> Self {
+        let TransformerSpec {
+            vocab_size,
+            d_model,
+            nhead,
+            num_layers,
+            max_seq_len,
+            positional_encoding,
+            ..
+        } = *spec;
+        assert!(
+            d_model.is_multiple_of(nhead),
+            "d_model must be divisible by nhead"
+        );
 
-        let mut rng = StdRng::seed_from_u64(42);
-        let dist = Uniform::new(-0.1f32, 0.1f32);
+        let mut init = Initializer::new(spec.init, seed);
 
-        let token_embedding = Array2::from_shape_fn((vocab_size, d_model), |_| rng.sample(dist));
-        let positional_encoding = Self::create_positional_encoding(max_seq_len, d_model);
+        let token_embedding = Table::new(init.embedding(vocab_size, d_model), spec.embedding_dtype);
+        let positional_encoding =
+            (positional_encoding == PositionalEncoding::Sinusoidal).then(|| {
+                Table::new(
+                    Self::create_positional_encoding(max_seq_len, d_model),
+                    spec.embedding_dtype,
+                )
+            });
 
         let mut encoder_layers = Vec::with_capacity(num_layers);
         let mut decoder_layers = Vec::with_capacity(num_layers);
 
         for _ in 0..num_layers {
-            encoder_layers.push(EncoderLayer::new(
-                d_model,
-                nhead,
-                dim_feedforward,
-                &mut rng,
-                dist,
-            ));
-            decoder_layers.push(DecoderLayer::new(
-                d_model,
-                nhead,
-                dim_feedforward,
-                &mut rng,
-                dist,
-            ));
-        }
-
-        let final_linear_weight =
-            Array2::from_shape_fn((d_model, vocab_size), |_| rng.sample(dist));
-        let final_linear_bias = Array1::from_shape_fn(vocab_size, |_| rng.sample(dist));
+            encoder_layers.push(EncoderLayer::new(spec, &mut init));
+            decoder_layers.push(DecoderLayer::new(spec, &mut init));
+        }
+
+        let stack_norm =
+            || (spec.norm_placement == NormPlacement::Pre).then(|| LayerNorm::new(d_model));
+        let final_linear_weight = init.matrix(d_model, vocab_size);
+        let final_linear_bias = init.bias(vocab_size);
 
         Self {
             vocab_size,
             d_model,
-            nhead,
-            num_layers,
             max_seq_len,
-            dim_feedforward,
             token_embedding,
             positional_encoding,
             encoder_layers,
+            encoder_norm: stack_norm(),
             decoder_layers,
+            decoder_norm: stack_norm(),
             final_linear_weight,
             final_linear_bias,
         }
     }
 
     fn forward(&self, encoder_input: &[usize], decoder_input: &[usize]) -> Array2<f32> {
-        let encoder_ids = self.sanitize_ids(encoder_input);
-        let decoder_ids = self.sanitize_ids(decoder_input);
+        let encoded = self.encode(encoder_input);
+        self.decode(&encoded, decoder_input)
+    }
 
+    fn encode(&self, encoder_input: &[usize]) -> EncodedInput {
+        let encoder_ids = self.sanitize_ids(encoder_input);
         let mut encoder_states = self.embed(&encoder_ids);
+        let encoder_self_mask = self.padding_mask(&encoder_ids);
+
+        for layer in &self.encoder_layers {
+            encoder_states = layer.forward(&encoder_states, &encoder_self_mask);
+        }
+        if let Some(norm) = &self.encoder_norm {
+            encoder_states = norm.forward(&encoder_states);
+        }
+
+        EncodedInput {
+            ids: encoder_ids,
+            states: encoder_states,
+        }
+    }
+
+    fn decode(&self, encoded: &EncodedInput, decoder_input: &[usize]) -> Array2<f32> {
+        let decoder_ids = self.sanitize_ids(decoder_input);
         let mut decoder_states = self.embed(&decoder_ids);
 
-        let encoder_self_mask = self.self_padding_mask(&encoder_ids);
-        let decoder_self_mask = self.self_padding_mask(&decoder_ids);
-        let look_ahead = self.look_ahead_mask(decoder_ids.len());
-        let decoder_mask = self.combine_masks(&decoder_self_mask, &look_ahead);
-        let cross_mask = self.cross_padding_mask(decoder_ids.len(), &encoder_ids);
+        let decoder_mask = self.padding_mask(&decoder_ids).causal();
+        let cross_mask = self.padding_mask(&encoded.ids);
+
+        for layer in &self.decoder_layers {
+            decoder_states =
+                layer.forward(&decoder_states, &encoded.states, &decoder_mask, &cross_mask);
+        }
+        if let Some(norm) = &self.decoder_norm {
+            decoder_states = norm.forward(&decoder_states);
+        }
+
+        matmul(&decoder_states, &self.final_linear_weight) + &self.final_linear_bias
+    }
+
+    fn encode_batch(&self, inputs: &[Vec<usize>]) -> Vec<EncodedInput> {
+        let ids: Vec<Vec<usize>> = inputs.iter().map(|ids| self.sanitize_ids(ids)).collect();
+        let (padded, len) = pad_batch(&ids);
+        let mut states = self.embed_batch(&padded, len);
+        let mask = self.padding_mask(&padded).batched(ids.len());
 
         for layer in &self.encoder_layers {
-            encoder_states = layer.forward(&encoder_states, Some(&encoder_self_mask));
+            states = layer.forward(&states, &mask);
+        }
+        if let Some(norm) = &self.encoder_norm {
+            states = norm.forward(&states);
+        }
+
+        ids.into_iter()
+            .enumerate()
+            .map(|(index, ids)| EncodedInput {
+                states: states
+                    .slice(s![index * len..index * len + ids.len(), ..])
+                    .to_owned(),
+                ids,
+            })
+            .collect()
+    }
+
+    fn decode_batch(
+        &self,
+        encoded: &[&EncodedInput],
+        decoder_inputs: &[&[usize]],
+    ) -> Vec<Array2<f32>> {
+        let decoder_ids: Vec<Vec<usize>> = decoder_inputs
+            .iter()
+            .map(|ids| self.sanitize_ids(ids))
+            .collect();
+        let (padded, len) = pad_batch(&decoder_ids);
+        let mut decoder_states = self.embed_batch(&padded, len);
+        let sequences = decoder_ids.len();
+        let decoder_mask = self.padding_mask(&padded).causal().batched(sequences);
+
+        // Encoder states padded with zero rows behind masked padding ids
+        let memory_len = encoded.iter().map(|e| e.ids.len()).max().unwrap_or(0);
+        let mut memory = Array2::<f32>::zeros((sequences * memory_len, self.d_model));
+        let mut memory_ids = vec![SpecialToken::Padding.token_id(); sequences * memory_len];
+        for (index, encoded) in encoded.iter().enumerate() {
+            let start = index * memory_len;
+            let rows = encoded.ids.len();
+            memory
+                .slice_mut(s![start..start + rows, ..])
+                .assign(&encoded.states);
+            memory_ids[start..start + rows].copy_from_slice(&encoded.ids);
         }
+        let cross_mask = self.padding_mask(&memory_ids).batched(sequences);
 
         for layer in &self.decoder_layers {
-            decoder_states = layer.forward(
-                &decoder_states,
-                &encoder_states,
-                Some(&decoder_mask),
-                Some(&cross_mask),
-            );
+            decoder_states = layer.forward(&decoder_states, &memory, &decoder_mask, &cross_mask);
+        }
+        if let Some(norm) = &self.decoder_norm {
+            decoder_states = norm.forward(&decoder_states);
         }
 
-        decoder_states.dot(&self.final_linear_weight) + &self.final_linear_bias
+        let logits = matmul(&decoder_states, &self.final_linear_weight) + &self.final_linear_bias;
+        decoder_ids
+            .iter()
+            .enumerate()
+            .map(|(index, ids)| {
+                logits
+                    .slice(s![index * len..index * len + ids.len(), ..])
+                    .to_owned()
+            })
+            .collect()
+    }
+
+    /// Forward pass with cached activations followed by back-propagation of the
+    /// summed cross-entropy of `labels`, mixed with the distillation loss of
+    /// `soft` when given; returns the summed NLL
+    fn backward(
+        &self,
+        encoder_input: &[usize],
+        decoder_input: &[usize],
+        labels: &[usize],
+        soft: Option<&SoftTargets>,
+        grads: &mut Transformer,
+    ) -> f64 {
+        let encoder_ids = self.sanitize_ids(encoder_input);
+        let encoder_mask = self.padding_mask(&encoder_ids);
+        let mut encoder_states = self.embed(&encoder_ids);
+        let mut encoder_caches: Vec<EncoderCache> = Vec::with_capacity(self.encoder_layers.len());
+        for layer in &self.encoder_layers {
+            let (output, cache) = layer.forward_cached(&encoder_states, &encoder_mask);
+            encoder_states = output;
+            encoder_caches.push(cache);
+        }
+        let encoder_norm_cache = self.encoder_norm.as_ref().map(|norm| {
+            let (output, cache) = norm.forward_cached(&encoder_states);
+            encoder_states = output;
+            cache
+        });
+
+        let decoder_ids = self.sanitize_ids(decoder_input);
+        let decoder_mask = self.padding_mask(&decoder_ids).causal();
+        let cross_mask = self.padding_mask(&encoder_ids);
+        let mut decoder_states = self.embed(&decoder_ids);
+        let mut decoder_caches: Vec<DecoderCache> = Vec::with_capacity(self.decoder_layers.len());
+        for layer in &self.decoder_layers {
+            let (output, cache) =
+                layer.forward_cached(&decoder_states, &encoder_states, &decoder_mask, &cross_mask);
+            decoder_states = output;
+            decoder_caches.push(cache);
+        }
+        let decoder_norm_cache = self.decoder_norm.as_ref().map(|norm| {
+            let (output, cache) = norm.forward_cached(&decoder_states);
+            decoder_states = output;
+            cache
+        });
+
+        // Cross-entropy: d(nll)/d(logits) = softmax - one_hot
+        let mut d_logits =
+            matmul(&decoder_states, &self.final_linear_weight) + &self.final_linear_bias;
+        let mut nll = 0.0f64;
+        for (position, (mut row, &label)) in d_logits.rows_mut().into_iter().zip(labels).enumerate()
+        {
+            // Distillation: d(T² KL(teacher || student_T))/d(logits) = T (student_T - teacher)
+            let distilled = soft
+                .filter(|soft| position < soft.probabilities.nrows())
+                .map(|soft| {
+                    let student = softmax_vec(row.iter().map(|&x| x / soft.temperature).collect());
+                    (soft, student)
+                });
+            let label = label.min(self.vocab_size - 1);
+            let max = row.fold(f32::NEG_INFINITY, |a, &b| a.max(b));
+            row.mapv_inplace(|x| (x - max).exp());
+            let sum = row.sum();
+            nll -= ((row[label] / sum) as f64).ln();
+            row.mapv_inplace(|x| x / sum);
+            row[label] -= 1.0;
+            if let Some((soft, student)) = distilled {
+                let teacher = soft.probabilities.row(position);
+                for ((d, s), t) in row.iter_mut().zip(student).zip(teacher) {
+                    *d = (1.0 - soft.weight) * *d + soft.weight * soft.temperature * (s - t);
+                }
+            }
+        }
+
+        add_matmul(
+            &mut grads.final_linear_weight,
+            &decoder_states.t(),
+            &d_logits,
+        );
+        grads.final_linear_bias += &d_logits.sum_axis(Axis(0));
+        let mut d_decoder = matmul(&d_logits, &self.final_linear_weight.t());
+        if let (Some(norm), Some(cache), Some(norm_grads)) = (
+            &self.decoder_norm,
+            &decoder_norm_cache,
+            &mut grads.decoder_norm,
+        ) {
+            d_decoder = norm.backward(cache, &d_decoder, norm_grads);
+        }
+
+        let mut d_encoder = Array2::<f32>::zeros(encoder_states.raw_dim());
+        for ((layer, cache), layer_grads) in self
+            .decoder_layers
+            .iter()
+            .zip(&decoder_caches)
+            .zip(grads.decoder_layers.iter_mut())
+            .rev()
+        {
+            let (d_input, d_memory) = layer.backward(cache, &d_decoder, layer_grads);
+            d_decoder = d_input;
+            d_encoder += &d_memory;
+        }
+        self.embed_backward(&decoder_ids, &d_decoder, grads);
+
+        if let (Some(norm), Some(cache), Some(norm_grads)) = (
+            &self.encoder_norm,
+            &encoder_norm_cache,
+            &mut grads.encoder_norm,
+        ) {
+            d_encoder = norm.backward(cache, &d_encoder, norm_grads);
+        }
+
+        for ((layer, cache), layer_grads) in self
+            .encoder_layers
+            .iter()
+            .zip(&encoder_caches)
+            .zip(grads.encoder_layers.iter_mut())
+            .rev()
+        {
+            d_encoder = layer.backward(cache, &d_encoder, layer_grads);
+        }
+        self.embed_backward(&encoder_ids, &d_encoder, grads);
+
+        nll
+    }
+
+    fn embed_backward(&self, ids: &[usize], d_output: &Array2<f32>, grads: &mut Transformer) {
+        for (&token_id, d_row) in ids.iter().zip(d_output.rows()) {
+            grads.token_embedding.add_to_row(token_id, d_row);
+        }
     }
 
     fn embed(&self, input_ids: &[usize]) -> Array2<f32> {
@@ -244,14 +1068,30 @@ impl Transformer {
 
         for (position, &token_id) in input_ids.iter().enumerate() {
             let token_vec = self.token_embedding.row(token_id);
-            let pos_vec = self.positional_encoding.row(position % self.max_seq_len);
             let mut dest = output.slice_mut(s![position, ..]);
-            dest.assign(&(&token_vec + &pos_vec));
+            match &self.positional_encoding {
+                Some(encoding) => {
+                    dest.assign(&(&token_vec + &encoding.row(position % self.max_seq_len)))
+                }
+                None => dest.assign(&token_vec),
+            }
         }
 
         output
     }
 
+    /// Embeddings of sequences of `len` ids each, stacked row by row, with
+    /// positions counted from the start of each sequence
+    fn embed_batch(&self, ids: &[usize], len: usize) -> Array2<f32> {
+        let mut output = Array2::<f32>::zeros((ids.len(), self.d_model));
+        for (index, sequence) in ids.chunks(len.max(1)).enumerate() {
+            output
+                .slice_mut(s![index * len..index * len + sequence.len(), ..])
+                .assign(&self.embed(sequence));
+        }
+        output
+    }
+
     fn sanitize_ids(&self, ids: &[usize]) -> Vec<usize> {
         ids.iter()
             .take(self.max_seq_len)
@@ -265,46 +1105,8 @@ impl Transformer {
             .collect()
     }
 
-    fn self_padding_mask(&self, ids: &[usize]) -> Array2<f32> {
-        let seq_len = ids.len();
-        let mut mask = Array2::<f32>::zeros((seq_len, seq_len));
-        for i in 0..seq_len {
-            for j in 0..seq_len {
-                if ids[j] == SpecialToken::Padding.token_id() {
-                    mask[[i, j]] = f32::NEG_INFINITY;
-                }
-            }
-        }
-        mask
-    }
-
-    fn cross_padding_mask(&self, query_len: usize, encoder_ids: &[usize]) -> Array2<f32> {
-        let mut mask = Array2::<f32>::zeros((query_len, encoder_ids.len()));
-        for i in 0..query_len {
-            for (j, &id) in encoder_ids.iter().enumerate() {
-                if id == SpecialToken::Padding.token_id() {
-                    mask[[i, j]] = f32::NEG_INFINITY;
-                }
-            }
-        }
-        mask
-    }
-
-    fn look_ahead_mask(&self, seq_len: usize) -> Array2<f32> {
-        let mut mask = Array2::<f32>::zeros((seq_len, seq_len));
-        for i in 0..seq_len {
-            for j in 0..seq_len {
-                if j > i {
-                    mask[[i, j]] = f32::NEG_INFINITY;
-                }
-            }
-        }
-        mask
-    }
-
-    fn combine_masks(&self, a: &Array2<f32>, b: &Array2<f32>) -> Array2<f32> {
-        assert_eq!(a.shape(), b.shape());
-        a + b
+    fn padding_mask(&self, ids: &[usize]) -> AttentionMask {
+        AttentionMask::padding(ids.iter().map(|&id| id == SpecialToken::Padding.token_id()))
     }
 
     fn create_positional_encoding(max_seq_len: usize, d_model: usize) -> Array2<f32> {
@@ -332,55 +1134,186 @@ impl Transformer {
         for layer in &self.decoder_layers {
             total += layer.num_parameters();
         }
+        for norm in self.encoder_norm.iter().chain(&self.decoder_norm) {
+            total += norm.num_parameters();
+        }
         total
     }
 }
 
-#[derive(Debug, Clone)]
+const SQRT_2_OVER_PI: f32 = 0.797_884_6;
+const GELU_COEFF: f32 = 0.044_715;
+
+fn sigmoid(x: f32) -> f32 {
+    1.0 / (1.0 + (-x).exp())
+}
+
+impl Activation {
+    /// Value of the activation; SwiGLU applies SiLU before gating
+    fn apply(self, x: f32) -> f32 {
+        match self {
+            Activation::Relu => x.max(0.0),
+            Activation::Gelu => {
+                0.5 * x * (1.0 + (SQRT_2_OVER_PI * (x + GELU_COEFF * x * x * x)).tanh())
+            }
+            Activation::Silu | Activation::Swiglu => x * sigmoid(x),
+        }
+    }
+
+    /// Derivative of [`apply`](Self::apply) at `x`
+    fn derivative(self, x: f32) -> f32 {
+        match self {
+            Activation::Relu => {
+                if x > 0.0 {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+            Activation::Gelu => {
+                let t = (SQRT_2_OVER_PI * (x + GELU_COEFF * x * x * x)).tanh();
+                0.5 * (1.0 + t)
+                    + 0.5 * x * (1.0 - t * t) * SQRT_2_OVER_PI * (1.0 + 3.0 * GELU_COEFF * x * x)
+            }
+            Activation::Silu | Activation::Swiglu => {
+                let s = sigmoid(x);
+                s * (1.0 + x * (1.0 - s))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub(super) struct FeedForward {
+    activation: Activation,
     linear1: Linear,
     linear2: Linear,
+    /// Gate projection, only for [`Activation::Swiglu`]
+    gate: Option<Linear>,
+}
+
+parameters!(FeedForward {
+    linear1,
+    linear2,
+    gate
+});
+
+/// Activations kept by [`FeedForward::forward_cached`]
+#[derive(Debug, Clone)]
+pub(super) struct FeedForwardCache {
+    input: Array2<f32>,
+    /// `linear1` output before the activation
+    pre_activation: Array2<f32>,
+    gate: Option<Array2<f32>>,
+    hidden: Array2<f32>,
 }
 
 impl FeedForward {
     pub(super) fn new(
         d_model: usize,
         hidden_dim: usize,
-        rng: &mut StdRng,
-        dist: Uniform<f32>,
+        activation: Activation,
+        init: &mut Initializer,
     ) -> Self {
+        let linear1 = Linear::new(d_model, hidden_dim, init);
+        let linear2 = Linear::new(hidden_dim, d_model, init);
+        let gate =
+            (activation == Activation::Swiglu).then(|| Linear::new(d_model, hidden_dim, init));
         Self {
-            linear1: Linear::new(d_model, hidden_dim, rng, dist),
-            linear2: Linear::new(hidden_dim, d_model, rng, dist),
+            activation,
+            linear1,
+            linear2,
+            gate,
         }
     }
 
     pub(super) fn forward(&self, x: &Array2<f32>) -> Array2<f32> {
-        let mut hidden = self.linear1.forward(x);
-        hidden.mapv_inplace(|v| v.max(0.0));
-        self.linear2.forward(&hidden)
+        self.forward_cached(x).0
+    }
+
+    pub(super) fn forward_cached(&self, x: &Array2<f32>) -> (Array2<f32>, FeedForwardCache) {
+        let pre_activation = self.linear1.forward(x);
+        let mut hidden = pre_activation.mapv(|v| self.activation.apply(v));
+        let gate = self.gate.as_ref().map(|gate| gate.forward(x));
+        if let Some(gate) = &gate {
+            hidden *= gate;
+        }
+        let output = self.linear2.forward(&hidden);
+        let cache = FeedForwardCache {
+            input: x.clone(),
+            pre_activation,
+            gate,
+            hidden,
+        };
+        (output, cache)
+    }
+
+    /// Accumulate parameter gradients into `grads` and return the input gradient
+    pub(super) fn backward(
+        &self,
+        cache: &FeedForwardCache,
+        d_output: &Array2<f32>,
+        grads: &mut FeedForward,
+    ) -> Array2<f32> {
+        let mut d_hidden = self
+            .linear2
+            .backward(&cache.hidden, d_output, &mut grads.linear2);
+        let mut d_gate_input = None;
+        if let (Some(gate), Some(gate_output), Some(gate_grads)) =
+            (&self.gate, &cache.gate, &mut grads.gate)
+        {
+            // hidden = activation(pre_activation) * gate_output
+            let d_gate = &d_hidden * &cache.pre_activation.mapv(|v| self.activation.apply(v));
+            d_gate_input = Some(gate.backward(&cache.input, &d_gate, gate_grads));
+            d_hidden *= gate_output;
+        }
+        d_hidden.zip_mut_with(&cache.pre_activation, |d, &x| {
+            *d *= self.activation.derivative(x)
+        });
+        let d_input = self
+            .linear1
+            .backward(&cache.input, &d_hidden, &mut grads.linear1);
+        match d_gate_input {
+            Some(d_gate_input) => d_input + &d_gate_input,
+            None => d_input,
+        }
     }
 
     pub(super) fn num_parameters(&self) -> usize {
-        self.linear1.num_parameters() + self.linear2.num_parameters()
+        self.linear1.num_parameters()
+            + self.linear2.num_parameters()
+            + self.gate.as_ref().map_or(0, Linear::num_parameters)
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 struct Linear {
     weight: Array2<f32>,
     bias: Array1<f32>,
 }
 
+parameters!(Linear { weight, bias });
+
 impl Linear {
-    fn new(in_dim: usize, out_dim: usize, rng: &mut StdRng, dist: Uniform<f32>) -> Self {
-        let weight = Array2::from_shape_fn((in_dim, out_dim), |_| rng.sample(dist));
-        let bias = Array1::from_shape_fn(out_dim, |_| rng.sample(dist));
+    fn new(in_dim: usize, out_dim: usize, init: &mut Initializer) -> Self {
+        let weight = init.matrix(in_dim, out_dim);
+        let bias = init.bias(out_dim);
         Self { weight, bias }
     }
 
     fn forward(&self, x: &Array2<f32>) -> Array2<f32> {
-        x.dot(&self.weight) + &self.bias
+        matmul(x, &self.weight) + &self.bias
+    }
+
+    fn backward(
+        &self,
+        input: &Array2<f32>,
+        d_output: &Array2<f32>,
+        grads: &mut Linear,
+    ) -> Array2<f32> {
+        add_matmul(&mut grads.weight, &input.t(), d_output);
+        grads.bias += &d_output.sum_axis(Axis(0));
+        matmul(d_output, &self.weight.t())
     }
 
     fn num_parameters(&self) -> usize {
@@ -388,13 +1321,22 @@ impl Linear {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub(super) struct LayerNorm {
     gamma: Array1<f32>,
     beta: Array1<f32>,
     eps: f32,
 }
 
+parameters!(LayerNorm { gamma, beta });
+
+/// Normalized rows and their inverse standard deviations, kept for the backward pass
+#[derive(Debug, Clone)]
+pub(super) struct LayerNormCache {
+    normalized: Array2<f32>,
+    inv_std: Array1<f32>,
+}
+
 impl LayerNorm {
     pub(super) fn new(dim: usize) -> Self {
         Self {
@@ -405,8 +1347,14 @@ impl LayerNorm {
     }
 
     pub(super) fn forward(&self, x: &Array2<f32>) -> Array2<f32> {
+        self.forward_cached(x).0
+    }
+
+    pub(super) fn forward_cached(&self, x: &Array2<f32>) -> (Array2<f32>, LayerNormCache) {
         let mut output = x.clone();
-        for mut row in output.rows_mut() {
+        let mut normalized = x.clone();
+        let mut inv_std = Array1::<f32>::zeros(x.nrows());
+        for (r, mut row) in output.rows_mut().into_iter().enumerate() {
             let len = row.len();
             let mut mean = 0.0f32;
             for idx in 0..len {
@@ -421,13 +1369,46 @@ impl LayerNorm {
             }
             variance /= len as f32;
             let denom = (variance + self.eps).sqrt();
+            inv_std[r] = 1.0 / denom;
 
             for idx in 0..len {
-                let normalized = (row[idx] - mean) / denom;
-                row[idx] = normalized * self.gamma[idx] + self.beta[idx];
+                let norm = (row[idx] - mean) / denom;
+                normalized[[r, idx]] = norm;
+                row[idx] = norm * self.gamma[idx] + self.beta[idx];
             }
         }
-        output
+        (
+            output,
+            LayerNormCache {
+                normalized,
+                inv_std,
+            },
+        )
+    }
+
+    /// Accumulate parameter gradients into `grads` and return the input gradient
+    pub(super) fn backward(
+        &self,
+        cache: &LayerNormCache,
+        d_output: &Array2<f32>,
+        grads: &mut LayerNorm,
+    ) -> Array2<f32> {
+        grads.gamma += &(d_output * &cache.normalized).sum_axis(Axis(0));
+        grads.beta += &d_output.sum_axis(Axis(0));
+
+        let mut d_input = d_output * &self.gamma;
+        for ((mut d_row, x_hat), &inv_std) in d_input
+            .rows_mut()
+            .into_iter()
+            .zip(cache.normalized.rows())
+            .zip(&cache.inv_std)
+        {
+            let n = d_row.len() as f32;
+            let mean_d = d_row.sum() / n;
+            let mean_dx = d_row.dot(&x_hat) / n;
+            d_row.zip_mut_with(&x_hat, |d, &x| *d = inv_std * (*d - mean_d - x * mean_dx));
+        }
+        d_input
     }
 
     pub(super) fn num_parameters(&self) -> usize {
@@ -511,6 +1492,14 @@ mod tests {
             dim_feedforward: 2048,
             dropout: 0.1,
             max_seq_len: 512,
+            activation: Activation::Relu,
+            norm_placement: NormPlacement::Post,
+            positional_encoding: PositionalEncoding::Sinusoidal,
+            embedding_dtype: Default::default(),
+            seed: None,
+            init: Default::default(),
+            attention: Default::default(),
+            pretrained: None,
         };
 
         let model = CodeGenerationModel::from_model_config(2048, &config);
@@ -518,4 +1507,298 @@ mod tests {
         assert_eq!(model.dim_feedforward, 2048);
         assert_eq!(model.max_seq_len, 512);
     }
+
+    #[test]
+    fn test_sequence_nll() {
+        let model = CodeGenerationModel::new(
+            ModelArchitecture::Transformer,
+            32,
+            16,
+            2,
+            1,
+            Some(32),
+            Some(8),
+        );
+        let (nll, tokens) = model.sequence_nll(&[5, 6, 7], &[8, 9, 10]);
+        assert_eq!(tokens, 4);
+        assert!(nll.is_finite() && nll > 0.0);
+
+        // Targets are truncated to fit the decoder, plus <eos>
+        let (_, tokens) = model.sequence_nll(&[5], &[9; 20]);
+        assert_eq!(tokens, 8);
+
+        // Uniform logits give exactly ln(vocab_size) per token
+        let lstm = CodeGenerationModel::new(ModelArchitecture::LSTM, 32, 16, 2, 1, None, None);
+        let (nll, tokens) = lstm.sequence_nll(&[5], &[8, 9]);
+        assert!((nll / tokens as f64 - 32f64.ln()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_batched_passes_match_single() {
+        let base = gradient_test_model(Activation::Relu);
+        let models = [
+            base.clone(),
+            base.clone()
+                .with_positional_encoding(PositionalEncoding::Rope)
+                .with_norm_placement(NormPlacement::Pre),
+            base.with_attention(AttentionConfig {
+                block_size: 2,
+                window: 3,
+            }),
+        ];
+        let inputs = vec![vec![5, 6, 7, 8, 9], vec![4], vec![10, 11, 12]];
+        let decoder_ids: [&[usize]; 3] = [&[2, 7, 8], &[2], &[2, 9, 10, 11, 12]];
+        for model in &models {
+            let encoded = model.encode_batch(&inputs);
+            let logits = model.decode_batch(&encoded.iter().collect::<Vec<_>>(), &decoder_ids);
+            for (index, ids) in inputs.iter().enumerate() {
+                let single = model.encode(ids);
+                assert_eq!(encoded[index].ids, single.ids);
+                for (a, b) in encoded[index].states.iter().zip(&single.states) {
+                    assert!((a - b).abs() < 1e-5, "{} != {}", a, b);
+                }
+                let expected = model.decode(&single, decoder_ids[index]);
+                assert_eq!(logits[index].shape(), expected.shape());
+                for (a, b) in logits[index].iter().zip(&expected) {
+                    assert!((a - b).abs() < 1e-4, "{} != {}", a, b);
+                }
+            }
+        }
+    }
+
+    fn gradient_test_model(activation: Activation) -> CodeGenerationModel {
+        CodeGenerationModel::new(
+            ModelArchitecture::Transformer,
+            16,
+            8,
+            2,
+            1,
+            Some(12),
+            Some(8),
+        )
+        .with_activation(activation)
+    }
+
+    #[test]
+    fn test_gradients_match_finite_differences() {
+        assert_gradients_match(&gradient_test_model(Activation::Relu));
+    }
+
+    #[test]
+    fn test_activation_gradients_match_finite_differences() {
+        for activation in [Activation::Gelu, Activation::Silu, Activation::Swiglu] {
+            assert_gradients_match(&gradient_test_model(activation));
+        }
+    }
+
+    #[test]
+    fn test_distillation_gradients_match_finite_differences() {
+        let student = gradient_test_model(Activation::Relu);
+        let teacher = gradient_test_model(Activation::Gelu).with_seed(3);
+        let (input, target, alpha, temperature) = ([5, 6, 7], [8, 9], 0.7f32, 2.0f32);
+        let mut grads = student.zero_gradients();
+        let (nll, tokens) = student.accumulate_distillation_gradients(
+            &teacher,
+            &input,
+            &target,
+            alpha,
+            temperature,
+            &mut grads,
+        );
+        let (expected_nll, expected_tokens) = student.sequence_nll(&input, &target);
+        assert_eq!(tokens, expected_tokens);
+        assert!((nll - expected_nll).abs() < 1e-4);
+
+        let (decoder_ids, _) = student.teacher_forcing(&target);
+        let soft_labels = tempered_softmax(
+            teacher.decode(&teacher.encode(&input), &decoder_ids),
+            temperature,
+        );
+        assert_matches_finite_differences(&student, &grads, |shifted| {
+            let logits = shifted.decode(&shifted.encode(&input), &decoder_ids);
+            let kl: f64 = soft_labels
+                .iter()
+                .zip(&tempered_softmax(logits, temperature))
+                .map(|(&t, &s)| t as f64 * (t as f64 / s as f64).ln())
+                .sum();
+            let nll = shifted.sequence_nll(&input, &target).0;
+            (1.0 - alpha as f64) * nll + (alpha * temperature * temperature) as f64 * kl
+        });
+    }
+
+    #[test]
+    fn test_pre_norm_gradients_match_finite_differences() {
+        let model = gradient_test_model(Activation::Relu).with_norm_placement(NormPlacement::Pre);
+        assert_gradients_match(&model);
+    }
+
+    #[test]
+    fn test_rope_gradients_match_finite_differences() {
+        let model = gradient_test_model(Activation::Relu)
+            .with_positional_encoding(PositionalEncoding::Rope);
+        assert_gradients_match(&model);
+    }
+
+    #[test]
+    fn test_pre_norm_adds_stack_norms() {
+        let post = gradient_test_model(Activation::Relu);
+        let pre = post.clone().with_norm_placement(NormPlacement::Pre);
+        let names: Vec<String> = pre
+            .parameter_shapes()
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+        assert!(names.contains(&"encoder_norm.gamma".to_string()));
+        assert!(names.contains(&"decoder_norm.beta".to_string()));
+        assert_eq!(pre.num_parameters(), post.num_parameters() + 4 * 8);
+
+        let ids = [4, 5, 6];
+        assert_ne!(
+            pre.decode(&pre.encode(&ids), &[2, 4]),
+            post.decode(&post.encode(&ids), &[2, 4])
+        );
+    }
+
+    #[test]
+    fn test_init_schemes() {
+        let xavier = gradient_test_model(Activation::Relu);
+        assert_eq!(xavier.init, InitScheme::Xavier);
+        let limit = (6.0f32 / 16.0).sqrt();
+        xavier.visit_parameters(&mut |name, values| {
+            if name == "encoder.0.self_attn.w_q" {
+                assert!(values.iter().all(|v| v.abs() <= limit));
+            }
+            if name == "output.bias" {
+                assert!(values.iter().all(|&v| v == 0.0));
+            }
+        });
+
+        let uniform = xavier.clone().with_init(InitScheme::Uniform);
+        let again = uniform.clone().with_seed(DEFAULT_SEED);
+        uniform.visit_parameters(&mut |name, values| {
+            if !name.contains("norm") {
+                assert!(values.iter().all(|v| v.abs() <= 0.1), "{}", name);
+            }
+        });
+        let ids = [4, 5, 6];
+        assert_eq!(
+            uniform.decode(&uniform.encode(&ids), &[2, 4]),
+            again.decode(&again.encode(&ids), &[2, 4])
+        );
+    }
+
+    #[test]
+    fn test_swiglu_adds_gate_projection() {
+        let relu = gradient_test_model(Activation::Relu);
+        let swiglu = gradient_test_model(Activation::Swiglu);
+        let names: Vec<String> = swiglu
+            .parameter_shapes()
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+        assert!(names.contains(&"encoder.0.feedforward.gate.weight".to_string()));
+        assert!(names.contains(&"decoder.0.feedforward.gate.bias".to_string()));
+        // One 8x12 gate and its bias per layer
+        assert_eq!(
+            swiglu.num_parameters(),
+            relu.num_parameters() + 2 * (8 * 12 + 12)
+        );
+    }
+
+    #[test]
+    fn test_half_embeddings() {
+        let full = gradient_test_model(Activation::Relu);
+        let half = full.clone().with_embedding_dtype(Dtype::F16);
+        let ids = [4, 5, 6];
+        let (expected, logits) = (
+            full.decode(&full.encode(&ids), &[2, 4]),
+            half.decode(&half.encode(&ids), &[2, 4]),
+        );
+        assert!(logits
+            .iter()
+            .zip(&expected)
+            .all(|(a, b)| (a - b).abs() < 1e-2));
+
+        let embedding_gradient = |model: &CodeGenerationModel| {
+            let mut grads = model.zero_gradients();
+            model.accumulate_gradients(&ids, &[8, 9], &mut grads);
+            let mut gradient = Vec::new();
+            grads.visit(&mut |name, values| {
+                if name == "token_embedding" {
+                    gradient = values.to_vec();
+                }
+            });
+            gradient
+        };
+        let (expected, gradient) = (embedding_gradient(&full), embedding_gradient(&half));
+        assert!(gradient.iter().any(|&g| g != 0.0));
+        assert!(gradient
+            .iter()
+            .zip(&expected)
+            .all(|(a, b)| (a - b).abs() < 1e-2));
+        let restored = half.with_embedding_dtype(Dtype::F32);
+        assert_eq!(restored.embedding_dtype, Dtype::F32);
+    }
+
+    fn assert_gradients_match(model: &CodeGenerationModel) {
+        let (input, target) = ([5, 6, 7], [8, 9]);
+        let mut grads = model.zero_gradients();
+        let (nll, tokens) = model.accumulate_gradients(&input, &target, &mut grads);
+        let (expected_nll, expected_tokens) = model.sequence_nll(&input, &target);
+        assert_eq!(tokens, expected_tokens);
+        assert!((nll - expected_nll).abs() < 1e-4);
+
+        let mut analytic = Vec::new();
+        grads.visit(&mut |name, values| analytic.push((name.to_string(), values.to_vec())));
+        let names: Vec<&str> = analytic.iter().map(|(name, _)| name.as_str()).collect();
+        assert!(names.contains(&"encoder.0.self_attn.w_q"));
+        assert!(names.contains(&"decoder.0.cross_attn.b_v"));
+        assert!(names.contains(&"decoder.0.norm3.gamma"));
+        assert!(names.contains(&"output.weight"));
+        let total: usize = analytic.iter().map(|(_, values)| values.len()).sum();
+        assert_eq!(total, model.num_parameters());
+
+        assert_matches_finite_differences(model, &grads, |shifted| {
+            shifted.sequence_nll(&input, &target).0
+        });
+    }
+
+    /// Check the largest entry of every tensor of `grads` against central
+    /// differences of `loss`
+    fn assert_matches_finite_differences(
+        model: &CodeGenerationModel,
+        grads: &Gradients,
+        loss: impl Fn(&CodeGenerationModel) -> f64,
+    ) {
+        let mut analytic = Vec::new();
+        grads.visit(&mut |name, values| analytic.push((name.to_string(), values.to_vec())));
+        let eps = 2e-3f32;
+        for (tensor, (name, values)) in analytic.iter().enumerate() {
+            let (index, &expected) = values
+                .iter()
+                .enumerate()
+                .max_by(|a, b| a.1.abs().total_cmp(&b.1.abs()))
+                .unwrap();
+            let loss_with = |delta: f32| {
+                let mut shifted = model.clone();
+                let mut current = 0;
+                shifted.visit_parameters_mut(&mut |_, values| {
+                    if current == tensor {
+                        values[index] += delta;
+                    }
+                    current += 1;
+                });
+                loss(&shifted)
+            };
+            let numeric = ((loss_with(eps) - loss_with(-eps)) / (2.0 * eps as f64)) as f32;
+            assert!(
+                (numeric - expected).abs() <= 1e-2 + 0.05 * expected.abs(),
+                "{}[{}]: analytic {} numeric {}",
+                name,
+                index,
+                expected,
+                numeric
+            );
+        }
+    }
 }
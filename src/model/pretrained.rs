@@ -0,0 +1,509 @@
+//! Initializing a model from pretrained weights
+//!
+//! [`CodeGenerationModel::import_pretrained`] reads a `.safetensors` or `.npz`
+//! file and copies tensors over the random initialization, guided by a
+//! [`PretrainedConfig`]:
+//!
+//! - parameters are looked up under their own name unless `map` names a
+//!   different source tensor, and are left untouched when there is none
+//! - source tensors larger than the parameter (a wider or bigger-vocabulary
+//!   model) are cropped to their leading block
+//! - with a `vocab` file, the rows of vocabulary-indexed tensors
+//!   (`token_embedding`, `output.weight`, `output.bias`) are matched by token
+//!   text, so embeddings carry over even though the two tokenizers number
+//!   tokens differently
+
+use super::weights::tensor_to_f32;
+use super::CodeGenerationModel;
+use crate::config::PretrainedConfig;
+use crate::tokenizer::WGSLTokenizer;
+use safetensors::SafeTensors;
+use std::collections::{BTreeMap, HashMap};
+use std::io::Read;
+use std::path::Path;
+
+/// A tensor read from an external checkpoint, in row-major order
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tensor {
+    pub shape: Vec<usize>,
+    pub data: Vec<f32>,
+}
+
+impl Tensor {
+    /// Rows and columns, treating vectors as a single column
+    fn dims(&self) -> crate::Result<(usize, usize)> {
+        match self.shape.as_slice() {
+            [n] => Ok((*n, 1)),
+            [rows, cols] => Ok((*rows, *cols)),
+            shape => Err(crate::Error::Other(format!(
+                "Expected a 1-D or 2-D tensor, found shape {:?}",
+                shape
+            ))),
+        }
+    }
+
+    fn transposed(&self) -> crate::Result<Self> {
+        let (rows, cols) = self.dims()?;
+        let mut data = vec![0.0; self.data.len()];
+        for r in 0..rows {
+            for c in 0..cols {
+                data[c * rows + r] = self.data[r * cols + c];
+            }
+        }
+        let shape = match self.shape.len() {
+            1 => self.shape.clone(),
+            _ => vec![cols, rows],
+        };
+        Ok(Self { shape, data })
+    }
+}
+
+/// Every tensor of a `.safetensors` or `.npz` file, converted to `f32`
+#[derive(Debug, Clone, Default)]
+pub struct PretrainedWeights {
+    pub tensors: BTreeMap<String, Tensor>,
+}
+
+impl PretrainedWeights {
+    /// Read `path`, choosing the format from its extension
+    pub fn load<P: AsRef<Path>>(path: P) -> crate::Result<Self> {
+        let path = path.as_ref();
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("safetensors") => Self::from_safetensors(&std::fs::read(path)?),
+            Some("npz") => Self::from_npz(path),
+            _ => Err(crate::Error::Other(format!(
+                "Unsupported weights file {}; expected .safetensors or .npz",
+                path.display()
+            ))),
+        }
+    }
+
+    fn from_safetensors(bytes: &[u8]) -> crate::Result<Self> {
+        let file = SafeTensors::deserialize(bytes)?;
+        let tensors = file
+            .tensors()
+            .into_iter()
+            .map(|(name, view)| {
+                let tensor = Tensor {
+                    shape: view.shape().to_vec(),
+                    data: tensor_to_f32(&view)?,
+                };
+                Ok((name, tensor))
+            })
+            .collect::<crate::Result<_>>()?;
+        Ok(Self { tensors })
+    }
+
+    fn from_npz(path: &Path) -> crate::Result<Self> {
+        let zip_error = |e: zip::result::ZipError| {
+            crate::Error::Other(format!("Cannot read {}: {}", path.display(), e))
+        };
+        let mut archive = zip::ZipArchive::new(std::fs::File::open(path)?).map_err(zip_error)?;
+        let mut tensors = BTreeMap::new();
+        for index in 0..archive.len() {
+            let mut entry = archive.by_index(index).map_err(zip_error)?;
+            let name = entry.name().trim_end_matches(".npy").to_string();
+            let mut bytes = Vec::new();
+            entry.read_to_end(&mut bytes)?;
+            let tensor = parse_npy(&bytes).map_err(|e| {
+                crate::Error::Other(format!("Array '{}' in {}: {}", name, path.display(), e))
+            })?;
+            tensors.insert(name, tensor);
+        }
+        Ok(Self { tensors })
+    }
+}
+
+/// Decode one `.npy` array of little-endian floats
+fn parse_npy(bytes: &[u8]) -> Result<Tensor, String> {
+    if !bytes.starts_with(b"\x93NUMPY") || bytes.len() < 10 {
+        return Err("not an .npy array".to_string());
+    }
+    let (header_len, start) = match bytes[6] {
+        1 => (u16::from_le_bytes([bytes[8], bytes[9]]) as usize, 10),
+        _ if bytes.len() >= 12 => (
+            u32::from_le_bytes([bytes[8], bytes[9], bytes[10], bytes[11]]) as usize,
+            12,
+        ),
+        _ => return Err("truncated header".to_string()),
+    };
+    let header = bytes
+        .get(start..start + header_len)
+        .and_then(|header| std::str::from_utf8(header).ok())
+        .ok_or("truncated header")?;
+
+    let descr = header
+        .split("'descr':")
+        .nth(1)
+        .and_then(|rest| rest.split('\'').nth(1))
+        .ok_or("missing 'descr'")?;
+    let shape: Vec<usize> = header
+        .split("'shape':")
+        .nth(1)
+        .and_then(|rest| rest.split(['(', ')']).nth(1))
+        .ok_or("missing 'shape'")?
+        .split(',')
+        .map(str::trim)
+        .filter(|dim| !dim.is_empty())
+        .map(|dim| dim.parse().map_err(|_| format!("bad dimension '{}'", dim)))
+        .collect::<Result<_, _>>()?;
+
+    let data = &bytes[start + header_len..];
+    let data: Vec<f32> = match descr {
+        "<f4" => data
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect(),
+        "<f8" => data
+            .chunks_exact(8)
+            .map(|b| f64::from_le_bytes(b.try_into().expect("8-byte chunk")) as f32)
+            .collect(),
+        "<f2" => data
+            .chunks_exact(2)
+            .map(|b| half::f16::from_le_bytes([b[0], b[1]]).to_f32())
+            .collect(),
+        other => return Err(format!("unsupported dtype '{}'", other)),
+    };
+    if data.len() != shape.iter().product::<usize>() {
+        return Err(format!("{} values for shape {:?}", data.len(), shape));
+    }
+
+    let tensor = Tensor { shape, data };
+    if header.contains("'fortran_order': True") && tensor.shape.len() == 2 {
+        // Column-major storage is the row-major transpose
+        let stored = Tensor {
+            shape: vec![tensor.shape[1], tensor.shape[0]],
+            data: tensor.data,
+        };
+        return stored.transposed().map_err(|e| e.to_string());
+    }
+    Ok(tensor)
+}
+
+/// What [`CodeGenerationModel::import_pretrained`] did to each parameter
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ImportReport {
+    /// Parameters copied from the file, with the source tensor name
+    pub loaded: Vec<(String, String)>,
+    /// Loaded parameters whose source was larger and was cropped
+    pub cropped: Vec<String>,
+    /// Parameters without a source tensor, left at their initialization
+    pub skipped: Vec<String>,
+    /// Tokenizer entries found in the pretrained vocabulary, when one was given
+    pub matched_tokens: Option<usize>,
+}
+
+/// Axis indexed by token id, for tensors that have one
+fn vocab_axis(name: &str) -> Option<usize> {
+    match name {
+        "token_embedding" | "output.bias" => Some(0),
+        "output.weight" => Some(1),
+        _ => None,
+    }
+}
+
+/// Pretrained id of every tokenizer id, trying the plain token and the
+/// GPT-2 (`Ġ`) and SentencePiece (`▁`) word-start forms
+fn match_vocab(
+    tokenizer: &WGSLTokenizer,
+    vocab_size: usize,
+    pretrained: &HashMap<String, usize>,
+) -> Vec<Option<usize>> {
+    (0..vocab_size)
+        .map(|id| {
+            let token = tokenizer.reverse_vocab.get(&id)?;
+            [token.clone(), format!("Ġ{}", token), format!("▁{}", token)]
+                .iter()
+                .find_map(|candidate| pretrained.get(candidate).copied())
+        })
+        .collect()
+}
+
+impl CodeGenerationModel {
+    /// Overwrite parameters with tensors from the pretrained file described
+    /// by `config`; `tokenizer` is only consulted when `config.vocab` is set
+    pub fn import_pretrained(
+        &mut self,
+        config: &PretrainedConfig,
+        tokenizer: &WGSLTokenizer,
+    ) -> crate::Result<ImportReport> {
+        let weights = PretrainedWeights::load(&config.path)?;
+        let rows = match &config.vocab {
+            Some(path) => {
+                let vocab: HashMap<String, usize> =
+                    serde_json::from_str(&std::fs::read_to_string(path)?)?;
+                Some(match_vocab(tokenizer, self.vocab_size, &vocab))
+            }
+            None => None,
+        };
+
+        let mut current: HashMap<String, Tensor> = HashMap::new();
+        let shapes: HashMap<String, Vec<usize>> = self.parameter_shapes().into_iter().collect();
+        self.visit_parameters(&mut |name, values| {
+            let tensor = Tensor {
+                shape: shapes[name].clone(),
+                data: values.to_vec(),
+            };
+            current.insert(name.to_string(), tensor);
+        });
+
+        let mut report = ImportReport {
+            matched_tokens: rows
+                .as_ref()
+                .map(|rows| rows.iter().filter(|row| row.is_some()).count()),
+            ..ImportReport::default()
+        };
+        let mut updates: HashMap<String, Vec<f32>> = HashMap::new();
+        for (name, _) in self.parameter_shapes() {
+            let source_name = config.map.get(&name).unwrap_or(&name);
+            let Some(source) = weights.tensors.get(source_name) else {
+                report.skipped.push(name);
+                continue;
+            };
+            let mut source = source.clone();
+            if config.transpose.contains(&name) {
+                source = source.transposed()?;
+            }
+
+            let target = &current[&name];
+            let context = |e: crate::Error| {
+                crate::Error::Other(format!(
+                    "Cannot initialize '{}' from '{}': {}",
+                    name, source_name, e
+                ))
+            };
+            let (values, cropped) = match (vocab_axis(&name), &rows) {
+                (Some(axis), Some(rows)) => {
+                    copy_rows(target, &source, axis, rows).map_err(context)?
+                }
+                _ => copy_leading(target, &source).map_err(context)?,
+            };
+            if cropped {
+                report.cropped.push(name.clone());
+            }
+            report.loaded.push((name.clone(), source_name.clone()));
+            updates.insert(name, values);
+        }
+
+        self.visit_parameters_mut(&mut |name, params| {
+            if let Some(values) = updates.get(name) {
+                params.copy_from_slice(values);
+            }
+        });
+        Ok(report)
+    }
+}
+
+/// The leading block of `source` with `target`'s shape, and whether anything
+/// was cropped
+fn copy_leading(target: &Tensor, source: &Tensor) -> crate::Result<(Vec<f32>, bool)> {
+    let (rows, cols) = target.dims()?;
+    let (source_rows, source_cols) = source.dims()?;
+    if source.shape.len() != target.shape.len() || source_rows < rows || source_cols < cols {
+        return Err(crate::Error::Other(format!(
+            "source shape {:?} does not cover {:?}",
+            source.shape, target.shape
+        )));
+    }
+    let mut values = Vec::with_capacity(rows * cols);
+    for r in 0..rows {
+        values.extend_from_slice(&source.data[r * source_cols..r * source_cols + cols]);
+    }
+    Ok((values, source.shape != target.shape))
+}
+
+/// `target` with every matched token's slice along `axis` replaced by the
+/// leading values of the source token's slice
+fn copy_rows(
+    target: &Tensor,
+    source: &Tensor,
+    axis: usize,
+    rows: &[Option<usize>],
+) -> crate::Result<(Vec<f32>, bool)> {
+    let (target, source) = match axis {
+        0 => (target.clone(), source.clone()),
+        _ => (target.transposed()?, source.transposed()?),
+    };
+    let (_, cols) = target.dims()?;
+    let (source_rows, source_cols) = source.dims()?;
+    if source.shape.len() != target.shape.len() || source_cols < cols {
+        return Err(crate::Error::Other(format!(
+            "source shape {:?} does not cover {:?}",
+            source.shape, target.shape
+        )));
+    }
+
+    let mut values = target.data.clone();
+    for (row, source_row) in rows.iter().enumerate() {
+        let Some(source_row) = source_row.filter(|&r| r < source_rows) else {
+            continue;
+        };
+        let from = source_row * source_cols;
+        values[row * cols..(row + 1) * cols].copy_from_slice(&source.data[from..from + cols]);
+    }
+
+    let result = Tensor {
+        shape: target.shape,
+        data: values,
+    };
+    let result = match axis {
+        0 => result,
+        _ => result.transposed()?,
+    };
+    Ok((result.data, source_cols > cols))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::ModelArchitecture;
+    use safetensors::tensor::TensorView;
+    use safetensors::Dtype;
+    use std::io::Write;
+
+    fn model(vocab_size: usize, d_model: usize) -> CodeGenerationModel {
+        CodeGenerationModel::new(
+            ModelArchitecture::Transformer,
+            vocab_size,
+            d_model,
+            2,
+            1,
+            Some(16),
+            Some(16),
+        )
+    }
+
+    fn parameter(model: &CodeGenerationModel, wanted: &str) -> Vec<f32> {
+        let mut found = Vec::new();
+        model.visit_parameters(&mut |name, values| {
+            if name == wanted {
+                found = values.to_vec();
+            }
+        });
+        found
+    }
+
+    fn npy(shape: &[usize], values: &[f32]) -> Vec<u8> {
+        let dims: Vec<String> = shape.iter().map(|d| format!("{},", d)).collect();
+        let mut header = format!(
+            "{{'descr': '<f4', 'fortran_order': False, 'shape': ({}), }}",
+            dims.join(" ")
+        );
+        while (10 + header.len() + 1) % 64 != 0 {
+            header.push(' ');
+        }
+        header.push('\n');
+        let mut bytes = b"\x93NUMPY\x01\x00".to_vec();
+        bytes.extend_from_slice(&(header.len() as u16).to_le_bytes());
+        bytes.extend_from_slice(header.as_bytes());
+        bytes.extend(values.iter().flat_map(|v| v.to_le_bytes()));
+        bytes
+    }
+
+    #[test]
+    fn test_import_crops_larger_model() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("big.safetensors");
+        let big = model(16, 16).with_seed(9);
+        big.save_safetensors(&path).unwrap();
+
+        let mut small = model(16, 8);
+        let config = PretrainedConfig {
+            path,
+            ..PretrainedConfig::default()
+        };
+        let report = small
+            .import_pretrained(&config, &WGSLTokenizer::new(16, false))
+            .unwrap();
+
+        assert!(report.skipped.is_empty());
+        assert_eq!(report.loaded.len(), small.parameter_shapes().len());
+        assert!(report.cropped.contains(&"token_embedding".to_string()));
+        let source = parameter(&big, "token_embedding");
+        let embedding = parameter(&small, "token_embedding");
+        assert_eq!(embedding[..8], source[..8]);
+        assert_eq!(embedding[8..16], source[16..24]);
+    }
+
+    #[test]
+    fn test_import_matches_vocabulary_and_transposes() {
+        let mut tokenizer = WGSLTokenizer::new(64, false);
+        tokenizer.fit(&["fn main"], 1);
+        let fn_id = tokenizer.vocab["fn"];
+        let vocab_size = tokenizer.vocab_size();
+
+        // Pretrained vocabulary of three tokens where "Ġfn" is id 2, with a
+        // [vocab, d] embedding and a PyTorch-style [vocab, d] output layer
+        let wte: Vec<f32> = (0..3 * 8).map(|i| i as f32).collect();
+        let bytes: Vec<u8> = wte.iter().flat_map(|v| v.to_le_bytes()).collect();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("lm.safetensors");
+        safetensors::serialize_to_file(
+            [
+                (
+                    "wte",
+                    TensorView::new(Dtype::F32, vec![3, 8], &bytes).unwrap(),
+                ),
+                (
+                    "lm_head",
+                    TensorView::new(Dtype::F32, vec![3, 8], &bytes).unwrap(),
+                ),
+            ],
+            &None,
+            &path,
+        )
+        .unwrap();
+        let vocab_path = dir.path().join("vocab.json");
+        std::fs::write(&vocab_path, r#"{"a": 0, "b": 1, "Ġfn": 2}"#).unwrap();
+
+        let mut small = model(vocab_size, 8);
+        let before = parameter(&small, "token_embedding");
+        let config = PretrainedConfig {
+            path,
+            vocab: Some(vocab_path),
+            map: [
+                ("token_embedding".to_string(), "wte".to_string()),
+                ("output.weight".to_string(), "lm_head".to_string()),
+            ]
+            .into_iter()
+            .collect(),
+            transpose: vec!["output.weight".to_string()],
+        };
+        let report = small.import_pretrained(&config, &tokenizer).unwrap();
+
+        assert_eq!(report.matched_tokens, Some(1));
+        assert_eq!(report.loaded.len(), 2);
+        let embedding = parameter(&small, "token_embedding");
+        assert_eq!(embedding[fn_id * 8..fn_id * 8 + 8], wte[16..24]);
+        let other = (fn_id + 1) % vocab_size;
+        assert_eq!(
+            embedding[other * 8..other * 8 + 8],
+            before[other * 8..other * 8 + 8]
+        );
+        let output = parameter(&small, "output.weight");
+        let column: Vec<f32> = (0..8).map(|d| output[d * vocab_size + fn_id]).collect();
+        assert_eq!(column, wte[16..24]);
+    }
+
+    #[test]
+    fn test_npz_weights() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("weights.npz");
+        let mut zip = zip::ZipWriter::new(std::fs::File::create(&path).unwrap());
+        let options = zip::write::FileOptions::default();
+        zip.start_file("output.bias.npy", options).unwrap();
+        zip.write_all(&npy(&[4], &[1.0, 2.0, 3.0, 4.0])).unwrap();
+        zip.start_file("w.npy", options).unwrap();
+        zip.write_all(&npy(&[2, 3], &[0.0, 1.0, 2.0, 3.0, 4.0, 5.0]))
+            .unwrap();
+        zip.finish().unwrap();
+
+        let weights = PretrainedWeights::load(&path).unwrap();
+        assert_eq!(weights.tensors["w"].shape, vec![2, 3]);
+        assert_eq!(
+            weights.tensors["output.bias"].data,
+            vec![1.0, 2.0, 3.0, 4.0]
+        );
+        assert!(parse_npy(b"not numpy").is_err());
+    }
+}
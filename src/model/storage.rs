@@ -0,0 +1,133 @@
+//! On-disk form of models
+//!
+//! A [`CodeGenerationModel`] is serialized as its hyperparameters, its
+//! architecture options (as JSON, so options can be added without breaking
+//! existing files) and every parameter by name, leaving the in-memory layout
+//! free to change.
+
+use super::checkpoint::json_string;
+use super::{CodeGenerationModel, ModelArchitecture};
+use crate::config::{
+    Activation, AttentionConfig, Dtype, InitScheme, NormPlacement, PositionalEncoding,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Architecture choices beyond the layer sizes
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct ModelOptions {
+    pub activation: Activation,
+    pub norm_placement: NormPlacement,
+    pub positional_encoding: PositionalEncoding,
+    pub attention: AttentionConfig,
+    pub embedding_dtype: Dtype,
+    pub init: InitScheme,
+}
+
+#[derive(Serialize, Deserialize)]
+pub(super) struct SerializedModel {
+    architecture: ModelArchitecture,
+    vocab_size: usize,
+    d_model: usize,
+    nhead: usize,
+    num_layers: usize,
+    max_seq_len: usize,
+    dim_feedforward: usize,
+    #[serde(with = "json_string")]
+    options: ModelOptions,
+    tensors: Vec<(String, Vec<f32>)>,
+}
+
+impl From<CodeGenerationModel> for SerializedModel {
+    fn from(model: CodeGenerationModel) -> Self {
+        let mut tensors = Vec::new();
+        model.visit_parameters(&mut |name, values| {
+            tensors.push((name.to_string(), values.to_vec()))
+        });
+        Self {
+            architecture: model.architecture.clone(),
+            vocab_size: model.vocab_size,
+            d_model: model.d_model,
+            nhead: model.nhead,
+            num_layers: model.num_layers,
+            max_seq_len: model.max_seq_len,
+            dim_feedforward: model.dim_feedforward,
+            options: model.options(),
+            tensors,
+        }
+    }
+}
+
+impl TryFrom<SerializedModel> for CodeGenerationModel {
+    type Error = String;
+
+    fn try_from(serialized: SerializedModel) -> Result<Self, String> {
+        let mut model = CodeGenerationModel::new(
+            serialized.architecture,
+            serialized.vocab_size,
+            serialized.d_model,
+            serialized.nhead,
+            serialized.num_layers,
+            Some(serialized.dim_feedforward),
+            Some(serialized.max_seq_len),
+        )
+        .with_options(&serialized.options);
+
+        let tensors: HashMap<String, Vec<f32>> = serialized.tensors.into_iter().collect();
+        let mut missing = None;
+        model.visit_parameters_mut(&mut |name, params| match tensors.get(name) {
+            Some(values) if values.len() == params.len() => params.copy_from_slice(values),
+            _ => {
+                missing.get_or_insert_with(|| name.to_string());
+            }
+        });
+        match missing {
+            Some(name) => Err(format!(
+                "saved model has no tensor '{}' of the expected size",
+                name
+            )),
+            None => Ok(model),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serialized_model_roundtrip() {
+        let model = CodeGenerationModel::new(
+            ModelArchitecture::Transformer,
+            16,
+            8,
+            2,
+            1,
+            Some(16),
+            Some(16),
+        )
+        .with_activation(Activation::Swiglu)
+        .with_norm_placement(NormPlacement::Pre)
+        .with_positional_encoding(PositionalEncoding::Rope)
+        .with_attention(AttentionConfig {
+            block_size: 2,
+            window: 3,
+        })
+        .with_seed(5)
+        .with_embedding_dtype(Dtype::F16);
+        let bytes = bincode::serialize(&model).unwrap();
+        let loaded: CodeGenerationModel = bincode::deserialize(&bytes).unwrap();
+
+        assert_eq!(loaded.activation, Activation::Swiglu);
+        assert_eq!(loaded.norm_placement, NormPlacement::Pre);
+        assert_eq!(loaded.positional_encoding, PositionalEncoding::Rope);
+        assert_eq!(loaded.attention, model.attention);
+        assert_eq!(loaded.embedding_dtype, Dtype::F16);
+        let ids = [4, 5, 6];
+        assert_eq!(
+            loaded.decode(&loaded.encode(&ids), &[2, 4]),
+            model.decode(&model.encode(&ids), &[2, 4])
+        );
+    }
+}
@@ -0,0 +1,254 @@
+//! Layer-by-layer parameter breakdown and weight statistics of a model
+
+use super::CodeGenerationModel;
+use std::fmt;
+
+/// Bytes per parameter of an `f32` tensor
+const BYTES_PER_PARAMETER: usize = std::mem::size_of::<f32>();
+
+/// Copies of every parameter held while training with Adam: the weights,
+/// their gradients and the two moment estimates
+const TRAINING_COPIES: usize = 4;
+
+/// One trainable tensor of a [`ModelSummary`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct LayerSummary {
+    pub name: String,
+    pub shape: Vec<usize>,
+    pub parameters: usize,
+    /// Size of the weights in bytes
+    pub bytes: usize,
+}
+
+/// Every trainable tensor of a model with its size
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModelSummary {
+    pub layers: Vec<LayerSummary>,
+}
+
+impl ModelSummary {
+    pub fn total_parameters(&self) -> usize {
+        self.layers.iter().map(|layer| layer.parameters).sum()
+    }
+
+    /// Size of the weights in bytes
+    pub fn total_bytes(&self) -> usize {
+        self.layers.iter().map(|layer| layer.bytes).sum()
+    }
+
+    /// Rough memory needed to train the model, excluding activations
+    pub fn training_bytes(&self) -> usize {
+        self.total_bytes() * TRAINING_COPIES
+    }
+}
+
+/// `bytes` in B, KB, MB or GB
+pub fn format_bytes(bytes: usize) -> String {
+    let mut size = bytes as f64;
+    for unit in ["B", "KB", "MB"] {
+        if size < 1024.0 {
+            return match unit {
+                "B" => format!("{} B", bytes),
+                _ => format!("{:.1} {}", size, unit),
+            };
+        }
+        size /= 1024.0;
+    }
+    format!("{:.1} GB", size)
+}
+
+impl fmt::Display for ModelSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name_width = self
+            .layers
+            .iter()
+            .map(|layer| layer.name.len())
+            .max()
+            .unwrap_or(0)
+            .max("Layer".len());
+        writeln!(
+            f,
+            "{:<name_width$}  {:<14}  {:>12}  {:>10}",
+            "Layer", "Shape", "Parameters", "Memory"
+        )?;
+        writeln!(f, "{}", "-".repeat(name_width + 44))?;
+        for layer in &self.layers {
+            writeln!(
+                f,
+                "{:<name_width$}  {:<14}  {:>12}  {:>10}",
+                layer.name,
+                format!("{:?}", layer.shape),
+                layer.parameters,
+                format_bytes(layer.bytes)
+            )?;
+        }
+        writeln!(f, "{}", "-".repeat(name_width + 44))?;
+        writeln!(
+            f,
+            "{:<name_width$}  {:<14}  {:>12}  {:>10}",
+            "Total",
+            "",
+            self.total_parameters(),
+            format_bytes(self.total_bytes())
+        )?;
+        write!(
+            f,
+            "Estimated training memory (weights, gradients, Adam moments): {}",
+            format_bytes(self.training_bytes())
+        )
+    }
+}
+
+/// Value statistics over every parameter of a model
+#[derive(Debug, Clone, PartialEq)]
+pub struct WeightStats {
+    /// Smallest finite value
+    pub min: f32,
+    /// Largest finite value
+    pub max: f32,
+    /// Mean of the finite values
+    pub mean: f64,
+    pub nan_count: usize,
+    pub inf_count: usize,
+    /// Tensors holding NaN or infinite values
+    pub non_finite: Vec<String>,
+}
+
+impl WeightStats {
+    pub fn is_finite(&self) -> bool {
+        self.nan_count == 0 && self.inf_count == 0
+    }
+}
+
+impl CodeGenerationModel {
+    /// Range, mean and non-finite counts of the weights
+    pub fn weight_stats(&self) -> WeightStats {
+        let mut stats = WeightStats {
+            min: f32::INFINITY,
+            max: f32::NEG_INFINITY,
+            mean: 0.0,
+            nan_count: 0,
+            inf_count: 0,
+            non_finite: Vec::new(),
+        };
+        let (mut sum, mut finite) = (0.0f64, 0usize);
+        self.visit_parameters(&mut |name, values| {
+            let before = stats.nan_count + stats.inf_count;
+            for &value in values {
+                if value.is_nan() {
+                    stats.nan_count += 1;
+                } else if value.is_infinite() {
+                    stats.inf_count += 1;
+                } else {
+                    stats.min = stats.min.min(value);
+                    stats.max = stats.max.max(value);
+                    sum += value as f64;
+                    finite += 1;
+                }
+            }
+            if stats.nan_count + stats.inf_count > before {
+                stats.non_finite.push(name.to_string());
+            }
+        });
+        if finite == 0 {
+            (stats.min, stats.max) = (0.0, 0.0);
+        } else {
+            stats.mean = sum / finite as f64;
+        }
+        stats
+    }
+
+    /// Shape, parameter count and memory of every trainable tensor
+    pub fn summary(&self) -> ModelSummary {
+        let layers = self
+            .parameter_shapes()
+            .into_iter()
+            .map(|(name, shape)| {
+                let parameters: usize = shape.iter().product();
+                let bytes_per_parameter = match name.as_str() {
+                    "token_embedding" => self.embedding_dtype.size(),
+                    _ => BYTES_PER_PARAMETER,
+                };
+                LayerSummary {
+                    name,
+                    shape,
+                    parameters,
+                    bytes: parameters * bytes_per_parameter,
+                }
+            })
+            .collect();
+        ModelSummary { layers }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::ModelArchitecture;
+
+    #[test]
+    fn test_summary_matches_parameter_count() {
+        let model = CodeGenerationModel::new(
+            ModelArchitecture::Transformer,
+            16,
+            8,
+            2,
+            1,
+            Some(16),
+            Some(16),
+        );
+        let summary = model.summary();
+
+        assert_eq!(summary.total_parameters(), model.num_parameters());
+        assert_eq!(summary.total_bytes(), model.num_parameters() * 4);
+        let embedding = &summary.layers[0];
+        assert_eq!(embedding.name, "token_embedding");
+        assert_eq!(embedding.shape, vec![16, 8]);
+        assert_eq!(embedding.parameters, 128);
+
+        let table = summary.to_string();
+        assert!(table.contains("encoder.0.self_attn.w_q"));
+        assert!(table.contains("[8, 8]"));
+
+        let half = model
+            .with_embedding_dtype(crate::config::Dtype::F16)
+            .summary();
+        assert_eq!(half.total_bytes(), summary.total_bytes() - 128 * 2);
+    }
+
+    #[test]
+    fn test_weight_stats_counts_non_finite_values() {
+        let mut model = CodeGenerationModel::new(
+            ModelArchitecture::Transformer,
+            16,
+            8,
+            2,
+            1,
+            Some(16),
+            Some(16),
+        );
+        let stats = model.weight_stats();
+        assert!(stats.is_finite());
+        assert!(stats.min < 0.0 && stats.max > 0.0);
+
+        model.visit_parameters_mut(&mut |name, values| {
+            if name == "output.bias" {
+                values[0] = f32::NAN;
+                values[1] = f32::INFINITY;
+                values[2] = 100.0;
+            }
+        });
+        let stats = model.weight_stats();
+        assert_eq!((stats.nan_count, stats.inf_count), (1, 1));
+        assert_eq!(stats.non_finite, vec!["output.bias".to_string()]);
+        assert_eq!(stats.max, 100.0);
+    }
+
+    #[test]
+    fn test_format_bytes() {
+        assert_eq!(format_bytes(512), "512 B");
+        assert_eq!(format_bytes(2048), "2.0 KB");
+        assert_eq!(format_bytes(3 * 1024 * 1024), "3.0 MB");
+        assert_eq!(format_bytes(5 * 1024 * 1024 * 1024), "5.0 GB");
+    }
+}
@@ -0,0 +1,248 @@
+//! Exporting and importing model weights in the safetensors format
+//!
+//! Every trainable tensor is stored as little-endian `F32` under its dotted
+//! parameter name (`token_embedding`, `encoder.0.self_attn.w_q`,
+//! `output.weight`, ...) and the model hyperparameters go in the
+//! `__metadata__` block (architecture options as JSON under `options`), so the file can be opened with PyTorch or candle and
+//! turned back into a model here.
+
+use super::checkpoint::{with_path, CheckpointErrorKind};
+use super::{CodeGenerationModel, ModelArchitecture};
+use safetensors::tensor::TensorView;
+use safetensors::{Dtype, SafeTensors};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Value of the `format` metadata entry written by [`CodeGenerationModel::save_safetensors`]
+pub const WEIGHTS_FORMAT: &str = "tiny-agent-trainer";
+
+impl CodeGenerationModel {
+    /// Write every parameter and the hyperparameters to a safetensors file
+    pub fn save_safetensors<P: AsRef<Path>>(&self, path: P) -> crate::Result<()> {
+        let mut tensors: Vec<(String, Vec<u8>)> = Vec::new();
+        self.visit_parameters(&mut |name, values| {
+            let bytes = values.iter().flat_map(|v| v.to_le_bytes()).collect();
+            tensors.push((name.to_string(), bytes));
+        });
+        let views = tensors
+            .iter()
+            .zip(self.parameter_shapes())
+            .map(|((name, bytes), (_, shape))| {
+                Ok((name.as_str(), TensorView::new(Dtype::F32, shape, bytes)?))
+            })
+            .collect::<crate::Result<Vec<_>>>()?;
+
+        safetensors::serialize_to_file(views, &Some(self.weights_metadata()), path.as_ref())?;
+        Ok(())
+    }
+
+    /// Build a model from a file written by [`save_safetensors`](Self::save_safetensors)
+    pub fn from_safetensors<P: AsRef<Path>>(path: P) -> crate::Result<Self> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path)?;
+        let (_, header) = SafeTensors::read_metadata(&bytes)?;
+        let metadata = header.metadata().clone().unwrap_or_default();
+        let field = |key: &str| -> crate::Result<usize> {
+            metadata
+                .get(key)
+                .and_then(|value| value.parse().ok())
+                .ok_or_else(|| CheckpointErrorKind::MissingMetadata(key.to_string()).at(path))
+        };
+        let architecture = match metadata.get("architecture").map(String::as_str) {
+            Some("lstm") => ModelArchitecture::LSTM,
+            _ => ModelArchitecture::Transformer,
+        };
+
+        let mut model = Self::new(
+            architecture,
+            field("vocab_size")?,
+            field("d_model")?,
+            field("nhead")?,
+            field("num_layers")?,
+            Some(field("dim_feedforward")?),
+            Some(field("max_seq_len")?),
+        );
+        if let Some(options) = metadata.get("options") {
+            model = model.with_options(&serde_json::from_str(options)?);
+        }
+        model.load_safetensors_bytes(&bytes, path)?;
+        Ok(model)
+    }
+
+    /// Overwrite this model's parameters with the tensors of the same name in
+    /// `path`; every parameter must be present with a matching shape
+    pub fn load_safetensors<P: AsRef<Path>>(&mut self, path: P) -> crate::Result<()> {
+        let path = path.as_ref();
+        self.load_safetensors_bytes(&std::fs::read(path)?, path)
+    }
+
+    fn load_safetensors_bytes(&mut self, bytes: &[u8], path: &Path) -> crate::Result<()> {
+        let file = SafeTensors::deserialize(bytes)?;
+        let mut values: HashMap<String, Vec<f32>> = HashMap::new();
+        for (name, expected) in self.parameter_shapes() {
+            let tensor = file
+                .tensor(&name)
+                .map_err(|_| CheckpointErrorKind::MissingTensor(name.clone()).at(path))?;
+            if tensor.shape() != expected.as_slice() {
+                return Err(CheckpointErrorKind::ShapeMismatch {
+                    name,
+                    found: tensor.shape().to_vec(),
+                    expected,
+                }
+                .at(path));
+            }
+            values.insert(
+                name,
+                tensor_to_f32(&tensor).map_err(|e| with_path(e, path))?,
+            );
+        }
+        let unused = file.len() - values.len();
+        if unused > 0 {
+            tracing::warn!(
+                "Ignoring {} tensor(s) in {} that are not model parameters",
+                unused,
+                path.display()
+            );
+        }
+
+        self.visit_parameters_mut(&mut |name, params| params.copy_from_slice(&values[name]));
+        Ok(())
+    }
+
+    fn weights_metadata(&self) -> HashMap<String, String> {
+        let architecture = match self.architecture {
+            ModelArchitecture::Transformer => "transformer",
+            ModelArchitecture::LSTM => "lstm",
+        };
+        [
+            ("format", WEIGHTS_FORMAT.to_string()),
+            ("architecture", architecture.to_string()),
+            ("vocab_size", self.vocab_size.to_string()),
+            ("d_model", self.d_model.to_string()),
+            ("nhead", self.nhead.to_string()),
+            ("num_layers", self.num_layers.to_string()),
+            ("dim_feedforward", self.dim_feedforward.to_string()),
+            ("max_seq_len", self.max_seq_len.to_string()),
+            (
+                "options",
+                serde_json::to_string(&self.options()).unwrap_or_default(),
+            ),
+        ]
+        .into_iter()
+        .map(|(key, value)| (key.to_string(), value))
+        .collect()
+    }
+}
+
+/// Decode a tensor's little-endian data to `f32`
+pub(crate) fn tensor_to_f32(tensor: &TensorView) -> crate::Result<Vec<f32>> {
+    match tensor.dtype() {
+        Dtype::F32 => Ok(tensor
+            .data()
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect()),
+        Dtype::F64 => Ok(tensor
+            .data()
+            .chunks_exact(8)
+            .map(|b| f64::from_le_bytes(b.try_into().expect("8-byte chunk")) as f32)
+            .collect()),
+        Dtype::F16 => Ok(tensor
+            .data()
+            .chunks_exact(2)
+            .map(|b| half::f16::from_le_bytes([b[0], b[1]]).to_f32())
+            .collect()),
+        Dtype::BF16 => Ok(tensor
+            .data()
+            .chunks_exact(2)
+            .map(|b| half::bf16::from_le_bytes([b[0], b[1]]).to_f32())
+            .collect()),
+        other => Err(CheckpointErrorKind::UnsupportedDtype(format!("{:?}", other)).into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Activation;
+
+    fn tiny_model() -> CodeGenerationModel {
+        CodeGenerationModel::new(
+            ModelArchitecture::Transformer,
+            16,
+            8,
+            2,
+            1,
+            Some(16),
+            Some(16),
+        )
+    }
+
+    #[test]
+    fn test_safetensors_roundtrip() {
+        let model = tiny_model().with_seed(1);
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("model.safetensors");
+        model.save_safetensors(&path).unwrap();
+
+        let bytes = std::fs::read(&path).unwrap();
+        let file = SafeTensors::deserialize(&bytes).unwrap();
+        let w_q = file.tensor("encoder.0.self_attn.w_q").unwrap();
+        assert_eq!(w_q.dtype(), Dtype::F32);
+        assert_eq!(w_q.shape(), &[8, 8]);
+        assert_eq!(file.len(), model.parameter_shapes().len());
+
+        let loaded = CodeGenerationModel::from_safetensors(&path).unwrap();
+        let ids = [4, 5, 6];
+        let expected = model.decode(&model.encode(&ids), &[2, 4]);
+        assert_eq!(loaded.decode(&loaded.encode(&ids), &[2, 4]), expected);
+
+        let mut other = tiny_model();
+        other.load_safetensors(&path).unwrap();
+        assert_eq!(other.decode(&other.encode(&ids), &[2, 4]), expected);
+    }
+
+    #[test]
+    fn test_safetensors_keeps_activation() {
+        let model = tiny_model()
+            .with_activation(Activation::Swiglu)
+            .with_seed(2);
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("model.safetensors");
+        model.save_safetensors(&path).unwrap();
+
+        let loaded = CodeGenerationModel::from_safetensors(&path).unwrap();
+        assert_eq!(loaded.activation, Activation::Swiglu);
+        let ids = [4, 5, 6];
+        assert_eq!(
+            loaded.decode(&loaded.encode(&ids), &[2, 4]),
+            model.decode(&model.encode(&ids), &[2, 4])
+        );
+    }
+
+    #[test]
+    fn test_load_rejects_mismatched_shapes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("model.safetensors");
+        tiny_model().save_safetensors(&path).unwrap();
+
+        let mut wider = CodeGenerationModel::new(
+            ModelArchitecture::Transformer,
+            20,
+            8,
+            2,
+            1,
+            Some(16),
+            Some(16),
+        );
+        let error = wider.load_safetensors(&path).unwrap_err();
+        assert!(error.to_string().contains("token_embedding"), "{}", error);
+        assert!(matches!(
+            error,
+            crate::Error::CheckpointError {
+                kind: CheckpointErrorKind::ShapeMismatch { .. },
+                ..
+            }
+        ));
+    }
+}
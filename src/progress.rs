@@ -0,0 +1,21 @@
+//! Terminal progress bars for long-running loops
+
+use indicatif::{ProgressBar, ProgressStyle};
+
+/// Bar over `len` steps prefixed with `label`; hidden when `enabled` is false
+/// (indicatif also hides it when stderr is not a terminal)
+pub(crate) fn bar(len: u64, label: &str, enabled: bool) -> ProgressBar {
+    if !enabled {
+        return ProgressBar::hidden();
+    }
+    let bar = ProgressBar::new(len);
+    bar.set_style(
+        ProgressStyle::with_template(
+            "{prefix} [{bar:30}] {pos}/{len} ({per_sec}, ETA {eta}) {msg}",
+        )
+        .expect("valid progress template")
+        .progress_chars("=> "),
+    );
+    bar.set_prefix(label.to_string());
+    bar
+}
@@ -0,0 +1,373 @@
+//! Python bindings for curating datasets and evaluating models
+//!
+//! Enabled by the `python` feature so notebooks can load, filter and inspect
+//! datasets, validate shaders and score trained checkpoints while training
+//! stays in the Rust CLI. Build a wheel with `maturin build --release` (the
+//! bundled `pyproject.toml` turns the feature on). Reports and results are
+//! returned as plain dicts with the field names of their Rust types, the same
+//! shape `--json` prints.
+
+use crate::config::GenerationConfig;
+use crate::dataset::{Difficulty, WGSLDataset, WGSLExample};
+use crate::eval::Evaluator;
+use crate::inference::WGSLGenerator;
+use crate::tokenizer::WGSLTokenizer;
+use crate::wgsl::WGSLValidator;
+use pyo3::exceptions::{PyOSError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use pythonize::{depythonize, pythonize};
+use std::path::PathBuf;
+
+impl From<crate::Error> for PyErr {
+    fn from(error: crate::Error) -> Self {
+        match error {
+            crate::Error::IoError(e) => PyOSError::new_err(e.to_string()),
+            error => PyValueError::new_err(error.to_string()),
+        }
+    }
+}
+
+/// Convert a serializable report to Python dicts and lists
+fn to_py<T: serde::Serialize>(py: Python<'_>, value: &T) -> PyResult<PyObject> {
+    Ok(pythonize(py, value)
+        .map_err(|e| PyValueError::new_err(e.to_string()))?
+        .unbind())
+}
+
+#[pyclass(name = "Tokenizer", module = "tiny_agent_trainer")]
+pub struct PyTokenizer {
+    inner: WGSLTokenizer,
+}
+
+#[pymethods]
+impl PyTokenizer {
+    #[new]
+    #[pyo3(signature = (max_length = 512, lowercase = false))]
+    fn new(max_length: usize, lowercase: bool) -> Self {
+        Self {
+            inner: WGSLTokenizer::new(max_length, lowercase),
+        }
+    }
+
+    /// Load a tokenizer saved as JSON
+    #[staticmethod]
+    fn load(path: PathBuf) -> PyResult<Self> {
+        Ok(Self {
+            inner: WGSLTokenizer::load(path)?,
+        })
+    }
+
+    fn save(&self, path: PathBuf) -> PyResult<()> {
+        Ok(self.inner.save(path)?)
+    }
+
+    /// Build the vocabulary from `texts`, keeping tokens seen `min_freq` times
+    #[pyo3(signature = (texts, min_freq = 1))]
+    fn fit(&mut self, texts: Vec<String>, min_freq: usize) {
+        self.inner.fit(&texts, min_freq);
+    }
+
+    fn tokenize(&self, text: &str) -> Vec<String> {
+        self.inner.tokenize(text)
+    }
+
+    fn encode(&self, text: &str) -> Vec<usize> {
+        self.inner.encode_text(text)
+    }
+
+    /// Ids truncated or padded to `max_len`, and the mask of real tokens
+    fn encode_padded(&self, text: &str, max_len: usize) -> (Vec<usize>, Vec<bool>) {
+        self.inner.encode_padded(text, max_len)
+    }
+
+    /// Ids of every text padded to the longest, at most `max_len`, with masks
+    fn encode_padded_batch(
+        &self,
+        texts: Vec<String>,
+        max_len: usize,
+    ) -> Vec<(Vec<usize>, Vec<bool>)> {
+        self.inner.encode_padded_batch(&texts, max_len)
+    }
+
+    fn decode(&self, ids: Vec<usize>) -> String {
+        self.inner.decode_to_text(&ids)
+    }
+
+    #[getter]
+    fn vocab_size(&self) -> usize {
+        self.inner.vocab_size()
+    }
+
+    fn __len__(&self) -> usize {
+        self.inner.vocab_size()
+    }
+}
+
+#[pyclass(name = "Validator", module = "tiny_agent_trainer")]
+#[derive(Clone)]
+pub struct PyValidator {
+    inner: WGSLValidator,
+}
+
+#[pymethods]
+impl PyValidator {
+    /// Validator for `profile`: "webgpu-core" (the default),
+    /// "native-extended" or "custom"
+    #[new]
+    #[pyo3(signature = (profile = None))]
+    fn new(profile: Option<&str>) -> PyResult<Self> {
+        let mut inner = WGSLValidator::new();
+        if let Some(profile) = profile {
+            inner = inner.with_profile(profile.parse()?);
+        }
+        Ok(Self { inner })
+    }
+
+    /// Parse and validate `code`, returning `is_valid`, `errors` and `warnings`
+    fn validate(&self, py: Python<'_>, code: &str) -> PyResult<PyObject> {
+        to_py(py, &self.inner.validate(code)?)
+    }
+
+    fn is_valid(&self, code: &str) -> PyResult<bool> {
+        Ok(self.inner.validate(code)?.is_valid)
+    }
+}
+
+#[pyclass(name = "Dataset", module = "tiny_agent_trainer")]
+#[derive(Clone)]
+pub struct PyDataset {
+    inner: WGSLDataset,
+}
+
+#[pymethods]
+impl PyDataset {
+    #[new]
+    fn new() -> Self {
+        Self {
+            inner: WGSLDataset::new(),
+        }
+    }
+
+    /// Load a `.toml`, `.json` or `.jsonl` dataset
+    #[staticmethod]
+    fn load(path: PathBuf) -> PyResult<Self> {
+        Ok(Self {
+            inner: WGSLDataset::from_file(path)?,
+        })
+    }
+
+    /// Save as `.toml`, `.json` or `.jsonl` based on the extension
+    fn save(&self, path: PathBuf) -> PyResult<()> {
+        Ok(self.inner.to_file(path)?)
+    }
+
+    #[pyo3(signature = (natural_language, wgsl_code, category = None, tags = Vec::new(), difficulty = None))]
+    fn add(
+        &mut self,
+        natural_language: String,
+        wgsl_code: String,
+        category: Option<String>,
+        tags: Vec<String>,
+        difficulty: Option<&str>,
+    ) -> PyResult<()> {
+        let difficulty = difficulty
+            .map(|d| match d {
+                "easy" => Ok(Difficulty::Easy),
+                "medium" => Ok(Difficulty::Medium),
+                "hard" => Ok(Difficulty::Hard),
+                other => Err(PyValueError::new_err(format!(
+                    "Unknown difficulty '{}'. Must be one of: easy, medium, hard",
+                    other
+                ))),
+            })
+            .transpose()?;
+        self.inner.examples.push(WGSLExample {
+            category,
+            tags,
+            difficulty,
+            ..WGSLExample::new(natural_language, wgsl_code)
+        });
+        Ok(())
+    }
+
+    /// Examples as dicts
+    #[getter]
+    fn examples(&self, py: Python<'_>) -> PyResult<PyObject> {
+        to_py(py, &self.inner.examples)
+    }
+
+    fn __len__(&self) -> usize {
+        self.inner.len()
+    }
+
+    fn __getitem__(&self, py: Python<'_>, index: usize) -> PyResult<PyObject> {
+        match self.inner.examples.get(index) {
+            Some(example) => to_py(py, example),
+            None => Err(pyo3::exceptions::PyIndexError::new_err(
+                "dataset index out of range",
+            )),
+        }
+    }
+
+    fn filter_valid(&self, validator: &PyValidator) -> PyResult<Self> {
+        Ok(Self {
+            inner: self.inner.filter_valid(&validator.inner)?,
+        })
+    }
+
+    fn filter_by_category(&self, category: &str) -> Self {
+        Self {
+            inner: self.inner.filter_by_category(category),
+        }
+    }
+
+    fn filter_by_tag(&self, tag: &str) -> Self {
+        Self {
+            inner: self.inner.filter_by_tag(tag),
+        }
+    }
+
+    fn dedup(&self) -> Self {
+        Self {
+            inner: self.inner.dedup(),
+        }
+    }
+
+    fn merge(&self, other: &PyDataset) -> Self {
+        Self {
+            inner: self.inner.merge(&other.inner),
+        }
+    }
+
+    /// `(train, val, test)` split in order
+    fn split(&self, train_ratio: f32, val_ratio: f32) -> (Self, Self, Self) {
+        let (train, val, test) = self.inner.split(train_ratio, val_ratio);
+        (
+            Self { inner: train },
+            Self { inner: val },
+            Self { inner: test },
+        )
+    }
+
+    fn categories(&self) -> Vec<String> {
+        self.inner.categories()
+    }
+
+    /// Counts, token lengths, categories, duplicates and validity
+    #[pyo3(signature = (tokenizer, validator = None))]
+    fn stats(
+        &self,
+        py: Python<'_>,
+        tokenizer: &PyTokenizer,
+        validator: Option<&PyValidator>,
+    ) -> PyResult<PyObject> {
+        let validator = validator.map_or_else(WGSLValidator::new, |v| v.inner.clone());
+        to_py(py, &self.inner.stats(&tokenizer.inner, &validator)?)
+    }
+
+    fn content_hash(&self) -> String {
+        self.inner.content_hash()
+    }
+}
+
+#[pyclass(name = "Generator", module = "tiny_agent_trainer")]
+pub struct PyGenerator {
+    inner: WGSLGenerator,
+}
+
+#[pymethods]
+impl PyGenerator {
+    /// Load a model and its tokenizer from a checkpoint file
+    #[staticmethod]
+    fn from_checkpoint(path: PathBuf) -> PyResult<Self> {
+        Ok(Self {
+            inner: WGSLGenerator::from_checkpoint(path)?,
+        })
+    }
+
+    /// Generate code for `prompt`, returning the generation result as a dict
+    ///
+    /// `config` is a dict shaped like the `[generation]` config section;
+    /// missing keys take their defaults.
+    #[pyo3(signature = (prompt, config = None, seed = None))]
+    fn generate(
+        &self,
+        py: Python<'_>,
+        prompt: &str,
+        config: Option<&Bound<'_, PyDict>>,
+        seed: Option<u64>,
+    ) -> PyResult<PyObject> {
+        let config: GenerationConfig = match config {
+            Some(config) => {
+                depythonize(config.as_any()).map_err(|e| PyValueError::new_err(e.to_string()))?
+            }
+            None => GenerationConfig::default(),
+        };
+        let errors = config.validation_errors();
+        if !errors.is_empty() {
+            return Err(PyValueError::new_err(errors.join("; ")));
+        }
+        let validator = WGSLValidator::new();
+        let result = py.allow_threads(|| {
+            self.inner
+                .generate_with_config(prompt, &config, seed, &validator)
+        })?;
+        to_py(py, &result)
+    }
+
+    /// Mean-pooled encoder embedding of `prompt`
+    fn embed(&self, py: Python<'_>, prompt: &str) -> Vec<f32> {
+        py.allow_threads(|| self.inner.embed(prompt))
+    }
+
+    /// Cosine similarity of the embeddings of two prompts
+    fn similarity(&self, py: Python<'_>, a: &str, b: &str) -> f32 {
+        py.allow_threads(|| self.inner.similarity(a, b))
+    }
+
+    /// `(index, similarity)` of the `k` examples of `dataset` most similar
+    /// to `prompt`, most similar first
+    #[pyo3(signature = (prompt, dataset, k = 5))]
+    fn nearest_examples(
+        &self,
+        py: Python<'_>,
+        prompt: &str,
+        dataset: &PyDataset,
+        k: usize,
+    ) -> Vec<(usize, f32)> {
+        py.allow_threads(|| self.inner.nearest_examples(prompt, &dataset.inner, k))
+    }
+
+    /// Generate for every example of `dataset` and score the output against
+    /// its reference, returning the evaluation report as a dict
+    #[pyo3(signature = (dataset, validator = None))]
+    fn evaluate(
+        &self,
+        py: Python<'_>,
+        dataset: &PyDataset,
+        validator: Option<&PyValidator>,
+    ) -> PyResult<PyObject> {
+        let validator = validator.map_or_else(WGSLValidator::new, |v| v.inner.clone());
+        let report =
+            py.allow_threads(|| Evaluator::new(validator).evaluate(&self.inner, &dataset.inner))?;
+        to_py(py, &report)
+    }
+
+    #[getter]
+    fn tokenizer(&self) -> PyTokenizer {
+        PyTokenizer {
+            inner: self.inner.tokenizer().clone(),
+        }
+    }
+}
+
+#[pymodule]
+fn tiny_agent_trainer(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add("__version__", crate::VERSION)?;
+    m.add_class::<PyTokenizer>()?;
+    m.add_class::<PyValidator>()?;
+    m.add_class::<PyDataset>()?;
+    m.add_class::<PyGenerator>()?;
+    Ok(())
+}
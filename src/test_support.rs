@@ -0,0 +1,26 @@
+//! Fixtures shared by the unit tests
+
+use crate::model::{CodeGenerationModel, ModelArchitecture};
+use crate::tokenizer::WGSLTokenizer;
+
+/// Untrained one-layer transformer over `tokenizer`'s vocabulary, small
+/// enough to run a full generation in milliseconds
+pub(crate) fn tiny_model(tokenizer: &WGSLTokenizer) -> CodeGenerationModel {
+    CodeGenerationModel::new(
+        ModelArchitecture::Transformer,
+        tokenizer.vocab_size(),
+        16,
+        2,
+        1,
+        Some(32),
+        Some(32),
+    )
+}
+
+/// Tokenizer fitted on `corpus` and a [`tiny_model`] over its vocabulary
+pub(crate) fn tiny_generator(corpus: &[&str]) -> (WGSLTokenizer, CodeGenerationModel) {
+    let mut tokenizer = WGSLTokenizer::new(64, false);
+    tokenizer.fit(corpus, 1);
+    let model = tiny_model(&tokenizer);
+    (tokenizer, model)
+}
@@ -2,11 +2,30 @@
 //!
 //! Provides specialized tokenization for WGSL (WebGPU Shading Language) syntax
 
+mod normalize;
+mod round_trip;
+
+pub use normalize::PromptNormalizer;
+pub use round_trip::{round_trip, round_trip_corpus, RoundTrip};
+
 use regex::Regex;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::collections::HashMap;
 use std::path::Path;
 
+/// Schema version written to tokenizer JSON files; files without one
+/// predate versioning and count as version 1
+pub const TOKENIZER_SCHEMA_VERSION: u32 = 2;
+
+/// Every WGSL operator, longest first so matching the first that fits picks
+/// the longest: `x<<=2` is `x`, `<<=`, `2` and `a=-b` is `a`, `=`, `-`, `b`
+pub const WGSL_OPERATORS: [&str; 34] = [
+    "<<=", ">>=", "->", "<<", ">>", "<=", ">=", "==", "!=", "&&", "||", "+=", "-=", "*=", "/=",
+    "%=", "&=", "|=", "^=", "++", "--", "+", "-", "*", "/", "%", "&", "|", "^", "<", ">", "=", "!",
+    "~",
+];
+
 /// Special tokens used in the vocabulary
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum SpecialToken {
@@ -36,6 +55,79 @@ impl SpecialToken {
     }
 }
 
+/// Lexical category a token was matched as
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TokenCategory {
+    /// Registered task control token, such as `<compute>`
+    Control,
+    /// Keyword, such as `fn` or `return`
+    Keyword,
+    /// Templated type, such as `vec4<f32>`
+    Type,
+    /// Attribute, such as `@compute` or `@binding`
+    Attribute,
+    /// Integer, float or hex literal
+    Number,
+    /// Arithmetic, comparison, logical or bitwise operator, or `->`
+    Operator,
+    /// Brackets, separators and `.`
+    Punctuation,
+    /// Any other name, including scalar types and builtins
+    Identifier,
+}
+
+impl TokenCategory {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TokenCategory::Control => "control",
+            TokenCategory::Keyword => "keyword",
+            TokenCategory::Type => "type",
+            TokenCategory::Attribute => "attribute",
+            TokenCategory::Number => "number",
+            TokenCategory::Operator => "operator",
+            TokenCategory::Punctuation => "punctuation",
+            TokenCategory::Identifier => "identifier",
+        }
+    }
+}
+
+/// Tokens of a text with their ids, for checking vocabulary coverage
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenizedText {
+    pub tokens: Vec<String>,
+    /// Category each token was matched as
+    pub categories: Vec<TokenCategory>,
+    /// Id of each token; tokens outside the vocabulary get `<unk>`
+    pub ids: Vec<usize>,
+    /// Distinct tokens outside the vocabulary, in order of appearance
+    pub unknown: Vec<String>,
+}
+
+impl TokenizedText {
+    /// Fraction of tokens in the vocabulary; 1 for an empty text
+    pub fn coverage(&self) -> f64 {
+        if self.ids.is_empty() {
+            return 1.0;
+        }
+        let unknown_id = SpecialToken::Unknown.token_id();
+        let known = self.ids.iter().filter(|&&id| id != unknown_id).count();
+        known as f64 / self.ids.len() as f64
+    }
+
+    /// Fail with [`Error::TokenizerError`](crate::Error::TokenizerError)
+    /// if any token is outside the vocabulary
+    pub fn require_known(&self) -> crate::Result<()> {
+        if self.unknown.is_empty() {
+            return Ok(());
+        }
+        Err(crate::Error::TokenizerError {
+            unknown: self.unknown.clone(),
+            coverage: self.coverage(),
+        })
+    }
+}
+
 /// WGSL-specialized tokenizer
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WGSLTokenizer {
@@ -49,11 +141,65 @@ pub struct WGSLTokenizer {
     pub max_length: usize,
     /// Convert to lowercase
     pub lowercase: bool,
+    /// Task control tokens such as `<compute>`, in registration order
+    #[serde(default)]
+    control_tokens: Vec<String>,
+    /// Rules natural language prompts are normalized with, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub normalizer: Option<PromptNormalizer>,
+    /// Changes to the built-in patterns, applied when loading
+    #[serde(default, skip_serializing_if = "PatternOverrides::is_empty")]
+    pattern_overrides: PatternOverrides,
     /// WGSL-specific regex patterns
     #[serde(skip)]
     patterns: WGSLPatterns,
 }
 
+/// Changes to the built-in WGSL patterns under `[tokenizer.patterns]`, for
+/// newer WGSL versions or project-specific extensions
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PatternOverrides {
+    /// Words matched as keywords besides the built-in ones, e.g. `enable`
+    pub keywords: Vec<String>,
+    /// Type names matched as types, with or without template arguments,
+    /// e.g. `binding_array` or `texture_depth_2d`
+    pub types: Vec<String>,
+    /// Attribute names matched after `@`, e.g. `must_use`
+    pub attributes: Vec<String>,
+    /// Pattern categories to stop matching; their text falls through to the
+    /// remaining patterns
+    pub disabled: Vec<TokenCategory>,
+}
+
+impl PatternOverrides {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Problems with the overrides, each prefixed with `field`'s subfields
+    pub fn validation_errors(&self, field: &str) -> Vec<String> {
+        let word = Regex::new(r"^@?[A-Za-z_][A-Za-z0-9_]*$").unwrap();
+        let mut errors = Vec::new();
+        for (name, words) in [
+            ("keywords", &self.keywords),
+            ("types", &self.types),
+            ("attributes", &self.attributes),
+        ] {
+            for w in words.iter().filter(|w| !word.is_match(w)) {
+                errors.push(format!("{}.{}: '{}' is not a name", field, name, w));
+            }
+        }
+        if self.disabled.contains(&TokenCategory::Control) {
+            errors.push(format!(
+                "{}.disabled: control tokens are not a pattern",
+                field
+            ));
+        }
+        errors
+    }
+}
+
 /// Compiled regex patterns for WGSL tokenization
 #[derive(Debug, Clone)]
 struct WGSLPatterns {
@@ -71,33 +217,88 @@ struct WGSLPatterns {
     operator: Regex,
     /// Punctuation
     punctuation: Regex,
+    /// Categories not matched
+    disabled: Vec<TokenCategory>,
 }
 
-impl Default for WGSLPatterns {
-    fn default() -> Self {
+impl WGSLPatterns {
+    /// The built-in patterns with `overrides` applied
+    fn new(overrides: &PatternOverrides) -> Self {
+        // Extra words as further alternatives, each preceded by `|`
+        let extra = |words: &[String]| -> String {
+            words
+                .iter()
+                .map(|w| format!("|{}", regex::escape(w.trim_start_matches('@'))))
+                .collect()
+        };
+        let extra_keywords: String = overrides
+            .keywords
+            .iter()
+            .map(|w| format!("|{}", regex::escape(w)))
+            .collect();
+        let extra_types = match extra(&overrides.types).strip_prefix('|') {
+            Some(types) => format!(r"|\b({})\b(<[^>]+>)?", types),
+            None => String::new(),
+        };
         Self {
             // WGSL keywords
-            keyword: Regex::new(
-                r"\b(fn|var|let|const|struct|type|if|else|for|while|loop|break|continue|return|switch|case|default|discard|@compute|@fragment|@vertex|@group|@binding|@location|@builtin|@workgroup_size|@stage|@size|@align|@interpolate)\b"
-            ).unwrap(),
+            keyword: Regex::new(&format!(
+                r"\b(fn|var|let|const|struct|type|if|else|for|while|loop|break|continue|return|switch|case|default|discard|@compute|@fragment|@vertex|@group|@binding|@location|@builtin|@workgroup_size|@stage|@size|@align|@interpolate{})\b",
+                extra_keywords
+            )).unwrap(),
             // Type specifiers: vec2<f32>, mat4x4<f32>, texture_2d<f32>, etc.
-            type_spec: Regex::new(
-                r"\b(vec[234]|mat[234]x[234]|array|texture_[123]d|texture_cube|texture_2d_array|texture_storage_[123]d|sampler|sampler_comparison|atomic|ptr)<[^>]+>"
-            ).unwrap(),
+            type_spec: Regex::new(&format!(
+                r"\b(vec[234]|mat[234]x[234]|array|texture_[123]d|texture_cube|texture_2d_array|texture_storage_[123]d|sampler|sampler_comparison|atomic|ptr)<[^>]+>{}",
+                extra_types
+            )).unwrap(),
             // Built-in types
-            attribute: Regex::new(
-                r"@(compute|fragment|vertex|group|binding|location|builtin|workgroup_size|stage|size|align|interpolate)"
-            ).unwrap(),
+            attribute: Regex::new(&format!(
+                r"@(compute|fragment|vertex|group|binding|location|builtin|workgroup_size|stage|size|align|interpolate{})",
+                extra(&overrides.attributes)
+            )).unwrap(),
             // Identifiers
             identifier: Regex::new(r"[a-zA-Z_][a-zA-Z0-9_]*").unwrap(),
-            // Numbers (int, float, hex)
-            number: Regex::new(r"0x[0-9a-fA-F]+|[0-9]+\.?[0-9]*([eE][+-]?[0-9]+)?[fu]?").unwrap(),
-            // Operators
-            operator: Regex::new(r"[+\-*/%&|^<>=!~]+|<<|>>|&&|\|\||==|!=|<=|>=|->").unwrap(),
+            // Numbers (int, float, hex), with their type suffix
+            number: Regex::new(
+                r"0[xX][0-9a-fA-F]+[iu]?|([0-9]+\.?[0-9]*|\.[0-9]+)([eE][+-]?[0-9]+)?[fhiu]?",
+            )
+            .unwrap(),
+            // Operators, as an alternation of the table
+            operator: Regex::new(
+                &WGSL_OPERATORS
+                    .iter()
+                    .map(|op| regex::escape(op))
+                    .collect::<Vec<_>>()
+                    .join("|"),
+            )
+            .unwrap(),
             // Punctuation
             punctuation: Regex::new(r"[(){}\[\];:,.]").unwrap(),
+            disabled: overrides.disabled.clone(),
         }
     }
+
+    /// Every enabled pattern with the category it matches, in priority
+    /// order; type specifiers come first as the most specific to WGSL
+    fn all(&self) -> impl Iterator<Item = (&Regex, TokenCategory)> {
+        [
+            (&self.type_spec, TokenCategory::Type),
+            (&self.attribute, TokenCategory::Attribute),
+            (&self.keyword, TokenCategory::Keyword),
+            (&self.number, TokenCategory::Number),
+            (&self.operator, TokenCategory::Operator),
+            (&self.punctuation, TokenCategory::Punctuation),
+            (&self.identifier, TokenCategory::Identifier),
+        ]
+        .into_iter()
+        .filter(|(_, category)| !self.disabled.contains(category))
+    }
+}
+
+impl Default for WGSLPatterns {
+    fn default() -> Self {
+        Self::new(&PatternOverrides::default())
+    }
 }
 
 impl WGSLTokenizer {
@@ -109,6 +310,9 @@ impl WGSLTokenizer {
             next_id: 4, // Reserve 0-3 for special tokens
             max_length,
             lowercase,
+            control_tokens: Vec::new(),
+            normalizer: None,
+            pattern_overrides: PatternOverrides::default(),
             patterns: WGSLPatterns::default(),
         };
 
@@ -128,6 +332,97 @@ impl WGSLTokenizer {
         tokenizer
     }
 
+    /// Tokenizer for `[tokenizer]` settings, with its prompt normalizer,
+    /// pattern overrides and control tokens registered ahead of any fitted
+    /// vocabulary
+    pub fn from_config(config: &crate::config::TokenizerConfig) -> crate::Result<Self> {
+        let mut tokenizer = Self::new(config.max_length, config.lowercase);
+        tokenizer.normalizer = config.normalize.clone();
+        tokenizer.set_pattern_overrides(config.patterns.clone());
+        for token in &config.control_tokens {
+            tokenizer.add_control_token(token)?;
+        }
+        Ok(tokenizer)
+    }
+
+    /// Register a task control token such as `<compute>` or `<sep>`,
+    /// returning its id
+    ///
+    /// Control tokens always tokenize as one unit, keep their id across
+    /// [`fit`](Self::fit) calls and are saved with the tokenizer. Registering
+    /// a token twice returns the id it already has.
+    pub fn add_control_token(&mut self, token: &str) -> crate::Result<usize> {
+        let token = if self.lowercase {
+            token.to_lowercase()
+        } else {
+            token.to_string()
+        };
+        if let Some(problem) = control_token_problem(&token) {
+            return Err(crate::Error::Other(format!(
+                "Invalid control token '{}': {}",
+                token, problem
+            )));
+        }
+        if let Some(&id) = self.vocab.get(&token) {
+            if self.control_tokens.contains(&token) {
+                return Ok(id);
+            }
+            return Err(crate::Error::Other(format!(
+                "Invalid control token '{}': already in the vocabulary",
+                token
+            )));
+        }
+        let id = self.next_id;
+        self.vocab.insert(token.clone(), id);
+        self.reverse_vocab.insert(id, token.clone());
+        self.next_id += 1;
+        self.control_tokens.push(token);
+        Ok(id)
+    }
+
+    /// Match text with the built-in patterns changed by `overrides`
+    ///
+    /// Set this before fitting: a vocabulary fitted under other patterns
+    /// no longer covers the tokens they split text into.
+    pub fn set_pattern_overrides(&mut self, overrides: PatternOverrides) {
+        self.patterns = WGSLPatterns::new(&overrides);
+        self.pattern_overrides = overrides;
+    }
+
+    /// Changes to the built-in patterns this tokenizer matches with
+    pub fn pattern_overrides(&self) -> &PatternOverrides {
+        &self.pattern_overrides
+    }
+
+    /// `prompt` normalized as the training prompts were; unchanged
+    /// without a normalizer
+    ///
+    /// A leading control token, such as a stage token, is kept as is.
+    pub fn normalize_prompt(&self, prompt: &str) -> String {
+        let normalizer = match &self.normalizer {
+            Some(normalizer) => normalizer,
+            None => return prompt.to_string(),
+        };
+        match prompt.split_once(' ') {
+            Some((token, rest)) if self.control_tokens.iter().any(|t| t == token) => {
+                format!("{} {}", token, normalizer.normalize(rest))
+            }
+            _ => normalizer.normalize(prompt),
+        }
+    }
+
+    /// Registered control tokens, in registration order
+    pub fn control_tokens(&self) -> &[String] {
+        &self.control_tokens
+    }
+
+    /// Whether `id` is a registered control token
+    pub fn is_control_id(&self, id: usize) -> bool {
+        self.reverse_vocab
+            .get(&id)
+            .is_some_and(|token| self.control_tokens.contains(token))
+    }
+
     /// Tokenize WGSL code into tokens
     pub fn tokenize(&self, text: &str) -> Vec<String> {
         let text = if self.lowercase {
@@ -136,10 +431,46 @@ impl WGSLTokenizer {
             text.to_string()
         };
 
-        let mut tokens = Vec::new();
+        self.token_spans(&text)
+            .into_iter()
+            .map(|(start, end, _)| text[start..end].to_string())
+            .collect()
+    }
+
+    /// Tokenize like [`tokenize`](Self::tokenize), with the category each
+    /// token matched
+    pub fn tokenize_categorized(&self, text: &str) -> Vec<(String, TokenCategory)> {
+        let text = if self.lowercase {
+            text.to_lowercase()
+        } else {
+            text.to_string()
+        };
+
+        self.token_spans(&text)
+            .into_iter()
+            .map(|(start, end, category)| (text[start..end].to_string(), category))
+            .collect()
+    }
+
+    /// Cut text after its first `max_tokens` tokens, keeping the original formatting
+    pub fn truncate_text(&self, text: &str, max_tokens: usize) -> String {
+        let spans = self.token_spans(text);
+        if spans.len() <= max_tokens {
+            return text.to_string();
+        }
+
+        match max_tokens.checked_sub(1) {
+            Some(last) => text[..spans[last].1].to_string(),
+            None => String::new(),
+        }
+    }
+
+    /// Byte ranges of every token in `text`, with the category it matched
+    fn token_spans(&self, text: &str) -> Vec<(usize, usize, TokenCategory)> {
+        let mut spans = Vec::new();
         let mut pos = 0;
 
-        while pos < text.len() {
+        'scan: while pos < text.len() {
             let remaining = &text[pos..];
 
             // Skip whitespace
@@ -150,92 +481,50 @@ impl WGSLTokenizer {
                 }
             }
 
-            // Try matching patterns in order of priority
-            let mut matched = false;
-
-            // 1. Type specifiers (highest priority for WGSL)
-            if let Some(mat) = self.patterns.type_spec.find(remaining) {
-                if mat.start() == 0 {
-                    tokens.push(mat.as_str().to_string());
-                    pos += mat.end();
-                    matched = true;
-                    continue;
-                }
-            }
-
-            // 2. Attributes
-            if let Some(mat) = self.patterns.attribute.find(remaining) {
-                if mat.start() == 0 {
-                    tokens.push(mat.as_str().to_string());
-                    pos += mat.end();
-                    matched = true;
-                    continue;
-                }
-            }
-
-            // 3. Keywords
-            if let Some(mat) = self.patterns.keyword.find(remaining) {
-                if mat.start() == 0 {
-                    tokens.push(mat.as_str().to_string());
-                    pos += mat.end();
-                    matched = true;
-                    continue;
-                }
+            // Skip comments, which would not survive joining tokens on one line
+            if remaining.starts_with("//") {
+                pos += remaining.find('\n').unwrap_or(remaining.len());
+                continue;
             }
-
-            // 4. Numbers
-            if let Some(mat) = self.patterns.number.find(remaining) {
-                if mat.start() == 0 {
-                    tokens.push(mat.as_str().to_string());
-                    pos += mat.end();
-                    matched = true;
-                    continue;
-                }
+            if let Some(comment) = remaining.strip_prefix("/*") {
+                pos += comment.find("*/").map_or(remaining.len(), |end| end + 4);
+                continue;
             }
 
-            // 5. Operators
-            if let Some(mat) = self.patterns.operator.find(remaining) {
-                if mat.start() == 0 {
-                    tokens.push(mat.as_str().to_string());
-                    pos += mat.end();
-                    matched = true;
-                    continue;
-                }
-            }
-
-            // 6. Punctuation
-            if let Some(mat) = self.patterns.punctuation.find(remaining) {
-                if mat.start() == 0 {
-                    tokens.push(mat.as_str().to_string());
-                    pos += mat.end();
-                    matched = true;
-                    continue;
-                }
+            // Control tokens are whole units, ahead of every pattern
+            if let Some(token) = self
+                .control_tokens
+                .iter()
+                .find(|token| remaining.starts_with(token.as_str()))
+            {
+                spans.push((pos, pos + token.len(), TokenCategory::Control));
+                pos += token.len();
+                continue;
             }
 
-            // 7. Identifiers
-            if let Some(mat) = self.patterns.identifier.find(remaining) {
-                if mat.start() == 0 {
-                    tokens.push(mat.as_str().to_string());
-                    pos += mat.end();
-                    matched = true;
-                    continue;
+            // Try matching patterns in order of priority
+            for (pattern, category) in self.patterns.all() {
+                if let Some(mat) = pattern.find(remaining) {
+                    if mat.start() == 0 {
+                        spans.push((pos, pos + mat.end(), category));
+                        pos += mat.end();
+                        continue 'scan;
+                    }
                 }
             }
 
             // If no pattern matched, skip this character
-            if !matched {
-                if let Some(ch) = remaining.chars().next() {
-                    pos += ch.len_utf8();
-                }
+            if let Some(ch) = remaining.chars().next() {
+                pos += ch.len_utf8();
             }
         }
 
-        tokens
+        spans
     }
 
     /// Build vocabulary from training texts
     pub fn fit<S: AsRef<str>>(&mut self, texts: &[S], min_freq: usize) {
+        let _span = tracing::debug_span!("fit_tokenizer", texts = texts.len()).entered();
         // Count token frequencies
         let mut freq_map: HashMap<String, usize> = HashMap::new();
 
@@ -246,8 +535,11 @@ impl WGSLTokenizer {
             }
         }
 
-        // Add tokens that meet minimum frequency
-        for (token, freq) in freq_map {
+        // Add tokens that meet minimum frequency, most frequent first, so
+        // the same texts always give the same ids
+        let mut counts: Vec<(String, usize)> = freq_map.into_iter().collect();
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        for (token, freq) in counts {
             if freq >= min_freq && !self.vocab.contains_key(&token) {
                 let id = self.next_id;
                 self.vocab.insert(token.clone(), id);
@@ -276,6 +568,54 @@ impl WGSLTokenizer {
         self.encode(&tokens)
     }
 
+    /// Encode `text` to exactly `max_len` ids, truncating or filling with
+    /// `<pad>`, alongside an attention mask that is `true` at real tokens
+    pub fn encode_padded(&self, text: &str, max_len: usize) -> (Vec<usize>, Vec<bool>) {
+        pad_ids(self.encode_text(text), max_len)
+    }
+
+    /// Encode every text, padded to the longest encoding but at most
+    /// `max_len` ids, so the batch stacks into one rectangular array
+    pub fn encode_padded_batch<S: AsRef<str>>(
+        &self,
+        texts: &[S],
+        max_len: usize,
+    ) -> Vec<(Vec<usize>, Vec<bool>)> {
+        let encoded: Vec<Vec<usize>> = texts
+            .iter()
+            .map(|text| self.encode_text(text.as_ref()))
+            .collect();
+        let len = encoded.iter().map(Vec::len).max().unwrap_or(0).min(max_len);
+        encoded.into_iter().map(|ids| pad_ids(ids, len)).collect()
+    }
+
+    /// Tokenize and encode `text`, noting each token's category and the
+    /// tokens outside the vocabulary
+    pub fn tokenize_detailed(&self, text: &str) -> TokenizedText {
+        let (tokens, categories): (Vec<String>, Vec<TokenCategory>) =
+            self.tokenize_categorized(text).into_iter().unzip();
+        let ids = self.encode(&tokens);
+        let mut unknown: Vec<String> = Vec::new();
+        for token in &tokens {
+            if !self.vocab.contains_key(token) && !unknown.contains(token) {
+                unknown.push(token.clone());
+            }
+        }
+        TokenizedText {
+            tokens,
+            categories,
+            ids,
+            unknown,
+        }
+    }
+
+    /// Encode `text`, failing if any token is outside the vocabulary
+    pub fn encode_strict(&self, text: &str) -> crate::Result<Vec<usize>> {
+        let tokenized = self.tokenize_detailed(text);
+        tokenized.require_known()?;
+        Ok(tokenized.ids)
+    }
+
     /// Decode IDs back to tokens
     pub fn decode(&self, ids: &[usize]) -> Vec<String> {
         ids.iter()
@@ -294,25 +634,145 @@ impl WGSLTokenizer {
         self.vocab.len()
     }
 
-    /// Save tokenizer to JSON
+    /// SHA-256 of everything encoding depends on: the vocabulary, case
+    /// folding, prompt normalization, the patterns that split the text and
+    /// the crate version
+    pub fn content_hash(&self) -> String {
+        let mut tokens: Vec<(&usize, &String)> = self.reverse_vocab.iter().collect();
+        tokens.sort();
+        let mut hasher = Sha256::new();
+        hasher.update(crate::VERSION.as_bytes());
+        for (pattern, _) in self.patterns.all() {
+            hasher.update(pattern.as_str().as_bytes());
+            hasher.update([0]);
+        }
+        hasher.update([self.lowercase as u8]);
+        if let Some(normalizer) = &self.normalizer {
+            hasher.update(serde_json::to_vec(normalizer).unwrap_or_default());
+        }
+        for (id, token) in tokens {
+            hasher.update(id.to_le_bytes());
+            hasher.update(token.as_bytes());
+            hasher.update([0]);
+        }
+        format!("{:x}", hasher.finalize())
+    }
+
+    /// SHA-256 of the token-to-id mapping and case folding, which a model's
+    /// embeddings and output head are tied to
+    ///
+    /// Unlike [`content_hash`](Self::content_hash) it doesn't change between
+    /// crate versions, so checkpoints record it to catch a model paired with
+    /// the wrong tokenizer.
+    pub fn vocab_hash(&self) -> String {
+        let mut tokens: Vec<(&usize, &String)> = self.reverse_vocab.iter().collect();
+        tokens.sort();
+        let mut hasher = Sha256::new();
+        hasher.update([self.lowercase as u8]);
+        for (id, token) in tokens {
+            hasher.update(id.to_le_bytes());
+            hasher.update(token.as_bytes());
+            hasher.update([0]);
+        }
+        format!("{:x}", hasher.finalize())
+    }
+
+    /// Save tokenizer to JSON, with its schema version and vocabulary hash
     pub fn save<P: AsRef<Path>>(&self, path: P) -> crate::Result<()> {
-        let json = serde_json::to_string_pretty(self)?;
-        std::fs::write(path, json)?;
+        std::fs::write(path, self.to_json()?)?;
         Ok(())
     }
 
+    /// JSON written by [`WGSLTokenizer::save`]
+    pub fn to_json(&self) -> crate::Result<String> {
+        let mut value = serde_json::to_value(self)?;
+        if let serde_json::Value::Object(fields) = &mut value {
+            fields.insert("schema_version".into(), TOKENIZER_SCHEMA_VERSION.into());
+            fields.insert("vocab_hash".into(), self.vocab_hash().into());
+        }
+        Ok(serde_json::to_string_pretty(&value)?)
+    }
+
     /// Load tokenizer from JSON
     pub fn load<P: AsRef<Path>>(path: P) -> crate::Result<Self> {
-        let json = std::fs::read_to_string(path)?;
-        let mut tokenizer: WGSLTokenizer = serde_json::from_str(&json)?;
-        tokenizer.patterns = WGSLPatterns::default();
+        Self::from_json(&std::fs::read_to_string(path)?)
+    }
+
+    /// Parse a tokenizer written by [`WGSLTokenizer::save`]
+    ///
+    /// Fails on files from a newer schema version; warns when the vocabulary
+    /// no longer matches the hash it was saved with, as after hand edits.
+    pub fn from_json(json: &str) -> crate::Result<Self> {
+        let mut value: serde_json::Value = serde_json::from_str(json)?;
+        let (version, saved_hash) = match &mut value {
+            serde_json::Value::Object(fields) => (
+                fields.remove("schema_version").and_then(|v| v.as_u64()),
+                fields.remove("vocab_hash"),
+            ),
+            _ => (None, None),
+        };
+        let version = version.unwrap_or(1);
+        if version > TOKENIZER_SCHEMA_VERSION as u64 {
+            return Err(crate::Error::Other(format!(
+                "Tokenizer schema version {} is newer than this build supports ({})",
+                version, TOKENIZER_SCHEMA_VERSION
+            )));
+        }
+        let mut tokenizer: WGSLTokenizer = serde_json::from_value(value)?;
+        tokenizer.patterns = WGSLPatterns::new(&tokenizer.pattern_overrides);
+        if let Some(saved_hash) = saved_hash.as_ref().and_then(|h| h.as_str()) {
+            if saved_hash != tokenizer.vocab_hash() {
+                tracing::warn!(
+                    "Tokenizer vocabulary differs from the one it was saved with; \
+                     models trained on the original will produce garbage"
+                );
+            }
+        }
         Ok(tokenizer)
     }
 }
 
+/// Why `token` can't be a control token, if it can't: it must be an
+/// angle-bracketed name like `<compute>` and not a built-in special token
+pub fn control_token_problem(token: &str) -> Option<String> {
+    let name = token
+        .strip_prefix('<')
+        .and_then(|rest| rest.strip_suffix('>'));
+    match name {
+        Some(name)
+            if !name.is_empty()
+                && name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') =>
+        {
+            let special = [
+                SpecialToken::Padding,
+                SpecialToken::Unknown,
+                SpecialToken::StartOfSequence,
+                SpecialToken::EndOfSequence,
+            ];
+            special
+                .iter()
+                .any(|special| special.as_str() == token)
+                .then(|| "reserved for a built-in special token".to_string())
+        }
+        _ => Some("expected a name in angle brackets, such as <compute>".to_string()),
+    }
+}
+
+/// Truncate or `<pad>`-fill `ids` to `len`, with the mask of real tokens
+fn pad_ids(mut ids: Vec<usize>, len: usize) -> (Vec<usize>, Vec<bool>) {
+    ids.truncate(len);
+    let mut mask = vec![true; ids.len()];
+    mask.resize(len, false);
+    ids.resize(len, SpecialToken::Padding.token_id());
+    (ids, mask)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::Config;
 
     #[test]
     fn test_special_tokens() {
@@ -350,6 +810,112 @@ mod tests {
         assert!(tokens.contains(&"@workgroup_size".to_string()));
     }
 
+    #[test]
+    fn test_token_categories() {
+        let mut tokenizer = WGSLTokenizer::new(512, false);
+        tokenizer.add_control_token("<compute>").unwrap();
+        let tokens = tokenizer.tokenize_categorized(
+            "<compute> @compute fn f(x: vec2<f32>) -> f32 { return x.y * 2.0; }",
+        );
+        let category = |token: &str| {
+            tokens
+                .iter()
+                .find(|(t, _)| t == token)
+                .map(|&(_, category)| category)
+        };
+        assert_eq!(category("<compute>"), Some(TokenCategory::Control));
+        assert_eq!(category("@compute"), Some(TokenCategory::Attribute));
+        assert_eq!(category("fn"), Some(TokenCategory::Keyword));
+        assert_eq!(category("vec2<f32>"), Some(TokenCategory::Type));
+        assert_eq!(category("->"), Some(TokenCategory::Operator));
+        assert_eq!(category("2.0"), Some(TokenCategory::Number));
+        assert_eq!(category(";"), Some(TokenCategory::Punctuation));
+        assert_eq!(category("f32"), Some(TokenCategory::Identifier));
+
+        let detailed = tokenizer.tokenize_detailed("fn f");
+        assert_eq!(
+            detailed.categories,
+            vec![TokenCategory::Keyword, TokenCategory::Identifier]
+        );
+    }
+
+    #[test]
+    fn test_operator_tokenization() {
+        let tokenizer = WGSLTokenizer::new(512, false);
+        for (code, expected) in [
+            ("a=-b", vec!["a", "=", "-", "b"]),
+            ("x<<=2u", vec!["x", "<<=", "2u"]),
+            ("y>>=z", vec!["y", ">>=", "z"]),
+            ("fn f()->f32", vec!["fn", "f", "(", ")", "->", "f32"]),
+            ("a<-b", vec!["a", "<", "-", "b"]),
+            ("i++;", vec!["i", "++", ";"]),
+            ("a+ +b", vec!["a", "+", "+", "b"]),
+            ("a!=!b", vec!["a", "!=", "!", "b"]),
+            ("p&&!q||r", vec!["p", "&&", "!", "q", "||", "r"]),
+            ("a*=-~b", vec!["a", "*=", "-", "~", "b"]),
+            ("x=a<=b==c>=d", vec!["x", "=", "a", "<=", "b", "==", "c", ">=", "d"]),
+            ("v=*&p", vec!["v", "=", "*", "&", "p"]),
+            ("m%=n^k", vec!["m", "%=", "n", "^", "k"]),
+        ] {
+            assert_eq!(tokenizer.tokenize(code), expected, "{}", code);
+        }
+
+        // The table lists longer operators before their prefixes
+        for (i, op) in WGSL_OPERATORS.iter().enumerate() {
+            assert!(
+                WGSL_OPERATORS[i + 1..]
+                    .iter()
+                    .all(|later| !later.starts_with(op) || later == op),
+                "{}",
+                op
+            );
+        }
+    }
+
+    #[test]
+    fn test_pattern_overrides() {
+        let mut tokenizer = WGSLTokenizer::new(512, false);
+        let code = "enable f16; @must_use fn f(t: binding_array<texture_2d<f32>>, d: texture_depth_2d)";
+        assert!(!tokenizer.tokenize(code).contains(&"@must_use".to_string()));
+
+        tokenizer.set_pattern_overrides(PatternOverrides {
+            keywords: vec!["enable".to_string()],
+            types: vec!["binding_array".to_string(), "texture_depth_2d".to_string()],
+            attributes: vec!["must_use".to_string()],
+            disabled: Vec::new(),
+        });
+        let tokens = tokenizer.tokenize_categorized(code);
+        assert_eq!(tokens[0], ("enable".to_string(), TokenCategory::Keyword));
+        assert!(tokens.contains(&("@must_use".to_string(), TokenCategory::Attribute)));
+        assert!(tokens.contains(&(
+            "binding_array<texture_2d<f32>".to_string(),
+            TokenCategory::Type
+        )));
+        assert!(tokens.contains(&("texture_depth_2d".to_string(), TokenCategory::Type)));
+
+        // Overrides are saved, and change the encoding cache key
+        let loaded = WGSLTokenizer::from_json(&tokenizer.to_json().unwrap()).unwrap();
+        assert_eq!(loaded.tokenize(code), tokenizer.tokenize(code));
+        assert_ne!(
+            tokenizer.content_hash(),
+            WGSLTokenizer::new(512, false).content_hash()
+        );
+
+        // Disabled types fall through to identifiers, operators and so on
+        tokenizer.set_pattern_overrides(PatternOverrides {
+            disabled: vec![TokenCategory::Type],
+            ..PatternOverrides::default()
+        });
+        assert_eq!(tokenizer.tokenize("vec2<f32>"), vec!["vec2", "<", "f32", ">"]);
+
+        let invalid = PatternOverrides {
+            keywords: vec!["two words".to_string()],
+            disabled: vec![TokenCategory::Control],
+            ..PatternOverrides::default()
+        };
+        assert_eq!(invalid.validation_errors("tokenizer.patterns").len(), 2);
+    }
+
     #[test]
     fn test_fit_and_encode() {
         let mut tokenizer = WGSLTokenizer::new(512, false);
@@ -360,12 +926,60 @@ mod tests {
         let tokens = tokenizer.tokenize("fn test");
         let ids = tokenizer.encode(&tokens);
 
-        assert!(ids.len() > 0);
+        assert!(!ids.is_empty());
         assert_ne!(ids[0], SpecialToken::Unknown.token_id());
 
         // Decode back
         let decoded = tokenizer.decode(&ids);
         assert_eq!(decoded, tokens);
+
+        // Ids depend only on the texts
+        let mut again = WGSLTokenizer::new(512, false);
+        again.fit(&texts, 1);
+        assert_eq!(again.vocab, tokenizer.vocab);
+    }
+
+    #[test]
+    fn test_json_roundtrip_and_versioning() {
+        let mut tokenizer = WGSLTokenizer::new(512, false);
+        tokenizer.fit(&["fn test() { }"], 1);
+
+        let json = tokenizer.to_json().unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["schema_version"], TOKENIZER_SCHEMA_VERSION);
+        assert_eq!(value["vocab_hash"], tokenizer.vocab_hash().as_str());
+        let loaded = WGSLTokenizer::from_json(&json).unwrap();
+        assert_eq!(loaded.vocab, tokenizer.vocab);
+        assert_eq!(loaded.vocab_hash(), tokenizer.vocab_hash());
+
+        // Unversioned files from before schema versions still load
+        let legacy = serde_json::to_string(&tokenizer).unwrap();
+        assert_eq!(
+            WGSLTokenizer::from_json(&legacy).unwrap().vocab,
+            tokenizer.vocab
+        );
+
+        let mut future = value.clone();
+        future["schema_version"] = (TOKENIZER_SCHEMA_VERSION + 1).into();
+        assert!(WGSLTokenizer::from_json(&future.to_string()).is_err());
+
+        let mut other = WGSLTokenizer::new(512, false);
+        other.fit(&["var x: f32 = 1.0;"], 1);
+        assert_ne!(other.vocab_hash(), tokenizer.vocab_hash());
+        assert_ne!(
+            WGSLTokenizer::new(512, true).vocab_hash(),
+            WGSLTokenizer::new(512, false).vocab_hash()
+        );
+    }
+
+    #[test]
+    fn test_truncate_text() {
+        let tokenizer = WGSLTokenizer::new(512, false);
+        let code = "let x = 1.0;\nlet y = 2.0;";
+
+        assert_eq!(tokenizer.truncate_text(code, 5), "let x = 1.0;");
+        assert_eq!(tokenizer.truncate_text(code, 100), code);
+        assert_eq!(tokenizer.truncate_text(code, 0), "");
     }
 
     #[test]
@@ -382,4 +996,117 @@ mod tests {
         assert!(decoded.contains("fn"));
         assert!(decoded.contains("main"));
     }
+
+    #[test]
+    fn test_encode_padded() {
+        let mut tokenizer = WGSLTokenizer::new(64, false);
+        tokenizer.fit(&["let x = 1.0;"], 1);
+        let pad = SpecialToken::Padding.token_id();
+
+        let (ids, mask) = tokenizer.encode_padded("let x", 4);
+        assert_eq!(&ids[..2], &tokenizer.encode_text("let x")[..]);
+        assert_eq!(&ids[2..], &[pad, pad]);
+        assert_eq!(mask, vec![true, true, false, false]);
+
+        let (ids, mask) = tokenizer.encode_padded("let x = 1.0;", 3);
+        assert_eq!(ids, tokenizer.encode_text("let x =")[..3].to_vec());
+        assert_eq!(mask, vec![true; 3]);
+
+        let batch = tokenizer.encode_padded_batch(&["let x", "let x = 1.0;", ""], 4);
+        assert!(batch
+            .iter()
+            .all(|(ids, mask)| ids.len() == 4 && mask.len() == 4));
+        assert_eq!(batch[0].1, vec![true, true, false, false]);
+        assert_eq!(batch[1].1, vec![true; 4]);
+        assert_eq!(batch[2], (vec![pad; 4], vec![false; 4]));
+
+        // Short batches pad only to their longest text
+        let batch = tokenizer.encode_padded_batch(&["let", "let x"], 16);
+        assert_eq!(
+            batch[0],
+            (vec![tokenizer.vocab["let"], pad], vec![true, false])
+        );
+        assert!(tokenizer.encode_padded_batch::<&str>(&[], 8).is_empty());
+    }
+
+    #[test]
+    fn test_control_tokens() {
+        let mut tokenizer = WGSLTokenizer::new(64, false);
+        let compute = tokenizer.add_control_token("<compute>").unwrap();
+        let sep = tokenizer.add_control_token("<sep>").unwrap();
+        assert_eq!((compute, sep), (4, 5));
+        assert_eq!(tokenizer.add_control_token("<compute>").unwrap(), compute);
+        assert!(tokenizer.add_control_token("<pad>").is_err());
+        assert!(tokenizer.add_control_token("compute").is_err());
+        assert!(tokenizer.add_control_token("<a b>").is_err());
+
+        assert_eq!(
+            tokenizer.tokenize("<compute>blur<sep> x < y"),
+            vec!["<compute>", "blur", "<sep>", "x", "<", "y"]
+        );
+
+        // Fitting keeps the ids; saving and loading keeps the tokens
+        tokenizer.fit(&["<compute> fn main() {}"], 1);
+        assert_eq!(tokenizer.vocab["<compute>"], compute);
+        assert!(tokenizer.is_control_id(sep));
+        assert!(!tokenizer.is_control_id(tokenizer.vocab["fn"]));
+        let loaded = WGSLTokenizer::from_json(&tokenizer.to_json().unwrap()).unwrap();
+        assert_eq!(loaded.control_tokens(), ["<compute>", "<sep>"]);
+        assert_eq!(loaded.tokenize("<sep>"), vec!["<sep>"]);
+
+        let config = crate::config::TokenizerConfig {
+            control_tokens: vec!["<vertex>".to_string()],
+            ..Config::default_wgsl_generation().tokenizer
+        };
+        let tokenizer = WGSLTokenizer::from_config(&config).unwrap();
+        assert_eq!(tokenizer.vocab["<vertex>"], 4);
+    }
+
+    #[test]
+    fn test_prompt_normalizer_is_saved() {
+        let config = crate::config::TokenizerConfig {
+            normalize: Some(PromptNormalizer::default()),
+            ..Config::default_wgsl_generation().tokenizer
+        };
+        let mut tokenizer = WGSLTokenizer::from_config(&config).unwrap();
+        tokenizer.add_control_token("<Compute>").unwrap();
+        let loaded = WGSLTokenizer::from_json(&tokenizer.to_json().unwrap()).unwrap();
+        assert_eq!(loaded.normalize_prompt("Two Colours"), "2 colors");
+        // A leading control token is left alone
+        assert_eq!(
+            loaded.normalize_prompt("<Compute> Two Colours"),
+            "<Compute> 2 colors"
+        );
+        assert_eq!(
+            WGSLTokenizer::new(16, false).normalize_prompt("Two Colours"),
+            "Two Colours"
+        );
+    }
+
+    #[test]
+    fn test_tokenize_detailed() {
+        let mut tokenizer = WGSLTokenizer::new(64, false);
+        tokenizer.fit(&["let x = 1.0;"], 1);
+
+        let text = tokenizer.tokenize_detailed("let y = x + y;");
+        assert_eq!(text.tokens.len(), text.ids.len());
+        assert_eq!(text.unknown, vec!["y".to_string(), "+".to_string()]);
+        assert_eq!(text.ids[1], SpecialToken::Unknown.token_id());
+        assert!((text.coverage() - 4.0 / 7.0).abs() < 1e-9);
+        assert_eq!(tokenizer.tokenize_detailed("").coverage(), 1.0);
+
+        assert!(tokenizer.encode_strict("let x = 1.0;").is_ok());
+        match tokenizer.encode_strict("let y = x + y;") {
+            Err(crate::Error::TokenizerError { unknown, coverage }) => {
+                assert_eq!(unknown, text.unknown);
+                assert_eq!(coverage, text.coverage());
+            }
+            other => panic!("expected a tokenizer error, got {:?}", other),
+        }
+
+        let hash = tokenizer.content_hash();
+        assert_eq!(hash, tokenizer.clone().content_hash());
+        tokenizer.fit(&["let y = x + y;"], 1);
+        assert_ne!(hash, tokenizer.content_hash());
+    }
 }
@@ -0,0 +1,201 @@
+//! Natural language prompt normalization
+//!
+//! A [`PromptNormalizer`] rewrites prompts into one canonical form: case,
+//! typographic punctuation, spelled-out numbers and spelling variants. It is
+//! saved with the tokenizer, so training data and inference prompts always go
+//! through the same rules. Normalizing is idempotent.
+
+use crate::dataset::{WGSLDataset, WGSLExample};
+use regex::{Captures, Regex};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+
+/// British spellings common in shader prompts, with their American forms
+const BRITISH_SPELLINGS: [(&str, &str); 10] = [
+    ("colour", "color"),
+    ("colours", "colors"),
+    ("grey", "gray"),
+    ("greyscale", "grayscale"),
+    ("centre", "center"),
+    ("centred", "centered"),
+    ("normalise", "normalize"),
+    ("normalised", "normalized"),
+    ("optimise", "optimize"),
+    ("behaviour", "behavior"),
+];
+
+const UNITS: [&str; 20] = [
+    "zero",
+    "one",
+    "two",
+    "three",
+    "four",
+    "five",
+    "six",
+    "seven",
+    "eight",
+    "nine",
+    "ten",
+    "eleven",
+    "twelve",
+    "thirteen",
+    "fourteen",
+    "fifteen",
+    "sixteen",
+    "seventeen",
+    "eighteen",
+    "nineteen",
+];
+
+const TENS: [&str; 8] = [
+    "twenty", "thirty", "forty", "fifty", "sixty", "seventy", "eighty", "ninety",
+];
+
+/// Rules prompts are rewritten with, under `[tokenizer.normalize]`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PromptNormalizer {
+    /// Lowercase the prompt
+    pub lowercase: bool,
+    /// Replace curly quotes, dashes and ellipses with ASCII, collapse
+    /// repeated `!`/`?` and runs of whitespace
+    pub punctuation: bool,
+    /// Spell numbers up to ninety-nine as digits: "four" becomes "4"
+    pub numbers: bool,
+    /// Rewrite British spellings such as "colour" to American ones
+    pub american_spelling: bool,
+    /// Further whole-word replacements, matched ignoring case
+    pub synonyms: BTreeMap<String, String>,
+}
+
+impl Default for PromptNormalizer {
+    fn default() -> Self {
+        Self {
+            lowercase: true,
+            punctuation: true,
+            numbers: true,
+            american_spelling: true,
+            synonyms: BTreeMap::new(),
+        }
+    }
+}
+
+impl PromptNormalizer {
+    /// `prompt` rewritten with every enabled rule
+    pub fn normalize(&self, prompt: &str) -> String {
+        let mut text = if self.punctuation {
+            normalize_punctuation(prompt)
+        } else {
+            prompt.to_string()
+        };
+        if self.lowercase {
+            text = text.to_lowercase();
+        }
+        static WORD: OnceLock<Regex> = OnceLock::new();
+        let word = WORD.get_or_init(|| Regex::new(r"\b[A-Za-z]+(?:-[A-Za-z]+)?\b").unwrap());
+        word.replace_all(&text, |caps: &Captures| {
+            let word = &caps[0];
+            self.replace_word(word)
+                .unwrap_or_else(|| word.to_string())
+        })
+        .into_owned()
+    }
+
+    /// `dataset` with every description normalized
+    pub fn normalize_dataset(&self, dataset: &WGSLDataset) -> WGSLDataset {
+        let examples = dataset
+            .examples
+            .iter()
+            .map(|example| WGSLExample {
+                natural_language: self.normalize(&example.natural_language),
+                ..example.clone()
+            })
+            .collect();
+        WGSLDataset { examples }
+    }
+
+    fn replace_word(&self, word: &str) -> Option<String> {
+        let lower = word.to_lowercase();
+        if let Some((_, to)) = self
+            .synonyms
+            .iter()
+            .find(|(from, _)| from.to_lowercase() == lower)
+        {
+            return Some(to.clone());
+        }
+        if self.american_spelling {
+            if let Some(&(_, to)) = BRITISH_SPELLINGS.iter().find(|(from, _)| *from == lower) {
+                return Some(to.to_string());
+            }
+        }
+        if self.numbers {
+            return number_word(&lower).map(|n| n.to_string());
+        }
+        None
+    }
+}
+
+/// Value of a spelled-out number below one hundred, e.g. "sixty-four"
+fn number_word(word: &str) -> Option<usize> {
+    let unit = |w: &str| UNITS.iter().position(|&u| u == w);
+    let tens = |w: &str| TENS.iter().position(|&t| t == w).map(|i| (i + 2) * 10);
+    match word.split_once('-') {
+        Some((high, low)) => Some(tens(high)? + unit(low).filter(|&u| (1..10).contains(&u))?),
+        None => unit(word).or_else(|| tens(word)),
+    }
+}
+
+/// ASCII quotes, dashes and ellipses, single `!`/`?` and single spaces
+fn normalize_punctuation(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '\u{2018}' | '\u{2019}' | '\u{201B}' => out.push('\''),
+            '\u{201C}' | '\u{201D}' | '\u{201F}' => out.push('"'),
+            '\u{2013}' | '\u{2014}' | '\u{2212}' => out.push('-'),
+            '\u{2026}' => out.push_str("..."),
+            '!' | '?' if out.ends_with(ch) => {}
+            c if c.is_whitespace() => {
+                if !out.is_empty() && !out.ends_with(' ') {
+                    out.push(' ');
+                }
+            }
+            c => out.push(c),
+        }
+    }
+    out.trim_end().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize() {
+        let normalizer = PromptNormalizer::default();
+        assert_eq!(
+            normalizer.normalize("  Mix two  Colours\u{2014}\u{201C}grey\u{201D} centre!!! "),
+            "mix 2 colors-\"gray\" center!"
+        );
+        assert_eq!(
+            normalizer.normalize("Workgroup of Sixty-Four threads\u{2026}"),
+            "workgroup of 64 threads..."
+        );
+        assert_eq!(normalizer.normalize("one-off blur"), "one-off blur");
+
+        let text = "Blend twenty Colours, mix?? them";
+        let once = normalizer.normalize(text);
+        assert_eq!(normalizer.normalize(&once), once);
+
+        let custom = PromptNormalizer {
+            lowercase: false,
+            numbers: false,
+            synonyms: [("tint".to_string(), "color".to_string())]
+                .into_iter()
+                .collect(),
+            ..PromptNormalizer::default()
+        };
+        assert_eq!(custom.normalize("Tint with two Colours"), "color with two colors");
+    }
+}
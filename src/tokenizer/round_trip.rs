@@ -0,0 +1,163 @@
+//! Checking that tokenization keeps WGSL intact
+//!
+//! Generated code is the model's tokens joined with spaces, so any shader the
+//! tokenizer splits in a way that doesn't survive that join is one the model
+//! can never write. [`round_trip`] checks a single shader and
+//! [`round_trip_corpus`] a whole dataset's.
+
+use super::WGSLTokenizer;
+
+/// A shader tokenized and joined back into code
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RoundTrip {
+    /// The tokens joined with spaces, as generation detokenizes them
+    pub code: String,
+    /// Whether the original code parsed
+    pub original_parses: bool,
+    /// Parse error of the detokenized code
+    pub error: Option<String>,
+}
+
+impl RoundTrip {
+    /// Whether the detokenized code parses, or the original didn't either
+    pub fn is_ok(&self) -> bool {
+        !self.original_parses || self.error.is_none()
+    }
+}
+
+/// Tokenize `code`, join the tokens as generation would and parse the
+/// result with naga
+pub fn round_trip(tokenizer: &WGSLTokenizer, code: &str) -> RoundTrip {
+    let detokenized = tokenizer.tokenize(code).join(" ");
+    let parse = |code: &str| naga::front::wgsl::parse_str(code).map(|_| ());
+    RoundTrip {
+        original_parses: parse(code).is_ok(),
+        error: parse(&detokenized).err().map(|e| e.emit_to_string(&detokenized)),
+        code: detokenized,
+    }
+}
+
+/// Round trip every shader of `codes`, returning the index and result of
+/// each valid shader that no longer parses
+pub fn round_trip_corpus<S: AsRef<str>>(
+    tokenizer: &WGSLTokenizer,
+    codes: &[S],
+) -> Vec<(usize, RoundTrip)> {
+    codes
+        .iter()
+        .enumerate()
+        .map(|(index, code)| (index, round_trip(tokenizer, code.as_ref())))
+        .filter(|(_, result)| !result.is_ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dataset::WGSLDataset;
+    use crate::wgsl::{TemplateParams, TemplateRegistry};
+    use proptest::prelude::*;
+
+    #[test]
+    fn test_round_trip_corpus() {
+        let tokenizer = WGSLTokenizer::new(512, false);
+        let dataset = WGSLDataset::from_file("config/wgsl_training_data.toml").unwrap();
+        let mut codes: Vec<String> = dataset
+            .examples
+            .into_iter()
+            .map(|example| example.wgsl_code)
+            .collect();
+        let registry = TemplateRegistry::new();
+        for template in registry.templates() {
+            codes.push(template.render(&TemplateParams::default()).unwrap());
+        }
+
+        let failures = round_trip_corpus(&tokenizer, &codes);
+        assert!(
+            failures.is_empty(),
+            "{}",
+            failures
+                .iter()
+                .map(|(index, result)| format!("#{}: {}", index, result.error.as_ref().unwrap()))
+                .collect::<Vec<_>>()
+                .join("\n")
+        );
+
+        let broken = round_trip(&tokenizer, "fn f( {");
+        assert!(!broken.original_parses && broken.is_ok());
+    }
+
+    /// Literals in every form WGSL allows
+    fn literal() -> impl Strategy<Value = String> {
+        prop_oneof![
+            (0u32..1000).prop_map(|n| n.to_string()),
+            (0u32..1000).prop_map(|n| format!("{}u", n)),
+            (0u32..1000).prop_map(|n| format!("{}i", n)),
+            (0u32..0xffff).prop_map(|n| format!("0x{:X}u", n)),
+            (0u32..1000, 0u32..100).prop_map(|(a, b)| format!("{}.{}", a, b)),
+            (0u32..1000, 0u32..100).prop_map(|(a, b)| format!("{}.{}f", a, b)),
+            (0u32..100).prop_map(|n| format!(".{}", n)),
+            (1u32..10, -9i32..10).prop_map(|(a, e)| format!("{}e{}", a, e)),
+            Just("1.5e-3f".to_string()),
+        ]
+    }
+
+    /// Float expressions over `x` with every arithmetic and unary operator
+    fn expression() -> impl Strategy<Value = String> {
+        let leaf = prop_oneof![
+            Just("x".to_string()),
+            literal().prop_map(|l| format!("f32({})", l))
+        ];
+        leaf.prop_recursive(4, 16, 2, |inner| {
+            prop_oneof![
+                (inner.clone(), prop::sample::select(vec!["+", "-", "*", "/", "%"]), inner.clone())
+                    .prop_map(|(a, op, b)| format!("({}{}{})", a, op, b)),
+                inner.clone().prop_map(|a| format!("(-{})", a)),
+                (inner.clone(), inner).prop_map(|(a, b)| format!("select({},{},x<=1.0)", a, b)),
+            ]
+        })
+    }
+
+    /// Statements mixing compound assignment, comparisons, bit operations
+    /// and comments, written without spaces wherever WGSL allows
+    fn module() -> impl Strategy<Value = String> {
+        (
+            prop::collection::vec(expression(), 1..4),
+            prop::sample::select(vec!["+=", "-=", "*=", "/="]),
+            prop::sample::select(vec!["<<=", ">>=", "&=", "|=", "^="]),
+            any::<bool>(),
+        )
+            .prop_map(|(expressions, float_op, int_op, compact)| {
+                let body: String = expressions
+                    .iter()
+                    .map(|e| format!("y{}{};// keep {}\n", float_op, e, e))
+                    .collect();
+                let code = format!(
+                    "@group(0)@binding(0)var<storage,read_write>data:array<f32>;\n\
+                     /* entry\n point */\n\
+                     @compute@workgroup_size(64)fn main(@builtin(global_invocation_id)id:vec3<u32>){{\n\
+                     var x=data[id.x];var y=0.0;var n=id.x;\n\
+                     {}n{}1u;n++;if((n!=0u&&!(x>=2.0))||x<0.5){{y=-y;}}\n\
+                     data[id.x]=y+f32(n);\n}}",
+                    body, int_op
+                );
+                if compact {
+                    code
+                } else {
+                    code.replace(';', " ;\n ").replace(',', " , ").replace('(', " ( ")
+                }
+            })
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(64))]
+
+        #[test]
+        fn prop_valid_modules_round_trip(code in module()) {
+            let tokenizer = WGSLTokenizer::new(512, false);
+            let result = round_trip(&tokenizer, &code);
+            prop_assert!(result.original_parses, "generated invalid WGSL:\n{}", code);
+            prop_assert!(result.is_ok(), "{}\n{}", result.code, result.error.unwrap_or_default());
+        }
+    }
+}
@@ -0,0 +1,167 @@
+//! Hooks into the training loop
+//!
+//! Register a [`TrainerCallback`] with [`Trainer::with_callback`](super::Trainer::with_callback)
+//! to add custom logging, adjust the learning rate or preview generations
+//! without changing the loop itself. Every hook has a no-op default.
+
+use super::EpochMetrics;
+use crate::config::GenerationConfig;
+use crate::model::CodeGenerationModel;
+use crate::tokenizer::WGSLTokenizer;
+use std::path::{Path, PathBuf};
+
+/// View of the running training loop handed to every hook
+pub struct TrainerState<'a> {
+    pub model: &'a CodeGenerationModel,
+    pub tokenizer: &'a WGSLTokenizer,
+    /// Current epoch, starting at 1
+    pub epoch: usize,
+    /// Global optimizer step
+    pub step: u64,
+    /// Learning rate used for the following steps; hooks may change it
+    pub learning_rate: f64,
+    /// Set to end training; the current epoch is cut short but still
+    /// recorded and checkpointed
+    pub stop: bool,
+}
+
+impl<'a> TrainerState<'a> {
+    pub(crate) fn new(
+        model: &'a CodeGenerationModel,
+        tokenizer: &'a WGSLTokenizer,
+        epoch: usize,
+        step: u64,
+        learning_rate: f64,
+    ) -> Self {
+        Self {
+            model,
+            tokenizer,
+            epoch,
+            step,
+            learning_rate,
+            stop: false,
+        }
+    }
+}
+
+/// Statistics of one optimizer step
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BatchMetrics {
+    /// Index of the batch within the epoch, starting at 0
+    pub batch: usize,
+    /// Mean per-token NLL of the batch
+    pub loss: f64,
+    /// Gradient norm before clipping
+    pub grad_norm: f64,
+}
+
+/// Observer of training progress
+pub trait TrainerCallback {
+    /// Called before the first batch of an epoch
+    fn on_epoch_start(&mut self, _state: &mut TrainerState) -> crate::Result<()> {
+        Ok(())
+    }
+
+    /// Called after every optimizer step
+    fn on_batch_end(
+        &mut self,
+        _batch: &BatchMetrics,
+        _state: &mut TrainerState,
+    ) -> crate::Result<()> {
+        Ok(())
+    }
+
+    /// Called once the epoch's losses are known, before checkpointing
+    fn on_epoch_end(
+        &mut self,
+        _metrics: &EpochMetrics,
+        _state: &mut TrainerState,
+    ) -> crate::Result<()> {
+        Ok(())
+    }
+
+    /// Called after a checkpoint has been written to `path`
+    fn on_checkpoint(&mut self, _path: &Path, _state: &mut TrainerState) -> crate::Result<()> {
+        Ok(())
+    }
+
+    /// Called when early stopping ends training
+    fn on_early_stop(
+        &mut self,
+        _metrics: &EpochMetrics,
+        _state: &mut TrainerState,
+    ) -> crate::Result<()> {
+        Ok(())
+    }
+}
+
+/// Logs a generation for each prompt every `every` epochs
+pub struct SamplePreview {
+    prompts: Vec<String>,
+    every: usize,
+    generation: GenerationConfig,
+    seed: Option<u64>,
+    output_dir: Option<PathBuf>,
+}
+
+impl SamplePreview {
+    /// Preview greedy generations
+    pub fn new(prompts: Vec<String>, every: usize) -> Self {
+        Self {
+            prompts,
+            every: every.max(1),
+            generation: GenerationConfig::default(),
+            seed: None,
+            output_dir: None,
+        }
+    }
+
+    /// Decode previews with the `[generation]` settings, seeded with `seed`
+    pub fn with_generation(mut self, generation: GenerationConfig, seed: Option<u64>) -> Self {
+        self.generation = generation;
+        self.seed = seed;
+        self
+    }
+
+    /// Also write each epoch's previews to `epoch-N.md` in `dir`, such as a
+    /// run's [`samples_dir`](super::Run::samples_dir)
+    pub fn with_output_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.output_dir = Some(dir.into());
+        self
+    }
+}
+
+impl TrainerCallback for SamplePreview {
+    fn on_epoch_end(
+        &mut self,
+        metrics: &EpochMetrics,
+        state: &mut TrainerState,
+    ) -> crate::Result<()> {
+        if !metrics.epoch.is_multiple_of(self.every) {
+            return Ok(());
+        }
+        let generator =
+            crate::inference::WGSLGenerator::new(state.model.clone(), state.tokenizer.clone());
+        let validator = crate::WGSLValidator::new();
+        let mut markdown = format!("# Epoch {} samples\n", metrics.epoch);
+        for prompt in &self.prompts {
+            let result =
+                generator.generate_with_config(prompt, &self.generation, self.seed, &validator)?;
+            tracing::info!(
+                "Epoch {} sample for {:?}:\n{}",
+                metrics.epoch,
+                prompt,
+                result.code
+            );
+            markdown.push_str(&format!(
+                "\n## {}\n\n```wgsl\n{}\n```\n",
+                prompt, result.code
+            ));
+        }
+        if let Some(dir) = &self.output_dir {
+            std::fs::create_dir_all(dir)?;
+            std::fs::write(dir.join(format!("epoch-{}.md", metrics.epoch)), markdown)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,68 @@
+//! Cooperative cancellation of training
+//!
+//! A [`Trainer`](super::Trainer) given a [`CancellationToken`] checks it
+//! between batches. Once cancelled it finishes the batch in flight, flushes
+//! its metrics, writes `interrupted.ckpt` and returns normally with
+//! [`TrainingResults::interrupted`](super::TrainingResults::interrupted) set.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Shared flag asking training to stop; clones cancel together
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    /// Token that stays live until [`cancel`](Self::cancel) is called
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Token cancelled by the first Ctrl-C; a second one exits immediately
+    ///
+    /// Replaces the process's default Ctrl-C handling, so call it at most
+    /// once. Unavailable in WebAssembly builds, which have no signals.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn on_ctrl_c() -> crate::Result<Self> {
+        let token = Self::new();
+        let handler = token.clone();
+        ctrlc::set_handler(move || {
+            if handler.is_cancelled() {
+                eprintln!("\nAborting");
+                std::process::exit(130);
+            }
+            handler.cancel();
+            eprintln!(
+                "\nInterrupted: finishing the current batch and saving a checkpoint \
+                 (press Ctrl-C again to abort)"
+            );
+        })
+        .map_err(|e| crate::Error::Other(format!("Failed to handle Ctrl-C: {}", e)))?;
+        Ok(token)
+    }
+
+    /// Ask training to stop after the current batch
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clones_cancel_together() {
+        let token = CancellationToken::new();
+        let clone = token.clone();
+        assert!(!clone.is_cancelled());
+        token.cancel();
+        assert!(clone.is_cancelled());
+    }
+}
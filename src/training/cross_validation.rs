@@ -0,0 +1,302 @@
+//! K-fold cross-validation for small datasets
+//!
+//! Every fold trains a fresh tokenizer and model on the remaining folds and
+//! evaluates generations and perplexity on the held-out fold, so the spread
+//! of the scores shows how much a single train/test split can be trusted.
+
+use super::{perplexity, Perplexity, Trainer};
+use crate::config::Config;
+use crate::dataset::WGSLDataset;
+use crate::device::Device;
+use crate::eval::{EvalMetrics, Evaluator};
+use crate::inference::{with_stage_tokens, WGSLGenerator};
+use crate::model::{Checkpoint, CodeGenerationModel};
+use crate::tokenizer::WGSLTokenizer;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Scores of the model trained for one fold
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FoldResult {
+    /// Fold index, starting at 1
+    pub fold: usize,
+    pub train_examples: usize,
+    pub test_examples: usize,
+    /// Final training loss
+    pub final_loss: f64,
+    /// Generation metrics on the held-out fold
+    pub metrics: EvalMetrics,
+    /// Perplexity of the held-out reference code
+    pub perplexity: Perplexity,
+}
+
+impl FoldResult {
+    /// Named scalar scores summarised across folds
+    fn scores(&self) -> [(&'static str, f64); 8] {
+        [
+            ("final_loss", self.final_loss),
+            ("perplexity", self.perplexity.perplexity),
+            ("validity_rate", self.metrics.validity_rate),
+            ("exact_match_rate", self.metrics.exact_match_rate),
+            ("token_accuracy", self.metrics.token_accuracy),
+            ("avg_edit_distance", self.metrics.avg_edit_distance),
+            ("bleu", self.metrics.bleu),
+            ("code_bleu", self.metrics.code_bleu),
+        ]
+    }
+}
+
+/// Mean and sample variance of one score across folds
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct MetricSummary {
+    pub mean: f64,
+    pub variance: f64,
+}
+
+impl MetricSummary {
+    /// Summarise `values`; the variance is 0 for fewer than two values
+    pub fn from_values(values: &[f64]) -> Self {
+        if values.is_empty() {
+            return Self::default();
+        }
+        let n = values.len() as f64;
+        let mean = values.iter().sum::<f64>() / n;
+        let variance = if values.len() > 1 {
+            values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (n - 1.0)
+        } else {
+            0.0
+        };
+        Self { mean, variance }
+    }
+
+    pub fn std_dev(&self) -> f64 {
+        self.variance.sqrt()
+    }
+}
+
+/// Results of a cross-validation run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CrossValidationReport {
+    pub folds: Vec<FoldResult>,
+    /// Summary of each score, keyed by name
+    pub summary: BTreeMap<String, MetricSummary>,
+}
+
+impl CrossValidationReport {
+    pub fn from_folds(folds: Vec<FoldResult>) -> Self {
+        let mut values: BTreeMap<String, Vec<f64>> = BTreeMap::new();
+        for fold in &folds {
+            for (name, value) in fold.scores() {
+                values.entry(name.to_string()).or_default().push(value);
+            }
+        }
+        let summary = values
+            .into_iter()
+            .map(|(name, values)| (name, MetricSummary::from_values(&values)))
+            .collect();
+        Self { folds, summary }
+    }
+
+    /// Print per-fold scores and the mean ± standard deviation of each metric
+    pub fn print(&self) {
+        println!("📊 Cross-validation ({} folds)", self.folds.len());
+        for fold in &self.folds {
+            println!(
+                "   Fold {}: {} train / {} test, loss {:.4}, perplexity {:.3}, validity {:.1}%, BLEU {:.3}",
+                fold.fold,
+                fold.train_examples,
+                fold.test_examples,
+                fold.final_loss,
+                fold.perplexity.perplexity,
+                fold.metrics.validity_rate * 100.0,
+                fold.metrics.bleu
+            );
+        }
+        println!();
+        for (name, summary) in &self.summary {
+            println!(
+                "   {:<18} {:.4} ± {:.4} (variance {:.6})",
+                name,
+                summary.mean,
+                summary.std_dev(),
+                summary.variance
+            );
+        }
+    }
+
+    /// Write the report as JSON
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> crate::Result<()> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+/// Trains and evaluates one model per fold
+pub struct CrossValidator {
+    config: Config,
+    folds: usize,
+    progress: bool,
+    cache_dir: Option<PathBuf>,
+}
+
+impl CrossValidator {
+    /// Cross-validate the model, tokenizer and training settings of `config`
+    /// over `folds` folds
+    pub fn new(config: Config, folds: usize) -> Self {
+        Self {
+            config,
+            folds,
+            progress: false,
+            cache_dir: None,
+        }
+    }
+
+    /// Show progress bars while training and generating
+    pub fn with_progress(mut self, enabled: bool) -> Self {
+        self.progress = enabled;
+        self
+    }
+
+    /// Cache every fold's encoded training data under `dir`, so rerunning
+    /// with the same folds and tokenizer settings skips tokenization
+    pub fn with_cache_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.cache_dir = Some(dir.into());
+        self
+    }
+
+    /// Run every fold over `dataset`, formatted with the configured prompt
+    /// template
+    pub fn run(&self, dataset: &WGSLDataset) -> crate::Result<CrossValidationReport> {
+        let dataset = match self.config.prompt_template()? {
+            Some(template) => template.format_dataset(dataset),
+            None => dataset.clone(),
+        };
+        // Distilled students share the teacher's tokenizer and normalizer
+        let teacher_tokenizer = match &self.config.training.distillation {
+            Some(distillation) => Some(Checkpoint::load(&distillation.teacher)?.tokenizer),
+            None => None,
+        };
+        let folds = dataset.k_fold(self.folds)?;
+        let device = Device::from_config(&self.config.device)?;
+        let mut results = Vec::with_capacity(folds.len());
+        for (index, (train, held_out)) in folds.iter().enumerate() {
+            tracing::info!(
+                "Fold {}/{}: {} train, {} held out",
+                index + 1,
+                folds.len(),
+                train.len(),
+                held_out.len()
+            );
+            results.push(self.run_fold(
+                index + 1,
+                train,
+                held_out,
+                teacher_tokenizer.as_ref(),
+                &device,
+            )?);
+        }
+        Ok(CrossValidationReport::from_folds(results))
+    }
+
+    fn run_fold(
+        &self,
+        fold: usize,
+        train: &WGSLDataset,
+        held_out: &WGSLDataset,
+        teacher_tokenizer: Option<&WGSLTokenizer>,
+        device: &Device,
+    ) -> crate::Result<FoldResult> {
+        let tokenizer_config = &self.config.tokenizer;
+        let mut tokenizer = match teacher_tokenizer {
+            Some(tokenizer) => tokenizer.clone(),
+            None => WGSLTokenizer::from_config(tokenizer_config)?,
+        };
+        let train = &with_stage_tokens(train, &tokenizer);
+        let held_out = &with_stage_tokens(held_out, &tokenizer);
+        let prompts: Vec<String> = train
+            .examples
+            .iter()
+            .map(|e| tokenizer.normalize_prompt(&e.natural_language))
+            .collect();
+        let texts: Vec<&str> = train
+            .examples
+            .iter()
+            .zip(&prompts)
+            .flat_map(|(e, prompt)| [prompt.as_str(), e.wgsl_code.as_str()])
+            .collect();
+        if teacher_tokenizer.is_none() {
+            tokenizer.fit(&texts, tokenizer_config.min_freq);
+        }
+
+        let mut model =
+            CodeGenerationModel::from_model_config(tokenizer.vocab_size(), &self.config.model)
+                .with_seed(self.config.init_seed());
+        if let Some(pretrained) = &self.config.model.pretrained {
+            model.import_pretrained(pretrained, &tokenizer)?;
+        }
+        let mut trainer = Trainer::new(self.config.training.clone())
+            .with_progress(self.progress)
+            .with_device(device.clone());
+        if let Some(dir) = &self.cache_dir {
+            trainer = trainer.with_cache_dir(dir);
+        }
+        let results = trainer.train(&mut model, &tokenizer, train, None)?;
+
+        let perplexity = perplexity(&model, &tokenizer, held_out)?;
+        let generator = WGSLGenerator::new(model, tokenizer);
+        let report = Evaluator::default()
+            .with_progress(self.progress)
+            .evaluate(&generator, held_out)?;
+
+        Ok(FoldResult {
+            fold,
+            train_examples: train.len(),
+            test_examples: held_out.len(),
+            final_loss: results.final_loss as f64,
+            metrics: report.metrics,
+            perplexity,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dataset::WGSLExample;
+
+    #[test]
+    fn test_metric_summary() {
+        let summary = MetricSummary::from_values(&[1.0, 2.0, 3.0]);
+        assert_eq!(summary.mean, 2.0);
+        assert_eq!(summary.variance, 1.0);
+        assert_eq!(MetricSummary::from_values(&[4.0]).variance, 0.0);
+    }
+
+    #[test]
+    fn test_cross_validation() {
+        let mut config = Config::default_wgsl_generation();
+        config.model.d_model = 8;
+        config.model.nhead = 2;
+        config.model.num_layers = 1;
+        config.model.dim_feedforward = 16;
+        config.model.max_seq_len = 16;
+        config.training.num_epochs = 1;
+        config.training.batch_size = 2;
+
+        let dataset = WGSLDataset {
+            examples: (0..6)
+                .map(|i| WGSLExample::new(format!("shader {}", i), "fn main() { }"))
+                .collect(),
+        };
+        let report = CrossValidator::new(config, 3).run(&dataset).unwrap();
+
+        assert_eq!(report.folds.len(), 3);
+        assert!(report.folds.iter().all(|f| f.test_examples == 2));
+        assert_eq!(report.summary.len(), 8);
+        let loss = report.summary["final_loss"];
+        let mean = report.folds.iter().map(|f| f.final_loss).sum::<f64>() / 3.0;
+        assert!((loss.mean - mean).abs() < 1e-9);
+        assert!(report.summary["perplexity"].mean > 1.0);
+    }
+}
@@ -0,0 +1,148 @@
+//! Curriculum ordering of training examples
+//!
+//! Tiny models trained on code tend to diverge early when long shaders land
+//! in the first batches. A [`Curriculum`] ranks examples by a difficulty
+//! measure and, for its first epochs, trains only on the easiest share of
+//! them, batched from easiest to hardest.
+
+use crate::config::{CurriculumConfig, CurriculumMetric};
+use crate::dataset::{Difficulty, ExampleSource};
+use rand::{seq::SliceRandom, Rng};
+
+/// Difficulty ranking of a dataset under a [`CurriculumConfig`]
+#[derive(Debug, Clone)]
+pub struct Curriculum {
+    config: CurriculumConfig,
+    /// Sort key of each example: its rank under the metric, then its length
+    keys: Vec<(usize, usize)>,
+}
+
+impl Curriculum {
+    /// Rank the examples of `dataset`, whose encoded prompt and code are
+    /// `pairs`; examples are read again only for metrics other than length
+    pub fn new(
+        config: &CurriculumConfig,
+        dataset: &dyn ExampleSource,
+        pairs: &[(Vec<usize>, Vec<usize>)],
+    ) -> crate::Result<Self> {
+        let keys = pairs
+            .iter()
+            .enumerate()
+            .map(|(index, (input, target))| {
+                let rank = match config.by {
+                    CurriculumMetric::Length => 0,
+                    CurriculumMetric::Difficulty => {
+                        dataset.get(index)?.difficulty.unwrap_or(Difficulty::Medium) as usize
+                    }
+                    CurriculumMetric::Tags => {
+                        let example = dataset.get(index)?;
+                        config
+                            .tags
+                            .iter()
+                            .rposition(|tag| example.has_tag(tag))
+                            .unwrap_or(config.tags.len())
+                    }
+                };
+                Ok((rank, input.len() + target.len()))
+            })
+            .collect::<crate::Result<_>>()?;
+        Ok(Self {
+            config: config.clone(),
+            keys,
+        })
+    }
+
+    /// Whether `epoch` (starting at 1) follows the curriculum
+    pub fn is_active(&self, epoch: usize) -> bool {
+        epoch <= self.config.epochs
+    }
+
+    /// Share of the examples trained on in `epoch`
+    pub fn fraction(&self, epoch: usize) -> f64 {
+        if !self.is_active(epoch) || self.config.epochs <= 1 {
+            return 1.0;
+        }
+        let start = self.config.start_fraction.clamp(0.0, 1.0);
+        let progress = (epoch - 1) as f64 / (self.config.epochs - 1) as f64;
+        start + (1.0 - start) * progress
+    }
+
+    /// Number of examples trained on in `epoch`, at least one
+    pub fn pool_size(&self, epoch: usize) -> usize {
+        let size = (self.keys.len() as f64 * self.fraction(epoch)).ceil() as usize;
+        size.max(1).min(self.keys.len())
+    }
+
+    /// Indices of the examples of `epoch`, easiest first; examples of equal
+    /// difficulty are shuffled with `rng`
+    pub fn order<R: Rng>(&self, epoch: usize, rng: &mut R) -> Vec<usize> {
+        let mut order: Vec<usize> = (0..self.keys.len()).collect();
+        order.shuffle(rng);
+        order.sort_by_key(|&index| self.keys[index]);
+        order.truncate(self.pool_size(epoch));
+        order
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dataset::{WGSLDataset, WGSLExample};
+    use rand::{rngs::StdRng, SeedableRng};
+
+    fn example(tags: &[&str], difficulty: Option<Difficulty>) -> WGSLExample {
+        WGSLExample {
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
+            difficulty,
+            ..WGSLExample::new("prompt", "code")
+        }
+    }
+
+    #[test]
+    fn test_curriculum() {
+        let dataset = WGSLDataset {
+            examples: vec![
+                example(&["loop"], Some(Difficulty::Hard)),
+                example(&[], None),
+                example(&["snippet", "loop"], Some(Difficulty::Easy)),
+                example(&["snippet"], Some(Difficulty::Easy)),
+            ],
+        };
+        let pairs = vec![
+            (vec![1; 2], vec![1; 10]),
+            (vec![1; 2], vec![1; 3]),
+            (vec![1; 2], vec![1; 8]),
+            (vec![1; 2], vec![1; 1]),
+        ];
+        let mut rng = StdRng::seed_from_u64(0);
+        let config = CurriculumConfig {
+            epochs: 3,
+            start_fraction: 0.5,
+            ..CurriculumConfig::default()
+        };
+
+        let curriculum = Curriculum::new(&config, &dataset, &pairs).unwrap();
+        assert_eq!(curriculum.fraction(1), 0.5);
+        assert_eq!(curriculum.fraction(2), 0.75);
+        assert_eq!(curriculum.fraction(3), 1.0);
+        assert!(!curriculum.is_active(4));
+        assert_eq!(curriculum.order(1, &mut rng), [3, 1]);
+        assert_eq!(curriculum.order(2, &mut rng), [3, 1, 2]);
+        assert_eq!(curriculum.order(3, &mut rng), [3, 1, 2, 0]);
+
+        let by_difficulty = CurriculumConfig {
+            by: CurriculumMetric::Difficulty,
+            ..config.clone()
+        };
+        let curriculum = Curriculum::new(&by_difficulty, &dataset, &pairs).unwrap();
+        assert_eq!(curriculum.order(3, &mut rng), [3, 2, 1, 0]);
+
+        let by_tags = CurriculumConfig {
+            by: CurriculumMetric::Tags,
+            tags: vec!["snippet".to_string(), "loop".to_string()],
+            ..config
+        };
+        let curriculum = Curriculum::new(&by_tags, &dataset, &pairs).unwrap();
+        assert_eq!(curriculum.order(3, &mut rng), [3, 2, 0, 1]);
+    }
+}
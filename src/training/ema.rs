@@ -0,0 +1,118 @@
+//! Exponential moving average of the weights
+//!
+//! On small, noisy datasets the weights after the last step are one sample of
+//! a jittery trajectory. An [`Ema`] keeps a shadow copy averaged over recent
+//! steps, which usually scores better and is what the trainer validates and
+//! checkpoints when `training.ema_decay` is set.
+
+use crate::model::CodeGenerationModel;
+
+/// Shadow copy of a model's parameters, averaged after every optimizer step
+#[derive(Debug, Clone)]
+pub struct Ema {
+    pub decay: f64,
+    updates: u64,
+    shadow: Vec<Vec<f32>>,
+}
+
+impl Ema {
+    /// Start averaging from the current weights of `model`
+    pub fn new(model: &CodeGenerationModel, decay: f64) -> Self {
+        let mut shadow = Vec::new();
+        model.visit_parameters(&mut |_, values| shadow.push(values.to_vec()));
+        Self {
+            decay,
+            updates: 0,
+            shadow,
+        }
+    }
+
+    /// Decay of the next update, ramped up over the first steps so the
+    /// average isn't dominated by the initial weights
+    pub fn current_decay(&self) -> f64 {
+        let warmup = (1 + self.updates) as f64 / (10 + self.updates) as f64;
+        self.decay.min(warmup)
+    }
+
+    /// Move the average towards the weights of `model` after a step
+    pub fn update(&mut self, model: &CodeGenerationModel) {
+        let decay = self.current_decay() as f32;
+        let mut index = 0;
+        model.visit_parameters(&mut |_, values| {
+            for (average, &value) in self.shadow[index].iter_mut().zip(values) {
+                *average = decay * *average + (1.0 - decay) * value;
+            }
+            index += 1;
+        });
+        self.updates += 1;
+    }
+
+    /// A copy of `model` holding the averaged weights
+    pub fn model(&self, model: &CodeGenerationModel) -> CodeGenerationModel {
+        let mut averaged = model.clone();
+        self.copy_to(&mut averaged);
+        averaged
+    }
+
+    /// Overwrite the weights of `model` with the averaged ones
+    pub fn copy_to(&self, model: &mut CodeGenerationModel) {
+        let mut index = 0;
+        model.visit_parameters_mut(&mut |_, values| {
+            values.copy_from_slice(&self.shadow[index]);
+            index += 1;
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::ModelArchitecture;
+
+    #[test]
+    fn test_ema() {
+        let mut model = CodeGenerationModel::new(
+            ModelArchitecture::Transformer,
+            16,
+            8,
+            2,
+            1,
+            Some(8),
+            Some(8),
+        );
+        let mut ema = Ema::new(&model, 0.5);
+        let mut initial = Vec::new();
+        model.visit_parameters(&mut |_, values| initial.push(values[0]));
+        assert_eq!(ema.current_decay(), 0.1);
+
+        let set = |model: &mut CodeGenerationModel, value: f32| {
+            model.visit_parameters_mut(&mut |_, values| values.fill(value));
+        };
+        set(&mut model, 1.0);
+        ema.update(&model);
+        set(&mut model, 3.0);
+        ema.update(&model);
+        assert_eq!(ema.current_decay(), 0.25);
+
+        // 0.1 * w + 0.9 * 1.0, then (2 / 11) * that + (9 / 11) * 3.0
+        let mut weights = Vec::new();
+        let averaged = ema.model(&model);
+        averaged.visit_parameters(&mut |name, values| weights.push((name.to_string(), values[0])));
+        for ((name, weight), initial) in weights.iter().zip(initial) {
+            let first = 0.1 * initial + 0.9;
+            let expected = 2.0 / 11.0 * first + 9.0 / 11.0 * 3.0;
+            assert!(
+                (weight - expected).abs() < 1e-5,
+                "{}: {} != {}",
+                name,
+                weight,
+                expected
+            );
+        }
+
+        ema.copy_to(&mut model);
+        let mut copied = Vec::new();
+        model.visit_parameters(&mut |_, values| copied.push(values[0]));
+        assert_eq!(copied, weights.iter().map(|(_, w)| *w).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,353 @@
+//! Scalar metric sinks for monitoring training runs
+//!
+//! [`TensorBoardWriter`] writes a tfevents file readable by TensorBoard and
+//! other dashboards; [`JsonMetricsWriter`] writes one JSON object per line
+//! with the same fields. [`CsvMetricsWriter`] keeps one row per epoch for
+//! plotting in a spreadsheet.
+
+use super::EpochMetrics;
+use crate::config::MetricsFormat;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Destination for training scalars such as `train/loss` or `val/perplexity`
+pub trait MetricsSink {
+    /// Record `value` for `tag` at global step `step`
+    fn log_scalar(&mut self, tag: &str, step: u64, value: f64) -> crate::Result<()>;
+
+    /// Flush buffered records to disk
+    fn flush(&mut self) -> crate::Result<()> {
+        Ok(())
+    }
+}
+
+/// Shared sink, so the caller can keep using it after handing a clone to a trainer
+impl<S: MetricsSink + ?Sized> MetricsSink for Arc<Mutex<S>> {
+    fn log_scalar(&mut self, tag: &str, step: u64, value: f64) -> crate::Result<()> {
+        lock(self).log_scalar(tag, step, value)
+    }
+
+    fn flush(&mut self) -> crate::Result<()> {
+        lock(self).flush()
+    }
+}
+
+fn lock<S: ?Sized>(sink: &Mutex<S>) -> std::sync::MutexGuard<'_, S> {
+    sink.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Create a sink of the given format writing into `dir`
+pub fn create_sink(format: MetricsFormat, dir: &Path) -> crate::Result<Box<dyn MetricsSink>> {
+    Ok(match format {
+        MetricsFormat::Tensorboard => Box::new(TensorBoardWriter::create(dir)?),
+        MetricsFormat::Json => Box::new(JsonMetricsWriter::create(dir)?),
+    })
+}
+
+/// Writes scalars as TensorBoard event records
+pub struct TensorBoardWriter {
+    path: PathBuf,
+    writer: BufWriter<File>,
+}
+
+impl TensorBoardWriter {
+    /// Create `dir` and a new event file inside it
+    pub fn create(dir: &Path) -> crate::Result<Self> {
+        std::fs::create_dir_all(dir)?;
+        let path = dir.join(format!(
+            "events.out.tfevents.{}.tiny-agent-trainer",
+            wall_time() as u64
+        ));
+        let mut writer = Self {
+            writer: BufWriter::new(File::create(&path)?),
+            path,
+        };
+
+        let mut event = event_header(wall_time(), 0);
+        proto_bytes(&mut event, 3, b"brain.Event:2");
+        writer.write_record(&event)?;
+        Ok(writer)
+    }
+
+    /// Path of the event file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// TFRecord framing: length, masked CRC of the length, data, masked CRC of the data
+    fn write_record(&mut self, data: &[u8]) -> crate::Result<()> {
+        let length = (data.len() as u64).to_le_bytes();
+        self.writer.write_all(&length)?;
+        self.writer
+            .write_all(&masked_crc32c(&length).to_le_bytes())?;
+        self.writer.write_all(data)?;
+        self.writer.write_all(&masked_crc32c(data).to_le_bytes())?;
+        Ok(())
+    }
+}
+
+impl MetricsSink for TensorBoardWriter {
+    fn log_scalar(&mut self, tag: &str, step: u64, value: f64) -> crate::Result<()> {
+        // Summary.Value { tag = 1, simple_value = 2 }
+        let mut summary_value = Vec::new();
+        proto_bytes(&mut summary_value, 1, tag.as_bytes());
+        proto_key(&mut summary_value, 2, 5);
+        summary_value.extend_from_slice(&(value as f32).to_le_bytes());
+
+        // Summary { value = 1 }
+        let mut summary = Vec::new();
+        proto_bytes(&mut summary, 1, &summary_value);
+
+        // Event { wall_time = 1, step = 2, summary = 5 }
+        let mut event = event_header(wall_time(), step);
+        proto_bytes(&mut event, 5, &summary);
+        self.write_record(&event)
+    }
+
+    fn flush(&mut self) -> crate::Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// One line of `scalars.jsonl`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScalarRecord {
+    pub wall_time: f64,
+    pub step: u64,
+    pub tag: String,
+    pub value: f64,
+}
+
+/// Writes scalars as JSON lines
+pub struct JsonMetricsWriter {
+    path: PathBuf,
+    writer: BufWriter<File>,
+}
+
+impl JsonMetricsWriter {
+    /// Create `dir` and `scalars.jsonl` inside it
+    pub fn create(dir: &Path) -> crate::Result<Self> {
+        std::fs::create_dir_all(dir)?;
+        let path = dir.join("scalars.jsonl");
+        Ok(Self {
+            writer: BufWriter::new(File::create(&path)?),
+            path,
+        })
+    }
+
+    /// Path of the JSON lines file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl MetricsSink for JsonMetricsWriter {
+    fn log_scalar(&mut self, tag: &str, step: u64, value: f64) -> crate::Result<()> {
+        let record = ScalarRecord {
+            wall_time: wall_time(),
+            step,
+            tag: tag.to_string(),
+            value,
+        };
+        serde_json::to_writer(&mut self.writer, &record)?;
+        self.writer.write_all(b"\n")?;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> crate::Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Writes one `metrics.csv` row per epoch
+pub struct CsvMetricsWriter {
+    path: PathBuf,
+    writer: BufWriter<File>,
+}
+
+impl CsvMetricsWriter {
+    /// Create `dir` and `metrics.csv` inside it, with a header row
+    pub fn create(dir: &Path) -> crate::Result<Self> {
+        std::fs::create_dir_all(dir)?;
+        let path = dir.join("metrics.csv");
+        let mut writer = BufWriter::new(File::create(&path)?);
+        writeln!(writer, "epoch,step,train_loss,val_loss,lr,time")?;
+        writer.flush()?;
+        Ok(Self { path, writer })
+    }
+
+    /// Path of the CSV file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Append and flush a row; `val_loss` is left empty without a validation set
+    pub fn write_epoch(&mut self, metrics: &EpochMetrics) -> crate::Result<()> {
+        writeln!(
+            self.writer,
+            "{},{},{},{},{},{:.3}",
+            metrics.epoch,
+            metrics.step,
+            metrics.train_loss,
+            metrics
+                .val_loss
+                .map(|loss| loss.to_string())
+                .unwrap_or_default(),
+            metrics.learning_rate,
+            metrics.elapsed_secs
+        )?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+fn wall_time() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs_f64())
+        .unwrap_or(0.0)
+}
+
+fn event_header(wall_time: f64, step: u64) -> Vec<u8> {
+    let mut event = Vec::new();
+    proto_key(&mut event, 1, 1);
+    event.extend_from_slice(&wall_time.to_le_bytes());
+    proto_key(&mut event, 2, 0);
+    proto_varint(&mut event, step);
+    event
+}
+
+fn proto_key(buf: &mut Vec<u8>, field: u32, wire_type: u8) {
+    proto_varint(buf, ((field as u64) << 3) | wire_type as u64);
+}
+
+fn proto_varint(buf: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buf.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+fn proto_bytes(buf: &mut Vec<u8>, field: u32, bytes: &[u8]) {
+    proto_key(buf, field, 2);
+    proto_varint(buf, bytes.len() as u64);
+    buf.extend_from_slice(bytes);
+}
+
+fn crc32c(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0x82F6_3B78
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+fn masked_crc32c(data: &[u8]) -> u32 {
+    let crc = crc32c(data);
+    crc.rotate_right(15).wrapping_add(0xa282_ead8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crc32c() {
+        assert_eq!(crc32c(b"123456789"), 0xE306_9283);
+        assert_eq!(crc32c(b""), 0);
+    }
+
+    #[test]
+    fn test_tensorboard_records() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut writer = TensorBoardWriter::create(dir.path()).unwrap();
+        writer.log_scalar("train/loss", 3, 0.5).unwrap();
+        writer.flush().unwrap();
+
+        let bytes = std::fs::read(writer.path()).unwrap();
+        let mut records = Vec::new();
+        let mut offset = 0;
+        while offset < bytes.len() {
+            let header = &bytes[offset..offset + 8];
+            let length = u64::from_le_bytes(header.try_into().unwrap()) as usize;
+            let length_crc = u32::from_le_bytes(bytes[offset + 8..offset + 12].try_into().unwrap());
+            assert_eq!(length_crc, masked_crc32c(header));
+
+            let data = &bytes[offset + 12..offset + 12 + length];
+            let data_crc = u32::from_le_bytes(
+                bytes[offset + 12 + length..offset + 16 + length]
+                    .try_into()
+                    .unwrap(),
+            );
+            assert_eq!(data_crc, masked_crc32c(data));
+            records.push(data.to_vec());
+            offset += 16 + length;
+        }
+
+        assert_eq!(records.len(), 2);
+        let contains = |haystack: &[u8], needle: &[u8]| {
+            haystack
+                .windows(needle.len())
+                .any(|window| window == needle)
+        };
+        assert!(contains(&records[0], b"brain.Event:2"));
+        assert!(contains(&records[1], b"train/loss"));
+        assert!(contains(&records[1], &0.5f32.to_le_bytes()));
+        // step = 3 as field 2 varint
+        assert!(contains(&records[1], &[0x10, 0x03]));
+    }
+
+    #[test]
+    fn test_json_writer() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut sink = create_sink(MetricsFormat::Json, dir.path()).unwrap();
+        sink.log_scalar("train/loss", 1, 2.0).unwrap();
+        sink.log_scalar("train/lr", 1, 0.001).unwrap();
+        sink.flush().unwrap();
+
+        let content = std::fs::read_to_string(dir.path().join("scalars.jsonl")).unwrap();
+        let records: Vec<ScalarRecord> = content
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1].tag, "train/lr");
+        assert_eq!(records[1].value, 0.001);
+    }
+
+    #[test]
+    fn test_csv_writer() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut writer = CsvMetricsWriter::create(dir.path()).unwrap();
+        writer
+            .write_epoch(&EpochMetrics {
+                epoch: 1,
+                step: 4,
+                train_loss: 2.5,
+                val_loss: None,
+                learning_rate: 0.001,
+                elapsed_secs: 1.25,
+            })
+            .unwrap();
+
+        let content = std::fs::read_to_string(writer.path()).unwrap();
+        assert_eq!(
+            content,
+            "epoch,step,train_loss,val_loss,lr,time\n1,4,2.5,,0.001,1.250\n"
+        );
+    }
+}
@@ -1,47 +1,793 @@
 //! Training pipeline for WGSL code generation models
 
-use crate::config::TrainingConfig;
-use crate::model::CodeGenerationModel;
+pub mod callbacks;
+pub mod cancel;
+pub mod cross_validation;
+pub mod curriculum;
+pub mod ema;
+pub mod metrics;
+pub mod optimizer;
+pub mod run;
+#[cfg(feature = "wandb")]
+pub mod wandb;
+
+use crate::config::{DistillationConfig, TrainingConfig};
+use crate::dataset::encoded::EncodedDataset;
+use crate::dataset::{ExampleSource, WGSLDataset};
+use crate::device::Device;
+use crate::logging::timed;
+use crate::model::diagnostics::LayerStats;
+use crate::model::{Checkpoint, CheckpointMetadata, CodeGenerationModel, Gradients};
+use crate::progress;
+use crate::tokenizer::WGSLTokenizer;
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::time::Instant;
+use tracing::field::Empty;
+
+pub use callbacks::{BatchMetrics, SamplePreview, TrainerCallback, TrainerState};
+pub use cancel::CancellationToken;
+pub use cross_validation::{CrossValidationReport, CrossValidator, FoldResult, MetricSummary};
+pub use curriculum::Curriculum;
+pub use ema::Ema;
+pub use metrics::{
+    create_sink, CsvMetricsWriter, JsonMetricsWriter, MetricsSink, TensorBoardWriter,
+};
+pub use optimizer::{Optimizer, OptimizerKind};
+pub use run::{Run, RunManager};
 
 /// Training orchestrator
 pub struct Trainer {
     pub config: TrainingConfig,
+    metrics: Vec<Box<dyn MetricsSink>>,
+    callbacks: Vec<Box<dyn TrainerCallback>>,
+    checkpoint_dir: Option<PathBuf>,
+    progress: bool,
+    device: Device,
+    cancellation: Option<CancellationToken>,
+    cache_dir: Option<PathBuf>,
 }
 
 impl Trainer {
     /// Create a new trainer with the given configuration
     pub fn new(config: TrainingConfig) -> Self {
-        Self { config }
+        Self {
+            config,
+            metrics: Vec::new(),
+            callbacks: Vec::new(),
+            checkpoint_dir: None,
+            progress: false,
+            device: Device::cpu(),
+            cancellation: None,
+            cache_dir: None,
+        }
+    }
+
+    /// Report `train/loss`, `train/lr` and `train/grad_norm` per step and
+    /// `val/loss` and `val/perplexity` per epoch to `sink`, in addition to any
+    /// sinks added before
+    pub fn with_metrics(mut self, sink: Box<dyn MetricsSink>) -> Self {
+        self.metrics.push(sink);
+        self
+    }
+
+    /// Run `callback`'s hooks during training, after those added before
+    pub fn with_callback(mut self, callback: Box<dyn TrainerCallback>) -> Self {
+        self.callbacks.push(callback);
+        self
+    }
+
+    /// Save `epoch-N.ckpt` every `save_every` epochs, `best.ckpt` whenever
+    /// the monitored loss improves, and a `metrics.csv` row per epoch
+    pub fn with_checkpoint_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.checkpoint_dir = Some(dir.into());
+        self
+    }
+
+    /// Show a progress bar with live loss and ETA while training
+    pub fn with_progress(mut self, enabled: bool) -> Self {
+        self.progress = enabled;
+        self
+    }
+
+    /// Spread each batch over `device`'s threads, and fail before training
+    /// when the model, its gradients and optimizer state exceed its memory
+    pub fn with_device(mut self, device: Device) -> Self {
+        self.device = device;
+        self
+    }
+
+    /// Stop after the batch in flight once `token` is cancelled, writing
+    /// `interrupted.ckpt` and the metrics of the partial epoch
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancellation = Some(token);
+        self
     }
 
-    /// Train a model (placeholder)
-    pub fn train(&mut self, _model: &mut CodeGenerationModel) -> crate::Result<TrainingResults> {
+    /// Reuse the training and validation sets encoded by earlier runs with
+    /// the same tokenizer, caching them in `dir`
+    pub fn with_cache_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.cache_dir = Some(dir.into());
+        self
+    }
+
+    fn cancelled(&self) -> bool {
+        self.cancellation
+            .as_ref()
+            .is_some_and(CancellationToken::is_cancelled)
+    }
+
+    /// Train `model` on `train` with teacher forcing, monitoring `val` when
+    /// given (otherwise the training loss) for early stopping
+    ///
+    /// Prompts are normalized with `tokenizer`'s normalizer as they are
+    /// encoded, so callers pass them as written. `train` may be any
+    /// [`ExampleSource`]; a [`LazyDataset`] is read from disk while it is
+    /// encoded, so only token ids stay in memory. With
+    /// `ema_decay` set, `model` ends up holding the averaged weights.
+    ///
+    /// [`LazyDataset`]: crate::dataset::lazy::LazyDataset
+    pub fn train(
+        &mut self,
+        model: &mut CodeGenerationModel,
+        tokenizer: &WGSLTokenizer,
+        train: &(dyn ExampleSource + Sync),
+        val: Option<&WGSLDataset>,
+    ) -> crate::Result<TrainingResults> {
+        if model.num_parameters() == 0 {
+            return Err(crate::Error::Other(format!(
+                "{:?} models have no trainable parameters",
+                model.architecture
+            )));
+        }
+        if let Some(freeze) = &self.config.freeze {
+            let (mut frozen, mut total) = (0, 0);
+            model.visit_parameters(&mut |name, values| {
+                total += values.len();
+                if freeze.freezes(name) {
+                    frozen += values.len();
+                }
+            });
+            tracing::info!("Freezing {} of {} parameters", frozen, total);
+        }
+        if train.is_empty() {
+            return Err(crate::Error::Other(
+                "Cannot train on an empty dataset".to_string(),
+            ));
+        }
+        let teacher = match self.config.distillation.clone() {
+            Some(distillation) => Some((load_teacher(&distillation, tokenizer)?, distillation)),
+            None => None,
+        };
+        // Weights, two optimizer moments and a set of gradients per thread
+        let copies = 3 + self.device.threads() as u64;
+        let teacher_bytes = teacher
+            .as_ref()
+            .map_or(0, |(teacher, _)| teacher.parameter_bytes());
+        self.device.check_memory(
+            "training state",
+            copies * model.parameter_bytes() + teacher_bytes,
+        )?;
+        let val = val.filter(|val| !val.is_empty());
+        let mut csv = match &self.checkpoint_dir {
+            Some(dir) => Some(CsvMetricsWriter::create(dir)?),
+            None => None,
+        };
+
+        let _train_span = tracing::info_span!(
+            "train",
+            epochs = self.config.num_epochs,
+            examples = train.len()
+        )
+        .entered();
         tracing::info!("Starting training for {} epochs", self.config.num_epochs);
+        let start = Instant::now();
+        let provenance = CheckpointMetadata {
+            dataset_hash: Some(train.content_hash()?),
+            ..CheckpointMetadata::provenance()
+        };
+        let pairs = self.encode(train, tokenizer)?.pairs;
+        let val = val.map(|val| self.encode(val, tokenizer)).transpose()?;
 
-        // TODO: Implement actual training loop
+        let mut optimizer = Optimizer::from_name(&self.config.optimizer)?;
+        optimizer.frozen = self.config.freeze.clone();
+        let mut ema = self.config.ema_decay.map(|decay| Ema::new(model, decay));
+        let mut rng = StdRng::seed_from_u64(self.config.seed);
+        let mut order: Vec<usize> = (0..pairs.len()).collect();
+        let batch_size = self.config.batch_size.max(1);
+        let mut lr = self.config.learning_rate;
+        let curriculum = self
+            .config
+            .curriculum
+            .as_ref()
+            .map(|config| Curriculum::new(config, train, &pairs))
+            .transpose()?;
+        let total_batches: usize = (1..=self.config.num_epochs)
+            .map(|epoch| {
+                curriculum
+                    .as_ref()
+                    .map_or(pairs.len(), |curriculum| curriculum.pool_size(epoch))
+                    .div_ceil(batch_size)
+            })
+            .sum();
+        let bar = progress::bar(total_batches as u64, "Training", self.progress);
 
+        let mut history = Vec::with_capacity(self.config.num_epochs);
+        let mut best_loss = f64::INFINITY;
+        let mut epochs_without_improvement = 0;
+        let mut stopped_early = false;
+        let mut interrupted = false;
+        let mut epoch_step = None;
+        let mut step = 0u64;
+
+        for epoch in 1..=self.config.num_epochs {
+            let _epoch_span = tracing::info_span!("epoch", epoch).entered();
+            match &curriculum {
+                Some(curriculum) if curriculum.is_active(epoch) => {
+                    order = curriculum.order(epoch, &mut rng);
+                    tracing::debug!(
+                        "Curriculum: {} easiest examples of {}",
+                        order.len(),
+                        pairs.len()
+                    );
+                    self.log_scalar(
+                        "train/curriculum_fraction",
+                        step,
+                        curriculum.fraction(epoch),
+                    )?;
+                }
+                _ => {
+                    if order.len() < pairs.len() {
+                        order = (0..pairs.len()).collect();
+                    }
+                    order.shuffle(&mut rng);
+                }
+            }
+            let (mut epoch_nll, mut epoch_tokens) = (0.0, 0);
+            let epoch_start = step;
+            let mut state = TrainerState::new(model, tokenizer, epoch, step, lr);
+            notify(&mut self.callbacks, &mut state, |cb, s| {
+                cb.on_epoch_start(s)
+            })?;
+            lr = state.learning_rate;
+            let mut stop = state.stop;
+
+            for (index, batch) in order.chunks(batch_size).enumerate() {
+                if stop || self.cancelled() {
+                    break;
+                }
+                let batch_span = tracing::debug_span!(
+                    "batch",
+                    batch = index,
+                    size = batch.len(),
+                    tokens = Empty,
+                    loss = Empty,
+                    elapsed_ms = Empty
+                );
+                let _batch_guard = batch_span.enter();
+                let batch_start = Instant::now();
+                let examples: Vec<&(Vec<usize>, Vec<usize>)> = timed(
+                    &tracing::debug_span!("load_batch", elapsed_ms = Empty),
+                    || batch.iter().map(|&index| &pairs[index]).collect(),
+                );
+                let model_ref: &CodeGenerationModel = model;
+                let teacher_ref = teacher.as_ref().map(|(teacher, config)| (teacher, config));
+                let (mut grads, batch_nll, batch_tokens) = timed(
+                    &tracing::debug_span!("forward_backward", elapsed_ms = Empty),
+                    || {
+                        self.device
+                            .install(|| batch_gradients(model_ref, teacher_ref, &examples))
+                    },
+                );
+                grads.scale(1.0 / batch_tokens.max(1) as f32);
+                if let Some(freeze) = &self.config.freeze {
+                    grads.visit_mut(&mut |name, values| {
+                        if freeze.freezes(name) {
+                            values.fill(0.0);
+                        }
+                    });
+                }
+
+                let grad_norm = grads.global_norm();
+                let every = self.config.layer_stats_every;
+                if every > 0
+                    && ((step + 1).is_multiple_of(every)
+                        || !grad_norm.is_finite()
+                        || !batch_nll.is_finite())
+                {
+                    self.log_layer_stats(model, &grads, &examples, step + 1)?;
+                }
+                let clip = self.config.gradient_clip_norm;
+                if clip > 0.0 && grad_norm > clip {
+                    grads.scale((clip / grad_norm) as f32);
+                }
+                timed(
+                    &tracing::debug_span!(
+                        "optimizer_step",
+                        step = step + 1,
+                        grad_norm,
+                        elapsed_ms = Empty
+                    ),
+                    || optimizer.step(model, &grads, lr),
+                );
+                if let Some(ema) = &mut ema {
+                    ema.update(model);
+                }
+                step += 1;
+
+                let batch_loss = batch_nll / batch_tokens.max(1) as f64;
+                batch_span.record("tokens", batch_tokens);
+                batch_span.record("loss", batch_loss);
+                batch_span.record("elapsed_ms", batch_start.elapsed().as_secs_f64() * 1000.0);
+                self.log_scalar("train/loss", step, batch_loss)?;
+                self.log_scalar("train/lr", step, lr)?;
+                self.log_scalar("train/grad_norm", step, grad_norm)?;
+                epoch_nll += batch_nll;
+                epoch_tokens += batch_tokens;
+                bar.set_message(format!(
+                    "epoch {}/{} loss {:.4}",
+                    epoch,
+                    self.config.num_epochs,
+                    epoch_nll / epoch_tokens.max(1) as f64
+                ));
+                bar.inc(1);
+
+                let metrics = BatchMetrics {
+                    batch: index,
+                    loss: batch_loss,
+                    grad_norm,
+                };
+                let mut state = TrainerState::new(model, tokenizer, epoch, step, lr);
+                notify(&mut self.callbacks, &mut state, |cb, s| {
+                    cb.on_batch_end(&metrics, s)
+                })?;
+                (lr, stop) = (state.learning_rate, state.stop);
+            }
+
+            let cancelled = self.cancelled();
+            let train_loss = epoch_nll / epoch_tokens.max(1) as f64;
+            // Validation and checkpoints use the averaged weights when there are any
+            let averaged = ema.as_ref().map(|ema| ema.model(model));
+            let evaluated = averaged.as_ref().unwrap_or(model);
+            let val_loss = match &val {
+                Some(val) if !cancelled => {
+                    let span =
+                        tracing::debug_span!("validate", examples = val.len(), elapsed_ms = Empty);
+                    let result = timed(&span, || encoded_perplexity(evaluated, val))?;
+                    self.log_scalar("val/loss", step, result.nll)?;
+                    self.log_scalar("val/perplexity", step, result.perplexity)?;
+                    Some(result.nll)
+                }
+                _ => None,
+            };
+            for sink in &mut self.metrics {
+                sink.flush()?;
+            }
+            let metrics = EpochMetrics {
+                epoch,
+                step,
+                train_loss,
+                val_loss,
+                learning_rate: lr,
+                elapsed_secs: start.elapsed().as_secs_f64(),
+            };
+
+            if cancelled {
+                let mut metadata = CheckpointMetadata {
+                    epochs: Some(epoch - 1),
+                    final_loss: history.last().map(|m: &EpochMetrics| m.train_loss),
+                    ..provenance.clone()
+                };
+                // Keep the batches of the partial epoch, skipping validation
+                if epoch_tokens > 0 {
+                    if let Some(csv) = &mut csv {
+                        csv.write_epoch(&metrics)?;
+                    }
+                    history.push(metrics);
+                    epoch_step = Some(step - epoch_start);
+                    metadata.epoch_step = epoch_step;
+                    metadata.final_loss = Some(train_loss);
+                    metadata
+                        .metrics
+                        .insert("train_loss".to_string(), train_loss);
+                }
+                let saved =
+                    self.save_checkpoint(evaluated, tokenizer, "interrupted.ckpt", &metadata)?;
+                bar.suspend(|| {
+                    tracing::warn!(
+                        "Training interrupted in epoch {} at step {}{}",
+                        epoch,
+                        step,
+                        saved
+                            .map(|path| format!("; saved {}", path.display()))
+                            .unwrap_or_default()
+                    )
+                });
+                interrupted = true;
+                stopped_early = true;
+                break;
+            }
+
+            bar.suspend(|| {
+                tracing::info!(
+                    "Epoch {}/{}: train loss {:.4}{}",
+                    epoch,
+                    self.config.num_epochs,
+                    train_loss,
+                    val_loss
+                        .map(|loss| format!(", val loss {:.4}", loss))
+                        .unwrap_or_default()
+                )
+            });
+            if let Some(csv) = &mut csv {
+                csv.write_epoch(&metrics)?;
+            }
+            history.push(metrics);
+            let mut state = TrainerState::new(model, tokenizer, epoch, step, lr);
+            notify(&mut self.callbacks, &mut state, |cb, s| {
+                cb.on_epoch_end(&metrics, s)
+            })?;
+            (lr, stop) = (state.learning_rate, stop || state.stop);
+
+            let monitored = val_loss.unwrap_or(train_loss);
+            let mut metadata = CheckpointMetadata {
+                epochs: Some(epoch),
+                final_loss: Some(monitored),
+                ..provenance.clone()
+            };
+            metadata
+                .metrics
+                .insert("train_loss".to_string(), train_loss);
+            if let Some(val_loss) = val_loss {
+                metadata.metrics.insert("val_loss".to_string(), val_loss);
+            }
+            if self.config.save_every > 0 && epoch % self.config.save_every == 0 {
+                let name = format!("epoch-{}.ckpt", epoch);
+                if let Some(path) = self.save_checkpoint(evaluated, tokenizer, &name, &metadata)? {
+                    let mut state = TrainerState::new(model, tokenizer, epoch, step, lr);
+                    notify(&mut self.callbacks, &mut state, |cb, s| {
+                        cb.on_checkpoint(&path, s)
+                    })?;
+                    (lr, stop) = (state.learning_rate, stop || state.stop);
+                }
+            }
+
+            if monitored < best_loss {
+                best_loss = monitored;
+                epochs_without_improvement = 0;
+                if let Some(path) =
+                    self.save_checkpoint(evaluated, tokenizer, "best.ckpt", &metadata)?
+                {
+                    let mut state = TrainerState::new(model, tokenizer, epoch, step, lr);
+                    notify(&mut self.callbacks, &mut state, |cb, s| {
+                        cb.on_checkpoint(&path, s)
+                    })?;
+                    (lr, stop) = (state.learning_rate, stop || state.stop);
+                }
+            } else {
+                epochs_without_improvement += 1;
+                if self.config.early_stopping
+                    && epochs_without_improvement >= self.config.early_stopping_patience
+                {
+                    bar.suspend(|| {
+                        tracing::info!(
+                            "Early stopping after {} epochs without improvement",
+                            epochs_without_improvement
+                        )
+                    });
+                    let mut state = TrainerState::new(model, tokenizer, epoch, step, lr);
+                    notify(&mut self.callbacks, &mut state, |cb, s| {
+                        cb.on_early_stop(&metrics, s)
+                    })?;
+                    stopped_early = true;
+                    break;
+                }
+            }
+            if stop {
+                bar.suspend(|| tracing::info!("Training stopped by a callback"));
+                stopped_early = true;
+                break;
+            }
+        }
+
+        bar.finish_and_clear();
+        if let Some(ema) = &ema {
+            ema.copy_to(model);
+        }
+
+        let final_loss = history
+            .last()
+            .map(|m| m.val_loss.unwrap_or(m.train_loss))
+            .unwrap_or(f64::NAN);
         Ok(TrainingResults {
-            final_loss: 0.1,
-            best_loss: 0.05,
-            epochs_completed: self.config.num_epochs,
-            training_time_secs: 0.0,
+            final_loss: final_loss as f32,
+            best_loss: best_loss as f32,
+            epochs_completed: history.len() - epoch_step.is_some() as usize,
+            epoch_step,
+            training_time_secs: start.elapsed().as_secs_f64(),
+            history,
+            stopped_early,
+            interrupted,
         })
     }
+
+    /// Token ids of `dataset`, from the cache directory when there is one
+    fn encode(
+        &self,
+        dataset: &(dyn ExampleSource + Sync),
+        tokenizer: &WGSLTokenizer,
+    ) -> crate::Result<EncodedDataset> {
+        let span = tracing::info_span!(
+            "tokenize",
+            examples = dataset.len(),
+            tokens = Empty,
+            cached = Empty,
+            elapsed_ms = Empty
+        );
+        let encoded = timed(&span, || match &self.cache_dir {
+            Some(dir) => {
+                let (encoded, hit) = EncodedDataset::load_or_encode(dataset, tokenizer, dir)?;
+                span.record("cached", hit);
+                Ok(encoded)
+            }
+            None => self
+                .device
+                .install(|| EncodedDataset::encode_source(dataset, tokenizer)),
+        })?;
+        span.record("tokens", encoded.token_count());
+        Ok(encoded)
+    }
+
+    /// Write `name` into the checkpoint directory, returning its path if
+    /// there is one
+    fn save_checkpoint(
+        &self,
+        model: &CodeGenerationModel,
+        tokenizer: &WGSLTokenizer,
+        name: &str,
+        metadata: &CheckpointMetadata,
+    ) -> crate::Result<Option<PathBuf>> {
+        let Some(dir) = &self.checkpoint_dir else {
+            return Ok(None);
+        };
+        let path = dir.join(name);
+        let span = tracing::debug_span!("save_checkpoint", name, elapsed_ms = Empty);
+        timed(&span, || {
+            Checkpoint::new(model.clone(), tokenizer.clone())
+                .with_metadata(metadata.clone())
+                .save(&path)
+        })?;
+        Ok(Some(path))
+    }
+
+    /// Log per-layer gradient norms of `grads` and activation statistics of
+    /// the first of `examples`, failing with the first layer of the forward
+    /// pass, or else of the gradients, that holds NaN or infinite values
+    fn log_layer_stats(
+        &mut self,
+        model: &CodeGenerationModel,
+        grads: &Gradients,
+        examples: &[&(Vec<usize>, Vec<usize>)],
+        step: u64,
+    ) -> crate::Result<()> {
+        let gradients = grads.layer_stats();
+        let activations = match examples.first() {
+            Some((input, target)) => model.activation_stats(input, target),
+            None => Vec::new(),
+        };
+        for layer in &gradients {
+            self.log_scalar(&format!("grad_norm/{}", layer.name), step, layer.norm)?;
+        }
+        for layer in &activations {
+            let stats = [
+                ("mean", layer.mean),
+                ("std", layer.std),
+                ("max_abs", layer.max_abs as f64),
+            ];
+            for (stat, value) in stats {
+                self.log_scalar(&format!("activations/{}/{}", layer.name, stat), step, value)?;
+            }
+        }
+        if activations
+            .iter()
+            .chain(&gradients)
+            .all(LayerStats::is_finite)
+        {
+            return Ok(());
+        }
+
+        // Another example of the batch may be the one whose forward pass broke
+        let first_non_finite =
+            |stats: Vec<LayerStats>| stats.into_iter().find(|layer| !layer.is_finite());
+        let diverged = first_non_finite(activations)
+            .or_else(|| {
+                examples.iter().skip(1).find_map(|(input, target)| {
+                    first_non_finite(model.activation_stats(input, target))
+                })
+            })
+            .map(|layer| ("activations", layer))
+            .or_else(|| first_non_finite(gradients).map(|layer| ("gradients", layer)));
+        match diverged {
+            Some((kind, layer)) => Err(crate::Error::Other(format!(
+                "Non-finite {} in layer `{}` at step {}: {} NaN and {} infinite of {} values",
+                kind, layer.name, step, layer.nan_count, layer.inf_count, layer.count
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    fn log_scalar(&mut self, tag: &str, step: u64, value: f64) -> crate::Result<()> {
+        for sink in &mut self.metrics {
+            sink.log_scalar(tag, step, value)?;
+        }
+        Ok(())
+    }
+}
+
+/// Run `hook` on every callback in registration order
+fn notify(
+    callbacks: &mut [Box<dyn TrainerCallback>],
+    state: &mut TrainerState,
+    hook: impl Fn(&mut dyn TrainerCallback, &mut TrainerState) -> crate::Result<()>,
+) -> crate::Result<()> {
+    for callback in callbacks {
+        hook(callback.as_mut(), state)?;
+    }
+    Ok(())
+}
+
+/// Losses recorded at the end of one epoch
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct EpochMetrics {
+    pub epoch: usize,
+    /// Global optimizer step at the end of the epoch
+    pub step: u64,
+    /// Mean per-token NLL over the epoch's batches
+    pub train_loss: f64,
+    /// Mean per-token NLL on the validation set
+    pub val_loss: Option<f64>,
+    pub learning_rate: f64,
+    /// Seconds since training started
+    pub elapsed_secs: f64,
+}
+
+/// Per-token likelihood of a dataset under a model
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Perplexity {
+    /// Number of target tokens scored, including one `<eos>` per example
+    pub tokens: usize,
+    /// Mean negative log likelihood per token (nats)
+    pub nll: f64,
+    /// `exp(nll)`
+    pub perplexity: f64,
+}
+
+/// Perplexity of the reference code in `dataset` given each prompt, computed
+/// with teacher forcing and without touching the model weights
+pub fn perplexity(
+    model: &CodeGenerationModel,
+    tokenizer: &WGSLTokenizer,
+    dataset: &WGSLDataset,
+) -> crate::Result<Perplexity> {
+    encoded_perplexity(model, &EncodedDataset::encode(dataset, tokenizer))
+}
+
+/// [`perplexity`] of an already encoded dataset
+pub fn encoded_perplexity(
+    model: &CodeGenerationModel,
+    dataset: &EncodedDataset,
+) -> crate::Result<Perplexity> {
+    if dataset.is_empty() {
+        return Err(crate::Error::Other(
+            "Cannot compute perplexity of an empty dataset".to_string(),
+        ));
+    }
+
+    let (total_nll, tokens) = dataset
+        .pairs
+        .iter()
+        .map(|(input, target)| model.sequence_nll(input, target))
+        .fold((0.0, 0), |(nll, count), (n, c)| (nll + n, count + c));
+
+    let nll = total_nll / tokens as f64;
+    Ok(Perplexity {
+        tokens,
+        nll,
+        perplexity: nll.exp(),
+    })
+}
+
+/// The teacher model of `distillation`, which must share `tokenizer`'s
+/// vocabulary
+fn load_teacher(
+    distillation: &DistillationConfig,
+    tokenizer: &WGSLTokenizer,
+) -> crate::Result<CodeGenerationModel> {
+    let path = &distillation.teacher;
+    let checkpoint = Checkpoint::load(path)?;
+    if checkpoint.tokenizer.vocab_hash() != tokenizer.vocab_hash() {
+        return Err(crate::Error::ConfigError(format!(
+            "Teacher {} has a different vocabulary; distill with the teacher's tokenizer",
+            path.display()
+        )));
+    }
+    tracing::info!(
+        "Distilling from {} ({} parameters)",
+        path.display(),
+        checkpoint.model.num_parameters()
+    );
+    Ok(checkpoint.model)
+}
+
+/// Summed gradients, hard NLL and target tokens of the `batch` (input,
+/// target) pairs, distilled from `teacher` when given, accumulated in one
+/// contiguous chunk per thread and summed in chunk order
+fn batch_gradients(
+    model: &CodeGenerationModel,
+    teacher: Option<(&CodeGenerationModel, &DistillationConfig)>,
+    batch: &[&(Vec<usize>, Vec<usize>)],
+) -> (Gradients, f64, usize) {
+    let chunk_size = batch.len().div_ceil(rayon::current_num_threads()).max(1);
+    let chunks: Vec<(Gradients, f64, usize)> = batch
+        .par_chunks(chunk_size)
+        .map(|chunk| {
+            let mut grads = model.zero_gradients();
+            let (mut nll, mut tokens) = (0.0, 0);
+            for (input, target) in chunk {
+                let (n, t) = match teacher {
+                    Some((teacher, distillation)) => model.accumulate_distillation_gradients(
+                        teacher,
+                        input,
+                        target,
+                        distillation.alpha as f32,
+                        distillation.temperature as f32,
+                        &mut grads,
+                    ),
+                    None => model.accumulate_gradients(input, target, &mut grads),
+                };
+                nll += n;
+                tokens += t;
+            }
+            (grads, nll, tokens)
+        })
+        .collect();
+
+    let mut chunks = chunks.into_iter();
+    let first = chunks
+        .next()
+        .unwrap_or_else(|| (model.zero_gradients(), 0.0, 0));
+    chunks.fold(first, |(mut grads, nll, tokens), (g, n, t)| {
+        grads.accumulate(&g);
+        (grads, nll + n, tokens + t)
+    })
 }
 
 /// Training results summary
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TrainingResults {
     pub final_loss: f32,
     pub best_loss: f32,
+    /// Epochs trained to the end; a partial last epoch isn't counted
     pub epochs_completed: usize,
+    /// Steps trained of the partial last epoch of an interrupted run
+    #[serde(default)]
+    pub epoch_step: Option<u64>,
     pub training_time_secs: f64,
+    pub history: Vec<EpochMetrics>,
+    pub stopped_early: bool,
+    /// Stopped by a [`CancellationToken`]; the last epoch of `history` may
+    /// be partial
+    #[serde(default)]
+    pub interrupted: bool,
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::config::TrainingConfig;
+    use crate::test_support::tiny_model;
 
     #[test]
     fn test_trainer_creation() {
@@ -54,9 +800,753 @@ mod tests {
             early_stopping_patience: 10,
             gradient_clip_norm: 1.0,
             save_every: 5,
+            metrics: None,
+            seed: 42,
+            curriculum: None,
+            layer_stats_every: 0,
+            ema_decay: None,
+            freeze: None,
+            distillation: None,
+            preview: None,
         };
 
         let trainer = Trainer::new(config);
         assert_eq!(trainer.config.num_epochs, 10);
     }
+
+    #[test]
+    fn test_perplexity() {
+        use crate::dataset::WGSLExample;
+        use crate::model::ModelArchitecture;
+
+        let mut dataset = WGSLDataset::new();
+        dataset
+            .examples
+            .push(WGSLExample::new("empty main", "fn main() { }"));
+        dataset.examples.push(WGSLExample::new(
+            "return one",
+            "fn f() -> f32 { return 1.0; }",
+        ));
+        let mut tokenizer = WGSLTokenizer::new(64, false);
+        tokenizer.fit(
+            &dataset
+                .examples
+                .iter()
+                .map(|e| e.wgsl_code.clone())
+                .collect::<Vec<_>>(),
+            1,
+        );
+
+        // An untrained LSTM has uniform logits, so perplexity is the vocab size
+        let model = CodeGenerationModel::new(
+            ModelArchitecture::LSTM,
+            tokenizer.vocab_size(),
+            16,
+            2,
+            1,
+            None,
+            None,
+        );
+        let result = perplexity(&model, &tokenizer, &dataset).unwrap();
+        let code_tokens: usize = dataset
+            .examples
+            .iter()
+            .map(|e| tokenizer.tokenize(&e.wgsl_code).len() + 1)
+            .sum();
+        assert_eq!(result.tokens, code_tokens);
+        assert!((result.perplexity - tokenizer.vocab_size() as f64).abs() < 1e-6);
+
+        assert!(perplexity(&model, &tokenizer, &WGSLDataset::new()).is_err());
+    }
+
+    #[test]
+    fn test_train_reduces_loss() {
+        use crate::config::MetricsFormat;
+        use crate::dataset::WGSLExample;
+        use crate::model::ModelArchitecture;
+
+        let mut dataset = WGSLDataset::new();
+        dataset
+            .examples
+            .push(WGSLExample::new("empty main", "fn main() { }"));
+        dataset.examples.push(WGSLExample::new(
+            "return one",
+            "fn f() -> f32 { return 1.0; }",
+        ));
+        let mut tokenizer = WGSLTokenizer::new(64, false);
+        let texts: Vec<String> = dataset
+            .examples
+            .iter()
+            .flat_map(|e| [e.natural_language.clone(), e.wgsl_code.clone()])
+            .collect();
+        tokenizer.fit(&texts, 1);
+        let mut model = tiny_model(&tokenizer);
+
+        let dir = tempfile::tempdir().unwrap();
+        let sink = create_sink(MetricsFormat::Json, &dir.path().join("logs")).unwrap();
+        let mut trainer = Trainer::new(TrainingConfig {
+            num_epochs: 6,
+            batch_size: 2,
+            learning_rate: 0.01,
+            optimizer: "adamw".to_string(),
+            early_stopping: true,
+            early_stopping_patience: 3,
+            gradient_clip_norm: 1.0,
+            save_every: 3,
+            metrics: Some(MetricsFormat::Json),
+            seed: 42,
+            curriculum: None,
+            layer_stats_every: 0,
+            ema_decay: None,
+            freeze: None,
+            distillation: None,
+            preview: None,
+        })
+        .with_metrics(sink)
+        .with_checkpoint_dir(dir.path().join("checkpoints"));
+
+        let results = trainer
+            .train(&mut model, &tokenizer, &dataset, Some(&dataset))
+            .unwrap();
+        assert_eq!(results.epochs_completed, 6);
+        let first = results.history.first().unwrap();
+        let last = results.history.last().unwrap();
+        assert!(last.train_loss < first.train_loss);
+        assert!(last.val_loss.unwrap() < first.val_loss.unwrap());
+        assert!(dir.path().join("checkpoints/epoch-3.ckpt").exists());
+        assert!(dir.path().join("checkpoints/best.ckpt").exists());
+
+        let csv = std::fs::read_to_string(dir.path().join("checkpoints/metrics.csv")).unwrap();
+        let rows: Vec<&str> = csv.lines().collect();
+        assert_eq!(rows[0], "epoch,step,train_loss,val_loss,lr,time");
+        assert_eq!(rows.len(), 7);
+        assert!(rows[6].starts_with("6,6,"));
+
+        let scalars = std::fs::read_to_string(dir.path().join("logs/scalars.jsonl")).unwrap();
+        // Three scalars per step plus two per epoch
+        assert_eq!(scalars.lines().count(), 6 * 3 + 6 * 2);
+        assert!(scalars.contains("\"val/perplexity\""));
+
+        // LSTM models have nothing to optimize
+        let mut lstm = CodeGenerationModel::new(ModelArchitecture::LSTM, 32, 16, 2, 1, None, None);
+        assert!(trainer
+            .train(&mut lstm, &tokenizer, &dataset, None)
+            .is_err());
+    }
+
+    #[test]
+    fn test_cancellation() {
+        use crate::dataset::WGSLExample;
+
+        struct CancelAt(u64, CancellationToken);
+
+        impl TrainerCallback for CancelAt {
+            fn on_batch_end(
+                &mut self,
+                _metrics: &BatchMetrics,
+                state: &mut TrainerState,
+            ) -> crate::Result<()> {
+                if state.step == self.0 {
+                    self.1.cancel();
+                }
+                Ok(())
+            }
+        }
+
+        let mut dataset = WGSLDataset::new();
+        for i in 0..4 {
+            dataset.examples.push(WGSLExample::new(
+                format!("return {}", i),
+                format!("fn f() -> f32 {{ return {}.0; }}", i),
+            ));
+        }
+        let mut tokenizer = WGSLTokenizer::new(64, false);
+        let texts: Vec<String> = dataset
+            .examples
+            .iter()
+            .flat_map(|e| [e.natural_language.clone(), e.wgsl_code.clone()])
+            .collect();
+        tokenizer.fit(&texts, 1);
+        let mut model = tiny_model(&tokenizer);
+
+        let dir = tempfile::tempdir().unwrap();
+        let token = CancellationToken::new();
+        let mut trainer = Trainer::new(TrainingConfig {
+            num_epochs: 3,
+            batch_size: 1,
+            learning_rate: 0.01,
+            optimizer: "adam".to_string(),
+            early_stopping: false,
+            early_stopping_patience: 10,
+            gradient_clip_norm: 1.0,
+            save_every: 0,
+            metrics: None,
+            seed: 7,
+            curriculum: None,
+            layer_stats_every: 0,
+            ema_decay: None,
+            freeze: None,
+            distillation: None,
+            preview: None,
+        })
+        .with_checkpoint_dir(dir.path())
+        .with_callback(Box::new(CancelAt(6, token.clone())))
+        .with_cancellation(token);
+
+        let results = trainer
+            .train(&mut model, &tokenizer, &dataset, Some(&dataset))
+            .unwrap();
+        assert!(results.interrupted && results.stopped_early);
+        assert_eq!(results.history.len(), 2);
+        assert_eq!((results.epochs_completed, results.epoch_step), (1, Some(2)));
+        let partial = results.history[1];
+        assert_eq!((partial.epoch, partial.step), (2, 6));
+        assert_eq!(partial.val_loss, None);
+
+        // The interrupted epoch isn't counted as trained
+        let checkpoint = Checkpoint::load(dir.path().join("interrupted.ckpt")).unwrap();
+        assert_eq!(checkpoint.metadata.epochs, Some(1));
+        assert_eq!(checkpoint.metadata.epoch_step, Some(2));
+        assert_eq!(checkpoint.metadata.final_loss, Some(partial.train_loss));
+        let csv = std::fs::read_to_string(dir.path().join("metrics.csv")).unwrap();
+        assert!(csv.lines().last().unwrap().starts_with("2,6,"));
+    }
+
+    #[test]
+    fn test_callbacks() {
+        use crate::dataset::WGSLExample;
+        use crate::model::ModelArchitecture;
+        use std::sync::{Arc, Mutex};
+
+        struct Recorder(Arc<Mutex<Vec<String>>>);
+
+        impl TrainerCallback for Recorder {
+            fn on_epoch_start(&mut self, state: &mut TrainerState) -> crate::Result<()> {
+                self.0
+                    .lock()
+                    .unwrap()
+                    .push(format!("start {}", state.epoch));
+                Ok(())
+            }
+
+            fn on_batch_end(
+                &mut self,
+                batch: &BatchMetrics,
+                state: &mut TrainerState,
+            ) -> crate::Result<()> {
+                assert!(batch.loss.is_finite());
+                self.0.lock().unwrap().push(format!("batch {}", state.step));
+                Ok(())
+            }
+
+            fn on_epoch_end(
+                &mut self,
+                metrics: &EpochMetrics,
+                state: &mut TrainerState,
+            ) -> crate::Result<()> {
+                self.0
+                    .lock()
+                    .unwrap()
+                    .push(format!("end {}", metrics.epoch));
+                state.learning_rate /= 2.0;
+                state.stop = metrics.epoch == 2;
+                Ok(())
+            }
+
+            fn on_checkpoint(
+                &mut self,
+                path: &std::path::Path,
+                _state: &mut TrainerState,
+            ) -> crate::Result<()> {
+                let name = path.file_name().unwrap().to_string_lossy();
+                self.0.lock().unwrap().push(format!("checkpoint {}", name));
+                Ok(())
+            }
+        }
+
+        let mut dataset = WGSLDataset::new();
+        dataset
+            .examples
+            .push(WGSLExample::new("empty main", "fn main() { }"));
+        let mut tokenizer = WGSLTokenizer::new(32, false);
+        tokenizer.fit(&["empty main".to_string(), "fn main() { }".to_string()], 1);
+        let mut model = CodeGenerationModel::new(
+            ModelArchitecture::Transformer,
+            tokenizer.vocab_size(),
+            8,
+            2,
+            1,
+            Some(16),
+            Some(16),
+        );
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let dir = tempfile::tempdir().unwrap();
+        let mut trainer = Trainer::new(TrainingConfig {
+            num_epochs: 5,
+            batch_size: 1,
+            learning_rate: 0.01,
+            optimizer: "adam".to_string(),
+            early_stopping: false,
+            early_stopping_patience: 10,
+            gradient_clip_norm: 1.0,
+            save_every: 0,
+            metrics: None,
+            seed: 42,
+            curriculum: None,
+            layer_stats_every: 0,
+            ema_decay: None,
+            freeze: None,
+            distillation: None,
+            preview: None,
+        })
+        .with_callback(Box::new(Recorder(Arc::clone(&events))))
+        .with_callback(Box::new(
+            SamplePreview::new(vec!["empty main".to_string()], 2)
+                .with_output_dir(dir.path().join("samples")),
+        ))
+        .with_checkpoint_dir(dir.path());
+
+        let results = trainer
+            .train(&mut model, &tokenizer, &dataset, None)
+            .unwrap();
+        assert!(results.stopped_early);
+        assert_eq!(results.epochs_completed, 2);
+        assert_eq!(results.history[0].learning_rate, 0.01);
+        assert_eq!(results.history[1].learning_rate, 0.005);
+        assert_eq!(
+            events.lock().unwrap()[..4],
+            ["start 1", "batch 1", "end 1", "checkpoint best.ckpt"]
+        );
+        assert_eq!(
+            events.lock().unwrap()[4..7],
+            ["start 2", "batch 2", "end 2"]
+        );
+        assert!(!dir.path().join("samples/epoch-1.md").exists());
+        let samples = std::fs::read_to_string(dir.path().join("samples/epoch-2.md")).unwrap();
+        assert!(samples.starts_with("# Epoch 2 samples\n\n## empty main\n"));
+    }
+
+    #[test]
+    fn test_layer_stats() {
+        use crate::config::MetricsFormat;
+        use crate::dataset::WGSLExample;
+        use crate::model::ModelArchitecture;
+
+        let mut dataset = WGSLDataset::new();
+        dataset
+            .examples
+            .push(WGSLExample::new("empty main", "fn main() { }"));
+        let mut tokenizer = WGSLTokenizer::new(32, false);
+        tokenizer.fit(&["empty main".to_string(), "fn main() { }".to_string()], 1);
+        let mut model = CodeGenerationModel::new(
+            ModelArchitecture::Transformer,
+            tokenizer.vocab_size(),
+            8,
+            2,
+            1,
+            Some(16),
+            Some(16),
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        let sink = create_sink(MetricsFormat::Json, dir.path()).unwrap();
+        let mut trainer = Trainer::new(TrainingConfig {
+            num_epochs: 2,
+            batch_size: 1,
+            learning_rate: 0.01,
+            optimizer: "adam".to_string(),
+            early_stopping: false,
+            early_stopping_patience: 10,
+            gradient_clip_norm: 1.0,
+            save_every: 0,
+            metrics: None,
+            seed: 42,
+            curriculum: None,
+            layer_stats_every: 2,
+            ema_decay: None,
+            freeze: None,
+            distillation: None,
+            preview: None,
+        })
+        .with_metrics(sink);
+        trainer
+            .train(&mut model, &tokenizer, &dataset, None)
+            .unwrap();
+        let scalars = std::fs::read_to_string(dir.path().join("scalars.jsonl")).unwrap();
+        let tagged = |tag: &str| {
+            scalars
+                .lines()
+                .filter(|line| line.contains(&format!("\"{}\"", tag)))
+                .count()
+        };
+        assert_eq!(tagged("grad_norm/encoder.0"), 1);
+        assert_eq!(tagged("activations/output/std"), 1);
+
+        model.visit_parameters_mut(&mut |name, values| {
+            if name == "decoder.0.self_attn.w_v" {
+                values[0] = f32::NAN;
+            }
+        });
+        let error = trainer
+            .train(&mut model, &tokenizer, &dataset, None)
+            .unwrap_err()
+            .to_string();
+        assert!(
+            error.starts_with("Non-finite activations in layer `decoder.0` at step 1"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn test_ema_weights_are_checkpointed() {
+        use crate::dataset::WGSLExample;
+        use crate::model::ModelArchitecture;
+
+        let mut dataset = WGSLDataset::new();
+        dataset
+            .examples
+            .push(WGSLExample::new("empty main", "fn main() { }"));
+        let mut tokenizer = WGSLTokenizer::new(32, false);
+        tokenizer.fit(&["empty main".to_string(), "fn main() { }".to_string()], 1);
+        let mut model = CodeGenerationModel::new(
+            ModelArchitecture::Transformer,
+            tokenizer.vocab_size(),
+            8,
+            2,
+            1,
+            Some(16),
+            Some(16),
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        let config = TrainingConfig {
+            num_epochs: 3,
+            batch_size: 1,
+            learning_rate: 0.01,
+            optimizer: "adam".to_string(),
+            early_stopping: false,
+            early_stopping_patience: 10,
+            gradient_clip_norm: 1.0,
+            save_every: 3,
+            metrics: None,
+            seed: 42,
+            curriculum: None,
+            layer_stats_every: 0,
+            ema_decay: Some(0.9),
+            freeze: None,
+            distillation: None,
+            preview: None,
+        };
+        let mut raw = model.clone();
+        Trainer::new(TrainingConfig {
+            ema_decay: None,
+            freeze: None,
+            distillation: None,
+            preview: None,
+            ..config.clone()
+        })
+        .train(&mut raw, &tokenizer, &dataset, None)
+        .unwrap();
+        Trainer::new(config)
+            .with_checkpoint_dir(dir.path())
+            .train(&mut model, &tokenizer, &dataset, None)
+            .unwrap();
+
+        let weights = |model: &CodeGenerationModel| {
+            let mut weights = Vec::new();
+            model.visit_parameters(&mut |_, values| weights.extend_from_slice(values));
+            weights
+        };
+        let checkpoint = Checkpoint::load(dir.path().join("epoch-3.ckpt")).unwrap();
+        assert_eq!(weights(&checkpoint.model), weights(&model));
+        assert_ne!(weights(&model), weights(&raw));
+    }
+
+    #[test]
+    fn test_frozen_parameters_stay_fixed() {
+        use crate::config::FreezeConfig;
+        use crate::dataset::WGSLExample;
+        use crate::model::ModelArchitecture;
+
+        let mut dataset = WGSLDataset::new();
+        dataset
+            .examples
+            .push(WGSLExample::new("empty main", "fn main() { }"));
+        let mut tokenizer = WGSLTokenizer::new(32, false);
+        tokenizer.fit(&["empty main".to_string(), "fn main() { }".to_string()], 1);
+        let base = CodeGenerationModel::new(
+            ModelArchitecture::Transformer,
+            tokenizer.vocab_size(),
+            8,
+            2,
+            2,
+            Some(16),
+            Some(16),
+        );
+        let freeze = FreezeConfig {
+            embeddings: true,
+            encoder: false,
+            layers: 1,
+        };
+        let config = TrainingConfig {
+            num_epochs: 2,
+            batch_size: 1,
+            learning_rate: 0.01,
+            optimizer: "adamw".to_string(),
+            early_stopping: false,
+            early_stopping_patience: 10,
+            gradient_clip_norm: 1.0,
+            save_every: 0,
+            metrics: None,
+            seed: 42,
+            curriculum: None,
+            layer_stats_every: 0,
+            ema_decay: None,
+            freeze: Some(freeze.clone()),
+            distillation: None,
+            preview: None,
+        };
+        let mut model = base.clone();
+        Trainer::new(config)
+            .train(&mut model, &tokenizer, &dataset, None)
+            .unwrap();
+
+        let mut before = Vec::new();
+        base.visit_parameters(&mut |name, values| before.push((name.to_string(), values.to_vec())));
+        let mut index = 0;
+        model.visit_parameters(&mut |name, values| {
+            let unchanged = before[index].1 == values;
+            assert_eq!(unchanged, freeze.freezes(name), "{}", name);
+            index += 1;
+        });
+    }
+
+    #[test]
+    fn test_distillation() {
+        use crate::config::DistillationConfig;
+        use crate::dataset::WGSLExample;
+        use crate::model::ModelArchitecture;
+
+        let mut dataset = WGSLDataset::new();
+        dataset
+            .examples
+            .push(WGSLExample::new("empty main", "fn main() { }"));
+        let mut tokenizer = WGSLTokenizer::new(32, false);
+        tokenizer.fit(&["empty main".to_string(), "fn main() { }".to_string()], 1);
+        let new_model = |d_model: usize, layers: usize| {
+            CodeGenerationModel::new(
+                ModelArchitecture::Transformer,
+                tokenizer.vocab_size(),
+                d_model,
+                2,
+                layers,
+                Some(16),
+                Some(16),
+            )
+        };
+
+        let dir = tempfile::tempdir().unwrap();
+        let teacher_path = dir.path().join("teacher.ckpt");
+        Checkpoint::new(new_model(16, 2), tokenizer.clone())
+            .save(&teacher_path)
+            .unwrap();
+        let mut trainer = Trainer::new(TrainingConfig {
+            num_epochs: 3,
+            batch_size: 1,
+            learning_rate: 0.01,
+            optimizer: "adam".to_string(),
+            early_stopping: false,
+            early_stopping_patience: 10,
+            gradient_clip_norm: 1.0,
+            save_every: 0,
+            metrics: None,
+            seed: 42,
+            curriculum: None,
+            layer_stats_every: 0,
+            ema_decay: None,
+            freeze: None,
+            distillation: Some(DistillationConfig {
+                teacher: teacher_path,
+                alpha: 0.5,
+                temperature: 2.0,
+            }),
+            preview: None,
+        });
+        let mut student = new_model(8, 1);
+        let results = trainer
+            .train(&mut student, &tokenizer, &dataset, None)
+            .unwrap();
+        let losses: Vec<f64> = results.history.iter().map(|m| m.train_loss).collect();
+        assert!(losses[2] < losses[0], "{:?}", losses);
+
+        let mut other = WGSLTokenizer::new(32, false);
+        other.fit(&["fn f() { }".to_string()], 1);
+        let error = trainer
+            .train(&mut new_model(8, 1), &other, &dataset, None)
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("different vocabulary"), "{}", error);
+    }
+
+    #[test]
+    fn test_same_seed_same_weights() {
+        use crate::dataset::WGSLExample;
+        use crate::model::ModelArchitecture;
+
+        let mut dataset = WGSLDataset::new();
+        for i in 0..4 {
+            dataset.examples.push(WGSLExample::new(
+                format!("shader {}", i),
+                format!("fn f() -> f32 {{ return {}.0; }}", i),
+            ));
+        }
+        let mut tokenizer = WGSLTokenizer::new(32, false);
+        let texts: Vec<&str> = dataset
+            .examples
+            .iter()
+            .flat_map(|e| [e.natural_language.as_str(), e.wgsl_code.as_str()])
+            .collect();
+        tokenizer.fit(&texts, 1);
+
+        let device = |threads: usize, memory_limit_mb: Option<u64>| {
+            Device::from_config(&crate::config::DeviceConfig {
+                threads,
+                memory_limit_mb,
+            })
+            .unwrap()
+        };
+        let train_on = |seed: u64, device: Device| {
+            let mut model = CodeGenerationModel::new(
+                ModelArchitecture::Transformer,
+                tokenizer.vocab_size(),
+                8,
+                2,
+                1,
+                Some(16),
+                Some(16),
+            )
+            .with_seed(seed);
+            Trainer::new(TrainingConfig {
+                num_epochs: 2,
+                batch_size: 2,
+                learning_rate: 0.01,
+                optimizer: "adam".to_string(),
+                early_stopping: false,
+                early_stopping_patience: 10,
+                gradient_clip_norm: 1.0,
+                save_every: 0,
+                metrics: None,
+                seed,
+                curriculum: None,
+                layer_stats_every: 0,
+                ema_decay: None,
+                freeze: None,
+                distillation: None,
+                preview: None,
+            })
+            .with_device(device)
+            .train(&mut model, &tokenizer, &dataset, None)
+            .map(|_| {
+                let mut weights = Vec::new();
+                model.visit_parameters(&mut |_, values| weights.extend_from_slice(values));
+                weights
+            })
+        };
+        let train = |seed: u64| train_on(seed, Device::cpu()).unwrap();
+
+        assert_eq!(train(7), train(7));
+        assert_ne!(train(7), train(8));
+
+        // Splitting batches over threads only changes rounding
+        let serial = train_on(7, device(1, None)).unwrap();
+        let parallel = train_on(7, device(2, None)).unwrap();
+        assert!(serial
+            .iter()
+            .zip(&parallel)
+            .all(|(a, b)| (a - b).abs() < 1e-4));
+        assert!(train_on(7, device(1, Some(1))).is_ok());
+    }
+
+    #[test]
+    fn test_train_from_lazy_dataset() {
+        use crate::config::{CurriculumConfig, CurriculumMetric};
+        use crate::dataset::{lazy::LazyDataset, WGSLExample};
+        use crate::model::ModelArchitecture;
+
+        let mut dataset = WGSLDataset::new();
+        for i in 0..4 {
+            dataset.examples.push(WGSLExample {
+                tags: vec![if i % 2 == 0 { "even" } else { "odd" }.to_string()],
+                ..WGSLExample::new(
+                    format!("shader {}", i),
+                    format!("fn f() -> f32 {{ return {}.0; }}", i),
+                )
+            });
+        }
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("train.jsonl");
+        dataset.to_jsonl(&path).unwrap();
+        let lazy = LazyDataset::open(&path).unwrap();
+        let mut tokenizer = WGSLTokenizer::new(32, false);
+        let texts: Vec<&str> = dataset
+            .examples
+            .iter()
+            .flat_map(|e| [e.natural_language.as_str(), e.wgsl_code.as_str()])
+            .collect();
+        tokenizer.fit(&texts, 1);
+
+        let train_on = |source: &(dyn ExampleSource + Sync)| {
+            let mut model = CodeGenerationModel::new(
+                ModelArchitecture::Transformer,
+                tokenizer.vocab_size(),
+                8,
+                2,
+                1,
+                Some(16),
+                Some(16),
+            )
+            .with_seed(3);
+            Trainer::new(TrainingConfig {
+                num_epochs: 2,
+                batch_size: 2,
+                learning_rate: 0.01,
+                optimizer: "adam".to_string(),
+                early_stopping: false,
+                early_stopping_patience: 10,
+                gradient_clip_norm: 1.0,
+                save_every: 0,
+                metrics: None,
+                seed: 3,
+                curriculum: Some(CurriculumConfig {
+                    by: CurriculumMetric::Tags,
+                    tags: vec!["even".to_string()],
+                    ..CurriculumConfig::default()
+                }),
+                layer_stats_every: 0,
+                ema_decay: None,
+                freeze: None,
+                distillation: None,
+                preview: None,
+            })
+            .with_cache_dir(dir.path().join("cache"))
+            .train(&mut model, &tokenizer, source, None)
+            .unwrap();
+            let mut weights = Vec::new();
+            model.visit_parameters(&mut |_, values| weights.extend_from_slice(values));
+            weights
+        };
+
+        assert_eq!(
+            ExampleSource::content_hash(&lazy).unwrap(),
+            dataset.content_hash()
+        );
+        // Both sources hash alike, so the second run hits the first run's cache
+        assert_eq!(train_on(&lazy), train_on(&dataset));
+        assert_eq!(
+            std::fs::read_dir(dir.path().join("cache")).unwrap().count(),
+            1
+        );
+    }
 }
@@ -0,0 +1,173 @@
+//! Parameter update rules used by the trainer
+
+use crate::config::FreezeConfig;
+use crate::model::{CodeGenerationModel, Gradients};
+use std::str::FromStr;
+
+/// Update rule selected by `training.optimizer`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OptimizerKind {
+    Sgd,
+    Adam,
+    /// Adam with decoupled weight decay
+    AdamW,
+}
+
+impl FromStr for OptimizerKind {
+    type Err = crate::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "sgd" => Ok(OptimizerKind::Sgd),
+            "adam" => Ok(OptimizerKind::Adam),
+            "adamw" => Ok(OptimizerKind::AdamW),
+            other => Err(crate::Error::ConfigError(format!(
+                "Unknown optimizer '{}'. Must be one of: sgd, adam, adamw",
+                other
+            ))),
+        }
+    }
+}
+
+/// Optimizer state for one model
+#[derive(Debug, Clone)]
+pub struct Optimizer {
+    pub kind: OptimizerKind,
+    pub beta1: f32,
+    pub beta2: f32,
+    pub eps: f32,
+    /// Decoupled weight decay applied by AdamW to weight matrices
+    pub weight_decay: f32,
+    /// Parameters never updated, not even by weight decay
+    pub frozen: Option<FreezeConfig>,
+    steps: i32,
+    first_moment: Vec<Vec<f32>>,
+    second_moment: Vec<Vec<f32>>,
+}
+
+impl Optimizer {
+    /// Optimizer with the usual Adam defaults
+    pub fn new(kind: OptimizerKind) -> Self {
+        Self {
+            kind,
+            beta1: 0.9,
+            beta2: 0.999,
+            eps: 1e-8,
+            weight_decay: 0.01,
+            frozen: None,
+            steps: 0,
+            first_moment: Vec::new(),
+            second_moment: Vec::new(),
+        }
+    }
+
+    /// Optimizer named in the training config
+    pub fn from_name(name: &str) -> crate::Result<Self> {
+        Ok(Self::new(name.parse()?))
+    }
+
+    /// Apply one update of `grads` to `model` with learning rate `lr`
+    pub fn step(&mut self, model: &mut CodeGenerationModel, grads: &Gradients, lr: f64) {
+        let mut gradients: Vec<Vec<f32>> = Vec::new();
+        grads.visit(&mut |_, values| gradients.push(values.to_vec()));
+        if self.first_moment.is_empty() && self.kind != OptimizerKind::Sgd {
+            self.first_moment = gradients.iter().map(|g| vec![0.0; g.len()]).collect();
+            self.second_moment = self.first_moment.clone();
+        }
+
+        self.steps += 1;
+        let lr = lr as f32;
+        let correction1 = 1.0 - self.beta1.powi(self.steps);
+        let correction2 = 1.0 - self.beta2.powi(self.steps);
+        let mut index = 0;
+        model.visit_parameters_mut(&mut |name, params| {
+            if self
+                .frozen
+                .as_ref()
+                .is_some_and(|frozen| frozen.freezes(name))
+            {
+                index += 1;
+                return;
+            }
+            let grad = &gradients[index];
+            match self.kind {
+                OptimizerKind::Sgd => {
+                    for (p, g) in params.iter_mut().zip(grad) {
+                        *p -= lr * g;
+                    }
+                }
+                OptimizerKind::Adam | OptimizerKind::AdamW => {
+                    let decay = if self.kind == OptimizerKind::AdamW && decays(name) {
+                        lr * self.weight_decay
+                    } else {
+                        0.0
+                    };
+                    let m = &mut self.first_moment[index];
+                    let v = &mut self.second_moment[index];
+                    for i in 0..params.len() {
+                        m[i] = self.beta1 * m[i] + (1.0 - self.beta1) * grad[i];
+                        v[i] = self.beta2 * v[i] + (1.0 - self.beta2) * grad[i] * grad[i];
+                        let m_hat = m[i] / correction1;
+                        let v_hat = v[i] / correction2;
+                        params[i] -= lr * m_hat / (v_hat.sqrt() + self.eps) + decay * params[i];
+                    }
+                }
+            }
+            index += 1;
+        });
+    }
+}
+
+/// Weight decay skips biases and layer norm parameters
+fn decays(name: &str) -> bool {
+    let last = name.rsplit('.').next().unwrap_or(name);
+    !(last == "bias" || last == "gamma" || last == "beta" || last.starts_with("b_"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::ModelArchitecture;
+
+    #[test]
+    fn test_optimizer_names() {
+        assert_eq!(
+            "AdamW".parse::<OptimizerKind>().unwrap(),
+            OptimizerKind::AdamW
+        );
+        assert_eq!("sgd".parse::<OptimizerKind>().unwrap(), OptimizerKind::Sgd);
+        assert!("rmsprop".parse::<OptimizerKind>().is_err());
+        assert!(decays("encoder.0.self_attn.w_q"));
+        assert!(!decays("encoder.0.self_attn.b_q"));
+        assert!(!decays("decoder.0.norm1.gamma"));
+    }
+
+    #[test]
+    fn test_step_reduces_loss() {
+        let (input, target) = ([5, 6, 7], [8, 9, 10]);
+        for kind in [
+            OptimizerKind::Sgd,
+            OptimizerKind::Adam,
+            OptimizerKind::AdamW,
+        ] {
+            let mut model = CodeGenerationModel::new(
+                ModelArchitecture::Transformer,
+                16,
+                8,
+                2,
+                1,
+                Some(12),
+                Some(8),
+            );
+            let mut optimizer = Optimizer::new(kind);
+            let (before, _) = model.sequence_nll(&input, &target);
+            for _ in 0..5 {
+                let mut grads = model.zero_gradients();
+                model.accumulate_gradients(&input, &target, &mut grads);
+                optimizer.step(&mut model, &grads, 0.01);
+            }
+            let (after, _) = model.sequence_nll(&input, &target);
+            assert!(after < before, "{:?}: {} -> {}", kind, before, after);
+        }
+    }
+}
@@ -0,0 +1,208 @@
+//! Per-run output directories
+//!
+//! Every training run gets its own timestamped directory, so consecutive
+//! runs keep their checkpoints, logs and samples side by side:
+//!
+//! ```text
+//! checkpoints/runs/20261016-093012-wgsl_generation/
+//!     config.toml     resolved configuration the run used
+//!     checkpoints/    epoch-N.ckpt, best.ckpt, metrics.csv
+//!     logs/           metrics sinks
+//!     samples/        sample previews
+//!     model.ckpt      final checkpoint
+//!     results.json    training results summary
+//! ```
+
+use super::TrainingResults;
+use crate::config::{Config, PathsConfig};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Directory under `paths.checkpoint_path` runs are created in
+pub const RUNS_DIR: &str = "runs";
+
+/// Creates and lists run directories under a root
+#[derive(Debug, Clone)]
+pub struct RunManager {
+    root: PathBuf,
+}
+
+impl RunManager {
+    /// Manage runs under `root`
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// Manage runs under `paths.checkpoint_path`/[`RUNS_DIR`]
+    pub fn from_paths(paths: &PathsConfig) -> Self {
+        Self::new(paths.checkpoint_path.join(RUNS_DIR))
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Create a run named after `name`, holding a copy of `config`
+    pub fn create(&self, name: &str, config: &Config) -> crate::Result<Run> {
+        self.create_at(SystemTime::now(), name, config)
+    }
+
+    fn create_at(&self, time: SystemTime, name: &str, config: &Config) -> crate::Result<Run> {
+        std::fs::create_dir_all(&self.root)?;
+        let base = format!("{}-{}", timestamp(time), slug(name));
+        // Runs started within the same second get a counter
+        let mut id = base.clone();
+        let mut counter = 1;
+        let dir = loop {
+            let dir = self.root.join(&id);
+            match std::fs::create_dir(&dir) {
+                Ok(()) => break dir,
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                    counter += 1;
+                    id = format!("{}-{}", base, counter);
+                }
+                Err(e) => return Err(e.into()),
+            }
+        };
+
+        let run = Run { id, dir };
+        for dir in [run.checkpoints_dir(), run.logs_dir(), run.samples_dir()] {
+            std::fs::create_dir_all(dir)?;
+        }
+        config.to_file(run.config_path())?;
+        Ok(run)
+    }
+
+    /// Every run, oldest first
+    pub fn list(&self) -> crate::Result<Vec<Run>> {
+        if !self.root.exists() {
+            return Ok(Vec::new());
+        }
+        let mut runs = Vec::new();
+        for entry in std::fs::read_dir(&self.root)? {
+            let entry = entry?;
+            if entry.file_type()?.is_dir() {
+                runs.push(Run {
+                    id: entry.file_name().to_string_lossy().into_owned(),
+                    dir: entry.path(),
+                });
+            }
+        }
+        // Ids start with the timestamp, so they sort by creation time
+        runs.sort_by(|a, b| a.id.cmp(&b.id));
+        Ok(runs)
+    }
+
+    /// The most recently created run
+    pub fn latest(&self) -> crate::Result<Option<Run>> {
+        Ok(self.list()?.pop())
+    }
+}
+
+/// One run's directory and the files in it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Run {
+    /// `<timestamp>-<name>`, unique under the manager's root
+    pub id: String,
+    pub dir: PathBuf,
+}
+
+impl Run {
+    /// Epoch and best checkpoints, and `metrics.csv`
+    pub fn checkpoints_dir(&self) -> PathBuf {
+        self.dir.join("checkpoints")
+    }
+
+    /// Output of metrics sinks
+    pub fn logs_dir(&self) -> PathBuf {
+        self.dir.join("logs")
+    }
+
+    /// Sample previews
+    pub fn samples_dir(&self) -> PathBuf {
+        self.dir.join("samples")
+    }
+
+    /// Resolved configuration of the run
+    pub fn config_path(&self) -> PathBuf {
+        self.dir.join("config.toml")
+    }
+
+    /// Final checkpoint
+    pub fn model_path(&self) -> PathBuf {
+        self.dir.join("model.ckpt")
+    }
+
+    pub fn results_path(&self) -> PathBuf {
+        self.dir.join("results.json")
+    }
+
+    /// Write the training results summary
+    pub fn write_results(&self, results: &TrainingResults) -> crate::Result<()> {
+        std::fs::write(self.results_path(), serde_json::to_string_pretty(results)?)?;
+        Ok(())
+    }
+}
+
+/// `time` in UTC as `YYYYMMDD-HHMMSS`
+fn timestamp(time: SystemTime) -> String {
+    // RFC 3339 is `YYYY-MM-DDTHH:MM:SSZ`
+    let digits: String = humantime::format_rfc3339_seconds(time)
+        .to_string()
+        .chars()
+        .filter(char::is_ascii_digit)
+        .collect();
+    format!("{}-{}", &digits[..8], &digits[8..])
+}
+
+/// Lowercase alphanumeric words of `name` joined by `_`
+fn slug(name: &str) -> String {
+    let words: Vec<String> = name
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect();
+    match words.join("_") {
+        slug if slug.is_empty() => "run".to_string(),
+        mut slug => {
+            slug.truncate(40);
+            slug.trim_end_matches('_').to_string()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn test_run_manager() {
+        let dir = tempfile::tempdir().unwrap();
+        let manager = RunManager::new(dir.path().join("runs"));
+        assert!(manager.list().unwrap().is_empty());
+
+        let config = Config::preset("tiny").unwrap();
+        let time = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let first = manager
+            .create_at(time, "WGSL Generation!", &config)
+            .unwrap();
+        let second = manager
+            .create_at(time, "WGSL Generation!", &config)
+            .unwrap();
+        let later = manager
+            .create_at(time + Duration::from_secs(1), "", &config)
+            .unwrap();
+
+        assert_eq!(first.id, "20231114-221320-wgsl_generation");
+        assert_eq!(second.id, "20231114-221320-wgsl_generation-2");
+        assert_eq!(later.id, "20231114-221321-run");
+        assert!(first.checkpoints_dir().is_dir());
+        assert!(first.logs_dir().is_dir() && first.samples_dir().is_dir());
+        let saved = Config::from_file(first.config_path()).unwrap();
+        assert_eq!(saved.model.d_model, config.model.d_model);
+
+        assert_eq!(manager.list().unwrap(), vec![first, second, later.clone()]);
+        assert_eq!(manager.latest().unwrap(), Some(later));
+    }
+}
@@ -0,0 +1,374 @@
+//! Weights & Biases run reporting (`wandb` feature)
+//!
+//! Talks to the W&B HTTP API directly: the run is created with its config via
+//! GraphQL, scalars are streamed as history rows through the file-stream
+//! endpoint, and artifacts are uploaded as run files.
+
+use super::MetricsSink;
+use crate::config::TrackingConfig;
+use base64::Engine as _;
+use rand::{distributions::Alphanumeric, Rng};
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use std::path::Path;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+const DEFAULT_BASE_URL: &str = "https://api.wandb.ai";
+
+const UPSERT_BUCKET: &str = "mutation UpsertBucket($name: String, $project: String, \
+    $entity: String, $config: JSONString, $displayName: String) { upsertBucket(input: \
+    {name: $name, modelName: $project, entityName: $entity, config: $config, \
+    displayName: $displayName}) { bucket { id name } } }";
+
+const CREATE_RUN_FILES: &str = "mutation CreateRunFiles($entity: String!, $project: String!, \
+    $run: String!, $files: [String!]!) { createRunFiles(input: {entityName: $entity, \
+    projectName: $project, runName: $run, files: $files}) { uploadHeaders files { name \
+    uploadUrl } } }";
+
+const VIEWER: &str = "query Viewer { viewer { entity } }";
+
+/// A live W&B run receiving training scalars and artifacts
+pub struct WandbRun {
+    agent: ureq::Agent,
+    base_url: String,
+    authorization: String,
+    entity: String,
+    project: String,
+    run_id: String,
+    started: Instant,
+    /// Scalars of the step currently being collected
+    row: Option<(u64, Map<String, Value>)>,
+    /// Completed history rows not yet sent
+    pending: Vec<String>,
+    history_offset: usize,
+    summary: Map<String, Value>,
+}
+
+impl WandbRun {
+    /// Create a run in `tracking.project` (or `default_project`) and record
+    /// `run_config` as its config; the API key is read from `WANDB_API_KEY`
+    pub fn start(
+        tracking: &TrackingConfig,
+        default_project: &str,
+        run_config: &impl Serialize,
+    ) -> crate::Result<Self> {
+        let api_key = std::env::var("WANDB_API_KEY").map_err(|_| {
+            crate::Error::ConfigError("W&B tracking requires WANDB_API_KEY".to_string())
+        })?;
+        let run_id: String = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(8)
+            .map(|c| (c as char).to_ascii_lowercase())
+            .collect();
+
+        let mut run = Self {
+            agent: ureq::AgentBuilder::new().build(),
+            base_url: tracking
+                .base_url
+                .clone()
+                .unwrap_or_else(|| DEFAULT_BASE_URL.to_string())
+                .trim_end_matches('/')
+                .to_string(),
+            authorization: format!(
+                "Basic {}",
+                base64::engine::general_purpose::STANDARD.encode(format!("api:{}", api_key))
+            ),
+            entity: String::new(),
+            project: tracking
+                .project
+                .clone()
+                .unwrap_or_else(|| default_project.to_string()),
+            run_id,
+            started: Instant::now(),
+            row: None,
+            pending: Vec::new(),
+            history_offset: 0,
+            summary: Map::new(),
+        };
+
+        run.entity = match &tracking.entity {
+            Some(entity) => entity.clone(),
+            None => run.graphql(VIEWER, json!({}))?["viewer"]["entity"]
+                .as_str()
+                .ok_or_else(|| crate::Error::Other("W&B did not return a default entity".into()))?
+                .to_string(),
+        };
+
+        run.graphql(
+            UPSERT_BUCKET,
+            json!({
+                "name": run.run_id,
+                "project": run.project,
+                "entity": run.entity,
+                "config": wandb_config(run_config)?.to_string(),
+                "displayName": tracking.run_name,
+            }),
+        )?;
+        tracing::info!("W&B run: {}", run.url());
+        Ok(run)
+    }
+
+    /// Web page of the run
+    pub fn url(&self) -> String {
+        let host = self.base_url.replacen("://api.", "://", 1);
+        format!(
+            "{}/{}/{}/runs/{}",
+            host, self.entity, self.project, self.run_id
+        )
+    }
+
+    /// Record a final value shown in the run summary
+    pub fn set_summary(&mut self, key: &str, value: f64) {
+        self.summary.insert(key.to_string(), json!(value));
+    }
+
+    /// Upload a local file to the run under `name`
+    pub fn upload_file(&mut self, path: &Path, name: &str) -> crate::Result<()> {
+        let response = self.graphql(
+            CREATE_RUN_FILES,
+            json!({
+                "entity": self.entity,
+                "project": self.project,
+                "run": self.run_id,
+                "files": [name],
+            }),
+        )?;
+        let upload = &response["createRunFiles"];
+        let url = upload["files"]
+            .as_array()
+            .and_then(|files| files.first())
+            .and_then(|file| file["uploadUrl"].as_str())
+            .ok_or_else(|| {
+                crate::Error::Other(format!("W&B returned no upload URL for {}", name))
+            })?;
+
+        let mut request = self.agent.put(url);
+        for header in upload["uploadHeaders"].as_array().into_iter().flatten() {
+            if let Some((key, value)) = header.as_str().and_then(|h| h.split_once(':')) {
+                request = request.set(key, value);
+            }
+        }
+        request
+            .send_bytes(&std::fs::read(path)?)
+            .map_err(request_error)?;
+        Ok(())
+    }
+
+    /// Send remaining history and mark the run as finished
+    pub fn finish(&mut self) -> crate::Result<()> {
+        self.flush()?;
+        self.stream(json!({ "complete": true, "exitcode": 0 }))
+    }
+
+    fn close_row(&mut self) {
+        if let Some((step, mut row)) = self.row.take() {
+            row.insert("_step".to_string(), json!(step));
+            row.insert(
+                "_runtime".to_string(),
+                json!(self.started.elapsed().as_secs_f64()),
+            );
+            row.insert("_timestamp".to_string(), json!(timestamp()));
+            for (key, value) in &row {
+                if !key.starts_with('_') {
+                    self.summary.insert(key.clone(), value.clone());
+                }
+            }
+            self.pending.push(Value::Object(row).to_string());
+        }
+    }
+
+    fn stream(&self, body: Value) -> crate::Result<()> {
+        let url = format!(
+            "{}/files/{}/{}/{}/file_stream",
+            self.base_url, self.entity, self.project, self.run_id
+        );
+        self.agent
+            .post(&url)
+            .set("Authorization", &self.authorization)
+            .send_json(body)
+            .map_err(request_error)?;
+        Ok(())
+    }
+
+    fn graphql(&self, query: &str, variables: Value) -> crate::Result<Value> {
+        let response: Value = self
+            .agent
+            .post(&format!("{}/graphql", self.base_url))
+            .set("Authorization", &self.authorization)
+            .send_json(json!({ "query": query, "variables": variables }))
+            .map_err(request_error)?
+            .into_json()?;
+        if let Some(errors) = response.get("errors") {
+            return Err(crate::Error::Other(format!("W&B API error: {}", errors)));
+        }
+        Ok(response["data"].clone())
+    }
+}
+
+impl MetricsSink for WandbRun {
+    fn log_scalar(&mut self, tag: &str, step: u64, value: f64) -> crate::Result<()> {
+        if self
+            .row
+            .as_ref()
+            .is_some_and(|(row_step, _)| *row_step != step)
+        {
+            self.close_row();
+        }
+        self.row
+            .get_or_insert_with(|| (step, Map::new()))
+            .1
+            .insert(tag.to_string(), json!(value));
+        Ok(())
+    }
+
+    fn flush(&mut self) -> crate::Result<()> {
+        self.close_row();
+        let lines = std::mem::take(&mut self.pending);
+        let summary = Value::Object(self.summary.clone()).to_string();
+        self.stream(json!({
+            "files": {
+                "wandb-history.jsonl": { "offset": self.history_offset, "content": lines },
+                "wandb-summary.json": { "offset": 0, "content": [summary] },
+            }
+        }))?;
+        self.history_offset += lines.len();
+        Ok(())
+    }
+}
+
+/// W&B expects every top-level config key wrapped as `{"value": ...}`
+fn wandb_config(run_config: &impl Serialize) -> crate::Result<Value> {
+    Ok(match serde_json::to_value(run_config)? {
+        Value::Object(sections) => Value::Object(
+            sections
+                .into_iter()
+                .map(|(key, value)| (key, json!({ "value": value })))
+                .collect(),
+        ),
+        other => json!({ "config": { "value": other } }),
+    })
+}
+
+fn timestamp() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs_f64())
+        .unwrap_or(0.0)
+}
+
+fn request_error(error: ureq::Error) -> crate::Error {
+    crate::Error::Other(format!("W&B request failed: {}", error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::sync::{Arc, Mutex};
+
+    /// (path, body) of every request received
+    type Requests = Arc<Mutex<Vec<(String, String)>>>;
+
+    /// Minimal HTTP server answering W&B calls and recording request bodies
+    fn fake_wandb() -> (String, Requests) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let recorded = Arc::clone(&requests);
+        let upload_url = format!("{}/upload", base);
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                let mut length = 0;
+                loop {
+                    let mut header = String::new();
+                    reader.read_line(&mut header).unwrap();
+                    if header.trim().is_empty() {
+                        break;
+                    }
+                    if let Some(value) = header.to_lowercase().strip_prefix("content-length:") {
+                        length = value.trim().parse().unwrap();
+                    }
+                }
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).unwrap();
+                let body = String::from_utf8_lossy(&body).to_string();
+                let path = request_line.split_whitespace().nth(1).unwrap().to_string();
+
+                let response = if body.contains("createRunFiles") {
+                    json!({ "data": { "createRunFiles": {
+                        "uploadHeaders": ["X-Test:1"],
+                        "files": [{ "name": "f", "uploadUrl": upload_url }],
+                    } } })
+                } else if body.contains("viewer") {
+                    json!({ "data": { "viewer": { "entity": "me" } } })
+                } else {
+                    json!({ "data": {} })
+                };
+                recorded.lock().unwrap().push((path, body));
+                let response = response.to_string();
+                write!(
+                    stream,
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\
+                     Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    response.len(),
+                    response
+                )
+                .unwrap();
+            }
+        });
+        (base, requests)
+    }
+
+    #[test]
+    fn test_wandb_run() {
+        let (base_url, requests) = fake_wandb();
+        std::env::set_var("WANDB_API_KEY", "test-key");
+        let tracking = TrackingConfig {
+            base_url: Some(base_url),
+            ..Default::default()
+        };
+
+        let mut run =
+            WandbRun::start(&tracking, "wgsl", &json!({ "training": { "lr": 0.1 } })).unwrap();
+        run.log_scalar("train/loss", 1, 2.0).unwrap();
+        run.log_scalar("train/lr", 1, 0.1).unwrap();
+        run.log_scalar("train/loss", 2, 1.5).unwrap();
+        run.flush().unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("model.ckpt");
+        std::fs::write(&file, b"weights").unwrap();
+        run.upload_file(&file, "model.ckpt").unwrap();
+        run.finish().unwrap();
+
+        let requests = requests.lock().unwrap();
+        let paths: Vec<&str> = requests.iter().map(|(path, _)| path.as_str()).collect();
+        assert_eq!(paths[0], "/graphql");
+        assert!(requests[1]
+            .1
+            .contains(r#"\"training\":{\"value\":{\"lr\":0.1}}"#));
+        let stream = format!(
+            "/files/me/wgsl/{}/file_stream",
+            paths[2].split('/').nth(4).unwrap()
+        );
+        assert_eq!(paths[2], stream);
+
+        let history: Value = serde_json::from_str(&requests[2].1).unwrap();
+        let lines = history["files"]["wandb-history.jsonl"]["content"]
+            .as_array()
+            .unwrap();
+        assert_eq!(lines.len(), 2);
+        let first: Value = serde_json::from_str(lines[0].as_str().unwrap()).unwrap();
+        assert_eq!(first["_step"], 1);
+        assert_eq!(first["train/lr"], 0.1);
+
+        assert_eq!(paths[4], "/upload");
+        assert_eq!(requests[4].1, "weights");
+        assert!(requests[6].1.contains("\"complete\":true"));
+    }
+}
@@ -0,0 +1,110 @@
+//! Browser bindings for tokenization, validation and small-model inference
+//!
+//! Enabled by the `wasm` feature for web-based shader editors that run the
+//! generator client-side. Build with
+//! `cargo build --lib --release --target wasm32-unknown-unknown --features wasm`
+//! and generate the JavaScript glue with `wasm-bindgen --target web`.
+//! Results cross into JavaScript as plain objects with the field names of
+//! their Rust types. Nothing here touches the filesystem: tokenizers and
+//! checkpoints are passed in as fetched bytes or text.
+
+use crate::config::GenerationConfig;
+use crate::inference::WGSLGenerator;
+use crate::tokenizer::WGSLTokenizer;
+use crate::wgsl::WGSLValidator;
+use wasm_bindgen::prelude::*;
+
+/// Validate `code` against the WebGPU core profile, returning a
+/// [`ValidationResult`](crate::wgsl::ValidationResult)
+#[wasm_bindgen]
+pub fn validate(code: &str) -> Result<JsValue, JsError> {
+    let result = WGSLValidator::new().validate(code)?;
+    Ok(serde_wasm_bindgen::to_value(&result)?)
+}
+
+/// Tokenizer parsed from the JSON [`WGSLTokenizer::save`] writes
+#[wasm_bindgen(js_name = Tokenizer)]
+pub struct WasmTokenizer {
+    inner: WGSLTokenizer,
+}
+
+#[wasm_bindgen(js_class = Tokenizer)]
+impl WasmTokenizer {
+    #[wasm_bindgen(constructor)]
+    pub fn new(json: &str) -> Result<WasmTokenizer, JsError> {
+        Ok(Self {
+            inner: WGSLTokenizer::from_json(json)?,
+        })
+    }
+
+    pub fn tokenize(&self, text: &str) -> Vec<String> {
+        self.inner.tokenize(text)
+    }
+
+    pub fn encode(&self, text: &str) -> Vec<u32> {
+        self.inner
+            .encode_text(text)
+            .into_iter()
+            .map(|id| id as u32)
+            .collect()
+    }
+
+    pub fn decode(&self, ids: &[u32]) -> String {
+        let ids: Vec<usize> = ids.iter().map(|&id| id as usize).collect();
+        self.inner.decode_to_text(&ids)
+    }
+
+    #[wasm_bindgen(getter, js_name = vocabSize)]
+    pub fn vocab_size(&self) -> usize {
+        self.inner.vocab_size()
+    }
+}
+
+/// Generator loaded from the bytes of a checkpoint file
+#[wasm_bindgen(js_name = Generator)]
+pub struct WasmGenerator {
+    inner: WGSLGenerator,
+    validator: WGSLValidator,
+}
+
+#[wasm_bindgen(js_class = Generator)]
+impl WasmGenerator {
+    #[wasm_bindgen(constructor)]
+    pub fn new(checkpoint: &[u8]) -> Result<WasmGenerator, JsError> {
+        Ok(Self {
+            inner: WGSLGenerator::from_checkpoint_bytes(checkpoint)?,
+            validator: WGSLValidator::new(),
+        })
+    }
+
+    /// Generate code for `prompt`, returning a
+    /// [`GenerationResult`](crate::inference::GenerationResult)
+    ///
+    /// `config` is shaped like the `[generation]` config section; missing
+    /// fields, or a missing object, take their defaults.
+    pub fn generate(
+        &self,
+        prompt: &str,
+        config: JsValue,
+        seed: Option<u64>,
+    ) -> Result<JsValue, JsError> {
+        let config: GenerationConfig = if config.is_undefined() || config.is_null() {
+            GenerationConfig::default()
+        } else {
+            serde_wasm_bindgen::from_value(config)?
+        };
+        let errors = config.validation_errors();
+        if !errors.is_empty() {
+            return Err(JsError::new(&errors.join("; ")));
+        }
+        let result = self
+            .inner
+            .generate_with_config(prompt, &config, seed, &self.validator)?;
+        Ok(serde_wasm_bindgen::to_value(&result)?)
+    }
+
+    #[wasm_bindgen(getter, js_name = vocabSize)]
+    pub fn vocab_size(&self) -> usize {
+        self.inner.tokenizer().vocab_size()
+    }
+}
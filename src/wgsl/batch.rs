@@ -0,0 +1,229 @@
+//! Validation of every WGSL file under a directory
+
+use super::WGSLValidator;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Validation outcome for one file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileValidation {
+    /// Path relative to the validated directory
+    pub path: PathBuf,
+    pub is_valid: bool,
+    pub errors: Vec<String>,
+}
+
+/// Pass/fail counts for one subdirectory
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DirectorySummary {
+    pub passed: usize,
+    pub failed: usize,
+}
+
+/// Results of validating a directory tree
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BatchReport {
+    pub root: PathBuf,
+    pub files: Vec<FileValidation>,
+}
+
+impl BatchReport {
+    /// Number of valid files
+    pub fn passed(&self) -> usize {
+        self.files.iter().filter(|f| f.is_valid).count()
+    }
+
+    /// Number of invalid files
+    pub fn failed(&self) -> usize {
+        self.files.len() - self.passed()
+    }
+
+    /// Pass/fail counts keyed by subdirectory relative to the root
+    pub fn by_directory(&self) -> BTreeMap<PathBuf, DirectorySummary> {
+        let mut summary: BTreeMap<PathBuf, DirectorySummary> = BTreeMap::new();
+        for file in &self.files {
+            let dir = file.path.parent().unwrap_or(Path::new("")).to_path_buf();
+            let entry = summary.entry(dir).or_default();
+            if file.is_valid {
+                entry.passed += 1;
+            } else {
+                entry.failed += 1;
+            }
+        }
+        summary
+    }
+
+    /// Print a per-directory summary table followed by the failures
+    pub fn print(&self) {
+        let summary = self.by_directory();
+        let width = summary
+            .keys()
+            .map(|dir| display_dir(dir).len())
+            .chain(std::iter::once("Directory".len()))
+            .max()
+            .unwrap_or(0);
+
+        println!(
+            "{:<width$}  {:>6}  {:>6}",
+            "Directory",
+            "Passed",
+            "Failed",
+            width = width
+        );
+        println!("{}", "-".repeat(width + 16));
+        for (dir, counts) in &summary {
+            println!(
+                "{:<width$}  {:>6}  {:>6}",
+                display_dir(dir),
+                counts.passed,
+                counts.failed,
+                width = width
+            );
+        }
+        println!("{}", "-".repeat(width + 16));
+        println!(
+            "{:<width$}  {:>6}  {:>6}",
+            "Total",
+            self.passed(),
+            self.failed(),
+            width = width
+        );
+
+        for file in self.files.iter().filter(|f| !f.is_valid) {
+            println!("\n❌ {}", file.path.display());
+            for error in &file.errors {
+                println!("  - {}", error);
+            }
+        }
+    }
+
+    /// Write the report as pretty-printed JSON
+    pub fn to_json_file<P: AsRef<Path>>(&self, path: P) -> crate::Result<()> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+fn display_dir(dir: &Path) -> String {
+    if dir.as_os_str().is_empty() {
+        ".".to_string()
+    } else {
+        dir.display().to_string()
+    }
+}
+
+/// Validate every `.wgsl` file under `root`, optionally filtered by a glob
+/// pattern matched against paths relative to `root` (e.g. `compute/**/*.wgsl`)
+pub fn validate_directory(
+    root: &Path,
+    pattern: Option<&str>,
+    validator: &WGSLValidator,
+) -> crate::Result<BatchReport> {
+    let mut files = Vec::new();
+    for (path, relative) in matching_wgsl_files(root, &parse_pattern(pattern)?)? {
+        let result = validator.validate_file(&path)?;
+        files.push(FileValidation {
+            path: relative,
+            is_valid: result.is_valid,
+            errors: result.errors,
+        });
+    }
+
+    Ok(BatchReport {
+        root: root.to_path_buf(),
+        files,
+    })
+}
+
+pub(super) fn parse_pattern(pattern: Option<&str>) -> crate::Result<Option<glob::Pattern>> {
+    pattern
+        .map(glob::Pattern::new)
+        .transpose()
+        .map_err(|e| crate::Error::Other(format!("Invalid glob pattern: {}", e)))
+}
+
+/// Sorted `.wgsl` files under `root` matching `pattern`, with their paths
+/// relative to `root`
+pub(super) fn matching_wgsl_files(
+    root: &Path,
+    pattern: &Option<glob::Pattern>,
+) -> crate::Result<Vec<(PathBuf, PathBuf)>> {
+    let mut paths = Vec::new();
+    collect_wgsl_files(root, &mut paths)?;
+    paths.sort();
+    Ok(paths
+        .into_iter()
+        .map(|path| {
+            let relative = path.strip_prefix(root).unwrap_or(&path).to_path_buf();
+            (path, relative)
+        })
+        .filter(|(_, relative)| {
+            pattern
+                .as_ref()
+                .is_none_or(|pattern| pattern.matches_path(relative))
+        })
+        .collect())
+}
+
+fn collect_wgsl_files(dir: &Path, out: &mut Vec<PathBuf>) -> crate::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_wgsl_files(&path, out)?;
+        } else if path.extension().is_some_and(|ext| ext == "wgsl") {
+            out.push(path);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wgsl::ChromaticTemplate;
+
+    #[test]
+    fn test_validate_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        std::fs::create_dir_all(root.join("compute/chromatic")).unwrap();
+        std::fs::create_dir_all(root.join("broken")).unwrap();
+        std::fs::write(root.join("top.wgsl"), ChromaticTemplate::mix()).unwrap();
+        std::fs::write(
+            root.join("compute/chromatic/filter.wgsl"),
+            ChromaticTemplate::filter(),
+        )
+        .unwrap();
+        std::fs::write(root.join("broken/bad.wgsl"), "fn main( {").unwrap();
+        std::fs::write(root.join("broken/notes.txt"), "not a shader").unwrap();
+
+        let validator = WGSLValidator::new();
+        let report = validate_directory(root, None, &validator).unwrap();
+        assert_eq!(report.files.len(), 3);
+        assert_eq!((report.passed(), report.failed()), (2, 1));
+
+        let summary = report.by_directory();
+        assert_eq!(
+            summary[Path::new("broken")],
+            DirectorySummary {
+                passed: 0,
+                failed: 1
+            }
+        );
+        assert_eq!(summary[Path::new("")].passed, 1);
+        assert_eq!(summary[Path::new("compute/chromatic")].passed, 1);
+
+        let filtered = validate_directory(root, Some("compute/**/*.wgsl"), &validator).unwrap();
+        assert_eq!(filtered.files.len(), 1);
+        assert_eq!(filtered.failed(), 0);
+
+        let json = root.join("report.json");
+        report.to_json_file(&json).unwrap();
+        let parsed: BatchReport =
+            serde_json::from_str(&std::fs::read_to_string(json).unwrap()).unwrap();
+        assert_eq!(parsed.files, report.files);
+
+        assert!(validate_directory(root, Some("[bad"), &validator).is_err());
+    }
+}
@@ -0,0 +1,317 @@
+//! Line and token diffs between shaders
+//!
+//! Triaging an evaluation starts with seeing how a generated shader differs
+//! from its reference, or from another model's shader for the same prompt.
+//! [`diff_wgsl`] lines the two sources up by their longest common
+//! subsequence of lines, and compares lines replaced one for one again token
+//! by token, so [`ShaderDiff::render`] can highlight the tokens that changed.
+
+use super::format::lex;
+use serde::Serialize;
+
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+const HIGHLIGHT: &str = "\x1b[7m";
+const RESET: &str = "\x1b[0m";
+
+/// Whether a line or token is in both sources or only one
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Change {
+    Equal,
+    /// Only in the old source
+    Delete,
+    /// Only in the new source
+    Insert,
+}
+
+/// A token, or the whitespace between tokens, of a changed line
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DiffSegment {
+    pub change: Change,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DiffLine {
+    pub change: Change,
+    pub text: String,
+    /// The line cut into segments marked against the line it replaces or is
+    /// replaced by; empty unless it was replaced one for one
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub segments: Vec<DiffSegment>,
+}
+
+impl DiffLine {
+    fn new(change: Change, text: &str) -> Self {
+        Self {
+            change,
+            text: text.to_string(),
+            segments: Vec::new(),
+        }
+    }
+}
+
+/// Every line of two shaders, in order, marked as kept, removed or added
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ShaderDiff {
+    pub lines: Vec<DiffLine>,
+}
+
+impl ShaderDiff {
+    pub fn is_identical(&self) -> bool {
+        self.lines.iter().all(|line| line.change == Change::Equal)
+    }
+
+    /// Lines only in the old source
+    pub fn deleted(&self) -> usize {
+        self.count(Change::Delete)
+    }
+
+    /// Lines only in the new source
+    pub fn inserted(&self) -> usize {
+        self.count(Change::Insert)
+    }
+
+    fn count(&self, change: Change) -> usize {
+        self.lines
+            .iter()
+            .filter(|line| line.change == change)
+            .count()
+    }
+
+    /// Lines prefixed with `-`, `+` or two spaces; with `color`, removed
+    /// lines are red, added ones green and changed tokens highlighted
+    pub fn render(&self, color: bool) -> String {
+        let mut out = String::new();
+        for line in &self.lines {
+            let (prefix, line_color) = match line.change {
+                Change::Equal => ("  ", ""),
+                Change::Delete => ("- ", RED),
+                Change::Insert => ("+ ", GREEN),
+            };
+            if !color || line.change == Change::Equal {
+                out.push_str(prefix);
+                out.push_str(&line.text);
+            } else {
+                out.push_str(line_color);
+                out.push_str(prefix);
+                if line.segments.is_empty() {
+                    out.push_str(&line.text);
+                }
+                for segment in &line.segments {
+                    if segment.change == Change::Equal {
+                        out.push_str(&segment.text);
+                    } else {
+                        out.push_str(&format!("{HIGHLIGHT}{}{RESET}{line_color}", segment.text));
+                    }
+                }
+                out.push_str(RESET);
+            }
+            out.push('\n');
+        }
+        out
+    }
+}
+
+/// Diff of `old` to `new` by line, and by token within lines replaced one
+/// for one; format both with
+/// [`format_wgsl_or_original`](super::format_wgsl_or_original) first to
+/// ignore layout
+pub fn diff_wgsl(old: &str, new: &str) -> ShaderDiff {
+    let old_lines: Vec<&str> = old.lines().collect();
+    let new_lines: Vec<&str> = new.lines().collect();
+    let mut lines = Vec::new();
+    let (mut deleted, mut inserted) = (Vec::new(), Vec::new());
+    for (change, position) in diff_sequences(&old_lines, &new_lines) {
+        match change {
+            Change::Equal => {
+                push_gap(&mut lines, &mut deleted, &mut inserted);
+                lines.push(DiffLine::new(Change::Equal, old_lines[position]));
+            }
+            Change::Delete => deleted.push(DiffLine::new(change, old_lines[position])),
+            Change::Insert => inserted.push(DiffLine::new(change, new_lines[position])),
+        }
+    }
+    push_gap(&mut lines, &mut deleted, &mut inserted);
+    ShaderDiff { lines }
+}
+
+/// Move the lines removed and added between two kept lines to `lines`,
+/// pairing them up in order to compare their tokens
+fn push_gap(lines: &mut Vec<DiffLine>, deleted: &mut Vec<DiffLine>, inserted: &mut Vec<DiffLine>) {
+    for (old, new) in deleted.iter_mut().zip(inserted.iter_mut()) {
+        (old.segments, new.segments) = diff_tokens(&old.text, &new.text);
+    }
+    lines.append(deleted);
+    lines.append(inserted);
+}
+
+/// Segments of `old` and `new`, with the tokens only in one of them marked
+fn diff_tokens(old: &str, new: &str) -> (Vec<DiffSegment>, Vec<DiffSegment>) {
+    let old_segments = segments(old);
+    let new_segments = segments(new);
+    let tokens = |segments: &[(&str, bool)]| -> Vec<(usize, String)> {
+        segments
+            .iter()
+            .enumerate()
+            .filter(|(_, (_, is_token))| *is_token)
+            .map(|(index, (text, _))| (index, text.to_string()))
+            .collect()
+    };
+    let old_tokens = tokens(&old_segments);
+    let new_tokens = tokens(&new_segments);
+    let old_texts: Vec<&String> = old_tokens.iter().map(|(_, text)| text).collect();
+    let new_texts: Vec<&String> = new_tokens.iter().map(|(_, text)| text).collect();
+
+    let mut old_changes = vec![Change::Equal; old_segments.len()];
+    let mut new_changes = vec![Change::Equal; new_segments.len()];
+    for (change, position) in diff_sequences(&old_texts, &new_texts) {
+        match change {
+            Change::Equal => {}
+            Change::Delete => old_changes[old_tokens[position].0] = Change::Delete,
+            Change::Insert => new_changes[new_tokens[position].0] = Change::Insert,
+        }
+    }
+    let marked = |segments: Vec<(&str, bool)>, changes: Vec<Change>| {
+        segments
+            .into_iter()
+            .zip(changes)
+            .map(|((text, _), change)| DiffSegment {
+                change,
+                text: text.to_string(),
+            })
+            .collect()
+    };
+    (
+        marked(old_segments, old_changes),
+        marked(new_segments, new_changes),
+    )
+}
+
+/// `line` cut into its WGSL tokens and the text between them, each marked
+/// with whether it is a token
+fn segments(line: &str) -> Vec<(&str, bool)> {
+    let mut segments = Vec::new();
+    let mut rest = line;
+    for token in lex(line) {
+        let Some(start) = rest.find(token.text.as_str()) else {
+            break;
+        };
+        if start > 0 {
+            segments.push((&rest[..start], false));
+        }
+        let end = start + token.text.len();
+        segments.push((&rest[start..end], true));
+        rest = &rest[end..];
+    }
+    if !rest.is_empty() {
+        segments.push((rest, false));
+    }
+    segments
+}
+
+/// Shortest edit script turning `a` into `b` through their longest common
+/// subsequence: each step with the index it refers to in `a` (equal and
+/// delete) or `b` (insert); within a gap, deletions come first
+pub fn diff_sequences<T: PartialEq>(a: &[T], b: &[T]) -> Vec<(Change, usize)> {
+    // common[i][j]: length of the longest common subsequence of a[i..], b[j..]
+    let mut common = vec![vec![0u32; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            common[i][j] = if a[i] == b[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    let mut script = Vec::with_capacity(a.len().max(b.len()));
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        if a[i] == b[j] {
+            script.push((Change::Equal, i));
+            i += 1;
+            j += 1;
+        } else if common[i + 1][j] >= common[i][j + 1] {
+            script.push((Change::Delete, i));
+            i += 1;
+        } else {
+            script.push((Change::Insert, j));
+            j += 1;
+        }
+    }
+    script.extend((i..a.len()).map(|i| (Change::Delete, i)));
+    script.extend((j..b.len()).map(|j| (Change::Insert, j)));
+    script
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff_wgsl() {
+        assert_eq!(
+            diff_sequences(&["a", "b", "c"], &["a", "x", "c", "d"]),
+            [
+                (Change::Equal, 0),
+                (Change::Delete, 1),
+                (Change::Insert, 1),
+                (Change::Equal, 2),
+                (Change::Insert, 3)
+            ]
+        );
+
+        let reference = "fn main() {\n    let x = 1.0;\n    let y = x * 2.0;\n}\n";
+        let generated = "fn main() {\n    let x = 1.0;\n    let y = x + 2.0;\n    return;\n}\n";
+        let diff = diff_wgsl(reference, generated);
+        assert!(!diff.is_identical());
+        assert_eq!((diff.deleted(), diff.inserted()), (1, 2));
+        let changes: Vec<Change> = diff.lines.iter().map(|line| line.change).collect();
+        assert_eq!(
+            changes,
+            [
+                Change::Equal,
+                Change::Equal,
+                Change::Delete,
+                Change::Insert,
+                Change::Insert,
+                Change::Equal
+            ]
+        );
+
+        // The replaced line pair is compared token by token
+        let changed = |line: &DiffLine| -> Vec<String> {
+            line.segments
+                .iter()
+                .filter(|segment| segment.change != Change::Equal)
+                .map(|segment| segment.text.clone())
+                .collect()
+        };
+        assert_eq!(changed(&diff.lines[2]), ["*"]);
+        assert_eq!(changed(&diff.lines[3]), ["+"]);
+        assert!(diff.lines[4].segments.is_empty());
+        let text: String = diff.lines[3]
+            .segments
+            .iter()
+            .map(|segment| segment.text.as_str())
+            .collect();
+        assert_eq!(text, diff.lines[3].text);
+
+        assert_eq!(
+            diff.render(false),
+            "  fn main() {\n\
+             \x20     let x = 1.0;\n\
+             -     let y = x * 2.0;\n\
+             +     let y = x + 2.0;\n\
+             +     return;\n\
+             \x20 }\n"
+        );
+        assert!(diff
+            .render(true)
+            .contains("\x1b[32m+     let y = x \x1b[7m+\x1b[0m\x1b[32m 2.0;\x1b[0m"));
+        assert!(diff_wgsl(reference, reference).is_identical());
+    }
+}
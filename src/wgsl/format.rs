@@ -0,0 +1,549 @@
+//! WGSL formatter / pretty-printer
+//!
+//! Code is parsed with naga to make sure it is well-formed, then re-emitted from
+//! its token stream with consistent indentation and spacing. Working on tokens
+//! rather than naga's IR keeps comments, names and control flow exactly as written.
+
+use super::ValidationResult;
+
+/// Indentation used for each nesting level
+const INDENT: &str = "    ";
+
+/// Format WGSL source, failing if it does not parse
+pub fn format_wgsl(code: &str) -> crate::Result<String> {
+    naga::front::wgsl::parse_str(code)
+        .map_err(|e| ValidationResult::failed(format!("Parse error: {}", e)))?;
+    Ok(format_tokens(code))
+}
+
+/// Format WGSL source if it parses, otherwise return it unchanged
+pub fn format_wgsl_or_original(code: &str) -> String {
+    format_wgsl(code).unwrap_or_else(|_| code.to_string())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Kind {
+    Ident,
+    Number,
+    Punct,
+    LineComment,
+    BlockComment,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Role {
+    Normal,
+    Unary,
+    TemplateOpen,
+    TemplateClose,
+}
+
+#[derive(Debug, Clone)]
+pub(super) struct Token {
+    pub(super) kind: Kind,
+    pub(super) text: String,
+    role: Role,
+    /// Newlines between this token and the previous one in the input
+    newlines_before: usize,
+    /// 1-based line the token starts on
+    pub(super) line: usize,
+}
+
+impl Token {
+    pub(super) fn is(&self, text: &str) -> bool {
+        self.kind == Kind::Punct && self.text == text
+    }
+
+    pub(super) fn is_comment(&self) -> bool {
+        matches!(self.kind, Kind::LineComment | Kind::BlockComment)
+    }
+}
+
+const PUNCTUATION: &[&str] = &[
+    "<<=", ">>=", "->", "<=", ">=", "==", "!=", "&&", "||", "<<", ">>", "+=", "-=", "*=", "/=",
+    "%=", "&=", "|=", "^=", "++", "--",
+];
+
+pub(super) fn lex(code: &str) -> Vec<Token> {
+    let chars: Vec<char> = code.chars().collect();
+    let mut tokens: Vec<Token> = Vec::new();
+    let mut newlines = 0;
+    let mut line = 1;
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            if c == '\n' {
+                newlines += 1;
+                line += 1;
+            }
+            i += 1;
+            continue;
+        }
+
+        let start = i;
+        let kind = if c == '/' && chars.get(i + 1) == Some(&'/') {
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+            Kind::LineComment
+        } else if c == '/' && chars.get(i + 1) == Some(&'*') {
+            // Block comments nest in WGSL
+            let mut depth = 0;
+            while i < chars.len() {
+                if chars[i] == '/' && chars.get(i + 1) == Some(&'*') {
+                    depth += 1;
+                    i += 2;
+                } else if chars[i] == '*' && chars.get(i + 1) == Some(&'/') {
+                    depth -= 1;
+                    i += 2;
+                    if depth == 0 {
+                        break;
+                    }
+                } else {
+                    i += 1;
+                }
+            }
+            Kind::BlockComment
+        } else if c.is_ascii_digit() || (c == '.' && starts_number(&chars, i, tokens.last())) {
+            let hex = c == '0' && matches!(chars.get(i + 1), Some('x') | Some('X'));
+            i += 1;
+            while i < chars.len() {
+                let ch = chars[i];
+                let exponent = if hex {
+                    matches!(ch, 'p' | 'P')
+                } else {
+                    matches!(ch, 'e' | 'E')
+                };
+                if exponent && matches!(chars.get(i + 1), Some('+') | Some('-')) {
+                    i += 2;
+                } else if ch.is_alphanumeric() || ch == '_' || ch == '.' {
+                    i += 1;
+                } else {
+                    break;
+                }
+            }
+            Kind::Number
+        } else if c.is_alphabetic() || c == '_' {
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            Kind::Ident
+        } else {
+            let rest: String = chars[i..chars.len().min(i + 3)].iter().collect();
+            let len = PUNCTUATION
+                .iter()
+                .find(|op| rest.starts_with(*op))
+                .map_or(1, |op| op.len());
+            i += len;
+            Kind::Punct
+        };
+
+        let text: String = chars[start..i].iter().collect();
+        let token_line = line;
+        line += text.matches('\n').count();
+        tokens.push(Token {
+            kind,
+            text,
+            role: Role::Normal,
+            newlines_before: newlines,
+            line: token_line,
+        });
+        newlines = 0;
+    }
+
+    tokens
+}
+
+/// A `.` starts a number like `.5` unless it is a member access
+fn starts_number(chars: &[char], i: usize, previous: Option<&Token>) -> bool {
+    let follows_value = previous.is_some_and(|token| {
+        matches!(token.kind, Kind::Ident | Kind::Number) || token.is(")") || token.is("]")
+    });
+    !follows_value && chars.get(i + 1).is_some_and(|ch| ch.is_ascii_digit())
+}
+
+/// Identifiers that take a `<...>` template parameter list
+fn is_template_type(name: &str) -> bool {
+    let bytes = name.as_bytes();
+    let dim = |b: u8| (b'2'..=b'4').contains(&b);
+    matches!(
+        name,
+        "array" | "ptr" | "atomic" | "var" | "bitcast" | "binding_array"
+    ) || name.starts_with("texture_")
+        || (bytes.len() == 4 && name.starts_with("vec") && dim(bytes[3]))
+        || (bytes.len() == 6
+            && name.starts_with("mat")
+            && dim(bytes[3])
+            && bytes[4] == b'x'
+            && dim(bytes[5]))
+}
+
+/// Keywords followed by a space before an opening parenthesis
+fn is_control_keyword(name: &str) -> bool {
+    matches!(name, "if" | "for" | "while" | "switch" | "return" | "case")
+}
+
+/// Mark template brackets and unary operators
+fn classify(tokens: &mut Vec<Token>) {
+    let mut depth = 0usize;
+    let mut previous: Option<usize> = None;
+    let mut i = 0;
+
+    while i < tokens.len() {
+        if tokens[i].is_comment() {
+            i += 1;
+            continue;
+        }
+
+        let prev = previous.map(|index| &tokens[index]);
+        let text = tokens[i].text.as_str();
+
+        if tokens[i].kind == Kind::Punct {
+            if text == "<"
+                && prev.is_some_and(|p| p.kind == Kind::Ident && is_template_type(&p.text))
+            {
+                tokens[i].role = Role::TemplateOpen;
+                depth += 1;
+            } else if depth > 0 && text == ">" {
+                tokens[i].role = Role::TemplateClose;
+                depth -= 1;
+            } else if depth > 0 && text == ">>" {
+                // `array<vec4<f32>>` closes two templates at once
+                tokens[i].text = ">".to_string();
+                tokens[i].role = Role::TemplateClose;
+                let mut second = tokens[i].clone();
+                second.newlines_before = 0;
+                tokens.insert(i + 1, second);
+                depth = depth.saturating_sub(2);
+                previous = Some(i + 1);
+                i += 2;
+                continue;
+            } else if matches!(text, "-" | "!" | "~" | "&" | "*") && starts_operand(prev) {
+                tokens[i].role = Role::Unary;
+            }
+        }
+
+        previous = Some(i);
+        i += 1;
+    }
+}
+
+/// Whether an operator after `previous` is in prefix (unary) position
+fn starts_operand(previous: Option<&Token>) -> bool {
+    match previous {
+        None => true,
+        Some(token) => match token.kind {
+            Kind::Ident => matches!(token.text.as_str(), "return" | "case"),
+            Kind::Number => false,
+            Kind::Punct => {
+                token.role != Role::TemplateClose
+                    && !matches!(token.text.as_str(), ")" | "]" | "++" | "--")
+            }
+            Kind::LineComment | Kind::BlockComment => true,
+        },
+    }
+}
+
+fn space_between(prev: &Token, cur: &Token) -> bool {
+    if cur.role == Role::TemplateOpen || cur.role == Role::TemplateClose {
+        return false;
+    }
+    if prev.role == Role::TemplateOpen || prev.role == Role::Unary {
+        return false;
+    }
+    if prev.kind == Kind::Punct && matches!(prev.text.as_str(), "(" | "[" | "." | "@") {
+        return false;
+    }
+    if cur.kind == Kind::Punct {
+        match cur.text.as_str() {
+            ")" | "]" | "," | ";" | "." | ":" | "[" => return false,
+            "++" | "--" => return prev.is_comment() || prev.is("("),
+            "(" => {
+                return match prev.kind {
+                    Kind::Ident => is_control_keyword(&prev.text),
+                    Kind::Punct => prev.role != Role::TemplateClose && !prev.is(")"),
+                    _ => true,
+                };
+            }
+            _ => {}
+        }
+    }
+    true
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Brace {
+    Block,
+    Struct,
+}
+
+struct Printer {
+    out: String,
+    indent: usize,
+    pending_newline: bool,
+    braces: Vec<Brace>,
+    parens: usize,
+}
+
+impl Printer {
+    fn at_line_start(&self) -> bool {
+        self.out.is_empty() || self.out.ends_with('\n')
+    }
+
+    fn newline(&mut self) {
+        let trimmed = self.out.trim_end_matches(' ').len();
+        self.out.truncate(trimmed);
+        if !self.out.is_empty() {
+            self.out.push('\n');
+        }
+    }
+
+    /// Start a new line if one is pending, keeping at most one blank line from the input
+    fn flush_newline(&mut self, newlines_before: usize, closing: bool) {
+        if !self.pending_newline {
+            return;
+        }
+        self.pending_newline = false;
+        self.newline();
+        let after_open = self.out.trim_end().ends_with('{');
+        if newlines_before >= 2 && !closing && !after_open && !self.out.is_empty() {
+            self.out.push('\n');
+        }
+    }
+
+    fn write(&mut self, text: &str, space: bool) {
+        if self.at_line_start() {
+            for _ in 0..self.indent {
+                self.out.push_str(INDENT);
+            }
+        } else if space {
+            self.out.push(' ');
+        }
+        self.out.push_str(text);
+    }
+}
+
+fn format_tokens(code: &str) -> String {
+    let mut tokens = lex(code);
+    classify(&mut tokens);
+
+    let mut printer = Printer {
+        out: String::new(),
+        indent: 0,
+        pending_newline: false,
+        braces: Vec::new(),
+        parens: 0,
+    };
+    let mut prev: Option<&Token> = None;
+
+    for (i, token) in tokens.iter().enumerate() {
+        let next = tokens[i + 1..].iter().find(|t| !t.is_comment());
+
+        if token.is_comment() {
+            let trailing = token.newlines_before == 0 && prev.is_some();
+            if trailing {
+                printer.write(&token.text, true);
+            } else {
+                printer.pending_newline = prev.is_some();
+                printer.flush_newline(token.newlines_before, false);
+                printer.write(&token.text, false);
+            }
+            if token.kind == Kind::LineComment || token.newlines_before > 0 {
+                printer.pending_newline = true;
+            }
+            prev = Some(token);
+            continue;
+        }
+
+        // Keep top-level declarations (and their attributes) on their own lines
+        if printer.braces.is_empty()
+            && printer.parens == 0
+            && token.newlines_before > 0
+            && prev.is_some()
+            && starts_declaration(token)
+        {
+            printer.pending_newline = true;
+        }
+
+        let space = prev.is_some_and(|p| space_between(p, token));
+
+        if token.kind == Kind::Punct {
+            match token.text.as_str() {
+                "{" => {
+                    let brace = if is_struct_body(&tokens, i) {
+                        Brace::Struct
+                    } else {
+                        Brace::Block
+                    };
+                    printer.flush_newline(token.newlines_before, false);
+                    printer.write("{", true);
+                    printer.braces.push(brace);
+                    printer.indent += 1;
+                    printer.pending_newline = true;
+                }
+                "}" => {
+                    let brace = printer.braces.pop();
+                    printer.indent = printer.indent.saturating_sub(1);
+                    if prev.is_some_and(|p| p.is("{")) {
+                        // Empty block stays on one line
+                        printer.pending_newline = false;
+                        printer.write("}", false);
+                    } else {
+                        let needs_comma = brace == Some(Brace::Struct)
+                            && prev.is_some_and(|p| !p.is(",") && !p.is_comment());
+                        if needs_comma && !printer.pending_newline {
+                            printer.write(",", false);
+                        }
+                        printer.pending_newline = true;
+                        printer.flush_newline(token.newlines_before, true);
+                        printer.write("}", false);
+                    }
+                    printer.pending_newline = true;
+                }
+                ";" => {
+                    if printer.ends_with_close_brace() {
+                        printer.pending_newline = false;
+                    }
+                    printer.flush_newline(token.newlines_before, false);
+                    printer.write(";", false);
+                    if printer.parens == 0 {
+                        printer.pending_newline = true;
+                    }
+                }
+                "," => {
+                    // Trailing commas inside parentheses and templates are dropped
+                    if next.is_some_and(|n| n.is(")") || n.role == Role::TemplateClose) {
+                        prev = Some(token);
+                        continue;
+                    }
+                    if printer.ends_with_close_brace() {
+                        printer.pending_newline = false;
+                    }
+                    printer.flush_newline(token.newlines_before, false);
+                    printer.write(",", false);
+                    if printer.parens == 0 && printer.braces.last() == Some(&Brace::Struct) {
+                        printer.pending_newline = true;
+                    }
+                }
+                text => {
+                    if matches!(text, "(" | "[") {
+                        printer.parens += usize::from(text == "(");
+                    } else if text == ")" {
+                        printer.parens = printer.parens.saturating_sub(1);
+                        if printer.ends_with_close_brace() {
+                            printer.pending_newline = false;
+                        }
+                    }
+                    printer.flush_newline(token.newlines_before, false);
+                    printer.write(text, space);
+                }
+            }
+        } else {
+            if token.kind == Kind::Ident && token.text == "else" && printer.ends_with_close_brace()
+            {
+                printer.pending_newline = false;
+            }
+            printer.flush_newline(token.newlines_before, false);
+            printer.write(&token.text, space);
+        }
+
+        prev = Some(token);
+    }
+
+    printer.newline();
+    printer.out
+}
+
+impl Printer {
+    fn ends_with_close_brace(&self) -> bool {
+        self.pending_newline && self.out.ends_with('}')
+    }
+}
+
+/// Tokens that begin a top-level declaration
+fn starts_declaration(token: &Token) -> bool {
+    token.is("@")
+        || (token.kind == Kind::Ident
+            && matches!(
+                token.text.as_str(),
+                "fn" | "var"
+                    | "let"
+                    | "const"
+                    | "override"
+                    | "struct"
+                    | "alias"
+                    | "enable"
+                    | "requires"
+                    | "diagnostic"
+                    | "const_assert"
+            ))
+}
+
+/// Whether the `{` at `index` opens a `struct Name { ... }` body
+fn is_struct_body(tokens: &[Token], index: usize) -> bool {
+    let mut significant = tokens[..index].iter().rev().filter(|t| !t.is_comment());
+    let _name = significant.next();
+    significant
+        .next()
+        .is_some_and(|t| t.kind == Kind::Ident && t.text == "struct")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wgsl::{TemplateParams, TemplateRegistry};
+
+    #[test]
+    fn test_templates_are_already_formatted() {
+        for template in TemplateRegistry::builtin().templates() {
+            let code = template.render(&TemplateParams::default()).unwrap();
+            assert_eq!(format_wgsl(&code).unwrap(), code, "{}", template.name);
+        }
+    }
+
+    #[test]
+    fn test_format_messy_code() {
+        let messy = "struct VOut{@builtin(position) pos:vec4<f32>,@location(0) uv:vec2<f32>}\n\
+            fn helper(x:f32)->f32{let y=x*-2.0;var z=y+1.0;// bump\n\
+            for(var i=0;i<4;i++){z+=1.0;}if z>0.5{return z;}else{return -z;}}\n\
+            @group(0) @binding(0)\nvar<storage,read_write> data:array<vec4<f32>>;";
+
+        let expected = "\
+struct VOut {
+    @builtin(position) pos: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+fn helper(x: f32) -> f32 {
+    let y = x * -2.0;
+    var z = y + 1.0; // bump
+    for (var i = 0; i < 4; i++) {
+        z += 1.0;
+    }
+    if z > 0.5 {
+        return z;
+    } else {
+        return -z;
+    }
+}
+@group(0) @binding(0)
+var<storage, read_write> data: array<vec4<f32>>;
+";
+        let formatted = format_wgsl(messy).unwrap();
+        assert_eq!(formatted, expected);
+        assert_eq!(format_wgsl(&formatted).unwrap(), formatted);
+    }
+
+    #[test]
+    fn test_format_rejects_invalid_code() {
+        match format_wgsl("fn broken( {") {
+            Err(crate::Error::ValidationError(result)) => {
+                assert!(!result.is_valid);
+                assert!(result.errors[0].starts_with("Parse error"));
+            }
+            other => panic!("expected a validation error, got {:?}", other),
+        }
+        assert_eq!(format_wgsl_or_original("fn broken( {"), "fn broken( {");
+    }
+}
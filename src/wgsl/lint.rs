@@ -0,0 +1,368 @@
+//! Style and correctness lints for WGSL
+//!
+//! Rules that need semantic information (bindings, uniformity) run on naga's
+//! validated module; purely syntactic rules run on the token stream so they can
+//! report what was actually written.
+
+use super::format::{lex, Kind, Token};
+use super::ValidationResult;
+use crate::config::{LintConfig, LintLevel, LintRule};
+use naga::valid::{Capabilities, FunctionInfo, ModuleInfo, ValidationFlags, Validator};
+use naga::{Block, Expression, Function, Module, SampleLevel, Statement};
+use std::fmt;
+
+/// A single lint finding
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LintDiagnostic {
+    pub rule: LintRule,
+    pub level: LintLevel,
+    /// 1-based source line
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for LintDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: [{}] {}", self.line, self.rule, self.message)
+    }
+}
+
+/// Run every enabled rule over `code`, sorted by line
+///
+/// Fails if the code does not parse or validate.
+pub fn lint_wgsl(code: &str, config: &LintConfig) -> crate::Result<Vec<LintDiagnostic>> {
+    let module = naga::front::wgsl::parse_str(code)
+        .map_err(|e| ValidationResult::failed(format!("Parse error: {}", e)))?;
+    let info = Validator::new(ValidationFlags::all(), Capabilities::all())
+        .validate(&module)
+        .map_err(|e| ValidationResult::failed(format!("Validation error: {:?}", e)))?;
+    let tokens: Vec<Token> = lex(code)
+        .into_iter()
+        .filter(|token| !token.is_comment())
+        .collect();
+
+    let mut findings = Vec::new();
+    unused_bindings(code, &module, &info, &mut findings);
+    unused_variables(&tokens, &mut findings);
+    magic_workgroup_sizes(&tokens, &mut findings);
+    missing_storage_access(&tokens, &mut findings);
+    non_uniform_samples(code, &module, &info, &mut findings);
+
+    let mut diagnostics: Vec<LintDiagnostic> = findings
+        .into_iter()
+        .filter_map(|(rule, line, message)| {
+            let level = config.level(rule);
+            (level != LintLevel::Allow).then_some(LintDiagnostic {
+                rule,
+                level,
+                line,
+                message,
+            })
+        })
+        .collect();
+    diagnostics.sort_by_key(|d| (d.line, d.rule));
+    Ok(diagnostics)
+}
+
+type Finding = (LintRule, usize, String);
+
+fn unused_bindings(code: &str, module: &Module, info: &ModuleInfo, out: &mut Vec<Finding>) {
+    for (handle, var) in module.global_variables.iter() {
+        let Some(binding) = &var.binding else {
+            continue;
+        };
+        let used = (0..module.entry_points.len())
+            .any(|index| !info.get_entry_point(index)[handle].is_empty());
+        if !used {
+            out.push((
+                LintRule::UnusedBinding,
+                line_of(code, module.global_variables.get_span(handle)),
+                format!(
+                    "@group({}) @binding({}) '{}' is not used by any entry point",
+                    binding.group,
+                    binding.binding,
+                    var.name.as_deref().unwrap_or("<unnamed>")
+                ),
+            ));
+        }
+    }
+}
+
+fn unused_variables(tokens: &[Token], out: &mut Vec<Finding>) {
+    let mut i = 0;
+    while i < tokens.len() {
+        if !(tokens[i].kind == Kind::Ident && tokens[i].text == "fn") {
+            i += 1;
+            continue;
+        }
+        let Some(open) = (i..tokens.len()).find(|&j| tokens[j].is("{")) else {
+            break;
+        };
+        let close = matching_brace(tokens, open);
+        let body = &tokens[open..close];
+
+        for (j, token) in body.iter().enumerate() {
+            let declares =
+                token.kind == Kind::Ident && matches!(token.text.as_str(), "let" | "var");
+            let Some(name) = body.get(j + 1).filter(|t| t.kind == Kind::Ident) else {
+                continue;
+            };
+            if !declares || name.text.starts_with('_') {
+                continue;
+            }
+            let uses = body
+                .iter()
+                .enumerate()
+                .filter(|(k, t)| {
+                    t.kind == Kind::Ident && t.text == name.text && !(*k > 0 && body[k - 1].is("."))
+                })
+                .count();
+            if uses == 1 {
+                out.push((
+                    LintRule::UnusedVariable,
+                    name.line,
+                    format!("variable '{}' is never used", name.text),
+                ));
+            }
+        }
+        i = close;
+    }
+}
+
+fn magic_workgroup_sizes(tokens: &[Token], out: &mut Vec<Finding>) {
+    for (i, token) in tokens.iter().enumerate() {
+        let is_attribute = token.kind == Kind::Ident
+            && token.text == "workgroup_size"
+            && i > 0
+            && tokens[i - 1].is("@");
+        if !is_attribute {
+            continue;
+        }
+        let literal = tokens[i + 1..]
+            .iter()
+            .take_while(|t| !t.is(")"))
+            .any(|t| t.kind == Kind::Number);
+        if literal {
+            out.push((
+                LintRule::MagicWorkgroupSize,
+                token.line,
+                "workgroup size uses literal values; prefer a named const or override".to_string(),
+            ));
+        }
+    }
+}
+
+fn missing_storage_access(tokens: &[Token], out: &mut Vec<Finding>) {
+    for window in tokens.windows(5) {
+        let matches = window[0].kind == Kind::Ident
+            && window[0].text == "var"
+            && window[1].is("<")
+            && window[2].text == "storage"
+            && window[3].is(">");
+        if matches {
+            out.push((
+                LintRule::MissingStorageAccess,
+                window[0].line,
+                format!(
+                    "storage buffer '{}' has no access mode; write `var<storage, read>` explicitly",
+                    window[4].text
+                ),
+            ));
+        }
+    }
+}
+
+fn non_uniform_samples(code: &str, module: &Module, info: &ModuleInfo, out: &mut Vec<Finding>) {
+    let functions = module
+        .functions
+        .iter()
+        .map(|(handle, function)| (function, &info[handle]))
+        .chain(
+            module
+                .entry_points
+                .iter()
+                .enumerate()
+                .map(|(index, ep)| (&ep.function, info.get_entry_point(index))),
+        );
+    for (function, function_info) in functions {
+        check_block(code, function, function_info, &function.body, false, out);
+    }
+}
+
+fn check_block(
+    code: &str,
+    function: &Function,
+    info: &FunctionInfo,
+    block: &Block,
+    non_uniform: bool,
+    out: &mut Vec<Finding>,
+) {
+    let is_non_uniform =
+        |expr: naga::Handle<Expression>| info[expr].uniformity.non_uniform_result.is_some();
+
+    for statement in block.iter() {
+        match statement {
+            Statement::Emit(range) if non_uniform => {
+                for handle in range.clone() {
+                    if let Expression::ImageSample {
+                        level: SampleLevel::Auto | SampleLevel::Bias(_),
+                        ..
+                    } = function.expressions[handle]
+                    {
+                        out.push((
+                            LintRule::NonUniformTextureSample,
+                            line_of(code, function.expressions.get_span(handle)),
+                            "textureSample under non-uniform control flow has undefined derivatives; \
+                             use textureSampleLevel or sample before branching"
+                                .to_string(),
+                        ));
+                    }
+                }
+            }
+            Statement::Block(inner) => check_block(code, function, info, inner, non_uniform, out),
+            Statement::If {
+                condition,
+                accept,
+                reject,
+            } => {
+                let branch = non_uniform || is_non_uniform(*condition);
+                check_block(code, function, info, accept, branch, out);
+                check_block(code, function, info, reject, branch, out);
+            }
+            Statement::Switch { selector, cases } => {
+                let branch = non_uniform || is_non_uniform(*selector);
+                for case in cases {
+                    check_block(code, function, info, &case.body, branch, out);
+                }
+            }
+            Statement::Loop {
+                body, continuing, ..
+            } => {
+                check_block(code, function, info, body, non_uniform, out);
+                check_block(code, function, info, continuing, non_uniform, out);
+            }
+            _ => {}
+        }
+    }
+}
+
+fn line_of(code: &str, span: naga::Span) -> usize {
+    if span.is_defined() {
+        span.location(code).line_number as usize
+    } else {
+        1
+    }
+}
+
+/// Index of the `}` closing the `{` at `open`
+fn matching_brace(tokens: &[Token], open: usize) -> usize {
+    let mut depth = 0;
+    for (i, token) in tokens.iter().enumerate().skip(open) {
+        if token.is("{") {
+            depth += 1;
+        } else if token.is("}") {
+            depth -= 1;
+            if depth == 0 {
+                return i;
+            }
+        }
+    }
+    tokens.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wgsl::{TemplateParams, TemplateRegistry};
+
+    const NOISY: &str = r#"@group(0) @binding(0) var<storage> input: array<f32>;
+@group(0) @binding(1) var<storage, read_write> output: array<f32>;
+@group(0) @binding(2) var<uniform> unused_scale: f32;
+
+@compute @workgroup_size(64)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let scale = 2.0;
+    var _scratch = 0.0;
+    let value = input[id.x];
+    output[id.x] = value;
+}
+"#;
+
+    const BRANCHY_SAMPLE: &str = r#"@group(0) @binding(0) var t: texture_2d<f32>;
+@group(0) @binding(1) var s: sampler;
+
+@fragment
+fn fs_main(@location(0) uv: vec2<f32>) -> @location(0) vec4<f32> {
+    var color = vec4<f32>(0.0);
+    if uv.x > 0.5 {
+        color = textureSample(t, s, uv);
+    }
+    return color + textureSample(t, s, uv);
+}
+"#;
+
+    fn rules(diagnostics: &[LintDiagnostic]) -> Vec<(LintRule, usize)> {
+        diagnostics.iter().map(|d| (d.rule, d.line)).collect()
+    }
+
+    #[test]
+    fn test_lint_rules() {
+        let diagnostics = lint_wgsl(NOISY, &LintConfig::default()).unwrap();
+        assert_eq!(
+            rules(&diagnostics),
+            vec![
+                (LintRule::MissingStorageAccess, 1),
+                (LintRule::UnusedBinding, 3),
+                (LintRule::UnusedVariable, 7),
+            ]
+        );
+        assert!(diagnostics[2].message.contains("'scale'"));
+
+        let diagnostics = lint_wgsl(BRANCHY_SAMPLE, &LintConfig::default()).unwrap();
+        assert_eq!(
+            rules(&diagnostics),
+            vec![(LintRule::NonUniformTextureSample, 8)]
+        );
+        assert_eq!(diagnostics[0].level, LintLevel::Deny);
+    }
+
+    #[test]
+    fn test_lint_levels() {
+        let mut config = LintConfig::default();
+        config
+            .levels
+            .insert(LintRule::MagicWorkgroupSize, LintLevel::Warn);
+        config
+            .levels
+            .insert(LintRule::UnusedVariable, LintLevel::Allow);
+        config
+            .levels
+            .insert(LintRule::UnusedBinding, LintLevel::Deny);
+
+        let diagnostics = lint_wgsl(NOISY, &config).unwrap();
+        assert_eq!(
+            rules(&diagnostics),
+            vec![
+                (LintRule::MissingStorageAccess, 1),
+                (LintRule::UnusedBinding, 3),
+                (LintRule::MagicWorkgroupSize, 5),
+            ]
+        );
+        assert_eq!(diagnostics[1].level, LintLevel::Deny);
+    }
+
+    #[test]
+    fn test_templates_are_lint_clean() {
+        for template in TemplateRegistry::builtin().templates() {
+            let code = template.render(&TemplateParams::default()).unwrap();
+            let diagnostics = lint_wgsl(&code, &LintConfig::default()).unwrap();
+            assert!(
+                diagnostics.is_empty(),
+                "{}: {:?}",
+                template.name,
+                diagnostics
+            );
+        }
+        assert!(lint_wgsl("fn broken( {", &LintConfig::default()).is_err());
+    }
+}
@@ -1,12 +1,47 @@
 //! WGSL validation and template generation using naga
 
+use crate::config::LintConfig;
 use naga::front::wgsl;
+use serde::{Deserialize, Serialize};
 use std::path::Path;
 
+pub mod batch;
+pub mod diff;
+pub mod format;
+pub mod lint;
+pub mod profile;
+pub mod reflect;
+pub mod repair;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod runner;
+pub mod similarity;
+pub mod templates;
+pub mod transpile;
+pub mod watch;
+
+pub use batch::{validate_directory, BatchReport, DirectorySummary, FileValidation};
+pub use diff::{diff_wgsl, ShaderDiff};
+pub use format::{format_wgsl, format_wgsl_or_original};
+pub use lint::{lint_wgsl, LintDiagnostic};
+pub use profile::ValidationProfile;
+pub use reflect::{reflect, BindingInfo, EntryPointInfo, ResourceKind, ShaderReflection, Stage};
+pub use repair::{repair_wgsl, RepairKind, RepairResult};
+#[cfg(not(target_arch = "wasm32"))]
+pub use runner::{BufferKind, RunOutput, ShaderBuffer, ShaderRunner};
+pub use similarity::compare;
+pub use templates::{ParamSlot, ScalarType, Template, TemplateParams, TemplateRegistry};
+pub use transpile::{ShaderTarget, TranspiledShader, WGSLTranspiler};
+pub use watch::{DirectoryWatcher, WatchChanges};
+
 /// WGSL validator using naga
+#[derive(Debug, Clone)]
 pub struct WGSLValidator {
     /// Whether to show warnings
     pub show_warnings: bool,
+    /// Capabilities and limits shaders are checked against
+    pub profile: ValidationProfile,
+    /// Lint rule levels used by [`WGSLValidator::lint`]
+    pub lint: LintConfig,
 }
 
 impl WGSLValidator {
@@ -14,9 +49,17 @@ impl WGSLValidator {
     pub fn new() -> Self {
         Self {
             show_warnings: true,
+            profile: ValidationProfile::default(),
+            lint: LintConfig::default(),
         }
     }
 
+    /// Use a different validation profile
+    pub fn with_profile(mut self, profile: ValidationProfile) -> Self {
+        self.profile = profile;
+        self
+    }
+
     /// Validate WGSL code
     pub fn validate(&self, code: &str) -> crate::Result<ValidationResult> {
         match wgsl::parse_str(code) {
@@ -24,30 +67,39 @@ impl WGSLValidator {
                 // Perform validation
                 match naga::valid::Validator::new(
                     naga::valid::ValidationFlags::all(),
-                    naga::valid::Capabilities::all(),
+                    self.profile.capabilities,
                 )
                 .validate(&module)
                 {
-                    Ok(_) => Ok(ValidationResult {
-                        is_valid: true,
-                        errors: Vec::new(),
-                        warnings: Vec::new(),
-                    }),
-                    Err(e) => Ok(ValidationResult {
-                        is_valid: false,
-                        errors: vec![format!("Validation error: {:?}", e)],
-                        warnings: Vec::new(),
-                    }),
+                    Ok(info) => {
+                        let errors = self.profile.check_limits(&module, &info);
+                        Ok(ValidationResult {
+                            is_valid: errors.is_empty(),
+                            errors,
+                            warnings: Vec::new(),
+                        })
+                    }
+                    Err(e) => Ok(ValidationResult::invalid(format!(
+                        "Validation error: {:?}",
+                        e
+                    ))),
                 }
             }
-            Err(e) => Ok(ValidationResult {
-                is_valid: false,
-                errors: vec![format!("Parse error: {}", e)],
-                warnings: Vec::new(),
-            }),
+            Err(e) => Ok(ValidationResult::invalid(format!("Parse error: {}", e))),
         }
     }
 
+    /// Use different lint rule levels
+    pub fn with_lint_config(mut self, lint: LintConfig) -> Self {
+        self.lint = lint;
+        self
+    }
+
+    /// Lint WGSL code for style issues and suspicious constructs
+    pub fn lint(&self, code: &str) -> crate::Result<Vec<LintDiagnostic>> {
+        lint_wgsl(code, &self.lint)
+    }
+
     /// Validate WGSL code from file
     pub fn validate_file<P: AsRef<Path>>(&self, path: P) -> crate::Result<ValidationResult> {
         let code = std::fs::read_to_string(path)?;
@@ -62,7 +114,7 @@ impl Default for WGSLValidator {
 }
 
 /// Validation result
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ValidationResult {
     pub is_valid: bool,
     pub errors: Vec<String>,
@@ -70,6 +122,21 @@ pub struct ValidationResult {
 }
 
 impl ValidationResult {
+    /// Result of code failing with `error`
+    pub fn invalid(error: String) -> Self {
+        Self {
+            is_valid: false,
+            errors: vec![error],
+            warnings: Vec::new(),
+        }
+    }
+
+    /// [`Error::ValidationError`](crate::Error::ValidationError) for code
+    /// failing with `error`
+    pub fn failed(error: String) -> crate::Error {
+        crate::Error::ValidationError(Self::invalid(error))
+    }
+
     /// Print validation results
     pub fn print(&self) {
         if self.is_valid {
@@ -96,89 +163,28 @@ pub struct ChromaticTemplate;
 impl ChromaticTemplate {
     /// Generate chromatic mix operation
     pub fn mix() -> String {
-        r#"// Chromatic mix operation - additive coherence
-@group(0) @binding(0) var<storage, read> tensor_a: array<vec4<f32>>;
-@group(0) @binding(1) var<storage, read> tensor_b: array<vec4<f32>>;
-@group(0) @binding(2) var<storage, read_write> output: array<vec4<f32>>;
-
-@compute @workgroup_size(8, 8, 1)
-fn chromatic_mix(@builtin(global_invocation_id) id: vec3<u32>) {
-    let idx = id.x + id.y * 8u;
-    let a = tensor_a[idx];
-    let b = tensor_b[idx];
-
-    // Additive blend and normalize
-    let mixed = normalize(a.rgb + b.rgb);
-    let certainty = (a.w + b.w) * 0.5;
-
-    output[idx] = vec4<f32>(mixed, certainty);
-}
-"#
-        .to_string()
+        Self::render("mix")
     }
 
     /// Generate chromatic filter operation
     pub fn filter() -> String {
-        r#"// Chromatic filter operation - subtractive distinction
-@group(0) @binding(0) var<storage, read> tensor_a: array<vec4<f32>>;
-@group(0) @binding(1) var<storage, read> tensor_b: array<vec4<f32>>;
-@group(0) @binding(2) var<storage, read_write> output: array<vec4<f32>>;
-
-@compute @workgroup_size(8, 8, 1)
-fn chromatic_filter(@builtin(global_invocation_id) id: vec3<u32>) {
-    let idx = id.x + id.y * 8u;
-    let a = tensor_a[idx];
-    let b = tensor_b[idx];
-
-    // Subtractive blend, clamped to [0, 1]
-    let filtered = clamp(a.rgb - b.rgb, vec3<f32>(0.0), vec3<f32>(1.0));
-
-    output[idx] = vec4<f32>(filtered, a.w);
-}
-"#
-        .to_string()
+        Self::render("filter")
     }
 
     /// Generate chromatic complement operation
     pub fn complement() -> String {
-        r#"// Chromatic complement operation - 180° hue rotation
-@group(0) @binding(0) var<storage, read> tensor: array<vec4<f32>>;
-@group(0) @binding(1) var<storage, read_write> output: array<vec4<f32>>;
-
-@compute @workgroup_size(8, 8, 1)
-fn chromatic_complement(@builtin(global_invocation_id) id: vec3<u32>) {
-    let idx = id.x + id.y * 8u;
-    let color = tensor[idx];
-
-    // Invert green and blue channels (hue rotation)
-    let complement = vec3<f32>(color.r, 1.0 - color.g, 1.0 - color.b);
-
-    output[idx] = vec4<f32>(complement, color.w);
-}
-"#
-        .to_string()
+        Self::render("complement")
     }
 
     /// Generate chromatic saturate operation
     pub fn saturate() -> String {
-        r#"// Chromatic saturate operation - adjust saturation
-@group(0) @binding(0) var<storage, read> tensor: array<vec4<f32>>;
-@group(0) @binding(1) var<storage, read_write> output: array<vec4<f32>>;
-@group(0) @binding(2) var<uniform> alpha: f32;
-
-@compute @workgroup_size(8, 8, 1)
-fn chromatic_saturate(@builtin(global_invocation_id) id: vec3<u32>) {
-    let idx = id.x + id.y * 8u;
-    let color = tensor[idx];
-
-    // Calculate mean and scale distance from mean
-    let mean = (color.r + color.g + color.b) / 3.0;
-    let saturated = mean + alpha * (color.rgb - vec3<f32>(mean));
+        Self::render("saturate")
+    }
 
-    output[idx] = vec4<f32>(clamp(saturated, vec3<f32>(0.0), vec3<f32>(1.0)), color.w);
-}
-"#
-        .to_string()
+    fn render(name: &str) -> String {
+        TemplateRegistry::builtin()
+            .render(name, &TemplateParams::default())
+            .expect("built-in templates render with default parameters")
     }
 }
 
@@ -0,0 +1,232 @@
+//! Capability and limit profiles for WGSL validation
+
+use crate::config::{ValidationConfig, ValidationLimits, ValidationProfileKind};
+use naga::valid::{Capabilities, ModuleInfo};
+use naga::{AddressSpace, Module, ShaderStage};
+use std::str::FromStr;
+
+/// Capabilities and limits a shader must fit within
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationProfile {
+    pub kind: ValidationProfileKind,
+    pub capabilities: Capabilities,
+    pub limits: ValidationLimits,
+}
+
+impl ValidationProfile {
+    /// WebGPU baseline: no optional features, default WebGPU limits
+    pub fn webgpu_core() -> Self {
+        Self {
+            kind: ValidationProfileKind::WebgpuCore,
+            capabilities: Capabilities::CUBE_ARRAY_TEXTURES,
+            limits: ValidationLimits::webgpu(),
+        }
+    }
+
+    /// Every naga capability with desktop GPU limits
+    pub fn native_extended() -> Self {
+        Self {
+            kind: ValidationProfileKind::NativeExtended,
+            capabilities: Capabilities::all(),
+            limits: ValidationLimits::native(),
+        }
+    }
+
+    /// Custom profile starting from no capabilities and WebGPU limits
+    pub fn custom() -> Self {
+        Self {
+            kind: ValidationProfileKind::Custom,
+            capabilities: Capabilities::empty(),
+            limits: ValidationLimits::webgpu(),
+        }
+    }
+
+    /// Build a profile from the `[validation]` config section
+    pub fn from_config(config: &ValidationConfig) -> crate::Result<Self> {
+        let mut profile = match config.profile {
+            ValidationProfileKind::WebgpuCore => Self::webgpu_core(),
+            ValidationProfileKind::NativeExtended => Self::native_extended(),
+            ValidationProfileKind::Custom => Self::custom(),
+        };
+        for name in &config.capabilities {
+            profile.capabilities |= parse_capability(name)?;
+        }
+        if let Some(limits) = config.limits {
+            profile.limits = limits;
+        }
+        Ok(profile)
+    }
+
+    /// Profile name as used in config files and on the command line
+    pub fn name(&self) -> &'static str {
+        match self.kind {
+            ValidationProfileKind::WebgpuCore => "webgpu-core",
+            ValidationProfileKind::NativeExtended => "native-extended",
+            ValidationProfileKind::Custom => "custom",
+        }
+    }
+
+    /// Check a validated module against the profile's limits
+    pub(super) fn check_limits(&self, module: &Module, info: &ModuleInfo) -> Vec<String> {
+        let limits = &self.limits;
+        let mut errors = Vec::new();
+
+        for (_, var) in module.global_variables.iter() {
+            if let Some(binding) = &var.binding {
+                if binding.group >= limits.max_bind_groups {
+                    errors.push(format!(
+                        "Limit exceeded: @group({}) on '{}' but at most {} bind groups are allowed",
+                        binding.group,
+                        var.name.as_deref().unwrap_or("<unnamed>"),
+                        limits.max_bind_groups
+                    ));
+                }
+            }
+        }
+
+        for (index, ep) in module.entry_points.iter().enumerate() {
+            if ep.stage == ShaderStage::Compute {
+                let size = ep.workgroup_size;
+                for (axis, (&value, &max)) in ["x", "y", "z"]
+                    .iter()
+                    .zip(size.iter().zip(&limits.max_workgroup_size))
+                {
+                    if value > max {
+                        errors.push(format!(
+                            "Limit exceeded: '{}' workgroup size {} is {} (max {})",
+                            ep.name, axis, value, max
+                        ));
+                    }
+                }
+                let invocations: u64 = size.iter().map(|&v| u64::from(v)).product();
+                if invocations > u64::from(limits.max_invocations_per_workgroup) {
+                    errors.push(format!(
+                        "Limit exceeded: '{}' uses {} invocations per workgroup (max {})",
+                        ep.name, invocations, limits.max_invocations_per_workgroup
+                    ));
+                }
+            }
+
+            let usage = info.get_entry_point(index);
+            let (mut storage, mut uniform) = (0, 0);
+            for (handle, var) in module.global_variables.iter() {
+                if usage[handle].is_empty() {
+                    continue;
+                }
+                match var.space {
+                    AddressSpace::Storage { .. } => storage += 1,
+                    AddressSpace::Uniform => uniform += 1,
+                    _ => {}
+                }
+            }
+            if storage > limits.max_storage_buffers_per_stage {
+                errors.push(format!(
+                    "Limit exceeded: '{}' uses {} storage buffers (max {})",
+                    ep.name, storage, limits.max_storage_buffers_per_stage
+                ));
+            }
+            if uniform > limits.max_uniform_buffers_per_stage {
+                errors.push(format!(
+                    "Limit exceeded: '{}' uses {} uniform buffers (max {})",
+                    ep.name, uniform, limits.max_uniform_buffers_per_stage
+                ));
+            }
+        }
+
+        errors
+    }
+}
+
+impl Default for ValidationProfile {
+    fn default() -> Self {
+        Self::native_extended()
+    }
+}
+
+impl FromStr for ValidationProfile {
+    type Err = crate::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "webgpu-core" => Ok(Self::webgpu_core()),
+            "native-extended" => Ok(Self::native_extended()),
+            "custom" => Ok(Self::custom()),
+            other => Err(crate::Error::ConfigError(format!(
+                "Unknown validation profile '{}'. Must be one of: webgpu-core, native-extended, custom",
+                other
+            ))),
+        }
+    }
+}
+
+/// Parse a naga capability name such as `float64` or `push-constant`
+fn parse_capability(name: &str) -> crate::Result<Capabilities> {
+    let flag = name.trim().to_uppercase().replace('-', "_");
+    Capabilities::from_name(&flag)
+        .ok_or_else(|| crate::Error::ConfigError(format!("Unknown shader capability '{}'", name)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wgsl::{ChromaticTemplate, WGSLValidator};
+
+    const FLOAT64_SHADER: &str = "fn double(x: f64) -> f64 { return x * 2.0lf; }";
+
+    #[test]
+    fn test_profile_capabilities() {
+        let native = WGSLValidator::new();
+        let webgpu = WGSLValidator::new().with_profile(ValidationProfile::webgpu_core());
+
+        assert!(native.validate(FLOAT64_SHADER).unwrap().is_valid);
+        assert!(!webgpu.validate(FLOAT64_SHADER).unwrap().is_valid);
+        assert!(webgpu.validate(&ChromaticTemplate::mix()).unwrap().is_valid);
+    }
+
+    #[test]
+    fn test_profile_limits() {
+        let code =
+            ChromaticTemplate::mix().replace("@workgroup_size(8, 8, 1)", "@workgroup_size(512)");
+        let native = WGSLValidator::new();
+        let webgpu = WGSLValidator::new().with_profile(ValidationProfile::webgpu_core());
+
+        assert!(native.validate(&code).unwrap().is_valid);
+
+        let result = webgpu.validate(&code).unwrap();
+        assert!(!result.is_valid);
+        assert!(result
+            .errors
+            .iter()
+            .any(|e| e.contains("workgroup size x is 512")));
+        assert!(result.errors.iter().any(|e| e.contains("512 invocations")));
+    }
+
+    #[test]
+    fn test_profile_from_config() {
+        let config = ValidationConfig {
+            profile: ValidationProfileKind::Custom,
+            capabilities: vec!["float64".to_string()],
+            limits: Some(ValidationLimits {
+                max_bind_groups: 1,
+                ..ValidationLimits::webgpu()
+            }),
+        };
+        let profile = ValidationProfile::from_config(&config).unwrap();
+        assert_eq!(profile.capabilities, Capabilities::FLOAT64);
+
+        let validator = WGSLValidator::new().with_profile(profile);
+        assert!(validator.validate(FLOAT64_SHADER).unwrap().is_valid);
+
+        let grouped =
+            ChromaticTemplate::mix().replace("@group(0) @binding(2)", "@group(1) @binding(0)");
+        assert!(!validator.validate(&grouped).unwrap().is_valid);
+
+        let bad = ValidationConfig {
+            capabilities: vec!["warp-drive".to_string()],
+            ..ValidationConfig::default()
+        };
+        assert!(ValidationProfile::from_config(&bad).is_err());
+        assert!("webgpu-core".parse::<ValidationProfile>().is_ok());
+        assert!("webgpu".parse::<ValidationProfile>().is_err());
+    }
+}
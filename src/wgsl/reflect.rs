@@ -0,0 +1,257 @@
+//! Shader reflection: entry points, workgroup sizes and resource bindings
+
+use super::ValidationResult;
+use naga::valid::{Capabilities, ValidationFlags, Validator};
+use naga::{AddressSpace, ImageClass, StorageAccess, TypeInner};
+use serde::{Deserialize, Serialize};
+
+/// Pipeline stage of an entry point
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Stage {
+    Vertex,
+    Fragment,
+    Compute,
+}
+
+impl Stage {
+    /// Lowercase name, as in `@compute`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Stage::Vertex => "vertex",
+            Stage::Fragment => "fragment",
+            Stage::Compute => "compute",
+        }
+    }
+}
+
+impl std::str::FromStr for Stage {
+    type Err = crate::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "vertex" => Ok(Stage::Vertex),
+            "fragment" => Ok(Stage::Fragment),
+            "compute" => Ok(Stage::Compute),
+            other => Err(crate::Error::ConfigError(format!(
+                "Unknown shader stage '{}'. Must be one of: vertex, fragment, compute",
+                other
+            ))),
+        }
+    }
+}
+
+impl From<naga::ShaderStage> for Stage {
+    fn from(stage: naga::ShaderStage) -> Self {
+        match stage {
+            naga::ShaderStage::Vertex => Stage::Vertex,
+            naga::ShaderStage::Fragment => Stage::Fragment,
+            naga::ShaderStage::Compute => Stage::Compute,
+        }
+    }
+}
+
+/// Entry point declared in a shader
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EntryPointInfo {
+    pub name: String,
+    pub stage: Stage,
+    /// `@workgroup_size` for compute entry points
+    pub workgroup_size: Option<[u32; 3]>,
+    /// `(group, binding)` pairs the entry point actually accesses
+    pub bindings: Vec<(u32, u32)>,
+}
+
+/// Kind of resource bound at a `@group`/`@binding`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResourceKind {
+    Uniform,
+    Storage { writable: bool },
+    Texture,
+    StorageTexture,
+    Sampler,
+    Other,
+}
+
+/// Resource binding declared in a shader
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BindingInfo {
+    pub group: u32,
+    pub binding: u32,
+    pub name: Option<String>,
+    pub kind: ResourceKind,
+    /// Type as written in WGSL, e.g. `array<vec4<f32>>`
+    pub ty: String,
+    /// Minimum buffer size in bytes (one element for runtime-sized arrays)
+    pub min_size: u32,
+}
+
+/// Reflection data for a WGSL module
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ShaderReflection {
+    pub entry_points: Vec<EntryPointInfo>,
+    pub bindings: Vec<BindingInfo>,
+}
+
+impl ShaderReflection {
+    /// Look up an entry point by name
+    pub fn entry_point(&self, name: &str) -> Option<&EntryPointInfo> {
+        self.entry_points.iter().find(|ep| ep.name == name)
+    }
+
+    /// Look up the binding at `group`/`binding`
+    pub fn binding(&self, group: u32, binding: u32) -> Option<&BindingInfo> {
+        self.bindings
+            .iter()
+            .find(|b| b.group == group && b.binding == binding)
+    }
+}
+
+/// Parse and validate WGSL, returning its entry points and resource bindings
+pub fn reflect(code: &str) -> crate::Result<ShaderReflection> {
+    let module = naga::front::wgsl::parse_str(code)
+        .map_err(|e| ValidationResult::failed(format!("Parse error: {}", e)))?;
+    let info = Validator::new(ValidationFlags::all(), Capabilities::all())
+        .validate(&module)
+        .map_err(|e| ValidationResult::failed(format!("Validation error: {:?}", e)))?;
+    let gctx = module.to_ctx();
+
+    let mut bindings: Vec<BindingInfo> = module
+        .global_variables
+        .iter()
+        .filter_map(|(_, var)| {
+            let binding = var.binding.as_ref()?;
+            let inner = &module.types[var.ty].inner;
+            Some(BindingInfo {
+                group: binding.group,
+                binding: binding.binding,
+                name: var.name.clone(),
+                kind: resource_kind(var.space, inner),
+                ty: var.ty.to_wgsl(&gctx),
+                min_size: inner.size(gctx),
+            })
+        })
+        .collect();
+    bindings.sort_by_key(|b| (b.group, b.binding));
+
+    let entry_points = module
+        .entry_points
+        .iter()
+        .enumerate()
+        .map(|(index, ep)| {
+            let usage = info.get_entry_point(index);
+            let mut used: Vec<(u32, u32)> = module
+                .global_variables
+                .iter()
+                .filter(|(handle, _)| !usage[*handle].is_empty())
+                .filter_map(|(_, var)| var.binding.as_ref().map(|b| (b.group, b.binding)))
+                .collect();
+            used.sort_unstable();
+
+            EntryPointInfo {
+                name: ep.name.clone(),
+                stage: ep.stage.into(),
+                workgroup_size: (ep.stage == naga::ShaderStage::Compute)
+                    .then_some(ep.workgroup_size),
+                bindings: used,
+            }
+        })
+        .collect();
+
+    Ok(ShaderReflection {
+        entry_points,
+        bindings,
+    })
+}
+
+fn resource_kind(space: AddressSpace, inner: &TypeInner) -> ResourceKind {
+    match space {
+        AddressSpace::Uniform => ResourceKind::Uniform,
+        AddressSpace::Storage { access } => ResourceKind::Storage {
+            writable: access.contains(StorageAccess::STORE),
+        },
+        AddressSpace::Handle => match inner {
+            TypeInner::Image {
+                class: ImageClass::Storage { .. },
+                ..
+            } => ResourceKind::StorageTexture,
+            TypeInner::Image { .. } => ResourceKind::Texture,
+            TypeInner::Sampler { .. } => ResourceKind::Sampler,
+            _ => ResourceKind::Other,
+        },
+        _ => ResourceKind::Other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wgsl::ChromaticTemplate;
+
+    #[test]
+    fn test_reflect_compute_template() {
+        let reflection = reflect(&ChromaticTemplate::saturate()).unwrap();
+
+        let ep = reflection.entry_point("chromatic_saturate").unwrap();
+        assert_eq!(ep.stage, Stage::Compute);
+        assert_eq!(ep.workgroup_size, Some([8, 8, 1]));
+        assert_eq!(ep.bindings, vec![(0, 0), (0, 1), (0, 2)]);
+
+        let input = reflection.binding(0, 0).unwrap();
+        assert_eq!(input.name.as_deref(), Some("tensor"));
+        assert_eq!(input.kind, ResourceKind::Storage { writable: false });
+        assert_eq!(input.ty, "array<vec4<f32>>");
+        assert_eq!(input.min_size, 16);
+
+        assert_eq!(
+            reflection.binding(0, 1).unwrap().kind,
+            ResourceKind::Storage { writable: true }
+        );
+
+        let alpha = reflection.binding(0, 2).unwrap();
+        assert_eq!(alpha.kind, ResourceKind::Uniform);
+        assert_eq!(alpha.ty, "f32");
+        assert_eq!(alpha.min_size, 4);
+    }
+
+    #[test]
+    fn test_reflect_render_pipeline() {
+        let code = r#"
+@group(0) @binding(0) var color_texture: texture_2d<f32>;
+@group(0) @binding(1) var color_sampler: sampler;
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    return vec4<f32>(f32(index), 0.0, 0.0, 1.0);
+}
+
+@fragment
+fn fs_main(@builtin(position) pos: vec4<f32>) -> @location(0) vec4<f32> {
+    return textureSample(color_texture, color_sampler, pos.xy);
+}
+"#;
+        let reflection = reflect(code).unwrap();
+
+        let vs = reflection.entry_point("vs_main").unwrap();
+        assert_eq!(vs.stage, Stage::Vertex);
+        assert_eq!(vs.workgroup_size, None);
+        assert!(vs.bindings.is_empty());
+
+        let fs = reflection.entry_point("fs_main").unwrap();
+        assert_eq!(fs.bindings, vec![(0, 0), (0, 1)]);
+        assert_eq!(
+            reflection.binding(0, 0).unwrap().kind,
+            ResourceKind::Texture
+        );
+        assert_eq!(
+            reflection.binding(0, 1).unwrap().kind,
+            ResourceKind::Sampler
+        );
+    }
+
+    #[test]
+    fn test_reflect_invalid_code() {
+        assert!(reflect("fn main( {").is_err());
+    }
+}
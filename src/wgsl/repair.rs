@@ -0,0 +1,472 @@
+//! Automatic repair of common mistakes in generated WGSL
+//!
+//! The passes here target errors the model makes frequently and that can be fixed
+//! mechanically: Rust-style literal suffixes, unbalanced braces, missing statement
+//! semicolons and fragment outputs without `@location`.
+
+use super::{ValidationResult, WGSLValidator};
+use regex::Regex;
+use std::fmt;
+use std::sync::OnceLock;
+
+/// A single kind of repair
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RepairKind {
+    /// `1.0f32` → `1.0f`, `2u32` → `2u`
+    LiteralSuffix,
+    /// Missing closing braces appended, unmatched ones removed
+    UnbalancedBraces,
+    /// `;` appended to statements that lack one
+    MissingSemicolon,
+    /// `@location(0)` added to fragment return types
+    FragmentLocation,
+}
+
+impl RepairKind {
+    /// Every repair, in the order passes are applied
+    pub const ALL: [RepairKind; 4] = [
+        RepairKind::LiteralSuffix,
+        RepairKind::UnbalancedBraces,
+        RepairKind::MissingSemicolon,
+        RepairKind::FragmentLocation,
+    ];
+
+    /// Run this repair pass over `code`
+    pub fn apply(&self, code: &str) -> String {
+        match self {
+            RepairKind::LiteralSuffix => fix_literal_suffixes(code),
+            RepairKind::UnbalancedBraces => balance_braces(code),
+            RepairKind::MissingSemicolon => add_missing_semicolons(code),
+            RepairKind::FragmentLocation => add_fragment_locations(code),
+        }
+    }
+}
+
+impl fmt::Display for RepairKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let description = match self {
+            RepairKind::LiteralSuffix => "fixed literal suffixes",
+            RepairKind::UnbalancedBraces => "balanced braces",
+            RepairKind::MissingSemicolon => "added missing semicolons",
+            RepairKind::FragmentLocation => "added @location to fragment output",
+        };
+        f.write_str(description)
+    }
+}
+
+/// Outcome of a repair attempt
+#[derive(Debug, Clone)]
+pub struct RepairResult {
+    /// Code after all applied repairs
+    pub code: String,
+    /// Repairs that changed the code, in application order
+    pub repairs: Vec<RepairKind>,
+    /// Validation of the repaired code
+    pub validation: ValidationResult,
+}
+
+impl RepairResult {
+    /// Whether any repair was applied
+    pub fn is_repaired(&self) -> bool {
+        !self.repairs.is_empty()
+    }
+}
+
+/// Repair common mistakes in `code` and revalidate it.
+///
+/// Valid code is returned unchanged. Otherwise each pass is applied in turn until
+/// the code validates or all passes have run.
+pub fn repair_wgsl(code: &str, validator: &WGSLValidator) -> crate::Result<RepairResult> {
+    let mut validation = validator.validate(code)?;
+    let mut current = code.to_string();
+    let mut repairs = Vec::new();
+
+    for kind in RepairKind::ALL {
+        if validation.is_valid {
+            break;
+        }
+        let repaired = kind.apply(&current);
+        if repaired != current {
+            tracing::debug!("WGSL repair: {}", kind);
+            current = repaired;
+            repairs.push(kind);
+            validation = validator.validate(&current)?;
+        }
+    }
+
+    Ok(RepairResult {
+        code: current,
+        repairs,
+        validation,
+    })
+}
+
+/// Replace comment contents with spaces so passes only see code.
+/// Byte offsets are preserved; comments are ASCII-delimited so slicing stays valid.
+fn mask_comments(code: &str) -> String {
+    let bytes = code.as_bytes();
+    let mut masked = bytes.to_vec();
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if bytes[i] == b'/' && bytes.get(i + 1) == Some(&b'/') {
+            while i < bytes.len() && bytes[i] != b'\n' {
+                masked[i] = b' ';
+                i += 1;
+            }
+        } else if bytes[i] == b'/' && bytes.get(i + 1) == Some(&b'*') {
+            let mut depth = 0;
+            while i < bytes.len() {
+                if bytes[i] == b'/' && bytes.get(i + 1) == Some(&b'*') {
+                    depth += 1;
+                    masked[i] = b' ';
+                    masked[i + 1] = b' ';
+                    i += 2;
+                } else if bytes[i] == b'*' && bytes.get(i + 1) == Some(&b'/') {
+                    depth -= 1;
+                    masked[i] = b' ';
+                    masked[i + 1] = b' ';
+                    i += 2;
+                    if depth == 0 {
+                        break;
+                    }
+                } else {
+                    if bytes[i] != b'\n' {
+                        masked[i] = b' ';
+                    }
+                    i += 1;
+                }
+            }
+        } else {
+            i += 1;
+        }
+    }
+
+    // Every byte of a comment (including multi-byte characters) becomes a space
+    String::from_utf8(masked).expect("masking whole comments keeps UTF-8 valid")
+}
+
+fn literal_suffix_regex() -> &'static Regex {
+    static REGEX: OnceLock<Regex> = OnceLock::new();
+    REGEX.get_or_init(|| {
+        Regex::new(r"\b(\d+(?:\.\d*)?(?:[eE][+-]?\d+)?)_?(f32|f64|f16|i32|i64|u32|u64)\b")
+            .expect("valid literal suffix regex")
+    })
+}
+
+/// Rewrite Rust-style numeric suffixes into WGSL ones
+fn fix_literal_suffixes(code: &str) -> String {
+    let masked = mask_comments(code);
+    let mut result = String::with_capacity(code.len());
+    let mut last = 0;
+
+    for captures in literal_suffix_regex().captures_iter(&masked) {
+        let whole = captures.get(0).expect("capture 0 is the full match");
+        let number = &captures[1];
+        let is_float = number.contains(['.', 'e', 'E']);
+        let suffix = match &captures[2] {
+            "f32" | "f64" => "f",
+            "f16" => "h",
+            // Integer suffixes are invalid on float literals; drop them
+            _ if is_float => "",
+            "i32" | "i64" => "i",
+            _ => "u",
+        };
+
+        result.push_str(&code[last..whole.start()]);
+        result.push_str(number);
+        result.push_str(suffix);
+        last = whole.end();
+    }
+
+    result.push_str(&code[last..]);
+    result
+}
+
+/// Append missing closing braces and drop unmatched ones
+fn balance_braces(code: &str) -> String {
+    let masked = mask_comments(code);
+    let mut depth = 0usize;
+    let mut unmatched = Vec::new();
+
+    for (index, byte) in masked.bytes().enumerate() {
+        match byte {
+            b'{' => depth += 1,
+            b'}' if depth == 0 => unmatched.push(index),
+            b'}' => depth -= 1,
+            _ => {}
+        }
+    }
+
+    if depth == 0 && unmatched.is_empty() {
+        return code.to_string();
+    }
+
+    let mut result: String = code
+        .char_indices()
+        .filter(|(index, _)| !unmatched.contains(index))
+        .map(|(_, ch)| ch)
+        .collect();
+
+    if depth > 0 {
+        let trimmed = result.trim_end().len();
+        result.truncate(trimmed);
+        for level in (0..depth).rev() {
+            result.push('\n');
+            result.push_str(&"    ".repeat(level));
+            result.push('}');
+        }
+        result.push('\n');
+    }
+
+    result
+}
+
+/// Keywords that begin a line which never ends in `;`
+const BLOCK_HEADERS: &[&str] = &[
+    "if",
+    "else",
+    "for",
+    "while",
+    "loop",
+    "switch",
+    "case",
+    "default",
+    "fn",
+    "struct",
+    "continuing",
+];
+
+/// Keywords that begin a top-level declaration ending in `;`
+const TOP_LEVEL_DECLARATIONS: &[&str] = &["var", "const", "override", "alias"];
+
+fn first_word(line: &str) -> &str {
+    let end = line
+        .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .unwrap_or(line.len());
+    &line[..end]
+}
+
+/// Strip leading `@attr(...)` attributes from a line
+fn skip_attributes(mut line: &str) -> &str {
+    while let Some(rest) = line.strip_prefix('@') {
+        let name_len = first_word(rest).len();
+        let mut rest = rest[name_len..].trim_start();
+        if rest.starts_with('(') {
+            let mut depth = 0;
+            let mut end = rest.len();
+            for (index, ch) in rest.char_indices() {
+                match ch {
+                    '(' => depth += 1,
+                    ')' => {
+                        depth -= 1;
+                        if depth == 0 {
+                            end = index + 1;
+                            break;
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            rest = &rest[end..];
+        }
+        line = rest.trim_start();
+    }
+    line
+}
+
+/// Append `;` to statement lines that end without one
+fn add_missing_semicolons(code: &str) -> String {
+    let masked = mask_comments(code);
+    let masked_lines: Vec<&str> = masked.lines().collect();
+    let lines: Vec<&str> = code.lines().collect();
+
+    // Brace stack: `true` for struct bodies, whose members end in `,`
+    let mut braces: Vec<bool> = Vec::new();
+    let mut parens = 0i32;
+    let mut result = Vec::with_capacity(lines.len());
+
+    for (index, line) in lines.iter().enumerate() {
+        let masked_line = masked_lines.get(index).copied().unwrap_or("");
+        let trimmed = masked_line.trim();
+
+        let depth_before = braces.len();
+        let in_struct = braces.last().copied().unwrap_or(false);
+        for ch in masked_line.chars() {
+            match ch {
+                '{' => braces.push(first_word(skip_attributes(trimmed)) == "struct"),
+                '}' => {
+                    braces.pop();
+                }
+                '(' => parens += 1,
+                ')' => parens -= 1,
+                _ => {}
+            }
+        }
+
+        let statement = skip_attributes(trimmed);
+        let keyword = first_word(statement);
+        let ends_open = trimmed.ends_with(|c: char| {
+            !(c.is_alphanumeric() || c == '_' || c == ')' || c == ']' || c == '>')
+        });
+        let next_continues = masked_lines[index + 1..]
+            .iter()
+            .map(|l| l.trim())
+            .find(|l| !l.is_empty())
+            .is_some_and(|next| {
+                next.starts_with(|c: char| ".+-*/%&|^)],=<>{?".contains(c))
+                    || first_word(next) == "else"
+            });
+
+        let needs_semicolon = !trimmed.is_empty()
+            && !ends_open
+            && parens == 0
+            && !next_continues
+            && !BLOCK_HEADERS.contains(&keyword)
+            && !statement.is_empty()
+            && if depth_before == 0 {
+                TOP_LEVEL_DECLARATIONS.contains(&keyword)
+            } else {
+                !in_struct && braces.len() == depth_before
+            };
+
+        if needs_semicolon {
+            // Insert after the last code character, before any trailing comment
+            let code_end = masked_line.trim_end().len();
+            result.push(format!("{};{}", &line[..code_end], &line[code_end..]));
+        } else {
+            result.push(line.to_string());
+        }
+    }
+
+    let mut repaired = result.join("\n");
+    if code.ends_with('\n') {
+        repaired.push('\n');
+    }
+    repaired
+}
+
+/// Scalar and vector types a fragment entry point can return directly
+fn is_builtin_value_type(ty: &str) -> bool {
+    ["vec2", "vec3", "vec4", "f32", "f16", "i32", "u32"]
+        .iter()
+        .any(|prefix| ty.starts_with(prefix))
+}
+
+/// Add `@location(0)` to fragment return types that lack an attribute
+fn add_fragment_locations(code: &str) -> String {
+    let masked = mask_comments(code);
+    let mut insertions = Vec::new();
+    let mut search_from = 0;
+
+    while let Some(offset) = masked[search_from..].find("@fragment") {
+        let start = search_from + offset + "@fragment".len();
+        search_from = start;
+
+        let Some(fn_offset) = masked[start..].find("fn ") else {
+            break;
+        };
+        let Some(paren_offset) = masked[start + fn_offset..].find('(') else {
+            break;
+        };
+
+        // Find the parameter list's closing parenthesis
+        let open = start + fn_offset + paren_offset;
+        let mut depth = 0;
+        let mut close = None;
+        for (index, ch) in masked[open..].char_indices() {
+            match ch {
+                '(' => depth += 1,
+                ')' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        close = Some(open + index);
+                        break;
+                    }
+                }
+                _ => {}
+            }
+        }
+        let Some(close) = close else {
+            break;
+        };
+
+        let after = &masked[close + 1..];
+        let Some(arrow) = after.trim_start().strip_prefix("->") else {
+            continue;
+        };
+        let return_type = arrow.trim_start();
+        if !return_type.starts_with('@') && is_builtin_value_type(return_type) {
+            insertions.push(masked.len() - return_type.len());
+        }
+    }
+
+    let mut result = code.to_string();
+    for position in insertions.into_iter().rev() {
+        result.insert_str(position, "@location(0) ");
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_valid_code_is_untouched() {
+        let code = crate::wgsl::ChromaticTemplate::mix();
+        let result = repair_wgsl(&code, &WGSLValidator::new()).unwrap();
+
+        assert!(!result.is_repaired());
+        assert!(result.validation.is_valid);
+        assert_eq!(result.code, code);
+    }
+
+    #[test]
+    fn test_fix_literal_suffixes() {
+        assert_eq!(
+            fix_literal_suffixes("let a = 1.0f32 + 2.5_f64; let b = 3u32; // 4u32"),
+            "let a = 1.0f + 2.5f; let b = 3u; // 4u32"
+        );
+        assert_eq!(fix_literal_suffixes("let v = vec4f32;"), "let v = vec4f32;");
+    }
+
+    #[test]
+    fn test_balance_braces() {
+        assert_eq!(
+            balance_braces("fn main() {\n    if true {\n        let x = 1;"),
+            "fn main() {\n    if true {\n        let x = 1;\n    }\n}\n"
+        );
+        assert_eq!(balance_braces("fn main() {\n}\n}\n"), "fn main() {\n}\n\n");
+    }
+
+    #[test]
+    fn test_add_missing_semicolons() {
+        let code = "struct S {\n    a: f32,\n    b: f32\n}\n\
+            var<private> counter: u32\n\
+            fn main() {\n    let x = vec2<f32>(\n        1.0,\n        2.0)\n    let y = x\n        * 2.0 // scale\n    if y.x > 0.0 {\n        counter += 1u\n    }\n}\n";
+        let expected = "struct S {\n    a: f32,\n    b: f32\n}\n\
+            var<private> counter: u32;\n\
+            fn main() {\n    let x = vec2<f32>(\n        1.0,\n        2.0);\n    let y = x\n        * 2.0; // scale\n    if y.x > 0.0 {\n        counter += 1u;\n    }\n}\n";
+
+        assert_eq!(add_missing_semicolons(code), expected);
+    }
+
+    #[test]
+    fn test_add_fragment_locations() {
+        let code = "@fragment\nfn fs_main(@builtin(position) pos: vec4<f32>) -> vec4<f32> {\n    return pos;\n}\n";
+        let repaired = add_fragment_locations(code);
+
+        assert!(repaired.contains("-> @location(0) vec4<f32> {"));
+        assert_eq!(add_fragment_locations(&repaired), repaired);
+    }
+
+    #[test]
+    fn test_repair_generated_shader() {
+        let broken = "@fragment\nfn fs_main() -> vec4<f32> {\n    let r = 1.0f32\n    return vec4<f32>(r, 0.0, 0.0, 1.0)\n";
+        let result = repair_wgsl(broken, &WGSLValidator::new()).unwrap();
+
+        assert!(result.validation.is_valid, "{:?}", result.validation.errors);
+        assert_eq!(result.repairs, RepairKind::ALL);
+    }
+}
@@ -0,0 +1,355 @@
+//! Compute shader execution harness for functional correctness testing
+//!
+//! [`ShaderRunner`] compiles a WGSL compute shader on the first available GPU
+//! adapter, binds caller-supplied buffers, dispatches it and reads the writable
+//! buffers back to host memory.
+
+use super::{ValidationResult, WGSLValidator};
+use std::collections::BTreeMap;
+use wgpu::util::DeviceExt;
+
+/// How a buffer is bound to the shader
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BufferKind {
+    /// `var<storage, read>`
+    StorageRead,
+    /// `var<storage, read_write>`; contents are read back after dispatch
+    StorageReadWrite,
+    /// `var<uniform>`
+    Uniform,
+}
+
+/// Buffer bound at a given `@group`/`@binding`
+#[derive(Debug, Clone)]
+pub struct ShaderBuffer {
+    pub group: u32,
+    pub binding: u32,
+    pub kind: BufferKind,
+    pub contents: Vec<u8>,
+}
+
+impl ShaderBuffer {
+    /// Read-only storage buffer holding `f32` values
+    pub fn input_f32(group: u32, binding: u32, values: &[f32]) -> Self {
+        Self {
+            group,
+            binding,
+            kind: BufferKind::StorageRead,
+            contents: f32_to_bytes(values),
+        }
+    }
+
+    /// Zero-initialized read-write storage buffer with room for `len` `f32` values
+    pub fn output_f32(group: u32, binding: u32, len: usize) -> Self {
+        Self {
+            group,
+            binding,
+            kind: BufferKind::StorageReadWrite,
+            contents: vec![0; len * std::mem::size_of::<f32>()],
+        }
+    }
+
+    /// Uniform buffer with raw contents
+    pub fn uniform(group: u32, binding: u32, contents: Vec<u8>) -> Self {
+        Self {
+            group,
+            binding,
+            kind: BufferKind::Uniform,
+            contents,
+        }
+    }
+}
+
+/// Read-back contents of every read-write buffer after a dispatch
+#[derive(Debug, Clone, Default)]
+pub struct RunOutput {
+    pub buffers: BTreeMap<(u32, u32), Vec<u8>>,
+}
+
+impl RunOutput {
+    /// Raw bytes of the buffer at `group`/`binding`
+    pub fn bytes(&self, group: u32, binding: u32) -> Option<&[u8]> {
+        self.buffers.get(&(group, binding)).map(Vec::as_slice)
+    }
+
+    /// Contents of the buffer at `group`/`binding` interpreted as `f32` values
+    pub fn f32(&self, group: u32, binding: u32) -> Option<Vec<f32>> {
+        self.bytes(group, binding).map(bytes_to_f32)
+    }
+}
+
+/// Runs WGSL compute shaders on the GPU
+pub struct ShaderRunner {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    adapter_name: String,
+}
+
+impl ShaderRunner {
+    /// Create a runner on the first available adapter
+    pub fn new() -> crate::Result<Self> {
+        pollster::block_on(Self::new_async())
+    }
+
+    async fn new_async() -> crate::Result<Self> {
+        let instance = wgpu::Instance::default();
+        let adapter = instance
+            .request_adapter(&wgpu::RequestAdapterOptions::default())
+            .await
+            .ok_or_else(|| crate::Error::Other("No GPU adapter available".to_string()))?;
+
+        let (device, queue) = adapter
+            .request_device(&wgpu::DeviceDescriptor::default(), None)
+            .await
+            .map_err(|e| crate::Error::Other(format!("Failed to create GPU device: {}", e)))?;
+
+        Ok(Self {
+            device,
+            queue,
+            adapter_name: adapter.get_info().name,
+        })
+    }
+
+    /// Name of the adapter the runner executes on
+    pub fn adapter_name(&self) -> &str {
+        &self.adapter_name
+    }
+
+    /// Compile and dispatch a compute shader.
+    ///
+    /// When `entry_point` is `None` the first `@compute` entry point is used.
+    pub fn run(
+        &self,
+        code: &str,
+        entry_point: Option<&str>,
+        buffers: &[ShaderBuffer],
+        workgroups: [u32; 3],
+    ) -> crate::Result<RunOutput> {
+        let validation = WGSLValidator::new().validate(code)?;
+        if !validation.is_valid {
+            return Err(crate::Error::ValidationError(validation));
+        }
+        let entry_point = match entry_point {
+            Some(name) => name.to_string(),
+            None => first_compute_entry_point(code)?,
+        };
+
+        let module = self
+            .device
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("shader_runner"),
+                source: wgpu::ShaderSource::Wgsl(code.into()),
+            });
+
+        self.device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let pipeline = self
+            .device
+            .create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some("shader_runner"),
+                layout: None,
+                module: &module,
+                entry_point: &entry_point,
+            });
+
+        let gpu_buffers: Vec<wgpu::Buffer> = buffers
+            .iter()
+            .map(|buffer| {
+                let usage = match buffer.kind {
+                    BufferKind::StorageRead => wgpu::BufferUsages::STORAGE,
+                    BufferKind::StorageReadWrite => {
+                        wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC
+                    }
+                    BufferKind::Uniform => wgpu::BufferUsages::UNIFORM,
+                };
+                self.device
+                    .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                        label: Some("shader_runner_buffer"),
+                        contents: &padded(&buffer.contents),
+                        usage,
+                    })
+            })
+            .collect();
+
+        let mut groups: BTreeMap<u32, Vec<wgpu::BindGroupEntry>> = BTreeMap::new();
+        for (buffer, gpu_buffer) in buffers.iter().zip(&gpu_buffers) {
+            groups
+                .entry(buffer.group)
+                .or_default()
+                .push(wgpu::BindGroupEntry {
+                    binding: buffer.binding,
+                    resource: gpu_buffer.as_entire_binding(),
+                });
+        }
+        let bind_groups: Vec<(u32, wgpu::BindGroup)> = groups
+            .into_iter()
+            .map(|(group, entries)| {
+                let layout = pipeline.get_bind_group_layout(group);
+                let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some("shader_runner_bind_group"),
+                    layout: &layout,
+                    entries: &entries,
+                });
+                (group, bind_group)
+            })
+            .collect();
+
+        // Staging buffers for read-back
+        let staging: Vec<(usize, wgpu::Buffer)> = buffers
+            .iter()
+            .enumerate()
+            .filter(|(_, buffer)| buffer.kind == BufferKind::StorageReadWrite)
+            .map(|(index, _)| {
+                let staging = self.device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("shader_runner_staging"),
+                    size: gpu_buffers[index].size(),
+                    usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                });
+                (index, staging)
+            })
+            .collect();
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("shader_runner"),
+            });
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("shader_runner"),
+                timestamp_writes: None,
+            });
+            pass.set_pipeline(&pipeline);
+            for (group, bind_group) in &bind_groups {
+                pass.set_bind_group(*group, bind_group, &[]);
+            }
+            pass.dispatch_workgroups(workgroups[0], workgroups[1], workgroups[2]);
+        }
+        for (index, staging_buffer) in &staging {
+            let source = &gpu_buffers[*index];
+            encoder.copy_buffer_to_buffer(source, 0, staging_buffer, 0, source.size());
+        }
+        self.queue.submit(Some(encoder.finish()));
+
+        if let Some(error) = pollster::block_on(self.device.pop_error_scope()) {
+            return Err(crate::Error::Other(format!(
+                "GPU validation error: {}",
+                error
+            )));
+        }
+
+        let mut output = RunOutput::default();
+        for (index, staging_buffer) in &staging {
+            let slice = staging_buffer.slice(..);
+            let (sender, receiver) = std::sync::mpsc::channel();
+            slice.map_async(wgpu::MapMode::Read, move |result| {
+                let _ = sender.send(result);
+            });
+            self.device.poll(wgpu::Maintain::Wait);
+            receiver
+                .recv()
+                .map_err(|e| crate::Error::Other(format!("Buffer read-back failed: {}", e)))?
+                .map_err(|e| crate::Error::Other(format!("Buffer read-back failed: {}", e)))?;
+
+            let buffer = &buffers[*index];
+            let data = slice.get_mapped_range()[..buffer.contents.len()].to_vec();
+            staging_buffer.unmap();
+            output.buffers.insert((buffer.group, buffer.binding), data);
+        }
+
+        Ok(output)
+    }
+}
+
+/// Name of the first `@compute` entry point in a module
+fn first_compute_entry_point(code: &str) -> crate::Result<String> {
+    let module = naga::front::wgsl::parse_str(code)
+        .map_err(|e| ValidationResult::failed(format!("Parse error: {}", e)))?;
+    module
+        .entry_points
+        .iter()
+        .find(|ep| ep.stage == naga::ShaderStage::Compute)
+        .map(|ep| ep.name.clone())
+        .ok_or_else(|| crate::Error::Other("Shader has no compute entry point".to_string()))
+}
+
+/// Pad buffer contents to wgpu's 4-byte copy alignment (and a non-zero size)
+fn padded(contents: &[u8]) -> Vec<u8> {
+    let align = wgpu::COPY_BUFFER_ALIGNMENT as usize;
+    let len = contents.len().max(1).div_ceil(align) * align;
+    let mut data = contents.to_vec();
+    data.resize(len, 0);
+    data
+}
+
+fn f32_to_bytes(values: &[f32]) -> Vec<u8> {
+    values
+        .iter()
+        .flat_map(|value| value.to_le_bytes())
+        .collect()
+}
+
+fn bytes_to_f32(bytes: &[u8]) -> Vec<f32> {
+    bytes
+        .chunks_exact(4)
+        .map(|chunk| f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wgsl::ChromaticTemplate;
+
+    #[test]
+    fn test_buffer_helpers() {
+        let input = ShaderBuffer::input_f32(0, 1, &[1.0, -2.5]);
+        assert_eq!(input.contents.len(), 8);
+        assert_eq!(bytes_to_f32(&input.contents), vec![1.0, -2.5]);
+
+        let output = ShaderBuffer::output_f32(0, 2, 3);
+        assert_eq!(output.kind, BufferKind::StorageReadWrite);
+        assert_eq!(output.contents.len(), 12);
+
+        assert_eq!(padded(&[1, 2, 3]).len(), 4);
+        assert_eq!(padded(&[]).len(), 4);
+    }
+
+    #[test]
+    fn test_entry_point_detection() {
+        assert_eq!(
+            first_compute_entry_point(&ChromaticTemplate::mix()).unwrap(),
+            "chromatic_mix"
+        );
+    }
+
+    #[test]
+    fn test_run_chromatic_mix() {
+        // Skip on machines without a usable adapter
+        let Ok(runner) = ShaderRunner::new() else {
+            return;
+        };
+
+        let a = [1.0, 0.0, 0.0, 1.0];
+        let b = [0.0, 1.0, 0.0, 0.5];
+        let output = runner
+            .run(
+                &ChromaticTemplate::mix(),
+                None,
+                &[
+                    ShaderBuffer::input_f32(0, 0, &a),
+                    ShaderBuffer::input_f32(0, 1, &b),
+                    ShaderBuffer::output_f32(0, 2, 4),
+                ],
+                [1, 1, 1],
+            )
+            .unwrap();
+
+        let mixed = output.f32(0, 2).unwrap();
+        let expected = std::f32::consts::FRAC_1_SQRT_2;
+        assert!((mixed[0] - expected).abs() < 1e-5);
+        assert!((mixed[1] - expected).abs() < 1e-5);
+        assert!(mixed[2].abs() < 1e-5);
+        assert!((mixed[3] - 0.75).abs() < 1e-5);
+    }
+}
@@ -0,0 +1,229 @@
+//! Structural similarity between WGSL modules
+//!
+//! Both modules are parsed with naga and flattened into a sequence of IR node
+//! labels (statement kinds, expression kinds with their operators, normalized
+//! types). Identifiers and literal values never appear in the labels, so
+//! renaming a variable or changing a constant does not affect the score.
+
+use super::ValidationResult;
+use naga::{Expression, Function, Literal, Module, Statement, TypeInner};
+use std::collections::HashMap;
+
+/// Structural similarity of two WGSL modules in `[0, 1]`
+///
+/// The score is the Dice coefficient over the multisets of IR node labels and
+/// adjacent label pairs. Identical structure scores 1.0.
+pub fn compare(module_a: &str, module_b: &str) -> crate::Result<f64> {
+    let a = features(&parse(module_a)?);
+    let b = features(&parse(module_b)?);
+
+    let total: usize = a.values().sum::<usize>() + b.values().sum::<usize>();
+    if total == 0 {
+        return Ok(1.0);
+    }
+    let shared: usize = a
+        .iter()
+        .map(|(feature, &count)| count.min(b.get(feature).copied().unwrap_or(0)))
+        .sum();
+    Ok(2.0 * shared as f64 / total as f64)
+}
+
+fn parse(code: &str) -> crate::Result<Module> {
+    naga::front::wgsl::parse_str(code)
+        .map_err(|e| ValidationResult::failed(format!("Parse error: {}", e)))
+}
+
+/// Label unigrams and bigrams, counted
+fn features(module: &Module) -> HashMap<String, usize> {
+    let mut counts = HashMap::new();
+    for sequence in label_sequences(module) {
+        for (i, label) in sequence.iter().enumerate() {
+            *counts.entry(label.clone()).or_default() += 1;
+            if let Some(next) = sequence.get(i + 1) {
+                *counts.entry(format!("{} > {}", label, next)).or_default() += 1;
+            }
+        }
+    }
+    counts
+}
+
+/// One label sequence for the globals and one per function
+fn label_sequences(module: &Module) -> Vec<Vec<String>> {
+    let globals = module
+        .global_variables
+        .iter()
+        .map(|(_, var)| format!("global {:?} {}", var.space, type_label(module, var.ty)))
+        .collect();
+
+    let functions = module
+        .functions
+        .iter()
+        .map(|(_, function)| function_labels(module, function, "fn".to_string()))
+        .chain(
+            module
+                .entry_points
+                .iter()
+                .map(|ep| function_labels(module, &ep.function, format!("entry {:?}", ep.stage))),
+        );
+
+    std::iter::once(globals).chain(functions).collect()
+}
+
+fn function_labels(module: &Module, function: &Function, header: String) -> Vec<String> {
+    let mut labels = vec![header];
+    labels.extend(
+        function
+            .arguments
+            .iter()
+            .map(|arg| format!("arg {}", type_label(module, arg.ty))),
+    );
+    labels.extend(
+        function
+            .result
+            .iter()
+            .map(|result| format!("result {}", type_label(module, result.ty))),
+    );
+    labels.extend(
+        function
+            .local_variables
+            .iter()
+            .map(|(_, var)| format!("local {}", type_label(module, var.ty))),
+    );
+    labels.extend(
+        function
+            .expressions
+            .iter()
+            .map(|(_, expr)| expression_label(expr)),
+    );
+    statement_labels(&function.body, &mut labels);
+    labels
+}
+
+fn statement_labels(block: &naga::Block, labels: &mut Vec<String>) {
+    for statement in block.iter() {
+        // Emit only marks evaluation points; expressions are labelled separately
+        if matches!(statement, Statement::Emit(_)) {
+            continue;
+        }
+        labels.push(format!("stmt {}", variant_name(statement)));
+        match statement {
+            Statement::Block(inner) => statement_labels(inner, labels),
+            Statement::If { accept, reject, .. } => {
+                statement_labels(accept, labels);
+                labels.push("else".to_string());
+                statement_labels(reject, labels);
+            }
+            Statement::Switch { cases, .. } => {
+                for case in cases {
+                    labels.push("case".to_string());
+                    statement_labels(&case.body, labels);
+                }
+            }
+            Statement::Loop {
+                body, continuing, ..
+            } => {
+                statement_labels(body, labels);
+                labels.push("continuing".to_string());
+                statement_labels(continuing, labels);
+            }
+            _ => continue,
+        }
+        labels.push("end".to_string());
+    }
+}
+
+fn expression_label(expr: &Expression) -> String {
+    match expr {
+        Expression::Literal(literal) => format!("literal {}", literal_type(literal)),
+        Expression::Binary { op, .. } => format!("binary {:?}", op),
+        Expression::Unary { op, .. } => format!("unary {:?}", op),
+        Expression::Math { fun, .. } => format!("math {:?}", fun),
+        Expression::Relational { fun, .. } => format!("relational {:?}", fun),
+        Expression::As { kind, convert, .. } => format!("as {:?} {:?}", kind, convert),
+        other => format!("expr {}", variant_name(other)),
+    }
+}
+
+fn literal_type(literal: &Literal) -> &'static str {
+    match literal {
+        Literal::F64(_) => "f64",
+        Literal::F32(_) => "f32",
+        Literal::U32(_) => "u32",
+        Literal::I32(_) => "i32",
+        Literal::I64(_) => "i64",
+        Literal::Bool(_) => "bool",
+        Literal::AbstractInt(_) => "abstract-int",
+        Literal::AbstractFloat(_) => "abstract-float",
+    }
+}
+
+/// Type shape without struct or member names
+fn type_label(module: &Module, ty: naga::Handle<naga::Type>) -> String {
+    match &module.types[ty].inner {
+        TypeInner::Struct { members, .. } => {
+            let members: Vec<String> = members
+                .iter()
+                .map(|member| type_label(module, member.ty))
+                .collect();
+            format!("struct {{{}}}", members.join(", "))
+        }
+        TypeInner::Array { base, size, .. } => match size {
+            naga::ArraySize::Constant(_) => format!("array<{}, N>", type_label(module, *base)),
+            naga::ArraySize::Dynamic => format!("array<{}>", type_label(module, *base)),
+        },
+        _ => ty.to_wgsl(&module.to_ctx()),
+    }
+}
+
+/// Enum variant name from its `Debug` output
+fn variant_name<T: std::fmt::Debug>(value: &T) -> String {
+    let debug = format!("{:?}", value);
+    debug
+        .split(|c: char| !c.is_alphanumeric() && c != '_')
+        .next()
+        .unwrap_or_default()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wgsl::{ChromaticTemplate, TemplateParams, TemplateRegistry};
+
+    #[test]
+    fn test_identical_and_renamed() {
+        let mix = ChromaticTemplate::mix();
+        assert_eq!(compare(&mix, &mix).unwrap(), 1.0);
+
+        // Renamed identifiers and different literal values keep the structure
+        let renamed = mix
+            .replace("tensor_a", "lhs")
+            .replace("tensor_b", "rhs")
+            .replace("certainty", "w")
+            .replace("0.5", "0.25")
+            .replace("8u", "16u");
+        assert_eq!(compare(&mix, &renamed).unwrap(), 1.0);
+    }
+
+    #[test]
+    fn test_structural_distance() {
+        let registry = TemplateRegistry::builtin();
+        let render = |name| registry.render(name, &TemplateParams::default()).unwrap();
+
+        let mix_filter = compare(&render("mix"), &render("filter")).unwrap();
+        let mix_quad = compare(&render("mix"), &render("textured_quad")).unwrap();
+        assert!(mix_filter > 0.0 && mix_filter < 1.0);
+        assert!(mix_quad < mix_filter, "{} >= {}", mix_quad, mix_filter);
+
+        // Symmetric
+        assert_eq!(
+            compare(&render("filter"), &render("mix")).unwrap(),
+            mix_filter
+        );
+    }
+
+    #[test]
+    fn test_compare_invalid() {
+        assert!(compare("fn main( {", &ChromaticTemplate::mix()).is_err());
+    }
+}
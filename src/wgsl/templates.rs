@@ -0,0 +1,1069 @@
+//! Registry of parameterized WGSL templates with keyword lookup
+//!
+//! Template sources contain `{{slot}}` placeholders that are filled in from
+//! [`TemplateParams`] when rendering:
+//!
+//! - `{{workgroup_size}}`: `@workgroup_size` arguments, e.g. `8, 8, 1`
+//! - `{{workgroup_x}}`: first workgroup dimension
+//! - `{{workgroup_invocations}}`: product of the workgroup dimensions
+//! - `{{scalar}}`: scalar element type, e.g. `f32`
+//! - `{{vec}}`: element type with `channels` components, e.g. `vec4<f32>`
+//! - `{{channels}}`: number of components per element
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// Scalar element type of a template
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScalarType {
+    #[default]
+    F32,
+    I32,
+    U32,
+}
+
+impl ScalarType {
+    /// WGSL spelling of the type
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ScalarType::F32 => "f32",
+            ScalarType::I32 => "i32",
+            ScalarType::U32 => "u32",
+        }
+    }
+}
+
+impl fmt::Display for ScalarType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for ScalarType {
+    type Err = crate::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "f32" => Ok(ScalarType::F32),
+            "i32" => Ok(ScalarType::I32),
+            "u32" => Ok(ScalarType::U32),
+            other => Err(crate::Error::Other(format!(
+                "Unknown scalar type '{}'. Must be one of: f32, i32, u32",
+                other
+            ))),
+        }
+    }
+}
+
+/// Parameter a template can expose
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParamSlot {
+    WorkgroupSize,
+    ScalarType,
+    Channels,
+}
+
+impl fmt::Display for ParamSlot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ParamSlot::WorkgroupSize => "workgroup_size",
+            ParamSlot::ScalarType => "scalar_type",
+            ParamSlot::Channels => "channels",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Values substituted into a template's slots
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TemplateParams {
+    pub workgroup_size: [u32; 3],
+    pub scalar_type: ScalarType,
+    /// Components per element, 1 to 4
+    pub channels: u32,
+}
+
+impl Default for TemplateParams {
+    fn default() -> Self {
+        Self {
+            workgroup_size: [8, 8, 1],
+            scalar_type: ScalarType::F32,
+            channels: 4,
+        }
+    }
+}
+
+impl TemplateParams {
+    /// Parse a workgroup size written as `x`, `x,y` or `x,y,z` (or with `x`
+    /// separators, e.g. `16x16`); missing dimensions are 1
+    pub fn parse_workgroup_size(text: &str) -> crate::Result<[u32; 3]> {
+        let invalid = || {
+            crate::Error::Other(format!(
+                "Invalid workgroup size '{}'. Expected x, x,y or x,y,z",
+                text
+            ))
+        };
+        let dims = text
+            .split([',', 'x'])
+            .map(|dim| dim.trim().parse::<u32>().map_err(|_| invalid()))
+            .collect::<crate::Result<Vec<_>>>()?;
+        if dims.is_empty() || dims.len() > 3 {
+            return Err(invalid());
+        }
+        let mut size = [1; 3];
+        size[..dims.len()].copy_from_slice(&dims);
+        Ok(size)
+    }
+
+    /// Slots whose value differs from the default
+    fn customized(&self) -> Vec<ParamSlot> {
+        let default = Self::default();
+        let mut slots = Vec::new();
+        if self.workgroup_size != default.workgroup_size {
+            slots.push(ParamSlot::WorkgroupSize);
+        }
+        if self.scalar_type != default.scalar_type {
+            slots.push(ParamSlot::ScalarType);
+        }
+        if self.channels != default.channels {
+            slots.push(ParamSlot::Channels);
+        }
+        slots
+    }
+
+    /// WGSL type of one element, e.g. `vec4<f32>` or `f32` for a single channel
+    fn element_type(&self) -> String {
+        match self.channels {
+            1 => self.scalar_type.to_string(),
+            n => format!("vec{}<{}>", n, self.scalar_type),
+        }
+    }
+}
+
+/// A WGSL template with lookup keywords and parameter slots
+#[derive(Debug, Clone)]
+pub struct Template {
+    /// Unique name, e.g. "mix"
+    pub name: String,
+    /// One-line description
+    pub description: String,
+    /// Words and aliases that select this template from a prompt
+    pub keywords: Vec<String>,
+    /// Parameters the template accepts
+    pub slots: Vec<ParamSlot>,
+    /// Source with `{{slot}}` placeholders
+    pub source: String,
+}
+
+impl Template {
+    /// Render the template, rejecting parameters it does not expose
+    pub fn render(&self, params: &TemplateParams) -> crate::Result<String> {
+        if let Some(slot) = params
+            .customized()
+            .into_iter()
+            .find(|slot| !self.slots.contains(slot))
+        {
+            return Err(crate::Error::Other(format!(
+                "Template '{}' does not support parameter '{}'",
+                self.name, slot
+            )));
+        }
+        if !(1..=4).contains(&params.channels) {
+            return Err(crate::Error::Other(format!(
+                "Channel count must be between 1 and 4, got {}",
+                params.channels
+            )));
+        }
+        if params.workgroup_size.contains(&0) {
+            return Err(crate::Error::Other(
+                "Workgroup size dimensions must be non-zero".to_string(),
+            ));
+        }
+
+        let [x, y, z] = params.workgroup_size;
+        Ok(self
+            .source
+            .replace("{{workgroup_size}}", &format!("{}, {}, {}", x, y, z))
+            .replace("{{workgroup_x}}", &x.to_string())
+            .replace("{{workgroup_invocations}}", &(x * y * z).to_string())
+            .replace("{{scalar}}", params.scalar_type.as_str())
+            .replace("{{vec}}", &params.element_type())
+            .replace("{{channels}}", &params.channels.to_string()))
+    }
+
+    /// How well a prompt matches this template; 0 means no match
+    fn score(&self, words: &[String]) -> usize {
+        std::iter::once(&self.name)
+            .chain(&self.keywords)
+            .filter(|keyword| {
+                let parts: Vec<String> =
+                    keyword.split_whitespace().map(str::to_lowercase).collect();
+                words.windows(parts.len()).any(|window| {
+                    window
+                        .iter()
+                        .zip(&parts)
+                        .all(|(word, part)| matches_word(word, part))
+                })
+            })
+            .count()
+    }
+}
+
+/// Whether a prompt word is `keyword` itself or its plural; other forms,
+/// such as "blending" for "blend", need keywords of their own
+fn matches_word(word: &str, keyword: &str) -> bool {
+    match word.strip_prefix(keyword) {
+        Some(suffix) => matches!(suffix, "" | "s" | "es"),
+        None => false,
+    }
+}
+
+/// Collection of templates looked up by name or prompt keywords
+#[derive(Debug, Clone, Default)]
+pub struct TemplateRegistry {
+    templates: Vec<Template>,
+}
+
+impl TemplateRegistry {
+    /// Create an empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registry with all built-in templates
+    pub fn builtin() -> Self {
+        let mut registry = Self::new();
+        for template in builtin_templates() {
+            registry.register(template);
+        }
+        registry
+    }
+
+    /// Add a template, replacing any existing one with the same name
+    pub fn register(&mut self, template: Template) {
+        self.templates
+            .retain(|existing| existing.name != template.name);
+        self.templates.push(template);
+    }
+
+    /// Look up a template by name
+    pub fn get(&self, name: &str) -> Option<&Template> {
+        self.templates.iter().find(|template| template.name == name)
+    }
+
+    /// All registered templates in registration order
+    pub fn templates(&self) -> &[Template] {
+        &self.templates
+    }
+
+    /// Best-matching template for a natural language prompt
+    pub fn find(&self, prompt: &str) -> Option<&Template> {
+        let words: Vec<String> = prompt
+            .split(|c: char| !c.is_alphanumeric() && c != '_')
+            .filter(|word| !word.is_empty())
+            .map(str::to_lowercase)
+            .collect();
+
+        // `max_by_key` keeps the last maximum; reverse so earlier templates win ties
+        self.templates
+            .iter()
+            .rev()
+            .map(|template| (template.score(&words), template))
+            .filter(|(score, _)| *score > 0)
+            .max_by_key(|(score, _)| *score)
+            .map(|(_, template)| template)
+    }
+
+    /// Render a template by name
+    pub fn render(&self, name: &str, params: &TemplateParams) -> crate::Result<String> {
+        self.get(name)
+            .ok_or_else(|| crate::Error::Other(format!("Unknown template '{}'", name)))?
+            .render(params)
+    }
+}
+
+fn template(
+    name: &str,
+    description: &str,
+    keywords: &[&str],
+    slots: &[ParamSlot],
+    source: &str,
+) -> Template {
+    Template {
+        name: name.to_string(),
+        description: description.to_string(),
+        keywords: keywords.iter().map(|k| k.to_string()).collect(),
+        slots: slots.to_vec(),
+        source: source.to_string(),
+    }
+}
+
+fn builtin_templates() -> Vec<Template> {
+    vec![
+        template(
+            "mix",
+            "Chromatic mix - additive coherence",
+            &["blend", "blending", "combine", "additive"],
+            &[ParamSlot::WorkgroupSize],
+            CHROMATIC_MIX,
+        ),
+        template(
+            "filter",
+            "Chromatic filter - subtractive distinction",
+            &["subtract", "subtractive"],
+            &[ParamSlot::WorkgroupSize],
+            CHROMATIC_FILTER,
+        ),
+        template(
+            "complement",
+            "Chromatic complement - 180° hue rotation",
+            &["invert", "opposite", "hue rotation"],
+            &[ParamSlot::WorkgroupSize],
+            CHROMATIC_COMPLEMENT,
+        ),
+        template(
+            "saturate",
+            "Chromatic saturate - adjust saturation",
+            &["saturation", "vivid"],
+            &[ParamSlot::WorkgroupSize],
+            CHROMATIC_SATURATE,
+        ),
+        template(
+            "add",
+            "Element-wise addition of two buffers",
+            &["sum", "elementwise", "element wise"],
+            &[
+                ParamSlot::WorkgroupSize,
+                ParamSlot::ScalarType,
+                ParamSlot::Channels,
+            ],
+            ELEMENTWISE_ADD,
+        ),
+        template(
+            "reduce",
+            "Parallel sum reduction producing one partial sum per workgroup",
+            &["reduction", "parallel reduction", "total"],
+            &[ParamSlot::WorkgroupSize, ParamSlot::ScalarType],
+            PARALLEL_REDUCE,
+        ),
+        template(
+            "prefix_sum",
+            "Exclusive prefix sum within each workgroup, plus per-workgroup totals",
+            &["prefix", "prefix sum", "scan", "cumulative"],
+            &[ParamSlot::WorkgroupSize, ParamSlot::ScalarType],
+            PREFIX_SUM,
+        ),
+        template(
+            "convolve",
+            "2D convolution with a 3x3 kernel and clamp-to-edge borders",
+            &["convolution", "blur", "kernel", "gaussian"],
+            &[ParamSlot::WorkgroupSize, ParamSlot::Channels],
+            CONVOLVE_2D,
+        ),
+        template(
+            "matmul",
+            "Tiled matrix multiply with square workgroup_x tiles",
+            &["matrix multiply", "matrix multiplication", "gemm"],
+            &[ParamSlot::WorkgroupSize, ParamSlot::ScalarType],
+            TILED_MATMUL,
+        ),
+        template(
+            "passthrough",
+            "Vertex shader forwarding position and color to the fragment stage",
+            &["vertex", "pass through", "vertex color"],
+            &[],
+            PASSTHROUGH_VERTEX,
+        ),
+        template(
+            "textured_quad",
+            "Full-screen quad (triangle strip) sampling a texture",
+            &["texture", "textured", "quad", "sprite", "fullscreen"],
+            &[],
+            TEXTURED_QUAD,
+        ),
+        template(
+            "instanced",
+            "Instanced vertex transform with a per-instance model matrix",
+            &["instance", "instancing", "transform", "model matrix"],
+            &[],
+            INSTANCED_TRANSFORM,
+        ),
+        template(
+            "tone_map",
+            "HDR tone-mapping fragment shader with exposure and gamma",
+            &["tone map", "tonemap", "hdr", "exposure", "aces"],
+            &[],
+            TONE_MAP,
+        ),
+    ]
+}
+
+const CHROMATIC_MIX: &str = r#"// Chromatic mix operation - additive coherence
+@group(0) @binding(0) var<storage, read> tensor_a: array<vec4<f32>>;
+@group(0) @binding(1) var<storage, read> tensor_b: array<vec4<f32>>;
+@group(0) @binding(2) var<storage, read_write> output: array<vec4<f32>>;
+
+@compute @workgroup_size({{workgroup_size}})
+fn chromatic_mix(@builtin(global_invocation_id) id: vec3<u32>) {
+    let idx = id.x + id.y * {{workgroup_x}}u;
+    let a = tensor_a[idx];
+    let b = tensor_b[idx];
+
+    // Additive blend and normalize
+    let mixed = normalize(a.rgb + b.rgb);
+    let certainty = (a.w + b.w) * 0.5;
+
+    output[idx] = vec4<f32>(mixed, certainty);
+}
+"#;
+
+const CHROMATIC_FILTER: &str = r#"// Chromatic filter operation - subtractive distinction
+@group(0) @binding(0) var<storage, read> tensor_a: array<vec4<f32>>;
+@group(0) @binding(1) var<storage, read> tensor_b: array<vec4<f32>>;
+@group(0) @binding(2) var<storage, read_write> output: array<vec4<f32>>;
+
+@compute @workgroup_size({{workgroup_size}})
+fn chromatic_filter(@builtin(global_invocation_id) id: vec3<u32>) {
+    let idx = id.x + id.y * {{workgroup_x}}u;
+    let a = tensor_a[idx];
+    let b = tensor_b[idx];
+
+    // Subtractive blend, clamped to [0, 1]
+    let filtered = clamp(a.rgb - b.rgb, vec3<f32>(0.0), vec3<f32>(1.0));
+
+    output[idx] = vec4<f32>(filtered, a.w);
+}
+"#;
+
+const CHROMATIC_COMPLEMENT: &str = r#"// Chromatic complement operation - 180° hue rotation
+@group(0) @binding(0) var<storage, read> tensor: array<vec4<f32>>;
+@group(0) @binding(1) var<storage, read_write> output: array<vec4<f32>>;
+
+@compute @workgroup_size({{workgroup_size}})
+fn chromatic_complement(@builtin(global_invocation_id) id: vec3<u32>) {
+    let idx = id.x + id.y * {{workgroup_x}}u;
+    let color = tensor[idx];
+
+    // Invert green and blue channels (hue rotation)
+    let complement = vec3<f32>(color.r, 1.0 - color.g, 1.0 - color.b);
+
+    output[idx] = vec4<f32>(complement, color.w);
+}
+"#;
+
+const CHROMATIC_SATURATE: &str = r#"// Chromatic saturate operation - adjust saturation
+@group(0) @binding(0) var<storage, read> tensor: array<vec4<f32>>;
+@group(0) @binding(1) var<storage, read_write> output: array<vec4<f32>>;
+@group(0) @binding(2) var<uniform> alpha: f32;
+
+@compute @workgroup_size({{workgroup_size}})
+fn chromatic_saturate(@builtin(global_invocation_id) id: vec3<u32>) {
+    let idx = id.x + id.y * {{workgroup_x}}u;
+    let color = tensor[idx];
+
+    // Calculate mean and scale distance from mean
+    let mean = (color.r + color.g + color.b) / 3.0;
+    let saturated = mean + alpha * (color.rgb - vec3<f32>(mean));
+
+    output[idx] = vec4<f32>(clamp(saturated, vec3<f32>(0.0), vec3<f32>(1.0)), color.w);
+}
+"#;
+
+const ELEMENTWISE_ADD: &str = r#"// Element-wise addition of two {{channels}}-channel buffers
+@group(0) @binding(0) var<storage, read> input_a: array<{{vec}}>;
+@group(0) @binding(1) var<storage, read> input_b: array<{{vec}}>;
+@group(0) @binding(2) var<storage, read_write> output: array<{{vec}}>;
+
+@compute @workgroup_size({{workgroup_size}})
+fn elementwise_add(@builtin(global_invocation_id) id: vec3<u32>) {
+    let idx = id.x;
+    if idx >= arrayLength(&output) {
+        return;
+    }
+
+    output[idx] = input_a[idx] + input_b[idx];
+}
+"#;
+
+const PARALLEL_REDUCE: &str = r#"// Parallel sum reduction: each workgroup writes the sum of its slice
+@group(0) @binding(0) var<storage, read> input: array<{{scalar}}>;
+@group(0) @binding(1) var<storage, read_write> partial_sums: array<{{scalar}}>;
+
+var<workgroup> scratch: array<{{scalar}}, {{workgroup_invocations}}>;
+
+@compute @workgroup_size({{workgroup_size}})
+fn reduce_sum(@builtin(local_invocation_index) local: u32, @builtin(workgroup_id) group: vec3<u32>) {
+    let idx = group.x * {{workgroup_invocations}}u + local;
+    var value = {{scalar}}(0);
+    if idx < arrayLength(&input) {
+        value = input[idx];
+    }
+    scratch[local] = value;
+    workgroupBarrier();
+
+    // Fold the upper half onto the lower half until one value remains
+    for (var remaining = {{workgroup_invocations}}u; remaining > 1u; remaining = (remaining + 1u) / 2u) {
+        let half = (remaining + 1u) / 2u;
+        if local + half < remaining {
+            scratch[local] = scratch[local] + scratch[local + half];
+        }
+        workgroupBarrier();
+    }
+
+    if local == 0u {
+        partial_sums[group.x] = scratch[0];
+    }
+}
+"#;
+
+const PREFIX_SUM: &str = r#"// Exclusive prefix sum over each workgroup's slice of the input
+@group(0) @binding(0) var<storage, read> input: array<{{scalar}}>;
+@group(0) @binding(1) var<storage, read_write> output: array<{{scalar}}>;
+@group(0) @binding(2) var<storage, read_write> block_sums: array<{{scalar}}>;
+
+var<workgroup> scratch: array<{{scalar}}, {{workgroup_invocations}}>;
+
+@compute @workgroup_size({{workgroup_size}})
+fn prefix_sum(@builtin(local_invocation_index) local: u32, @builtin(workgroup_id) group: vec3<u32>) {
+    let idx = group.x * {{workgroup_invocations}}u + local;
+    var value = {{scalar}}(0);
+    if idx < arrayLength(&input) {
+        value = input[idx];
+    }
+    scratch[local] = value;
+    workgroupBarrier();
+
+    // Inclusive Hillis-Steele scan in workgroup memory
+    for (var offset = 1u; offset < {{workgroup_invocations}}u; offset = offset * 2u) {
+        var addend = {{scalar}}(0);
+        if local >= offset {
+            addend = scratch[local - offset];
+        }
+        workgroupBarrier();
+        scratch[local] = scratch[local] + addend;
+        workgroupBarrier();
+    }
+
+    // Shift right by one to make the scan exclusive
+    if idx < arrayLength(&output) {
+        output[idx] = scratch[local] - value;
+    }
+    if local == {{workgroup_invocations}}u - 1u {
+        block_sums[group.x] = scratch[local];
+    }
+}
+"#;
+
+const CONVOLVE_2D: &str = r#"// 2D convolution with a 3x3 kernel, clamping reads to the image edge
+struct ImageSize {
+    width: u32,
+    height: u32,
+}
+
+@group(0) @binding(0) var<storage, read> image: array<{{vec}}>;
+@group(0) @binding(1) var<storage, read> weights: array<f32, 9>;
+@group(0) @binding(2) var<storage, read_write> output: array<{{vec}}>;
+@group(0) @binding(3) var<uniform> size: ImageSize;
+
+@compute @workgroup_size({{workgroup_size}})
+fn convolve(@builtin(global_invocation_id) id: vec3<u32>) {
+    if id.x >= size.width || id.y >= size.height {
+        return;
+    }
+
+    var sum = {{vec}}(0.0);
+    for (var ky = 0u; ky < 3u; ky = ky + 1u) {
+        for (var kx = 0u; kx < 3u; kx = kx + 1u) {
+            let x = clamp(i32(id.x + kx) - 1, 0, i32(size.width) - 1);
+            let y = clamp(i32(id.y + ky) - 1, 0, i32(size.height) - 1);
+            sum = sum + image[u32(y) * size.width + u32(x)] * weights[ky * 3u + kx];
+        }
+    }
+
+    output[id.y * size.width + id.x] = sum;
+}
+"#;
+
+const TILED_MATMUL: &str = r#"// Tiled matrix multiply: out (m x n) = a (m x k) * b (k x n), row-major
+struct MatmulDims {
+    m: u32,
+    n: u32,
+    k: u32,
+}
+
+@group(0) @binding(0) var<storage, read> a: array<{{scalar}}>;
+@group(0) @binding(1) var<storage, read> b: array<{{scalar}}>;
+@group(0) @binding(2) var<storage, read_write> out: array<{{scalar}}>;
+@group(0) @binding(3) var<uniform> dims: MatmulDims;
+
+var<workgroup> tile_a: array<array<{{scalar}}, {{workgroup_x}}>, {{workgroup_x}}>;
+var<workgroup> tile_b: array<array<{{scalar}}, {{workgroup_x}}>, {{workgroup_x}}>;
+
+@compute @workgroup_size({{workgroup_x}}, {{workgroup_x}}, 1)
+fn matmul(@builtin(global_invocation_id) id: vec3<u32>, @builtin(local_invocation_id) local: vec3<u32>) {
+    let row = id.y;
+    let col = id.x;
+    var acc = {{scalar}}(0);
+
+    let tiles = (dims.k + {{workgroup_x}}u - 1u) / {{workgroup_x}}u;
+    for (var t = 0u; t < tiles; t = t + 1u) {
+        // Stage one tile of each operand, padding out-of-range reads with zero
+        let a_col = t * {{workgroup_x}}u + local.x;
+        let b_row = t * {{workgroup_x}}u + local.y;
+        var a_value = {{scalar}}(0);
+        var b_value = {{scalar}}(0);
+        if row < dims.m && a_col < dims.k {
+            a_value = a[row * dims.k + a_col];
+        }
+        if b_row < dims.k && col < dims.n {
+            b_value = b[b_row * dims.n + col];
+        }
+        tile_a[local.y][local.x] = a_value;
+        tile_b[local.y][local.x] = b_value;
+        workgroupBarrier();
+
+        for (var i = 0u; i < {{workgroup_x}}u; i = i + 1u) {
+            acc = acc + tile_a[local.y][i] * tile_b[i][local.x];
+        }
+        workgroupBarrier();
+    }
+
+    if row < dims.m && col < dims.n {
+        out[row * dims.n + col] = acc;
+    }
+}
+"#;
+
+const PASSTHROUGH_VERTEX: &str = r#"// Passthrough vertex shader: clip-space position and per-vertex color
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) color: vec4<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+}
+
+@vertex
+fn vs_main(input: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(input.position, 1.0);
+    out.color = input.color;
+    return out;
+}
+
+@fragment
+fn fs_main(input: VertexOutput) -> @location(0) vec4<f32> {
+    return input.color;
+}
+"#;
+
+const TEXTURED_QUAD: &str = r#"// Full-screen textured quad drawn as a 4-vertex triangle strip without a vertex buffer
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+@group(0) @binding(0) var quad_texture: texture_2d<f32>;
+@group(0) @binding(1) var quad_sampler: sampler;
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    // Corners (-1, -1), (1, -1), (-1, 1), (1, 1) in triangle strip order
+    let corner = vec2<f32>(f32(index & 1u), f32(index >> 1u)) * 2.0 - 1.0;
+
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(corner, 0.0, 1.0);
+    out.uv = vec2<f32>(corner.x * 0.5 + 0.5, 0.5 - corner.y * 0.5);
+    return out;
+}
+
+@fragment
+fn fs_main(input: VertexOutput) -> @location(0) vec4<f32> {
+    return textureSample(quad_texture, quad_sampler, input.uv);
+}
+"#;
+
+const INSTANCED_TRANSFORM: &str = r#"// Instanced vertex transform: per-instance model matrix and shared camera
+struct Camera {
+    view_proj: mat4x4<f32>,
+}
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+}
+
+// Model matrix columns supplied by an instance-rate vertex buffer
+struct InstanceInput {
+    @location(2) model_0: vec4<f32>,
+    @location(3) model_1: vec4<f32>,
+    @location(4) model_2: vec4<f32>,
+    @location(5) model_3: vec4<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) world_normal: vec3<f32>,
+}
+
+@group(0) @binding(0) var<uniform> camera: Camera;
+
+@vertex
+fn vs_main(vertex: VertexInput, instance: InstanceInput) -> VertexOutput {
+    let model = mat4x4<f32>(instance.model_0, instance.model_1, instance.model_2, instance.model_3);
+    let world_position = model * vec4<f32>(vertex.position, 1.0);
+
+    var out: VertexOutput;
+    out.clip_position = camera.view_proj * world_position;
+    out.world_normal = normalize((model * vec4<f32>(vertex.normal, 0.0)).xyz);
+    return out;
+}
+
+@fragment
+fn fs_main(input: VertexOutput) -> @location(0) vec4<f32> {
+    // Simple directional light so instances are distinguishable
+    let light = normalize(vec3<f32>(0.5, 1.0, 0.3));
+    let shade = 0.2 + 0.8 * max(dot(input.world_normal, light), 0.0);
+    return vec4<f32>(vec3<f32>(shade), 1.0);
+}
+"#;
+
+const TONE_MAP: &str = r#"// HDR tone mapping: exposure, ACES filmic curve and gamma correction
+struct ToneMapSettings {
+    exposure: f32,
+    gamma: f32,
+}
+
+@group(0) @binding(0) var hdr_texture: texture_2d<f32>;
+@group(0) @binding(1) var hdr_sampler: sampler;
+@group(0) @binding(2) var<uniform> settings: ToneMapSettings;
+
+// Narkowicz's fit of the ACES filmic curve
+fn aces(x: vec3<f32>) -> vec3<f32> {
+    let a = 2.51;
+    let b = 0.03;
+    let c = 2.43;
+    let d = 0.59;
+    let e = 0.14;
+    return clamp((x * (a * x + b)) / (x * (c * x + d) + e), vec3<f32>(0.0), vec3<f32>(1.0));
+}
+
+@fragment
+fn fs_main(@location(0) uv: vec2<f32>) -> @location(0) vec4<f32> {
+    let hdr = textureSample(hdr_texture, hdr_sampler, uv);
+    let mapped = aces(hdr.rgb * settings.exposure);
+    let corrected = pow(mapped, vec3<f32>(1.0 / settings.gamma));
+    return vec4<f32>(corrected, hdr.a);
+}
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wgsl::WGSLValidator;
+
+    #[test]
+    fn test_builtin_templates_validate() {
+        let registry = TemplateRegistry::builtin();
+        let validator = WGSLValidator::new();
+
+        for template in registry.templates() {
+            let code = template.render(&TemplateParams::default()).unwrap();
+            let result = validator.validate(&code).unwrap();
+            assert!(
+                result.is_valid,
+                "Template '{}' should be valid: {:?}",
+                template.name, result.errors
+            );
+            assert!(
+                !code.contains("{{"),
+                "Template '{}' left a placeholder",
+                template.name
+            );
+        }
+    }
+
+    #[test]
+    fn test_keyword_lookup() {
+        let registry = TemplateRegistry::builtin();
+
+        assert_eq!(registry.find("mix two colors").unwrap().name, "mix");
+        assert_eq!(registry.find("Blending shader").unwrap().name, "mix");
+        assert_eq!(
+            registry.find("increase the saturation").unwrap().name,
+            "saturate"
+        );
+        assert_eq!(
+            registry.find("apply a hue rotation").unwrap().name,
+            "complement"
+        );
+        assert_eq!(registry.find("element wise sum").unwrap().name, "add");
+        assert_eq!(
+            registry
+                .find("parallel reduction of a buffer")
+                .unwrap()
+                .name,
+            "reduce"
+        );
+        assert_eq!(registry.find("prefix sum").unwrap().name, "prefix_sum");
+        assert_eq!(
+            registry.find("gaussian blur an image").unwrap().name,
+            "convolve"
+        );
+        assert_eq!(
+            registry.find("tiled matrix multiplication").unwrap().name,
+            "matmul"
+        );
+        assert!(registry.find("draw a teapot").is_none());
+
+        // Whole words only, plus plurals
+        assert_eq!(registry.find("two blends").unwrap().name, "mix");
+        for prompt in ["address lookup", "additional summary", "summary"] {
+            assert!(registry.find(prompt).is_none(), "{} matched", prompt);
+        }
+    }
+
+    #[test]
+    fn test_render_params() {
+        let registry = TemplateRegistry::builtin();
+        let params = TemplateParams {
+            workgroup_size: [64, 1, 1],
+            scalar_type: ScalarType::U32,
+            channels: 2,
+        };
+
+        let code = registry.render("add", &params).unwrap();
+        assert!(code.contains("@workgroup_size(64, 1, 1)"));
+        assert!(code.contains("array<vec2<u32>>"));
+        assert!(WGSLValidator::new().validate(&code).unwrap().is_valid);
+
+        let scalar = TemplateParams {
+            channels: 1,
+            ..TemplateParams::default()
+        };
+        assert!(registry
+            .render("add", &scalar)
+            .unwrap()
+            .contains("array<f32>"));
+
+        // Chromatic templates only expose the workgroup size
+        assert!(registry.render("mix", &params).is_err());
+
+        assert_eq!(
+            TemplateParams::parse_workgroup_size("16").unwrap(),
+            [16, 1, 1]
+        );
+        assert_eq!(
+            TemplateParams::parse_workgroup_size("16x16").unwrap(),
+            [16, 16, 1]
+        );
+        assert_eq!(
+            TemplateParams::parse_workgroup_size("4, 4, 2").unwrap(),
+            [4, 4, 2]
+        );
+        assert!(TemplateParams::parse_workgroup_size("1,2,3,4").is_err());
+        assert!(TemplateParams::parse_workgroup_size("wide").is_err());
+        assert!(registry
+            .render("unknown", &TemplateParams::default())
+            .is_err());
+    }
+
+    #[test]
+    fn test_compute_templates_params_validate() {
+        let registry = TemplateRegistry::builtin();
+        let validator = WGSLValidator::new();
+
+        for (name, params) in [
+            (
+                "reduce",
+                TemplateParams {
+                    workgroup_size: [100, 1, 1],
+                    scalar_type: ScalarType::U32,
+                    ..TemplateParams::default()
+                },
+            ),
+            (
+                "prefix_sum",
+                TemplateParams {
+                    workgroup_size: [256, 1, 1],
+                    scalar_type: ScalarType::I32,
+                    ..TemplateParams::default()
+                },
+            ),
+            (
+                "convolve",
+                TemplateParams {
+                    workgroup_size: [16, 16, 1],
+                    channels: 1,
+                    ..TemplateParams::default()
+                },
+            ),
+            (
+                "matmul",
+                TemplateParams {
+                    workgroup_size: [16, 16, 1],
+                    scalar_type: ScalarType::I32,
+                    ..TemplateParams::default()
+                },
+            ),
+        ] {
+            let code = registry.render(name, &params).unwrap();
+            let result = validator.validate(&code).unwrap();
+            assert!(result.is_valid, "'{}': {:?}", name, result.errors);
+        }
+    }
+
+    #[test]
+    fn test_run_compute_templates() {
+        use crate::wgsl::{ShaderBuffer, ShaderRunner};
+
+        // Skip on machines without a usable adapter
+        let Ok(runner) = ShaderRunner::new() else {
+            return;
+        };
+        let registry = TemplateRegistry::builtin();
+        let params = TemplateParams::default();
+        let values: Vec<f32> = (1..=100).map(|v| v as f32).collect();
+
+        // 100 values over two 64-wide workgroups
+        let reduce = registry.render("reduce", &params).unwrap();
+        let output = runner
+            .run(
+                &reduce,
+                None,
+                &[
+                    ShaderBuffer::input_f32(0, 0, &values),
+                    ShaderBuffer::output_f32(0, 1, 2),
+                ],
+                [2, 1, 1],
+            )
+            .unwrap();
+        let sums = output.f32(0, 1).unwrap();
+        assert_eq!(
+            sums,
+            vec![(1..=64).sum::<u32>() as f32, (65..=100).sum::<u32>() as f32]
+        );
+
+        let scan = registry.render("prefix_sum", &params).unwrap();
+        let output = runner
+            .run(
+                &scan,
+                None,
+                &[
+                    ShaderBuffer::input_f32(0, 0, &values[..64]),
+                    ShaderBuffer::output_f32(0, 1, 64),
+                    ShaderBuffer::output_f32(0, 2, 1),
+                ],
+                [1, 1, 1],
+            )
+            .unwrap();
+        let prefix = output.f32(0, 1).unwrap();
+        assert_eq!(prefix[0], 0.0);
+        assert_eq!(prefix[1], 1.0);
+        assert_eq!(prefix[63], (1..=63).sum::<u32>() as f32);
+        assert_eq!(
+            output.f32(0, 2).unwrap(),
+            vec![(1..=64).sum::<u32>() as f32]
+        );
+
+        // 2x3 * 3x2 with an 8x8 tile
+        let matmul = registry.render("matmul", &params).unwrap();
+        let dims: Vec<u8> = [2u32, 2, 3, 0]
+            .iter()
+            .flat_map(|v| v.to_le_bytes())
+            .collect();
+        let output = runner
+            .run(
+                &matmul,
+                None,
+                &[
+                    ShaderBuffer::input_f32(0, 0, &[1.0, 2.0, 3.0, 4.0, 5.0, 6.0]),
+                    ShaderBuffer::input_f32(0, 1, &[1.0, 0.0, 0.0, 1.0, 1.0, 1.0]),
+                    ShaderBuffer::output_f32(0, 2, 4),
+                    ShaderBuffer::uniform(0, 3, dims),
+                ],
+                [1, 1, 1],
+            )
+            .unwrap();
+        assert_eq!(output.f32(0, 2).unwrap(), vec![4.0, 5.0, 10.0, 11.0]);
+
+        // Box blur of a single bright pixel in a 3x3 single-channel image
+        let convolve = registry
+            .render(
+                "convolve",
+                &TemplateParams {
+                    channels: 1,
+                    ..params
+                },
+            )
+            .unwrap();
+        let mut image = vec![0.0; 9];
+        image[4] = 9.0;
+        let size: Vec<u8> = [3u32, 3].iter().flat_map(|v| v.to_le_bytes()).collect();
+        let output = runner
+            .run(
+                &convolve,
+                None,
+                &[
+                    ShaderBuffer::input_f32(0, 0, &image),
+                    ShaderBuffer::input_f32(0, 1, &[1.0 / 9.0; 9]),
+                    ShaderBuffer::output_f32(0, 2, 9),
+                    ShaderBuffer::uniform(0, 3, size),
+                ],
+                [1, 1, 1],
+            )
+            .unwrap();
+        for value in output.f32(0, 2).unwrap() {
+            assert!((value - 1.0).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn test_render_pipeline_templates() {
+        use crate::wgsl::{reflect, Stage, ValidationProfile};
+
+        let registry = TemplateRegistry::builtin();
+        let webgpu = WGSLValidator::new().with_profile(ValidationProfile::webgpu_core());
+
+        for (name, stages) in [
+            ("passthrough", vec![Stage::Vertex, Stage::Fragment]),
+            ("textured_quad", vec![Stage::Vertex, Stage::Fragment]),
+            ("instanced", vec![Stage::Vertex, Stage::Fragment]),
+            ("tone_map", vec![Stage::Fragment]),
+        ] {
+            let code = registry.render(name, &TemplateParams::default()).unwrap();
+            let result = webgpu.validate(&code).unwrap();
+            assert!(result.is_valid, "'{}': {:?}", name, result.errors);
+
+            let reflection = reflect(&code).unwrap();
+            let found: Vec<Stage> = reflection.entry_points.iter().map(|ep| ep.stage).collect();
+            assert_eq!(found, stages, "{}", name);
+
+            // Render templates have no compute parameters to customize
+            let params = TemplateParams {
+                workgroup_size: [64, 1, 1],
+                ..TemplateParams::default()
+            };
+            assert!(registry.render(name, &params).is_err());
+        }
+
+        assert_eq!(
+            registry.find("render a textured sprite").unwrap().name,
+            "textured_quad"
+        );
+        assert_eq!(
+            registry.find("instanced vertex transform").unwrap().name,
+            "instanced"
+        );
+        assert_eq!(registry.find("HDR tone mapping").unwrap().name, "tone_map");
+        assert_eq!(
+            registry.find("simple vertex shader").unwrap().name,
+            "passthrough"
+        );
+    }
+}
@@ -0,0 +1,353 @@
+//! WGSL transpilation to other shading languages using naga backends
+
+use super::ValidationResult;
+use naga::back::{glsl, hlsl, msl, spv};
+use naga::valid::{Capabilities, ModuleInfo, ValidationFlags, Validator};
+use naga::{Module, ShaderStage};
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Output language for transpilation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShaderTarget {
+    SpirV,
+    Glsl,
+    Hlsl,
+    Msl,
+}
+
+impl ShaderTarget {
+    /// Conventional file extension for the target
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ShaderTarget::SpirV => "spv",
+            ShaderTarget::Glsl => "glsl",
+            ShaderTarget::Hlsl => "hlsl",
+            ShaderTarget::Msl => "metal",
+        }
+    }
+}
+
+impl FromStr for ShaderTarget {
+    type Err = crate::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "spirv" | "spir-v" | "spv" => Ok(ShaderTarget::SpirV),
+            "glsl" => Ok(ShaderTarget::Glsl),
+            "hlsl" => Ok(ShaderTarget::Hlsl),
+            "msl" | "metal" => Ok(ShaderTarget::Msl),
+            other => Err(crate::Error::Other(format!(
+                "Unknown shader target '{}'. Must be one of: spirv, glsl, hlsl, msl",
+                other
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for ShaderTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ShaderTarget::SpirV => "SPIR-V",
+            ShaderTarget::Glsl => "GLSL",
+            ShaderTarget::Hlsl => "HLSL",
+            ShaderTarget::Msl => "MSL",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Transpiled shader output
+#[derive(Debug, Clone, PartialEq)]
+pub enum TranspiledShader {
+    /// SPIR-V words
+    Binary(Vec<u32>),
+    /// Source code in a text-based shading language
+    Text(String),
+}
+
+impl TranspiledShader {
+    /// Raw bytes suitable for writing to disk (SPIR-V is little-endian)
+    pub fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            TranspiledShader::Binary(words) => {
+                words.iter().flat_map(|word| word.to_le_bytes()).collect()
+            }
+            TranspiledShader::Text(text) => text.as_bytes().to_vec(),
+        }
+    }
+
+    /// Write the shader to a file
+    pub fn write_to<P: AsRef<Path>>(&self, path: P) -> crate::Result<()> {
+        std::fs::write(path, self.to_bytes())?;
+        Ok(())
+    }
+}
+
+/// Converts WGSL into SPIR-V, GLSL, HLSL or MSL
+#[derive(Debug, Clone)]
+pub struct WGSLTranspiler {
+    /// Desktop GLSL version emitted for the GLSL target
+    pub glsl_version: u16,
+    /// Metal Shading Language version (major, minor)
+    pub msl_version: (u8, u8),
+}
+
+impl WGSLTranspiler {
+    /// Create a transpiler with GLSL 450 and MSL 2.0 output
+    pub fn new() -> Self {
+        Self {
+            glsl_version: 450,
+            msl_version: (2, 0),
+        }
+    }
+
+    /// Transpile WGSL source to the given target.
+    ///
+    /// GLSL output contains a single entry point; when `entry_point` is `None` the
+    /// first entry point of the module is used. Other targets emit every entry point
+    /// unless one is selected, in which case the others are left out.
+    pub fn transpile(
+        &self,
+        code: &str,
+        target: ShaderTarget,
+        entry_point: Option<&str>,
+    ) -> crate::Result<TranspiledShader> {
+        let (module, info) = parse_and_validate(code)?;
+        let selected = select_entry_point(&module, entry_point)?;
+        // HLSL and MSL writers have no pipeline options naming an entry point,
+        // so drop the others from the module instead
+        let (module, info) = match (target, entry_point) {
+            (ShaderTarget::Hlsl | ShaderTarget::Msl, Some(name)) => {
+                retain_entry_point(module, name)?
+            }
+            _ => (module, info),
+        };
+
+        match target {
+            ShaderTarget::SpirV => {
+                // Pipeline options limit the writer to one entry point, so
+                // only pass them for an explicit selection
+                let selected = selected.filter(|_| entry_point.is_some());
+                let pipeline = selected.map(|(name, stage)| spv::PipelineOptions {
+                    shader_stage: stage,
+                    entry_point: name,
+                });
+                let words =
+                    spv::write_vec(&module, &info, &spv::Options::default(), pipeline.as_ref())
+                        .map_err(|e| backend_error(target, e))?;
+                Ok(TranspiledShader::Binary(words))
+            }
+            ShaderTarget::Glsl => {
+                let (name, stage) = selected.ok_or_else(|| {
+                    crate::Error::Other("GLSL output requires an entry point".to_string())
+                })?;
+                let options = glsl::Options {
+                    version: glsl::Version::Desktop(self.glsl_version),
+                    ..Default::default()
+                };
+                let pipeline = glsl::PipelineOptions {
+                    shader_stage: stage,
+                    entry_point: name,
+                    multiview: None,
+                };
+
+                let mut output = String::new();
+                glsl::Writer::new(
+                    &mut output,
+                    &module,
+                    &info,
+                    &options,
+                    &pipeline,
+                    naga::proc::BoundsCheckPolicies::default(),
+                )
+                .and_then(|mut writer| writer.write())
+                .map_err(|e| backend_error(target, e))?;
+                Ok(TranspiledShader::Text(output))
+            }
+            ShaderTarget::Hlsl => {
+                let options = hlsl::Options::default();
+                let mut output = String::new();
+                hlsl::Writer::new(&mut output, &options)
+                    .write(&module, &info)
+                    .map_err(|e| backend_error(target, e))?;
+                Ok(TranspiledShader::Text(output))
+            }
+            ShaderTarget::Msl => {
+                let options = msl::Options {
+                    lang_version: self.msl_version,
+                    ..Default::default()
+                };
+                let (output, _) =
+                    msl::write_string(&module, &info, &options, &msl::PipelineOptions::default())
+                        .map_err(|e| backend_error(target, e))?;
+                Ok(TranspiledShader::Text(output))
+            }
+        }
+    }
+}
+
+impl Default for WGSLTranspiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn parse_and_validate(code: &str) -> crate::Result<(Module, ModuleInfo)> {
+    let module = naga::front::wgsl::parse_str(code)
+        .map_err(|e| ValidationResult::failed(format!("Parse error: {}", e)))?;
+    validate(module)
+}
+
+fn validate(module: Module) -> crate::Result<(Module, ModuleInfo)> {
+    let info = Validator::new(ValidationFlags::all(), Capabilities::all())
+        .validate(&module)
+        .map_err(|e| ValidationResult::failed(format!("Validation error: {:?}", e)))?;
+    Ok((module, info))
+}
+
+/// Remove every entry point but `name`, revalidating the smaller module
+fn retain_entry_point(mut module: Module, name: &str) -> crate::Result<(Module, ModuleInfo)> {
+    module.entry_points.retain(|ep| ep.name == name);
+    validate(module)
+}
+
+/// Resolve the requested entry point, defaulting to the first one in the module
+fn select_entry_point(
+    module: &Module,
+    name: Option<&str>,
+) -> crate::Result<Option<(String, ShaderStage)>> {
+    match name {
+        Some(name) => module
+            .entry_points
+            .iter()
+            .find(|ep| ep.name == name)
+            .map(|ep| Some((ep.name.clone(), ep.stage)))
+            .ok_or_else(|| crate::Error::Other(format!("Entry point '{}' not found", name))),
+        None => Ok(module
+            .entry_points
+            .first()
+            .map(|ep| (ep.name.clone(), ep.stage))),
+    }
+}
+
+fn backend_error(target: ShaderTarget, error: impl fmt::Display) -> crate::Error {
+    crate::Error::Other(format!("{} backend error: {}", target, error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wgsl::ChromaticTemplate;
+
+    #[test]
+    fn test_target_parsing() {
+        assert_eq!(
+            "spirv".parse::<ShaderTarget>().unwrap(),
+            ShaderTarget::SpirV
+        );
+        assert_eq!("Metal".parse::<ShaderTarget>().unwrap(), ShaderTarget::Msl);
+        assert!("dxil".parse::<ShaderTarget>().is_err());
+    }
+
+    #[test]
+    fn test_transpile_all_targets() {
+        let transpiler = WGSLTranspiler::new();
+        let code = ChromaticTemplate::mix();
+
+        for target in [
+            ShaderTarget::SpirV,
+            ShaderTarget::Glsl,
+            ShaderTarget::Hlsl,
+            ShaderTarget::Msl,
+        ] {
+            let output = transpiler.transpile(&code, target, None).unwrap();
+            match output {
+                TranspiledShader::Binary(words) => {
+                    // SPIR-V magic number
+                    assert_eq!(words[0], 0x0723_0203);
+                }
+                TranspiledShader::Text(text) => {
+                    // GLSL always names its single entry point `main`
+                    let expected = if target == ShaderTarget::Glsl {
+                        "void main()"
+                    } else {
+                        "chromatic_mix"
+                    };
+                    assert!(
+                        text.contains(expected),
+                        "{} output missing entry point",
+                        target
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_transpile_errors() {
+        let transpiler = WGSLTranspiler::new();
+
+        assert!(transpiler
+            .transpile("not wgsl", ShaderTarget::Hlsl, None)
+            .is_err());
+        assert!(transpiler
+            .transpile(
+                &ChromaticTemplate::mix(),
+                ShaderTarget::Glsl,
+                Some("missing")
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn test_transpile_selected_entry_point() {
+        let code = r#"
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    return vec4<f32>(f32(index), 0.0, 0.0, 1.0);
+}
+
+@fragment
+fn fs_main() -> @location(0) vec4<f32> {
+    return vec4<f32>(1.0, 0.0, 0.0, 1.0);
+}
+"#;
+        let transpiler = WGSLTranspiler::new();
+
+        for target in [ShaderTarget::Hlsl, ShaderTarget::Msl] {
+            let text = |entry_point| match transpiler.transpile(code, target, entry_point) {
+                Ok(TranspiledShader::Text(text)) => text,
+                other => panic!("{} transpilation failed: {:?}", target, other),
+            };
+            let all = text(None);
+            assert!(all.contains("vs_main") && all.contains("fs_main"));
+
+            let selected = text(Some("fs_main"));
+            assert!(selected.contains("fs_main"), "{}", selected);
+            assert!(!selected.contains("vs_main"), "{}", selected);
+            assert!(transpiler.transpile(code, target, Some("missing")).is_err());
+        }
+
+        // SPIR-V stores entry point names as nul-terminated strings
+        let binary =
+            |entry_point| match transpiler.transpile(code, ShaderTarget::SpirV, entry_point) {
+                Ok(TranspiledShader::Binary(words)) => words
+                    .iter()
+                    .flat_map(|word| word.to_le_bytes())
+                    .collect::<Vec<u8>>(),
+                other => panic!("SPIR-V transpilation failed: {:?}", other),
+            };
+        let has = |bytes: &[u8], name: &str| {
+            let name = format!("{}\0", name);
+            bytes
+                .windows(name.len())
+                .any(|window| window == name.as_bytes())
+        };
+        let all = binary(None);
+        assert!(has(&all, "vs_main") && has(&all, "fs_main"));
+        let selected = binary(Some("fs_main"));
+        assert!(has(&selected, "fs_main") && !has(&selected, "vs_main"));
+    }
+}
@@ -0,0 +1,110 @@
+//! Detecting changed WGSL files under a directory
+//!
+//! [`DirectoryWatcher`] compares modification times between polls, which
+//! needs no platform file-notification support and is cheap for the few
+//! hundred files of a dataset or output directory.
+
+use super::batch::{matching_wgsl_files, parse_pattern};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Files that changed between two polls, relative to the watched directory
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WatchChanges {
+    /// New or modified files
+    pub changed: Vec<PathBuf>,
+    pub removed: Vec<PathBuf>,
+}
+
+impl WatchChanges {
+    pub fn is_empty(&self) -> bool {
+        self.changed.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// Polls a directory for changes to its `.wgsl` files
+pub struct DirectoryWatcher {
+    root: PathBuf,
+    pattern: Option<glob::Pattern>,
+    modified: HashMap<PathBuf, SystemTime>,
+}
+
+impl DirectoryWatcher {
+    /// Watch `.wgsl` files under `root`, optionally only those matching a glob
+    /// pattern relative to `root`; the first poll reports every file
+    pub fn new(root: impl Into<PathBuf>, pattern: Option<&str>) -> crate::Result<Self> {
+        Ok(Self {
+            root: root.into(),
+            pattern: parse_pattern(pattern)?,
+            modified: HashMap::new(),
+        })
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Files added, modified or removed since the last poll
+    pub fn poll(&mut self) -> crate::Result<WatchChanges> {
+        let mut changes = WatchChanges::default();
+        let mut modified = HashMap::new();
+        for (path, relative) in matching_wgsl_files(&self.root, &self.pattern)? {
+            // A file removed since it was listed shows up as removed next poll
+            let Ok(time) = std::fs::metadata(&path).and_then(|meta| meta.modified()) else {
+                continue;
+            };
+            if self.modified.get(&relative) != Some(&time) {
+                changes.changed.push(relative.clone());
+            }
+            modified.insert(relative, time);
+        }
+        changes.removed = self
+            .modified
+            .keys()
+            .filter(|path| !modified.contains_key(*path))
+            .cloned()
+            .collect();
+        changes.removed.sort();
+        self.modified = modified;
+        Ok(changes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_poll_reports_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        std::fs::create_dir_all(root.join("sub")).unwrap();
+        std::fs::write(root.join("a.wgsl"), "fn a() { }").unwrap();
+        std::fs::write(root.join("sub/b.wgsl"), "fn b() { }").unwrap();
+        std::fs::write(root.join("notes.txt"), "ignored").unwrap();
+
+        let mut watcher = DirectoryWatcher::new(root, None).unwrap();
+        let first = watcher.poll().unwrap();
+        assert_eq!(
+            first.changed,
+            vec![PathBuf::from("a.wgsl"), PathBuf::from("sub/b.wgsl")]
+        );
+        assert!(watcher.poll().unwrap().is_empty());
+
+        let file = std::fs::File::options()
+            .write(true)
+            .open(root.join("a.wgsl"))
+            .unwrap();
+        file.set_modified(SystemTime::now() + Duration::from_secs(5))
+            .unwrap();
+        std::fs::remove_file(root.join("sub/b.wgsl")).unwrap();
+        let changes = watcher.poll().unwrap();
+        assert_eq!(changes.changed, vec![PathBuf::from("a.wgsl")]);
+        assert_eq!(changes.removed, vec![PathBuf::from("sub/b.wgsl")]);
+
+        let mut filtered = DirectoryWatcher::new(root, Some("sub/*.wgsl")).unwrap();
+        assert!(filtered.poll().unwrap().changed.is_empty());
+    }
+}